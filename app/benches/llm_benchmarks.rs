@@ -1,27 +1,104 @@
-//! Performance benchmarks for LucAstra LLM and vector search.
-
-use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion};
-use lucastra_llm::{cache::EmbeddingCache, conversation::Conversation, rate_limit::RateLimiter};
-use lucastra_search::vector::VectorIndex;
-use std::time::Duration;
+//! Performance benchmarks for LucAstra LLM, vector and full-text search.
+
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use lucastra_llm::{
+    cache::EmbeddingCache,
+    conversation::Conversation,
+    rate_limit::{Budget, RateLimiter},
+};
+use lucastra_search::{vector::VectorIndex, SearchService};
+use std::path::PathBuf;
 use tempfile::TempDir;
 
+/// Dimensions for the search benchmark; kept small so building the 100k
+/// graph stays within a reasonable setup time.
+const SEARCH_DIMS: usize = 64;
+
+/// Deterministic pseudo-random vectors so graph quality is representative.
+fn random_embeddings(count: usize, seed: u64) -> Vec<Vec<f32>> {
+    let mut state = seed;
+    (0..count)
+        .map(|_| {
+            (0..SEARCH_DIMS)
+                .map(|_| {
+                    state = state
+                        .wrapping_mul(6364136223846793005)
+                        .wrapping_add(1442695040888963407);
+                    ((state >> 33) as f32 / (1u64 << 31) as f32) * 2.0 - 1.0
+                })
+                .collect()
+        })
+        .collect()
+}
+
 fn benchmark_vector_search(c: &mut Criterion) {
     let mut group = c.benchmark_group("vector_search");
+    let query = random_embeddings(1, 42).remove(0);
 
-    for size in [10, 100, 1000].iter() {
-        let mut index = VectorIndex::new();
+    for size in [1_000, 10_000, 100_000].iter() {
+        let mut hnsw = VectorIndex::new();
+        let mut exact = VectorIndex::exact();
 
         // Populate index
-        for i in 0..*size {
-            let embedding = (0..384).map(|j| ((i + j) as f32) / 1000.0).collect();
-            index.add_document(format!("doc_{}", i), embedding);
+        for (i, embedding) in random_embeddings(*size, 7).into_iter().enumerate() {
+            let path = PathBuf::from(format!("doc_{}", i));
+            hnsw.add_document(path.clone(), embedding.clone(), String::new())
+                .unwrap();
+            exact.add_document(path, embedding, String::new()).unwrap();
         }
 
-        group.bench_with_input(BenchmarkId::from_parameter(size), size, |b, _| {
-            let query = (0..384).map(|i| (i as f32) / 1000.0).collect();
-            b.iter(|| black_box(index.search(&query, 5)));
+        group.bench_with_input(BenchmarkId::new("hnsw", size), size, |b, _| {
+            b.iter(|| black_box(hnsw.search(&query, 10)));
         });
+        group.bench_with_input(BenchmarkId::new("exact", size), size, |b, _| {
+            b.iter(|| black_box(exact.search(&query, 10)));
+        });
+    }
+
+    group.finish();
+}
+
+/// Searches each thread runs per iteration of the concurrent search benchmark.
+const SEARCHES_PER_THREAD: u64 = 50;
+
+fn benchmark_concurrent_search(c: &mut Criterion) {
+    let mut group = c.benchmark_group("concurrent_search");
+    let words = ["kernel", "search", "llamafile", "vector", "device", "mount"];
+    let service = SearchService::new(None);
+    let docs = (0..5_000)
+        .map(|i| {
+            let content = format!(
+                "document {} about {} and {} with {}",
+                i,
+                words[i % words.len()],
+                words[(i * 7) % words.len()],
+                words[(i / 3) % words.len()]
+            );
+            (format!("/docs/{}.txt", i), content)
+        })
+        .collect();
+    service.index_documents(docs).unwrap();
+
+    // With shared reads, time per iteration stays flat as threads are added
+    for threads in [1u64, 2, 4, 8].iter() {
+        group.throughput(Throughput::Elements(threads * SEARCHES_PER_THREAD));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(threads),
+            threads,
+            |b, &threads| {
+                b.iter(|| {
+                    std::thread::scope(|scope| {
+                        for _ in 0..threads {
+                            scope.spawn(|| {
+                                for _ in 0..SEARCHES_PER_THREAD {
+                                    black_box(service.search("kernel vector", 10).unwrap());
+                                }
+                            });
+                        }
+                    });
+                });
+            },
+        );
     }
 
     group.finish();
@@ -46,20 +123,22 @@ fn benchmark_embedding_cache(c: &mut Criterion) {
     let mut group = c.benchmark_group("embedding_cache");
 
     let temp_dir = TempDir::new().unwrap();
-    let mut cache = EmbeddingCache::new(temp_dir.path().to_path_buf());
+    let mut cache = EmbeddingCache::new(temp_dir.path().to_path_buf()).unwrap();
 
     // Warm up cache
     for i in 0..100 {
         let embedding = (0..384).map(|j| ((i + j) as f32) / 1000.0).collect();
-        cache.set(format!("text_{}", i), embedding);
+        cache
+            .put(&format!("text_{}", i), "bench-model", embedding)
+            .unwrap();
     }
 
     group.bench_function("cache_get_hit", |b| {
-        b.iter(|| black_box(cache.get("text_50")));
+        b.iter(|| black_box(cache.get("text_50", "bench-model").unwrap()));
     });
 
     group.bench_function("cache_get_miss", |b| {
-        b.iter(|| black_box(cache.get("text_9999")));
+        b.iter(|| black_box(cache.get("text_9999", "bench-model").unwrap()));
     });
 
     group.bench_function("cache_set", |b| {
@@ -67,7 +146,11 @@ fn benchmark_embedding_cache(c: &mut Criterion) {
         let mut counter = 0;
         b.iter(|| {
             counter += 1;
-            black_box(cache.set(format!("new_text_{}", counter), embedding.clone()))
+            black_box(cache.put(
+                &format!("new_text_{}", counter),
+                "bench-model",
+                embedding.clone(),
+            ))
         });
     });
 
@@ -82,11 +165,12 @@ fn benchmark_conversation(c: &mut Criterion) {
         let mut counter = 0;
         b.iter(|| {
             counter += 1;
-            black_box(conv.add_message(lucastra_llm::conversation::Message {
+            conv.add_message(lucastra_llm::conversation::Message {
                 role: lucastra_llm::conversation::Role::User,
                 content: format!("Message {}", counter),
                 timestamp: counter,
-            }))
+            });
+            black_box(conv.len())
         });
     });
 
@@ -109,10 +193,14 @@ fn benchmark_rate_limiter(c: &mut Criterion) {
     let mut group = c.benchmark_group("rate_limiter");
 
     group.bench_function("acquire_under_limit", |b| {
-        let limiter = RateLimiter::new(1000); // High limit
+        // High limit
+        let limiter = RateLimiter::new(Budget {
+            requests_per_min: Some(1_000_000),
+            tokens_per_min: Some(100_000_000),
+        });
         let runtime = tokio::runtime::Runtime::new().unwrap();
         b.to_async(runtime)
-            .iter(|| async { black_box(limiter.acquire().await) });
+            .iter(|| async { limiter.acquire(50).await });
     });
 
     group.finish();
@@ -121,6 +209,7 @@ fn benchmark_rate_limiter(c: &mut Criterion) {
 criterion_group!(
     benches,
     benchmark_vector_search,
+    benchmark_concurrent_search,
     benchmark_cosine_similarity,
     benchmark_embedding_cache,
     benchmark_conversation,
@@ -13,7 +13,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("=== LucAstra Tool Execution Demo ===\n");
 
     // Initialize system
-    let state = SystemState::new()?;
+    let mut state = SystemState::new()?;
 
     // Test 1: Search tool
     println!("Test 1: Search Tool");
@@ -28,18 +28,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Test 2: Read Tool");
     let read_tool = Tool::Read {
         path: "/mnt/root/guide.txt".to_string(),
+        offset: None,
+        length: None,
     };
     let result = state.execute_tool(read_tool);
     println!("Result: {:?}\n", result);
 
     // Test 3: Install tool (checking Rust installation)
-    println!("Test 3: Install Tool - Check Rust version");
+    println!("Test 3: Install Tool - Check Cargo version");
     let install_tool = Tool::Install {
         program: "rust".to_string(),
         method: InstallMethod::Command {
-            cmd: "rustc".to_string(),
+            cmd: "cargo".to_string(),
             args: vec!["--version".to_string()],
         },
+        dry_run: false,
     };
     let result = state.execute_tool(install_tool);
     println!("Result: {:?}\n", result);
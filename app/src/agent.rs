@@ -0,0 +1,443 @@
+//! Multi-step agent loop: the model calls tools, sees their results and
+//! keeps going until it produces a final answer.
+
+use crate::SystemState;
+use lucastra_llm::prompt_template::AGENT_TEMPLATE;
+use lucastra_llm::{
+    merge_consecutive_roles, standard_variables, validate_messages, CompletionRequest, LLMProvider,
+    Message, PromptTemplate, ProviderResult,
+};
+use lucastra_tools::{
+    file_access::FileOperation, parser::ToolCallParser, InstallMethod, Tool, ToolResult,
+};
+use serde::Serialize;
+use std::collections::HashMap;
+
+/// Prefix the model uses to end the loop with its answer.
+pub const FINAL_ANSWER_MARKER: &str = "FINAL ANSWER:";
+
+/// Default cap on model calls per goal.
+pub const DEFAULT_MAX_STEPS: usize = 5;
+
+/// One model turn and the tools it ran.
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentStep {
+    pub model_output: String,
+    pub tool_calls: Vec<Tool>,
+    pub results: Vec<ToolResult>,
+}
+
+/// Everything the agent did for a goal, for display in the GUI.
+#[derive(Debug, Clone, Serialize)]
+pub struct AgentTranscript {
+    pub goal: String,
+    pub steps: Vec<AgentStep>,
+    /// `None` when the step limit ran out before the model answered.
+    pub final_answer: Option<String>,
+}
+
+/// Drives an [`LLMProvider`] through tool calls against a [`SystemState`].
+pub struct AgentExecutor {
+    provider: Box<dyn LLMProvider>,
+    max_steps: usize,
+    prompt: PromptTemplate,
+}
+
+impl AgentExecutor {
+    pub fn new(provider: Box<dyn LLMProvider>) -> Self {
+        Self {
+            provider,
+            max_steps: DEFAULT_MAX_STEPS,
+            prompt: builtin_prompt(),
+        }
+    }
+
+    pub fn with_max_steps(mut self, max_steps: usize) -> Self {
+        self.max_steps = max_steps.max(1);
+        self
+    }
+
+    /// Instruct the model with `template` instead of the built-in
+    /// [`AGENT_TEMPLATE`]. It is rendered with the tool variables listed
+    /// there plus [`standard_variables`].
+    pub fn with_prompt_template(mut self, template: PromptTemplate) -> Self {
+        self.prompt = template;
+        self
+    }
+
+    /// Work towards `goal`, feeding every tool result back to the model.
+    pub async fn run(
+        &self,
+        state: &mut SystemState,
+        goal: &str,
+    ) -> ProviderResult<AgentTranscript> {
+        let mut messages = vec![
+            Message::system(self.system_prompt()),
+            Message::user(goal.to_string()),
+        ];
+        let mut transcript = AgentTranscript {
+            goal: goal.to_string(),
+            steps: Vec::new(),
+            final_answer: None,
+        };
+        let parser = ToolCallParser::new();
+
+        for step in 1..=self.max_steps {
+            messages = healed(messages);
+            let response = self
+                .provider
+                .complete_chat(
+                    &messages,
+                    CompletionRequest {
+                        max_tokens: Some(512),
+                        temperature: Some(0.2),
+                        ..Default::default()
+                    },
+                )
+                .await?;
+            let output = response.content.trim().to_string();
+            messages.push(Message::assistant(output.clone()));
+
+            if let Some(answer) = final_answer(&output) {
+                tracing::info!("Agent answered after {} step(s)", step);
+                transcript.final_answer = Some(answer);
+                transcript.steps.push(AgentStep {
+                    model_output: output,
+                    tool_calls: Vec::new(),
+                    results: Vec::new(),
+                });
+                break;
+            }
+
+            let parsed = parser.parse(&output);
+            let tool_calls = parsed.tools();
+            let results = state.execute_parsed_tools(&parsed);
+            tracing::debug!("Agent step {} ran {} tool(s)", step, tool_calls.len());
+
+            let results_json =
+                serde_json::to_string_pretty(&results).unwrap_or_else(|e| e.to_string());
+            // Parse problems go back to the model so it can fix its calls
+            let diagnostics = parsed.diagnostics();
+            let problems = if diagnostics.is_empty() {
+                String::new()
+            } else {
+                format!(
+                    "\n\nProblems with your tool calls:\n- {}",
+                    diagnostics.join("\n- ")
+                )
+            };
+            messages.push(Message::user(format!(
+                "Tool results:\n{}{}\n\nCall more tools or reply with \"{} <answer>\".",
+                results_json, problems, FINAL_ANSWER_MARKER
+            )));
+            transcript.steps.push(AgentStep {
+                model_output: output,
+                tool_calls,
+                results,
+            });
+        }
+
+        Ok(transcript)
+    }
+
+    /// The prompt template rendered, or the built-in one if it can't be.
+    fn system_prompt(&self) -> String {
+        let variables = prompt_variables();
+        self.prompt.render(&variables).unwrap_or_else(|e| {
+            tracing::warn!("Using the built-in agent prompt: {}", e);
+            builtin_prompt()
+                .render(&variables)
+                .expect("built-in agent prompt renders")
+        })
+    }
+}
+
+/// `messages`, with runs of the same role merged if they fail
+/// [`validate_messages`], so the provider never sees back-to-back answers.
+fn healed(messages: Vec<Message>) -> Vec<Message> {
+    match validate_messages(&messages) {
+        Ok(()) => messages,
+        Err(e) => {
+            tracing::warn!("Merging agent messages: {}", e);
+            merge_consecutive_roles(messages)
+        }
+    }
+}
+
+fn builtin_prompt() -> PromptTemplate {
+    PromptTemplate::builtin(AGENT_TEMPLATE).expect("agent prompt is built in")
+}
+
+/// The answer text if the model used the final answer marker.
+fn final_answer(output: &str) -> Option<String> {
+    output.find(FINAL_ANSWER_MARKER).map(|start| {
+        output[start + FINAL_ANSWER_MARKER.len()..]
+            .trim()
+            .to_string()
+    })
+}
+
+/// Variables for the agent prompt: every tool, with the schema and examples
+/// generated from [`Tool`] so they cannot drift from what the parser accepts.
+fn prompt_variables() -> HashMap<String, String> {
+    let examples = [
+        Tool::Search {
+            query: "quarterly report".to_string(),
+            top_k: Some(3),
+        },
+        Tool::Read {
+            path: "/mnt/root/guide.txt".to_string(),
+            offset: Some(0),
+            length: Some(4096),
+        },
+        Tool::Write {
+            path: "/mnt/root/notes.txt".to_string(),
+            content: "Summary...".to_string(),
+            append: false,
+        },
+        Tool::Delete {
+            path: "/mnt/root/old.txt".to_string(),
+        },
+        Tool::Install {
+            program: "ripgrep".to_string(),
+            method: InstallMethod::Command {
+                cmd: "cargo".to_string(),
+                args: vec!["install".to_string(), "ripgrep".to_string()],
+            },
+            dry_run: true,
+        },
+        Tool::HostFileAccess {
+            operation: FileOperation::List,
+            path: "~/Documents".to_string(),
+            dest_path: None,
+        },
+        Tool::Calculate {
+            expression: "1250 * 0.15".to_string(),
+        },
+        Tool::Fetch {
+            url: "https://docs.rs/serde".to_string(),
+            max_bytes: Some(8192),
+        },
+    ];
+    let allowed_tools = examples
+        .iter()
+        .filter_map(|tool| serde_json::to_value(tool).ok())
+        .filter_map(|call| call["tool"].as_str().map(str::to_string))
+        .collect::<Vec<_>>()
+        .join(", ");
+    let examples = examples
+        .iter()
+        .filter_map(|tool| serde_json::to_string(tool).ok())
+        .collect::<Vec<_>>()
+        .join("\n");
+
+    let mut variables = standard_variables();
+    variables.extend([
+        (
+            "first_tool_example".to_string(),
+            examples.lines().next().unwrap_or_default().to_string(),
+        ),
+        ("allowed_tools".to_string(), allowed_tools),
+        ("tool_examples".to_string(), examples),
+        ("tool_schema".to_string(), Tool::schema_json().to_string()),
+        (
+            "final_answer_marker".to_string(),
+            FINAL_ANSWER_MARKER.to_string(),
+        ),
+    ]);
+    variables
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use lucastra_config::Config;
+    use lucastra_llm::{CompletionResponse, StopReason};
+    use std::collections::VecDeque;
+    use std::sync::{Arc, Mutex, OnceLock};
+    use tempfile::TempDir;
+
+    /// Replies with canned outputs in order and records the prompts it saw.
+    struct ScriptedProvider {
+        replies: Mutex<VecDeque<String>>,
+        seen: Arc<Mutex<Vec<Vec<Message>>>>,
+    }
+
+    #[async_trait]
+    impl LLMProvider for ScriptedProvider {
+        fn name(&self) -> &str {
+            "scripted"
+        }
+
+        fn default_model(&self) -> &str {
+            "scripted-model"
+        }
+
+        async fn health_check(&self) -> ProviderResult<bool> {
+            Ok(true)
+        }
+
+        async fn complete(
+            &self,
+            _request: CompletionRequest,
+        ) -> ProviderResult<CompletionResponse> {
+            unreachable!("agent uses complete_chat")
+        }
+
+        async fn complete_chat(
+            &self,
+            messages: &[Message],
+            _request: CompletionRequest,
+        ) -> ProviderResult<CompletionResponse> {
+            self.seen.lock().unwrap().push(messages.to_vec());
+            let content = self.replies.lock().unwrap().pop_front().unwrap_or_default();
+            Ok(CompletionResponse {
+                content,
+                stop_reason: StopReason::Complete,
+                tokens_used: None,
+                model: None,
+            })
+        }
+    }
+
+    /// State booted from the default config with `auto_start` off and data
+    /// in a temp dir, so tests never read the user's config or start an LLM
+    /// server.
+    fn test_state() -> SystemState {
+        static DATA: OnceLock<TempDir> = OnceLock::new();
+        let data = DATA.get_or_init(|| tempfile::tempdir().unwrap());
+        let mut config = Config::default();
+        config.llm.auto_start = false;
+        config.storage.data_dir = data.path().to_path_buf();
+        SystemState::with_config(config).unwrap()
+    }
+
+    fn scripted(replies: &[&str]) -> (AgentExecutor, Arc<Mutex<Vec<Vec<Message>>>>) {
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let provider = ScriptedProvider {
+            replies: Mutex::new(replies.iter().map(|r| r.to_string()).collect()),
+            seen: seen.clone(),
+        };
+        (AgentExecutor::new(Box::new(provider)), seen)
+    }
+
+    #[tokio::test]
+    async fn test_search_then_answer() {
+        let mut state = test_state();
+        let (agent, seen) = scripted(&[
+            r#"[{"tool":"Search","params":{"query":"llamafile","top_k":1}}]"#,
+            "FINAL ANSWER: LucAstra uses llamafile.",
+        ]);
+
+        let transcript = agent.run(&mut state, "What runs inference?").await.unwrap();
+
+        assert_eq!(transcript.steps.len(), 2);
+        assert_eq!(seen.lock().unwrap().len(), 2);
+        assert!(matches!(
+            transcript.steps[0].tool_calls[0],
+            Tool::Search { .. }
+        ));
+        assert_eq!(transcript.steps[0].results.len(), 1);
+        assert_eq!(
+            transcript.final_answer.as_deref(),
+            Some("LucAstra uses llamafile.")
+        );
+
+        let second_prompt = &seen.lock().unwrap()[1];
+        assert!(second_prompt
+            .last()
+            .unwrap()
+            .content
+            .contains("Tool results"));
+    }
+
+    #[tokio::test]
+    async fn test_prompt_template_is_rendered_with_the_tool_schema() {
+        let mut state = test_state();
+        let (agent, seen) = scripted(&["FINAL ANSWER: done"]);
+        agent.run(&mut state, "Anything").await.unwrap();
+        let prompt = seen.lock().unwrap()[0][0].content.clone();
+        assert!(prompt.contains(&Tool::schema_json().to_string()));
+        assert!(prompt.contains("Search, Read, Write"));
+        assert!(!prompt.contains("{tool_schema}"));
+
+        let (agent, seen) = scripted(&["FINAL ANSWER: done"]);
+        let custom = PromptTemplate::new(
+            "mine",
+            "Tools: {allowed_tools}. End with {final_answer_marker}",
+        );
+        agent
+            .with_prompt_template(custom)
+            .run(&mut state, "Anything")
+            .await
+            .unwrap();
+        assert!(seen.lock().unwrap()[0][0]
+            .content
+            .starts_with("Tools: Search, Read"));
+
+        // A template the agent can't fill falls back to the built-in one
+        let (agent, seen) = scripted(&["FINAL ANSWER: done"]);
+        agent
+            .with_prompt_template(PromptTemplate::new("bad", "Hi {nickname}"))
+            .run(&mut state, "Anything")
+            .await
+            .unwrap();
+        assert_eq!(seen.lock().unwrap()[0][0].content, prompt);
+    }
+
+    #[tokio::test]
+    async fn test_stops_at_max_steps() {
+        let mut state = test_state();
+        let call = r#"{"tool":"Read","params":{"path":"/mnt/root/missing.txt"}}"#;
+        let (agent, seen) = scripted(&[call, call, call]);
+
+        let transcript = agent
+            .with_max_steps(2)
+            .run(&mut state, "Loop forever")
+            .await
+            .unwrap();
+
+        assert_eq!(transcript.steps.len(), 2);
+        assert_eq!(seen.lock().unwrap().len(), 2);
+        assert!(transcript.final_answer.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_parse_problems_are_fed_back() {
+        let mut state = test_state();
+        let (agent, seen) = scripted(&[
+            "```json\n[{\"tool\":\"Calculate\",\"params\":{\"expression\":\"6 * 7\"}},\n {\"tool\":\"Serch\",\"params\":{\"query\":\"x\"}},]\n```",
+            "FINAL ANSWER: 42",
+        ]);
+
+        let transcript = agent.run(&mut state, "What is 6 * 7?").await.unwrap();
+
+        let step = &transcript.steps[0];
+        assert_eq!(step.tool_calls.len(), 1);
+        assert_eq!(step.results.len(), 2);
+        assert!(step.results[0].success);
+        assert_eq!(step.results[1].tool, "parse");
+
+        let feedback = seen.lock().unwrap()[1].last().unwrap().content.clone();
+        assert!(feedback
+            .contains("Problems with your tool calls:\n- call 2 (Serch): unknown tool 'Serch'"));
+    }
+
+    #[test]
+    fn test_back_to_back_answers_are_merged_before_sending() {
+        let valid = vec![
+            Message::system("Tools".to_string()),
+            Message::user("Goal".to_string()),
+            Message::assistant("Thinking".to_string()),
+        ];
+        assert_eq!(healed(valid.clone()).len(), 3);
+
+        let mut doubled = valid;
+        doubled.push(Message::assistant("FINAL ANSWER: done".to_string()));
+        let healed = healed(doubled);
+        assert_eq!(healed.len(), 3);
+        assert_eq!(healed[2].content, "Thinking\n\nFINAL ANSWER: done");
+        assert!(validate_messages(&healed).is_ok());
+    }
+}
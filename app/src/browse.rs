@@ -0,0 +1,102 @@
+//! The system browser.
+//!
+//! With `search.index_browsed_pages` on, pages opened through
+//! [`SystemState::browse`](crate::SystemState::browse) are added to the
+//! search index under `web://<url>`, so searches and RAG queries find them
+//! next to local files. Opening a page again replaces its earlier copy.
+
+use lucastra_browser::{Browser, BrowserError, BrowserStore, HtmlContent, HttpClient, Tab};
+use lucastra_config::SearchConfig;
+use lucastra_core::{LuCastraError, WEB_DOC_PREFIX};
+use lucastra_search::SearchService;
+use std::path::PathBuf;
+
+/// Browser tabs, bookmarks and history with the client that fetches pages.
+pub struct BrowserService {
+    browser: Browser,
+    client: HttpClient,
+}
+
+impl BrowserService {
+    pub fn new(browser: Browser) -> Self {
+        Self {
+            browser,
+            client: HttpClient::new(),
+        }
+    }
+
+    /// Browser keeping its bookmarks and history in `dir`; in memory when
+    /// they can't be read.
+    pub fn open(dir: PathBuf) -> Self {
+        let browser = Browser::with_store(BrowserStore::new(dir)).unwrap_or_else(|e| {
+            tracing::warn!("Browser history not loaded: {}", e);
+            Browser::new()
+        });
+        Self::new(browser)
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    pub fn browser(&self) -> &Browser {
+        &self.browser
+    }
+
+    pub fn browser_mut(&mut self) -> &mut Browser {
+        &mut self.browser
+    }
+
+    /// Load `url` in the current tab and return the tab.
+    pub fn navigate(&mut self, url: &str) -> lucastra_core::Result<&Tab> {
+        self.browser
+            .navigate(url.to_string(), &self.client)
+            .map_err(browser_error)?;
+        self.browser
+            .current_tab()
+            .ok_or_else(|| LuCastraError::ServiceError("Browser has no open tab".to_string()))
+    }
+}
+
+/// Search document id for the page at `url`.
+pub fn web_doc_id(url: &str) -> String {
+    format!("{}{}", WEB_DOC_PREFIX, url)
+}
+
+/// Text indexed for a page: its title, then its main content.
+pub fn page_document(content: &HtmlContent) -> String {
+    format!("{}\n\n{}", content.title, content.main_text)
+}
+
+/// Add the page shown in `tab` to `search` when `config` allows it,
+/// replacing any earlier copy. Pages that failed to load, have no text or
+/// come from a blocked domain are skipped. Returns the document id the
+/// page was indexed under.
+pub fn index_page(tab: &Tab, search: &SearchService, config: &SearchConfig) -> Option<String> {
+    if !config.index_browsed_pages || !tab.status.is_some_and(|s| (200..300).contains(&s)) {
+        return None;
+    }
+    let content = tab.content.as_ref().filter(|c| !c.main_text.is_empty())?;
+    let host = tab.host()?;
+    if config.blocks_domain(&host) {
+        tracing::debug!("Not indexing {}: {} is blocked", tab.url, host);
+        return None;
+    }
+
+    let doc_id = web_doc_id(&tab.url);
+    match search.index_document(&doc_id, &page_document(content)) {
+        Ok(()) => Some(doc_id),
+        Err(e) => {
+            tracing::warn!("Failed to index {}: {}", tab.url, e);
+            None
+        }
+    }
+}
+
+fn browser_error(error: BrowserError) -> LuCastraError {
+    match error {
+        BrowserError::InvalidUrl(url) => LuCastraError::InvalidCommand(url),
+        other => LuCastraError::ServiceError(other.to_string()),
+    }
+}
@@ -0,0 +1,531 @@
+//! Background command bus.
+//!
+//! [`CommandBus`] runs commands off the caller's thread so a slow LLM call
+//! doesn't block the GUI. Commands go to a tokio runtime on a background
+//! thread over an mpsc channel; each one runs on its own worker thread and
+//! its [`Response`] comes back through a oneshot channel tied to its
+//! `command_id`. An in-flight command can be cancelled by id.
+//!
+//! Streaming commands send [`ResponsePayload::Partial`] text as it is
+//! generated. Text arriving within one frame is merged into a single
+//! partial so a fast model doesn't flood the GUI with redraws.
+
+use crate::SystemState;
+use lucastra_core::{Command, CommandPayload, ErrorCode, LuCastraError, Response, ResponsePayload};
+use lucastra_llm::{InferenceRequest, InferenceResponse, LLMService};
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::AbortHandle;
+use tokio::time::Instant;
+
+/// How long partial text is collected before being sent on: one frame at
+/// 60 Hz.
+const FRAME: Duration = Duration::from_millis(16);
+
+/// Something that can run a command to completion, blocking the calling
+/// thread while it does.
+pub trait CommandExecutor: Send + Sync + 'static {
+    fn execute(&self, command: Command) -> Response;
+
+    /// Run `command`, passing generated text to `emit` as it arrives. Once
+    /// `emit` returns `false` nobody is listening and the command should
+    /// stop early. By default nothing is streamed.
+    fn execute_streaming(&self, command: Command, emit: &mut dyn FnMut(&str) -> bool) -> Response {
+        let _ = emit;
+        self.execute(command)
+    }
+}
+
+/// Runs commands against shared system state. Queries only hold the lock
+/// while gathering search context, so several can wait on the LLM at once.
+impl CommandExecutor for Mutex<SystemState> {
+    fn execute(&self, command: Command) -> Response {
+        run_query(
+            self,
+            command,
+            |llm, request| llm.infer_blocking(request),
+            crate::answer_payload,
+        )
+    }
+
+    fn execute_streaming(&self, command: Command, emit: &mut dyn FnMut(&str) -> bool) -> Response {
+        run_query(
+            self,
+            command,
+            |llm, request| llm.infer_stream_blocking(request, emit),
+            |request, response| ResponsePayload::Finished {
+                stop_reason: response.stop_reason,
+                sources: response.sources,
+                used_context: request.context.as_ref().is_some_and(|c| !c.is_empty()),
+            },
+        )
+    }
+}
+
+/// Answer a query with `infer` once its context is gathered and turn the
+/// answer into a reply with `payload`; other commands go straight to
+/// [`SystemState::handle_command`].
+fn run_query(
+    shared: &Mutex<SystemState>,
+    command: Command,
+    infer: impl FnOnce(&LLMService, InferenceRequest) -> lucastra_core::Result<InferenceResponse>,
+    payload: impl FnOnce(&InferenceRequest, InferenceResponse) -> ResponsePayload,
+) -> Response {
+    let Ok(mut state) = shared.lock() else {
+        return error_response(command.id, "System state is unavailable");
+    };
+
+    // Probes wait on the network, so they run without the lock too
+    if let CommandPayload::TestLlmConnection = command.payload {
+        let llm = state.llm_service.clone();
+        drop(state);
+        return Response {
+            command_id: command.id,
+            payload: crate::connection_payload(&llm),
+        };
+    }
+
+    let CommandPayload::Query {
+        text,
+        use_rag,
+        conversation_id,
+        attachments,
+    } = &command.payload
+    else {
+        return state.handle_command(command.clone());
+    };
+
+    let _span = crate::observability::command_span(&command).entered();
+    state.metrics.record_command();
+    let metrics = state.metrics.clone();
+    let usage_tracker = state.usage.clone();
+    let prepared = state.prepare_query(text, *use_rag, conversation_id.as_deref(), attachments);
+    drop(state);
+
+    let answered = prepared.and_then(|(llm, request)| {
+        let span = crate::observability::inference_span();
+        let response = span.in_scope(|| infer(&llm, request.clone()))?;
+        metrics.record_llm_latency(span.finish());
+        if let Some(usage) = llm.usage(&request, &response) {
+            metrics.record_llm_usage(&usage);
+            crate::record_usage(&usage_tracker, usage);
+        }
+        Ok((request, response))
+    });
+    match answered {
+        Ok((request, response)) => {
+            if let (Some(id), Ok(mut state)) = (conversation_id, shared.lock()) {
+                state.record_answer(id, text, &response.text);
+            }
+            Response {
+                command_id: command.id,
+                payload: payload(&request, response),
+            }
+        }
+        Err(LuCastraError::LlmUnavailable(reason)) => Response {
+            command_id: command.id,
+            payload: ResponsePayload::LlmUnavailable(reason),
+        },
+        Err(e) => Response {
+            command_id: command.id,
+            payload: ResponsePayload::error(&e),
+        },
+    }
+}
+
+/// A failure of the bus itself rather than of the command.
+fn error_response(command_id: String, message: impl std::fmt::Display) -> Response {
+    Response {
+        command_id,
+        payload: ResponsePayload::Error {
+            code: ErrorCode::Internal,
+            message: message.to_string(),
+        },
+    }
+}
+
+/// Where a command's responses go.
+enum Reply {
+    Once(oneshot::Sender<Response>),
+    Stream(mpsc::UnboundedSender<Response>),
+}
+
+impl Reply {
+    fn send(self, response: Response) {
+        match self {
+            Reply::Once(reply) => {
+                let _ = reply.send(response);
+            }
+            Reply::Stream(reply) => {
+                let _ = reply.send(response);
+            }
+        }
+    }
+}
+
+enum BusMessage {
+    Run(Command, Reply),
+    Cancel(String),
+}
+
+/// Handle for sending commands to the background runtime.
+///
+/// Dropping the bus stops the runtime; commands still running finish on
+/// their worker threads but their responses are discarded.
+pub struct CommandBus {
+    messages: mpsc::UnboundedSender<BusMessage>,
+}
+
+impl CommandBus {
+    /// Start the runtime thread, running commands with `executor`.
+    pub fn start(executor: Arc<dyn CommandExecutor>) -> std::io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("lucastra-bus")
+            .enable_time()
+            .build()?;
+        let (messages, receiver) = mpsc::unbounded_channel();
+        thread::Builder::new()
+            .name("lucastra-bus".to_string())
+            .spawn(move || runtime.block_on(dispatch(executor, receiver)))?;
+        Ok(Self { messages })
+    }
+
+    /// Queue `command`. The returned future resolves to its response, or to
+    /// an error response if the command is cancelled or the bus stops.
+    pub fn send(&self, command: Command) -> impl Future<Output = Response> + Send + 'static {
+        let command_id = command.id.clone();
+        let (reply, response) = oneshot::channel();
+        let _ = self
+            .messages
+            .send(BusMessage::Run(command, Reply::Once(reply)));
+        async move {
+            response
+                .await
+                .unwrap_or_else(|_| error_response(command_id, "Command bus stopped"))
+        }
+    }
+
+    /// Queue `command`, streaming its output. The receiver gets zero or
+    /// more [`ResponsePayload::Partial`] responses, then one final response
+    /// (a [`ResponsePayload::Finished`] for queries), then closes.
+    pub fn send_streaming(&self, command: Command) -> mpsc::UnboundedReceiver<Response> {
+        let (reply, responses) = mpsc::unbounded_channel();
+        let _ = self
+            .messages
+            .send(BusMessage::Run(command, Reply::Stream(reply)));
+        responses
+    }
+
+    /// Cancel the in-flight command with this id. Its response future
+    /// resolves to a "cancelled" error; unknown ids are ignored.
+    pub fn cancel(&self, command_id: &str) {
+        let _ = self
+            .messages
+            .send(BusMessage::Cancel(command_id.to_string()));
+    }
+}
+
+/// Receive bus messages until the bus is dropped.
+async fn dispatch(
+    executor: Arc<dyn CommandExecutor>,
+    mut receiver: mpsc::UnboundedReceiver<BusMessage>,
+) {
+    // Replies wait here until their command finishes or is cancelled
+    let mut in_flight: HashMap<String, (AbortHandle, Reply)> = HashMap::new();
+    let (results_tx, mut results) = mpsc::unbounded_channel::<Response>();
+
+    loop {
+        tokio::select! {
+            message = receiver.recv() => match message {
+                Some(BusMessage::Run(command, reply)) => {
+                    let command_id = command.id.clone();
+                    let executor = Arc::clone(&executor);
+                    let task = match &reply {
+                        Reply::Once(_) => tokio::spawn(run(executor, command, results_tx.clone())),
+                        Reply::Stream(partials) => tokio::spawn(run_streaming(
+                            executor,
+                            command,
+                            partials.clone(),
+                            results_tx.clone(),
+                        )),
+                    };
+                    in_flight.insert(command_id, (task.abort_handle(), reply));
+                }
+                Some(BusMessage::Cancel(command_id)) => {
+                    if let Some((task, reply)) = in_flight.remove(&command_id) {
+                        tracing::info!("Cancelled command {}", command_id);
+                        task.abort();
+                        reply.send(error_response(command_id, "Command cancelled"));
+                    }
+                }
+                None => break,
+            },
+            Some(response) = results.recv() => {
+                if let Some((_, reply)) = in_flight.remove(&response.command_id) {
+                    reply.send(response);
+                }
+            }
+        }
+    }
+}
+
+/// Run `command` and hand its response to the dispatcher.
+async fn run(
+    executor: Arc<dyn CommandExecutor>,
+    command: Command,
+    results: mpsc::UnboundedSender<Response>,
+) {
+    let (done, result) = oneshot::channel();
+    // LLM calls block on a runtime of their own, so keep them off ours
+    thread::spawn(move || {
+        let _ = done.send(executor.execute(command));
+    });
+    if let Ok(response) = result.await {
+        let _ = results.send(response);
+    }
+}
+
+/// Run `command`, sending its text to `partials` one frame's worth at a
+/// time, then hand the final response to the dispatcher.
+///
+/// Aborting this task drops the text receiver, which tells the executor to
+/// stop generating.
+async fn run_streaming(
+    executor: Arc<dyn CommandExecutor>,
+    command: Command,
+    partials: mpsc::UnboundedSender<Response>,
+    results: mpsc::UnboundedSender<Response>,
+) {
+    let command_id = command.id.clone();
+    let (done, result) = oneshot::channel();
+    let (text_tx, mut text) = mpsc::unbounded_channel::<String>();
+    thread::spawn(move || {
+        let response = executor.execute_streaming(command, &mut |delta| {
+            text_tx.send(delta.to_string()).is_ok()
+        });
+        let _ = done.send(response);
+    });
+
+    // Closes once the worker thread drops its sender
+    while let Some(mut batch) = text.recv().await {
+        let deadline = Instant::now() + FRAME;
+        while let Ok(Some(delta)) = tokio::time::timeout_at(deadline, text.recv()).await {
+            batch.push_str(&delta);
+        }
+        let _ = partials.send(Response {
+            command_id: command_id.clone(),
+            payload: ResponsePayload::Partial(batch),
+        });
+    }
+    if let Ok(response) = result.await {
+        let _ = results.send(response);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc as std_mpsc;
+    use std::time::{Duration, Instant};
+
+    /// Answers queries after sleeping for the number of milliseconds in
+    /// their text, recording the order they finish in.
+    struct SlowExecutor {
+        finished: Mutex<Vec<String>>,
+    }
+
+    impl CommandExecutor for SlowExecutor {
+        fn execute(&self, command: Command) -> Response {
+            let CommandPayload::Query { text, .. } = &command.payload else {
+                return error_response(command.id, "unexpected command");
+            };
+            thread::sleep(Duration::from_millis(text.parse().unwrap()));
+            self.finished.lock().unwrap().push(command.id.clone());
+            Response {
+                command_id: command.id,
+                payload: ResponsePayload::Success(format!("slept {}", text)),
+            }
+        }
+    }
+
+    /// Streams "a", "b", "c" at once, then "d" a few frames later, until
+    /// told to stop. Queries with text "forever" keep emitting "x".
+    #[derive(Default)]
+    struct ChattyExecutor {
+        stopped_early: Mutex<bool>,
+    }
+
+    impl CommandExecutor for ChattyExecutor {
+        fn execute(&self, command: Command) -> Response {
+            error_response(command.id, "not streaming")
+        }
+
+        fn execute_streaming(
+            &self,
+            command: Command,
+            emit: &mut dyn FnMut(&str) -> bool,
+        ) -> Response {
+            let forever =
+                matches!(&command.payload, CommandPayload::Query { text, .. } if text == "forever");
+            let finished = Response {
+                command_id: command.id.clone(),
+                payload: ResponsePayload::Finished {
+                    stop_reason: "length".to_string(),
+                    sources: Vec::new(),
+                    used_context: false,
+                },
+            };
+            if forever {
+                let started = Instant::now();
+                while started.elapsed() < Duration::from_secs(5) {
+                    if !emit("x") {
+                        *self.stopped_early.lock().unwrap() = true;
+                        break;
+                    }
+                    thread::sleep(Duration::from_millis(5));
+                }
+                return finished;
+            }
+            for delta in ["a", "b", "c"] {
+                emit(delta);
+            }
+            thread::sleep(Duration::from_millis(100));
+            emit("d");
+            finished
+        }
+    }
+
+    fn query(id: &str, millis: u64) -> Command {
+        Command {
+            id: id.to_string(),
+            payload: CommandPayload::Query {
+                text: millis.to_string(),
+                use_rag: None,
+                conversation_id: None,
+                attachments: Vec::new(),
+            },
+        }
+    }
+
+    fn bus() -> (CommandBus, Arc<SlowExecutor>) {
+        let executor = Arc::new(SlowExecutor {
+            finished: Mutex::new(Vec::new()),
+        });
+        (CommandBus::start(executor.clone()).unwrap(), executor)
+    }
+
+    /// Drive `future` to completion on a throwaway runtime, sending the
+    /// result to `tx`.
+    fn wait_on(
+        future: impl Future<Output = Response> + Send + 'static,
+        tx: std_mpsc::Sender<Response>,
+    ) {
+        thread::spawn(move || {
+            let runtime = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let _ = tx.send(runtime.block_on(future));
+        });
+    }
+
+    #[test]
+    fn test_concurrent_queries_complete_independently() {
+        let (bus, executor) = bus();
+        let (tx, rx) = std_mpsc::channel();
+
+        let started = Instant::now();
+        wait_on(bus.send(query("slow", 600)), tx.clone());
+        wait_on(bus.send(query("fast", 50)), tx);
+
+        let first = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(first.command_id, "fast");
+        assert!(matches!(first.payload, ResponsePayload::Success(ref s) if s == "slept 50"));
+
+        let second = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(second.command_id, "slow");
+        // Run side by side, not one after the other
+        assert!(started.elapsed() < Duration::from_millis(600 + 50 + 300));
+        assert_eq!(*executor.finished.lock().unwrap(), vec!["fast", "slow"]);
+    }
+
+    #[test]
+    fn test_cancel_resolves_only_that_command() {
+        let (bus, _executor) = bus();
+        let (tx, rx) = std_mpsc::channel();
+
+        wait_on(bus.send(query("stuck", 2_000)), tx.clone());
+        wait_on(bus.send(query("quick", 100)), tx);
+        bus.cancel("stuck");
+        bus.cancel("unknown");
+
+        let first = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(first.command_id, "stuck");
+        assert!(
+            matches!(first.payload, ResponsePayload::Error { ref message, .. } if message == "Command cancelled")
+        );
+
+        let second = rx.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert_eq!(second.command_id, "quick");
+        assert!(matches!(second.payload, ResponsePayload::Success(_)));
+    }
+
+    fn streaming_query(id: &str, text: &str) -> Command {
+        Command {
+            id: id.to_string(),
+            payload: CommandPayload::Query {
+                text: text.to_string(),
+                use_rag: None,
+                conversation_id: None,
+                attachments: Vec::new(),
+            },
+        }
+    }
+
+    #[test]
+    fn test_streaming_batches_text_within_a_frame() {
+        let bus = CommandBus::start(Arc::new(ChattyExecutor::default())).unwrap();
+        let mut responses = bus.send_streaming(streaming_query("chat", "hello"));
+
+        let mut received = Vec::new();
+        while let Some(response) = responses.blocking_recv() {
+            assert_eq!(response.command_id, "chat");
+            received.push(response.payload);
+        }
+
+        assert_eq!(received.len(), 3);
+        assert!(matches!(received[0], ResponsePayload::Partial(ref t) if t == "abc"));
+        assert!(matches!(received[1], ResponsePayload::Partial(ref t) if t == "d"));
+        assert!(matches!(
+            received[2],
+            ResponsePayload::Finished { ref stop_reason, .. } if stop_reason == "length"
+        ));
+    }
+
+    #[test]
+    fn test_cancel_stops_streaming_generation() {
+        let executor = Arc::new(ChattyExecutor::default());
+        let bus = CommandBus::start(executor.clone()).unwrap();
+        let mut responses = bus.send_streaming(streaming_query("chat", "forever"));
+
+        let first = responses.blocking_recv().unwrap();
+        assert!(matches!(first.payload, ResponsePayload::Partial(_)));
+        bus.cancel("chat");
+
+        // A partial already in flight may still arrive after the cancellation
+        let rest: Vec<Response> = std::iter::from_fn(|| responses.blocking_recv()).collect();
+        assert!(rest.iter().any(
+            |r| matches!(r.payload, ResponsePayload::Error { ref message, .. } if message == "Command cancelled")
+        ));
+
+        let started = Instant::now();
+        while !*executor.stopped_early.lock().unwrap() {
+            assert!(started.elapsed() < Duration::from_secs(2));
+            thread::sleep(Duration::from_millis(10));
+        }
+    }
+}
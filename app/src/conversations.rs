@@ -0,0 +1,186 @@
+//! Conversations continued by `Query` commands.
+//!
+//! A query naming a conversation is answered with the conversation's
+//! earlier turns in its prompt, and its answer is added for the next one.
+//! Conversations start with their first query and stay in memory until
+//! ended or closed. With a [`ConversationStore`] each is also saved as it is
+//! answered, so a closed conversation can be restored later.
+
+use lucastra_llm::{
+    Conversation, ConversationError, ConversationResult, ConversationStore, ConversationSummary,
+    Message,
+};
+use std::collections::HashMap;
+
+#[derive(Debug, Default)]
+pub struct Conversations {
+    conversations: HashMap<String, Conversation>,
+    /// Where answered conversations are saved; `None` keeps them in memory.
+    store: Option<ConversationStore>,
+}
+
+impl Conversations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Conversations also saved to `store`.
+    pub fn with_store(store: ConversationStore) -> Self {
+        Self {
+            store: Some(store),
+            ..Self::default()
+        }
+    }
+
+    /// Earlier turns of conversation `id`, oldest first; empty until its
+    /// first answer.
+    pub fn history(&self, id: &str) -> Vec<Message> {
+        self.conversations
+            .get(id)
+            .map(Conversation::messages)
+            .unwrap_or_default()
+    }
+
+    /// Add a question and its answer to conversation `id`. A new
+    /// conversation keeps its last `max_messages` messages.
+    pub fn record(&mut self, id: &str, question: &str, answer: &str, max_messages: usize) {
+        let conversation = self.conversations.entry(id.to_string()).or_insert_with(|| {
+            Conversation::with_id(id.to_string(), None).with_max_messages(max_messages)
+        });
+        conversation.add_user_message(question.to_string());
+        conversation.add_assistant_message(answer.to_string());
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save(conversation) {
+                tracing::warn!("Failed to save conversation {}: {}", id, e);
+            }
+        }
+    }
+
+    /// Forget conversation `id`, including its saved copy. Returns whether
+    /// it had any turns.
+    pub fn end(&mut self, id: &str) -> bool {
+        if let Some(store) = self.store.as_ref().filter(|store| store.exists(id)) {
+            if let Err(e) = store.delete(id) {
+                tracing::warn!("Failed to delete conversation {}: {}", id, e);
+            }
+        }
+        self.conversations.remove(id).is_some()
+    }
+
+    /// Stop keeping conversation `id` in memory. Its saved copy stays, for
+    /// [`Conversations::restore`]. Returns whether it had any turns.
+    pub fn close(&mut self, id: &str) -> bool {
+        self.conversations.remove(id).is_some()
+    }
+
+    /// Continue conversation `id` from its saved copy, unless it is still
+    /// in memory. Returns its turns, oldest first.
+    pub fn restore(&mut self, id: &str) -> ConversationResult<Vec<Message>> {
+        if let Some(conversation) = self.conversations.get(id) {
+            return Ok(conversation.messages());
+        }
+        let store = self
+            .store
+            .as_ref()
+            .ok_or_else(|| ConversationError::NotFound(id.to_string()))?;
+        let conversation = store.load(id)?;
+        let messages = conversation.messages();
+        self.conversations.insert(id.to_string(), conversation);
+        Ok(messages)
+    }
+
+    /// Saved conversations whose ids start with `prefix`, most recently
+    /// updated first; empty without a store.
+    pub fn saved(&self, prefix: &str) -> Vec<ConversationSummary> {
+        let Some(store) = &self.store else {
+            return Vec::new();
+        };
+        match store.list() {
+            Ok(summaries) => summaries
+                .into_iter()
+                .filter(|summary| summary.id.starts_with(prefix))
+                .collect(),
+            Err(e) => {
+                tracing::warn!("Failed to list saved conversations: {}", e);
+                Vec::new()
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.conversations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.conversations.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_turns_are_kept_per_conversation() {
+        let mut conversations = Conversations::new();
+        assert!(conversations.history("a").is_empty());
+
+        conversations.record("a", "first?", "one", 20);
+        conversations.record("b", "other?", "two", 20);
+        conversations.record("a", "second?", "three", 20);
+        let contents: Vec<String> = conversations
+            .history("a")
+            .into_iter()
+            .map(|m| m.content)
+            .collect();
+        assert_eq!(contents, vec!["first?", "one", "second?", "three"]);
+
+        assert!(conversations.end("a"));
+        assert!(!conversations.end("a"));
+        assert_eq!(conversations.len(), 1);
+    }
+
+    #[test]
+    fn test_old_turns_are_trimmed() {
+        let mut conversations = Conversations::new();
+        for i in 0..5 {
+            conversations.record("a", &format!("q{}", i), &format!("a{}", i), 4);
+        }
+        let history = conversations.history("a");
+        assert_eq!(history.len(), 4);
+        assert_eq!(history[0].content, "q3");
+    }
+
+    #[test]
+    fn test_closed_conversations_are_restored_from_the_store() {
+        let dir = std::env::temp_dir().join(format!("lucastra_convs_{}", std::process::id()));
+        let mut conversations =
+            Conversations::with_store(ConversationStore::new(dir.clone()).unwrap());
+        conversations.record("gui-a", "about code?", "yes", 20);
+        conversations.record("gui-b", "about documents?", "sure", 20);
+        conversations.record("cli-c", "other client?", "no", 20);
+
+        assert!(conversations.close("gui-a"));
+        assert!(conversations.history("gui-a").is_empty());
+        let restored = conversations.restore("gui-a").unwrap();
+        assert_eq!(restored.len(), 2);
+        assert_eq!(conversations.history("gui-a")[0].content, "about code?");
+        assert_eq!(conversations.history("gui-b")[1].content, "sure");
+
+        let mut saved: Vec<String> = conversations
+            .saved("gui-")
+            .into_iter()
+            .map(|summary| summary.id)
+            .collect();
+        saved.sort();
+        assert_eq!(saved, ["gui-a", "gui-b"]);
+
+        // Ending forgets the saved copy too
+        assert!(conversations.end("gui-b"));
+        assert!(matches!(
+            conversations.restore("gui-b"),
+            Err(ConversationError::NotFound(_))
+        ));
+        let _ = std::fs::remove_dir_all(dir);
+    }
+}
@@ -0,0 +1,74 @@
+//! Status dashboard data.
+//!
+//! [`SystemState::dashboard`] gathers devices, mounts, the search index,
+//! the LLM, metrics and background jobs in one snapshot. Each panel that
+//! can fail carries its own error so one broken subsystem doesn't hide the
+//! others.
+
+use crate::{JobStatus, MetricsSnapshot, SystemState};
+use lucastra_core::DeviceInfo;
+use lucastra_fs::MountInfo;
+use lucastra_llm::HealthStatus;
+
+/// Documents in the search index and its size on disk.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchStats {
+    pub doc_count: usize,
+    /// `None` when the index isn't persisted
+    pub index_bytes: Option<u64>,
+}
+
+/// The LLM backend queries go to.
+#[derive(Debug, Clone)]
+pub struct LlmStatus {
+    pub provider: String,
+    /// Model size and quantization, e.g. `7b (4bit)`
+    pub model: String,
+    pub endpoint: String,
+    /// Latest health check; `None` while monitoring is disabled
+    pub health: Option<HealthStatus>,
+}
+
+/// Everything the dashboard shows, taken at one moment.
+#[derive(Debug, Clone)]
+pub struct Dashboard {
+    pub devices: Result<Vec<DeviceInfo>, String>,
+    pub mounts: Vec<MountInfo>,
+    pub search: Result<SearchStats, String>,
+    pub llm: LlmStatus,
+    pub metrics: MetricsSnapshot,
+    /// Empty while background jobs aren't running
+    pub jobs: Vec<JobStatus>,
+}
+
+impl SystemState {
+    /// Snapshot of every dashboard panel.
+    pub fn dashboard(&self) -> Dashboard {
+        let search = self
+            .search_service
+            .index_size()
+            .map(|index_bytes| SearchStats {
+                doc_count: self.search_service.doc_count(),
+                index_bytes,
+            })
+            .map_err(|e| e.to_string());
+        let llm = &self.config.llm;
+
+        Dashboard {
+            devices: self
+                .device_manager
+                .list_devices()
+                .map_err(|e| e.to_string()),
+            mounts: self.filesystem.list_mounts(),
+            search,
+            llm: LlmStatus {
+                provider: "llamafile".to_string(),
+                model: format!("{} ({})", llm.model_size, llm.quantization),
+                endpoint: self.llm_service.endpoint().to_string(),
+                health: self.llm_health(),
+            },
+            metrics: self.metrics.snapshot(),
+            jobs: self.job_statuses(),
+        }
+    }
+}
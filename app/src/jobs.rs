@@ -0,0 +1,573 @@
+//! Scheduled background jobs.
+//!
+//! [`JobScheduler`] runs named jobs on a tokio runtime of its own, each on
+//! its [`Schedule`]. Every run happens on a blocking thread and is reported
+//! as timed out once it takes longer than the job's timeout. A job still
+//! running when it is next due is skipped rather than started twice, and a
+//! job that fails or panics is marked unhealthy without stopping the
+//! others. The outcome of each job's last run is kept for the dashboard,
+//! and any job can be started on demand with [`JobScheduler::trigger`].
+//!
+//! [`SystemState::start_jobs`] registers the built-in jobs named in
+//! [`lucastra_config::jobs`], scheduled from the `[jobs]` settings.
+
+use crate::{observability, SystemState};
+use lucastra_config::jobs::{self, JOB_NAMES};
+use lucastra_config::{Config, Schedule};
+use lucastra_core::LuCastraError;
+use lucastra_llm::EmbeddingCache;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, Weak};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tokio::runtime::Runtime;
+use tokio::task::AbortHandle;
+
+/// The work a job does, returning a summary of what it did.
+type JobFn = dyn Fn() -> Result<String, String> + Send + Sync;
+
+/// How a run of a job ended.
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobOutcome {
+    Succeeded(String),
+    Failed(String),
+    /// Still running at the timeout; it finishes in the background.
+    TimedOut(Duration),
+    Panicked(String),
+}
+
+impl JobOutcome {
+    pub fn is_success(&self) -> bool {
+        matches!(self, JobOutcome::Succeeded(_))
+    }
+}
+
+impl fmt::Display for JobOutcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JobOutcome::Succeeded(summary) => f.write_str(summary),
+            JobOutcome::Failed(error) => write!(f, "failed: {}", error),
+            JobOutcome::TimedOut(timeout) => {
+                write!(f, "timed out after {}s", timeout.as_secs_f32())
+            }
+            JobOutcome::Panicked(message) => write!(f, "panicked: {}", message),
+        }
+    }
+}
+
+/// One finished run of a job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobRun {
+    /// Unix time the run started, in seconds
+    pub started_at: i64,
+    pub duration: Duration,
+    pub outcome: JobOutcome,
+    /// Started on demand rather than by the schedule
+    pub manual: bool,
+}
+
+/// What the dashboard shows about a job.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JobStatus {
+    pub name: String,
+    /// `None` while the job only runs on demand
+    pub schedule: Option<String>,
+    pub running: bool,
+    pub last_run: Option<JobRun>,
+    pub runs: u64,
+    pub failures: u64,
+    /// Times the job was due while its previous run was still going
+    pub skipped: u64,
+}
+
+impl JobStatus {
+    /// Whether the last run, if any, succeeded.
+    pub fn is_healthy(&self) -> bool {
+        self.last_run
+            .as_ref()
+            .is_none_or(|run| run.outcome.is_success())
+    }
+}
+
+struct Job {
+    work: Arc<JobFn>,
+    /// When it runs, and how long a run may take
+    timing: Mutex<(Option<Schedule>, Duration)>,
+    running: AtomicBool,
+    status: Mutex<JobStatus>,
+    /// The task starting runs on the schedule
+    ticker: Mutex<Option<AbortHandle>>,
+}
+
+impl Job {
+    fn timing(&self) -> MutexGuard<'_, (Option<Schedule>, Duration)> {
+        self.timing.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn status(&self) -> MutexGuard<'_, JobStatus> {
+        self.status.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn snapshot(&self) -> JobStatus {
+        JobStatus {
+            running: self.running.load(Ordering::SeqCst),
+            ..self.status().clone()
+        }
+    }
+}
+
+/// Clears a job's running flag when its work returns or panics.
+struct RunningGuard(Arc<Job>);
+
+impl Drop for RunningGuard {
+    fn drop(&mut self) {
+        self.0.running.store(false, Ordering::SeqCst);
+    }
+}
+
+/// Runs registered jobs on their schedules until dropped.
+pub struct JobScheduler {
+    runtime: Option<Runtime>,
+    jobs: Mutex<BTreeMap<String, Arc<Job>>>,
+}
+
+impl JobScheduler {
+    /// Start the scheduler's runtime, with no jobs yet.
+    pub fn start() -> std::io::Result<Self> {
+        let runtime = tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("lucastra-jobs")
+            .enable_time()
+            .build()?;
+        Ok(Self {
+            runtime: Some(runtime),
+            jobs: Mutex::new(BTreeMap::new()),
+        })
+    }
+
+    /// Add job `name` doing `work` on `schedule`, or only on demand when
+    /// that is `None`. Runs longer than `timeout` are reported as timed
+    /// out. A job already called `name` is replaced.
+    pub fn register(
+        &self,
+        name: &str,
+        schedule: Option<Schedule>,
+        timeout: Duration,
+        work: impl Fn() -> Result<String, String> + Send + Sync + 'static,
+    ) {
+        let job = Arc::new(Job {
+            work: Arc::new(work),
+            timing: Mutex::new((None, timeout)),
+            running: AtomicBool::new(false),
+            status: Mutex::new(JobStatus {
+                name: name.to_string(),
+                schedule: None,
+                running: false,
+                last_run: None,
+                runs: 0,
+                failures: 0,
+                skipped: 0,
+            }),
+            ticker: Mutex::new(None),
+        });
+        if let Some(old) = self.lock().insert(name.to_string(), job.clone()) {
+            self.stop_ticker(&old);
+        }
+        self.schedule(&job, schedule, timeout);
+    }
+
+    /// Change when job `name` runs and its timeout. Returns `false` if
+    /// there is no such job.
+    pub fn reschedule(&self, name: &str, schedule: Option<Schedule>, timeout: Duration) -> bool {
+        let Some(job) = self.job(name) else {
+            return false;
+        };
+        self.schedule(&job, schedule, timeout);
+        true
+    }
+
+    /// Start job `name` now. Fails if there is no such job or it is
+    /// already running; the outcome shows in its [`JobStatus`].
+    pub fn trigger(&self, name: &str) -> lucastra_core::Result<()> {
+        let job = self
+            .job(name)
+            .ok_or_else(|| LuCastraError::InvalidCommand(format!("No job called {}", name)))?;
+        if job.running.load(Ordering::SeqCst) {
+            return Err(LuCastraError::ServiceError(format!(
+                "Job {} is already running",
+                name
+            )));
+        }
+        if let Some(runtime) = &self.runtime {
+            runtime.spawn(run(job, true));
+        }
+        Ok(())
+    }
+
+    /// Status of job `name`.
+    pub fn status(&self, name: &str) -> Option<JobStatus> {
+        self.job(name).map(|job| job.snapshot())
+    }
+
+    /// Status of every job, by name.
+    pub fn statuses(&self) -> Vec<JobStatus> {
+        self.lock().values().map(|job| job.snapshot()).collect()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, BTreeMap<String, Arc<Job>>> {
+        self.jobs.lock().unwrap_or_else(|p| p.into_inner())
+    }
+
+    fn job(&self, name: &str) -> Option<Arc<Job>> {
+        self.lock().get(name).cloned()
+    }
+
+    fn schedule(&self, job: &Arc<Job>, schedule: Option<Schedule>, timeout: Duration) {
+        self.stop_ticker(job);
+        job.status().schedule = schedule.as_ref().map(Schedule::to_string);
+        *job.timing() = (schedule.clone(), timeout);
+        if let (Some(schedule), Some(runtime)) = (schedule, &self.runtime) {
+            let ticker = runtime.spawn(tick(job.clone(), schedule));
+            *job.ticker.lock().unwrap_or_else(|p| p.into_inner()) = Some(ticker.abort_handle());
+        }
+    }
+
+    fn stop_ticker(&self, job: &Job) {
+        if let Some(ticker) = job.ticker.lock().unwrap_or_else(|p| p.into_inner()).take() {
+            ticker.abort();
+        }
+    }
+}
+
+impl Drop for JobScheduler {
+    fn drop(&mut self) {
+        // Runs in progress finish on their own threads, unrecorded
+        if let Some(runtime) = self.runtime.take() {
+            runtime.shutdown_background();
+        }
+    }
+}
+
+/// Start a run of `job` each time `schedule` comes round.
+async fn tick(job: Arc<Job>, schedule: Schedule) {
+    while let Some(delay) = schedule.next_delay(chrono::Local::now()) {
+        tokio::time::sleep(delay).await;
+        tokio::spawn(run(job.clone(), false));
+    }
+}
+
+/// Run `job` once unless it is already running, recording the outcome.
+async fn run(job: Arc<Job>, manual: bool) {
+    let name = job.status().name.clone();
+    if job.running.swap(true, Ordering::SeqCst) {
+        tracing::debug!("Skipping job {}: the previous run is still going", name);
+        job.status().skipped += 1;
+        return;
+    }
+    let timeout = job.timing().1;
+    let started_at = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs() as i64;
+    let started = Instant::now();
+
+    let guard = RunningGuard(job.clone());
+    let work = job.work.clone();
+    let span = observability::job_span(&name);
+    let task = tokio::task::spawn_blocking(move || {
+        let _guard = guard;
+        span.in_scope(|| work())
+    });
+    let outcome = match tokio::time::timeout(timeout, task).await {
+        Ok(Ok(Ok(summary))) => JobOutcome::Succeeded(summary),
+        Ok(Ok(Err(error))) => JobOutcome::Failed(error),
+        Ok(Err(join)) => JobOutcome::Panicked(panic_message(join)),
+        Err(_) => JobOutcome::TimedOut(timeout),
+    };
+    if outcome.is_success() {
+        tracing::debug!("Job {}: {}", name, outcome);
+    } else {
+        tracing::warn!("Job {} {}", name, outcome);
+    }
+
+    let mut status = job.status();
+    status.runs += 1;
+    if !outcome.is_success() {
+        status.failures += 1;
+    }
+    status.last_run = Some(JobRun {
+        started_at,
+        duration: started.elapsed(),
+        outcome,
+        manual,
+    });
+}
+
+fn panic_message(error: tokio::task::JoinError) -> String {
+    match error.try_into_panic() {
+        Ok(panic) => panic
+            .downcast_ref::<&str>()
+            .map(|s| s.to_string())
+            .or_else(|| panic.downcast_ref::<String>().cloned())
+            .unwrap_or_else(|| "unknown panic".to_string()),
+        Err(error) => error.to_string(),
+    }
+}
+
+/// When built-in job `name` runs under `config`, and its timeout.
+fn timing(config: &Config, name: &str) -> (Option<Schedule>, Duration) {
+    let job = config.jobs.job(name);
+    let timeout = Duration::from_secs(job.timeout_secs);
+    if !job.enabled {
+        return (None, timeout);
+    }
+    let spec = job
+        .schedule
+        .or_else(|| jobs::default_schedule(name).map(str::to_string))
+        .unwrap_or_else(|| format!("every {}s", config.metrics.export_interval_secs.max(1)));
+    match spec.parse() {
+        Ok(schedule) => (Some(schedule), timeout),
+        Err(e) => {
+            tracing::warn!("Job {} only runs on demand: {}", name, e);
+            (None, timeout)
+        }
+    }
+}
+
+/// The work built-in job `name` does on `state`.
+fn builtin(
+    name: &'static str,
+    state: Weak<Mutex<SystemState>>,
+) -> impl Fn() -> Result<String, String> + Send + Sync + 'static {
+    move || {
+        let shared = state
+            .upgrade()
+            .ok_or_else(|| "the system has shut down".to_string())?;
+        let mut state = shared
+            .lock()
+            .map_err(|_| "system state is unavailable".to_string())?;
+        match name {
+            jobs::REINDEX_CHANGED_FILES => {
+                if !state.is_watching() {
+                    return Ok("Auto-indexing is off".to_string());
+                }
+                let applied = state.process_watch_events();
+                Ok(format!("Applied {} file changes", applied))
+            }
+            jobs::EMBEDDING_CACHE_EVICT => {
+                let max_age = state.config.jobs.embedding_cache_max_age_days;
+                drop(state);
+                let dir = lucastra_config::get_data_dir()
+                    .map_err(|e| e.to_string())?
+                    .join("embedding_cache");
+                if !dir.is_dir() {
+                    return Ok("No embedding cache".to_string());
+                }
+                let removed = EmbeddingCache::new(dir)
+                    .and_then(|cache| cache.clear_old(max_age.into()))
+                    .map_err(|e| e.to_string())?;
+                Ok(format!(
+                    "Removed {} embeddings older than {} days",
+                    removed, max_age
+                ))
+            }
+            jobs::AUDIT_ROTATE => {
+                let log = state
+                    .file_access_tool()
+                    .map_err(|result| result.output)?
+                    .audit_log()
+                    .clone();
+                drop(state);
+                match log.rotate_if_full() {
+                    Ok(true) => Ok(format!("Rotated {}", log.path().display())),
+                    Ok(false) => Ok("Audit log is within its size limit".to_string()),
+                    Err(e) => Err(e.to_string()),
+                }
+            }
+            jobs::METRICS_EXPORT => {
+                let config = state.config.metrics.clone();
+                let snapshot = state.metrics.snapshot();
+                drop(state);
+                if !(config.enabled && config.export_to_file) {
+                    return Ok("Metrics export is off".to_string());
+                }
+                let keep = config.export_files_keep.max(1) as usize;
+                observability::export_metrics(&snapshot, &config.export_dir, keep)
+                    .map(|path| format!("Exported metrics to {}", path.display()))
+                    .map_err(|e| e.to_string())
+            }
+            _ => Err(format!("no built-in job called {}", name)),
+        }
+    }
+}
+
+impl SystemState {
+    /// Run the built-in jobs on the schedules in `[jobs]`, unless
+    /// `jobs.enabled` is off. Metrics are exported by the metrics-export
+    /// job from then on.
+    pub fn start_jobs(state: &Arc<Mutex<Self>>) -> lucastra_core::Result<()> {
+        let mut locked = state
+            .lock()
+            .map_err(|_| LuCastraError::ServiceError("System state is unavailable".to_string()))?;
+        if !locked.config.jobs.enabled || locked.jobs.is_some() {
+            return Ok(());
+        }
+        let scheduler = JobScheduler::start().map_err(|e| {
+            LuCastraError::ServiceError(format!("Job scheduler not started: {}", e))
+        })?;
+        for name in JOB_NAMES {
+            let (schedule, timeout) = timing(&locked.config, name);
+            scheduler.register(
+                name,
+                schedule,
+                timeout,
+                builtin(name, Arc::downgrade(state)),
+            );
+        }
+        locked.metrics_exporter = None;
+        locked.jobs = Some(scheduler);
+        tracing::info!("Started {} background jobs", JOB_NAMES.len());
+        Ok(())
+    }
+
+    /// Status of every background job; empty while jobs aren't running.
+    pub fn job_statuses(&self) -> Vec<JobStatus> {
+        self.jobs
+            .as_ref()
+            .map(JobScheduler::statuses)
+            .unwrap_or_default()
+    }
+
+    /// Start background job `name` now.
+    pub fn run_job(&self, name: &str) -> lucastra_core::Result<()> {
+        match &self.jobs {
+            Some(scheduler) => scheduler.trigger(name),
+            None => Err(LuCastraError::ServiceError(
+                "Background jobs are not running".to_string(),
+            )),
+        }
+    }
+
+    /// Bring job schedules in line with the current config.
+    pub(crate) fn reschedule_jobs(&self) {
+        let Some(scheduler) = &self.jobs else {
+            return;
+        };
+        for name in JOB_NAMES {
+            let (schedule, timeout) = if self.config.jobs.enabled {
+                timing(&self.config, name)
+            } else {
+                (None, timing(&self.config, name).1)
+            };
+            scheduler.reschedule(name, schedule, timeout);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::thread;
+
+    fn every_second() -> Option<Schedule> {
+        Some(Schedule::Every(Duration::from_secs(1)))
+    }
+
+    /// Poll `status` of job `name` until `done` holds, for up to 10s.
+    fn wait_for(
+        scheduler: &JobScheduler,
+        name: &str,
+        done: impl Fn(&JobStatus) -> bool,
+    ) -> JobStatus {
+        let started = Instant::now();
+        loop {
+            let status = scheduler.status(name).unwrap();
+            if done(&status) {
+                return status;
+            }
+            assert!(started.elapsed() < Duration::from_secs(10), "{:?}", status);
+            thread::sleep(Duration::from_millis(20));
+        }
+    }
+
+    #[test]
+    fn test_runs_still_going_are_not_overlapped() {
+        let scheduler = JobScheduler::start().unwrap();
+        let active = Arc::new(AtomicUsize::new(0));
+        let most_active = Arc::new(AtomicUsize::new(0));
+        let (counter, most) = (active.clone(), most_active.clone());
+        // Due every second, takes a second and a half
+        scheduler.register("slow", every_second(), Duration::from_secs(10), move || {
+            let now = counter.fetch_add(1, Ordering::SeqCst) + 1;
+            most.fetch_max(now, Ordering::SeqCst);
+            thread::sleep(Duration::from_millis(1500));
+            counter.fetch_sub(1, Ordering::SeqCst);
+            Ok("done".to_string())
+        });
+
+        let status = wait_for(&scheduler, "slow", |s| s.runs >= 1 && s.skipped >= 1);
+        assert_eq!(most_active.load(Ordering::SeqCst), 1);
+        assert_eq!(status.schedule.as_deref(), Some("every 1s"));
+        assert!(status.is_healthy());
+        assert_eq!(
+            status.last_run.unwrap().outcome,
+            JobOutcome::Succeeded("done".to_string())
+        );
+    }
+
+    #[test]
+    fn test_a_panicking_job_is_isolated() {
+        let scheduler = JobScheduler::start().unwrap();
+        scheduler.register("boom", every_second(), Duration::from_secs(5), || {
+            panic!("index is corrupt")
+        });
+        scheduler.register("steady", every_second(), Duration::from_secs(5), || {
+            Ok("fine".to_string())
+        });
+
+        // Still scheduled after panicking
+        let boom = wait_for(&scheduler, "boom", |s| s.runs >= 2);
+        assert!(!boom.is_healthy());
+        assert_eq!(boom.failures, boom.runs);
+        assert_eq!(
+            boom.last_run.unwrap().outcome,
+            JobOutcome::Panicked("index is corrupt".to_string())
+        );
+        assert!(!boom.running);
+
+        let steady = wait_for(&scheduler, "steady", |s| s.runs >= 2);
+        assert!(steady.is_healthy());
+        assert_eq!(steady.failures, 0);
+    }
+
+    #[test]
+    fn test_manual_runs_time_out_and_refuse_to_overlap() {
+        let scheduler = JobScheduler::start().unwrap();
+        scheduler.register("stuck", None, Duration::from_secs(1), || {
+            thread::sleep(Duration::from_secs(2));
+            Err("gave up".to_string())
+        });
+        assert!(scheduler.trigger("missing").is_err());
+        assert_eq!(scheduler.status("stuck").unwrap().schedule, None);
+
+        scheduler.trigger("stuck").unwrap();
+        let timed_out = wait_for(&scheduler, "stuck", |s| s.runs == 1);
+        let run = timed_out.last_run.unwrap();
+        assert_eq!(run.outcome, JobOutcome::TimedOut(Duration::from_secs(1)));
+        assert!(run.manual);
+        // The work carries on past the timeout and blocks a second run
+        assert!(timed_out.running);
+        assert!(scheduler.trigger("stuck").is_err());
+
+        wait_for(&scheduler, "stuck", |s| !s.running);
+        assert!(scheduler.reschedule("stuck", every_second(), Duration::from_secs(5)));
+        scheduler.trigger("stuck").unwrap();
+        let failed = wait_for(&scheduler, "stuck", |s| s.runs == 2);
+        assert_eq!(
+            failed.last_run.unwrap().outcome,
+            JobOutcome::Failed("gave up".to_string())
+        );
+    }
+}
@@ -1,27 +1,183 @@
-use lucastra_config::Config;
-use lucastra_core::{Command, CommandPayload, Response, ResponsePayload};
-use lucastra_devices::DeviceManager;
+use lucastra_browser::HtmlExtractor;
+use lucastra_compat::Fat32FileSystem;
+use lucastra_config::{Config, ConfigEvent, ConfigWatcher, LlmConfig};
+use lucastra_core::{
+    Attachment, Command, CommandPayload, Context, DeviceEvent, DeviceType, ErrorCode,
+    LuCastraError, Response, ResponsePayload, SourceOrigin, SourceRef,
+};
+use lucastra_devices::{DeviceManager, DEFAULT_HOTPLUG_INTERVAL};
 use lucastra_fs::FilesystemManager;
-use lucastra_hal::filesystem::MockFileSystem;
+use lucastra_hal::filesystem::{HostFileSystem, MockFileSystem};
 use lucastra_input::InputManager;
-use lucastra_llm::LLMService;
-use lucastra_search::SearchService;
+use lucastra_llm::providers::mock::MockProvider;
+use lucastra_llm::{
+    standard_variables, ConversationStore, ConversationSummary, HealthChecker, HealthMonitor,
+    HealthStatus, InferenceRequest, InferenceResponse, LLMService, Message, PromptRegistry,
+    UsageRecord, UsageTracker,
+};
+use lucastra_search::{
+    Chunker, FileWatcher, IndexSummary, Indexer, RetrievalOptions, SearchService,
+};
 use lucastra_services::ServiceRegistry;
 use lucastra_tools::{
-    file_access::{FileAccessTool, FileAccessValidator},
-    install::InstallTool,
+    approval::{Approval, ApprovalBroker},
+    calculate::CalculatorTool,
+    delete::DeleteTool,
+    fetch::FetchTool,
+    file_access::{
+        AuditLog, FileAccessError, FileAccessTool, FileAccessValidator, HostFileAccessRequest,
+    },
+    install::{InstallRequest, InstallTool},
+    parser::{ParsedToolCalls, ToolCallParser},
+    rbac::PermissionPolicy,
     read::ReadTool,
     search::SearchTool,
-    Tool, ToolResult,
+    write::WriteTool,
+    InstallMethod, Tool, ToolResult,
 };
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 
+pub mod agent;
+pub mod browse;
+pub mod bus;
+pub mod conversations;
+pub mod dashboard;
+pub mod jobs;
 pub mod metrics;
 pub mod observability;
-pub use metrics::{Metrics, MetricsSnapshot};
+#[cfg(feature = "rpc")]
+pub mod rpc;
+pub mod supervisor;
+pub use agent::{AgentExecutor, AgentStep, AgentTranscript};
+pub use browse::BrowserService;
+pub use bus::{CommandBus, CommandExecutor};
+pub use conversations::Conversations;
+pub use dashboard::{Dashboard, LlmStatus, SearchStats};
+pub use jobs::{JobOutcome, JobRun, JobScheduler, JobStatus};
+pub use metrics::{LatencySummary, Metrics, MetricsSnapshot, MetricsTotals};
+pub use observability::{MetricsExporter, MetricsPersister};
+#[cfg(feature = "rpc")]
+pub use rpc::RpcServer;
 
 #[cfg(feature = "relibc")]
-use lucastra_kernel::SyscallHandler;
+use lucastra_kernel::ProcessTable;
+
+/// Serve `/metrics` on `addr`, logging instead of failing the boot.
+#[cfg(feature = "metrics-http")]
+fn serve_metrics(metrics: &Metrics, addr: &str) {
+    match observability::serve_metrics(metrics.clone(), addr) {
+        Ok(bound) => tracing::info!("Serving metrics on http://{}/metrics", bound),
+        Err(e) => tracing::warn!("Metrics endpoint not started on {}: {}", addr, e),
+    }
+}
+
+#[cfg(not(feature = "metrics-http"))]
+fn serve_metrics(_metrics: &Metrics, addr: &str) {
+    tracing::warn!(
+        "metrics.http_addr is {} but this build lacks the metrics-http feature",
+        addr
+    );
+}
+
+/// The LLM service for `config`. With `offline_placeholders` on it answers
+/// with the mock provider while the server is unreachable.
+fn llm_service(config: &LlmConfig) -> LLMService {
+    let mut service = LLMService::new(config.server_url.clone());
+    if let Some(prompt) = system_prompt(config) {
+        service.set_system_prompt(prompt);
+    }
+    if config.offline_placeholders {
+        service.with_offline_provider(Box::new(MockProvider::new()))
+    } else {
+        service
+    }
+}
+
+/// Usage tracker backed by `usage.json` in the data directory, priced from
+/// the configured providers. Starts empty when the file can't be read.
+fn usage_tracker(config: &Config) -> UsageTracker {
+    let path = config.storage.data_dir.join("usage.json");
+    let tracker = UsageTracker::load(&path).unwrap_or_else(|e| {
+        tracing::warn!(
+            "Starting usage tracking afresh, {} unreadable: {}",
+            path.display(),
+            e
+        );
+        UsageTracker::new()
+    });
+    config
+        .providers
+        .entries
+        .values()
+        .flat_map(|entry| std::iter::once(entry).chain(&entry.fallbacks))
+        .fold(tracker, |tracker, entry| {
+            tracker.with_pricing(&entry.provider, entry.pricing.clone())
+        })
+}
+
+/// Price `usage`, add it to `tracker` and save the tracker.
+fn record_usage(tracker: &Mutex<UsageTracker>, mut usage: UsageRecord) {
+    let Ok(mut tracker) = tracker.lock() else {
+        return;
+    };
+    usage.cost_usd = tracker
+        .price(&usage.provider, &usage.model)
+        .cost(usage.input_tokens, usage.output_tokens);
+    tracker.record(usage);
+    if let Err(e) = tracker.save() {
+        tracing::warn!("Failed to save usage: {}", e);
+    }
+}
+
+/// `system_prompt_template` rendered, or `None` to keep the service's own
+/// prompt when it can't be.
+fn system_prompt(config: &LlmConfig) -> Option<String> {
+    let registry = match lucastra_config::get_prompts_dir() {
+        Ok(dir) => PromptRegistry::load(&dir),
+        Err(e) => {
+            tracing::warn!("Prompt templates directory unavailable: {}", e);
+            PromptRegistry::builtin()
+        }
+    };
+    match registry.render(&config.system_prompt_template, &standard_variables()) {
+        Ok(prompt) => Some(prompt),
+        Err(e) => {
+            tracing::warn!("Keeping the built-in system prompt: {}", e);
+            None
+        }
+    }
+}
+
+/// Register the llama server and start it in the background, logging
+/// instead of failing the boot. Returns the server's handle once it is
+/// registered.
+fn start_llm_server(
+    registry: &mut ServiceRegistry,
+    config: &LlmConfig,
+) -> Option<supervisor::LlmServerHandle> {
+    let models_dir = match lucastra_config::get_models_dir() {
+        Ok(dir) => dir,
+        Err(e) => {
+            tracing::warn!("LLM server not started: {}", e);
+            return None;
+        }
+    };
+    let log_file = lucastra_config::get_logs_dir()
+        .ok()
+        .map(|dir| dir.join("llama-server.log"));
+    let server = supervisor::LlmServerService::from_config(config, models_dir, log_file);
+    let handle = server.handle();
+    if let Err(e) = registry.register(Box::new(server)) {
+        tracing::warn!("LLM server not started: {}", e);
+        return None;
+    }
+    if let Err(e) = registry.start_all() {
+        tracing::warn!("LLM server not started: {}", e);
+    }
+    Some(handle)
+}
 
 /// System state holding all services.
 pub struct SystemState {
@@ -30,11 +186,40 @@ pub struct SystemState {
     pub device_manager: DeviceManager,
     pub filesystem: FilesystemManager,
     pub input_manager: InputManager,
-    pub search_service: SearchService,
+    /// Shared so searches can run on other threads while the state is
+    /// busy; it locks its own index.
+    pub search_service: Arc<SearchService>,
     pub llm_service: LLMService,
+    pub browser: BrowserService,
     pub metrics: Metrics,
+    /// Tokens and cost per day, saved to `usage.json` in the data directory.
+    pub usage: Arc<Mutex<UsageTracker>>,
+    /// The auto-started llama server, restarted when health checks fail.
+    llm_server: Option<supervisor::LlmServerHandle>,
+    /// Probes the LLM every `llm.health_check_interval_secs`.
+    health_monitor: Option<HealthMonitor>,
+    /// Writes metrics to `metrics.export_dir` while `metrics.export_to_file`
+    /// is on, until the metrics-export job takes over; held only so
+    /// exporting stops when the state is dropped.
+    metrics_exporter: Option<MetricsExporter>,
+    /// Saves lifetime metrics while `metrics.persist` is on, and once more
+    /// when the state is dropped.
+    _metrics_persister: Option<MetricsPersister>,
+    /// Keeps the search index in sync with disk while `storage.auto_index` is on.
+    watcher: Option<FileWatcher>,
+    /// Host file operations waiting for the user's approval.
+    approvals: ApprovalBroker,
+    /// Installer downloads waiting for the user's approval.
+    install_approvals: ApprovalBroker<InstallRequest>,
+    /// Reports edits to `config.toml` made while the system is running.
+    config_watcher: Option<ConfigWatcher>,
+    /// Earlier turns of the conversations queries continue.
+    conversations: Conversations,
+    /// Background jobs, once [`SystemState::start_jobs`] has run.
+    jobs: Option<JobScheduler>,
     #[cfg(feature = "relibc")]
-    pub syscall_handler: Option<SyscallHandler>,
+    /// Processes started through the compatibility layer.
+    pub processes: ProcessTable,
 }
 
 impl SystemState {
@@ -45,53 +230,345 @@ impl SystemState {
         // Load configuration
         let config = Config::load().map_err(|e| {
             tracing::error!("Failed to load config: {}", e);
-            lucastra_core::LuCastraError::ConfigError(format!("Config error: {}", e))
+            LuCastraError::from(e)
         })?;
 
         tracing::info!("Configuration loaded successfully");
+        let mut state = Self::with_config(config)?;
+        state.config_watcher = lucastra_config::get_config_file_path()
+            .and_then(|path| ConfigWatcher::start(path, state.config.clone()))
+            .map_err(|e| tracing::warn!("Config reload disabled: {}", e))
+            .ok();
+        Ok(state)
+    }
+
+    /// Boot from an already loaded `config`, which no config file backs, so
+    /// nothing is reloaded when the config file changes.
+    pub fn with_config(config: Config) -> lucastra_core::Result<Self> {
+        lucastra_llm::wire_log::install(lucastra_llm::WireLog::from_config(&config.llm));
         tracing::debug!("LLM server: {}", config.llm.server_url);
         tracing::debug!("Model size: {}", config.llm.model_size);
         tracing::debug!("Data directory: {}", config.storage.data_dir.display());
 
-        let service_registry = ServiceRegistry::new();
+        let mut service_registry =
+            supervisor::core_registry().map_err(|e| LuCastraError::ServiceError(e.to_string()))?;
+        let llm_server = if config.llm.auto_start {
+            start_llm_server(&mut service_registry, &config.llm)
+        } else {
+            None
+        };
         let mut device_manager = DeviceManager::new();
         let mut filesystem = FilesystemManager::new();
         let input_manager = InputManager::new();
-        let mut search_service = SearchService::new();
-        let llm_service = LLMService::new(config.llm.server_url.clone());
+        let search_service = SearchService::new(Some(config.storage.data_dir.join("search_index")))
+            .with_bm25_params(lucastra_search::Bm25Params::from_config(&config.search))
+            .with_max_results(config.search.max_results);
+        if search_service.set_tokenizer(lucastra_search::Tokenizer::from_config(&config.search)) {
+            if let Err(e) = search_service.save() {
+                tracing::warn!("Failed to persist search index: {}", e);
+            }
+        }
+        let llm_service = llm_service(&config.llm);
+        let browser = BrowserService::open(config.storage.data_dir.join("browser"));
 
         // Scan devices
         device_manager.scan()?;
         tracing::info!("Found {} devices", device_manager.list_devices()?.len());
+        device_manager.watch(DEFAULT_HOTPLUG_INTERVAL);
 
         // Mount mock filesystem
         let mock_fs = MockFileSystem::new();
         filesystem.mount("/mnt/root", mock_fs)?;
 
-        // Index some example documents
-        search_service.index_document(
-            "/mnt/root/guide.txt",
-            "LucAstra is an augmented OS with embedded LLM. It supports RAG for contextual responses.",
-        )?;
-        search_service.index_document(
-            "/mnt/root/readme.txt",
-            "LucAstra OS runs on Rust. It integrates with llamafile for 7B model inference.",
-        )?;
+        // Expose the data directory for real file I/O
+        if config.storage.use_host_fs {
+            let data_dir = config.storage.data_dir.clone();
+            let mounted = std::fs::create_dir_all(&data_dir)
+                .map_err(|e| lucastra_core::LuCastraError::FilesystemError(e.to_string()))
+                .and_then(|_| filesystem.mount("/mnt/host", HostFileSystem::new(data_dir)));
+            if let Err(e) = mounted {
+                tracing::warn!("Host filesystem not mounted: {}", e);
+            }
+        }
+
+        // Seed example documents on first boot only; later boots reuse the saved index
+        if search_service.doc_count() == 0 {
+            search_service.index_document(
+                "/mnt/root/guide.txt",
+                "LucAstra is an augmented OS with embedded LLM. It supports RAG for contextual responses.",
+            )?;
+            search_service.index_document(
+                "/mnt/root/readme.txt",
+                "LucAstra OS runs on Rust. It integrates with llamafile for 7B model inference.",
+            )?;
+            if let Err(e) = search_service.save() {
+                tracing::warn!("Failed to persist search index: {}", e);
+            }
+        }
 
-        let metrics = Metrics::new();
+        let persist_metrics = config.metrics.enabled && config.metrics.persist;
+        let metrics = if persist_metrics {
+            Metrics::persistent(config.storage.data_dir.join("metrics_state.json"))
+        } else {
+            Metrics::new()
+        };
+        let metrics_persister =
+            persist_metrics.then(|| MetricsPersister::start(metrics.clone(), &config.metrics));
+        let metrics_exporter = (config.metrics.enabled && config.metrics.export_to_file)
+            .then(|| MetricsExporter::start(metrics.clone(), &config.metrics));
+        if let Some(addr) = config.metrics.http_addr.as_deref() {
+            serve_metrics(&metrics, addr);
+        }
+        let usage = Arc::new(Mutex::new(usage_tracker(&config)));
+        let approval_ttl = Duration::from_secs(config.security.approval_ttl_secs);
+        let approvals = ApprovalBroker::new(approval_ttl);
+        let install_approvals = ApprovalBroker::new(approval_ttl).with_prefix("install");
+        let conversations = ConversationStore::new(config.storage.data_dir.join("conversations"))
+            .map(Conversations::with_store)
+            .unwrap_or_else(|e| {
+                tracing::warn!("Conversations won't be saved: {}", e);
+                Conversations::new()
+            });
 
-        Ok(Self {
+        let mut state = Self {
             config,
             service_registry,
             device_manager,
             filesystem,
             input_manager,
-            search_service,
+            search_service: Arc::new(search_service),
             llm_service,
+            browser,
             metrics,
+            usage,
+            llm_server,
+            health_monitor: None,
+            metrics_exporter,
+            _metrics_persister: metrics_persister,
+            watcher: None,
+            approvals,
+            install_approvals,
+            config_watcher: None,
+            conversations,
+            jobs: None,
             #[cfg(feature = "relibc")]
-            syscall_handler: Some(SyscallHandler::new()),
-        })
+            processes: ProcessTable::new(),
+        };
+
+        if state.config.storage.auto_index {
+            if let Err(e) = state.start_watcher() {
+                tracing::warn!("Auto-indexing disabled: {}", e);
+            }
+        }
+        state.restart_health_monitor();
+
+        Ok(state)
+    }
+
+    /// Directories crawled and watched for auto-indexing.
+    fn index_roots(&self) -> Vec<std::path::PathBuf> {
+        let mut roots = vec![self.config.storage.data_dir.clone()];
+        roots.extend(self.config.security.resolved_allowed_dirs());
+        roots.extend(
+            self.config
+                .search
+                .profile_matchers()
+                .into_iter()
+                .filter(|profile| profile.profile().enabled)
+                .map(|profile| profile.root().to_path_buf()),
+        );
+        roots
+    }
+
+    /// Indexer for the indexed directories, following the index profiles.
+    fn indexer(&self) -> Indexer {
+        Indexer::new()
+            .with_allowed_roots(self.index_roots())
+            .with_profiles(self.config.search.profile_matchers())
+            .with_extractor(Arc::new(HtmlExtractor))
+    }
+
+    /// Crawl a host file or directory inside the indexed directories into the
+    /// search index and persist it.
+    pub fn index_host_path(&mut self, path: &Path) -> lucastra_core::Result<IndexSummary> {
+        let summary = self.indexer().index_path(path, &self.search_service)?;
+        if summary.files_indexed > 0 {
+            self.search_service.save()?;
+        }
+        Ok(summary)
+    }
+
+    /// Start watching the data and allowed directories (no-op if already running).
+    pub fn start_watcher(&mut self) -> lucastra_core::Result<()> {
+        if self.watcher.is_none() {
+            self.watcher = Some(FileWatcher::start(self.index_roots())?);
+        }
+        Ok(())
+    }
+
+    /// Stop the auto-index watcher, applying any changes it already reported.
+    pub fn stop_watcher(&mut self) {
+        self.process_watch_events();
+        if let Some(mut watcher) = self.watcher.take() {
+            watcher.stop();
+        }
+    }
+
+    /// Whether the auto-index watcher is running.
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Apply settled file changes to the search index and persist it.
+    /// Returns how many changes were applied.
+    pub fn process_watch_events(&mut self) -> usize {
+        let Some(watcher) = &self.watcher else {
+            return 0;
+        };
+
+        let indexer = self.indexer();
+        let applied = watcher.apply(&self.search_service, &indexer);
+        if applied > 0 {
+            tracing::debug!("Applied {} file changes to the search index", applied);
+            if let Err(e) = self.search_service.save() {
+                tracing::warn!("Failed to persist search index: {}", e);
+            }
+        }
+        applied
+    }
+
+    /// Take hotplug events from the device manager. Volumes mounted inside the
+    /// indexed directories are indexed; removed volumes are dropped from the
+    /// search index and unmounted from the virtual filesystem.
+    pub fn process_device_events(&mut self) -> Vec<DeviceEvent> {
+        self.device_manager.poll_events();
+
+        let mut events = Vec::new();
+        let mut index_changed = false;
+        while let Some(event) = self.device_manager.next_event() {
+            let mount_point = event.device().mount_point.clone();
+            match (&event, mount_point) {
+                (DeviceEvent::Added(device), Some(mount_point))
+                    if self.config.storage.auto_index =>
+                {
+                    let indexer = self.indexer();
+                    match indexer.index_path(Path::new(&mount_point), &self.search_service) {
+                        Ok(summary) => {
+                            tracing::info!(
+                                "Indexed {} files from {}",
+                                summary.files_indexed,
+                                device.path
+                            );
+                            index_changed |= summary.files_indexed > 0;
+                        }
+                        Err(e) => tracing::debug!("Not indexing {}: {}", mount_point, e),
+                    }
+                }
+                (DeviceEvent::Removed(_), Some(mount_point)) => {
+                    index_changed |= self.search_service.remove_document(&mount_point) > 0;
+                    let mounted = self
+                        .filesystem
+                        .list_mounts()
+                        .iter()
+                        .any(|m| m.mount_point == mount_point);
+                    if mounted {
+                        if let Err(e) = self.filesystem.unmount(&mount_point) {
+                            tracing::warn!("Failed to unmount {}: {}", mount_point, e);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            events.push(event);
+        }
+
+        if index_changed {
+            if let Err(e) = self.search_service.save() {
+                tracing::warn!("Failed to persist search index: {}", e);
+            }
+        }
+        events
+    }
+
+    /// Make a block device from the device manager readable at `mount_point`.
+    /// Volumes the host OS already mounted are passed through; others are
+    /// read directly as FAT32. Requires `security.allow_usb`.
+    pub fn mount_block_device(
+        &mut self,
+        path: &str,
+        mount_point: &str,
+    ) -> lucastra_core::Result<()> {
+        self.mount_block_device_with(path, mount_point, |device| Fat32FileSystem::open(device))
+    }
+
+    /// [`SystemState::mount_block_device`], opening FAT32 volumes with `open`
+    /// rather than from the device node, e.g. to mount an in-memory image.
+    pub fn mount_block_device_with(
+        &mut self,
+        path: &str,
+        mount_point: &str,
+        open: impl FnOnce(&str) -> lucastra_core::Result<Fat32FileSystem>,
+    ) -> lucastra_core::Result<()> {
+        if !self.config.security.allow_usb {
+            return Err(LuCastraError::PermissionDenied(format!(
+                "USB access is disabled; not mounting {}",
+                path
+            )));
+        }
+
+        let device = self.device_manager.get_device(path)?;
+        if device.device_type != DeviceType::BlockDevice {
+            return Err(LuCastraError::InvalidCommand(format!(
+                "{} is not a block device",
+                path
+            )));
+        }
+
+        match device
+            .mount_point
+            .as_deref()
+            .filter(|dir| Path::new(dir).is_dir())
+        {
+            Some(host_dir) => self
+                .filesystem
+                .mount(mount_point, HostFileSystem::new(host_dir))?,
+            None => self.filesystem.mount(mount_point, open(&device.path)?)?,
+        }
+        self.device_manager.mount_device(path, mount_point)
+    }
+
+    /// Unmount the filesystem at `mount_point` and mark any device mounted
+    /// there as unmounted.
+    pub fn unmount_volume(&mut self, mount_point: &str) -> lucastra_core::Result<()> {
+        self.filesystem.unmount(mount_point)?;
+        let devices = self.device_manager.list_devices()?;
+        for device in devices
+            .iter()
+            .filter(|d| d.mount_point.as_deref() == Some(mount_point))
+        {
+            self.device_manager.unmount_device(&device.path)?;
+        }
+        Ok(())
+    }
+
+    /// Open `url` in the browser's current tab. With
+    /// `search.index_browsed_pages` on, the page is indexed for search and
+    /// RAG; returns the document id it was indexed under. Fails when
+    /// `security.allow_network` is off.
+    pub fn browse(&mut self, url: &str) -> lucastra_core::Result<Option<String>> {
+        if !self.config.security.allow_network {
+            return Err(LuCastraError::PermissionDenied(
+                "network access is disabled".to_string(),
+            ));
+        }
+        let tab = self.browser.navigate(url)?;
+        let indexed = browse::index_page(tab, &self.search_service, &self.config.search);
+        if indexed.is_some() {
+            if let Err(e) = self.search_service.save() {
+                tracing::warn!("Failed to persist search index: {}", e);
+            }
+        }
+        Ok(indexed)
     }
 
     /// Get current configuration
@@ -101,17 +578,224 @@ impl SystemState {
 
     /// Update configuration and save
     pub fn update_config(&mut self, new_config: Config) -> lucastra_core::Result<()> {
-        new_config.save().map_err(|e| {
-            lucastra_core::LuCastraError::ConfigError(format!("Failed to save config: {}", e))
-        })?;
+        new_config.save().context("saving the config")?;
 
-        self.config = new_config;
         tracing::info!("Configuration updated and saved");
+        for event in new_config.changes_since(&self.config) {
+            self.apply_config_event(&event);
+        }
         Ok(())
     }
 
-    /// Handle a command and return a response.
-    pub fn handle_command(&mut self, cmd: Command) -> lucastra_core::Result<Response> {
+    /// Apply edits to `config.toml` reported since the last call. Returns the
+    /// events that changed the running configuration, plus any rejections.
+    pub fn process_config_events(&mut self) -> Vec<ConfigEvent> {
+        let Some(watcher) = &self.config_watcher else {
+            return Vec::new();
+        };
+        watcher
+            .drain()
+            .into_iter()
+            .filter(|event| self.apply_config_event(event))
+            .collect()
+    }
+
+    /// Bring services in line with one changed config section. Returns
+    /// `false` if the section already matched (e.g. our own `update_config`).
+    fn apply_config_event(&mut self, event: &ConfigEvent) -> bool {
+        match event {
+            ConfigEvent::LlmChanged(llm) => {
+                if *llm == self.config.llm {
+                    return false;
+                }
+                let url_changed = llm.server_url != self.config.llm.server_url;
+                let interval_changed =
+                    llm.health_check_interval_secs != self.config.llm.health_check_interval_secs;
+                let placeholders_changed =
+                    llm.offline_placeholders != self.config.llm.offline_placeholders;
+                let template_changed =
+                    llm.system_prompt_template != self.config.llm.system_prompt_template;
+                if url_changed {
+                    tracing::info!("LLM server changed to {}", llm.server_url);
+                }
+                if lucastra_llm::WireLog::from_config(llm)
+                    != lucastra_llm::WireLog::from_config(&self.config.llm)
+                {
+                    lucastra_llm::wire_log::install(lucastra_llm::WireLog::from_config(llm));
+                }
+                self.config.llm = llm.clone();
+                // Restarting the monitor rebuilds the service as well
+                if url_changed || interval_changed || placeholders_changed {
+                    self.restart_health_monitor();
+                } else if template_changed {
+                    if let Some(prompt) = system_prompt(llm) {
+                        self.llm_service.set_system_prompt(prompt);
+                    }
+                }
+            }
+            ConfigEvent::StorageChanged(storage) => {
+                if *storage == self.config.storage {
+                    return false;
+                }
+                self.config.storage = storage.clone();
+                self.restart_watcher();
+            }
+            ConfigEvent::SecurityChanged(security) => {
+                if *security == self.config.security {
+                    return false;
+                }
+                self.config.security = security.clone();
+                let approval_ttl = Duration::from_secs(security.approval_ttl_secs);
+                self.approvals.set_ttl(approval_ttl);
+                self.install_approvals.set_ttl(approval_ttl);
+                // Allowed directories are watched roots too
+                self.restart_watcher();
+            }
+            ConfigEvent::TracingChanged(tracing_config) => {
+                if *tracing_config == self.config.tracing {
+                    return false;
+                }
+                if tracing_config.level != self.config.tracing.level {
+                    observability::set_log_level(&tracing_config.level);
+                }
+                self.config.tracing = tracing_config.clone();
+            }
+            ConfigEvent::SearchChanged(search) => {
+                if *search == self.config.search {
+                    return false;
+                }
+                let profiles_changed = search.profiles != self.config.search.profiles;
+                self.config.search = search.clone();
+                self.search_service
+                    .set_bm25_params(lucastra_search::Bm25Params::from_config(search));
+                self.search_service.set_max_results(search.max_results);
+                let mut index_changed = self
+                    .search_service
+                    .set_tokenizer(lucastra_search::Tokenizer::from_config(search));
+                if profiles_changed {
+                    let removed = self.indexer().remove_excluded(&self.search_service);
+                    if !removed.is_empty() {
+                        tracing::info!(
+                            "Removed {} newly excluded documents from the search index",
+                            removed.len()
+                        );
+                        index_changed = true;
+                    }
+                    self.restart_watcher();
+                }
+                if index_changed {
+                    if let Err(e) = self.search_service.save() {
+                        tracing::warn!("Failed to persist search index: {}", e);
+                    }
+                }
+            }
+            ConfigEvent::GuiChanged(gui) => {
+                if *gui == self.config.gui {
+                    return false;
+                }
+                self.config.gui = gui.clone();
+            }
+            ConfigEvent::AdvancedChanged(advanced) => {
+                if *advanced == self.config.advanced {
+                    return false;
+                }
+                self.config.advanced = advanced.clone();
+            }
+            ConfigEvent::ToolsChanged(tools) => {
+                if *tools == self.config.tools {
+                    return false;
+                }
+                self.config.tools = tools.clone();
+            }
+            ConfigEvent::MetricsChanged(metrics) => {
+                if *metrics == self.config.metrics {
+                    return false;
+                }
+                self.config.metrics = metrics.clone();
+                // metrics-export follows the export interval
+                self.reschedule_jobs();
+            }
+            ConfigEvent::ProvidersChanged(providers) => {
+                if *providers == self.config.providers {
+                    return false;
+                }
+                self.config.providers = providers.clone();
+            }
+            ConfigEvent::JobsChanged(jobs) => {
+                if *jobs == self.config.jobs {
+                    return false;
+                }
+                if jobs.enabled && self.jobs.is_none() {
+                    tracing::info!("Background jobs start on the next launch");
+                }
+                self.config.jobs = jobs.clone();
+                self.reschedule_jobs();
+            }
+            ConfigEvent::Rejected(reason) => {
+                tracing::warn!("Keeping previous configuration: {}", reason);
+            }
+        }
+        true
+    }
+
+    /// (Re)start health checks against the current LLM endpoint. Once the
+    /// server has been down for a while, an auto-started server is restarted.
+    fn restart_health_monitor(&mut self) {
+        if let Some(mut monitor) = self.health_monitor.take() {
+            monitor.stop();
+        }
+        let interval = self.config.llm.health_check_interval_secs;
+        if interval == 0 {
+            self.llm_service = llm_service(&self.config.llm);
+            return;
+        }
+
+        let probe = LLMService::new(self.config.llm.server_url.clone());
+        let metrics = self.metrics.clone();
+        let mut checker =
+            HealthChecker::new(move || probe.health_check_blocking().unwrap_or(false))
+                .with_observer(move |status| metrics.record_llm_health(status));
+        if let Some(server) = self.llm_server.clone() {
+            checker = checker.with_recovery(move || server.restart());
+        }
+
+        let monitor = HealthMonitor::start(checker, Duration::from_secs(interval));
+        self.llm_service = llm_service(&self.config.llm).with_health(monitor.handle());
+        self.health_monitor = Some(monitor);
+    }
+
+    /// Latest LLM health check, or `None` while monitoring is disabled.
+    pub fn llm_health(&self) -> Option<HealthStatus> {
+        self.health_monitor.as_ref().map(|m| m.status())
+    }
+
+    /// Restart the auto-index watcher so `auto_index` and watched directory
+    /// changes apply immediately.
+    fn restart_watcher(&mut self) {
+        self.stop_watcher();
+        if self.config.storage.auto_index {
+            if let Err(e) = self.start_watcher() {
+                tracing::warn!("Auto-indexing disabled: {}", e);
+            }
+        }
+    }
+
+    /// Handle a command and return a response. Failures are answered with
+    /// [`ResponsePayload::Error`], coded by their cause.
+    pub fn handle_command(&mut self, cmd: Command) -> Response {
+        let _span = observability::command_span(&cmd).entered();
+        let command_id = cmd.id.clone();
+        self.run_command(cmd)
+            .unwrap_or_else(|e| respond(&command_id, Err(e)))
+    }
+
+    fn run_command(&mut self, cmd: Command) -> lucastra_core::Result<Response> {
+        self.process_config_events();
+        self.process_watch_events();
+        self.process_device_events();
+        self.expire_approvals();
+        self.metrics.record_command();
+
         match &cmd.payload {
             CommandPayload::ListDevices => {
                 let devices = self.device_manager.list_devices()?;
@@ -124,47 +808,135 @@ impl SystemState {
                     payload: ResponsePayload::Devices(device_strs),
                 })
             }
-            CommandPayload::Search { query } => {
-                let results = self.search_service.search(query, 5)?;
+            CommandPayload::Search {
+                query,
+                offset,
+                limit,
+            } => {
+                let span = observability::search_span(query);
+                let limit = limit.unwrap_or(self.search_service.max_results());
+                let page =
+                    span.in_scope(|| self.search_service.search_paged(query, *offset, limit))?;
+                span.record("result_count", page.results.len());
+                self.metrics.record_search(span.finish());
                 Ok(Response {
                     command_id: cmd.id.clone(),
-                    payload: ResponsePayload::SearchResults(results),
+                    payload: ResponsePayload::SearchResults(page),
                 })
             }
-            CommandPayload::Query { text, use_rag } => {
-                let mut context = None;
-
-                // Retrieve context if RAG is enabled
-                if use_rag.unwrap_or(false) {
-                    let search_results = self.search_service.search(text, 3)?;
-                    context = Some(search_results.iter().map(|r| r.snippet.clone()).collect());
+            CommandPayload::Query {
+                text,
+                use_rag,
+                conversation_id,
+                attachments,
+            } => {
+                let (llm, request) =
+                    self.prepare_query(text, *use_rag, conversation_id.as_deref(), attachments)?;
+                let span = observability::inference_span();
+                let response = match span.in_scope(|| llm.infer_blocking(request.clone())) {
+                    Ok(response) => response,
+                    Err(LuCastraError::LlmUnavailable(reason)) => {
+                        return Ok(Response {
+                            command_id: cmd.id.clone(),
+                            payload: ResponsePayload::LlmUnavailable(reason),
+                        })
+                    }
+                    Err(e) => return Err(e),
+                };
+                self.metrics.record_llm_latency(span.finish());
+                if let Some(usage) = llm.usage(&request, &response) {
+                    self.metrics.record_llm_usage(&usage);
+                    record_usage(&self.usage, usage);
+                }
+                if let Some(id) = conversation_id {
+                    self.record_answer(id, text, &response.text);
                 }
 
-                // Call LLM
-                let response = self.llm_service.infer(lucastra_llm::InferenceRequest {
-                    prompt: text.clone(),
-                    max_tokens: Some(256),
-                    temperature: Some(0.7),
-                    context,
-                })?;
-
                 Ok(Response {
                     command_id: cmd.id.clone(),
-                    payload: ResponsePayload::Success(response.text),
+                    payload: answer_payload(&request, response),
+                })
+            }
+            CommandPayload::EndConversation { conversation_id } => {
+                self.conversations.end(conversation_id);
+                Ok(Response {
+                    command_id: cmd.id.clone(),
+                    payload: ResponsePayload::Success(format!(
+                        "Ended conversation {}",
+                        conversation_id
+                    )),
                 })
             }
             CommandPayload::Status => Ok(Response {
                 command_id: cmd.id.clone(),
                 payload: ResponsePayload::Status(format!(
-                    "LucAstra OS running. Devices: {}, Indexed docs: {}",
+                    "LucAstra OS running. Devices: {}, Indexed docs: {}, LLM: {}",
                     self.device_manager.list_devices()?.len(),
-                    self.search_service.doc_count()
+                    self.search_service.doc_count(),
+                    self.llm_health()
+                        .map(|h| h.state.to_string())
+                        .unwrap_or_else(|| "unmonitored".to_string())
                 )),
             }),
+            CommandPayload::IndexPath { path } => Ok(respond(
+                &cmd.id,
+                self.index_host_path(Path::new(path)).map(|summary| {
+                    let mut message = format!(
+                        "Indexed {} files from {} ({} skipped)",
+                        summary.files_indexed, path, summary.files_skipped
+                    );
+                    if summary.extraction_failures > 0 {
+                        message.push_str(&format!(
+                            "; could not extract text from {}",
+                            summary.extraction_failures
+                        ));
+                    }
+                    ResponsePayload::Success(message)
+                }),
+            )),
+            CommandPayload::TestLlmConnection => Ok(Response {
+                command_id: cmd.id.clone(),
+                payload: connection_payload(&self.llm_service),
+            }),
+            CommandPayload::RunJob { name } => Ok(respond(
+                &cmd.id,
+                self.run_job(name)
+                    .map(|_| ResponsePayload::Success(format!("Started job {}", name))),
+            )),
             CommandPayload::Echo { message } => Ok(Response {
                 command_id: cmd.id.clone(),
                 payload: ResponsePayload::Success(format!("Echo: {}", message)),
             }),
+            CommandPayload::ListFiles { path } => Ok(respond(
+                &cmd.id,
+                self.filesystem.list_files(path).map(ResponsePayload::Files),
+            )),
+            CommandPayload::ReadFile { path } => Ok(respond(
+                &cmd.id,
+                self.filesystem
+                    .read_file(path)
+                    .map(ResponsePayload::Content),
+            )),
+            CommandPayload::WriteFile { path, content } => Ok(respond(
+                &cmd.id,
+                self.filesystem.write_file(path, content).map(|_| {
+                    ResponsePayload::Success(format!("Wrote {} bytes to {}", content.len(), path))
+                }),
+            )),
+            CommandPayload::Mount {
+                device_path,
+                mount_point,
+            } => Ok(respond(
+                &cmd.id,
+                self.mount_block_device(device_path, mount_point).map(|_| {
+                    ResponsePayload::Success(format!("Mounted {} at {}", device_path, mount_point))
+                }),
+            )),
+            CommandPayload::Unmount { mount_point } => Ok(respond(
+                &cmd.id,
+                self.unmount_volume(mount_point)
+                    .map(|_| ResponsePayload::Success(format!("Unmounted {}", mount_point))),
+            )),
             _ => Ok(Response {
                 command_id: cmd.id.clone(),
                 payload: ResponsePayload::Success("Command not implemented".to_string()),
@@ -172,8 +944,118 @@ impl SystemState {
         }
     }
 
+    /// Build the LLM request for a query, retrieving search context when
+    /// `use_rag` is set and including the earlier turns of the named
+    /// conversation. `attachments` are chunked and come before the search
+    /// results. The returned service handle lets the caller run the slow
+    /// inference without holding on to `self`.
+    pub fn prepare_query(
+        &mut self,
+        text: &str,
+        use_rag: Option<bool>,
+        conversation_id: Option<&str>,
+        attachments: &[Attachment],
+    ) -> lucastra_core::Result<(LLMService, InferenceRequest)> {
+        let attached = attachment_sources(attachments);
+        let context = if use_rag.unwrap_or(false) {
+            let span = observability::search_span(text);
+            let options = RetrievalOptions {
+                top_k: self.search_service.max_results(),
+                min_score: self.config.search.min_rag_score,
+                token_budget: self.config.search.rag_token_budget,
+                ..RetrievalOptions::default()
+            };
+            let search_results = span.in_scope(|| self.search_service.retrieve(text, &options))?;
+            span.record("result_count", search_results.len());
+            self.metrics.record_search(span.finish());
+            Some(
+                attached
+                    .into_iter()
+                    .chain(search_results.iter().map(SourceRef::from))
+                    .collect(),
+            )
+        } else if !attached.is_empty() {
+            Some(attached)
+        } else {
+            None
+        };
+
+        let request = InferenceRequest {
+            prompt: text.to_string(),
+            max_tokens: Some(256),
+            temperature: Some(0.7),
+            context,
+            history: conversation_id
+                .map(|id| self.conversations.history(id))
+                .unwrap_or_default(),
+        };
+        Ok((self.llm_service.clone(), request))
+    }
+
+    /// Add a query and its answer to conversation `conversation_id`,
+    /// starting it if it's new.
+    pub fn record_answer(&mut self, conversation_id: &str, question: &str, answer: &str) {
+        self.conversations.record(
+            conversation_id,
+            question,
+            answer,
+            self.config.llm.context_messages,
+        );
+    }
+
+    /// Stop keeping conversation `conversation_id` in memory, e.g. when
+    /// its tab is closed. Its saved copy stays for
+    /// [`SystemState::restore_conversation`].
+    pub fn close_conversation(&mut self, conversation_id: &str) {
+        self.conversations.close(conversation_id);
+    }
+
+    /// Continue a closed conversation from its saved copy, returning its
+    /// turns so they can be shown again.
+    pub fn restore_conversation(
+        &mut self,
+        conversation_id: &str,
+    ) -> lucastra_llm::ConversationResult<Vec<Message>> {
+        self.conversations.restore(conversation_id)
+    }
+
+    /// Saved conversations whose ids start with `prefix`, most recently
+    /// updated first.
+    pub fn saved_conversations(&self, prefix: &str) -> Vec<ConversationSummary> {
+        self.conversations.saved(prefix)
+    }
+
     /// Execute a tool (for agentic tasks).
-    pub fn execute_tool(&self, tool: Tool) -> ToolResult {
+    ///
+    /// With `security.enable_rbac` on, tools the configured role may not use
+    /// are rejected and the denial is audited.
+    pub fn execute_tool(&mut self, tool: Tool) -> ToolResult {
+        let span = observability::tool_span(tool.name());
+        let result = span.in_scope(|| self.run_tool(tool));
+        span.record("success", result.success);
+        span.finish();
+        result
+    }
+
+    fn run_tool(&mut self, tool: Tool) -> ToolResult {
+        self.expire_approvals();
+
+        if self.config.security.enable_rbac {
+            let policy = PermissionPolicy::from_config(&self.config.security);
+            if let Err(e) = policy.check(&tool) {
+                tracing::warn!("Denied {} tool: {}", tool.name(), e);
+                match self.audit_log() {
+                    Ok(log) => {
+                        if let Err(err) = log.append(&policy.denial_entry(&tool, e.to_string())) {
+                            tracing::warn!("Failed to audit denied {} tool: {}", tool.name(), err);
+                        }
+                    }
+                    Err(err) => tracing::warn!("Unable to resolve audit log dir: {}", err),
+                }
+                return ToolResult::failure("permission_denied", e.to_string());
+            }
+        }
+
         match tool {
             Tool::Search { query, top_k } => {
                 let search_tool = SearchTool::new(&self.search_service);
@@ -181,16 +1063,54 @@ impl SystemState {
                     .execute(&query, top_k.unwrap_or(5))
                     .unwrap_or_else(|e| ToolResult::failure("search", e.to_string()))
             }
-            Tool::Read { path } => {
-                let read_tool = ReadTool::new(&self.filesystem);
+            Tool::Read {
+                path,
+                offset,
+                length,
+            } => {
+                let read_tool = ReadTool::new(&self.filesystem)
+                    .with_max_bytes(self.config.tools.max_read_bytes);
                 read_tool
-                    .execute(&path)
+                    .execute(&path, offset, length)
                     .unwrap_or_else(|e| ToolResult::failure("read", e.to_string()))
             }
-            Tool::Install { program, method } => {
-                let install_tool = InstallTool::new();
+            Tool::Write {
+                path,
+                content,
+                append,
+            } => {
+                let sandboxed = self.config.security.enable_sandboxing;
+                let mut write_tool = WriteTool::new(&mut self.filesystem, sandboxed);
+                write_tool
+                    .execute(&path, &content, append)
+                    .unwrap_or_else(|e| ToolResult::failure("write", e.to_string()))
+            }
+            Tool::Delete { path } => {
+                let sandboxed = self.config.security.enable_sandboxing;
+                let mut delete_tool = DeleteTool::new(&mut self.filesystem, sandboxed);
+                delete_tool
+                    .execute(&path)
+                    .unwrap_or_else(|e| ToolResult::failure("delete", e.to_string()))
+            }
+            Tool::Install {
+                program,
+                method,
+                dry_run,
+            } => {
+                let install_tool = self.install_tool();
+                // Downloaded installers only run once the user approves them
+                if matches!(method, InstallMethod::Download { .. }) && !dry_run {
+                    if let Err(reason) = install_tool.plan(&program, &method) {
+                        return ToolResult::failure("install", reason);
+                    }
+                    let request = InstallRequest { program, method };
+                    let message = format!("{} awaits user approval", request);
+                    let token = self.install_approvals.queue(request);
+                    tracing::info!("Queued install for approval ({})", token);
+                    return ToolResult::pending("install", token, message);
+                }
                 install_tool
-                    .execute(&program, &method)
+                    .execute(&program, &method, dry_run)
                     .unwrap_or_else(|e| ToolResult::failure("install", e.to_string()))
             }
             Tool::HostFileAccess {
@@ -198,46 +1118,237 @@ impl SystemState {
                 path,
                 dest_path,
             } => {
-                let validator = FileAccessValidator::new(
-                    self.config.security.resolved_allowed_dirs(),
-                    self.config.security.allow_host_read,
-                    self.config.security.allow_host_write,
-                    self.config.security.allow_usb,
-                );
-
-                let audit_path = match lucastra_config::get_logs_dir() {
-                    Ok(dir) => dir.join("file_access_audit.log"),
-                    Err(e) => {
-                        return ToolResult::failure(
-                            "host_file_access",
-                            format!("Unable to resolve audit log dir: {}", e),
-                        )
-                    }
+                let tool = match self.file_access_tool() {
+                    Ok(tool) => tool,
+                    Err(result) => return result,
                 };
+                let path = PathBuf::from(path);
+                let dest_path = dest_path.map(PathBuf::from);
+
+                // Only ask for consent when the operation could actually run
+                let needs_approval = self.config.security.require_write_approval
+                    && ApprovalBroker::needs_approval(operation)
+                    && tool.validator().validate_path(&path, operation).is_ok();
+                if !needs_approval {
+                    return tool.execute(operation, &path, dest_path.as_deref());
+                }
 
-                let tool = FileAccessTool::new(validator, audit_path);
-                tool.execute(
+                let message = format!("{} {} awaits user approval", operation, path.display());
+                let token = self.approvals.queue(HostFileAccessRequest {
                     operation,
-                    Path::new(&path),
-                    dest_path.as_deref().map(Path::new),
+                    path,
+                    dest_path,
+                    requires_approval: true,
+                });
+                tracing::info!("Queued {} for approval ({})", operation, token);
+                ToolResult::pending("host_file_access", token, message)
+            }
+            Tool::Calculate { expression } => CalculatorTool::new()
+                .execute(&expression)
+                .unwrap_or_else(|e| ToolResult::failure("calculate", e.to_string())),
+            Tool::Fetch { url, max_bytes } => FetchTool::new(&self.config.security)
+                .execute(&url, max_bytes)
+                .unwrap_or_else(|e| ToolResult::failure("fetch", e.to_string())),
+        }
+    }
+
+    /// Install tool built from the current security config.
+    fn install_tool(&self) -> InstallTool {
+        InstallTool::new(self.config.security.allowed_installers.clone())
+            .with_download_hosts(self.config.security.allowed_download_hosts.clone())
+    }
+
+    /// Host file access tool built from the current security config.
+    fn file_access_tool(&self) -> Result<FileAccessTool, ToolResult> {
+        let validator = FileAccessValidator::new(
+            self.config.security.resolved_allowed_dirs(),
+            self.config.security.allow_host_read,
+            self.config.security.allow_host_write,
+            self.config.security.allow_usb,
+        );
+
+        let audit_log = self.audit_log().map_err(|e| {
+            ToolResult::failure(
+                "host_file_access",
+                format!("Unable to resolve audit log dir: {}", e),
+            )
+        })?;
+
+        Ok(
+            FileAccessTool::new(validator, audit_log.path().to_path_buf())
+                .with_audit_max_bytes(self.audit_log_max_bytes()),
+        )
+    }
+
+    /// The JSONL audit log shared by host file access and permission denials.
+    fn audit_log(&self) -> lucastra_core::Result<AuditLog> {
+        let path = lucastra_config::get_logs_dir()?.join("file_access_audit.log");
+        Ok(AuditLog::new(path).with_max_bytes(self.audit_log_max_bytes()))
+    }
+
+    fn audit_log_max_bytes(&self) -> u64 {
+        self.config.security.audit_log_max_mb * 1024 * 1024
+    }
+
+    /// Host file `path` as an attachment for a query. It is checked against
+    /// the host file whitelist and audited like the file access tool, and
+    /// cut off at `tools.max_read_bytes`.
+    pub fn read_attachment(&self, path: &Path) -> lucastra_core::Result<Attachment> {
+        let tool = self
+            .file_access_tool()
+            .map_err(|result| LuCastraError::ServiceError(result.output))?;
+        let (mut text, size) = tool
+            .read_text(path, self.config.tools.max_read_bytes)
+            .map_err(|e| match e {
+                FileAccessError::NotWhitelisted(_)
+                | FileAccessError::PermissionDenied
+                | FileAccessError::UsbNotAllowed => LuCastraError::PermissionDenied(e.to_string()),
+                e => LuCastraError::FilesystemError(e.to_string()),
+            })?;
+        if (text.len() as u64) < size {
+            text.push_str(&format!(
+                "\n[cut off after {} of {} bytes]",
+                text.len(),
+                size
+            ));
+        }
+        Ok(Attachment {
+            name: path.display().to_string(),
+            text,
+        })
+    }
+
+    /// Host file operations waiting for approval, oldest first.
+    pub fn pending_approvals(&self) -> Vec<(String, HostFileAccessRequest)> {
+        self.approvals.pending()
+    }
+
+    /// Installer downloads waiting for approval, oldest first.
+    pub fn pending_installs(&self) -> Vec<(String, InstallRequest)> {
+        self.install_approvals.pending()
+    }
+
+    /// Run the operation or install queued under `token`.
+    pub fn approve(&mut self, token: &str) -> ToolResult {
+        if self.install_approvals.contains(token) {
+            return match self.install_approvals.take(token) {
+                Ok(Approval::Valid(request)) => self
+                    .install_tool()
+                    .execute(&request.program, &request.method, false)
+                    .unwrap_or_else(|e| ToolResult::failure("install", e.to_string())),
+                Ok(Approval::Expired(_)) => {
+                    ToolResult::failure("install", format!("Approval {} expired", token))
+                }
+                Err(e) => ToolResult::failure("install", e.to_string()),
+            };
+        }
+
+        let tool = match self.file_access_tool() {
+            Ok(tool) => tool,
+            Err(result) => return result,
+        };
+
+        match self.approvals.take(token) {
+            Ok(Approval::Valid(request)) => tool.execute(
+                request.operation,
+                &request.path,
+                request.dest_path.as_deref(),
+            ),
+            Ok(Approval::Expired(request)) => {
+                tool.record_rejected(&request, "approval expired");
+                ToolResult::failure("host_file_access", format!("Approval {} expired", token))
+            }
+            Err(e) => ToolResult::failure("host_file_access", e.to_string()),
+        }
+    }
+
+    /// Cancel the operation or install queued under `token`.
+    pub fn deny(&mut self, token: &str) -> ToolResult {
+        if self.install_approvals.contains(token) {
+            return match self.install_approvals.take(token) {
+                Ok(Approval::Valid(request) | Approval::Expired(request)) => {
+                    tracing::info!("Denied {}", request);
+                    ToolResult::success("install", format!("{} cancelled", request))
+                }
+                Err(e) => ToolResult::failure("install", e.to_string()),
+            };
+        }
+
+        let tool = match self.file_access_tool() {
+            Ok(tool) => tool,
+            Err(result) => return result,
+        };
+
+        match self.approvals.take(token) {
+            Ok(Approval::Valid(request) | Approval::Expired(request)) => {
+                tool.record_rejected(&request, "denied by user");
+                ToolResult::success(
+                    "host_file_access",
+                    format!("{} {} cancelled", request.operation, request.path.display()),
                 )
             }
+            Err(e) => ToolResult::failure("host_file_access", e.to_string()),
+        }
+    }
+
+    /// Drop approvals past their TTL, auditing each. Returns how many expired.
+    pub fn expire_approvals(&mut self) -> usize {
+        let installs = self.install_approvals.drain_expired();
+        for request in &installs {
+            tracing::info!("Approval expired: {}", request);
+        }
+
+        let expired = self.approvals.drain_expired();
+        if expired.is_empty() {
+            return installs.len();
+        }
+
+        match self.file_access_tool() {
+            Ok(tool) => {
+                for request in &expired {
+                    tool.record_rejected(request, "approval expired");
+                }
+            }
+            Err(result) => tracing::warn!("Expired approvals not audited: {}", result.output),
         }
+        installs.len() + expired.len()
     }
 
     /// Parse and execute tools from LLM JSON output.
-    pub fn execute_tools_from_json(&self, json_str: &str) -> Vec<ToolResult> {
-        let tools: Result<Vec<Tool>, _> = serde_json::from_str(json_str);
-
-        match tools {
-            Ok(tools) => tools
-                .iter()
-                .map(|tool| self.execute_tool(tool.clone()))
-                .collect(),
-            Err(e) => vec![ToolResult::failure(
+    pub fn execute_tools_from_json(&mut self, json_str: &str) -> Vec<ToolResult> {
+        let parsed = ToolCallParser::new().parse(json_str);
+        self.execute_parsed_tools(&parsed)
+    }
+
+    /// Run the valid calls in `parsed` in order. Calls that failed to parse
+    /// get a `parse` failure result in their place.
+    pub fn execute_parsed_tools(&mut self, parsed: &ParsedToolCalls) -> Vec<ToolResult> {
+        if let Some(error) = &parsed.error {
+            return vec![ToolResult::failure(
                 "parse",
-                format!("Failed to parse tools: {}", e),
-            )],
+                format!("Failed to parse tools: {}", error),
+            )];
+        }
+        parsed
+            .calls
+            .iter()
+            .map(|call| match &call.tool {
+                Ok(tool) => self.execute_tool(tool.clone()),
+                Err(e) => ToolResult::failure("parse", format!("{}: {}", call.label(), e)),
+            })
+            .collect()
+    }
+}
+
+impl Drop for SystemState {
+    /// Stop services in reverse start order, killing an auto-started LLM server.
+    fn drop(&mut self) {
+        // Stop health checks first so they can't restart the server we stop
+        if let Some(mut monitor) = self.health_monitor.take() {
+            monitor.stop();
+        }
+        if let Err(e) = self.service_registry.stop_all() {
+            tracing::warn!("Failed to stop services: {}", e);
         }
     }
 }
@@ -247,3 +1358,74 @@ impl Default for SystemState {
         Self::new().expect("Failed to initialize system state")
     }
 }
+
+/// Response for a command whose failure should be reported to the caller
+/// rather than returned as an `Err`.
+fn respond(command_id: &str, result: lucastra_core::Result<ResponsePayload>) -> Response {
+    let payload = result.unwrap_or_else(|e| {
+        tracing::warn!("Command {} failed: {}", command_id, e);
+        ResponsePayload::error(&e)
+    });
+    Response {
+        command_id: command_id.to_string(),
+        payload,
+    }
+}
+
+/// Reply for [`CommandPayload::TestLlmConnection`], probing `llm` now.
+pub(crate) fn connection_payload(llm: &LLMService) -> ResponsePayload {
+    match llm.health_check_blocking() {
+        Ok(true) => {
+            ResponsePayload::Success(format!("LLM server at {} is reachable", llm.endpoint()))
+        }
+        Ok(false) => ResponsePayload::Error {
+            code: ErrorCode::LlmUnavailable,
+            message: format!(
+                "LLM server at {} didn't pass its health check",
+                llm.endpoint()
+            ),
+        },
+        Err(e) => ResponsePayload::error(&e.context(format!(
+            "Couldn't check the LLM server at {}",
+            llm.endpoint()
+        ))),
+    }
+}
+
+/// Sources for `attachments`, one per chunk, labelled with the part when an
+/// attachment is split.
+fn attachment_sources(attachments: &[Attachment]) -> Vec<SourceRef> {
+    let chunker = Chunker::default();
+    let mut sources = Vec::new();
+    for attachment in attachments {
+        let chunks = chunker.chunk(&attachment.text);
+        let parts = chunks.len();
+        sources.extend(chunks.into_iter().enumerate().map(|(i, chunk)| SourceRef {
+            path: if parts > 1 {
+                format!("{} (part {} of {})", attachment.name, i + 1, parts)
+            } else {
+                attachment.name.clone()
+            },
+            score: 1.0,
+            snippet: chunk.text,
+            origin: SourceOrigin::Attachment,
+        }));
+    }
+    sources
+}
+
+/// Reply for an LLM answer; queries that asked for search context report
+/// its sources and whether any context was found.
+pub(crate) fn answer_payload(
+    request: &InferenceRequest,
+    response: InferenceResponse,
+) -> ResponsePayload {
+    match &request.context {
+        None => ResponsePayload::Success(response.text),
+        Some(context) => ResponsePayload::RagAnswer {
+            text: response.text,
+            sources: response.sources,
+            used_context: !context.is_empty(),
+        },
+    }
+}
@@ -1,34 +1,12 @@
-use lucastra_app::SystemState;
+use lucastra_app::{observability::init_tracing, SystemState};
 use lucastra_core::{Command, CommandPayload};
 use lucastra_kernel::KernelConfig;
 use tracing::info;
-use tracing_subscriber::layer::SubscriberExt;
 
 fn main() -> lucastra_core::Result<()> {
-    // Initialize rotating file logging
-    let logs_dir =
-        lucastra_config::get_logs_dir().unwrap_or_else(|_| std::path::PathBuf::from("./logs"));
-
-    std::fs::create_dir_all(&logs_dir).ok();
-
-    let file_appender = tracing_appender::rolling::daily(logs_dir, "lucastra.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
-
-    let file_layer = tracing_subscriber::fmt::layer()
-        .with_writer(non_blocking)
-        .with_ansi(false);
-
-    let stdout_layer = tracing_subscriber::fmt::layer().with_writer(std::io::stdout);
-
-    let subscriber = tracing_subscriber::registry()
-        .with(
-            tracing_subscriber::EnvFilter::from_default_env()
-                .add_directive(tracing_subscriber::filter::LevelFilter::INFO.into()),
-        )
-        .with(file_layer)
-        .with(stdout_layer);
-
-    tracing::subscriber::set_global_default(subscriber).map_err(|e| {
+    // Initialize logging as configured; SystemState reports config errors later
+    let config = lucastra_config::Config::load().unwrap_or_default();
+    let _guard = init_tracing(&config.tracing, "lucastra.log").map_err(|e| {
         lucastra_core::LuCastraError::ServiceError(format!("Failed to set logger: {}", e))
     })?;
 
@@ -50,12 +28,13 @@ fn main() -> lucastra_core::Result<()> {
 
     // Check LLM health
     info!("Checking LLM server health...");
-    match state.llm_service.health_check() {
+    match state.llm_service.health_check_blocking() {
         Ok(true) => info!("LLM server is online"),
-        Ok(false) => {
-            info!("LLM server is unreachable (expected if not running; will use mock responses)")
+        Ok(false) if state.get_config().llm.offline_placeholders => {
+            info!("LLM server is unreachable; queries will get placeholder answers")
         }
-        Err(e) => info!("LLM health check error (expected; will use mock): {}", e),
+        Ok(false) => info!("LLM server is unreachable; queries will report it unavailable"),
+        Err(e) => info!("LLM health check error: {}", e),
     }
 
     // Simulate a command loop (simplified for MVP)
@@ -66,7 +45,7 @@ fn main() -> lucastra_core::Result<()> {
         id: "cmd-1".to_string(),
         payload: CommandPayload::ListDevices,
     };
-    let response = state.handle_command(cmd)?;
+    let response = state.handle_command(cmd);
     info!("Response: {:?}", response);
 
     // Example 2: Search
@@ -74,9 +53,11 @@ fn main() -> lucastra_core::Result<()> {
         id: "cmd-2".to_string(),
         payload: CommandPayload::Search {
             query: "LucAstra OS".to_string(),
+            offset: 0,
+            limit: None,
         },
     };
-    let response = state.handle_command(cmd)?;
+    let response = state.handle_command(cmd);
     info!("Response: {:?}", response);
 
     // Example 3: Query with RAG
@@ -85,9 +66,11 @@ fn main() -> lucastra_core::Result<()> {
         payload: CommandPayload::Query {
             text: "What is LucAstra?".to_string(),
             use_rag: Some(true),
+            conversation_id: None,
+            attachments: Vec::new(),
         },
     };
-    let response = state.handle_command(cmd)?;
+    let response = state.handle_command(cmd);
     info!("Response: {:?}", response);
 
     // Example 4: Echo
@@ -97,7 +80,7 @@ fn main() -> lucastra_core::Result<()> {
             message: "Hello from LucAstra!".to_string(),
         },
     };
-    let response = state.handle_command(cmd)?;
+    let response = state.handle_command(cmd);
     info!("Response: {:?}", response);
 
     info!("=== Boot Complete ===");
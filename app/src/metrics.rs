@@ -1,7 +1,71 @@
-use serde::Serialize;
+use lucastra_core::compat;
+use lucastra_llm::{HealthState, HealthStatus, UsageRecord};
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Version of `metrics_state.json`.
+pub const METRICS_STATE_VERSION: u32 = 1;
+
+/// Latency samples kept per operation for percentiles.
+const RESERVOIR_SIZE: usize = 1024;
+
+/// The most recent [`RESERVOIR_SIZE`] latencies of one operation.
+struct LatencyReservoir {
+    samples: Vec<u64>,
+    /// Slot the next sample overwrites once the reservoir is full.
+    next: usize,
+}
+
+impl LatencyReservoir {
+    fn new() -> Self {
+        Self {
+            samples: Vec::with_capacity(RESERVOIR_SIZE),
+            next: 0,
+        }
+    }
+
+    fn record(&mut self, latency_ms: u64) {
+        if self.samples.len() < RESERVOIR_SIZE {
+            self.samples.push(latency_ms);
+        } else {
+            self.samples[self.next] = latency_ms;
+        }
+        self.next = (self.next + 1) % RESERVOIR_SIZE;
+    }
+
+    fn summary(&self) -> LatencySummary {
+        let mut sorted = self.samples.clone();
+        sorted.sort_unstable();
+        // Nearest-rank percentile
+        let percentile = |p: usize| {
+            let rank = (sorted.len() * p).div_ceil(100).max(1);
+            sorted.get(rank - 1).copied().unwrap_or(0)
+        };
+        LatencySummary {
+            samples: sorted.len() as u64,
+            p50_ms: percentile(50),
+            p95_ms: percentile(95),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.samples.clear();
+        self.next = 0;
+    }
+}
+
+/// Latency percentiles over recent samples.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LatencySummary {
+    pub samples: u64,
+    pub p50_ms: u64,
+    pub p95_ms: u64,
+}
 
 /// Metrics collector for system observability
 #[derive(Clone)]
@@ -16,9 +80,109 @@ struct MetricsInner {
     search_queries: AtomicU64,
     total_search_latency_ms: AtomicU64,
     app_startup_time_ms: AtomicU64,
+    llm_requests: AtomicU64,
+    llm_tokens: AtomicU64,
+    llm_estimated_requests: AtomicU64,
+    /// Millionths of a US dollar, so it fits an atomic integer.
+    llm_cost_micro_usd: AtomicU64,
+    search_latency: Mutex<LatencyReservoir>,
+    llm_latency: Mutex<LatencyReservoir>,
+    llm_health: Mutex<HealthStatus>,
     custom_counters: std::sync::Mutex<HashMap<String, u64>>,
+    /// Totals of earlier sessions, loaded from `state_path`.
+    previous: Mutex<MetricsTotals>,
+    state_path: Option<PathBuf>,
+}
+
+/// Counters summed over every session that persisted its metrics.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct MetricsTotals {
+    #[serde(default)]
+    pub sessions: u64,
+    #[serde(default)]
+    pub command_count: u64,
+    #[serde(default)]
+    pub tool_success_count: u64,
+    #[serde(default)]
+    pub tool_failure_count: u64,
+    #[serde(default)]
+    pub search_queries: u64,
+    #[serde(default)]
+    pub total_search_latency_ms: u64,
+    #[serde(default)]
+    pub llm_requests: u64,
+    #[serde(default)]
+    pub llm_tokens: u64,
+    #[serde(default)]
+    pub llm_estimated_requests: u64,
+    /// Millionths of a US dollar.
+    #[serde(default)]
+    pub llm_cost_micro_usd: u64,
 }
 
+impl MetricsTotals {
+    /// Counter by counter, the larger of the two. Saved totals only grow,
+    /// so this never loses counts another session already wrote.
+    fn max(self, other: Self) -> Self {
+        Self {
+            sessions: self.sessions.max(other.sessions),
+            command_count: self.command_count.max(other.command_count),
+            tool_success_count: self.tool_success_count.max(other.tool_success_count),
+            tool_failure_count: self.tool_failure_count.max(other.tool_failure_count),
+            search_queries: self.search_queries.max(other.search_queries),
+            total_search_latency_ms: self
+                .total_search_latency_ms
+                .max(other.total_search_latency_ms),
+            llm_requests: self.llm_requests.max(other.llm_requests),
+            llm_tokens: self.llm_tokens.max(other.llm_tokens),
+            llm_estimated_requests: self
+                .llm_estimated_requests
+                .max(other.llm_estimated_requests),
+            llm_cost_micro_usd: self.llm_cost_micro_usd.max(other.llm_cost_micro_usd),
+        }
+    }
+
+    fn add(self, other: Self) -> Self {
+        Self {
+            sessions: self.sessions + other.sessions,
+            command_count: self.command_count + other.command_count,
+            tool_success_count: self.tool_success_count + other.tool_success_count,
+            tool_failure_count: self.tool_failure_count + other.tool_failure_count,
+            search_queries: self.search_queries + other.search_queries,
+            total_search_latency_ms: self.total_search_latency_ms + other.total_search_latency_ms,
+            llm_requests: self.llm_requests + other.llm_requests,
+            llm_tokens: self.llm_tokens + other.llm_tokens,
+            llm_estimated_requests: self.llm_estimated_requests + other.llm_estimated_requests,
+            llm_cost_micro_usd: self.llm_cost_micro_usd + other.llm_cost_micro_usd,
+        }
+    }
+
+    pub fn llm_cost_usd(&self) -> f64 {
+        self.llm_cost_micro_usd as f64 / 1e6
+    }
+
+    /// Tool success rate as a percentage (0-100)
+    pub fn tool_success_rate(&self) -> f64 {
+        let total = self.tool_success_count + self.tool_failure_count;
+        if total == 0 {
+            0.0
+        } else {
+            self.tool_success_count as f64 * 100.0 / total as f64
+        }
+    }
+}
+
+/// Contents of `metrics_state.json`.
+#[derive(Serialize, Deserialize)]
+struct MetricsState {
+    schema_version: u32,
+    /// Unix time of the last save.
+    updated_at: u64,
+    totals: MetricsTotals,
+}
+
+/// Counters of the current session, which start at zero every launch, and
+/// `lifetime` totals that include earlier sessions when metrics persist.
 #[derive(Debug, Clone, Serialize)]
 pub struct MetricsSnapshot {
     pub command_count: u64,
@@ -27,11 +191,48 @@ pub struct MetricsSnapshot {
     pub search_queries: u64,
     pub average_search_latency_ms: u64,
     pub app_startup_time_ms: u64,
+    pub llm_requests: u64,
+    pub llm_tokens: u64,
+    /// LLM requests whose token counts were estimated.
+    pub llm_estimated_requests: u64,
+    pub llm_cost_usd: f64,
+    pub search_latency: LatencySummary,
+    pub llm_latency: LatencySummary,
+    pub llm_health: HealthStatus,
+    pub lifetime: MetricsTotals,
 }
 
 impl Metrics {
     /// Create a new metrics collector
     pub fn new() -> Self {
+        Self::with_state(MetricsTotals::default(), None)
+    }
+
+    /// Metrics whose lifetime totals are kept in `path`: earlier totals are
+    /// loaded now and [`Metrics::persist`] adds this session's. A file that
+    /// can't be read is moved aside as `<name>.corrupt-<unix time>` and
+    /// totals start over.
+    pub fn persistent(path: PathBuf) -> Self {
+        let mut previous = match read_state(&path) {
+            Ok(totals) => totals.unwrap_or_default(),
+            Err(e) => {
+                let aside = path.with_extension(format!("json.corrupt-{}", unix_now()));
+                tracing::warn!(
+                    "Starting metrics totals over: {}; moved the old file to {}",
+                    e,
+                    aside.display()
+                );
+                if let Err(e) = std::fs::rename(&path, &aside) {
+                    tracing::warn!("Could not move {} aside: {}", path.display(), e);
+                }
+                MetricsTotals::default()
+            }
+        };
+        previous.sessions += 1;
+        Self::with_state(previous, Some(path))
+    }
+
+    fn with_state(previous: MetricsTotals, state_path: Option<PathBuf>) -> Self {
         Metrics {
             inner: Arc::new(MetricsInner {
                 command_count: AtomicU64::new(0),
@@ -40,11 +241,75 @@ impl Metrics {
                 search_queries: AtomicU64::new(0),
                 total_search_latency_ms: AtomicU64::new(0),
                 app_startup_time_ms: AtomicU64::new(0),
+                llm_requests: AtomicU64::new(0),
+                llm_tokens: AtomicU64::new(0),
+                llm_estimated_requests: AtomicU64::new(0),
+                llm_cost_micro_usd: AtomicU64::new(0),
+                search_latency: Mutex::new(LatencyReservoir::new()),
+                llm_latency: Mutex::new(LatencyReservoir::new()),
+                llm_health: Mutex::new(HealthStatus::default()),
                 custom_counters: std::sync::Mutex::new(HashMap::new()),
+                previous: Mutex::new(previous),
+                state_path,
             }),
         }
     }
 
+    /// File the lifetime totals are kept in, if they persist.
+    pub fn state_path(&self) -> Option<&Path> {
+        self.inner.state_path.as_deref()
+    }
+
+    /// Write the lifetime totals to the state file, if there is one. The
+    /// file is replaced atomically, and counters already saved higher (by
+    /// another instance) are kept rather than lowered.
+    pub fn persist(&self) -> std::io::Result<()> {
+        let Some(path) = &self.inner.state_path else {
+            return Ok(());
+        };
+        // A corrupt file was moved aside at startup; one corrupted since is
+        // simply overwritten
+        let saved = read_state(path).ok().flatten().unwrap_or_default();
+        let state = MetricsState {
+            schema_version: METRICS_STATE_VERSION,
+            updated_at: unix_now(),
+            totals: self.lifetime().max(saved),
+        };
+
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let temp = path.with_extension("json.tmp");
+        std::fs::write(&temp, serde_json::to_string_pretty(&state)?)?;
+        std::fs::rename(&temp, path)
+    }
+
+    /// This session's counters.
+    fn session(&self) -> MetricsTotals {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        MetricsTotals {
+            sessions: 0,
+            command_count: load(&self.inner.command_count),
+            tool_success_count: load(&self.inner.tool_success_count),
+            tool_failure_count: load(&self.inner.tool_failure_count),
+            search_queries: load(&self.inner.search_queries),
+            total_search_latency_ms: load(&self.inner.total_search_latency_ms),
+            llm_requests: load(&self.inner.llm_requests),
+            llm_tokens: load(&self.inner.llm_tokens),
+            llm_estimated_requests: load(&self.inner.llm_estimated_requests),
+            llm_cost_micro_usd: load(&self.inner.llm_cost_micro_usd),
+        }
+    }
+
+    /// Earlier sessions' totals plus this one's.
+    pub fn lifetime(&self) -> MetricsTotals {
+        let previous = self.inner.previous.lock().map(|p| *p).unwrap_or_default();
+        let mut lifetime = previous.add(self.session());
+        // A collector that doesn't persist still counts its own session
+        lifetime.sessions = lifetime.sessions.max(1);
+        lifetime
+    }
+
     /// Record a command execution
     pub fn record_command(&self) {
         self.inner.command_count.fetch_add(1, Ordering::Relaxed);
@@ -70,6 +335,16 @@ impl Metrics {
         self.inner
             .total_search_latency_ms
             .fetch_add(latency_ms, Ordering::Relaxed);
+        if let Ok(mut reservoir) = self.inner.search_latency.lock() {
+            reservoir.record(latency_ms);
+        }
+    }
+
+    /// Record how long an LLM call took
+    pub fn record_llm_latency(&self, latency_ms: u64) {
+        if let Ok(mut reservoir) = self.inner.llm_latency.lock() {
+            reservoir.record(latency_ms);
+        }
     }
 
     /// Record app startup time
@@ -79,6 +354,29 @@ impl Metrics {
             .store(startup_ms, Ordering::Relaxed);
     }
 
+    /// Record the tokens and cost of one LLM request
+    pub fn record_llm_usage(&self, usage: &UsageRecord) {
+        self.inner.llm_requests.fetch_add(1, Ordering::Relaxed);
+        self.inner
+            .llm_tokens
+            .fetch_add(usage.input_tokens + usage.output_tokens, Ordering::Relaxed);
+        if usage.estimated {
+            self.inner
+                .llm_estimated_requests
+                .fetch_add(1, Ordering::Relaxed);
+        }
+        self.inner
+            .llm_cost_micro_usd
+            .fetch_add((usage.cost_usd * 1e6).round() as u64, Ordering::Relaxed);
+    }
+
+    /// Record the latest LLM health check
+    pub fn record_llm_health(&self, status: &HealthStatus) {
+        if let Ok(mut health) = self.inner.llm_health.lock() {
+            *health = status.clone();
+        }
+    }
+
     /// Get a snapshot of current metrics
     pub fn snapshot(&self) -> MetricsSnapshot {
         let command_count = self.inner.command_count.load(Ordering::Relaxed);
@@ -88,11 +386,9 @@ impl Metrics {
         let total_search_latency_ms = self.inner.total_search_latency_ms.load(Ordering::Relaxed);
         let app_startup_time_ms = self.inner.app_startup_time_ms.load(Ordering::Relaxed);
 
-        let average_search_latency_ms = if search_queries > 0 {
-            total_search_latency_ms / search_queries
-        } else {
-            0
-        };
+        let average_search_latency_ms = total_search_latency_ms
+            .checked_div(search_queries)
+            .unwrap_or(0);
 
         MetricsSnapshot {
             command_count,
@@ -101,10 +397,24 @@ impl Metrics {
             search_queries,
             average_search_latency_ms,
             app_startup_time_ms,
+            llm_requests: self.inner.llm_requests.load(Ordering::Relaxed),
+            llm_tokens: self.inner.llm_tokens.load(Ordering::Relaxed),
+            llm_estimated_requests: self.inner.llm_estimated_requests.load(Ordering::Relaxed),
+            llm_cost_usd: self.inner.llm_cost_micro_usd.load(Ordering::Relaxed) as f64 / 1e6,
+            search_latency: latency_summary(&self.inner.search_latency),
+            llm_latency: latency_summary(&self.inner.llm_latency),
+            llm_health: self
+                .inner
+                .llm_health
+                .lock()
+                .map(|h| h.clone())
+                .unwrap_or_default(),
+            lifetime: self.lifetime(),
         }
     }
 
-    /// Reset all metrics to zero
+    /// Reset this session's metrics to zero. Lifetime totals keep what
+    /// earlier sessions counted.
     pub fn reset(&self) {
         self.inner.command_count.store(0, Ordering::Relaxed);
         self.inner.tool_success_count.store(0, Ordering::Relaxed);
@@ -114,6 +424,14 @@ impl Metrics {
             .total_search_latency_ms
             .store(0, Ordering::Relaxed);
         self.inner.app_startup_time_ms.store(0, Ordering::Relaxed);
+        self.inner.llm_requests.store(0, Ordering::Relaxed);
+        self.inner.llm_tokens.store(0, Ordering::Relaxed);
+        self.inner
+            .llm_estimated_requests
+            .store(0, Ordering::Relaxed);
+        self.inner.llm_cost_micro_usd.store(0, Ordering::Relaxed);
+        let _ = self.inner.search_latency.lock().map(|mut r| r.clear());
+        let _ = self.inner.llm_latency.lock().map(|mut r| r.clear());
         let _ = self.inner.custom_counters.lock().map(|mut m| m.clear());
     }
 
@@ -131,6 +449,182 @@ impl Metrics {
     }
 }
 
+/// Totals saved at `path`; `None` when there is no file yet.
+fn read_state(path: &Path) -> std::io::Result<Option<MetricsTotals>> {
+    let data = match std::fs::read_to_string(path) {
+        Ok(data) => data,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let invalid = |e: String| std::io::Error::new(std::io::ErrorKind::InvalidData, e);
+    let value: serde_json::Value =
+        serde_json::from_str(&data).map_err(|e| invalid(e.to_string()))?;
+    compat::json_version("metrics state", &value, METRICS_STATE_VERSION)
+        .map_err(|e| invalid(e.to_string()))?;
+    let state: MetricsState = serde_json::from_value(value).map_err(|e| invalid(e.to_string()))?;
+    Ok(Some(state.totals))
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn latency_summary(reservoir: &Mutex<LatencyReservoir>) -> LatencySummary {
+    reservoir.lock().map(|r| r.summary()).unwrap_or_default()
+}
+
+impl MetricsSnapshot {
+    /// Render in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+        let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} {}", name, kind);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+        metric(
+            "lucastra_command_count",
+            "counter",
+            "Commands handled.",
+            self.command_count.to_string(),
+        );
+        metric(
+            "lucastra_tool_success_total",
+            "counter",
+            "Tool executions that succeeded.",
+            self.tool_success_count.to_string(),
+        );
+        metric(
+            "lucastra_tool_failure_total",
+            "counter",
+            "Tool executions that failed.",
+            self.tool_failure_count.to_string(),
+        );
+        metric(
+            "lucastra_search_queries_total",
+            "counter",
+            "Search queries run.",
+            self.search_queries.to_string(),
+        );
+        metric(
+            "lucastra_app_startup_time_ms",
+            "gauge",
+            "Time taken to start the app.",
+            self.app_startup_time_ms.to_string(),
+        );
+        metric(
+            "lucastra_llm_requests_total",
+            "counter",
+            "LLM requests made.",
+            self.llm_requests.to_string(),
+        );
+        metric(
+            "lucastra_llm_tokens_total",
+            "counter",
+            "LLM tokens used, prompt and answer.",
+            self.llm_tokens.to_string(),
+        );
+        metric(
+            "lucastra_llm_estimated_requests_total",
+            "counter",
+            "LLM requests whose token counts were estimated.",
+            self.llm_estimated_requests.to_string(),
+        );
+        metric(
+            "lucastra_llm_cost_usd_total",
+            "counter",
+            "Estimated LLM cost in US dollars.",
+            self.llm_cost_usd.to_string(),
+        );
+        metric(
+            "lucastra_llm_health",
+            "gauge",
+            "LLM backend health: 2 healthy, 1 degraded, 0 down.",
+            match self.llm_health.state {
+                HealthState::Healthy => "2",
+                HealthState::Degraded => "1",
+                HealthState::Down => "0",
+            }
+            .to_string(),
+        );
+        metric(
+            "lucastra_llm_consecutive_failures",
+            "gauge",
+            "Failed LLM health checks in a row.",
+            self.llm_health.consecutive_failures.to_string(),
+        );
+        let lifetime = [
+            (
+                "lucastra_lifetime_sessions_total",
+                "Sessions that persisted their metrics.",
+                self.lifetime.sessions.to_string(),
+            ),
+            (
+                "lucastra_lifetime_command_count",
+                "Commands handled, across restarts.",
+                self.lifetime.command_count.to_string(),
+            ),
+            (
+                "lucastra_lifetime_tool_success_total",
+                "Tool executions that succeeded, across restarts.",
+                self.lifetime.tool_success_count.to_string(),
+            ),
+            (
+                "lucastra_lifetime_tool_failure_total",
+                "Tool executions that failed, across restarts.",
+                self.lifetime.tool_failure_count.to_string(),
+            ),
+            (
+                "lucastra_lifetime_search_queries_total",
+                "Search queries run, across restarts.",
+                self.lifetime.search_queries.to_string(),
+            ),
+            (
+                "lucastra_lifetime_llm_requests_total",
+                "LLM requests made, across restarts.",
+                self.lifetime.llm_requests.to_string(),
+            ),
+            (
+                "lucastra_lifetime_llm_tokens_total",
+                "LLM tokens used, across restarts.",
+                self.lifetime.llm_tokens.to_string(),
+            ),
+            (
+                "lucastra_lifetime_llm_cost_usd_total",
+                "Estimated LLM cost in US dollars, across restarts.",
+                self.lifetime.llm_cost_usd().to_string(),
+            ),
+        ];
+        for (name, help, value) in lifetime {
+            metric(name, "counter", help, value);
+        }
+        write_summary(
+            &mut out,
+            "lucastra_search_latency_ms",
+            "Search latency over recent queries.",
+            &self.search_latency,
+        );
+        write_summary(
+            &mut out,
+            "lucastra_llm_latency_ms",
+            "LLM call latency over recent calls.",
+            &self.llm_latency,
+        );
+        out
+    }
+}
+
+fn write_summary(out: &mut String, name: &str, help: &str, summary: &LatencySummary) {
+    let _ = writeln!(out, "# HELP {} {}", name, help);
+    let _ = writeln!(out, "# TYPE {} summary", name);
+    let _ = writeln!(out, "{}{{quantile=\"0.5\"}} {}", name, summary.p50_ms);
+    let _ = writeln!(out, "{}{{quantile=\"0.95\"}} {}", name, summary.p95_ms);
+    let _ = writeln!(out, "{}_count {}", name, summary.samples);
+}
+
 impl Default for Metrics {
     fn default() -> Self {
         Self::new()
@@ -190,6 +684,161 @@ mod tests {
         assert_eq!(snapshot.average_search_latency_ms, 200);
     }
 
+    #[test]
+    fn test_latency_percentiles() {
+        let metrics = Metrics::new();
+        for latency in 1..=100 {
+            metrics.record_llm_latency(latency);
+        }
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.llm_latency.samples, 100);
+        assert_eq!(snapshot.llm_latency.p50_ms, 50);
+        assert_eq!(snapshot.llm_latency.p95_ms, 95);
+        assert_eq!(snapshot.search_latency, LatencySummary::default());
+
+        // Only the most recent samples count
+        for _ in 0..RESERVOIR_SIZE {
+            metrics.record_llm_latency(7);
+        }
+        assert_eq!(metrics.snapshot().llm_latency.p95_ms, 7);
+    }
+
+    #[test]
+    fn test_prometheus_format() {
+        let metrics = Metrics::new();
+        metrics.record_command();
+        metrics.record_search(40);
+        let text = metrics.snapshot().to_prometheus();
+        assert!(text.contains("# TYPE lucastra_command_count counter\nlucastra_command_count 1\n"));
+        assert!(text.contains("lucastra_search_latency_ms{quantile=\"0.95\"} 40\n"));
+        assert!(text.contains("lucastra_search_latency_ms_count 1\n"));
+    }
+
+    #[test]
+    fn test_llm_usage() {
+        let metrics = Metrics::new();
+        let usage = UsageRecord {
+            provider: "openai".to_string(),
+            model: "gpt-4o-mini".to_string(),
+            class: lucastra_llm::RequestClass::Completion,
+            input_tokens: 100,
+            output_tokens: 20,
+            cost_usd: 0.0015,
+            estimated: false,
+            timestamp: 0,
+        };
+        metrics.record_llm_usage(&usage);
+        metrics.record_llm_usage(&UsageRecord {
+            estimated: true,
+            ..usage
+        });
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.llm_requests, 2);
+        assert_eq!(snapshot.llm_tokens, 240);
+        assert_eq!(snapshot.llm_estimated_requests, 1);
+        assert!((snapshot.llm_cost_usd - 0.003).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_llm_health_gauges() {
+        let metrics = Metrics::new();
+        assert!(metrics
+            .snapshot()
+            .to_prometheus()
+            .contains("lucastra_llm_health 2\n"));
+
+        metrics.record_llm_health(&HealthStatus {
+            state: HealthState::Down,
+            consecutive_failures: 4,
+            ..Default::default()
+        });
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.llm_health.state, HealthState::Down);
+        let text = snapshot.to_prometheus();
+        assert!(text.contains("lucastra_llm_health 0\n"));
+        assert!(text.contains("lucastra_llm_consecutive_failures 4\n"));
+    }
+
+    #[test]
+    fn test_lifetime_totals_accumulate_across_sessions() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics_state.json");
+
+        let first = Metrics::persistent(path.clone());
+        first.record_command();
+        first.record_command();
+        first.record_tool_success();
+        first.persist().unwrap();
+        // Later counts reach the file on the next save, e.g. at shutdown
+        first.record_tool_failure();
+        first.persist().unwrap();
+        drop(first);
+
+        let second = Metrics::persistent(path.clone());
+        let snapshot = second.snapshot();
+        assert_eq!(snapshot.command_count, 0);
+        assert_eq!(snapshot.lifetime.command_count, 2);
+        assert_eq!(snapshot.lifetime.sessions, 2);
+
+        second.record_command();
+        second.record_tool_success();
+        second.reset();
+        second.record_tool_success();
+        second.persist().unwrap();
+
+        let third = Metrics::persistent(path);
+        let lifetime = third.lifetime();
+        assert_eq!(lifetime.sessions, 3);
+        assert_eq!(lifetime.command_count, 2);
+        assert_eq!(lifetime.tool_success_count, 2);
+        assert_eq!(lifetime.tool_failure_count, 1);
+        assert!((lifetime.tool_success_rate() - 66.67).abs() < 0.01);
+        assert!(third
+            .snapshot()
+            .to_prometheus()
+            .contains("lucastra_lifetime_command_count 2\n"));
+    }
+
+    #[test]
+    fn test_saved_totals_are_never_lowered() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics_state.json");
+        let busy = Metrics::persistent(path.clone());
+        let idle = Metrics::persistent(path.clone());
+        for _ in 0..5 {
+            busy.record_command();
+        }
+        busy.persist().unwrap();
+        idle.persist().unwrap();
+
+        assert_eq!(Metrics::persistent(path).lifetime().command_count, 5);
+    }
+
+    #[test]
+    fn test_corrupt_state_is_moved_aside() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("metrics_state.json");
+        std::fs::write(&path, "{ not json").unwrap();
+
+        let metrics = Metrics::persistent(path.clone());
+        assert_eq!(metrics.lifetime().command_count, 0);
+        let aside: Vec<_> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().into_string().unwrap())
+            .filter(|name| name.starts_with("metrics_state.json.corrupt-"))
+            .collect();
+        assert_eq!(aside.len(), 1);
+        assert_eq!(
+            std::fs::read_to_string(dir.path().join(&aside[0])).unwrap(),
+            "{ not json"
+        );
+
+        metrics.record_command();
+        metrics.persist().unwrap();
+        assert_eq!(Metrics::persistent(path).lifetime().command_count, 1);
+    }
+
     #[test]
     fn test_reset_metrics() {
         let metrics = Metrics::new();
@@ -1,86 +1,610 @@
-use std::path::Path;
+use crate::metrics::{Metrics, MetricsSnapshot};
+use lucastra_config::{MetricsConfig, TracingConfig};
+use lucastra_core::Command;
+use std::fs::{File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::sync::OnceLock;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use tracing::field::Empty;
 use tracing::level_filters::LevelFilter;
-use tracing_appender::rolling;
+use tracing::{info_span, Span, Subscriber};
+use tracing_appender::non_blocking::WorkerGuard;
 use tracing_subscriber::{
-    fmt::{self, format::FmtSpan},
-    layer::SubscriberExt,
+    fmt,
+    layer::{Layered, SubscriberExt},
+    reload,
     util::SubscriberInitExt,
-    EnvFilter, Registry,
+    EnvFilter, Layer, Registry,
 };
 
-/// Initialize tracing with file appender and console output
+/// Initialize tracing from `config`: console output, file logging to
+/// `file_name` in `config.log_dir` (JSON when `config.json_format` is set)
+/// rotated by size, and the level from `config.level` unless `RUST_LOG`
+/// overrides it.
+///
+/// Keep the returned guard alive for as long as logs should be written; the
+/// file writer flushes when it is dropped.
 pub fn init_tracing(
-    log_dir: &Path,
-    log_level: &str,
-    console_output: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    // Create log directory if it doesn't exist
-    std::fs::create_dir_all(log_dir)?;
-
-    // Parse log level
-    let level_filter = match log_level.to_lowercase().as_str() {
+    config: &TracingConfig,
+    file_name: &str,
+) -> Result<Option<WorkerGuard>, Box<dyn std::error::Error>> {
+    let (subscriber, guard, level) = build_subscriber(config, file_name)?;
+    subscriber.try_init()?;
+    let _ = LOG_LEVEL.set(level);
+    tracing::info!("Tracing initialized with level: {}", config.level);
+    Ok(guard)
+}
+
+fn level_filter(level: &str) -> LevelFilter {
+    match level.to_lowercase().as_str() {
         "trace" => LevelFilter::TRACE,
         "debug" => LevelFilter::DEBUG,
         "info" => LevelFilter::INFO,
         "warn" => LevelFilter::WARN,
         "error" => LevelFilter::ERROR,
         _ => LevelFilter::INFO,
+    }
+}
+
+type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+type LevelHandle = reload::Handle<EnvFilter, Layered<Vec<BoxedLayer>, Registry>>;
+
+/// Changes the level of the subscriber installed by [`init_tracing`].
+static LOG_LEVEL: OnceLock<LevelHandle> = OnceLock::new();
+
+/// Switch the installed subscriber to `level`, replacing any `RUST_LOG`
+/// filter. Returns `false` if tracing wasn't set up with [`init_tracing`].
+pub fn set_log_level(level: &str) -> bool {
+    let Some(handle) = LOG_LEVEL.get() else {
+        return false;
     };
+    let filter = EnvFilter::new(level_filter(level).to_string());
+    match handle.reload(filter) {
+        Ok(()) => {
+            tracing::info!("Log level changed to {}", level);
+            true
+        }
+        Err(e) => {
+            tracing::warn!("Could not change log level: {}", e);
+            false
+        }
+    }
+}
+
+fn build_subscriber(
+    config: &TracingConfig,
+    file_name: &str,
+) -> std::io::Result<(
+    impl Subscriber + Send + Sync,
+    Option<WorkerGuard>,
+    LevelHandle,
+)> {
+    let mut layers: Vec<BoxedLayer> = Vec::new();
+    let mut guard = None;
+
+    if config.file_logging {
+        let writer = RotatingFileWriter::new(
+            &config.log_dir,
+            file_name,
+            config.max_log_size_mb as u64 * 1024 * 1024,
+            config.log_files_keep as usize,
+        )?;
+        let (non_blocking, worker_guard) = tracing_appender::non_blocking(writer);
+        guard = Some(worker_guard);
+
+        let file_layer = fmt::layer()
+            .with_writer(non_blocking)
+            .with_ansi(false)
+            .with_target(true)
+            .with_level(true);
+        layers.push(if config.json_format {
+            file_layer.json().boxed()
+        } else {
+            file_layer.boxed()
+        });
+    }
 
-    let registry = Registry::default().with(
-        EnvFilter::try_from_default_env()
-            .unwrap_or_else(|_| EnvFilter::new(level_filter.to_string())),
-    );
-
-    // File appender - daily rotation
-    let file_appender = rolling::daily(log_dir, "lucastra.log");
-    let file_layer = fmt::layer()
-        .with_writer(file_appender)
-        .with_span_events(FmtSpan::CLOSE)
-        .with_target(true)
-        .with_level(true);
-
-    if console_output {
-        // Console layer with pretty formatting
+    if config.console_output {
         let console_layer = fmt::layer()
-            .pretty()
-            .with_span_events(FmtSpan::CLOSE)
+            .with_writer(std::io::stdout)
             .with_target(true)
             .with_level(true);
+        layers.push(if config.json_format {
+            console_layer.json().boxed()
+        } else {
+            console_layer.boxed()
+        });
+    }
+
+    let filter = EnvFilter::try_from_default_env()
+        .unwrap_or_else(|_| EnvFilter::new(level_filter(&config.level).to_string()));
+    let (filter, level) = reload::Layer::new(filter);
+    Ok((Registry::default().with(layers).with(filter), guard, level))
+}
+
+/// Log file writer that rotates by size. Once `name` would grow past
+/// `max_bytes` it becomes `name.1`, `name.1` becomes `name.2` and so on,
+/// keeping at most `keep` files including the current one. A `max_bytes` of
+/// 0 never rotates.
+pub struct RotatingFileWriter {
+    dir: PathBuf,
+    name: String,
+    max_bytes: u64,
+    keep: usize,
+    file: File,
+    written: u64,
+}
+
+impl RotatingFileWriter {
+    pub fn new(dir: &Path, name: &str, max_bytes: u64, keep: usize) -> std::io::Result<Self> {
+        std::fs::create_dir_all(dir)?;
+        let mut writer = Self {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            max_bytes,
+            keep: keep.max(1),
+            file: OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(dir.join(name))?,
+            written: 0,
+        };
+        writer.written = writer.file.metadata()?.len();
+        writer.prune()?;
+        Ok(writer)
+    }
+
+    /// Path of the `index`th file; 0 is the one being written.
+    fn path(&self, index: usize) -> PathBuf {
+        match index {
+            0 => self.dir.join(&self.name),
+            _ => self.dir.join(format!("{}.{}", self.name, index)),
+        }
+    }
+
+    /// Delete rotated files beyond `keep`, e.g. after it was lowered.
+    fn prune(&self) -> std::io::Result<()> {
+        let mut index = self.keep;
+        while self.path(index).exists() {
+            std::fs::remove_file(self.path(index))?;
+            index += 1;
+        }
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> std::io::Result<()> {
+        self.file.flush()?;
+        for index in (1..self.keep).rev() {
+            let from = self.path(index - 1);
+            if from.exists() {
+                std::fs::rename(from, self.path(index))?;
+            }
+        }
+        // With `keep` at 1 the current file is simply started over
+        self.file = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .truncate(true)
+            .open(self.path(0))?;
+        self.written = 0;
+        Ok(())
+    }
+}
+
+impl Write for RotatingFileWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        if self.max_bytes > 0
+            && self.written > 0
+            && self.written + buf.len() as u64 > self.max_bytes
+        {
+            self.rotate()?;
+        }
+        let written = self.file.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
 
-        registry.with(file_layer).with(console_layer).init();
-    } else {
-        registry.with(file_layer).init();
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.flush()
     }
+}
+
+/// Prefix of exported metrics files, followed by a millisecond timestamp.
+const EXPORT_PREFIX: &str = "metrics-";
+
+/// Write `snapshot` to `dir` as `metrics-<millis>.json` and
+/// `metrics-<millis>.prom` (Prometheus text format), then delete all but the
+/// newest `keep` exports. Returns the path of the JSON file.
+pub fn export_metrics(
+    snapshot: &MetricsSnapshot,
+    dir: &Path,
+    keep: usize,
+) -> std::io::Result<PathBuf> {
+    std::fs::create_dir_all(dir)?;
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    // Zero-padded so names sort by time
+    let stem = format!("{}{:013}", EXPORT_PREFIX, millis);
 
-    tracing::info!("Tracing initialized with level: {}", log_level);
+    let json_path = dir.join(format!("{}.json", stem));
+    std::fs::write(&json_path, serde_json::to_string_pretty(snapshot)?)?;
+    std::fs::write(dir.join(format!("{}.prom", stem)), snapshot.to_prometheus())?;
+
+    rotate_exports(dir, keep)?;
+    Ok(json_path)
+}
+
+fn rotate_exports(dir: &Path, keep: usize) -> std::io::Result<()> {
+    let mut stems: Vec<String> = std::fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            let stem = name
+                .strip_suffix(".json")
+                .or_else(|| name.strip_suffix(".prom"))?;
+            stem.starts_with(EXPORT_PREFIX).then(|| stem.to_string())
+        })
+        .collect();
+    stems.sort();
+    stems.dedup();
+
+    let stale = stems.len().saturating_sub(keep);
+    for stem in &stems[..stale] {
+        for extension in ["json", "prom"] {
+            match std::fs::remove_file(dir.join(format!("{}.{}", stem, extension))) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => return Err(e),
+                _ => {}
+            }
+        }
+    }
     Ok(())
 }
 
+/// Exports metrics to `MetricsConfig::export_dir` every
+/// `export_interval_secs` on a background thread until dropped.
+pub struct MetricsExporter {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetricsExporter {
+    pub fn start(metrics: Metrics, config: &MetricsConfig) -> Self {
+        let dir = config.export_dir.clone();
+        let interval = Duration::from_secs(config.export_interval_secs.max(1) as u64);
+        let keep = config.export_files_keep.max(1) as usize;
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let thread = std::thread::spawn(move || {
+            // Anything but a timeout means the exporter was dropped
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                if let Err(e) = export_metrics(&metrics.snapshot(), &dir, keep) {
+                    tracing::warn!("Metrics export to {} failed: {}", dir.display(), e);
+                }
+            }
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for MetricsExporter {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Saves lifetime metrics totals every `persist_interval_secs` on a
+/// background thread, and once more when dropped.
+pub struct MetricsPersister {
+    stop: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl MetricsPersister {
+    pub fn start(metrics: Metrics, config: &MetricsConfig) -> Self {
+        let interval = Duration::from_secs(config.persist_interval_secs.max(1) as u64);
+        let (stop, stopped) = mpsc::channel::<()>();
+
+        let thread = std::thread::spawn(move || {
+            let persist = || {
+                if let Err(e) = metrics.persist() {
+                    tracing::warn!("Saving metrics totals failed: {}", e);
+                }
+            };
+            while let Err(mpsc::RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+                persist();
+            }
+            // Dropped: save what this session counted since the last save
+            persist();
+        });
+
+        Self {
+            stop: Some(stop),
+            thread: Some(thread),
+        }
+    }
+}
+
+impl Drop for MetricsPersister {
+    fn drop(&mut self) {
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+/// Span for handling `command`. Its `request_id` is the command id, so every
+/// log line written while handling it can be found by that id.
+pub(crate) fn command_span(command: &Command) -> Span {
+    info_span!(
+        "handle_command",
+        request_id = %command.id,
+        command = command.payload.name(),
+    )
+}
+
+/// Span for a search of the index for `query`.
+pub(crate) fn search_span(query: &str) -> TimedSpan {
+    TimedSpan::new(info_span!(
+        "search",
+        query_len = query.len(),
+        result_count = Empty,
+        latency_ms = Empty,
+    ))
+}
+
+/// Span for answering a query with the LLM; the provider calls it makes
+/// are its children.
+pub(crate) fn inference_span() -> TimedSpan {
+    TimedSpan::new(info_span!("inference", latency_ms = Empty))
+}
+
+/// Span for a run of background job `name`.
+pub(crate) fn job_span(name: &str) -> Span {
+    info_span!("run_job", job = name)
+}
+
+/// Span for running `tool`.
+pub(crate) fn tool_span(tool: &str) -> TimedSpan {
+    TimedSpan::new(info_span!(
+        "execute_tool",
+        tool,
+        success = Empty,
+        latency_ms = Empty,
+    ))
+}
+
+/// A span and when it opened. [`TimedSpan::finish`] records the latency on
+/// the span and returns it for [`Metrics`], so the dashboard and the trace
+/// show the same number.
+pub(crate) struct TimedSpan {
+    span: Span,
+    started: Instant,
+}
+
+impl TimedSpan {
+    fn new(span: Span) -> Self {
+        Self {
+            span,
+            started: Instant::now(),
+        }
+    }
+
+    /// Run `f` inside the span.
+    pub(crate) fn in_scope<T>(&self, f: impl FnOnce() -> T) -> T {
+        self.span.in_scope(f)
+    }
+
+    pub(crate) fn record(&self, field: &str, value: impl tracing::Value) {
+        self.span.record(field, value);
+    }
+
+    /// Milliseconds since the span opened, recorded as its `latency_ms`.
+    pub(crate) fn finish(self) -> u64 {
+        let latency_ms = self.started.elapsed().as_millis() as u64;
+        self.span.record("latency_ms", latency_ms);
+        latency_ms
+    }
+}
+
+/// Serve Prometheus metrics at `GET /metrics` on `addr` from a background
+/// thread. Returns the bound address, useful when `addr` uses port 0.
+#[cfg(feature = "metrics-http")]
+pub fn serve_metrics(metrics: Metrics, addr: &str) -> std::io::Result<std::net::SocketAddr> {
+    use std::io::{BufRead, BufReader};
+
+    let listener = std::net::TcpListener::bind(addr)?;
+    let local_addr = listener.local_addr()?;
+    std::thread::spawn(move || {
+        for stream in listener.incoming().filter_map(|s| s.ok()) {
+            let mut request_line = String::new();
+            if BufReader::new(&stream)
+                .read_line(&mut request_line)
+                .is_err()
+            {
+                continue;
+            }
+            let (status, body) = if request_line.starts_with("GET /metrics ") {
+                ("200 OK", metrics.snapshot().to_prometheus())
+            } else {
+                ("404 Not Found", "not found\n".to_string())
+            };
+            let _ = write!(
+                &stream,
+                "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                status,
+                body.len(),
+                body
+            );
+        }
+    });
+    Ok(local_addr)
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn test_log_level_parsing() {
-        // Test the log level parsing logic directly without actually initializing tracing
         let test_cases = vec![
-            ("trace", "TRACE"),
-            ("debug", "DEBUG"),
-            ("info", "INFO"),
-            ("warn", "WARN"),
-            ("error", "ERROR"),
-            ("invalid", "INFO"), // Should default to INFO
+            ("trace", LevelFilter::TRACE),
+            ("DEBUG", LevelFilter::DEBUG),
+            ("info", LevelFilter::INFO),
+            ("warn", LevelFilter::WARN),
+            ("error", LevelFilter::ERROR),
+            ("invalid", LevelFilter::INFO), // Should default to INFO
         ];
 
-        for (input, _expected) in test_cases {
-            let level_str = match input.to_lowercase().as_str() {
-                "trace" => "TRACE",
-                "debug" => "DEBUG",
-                "info" => "INFO",
-                "warn" => "WARN",
-                "error" => "ERROR",
-                _ => "INFO",
-            };
-            assert!(!level_str.is_empty());
+        for (input, expected) in test_cases {
+            assert_eq!(level_filter(input), expected);
+        }
+    }
+
+    fn log_files(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = std::fs::read_dir(dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn test_rotating_writer_shifts_and_caps_files() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mut writer = RotatingFileWriter::new(dir.path(), "app.log", 10, 3).unwrap();
+        for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        assert_eq!(
+            log_files(dir.path()),
+            vec!["app.log", "app.log.1", "app.log.2"]
+        );
+        let read = |name: &str| std::fs::read_to_string(dir.path().join(name)).unwrap();
+        assert_eq!(read("app.log"), "fourth\n");
+        assert_eq!(read("app.log.1"), "third\n");
+        assert_eq!(read("app.log.2"), "second\n");
+
+        // Reopening with a lower cap prunes the extra file
+        RotatingFileWriter::new(dir.path(), "app.log", 10, 2).unwrap();
+        assert_eq!(log_files(dir.path()), vec!["app.log", "app.log.1"]);
+    }
+
+    #[test]
+    fn test_json_logs_rotate_at_configured_size() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = TracingConfig {
+            level: "info".to_string(),
+            log_dir: dir.path().to_path_buf(),
+            max_log_size_mb: 1,
+            log_files_keep: 3,
+            console_output: false,
+            json_format: true,
+            ..TracingConfig::default()
+        };
+        let (subscriber, guard, _) = build_subscriber(&config, "lucastra.log").unwrap();
+        let padding = "x".repeat(500);
+        tracing::subscriber::with_default(subscriber, || {
+            // About 4 MB, enough to rotate more often than files are kept
+            for i in 0..8000 {
+                tracing::info!(i, "{}", padding);
+            }
+            tracing::debug!("filtered out by level");
+        });
+        drop(guard);
+
+        let files = log_files(dir.path());
+        assert_eq!(
+            files,
+            vec!["lucastra.log", "lucastra.log.1", "lucastra.log.2"]
+        );
+        for name in &files {
+            let size = std::fs::metadata(dir.path().join(name)).unwrap().len();
+            assert!(size <= 1024 * 1024, "{} is {} bytes", name, size);
+        }
+        let rotated = std::fs::read_to_string(dir.path().join("lucastra.log.1")).unwrap();
+        let line: serde_json::Value =
+            serde_json::from_str(rotated.lines().next().unwrap()).unwrap();
+        assert_eq!(line["level"], "INFO");
+        assert!(!rotated.contains("filtered out"));
+    }
+
+    #[test]
+    fn test_export_writes_json_and_prometheus_and_rotates() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let metrics = Metrics::new();
+        metrics.record_command();
+
+        for _ in 0..3 {
+            export_metrics(&metrics.snapshot(), dir.path(), 2).unwrap();
+            std::thread::sleep(Duration::from_millis(2));
         }
+        let mut names: Vec<String> = std::fs::read_dir(dir.path())
+            .unwrap()
+            .map(|e| e.unwrap().file_name().into_string().unwrap())
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 4);
+
+        let json: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.path().join(&names[0])).unwrap())
+                .unwrap();
+        assert_eq!(json["command_count"], 1);
+        let prom = std::fs::read_to_string(dir.path().join(&names[1])).unwrap();
+        assert!(prom.contains("lucastra_command_count 1"));
+    }
+
+    #[test]
+    fn test_exporter_runs_on_interval_and_stops_on_drop() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let config = MetricsConfig {
+            export_to_file: true,
+            export_dir: dir.path().to_path_buf(),
+            export_interval_secs: 1,
+            ..MetricsConfig::default()
+        };
+        let exporter = MetricsExporter::start(Metrics::new(), &config);
+        std::thread::sleep(Duration::from_millis(1500));
+        drop(exporter);
+
+        let exports = std::fs::read_dir(dir.path()).unwrap().count();
+        assert_eq!(exports, 2);
+    }
+
+    #[cfg(feature = "metrics-http")]
+    #[test]
+    fn test_serve_metrics() {
+        use std::io::Read;
+
+        let metrics = Metrics::new();
+        metrics.record_command();
+        let addr = serve_metrics(metrics, "127.0.0.1:0").unwrap();
+
+        let get = |path: &str| {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            write!(stream, "GET {} HTTP/1.1\r\nHost: localhost\r\n\r\n", path).unwrap();
+            let mut response = String::new();
+            stream.read_to_string(&mut response).unwrap();
+            response
+        };
+        let response = get("/metrics");
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains("lucastra_command_count 1"));
+        assert!(get("/other").starts_with("HTTP/1.1 404"));
     }
 }
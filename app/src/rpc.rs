@@ -0,0 +1,468 @@
+//! Local JSON-RPC API: other processes drive [`SystemState`] over a Unix
+//! domain socket, one [`RpcRequest`] per line in and one [`RpcResponse`] per
+//! line out.
+//!
+//! A connection must first call `authenticate` with the token from
+//! `rpc.token` in the config dir, which is created readable by its owner
+//! only. Each connection may send `rpc.max_requests_per_connection`
+//! requests. The server stops when [`RpcServer`] or the last handle to the
+//! system state is dropped.
+
+use crate::{CommandExecutor, SystemState};
+use lucastra_config::RpcConfig;
+use lucastra_core::rpc::{self, RpcError, RpcRequest, RpcResponse};
+use lucastra_core::Command;
+use lucastra_tools::Tool;
+use serde_json::{json, Value};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::thread::JoinHandle;
+use std::time::Duration;
+
+/// How often idle threads check whether the server should stop.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Longest request line accepted; longer ones close the connection.
+const MAX_LINE_BYTES: usize = 1024 * 1024;
+
+/// A running API server.
+pub struct RpcServer {
+    socket_path: PathBuf,
+    token_path: PathBuf,
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl RpcServer {
+    /// Listen on `config.rpc` socket, creating the token file if needed.
+    pub fn start(state: &Arc<Mutex<SystemState>>, config: &RpcConfig) -> io::Result<Self> {
+        let token_path = lucastra_config::get_rpc_token_path()
+            .map_err(|e| io::Error::new(io::ErrorKind::NotFound, e.to_string()))?;
+        let token = load_or_create_token(&token_path)?;
+        Self::start_at(
+            state,
+            config.resolved_socket_path(),
+            token_path,
+            token,
+            config.max_requests_per_connection,
+        )
+    }
+
+    #[cfg(unix)]
+    fn start_at(
+        state: &Arc<Mutex<SystemState>>,
+        socket_path: PathBuf,
+        token_path: PathBuf,
+        token: String,
+        max_requests: usize,
+    ) -> io::Result<Self> {
+        let listener = unix::bind(&socket_path)?;
+        let stop = Arc::new(AtomicBool::new(false));
+        let server = Server {
+            state: Arc::downgrade(state),
+            stop: stop.clone(),
+            token: Arc::new(token),
+            max_requests,
+        };
+        let path = socket_path.clone();
+        let thread = std::thread::Builder::new()
+            .name("lucastra-rpc".to_string())
+            .spawn(move || {
+                unix::serve(listener, server);
+                let _ = std::fs::remove_file(&path);
+            })?;
+        tracing::info!("Serving the JSON-RPC API on {}", socket_path.display());
+        Ok(Self {
+            socket_path,
+            token_path,
+            stop,
+            thread: Some(thread),
+        })
+    }
+
+    #[cfg(not(unix))]
+    fn start_at(
+        _state: &Arc<Mutex<SystemState>>,
+        _socket_path: PathBuf,
+        _token_path: PathBuf,
+        _token: String,
+        _max_requests: usize,
+    ) -> io::Result<Self> {
+        Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the JSON-RPC API needs Unix domain sockets; named pipes aren't supported yet",
+        ))
+    }
+
+    pub fn socket_path(&self) -> &Path {
+        &self.socket_path
+    }
+
+    /// File holding the token clients authenticate with.
+    pub fn token_path(&self) -> &Path {
+        &self.token_path
+    }
+
+    /// Close the socket and wait for open connections to finish their
+    /// current request.
+    pub fn stop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+impl Drop for RpcServer {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// What every connection shares.
+#[derive(Clone)]
+struct Server {
+    state: Weak<Mutex<SystemState>>,
+    stop: Arc<AtomicBool>,
+    token: Arc<String>,
+    max_requests: usize,
+}
+
+impl Server {
+    /// Stop once asked to, or once the system state is gone.
+    fn stopping(&self) -> bool {
+        self.stop.load(Ordering::SeqCst) || self.state.strong_count() == 0
+    }
+}
+
+/// One client's progress.
+struct Session {
+    authenticated: bool,
+    requests: usize,
+}
+
+/// Answer one request line. The flag is set when the connection must close.
+fn handle_line(server: &Server, session: &mut Session, line: &[u8]) -> (RpcResponse, bool) {
+    session.requests += 1;
+    if session.requests > server.max_requests {
+        let error = RpcError::new(
+            RpcError::LIMIT_REACHED,
+            format!(
+                "Connection used its {} requests; reconnect to continue",
+                server.max_requests
+            ),
+        );
+        return (RpcResponse::error(Value::Null, error), true);
+    }
+
+    let request: RpcRequest = match serde_json::from_slice(line) {
+        Ok(request) => request,
+        Err(e) => {
+            let code = if serde_json::from_slice::<Value>(line).is_ok() {
+                RpcError::INVALID_REQUEST
+            } else {
+                RpcError::PARSE_ERROR
+            };
+            let error = RpcError::new(code, e.to_string());
+            return (
+                RpcResponse::error(Value::Null, error),
+                !session.authenticated,
+            );
+        }
+    };
+    let id = request.id.clone();
+    if request.jsonrpc != rpc::JSONRPC_VERSION {
+        let error = RpcError::new(RpcError::INVALID_REQUEST, "jsonrpc must be \"2.0\"");
+        return (RpcResponse::error(id, error), !session.authenticated);
+    }
+
+    if request.method == rpc::AUTHENTICATE {
+        let token = request.params.get("token").and_then(Value::as_str);
+        if token == Some(server.token.as_str()) {
+            session.authenticated = true;
+            return (
+                RpcResponse::result(id, json!({"authenticated": true})),
+                false,
+            );
+        }
+    }
+    if !session.authenticated {
+        let error = RpcError::new(
+            RpcError::UNAUTHORIZED,
+            "Authenticate with the API token first",
+        );
+        return (RpcResponse::error(id, error), true);
+    }
+
+    let Some(state) = server.state.upgrade() else {
+        let error = RpcError::new(RpcError::COMMAND_FAILED, "LucAstra is shutting down");
+        return (RpcResponse::error(id, error), true);
+    };
+    let result = if request.method == rpc::EXECUTE_TOOL {
+        serde_json::from_value::<Tool>(request.params)
+            .map_err(|e| {
+                RpcError::new(
+                    RpcError::INVALID_PARAMS,
+                    format!("Invalid tool call: {}", e),
+                )
+            })
+            .map(|tool| {
+                let mut state = state
+                    .lock()
+                    .unwrap_or_else(|poisoned| poisoned.into_inner());
+                json!(state.execute_tool(tool))
+            })
+    } else {
+        // Through the executor, so queries release the state while the LLM
+        // answers and the GUI stays usable
+        rpc::command_payload(&request.method, request.params).map(|payload| {
+            let command = Command {
+                id: format!("rpc-{}", id),
+                payload,
+            };
+            json!(state.execute(command))
+        })
+    };
+
+    let response = match result {
+        Ok(result) => RpcResponse::result(id, result),
+        Err(error) => RpcResponse::error(id, error),
+    };
+    (response, false)
+}
+
+/// The API token at `path`, or a new random one saved there.
+fn load_or_create_token(path: &Path) -> io::Result<String> {
+    match std::fs::read_to_string(path) {
+        Ok(token) if !token.trim().is_empty() => {
+            #[cfg(unix)]
+            unix::restrict_permissions(path)?;
+            Ok(token.trim().to_string())
+        }
+        Ok(_) => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is empty; delete it to get a new token", path.display()),
+        )),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let token = uuid::Uuid::new_v4().simple().to_string();
+            if let Some(parent) = path.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            let mut options = std::fs::OpenOptions::new();
+            options.write(true).create_new(true);
+            #[cfg(unix)]
+            std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+            io::Write::write_all(&mut options.open(path)?, token.as_bytes())?;
+            tracing::info!("Created JSON-RPC API token at {}", path.display());
+            Ok(token)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(unix)]
+mod unix {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::fs::PermissionsExt;
+    use std::os::unix::net::{UnixListener, UnixStream};
+
+    /// Make `path` readable and writable by its owner only.
+    pub(super) fn restrict_permissions(path: &Path) -> io::Result<()> {
+        let mode = std::fs::metadata(path)?.permissions().mode();
+        if mode & 0o077 != 0 {
+            tracing::warn!(
+                "{} was readable by other users; restricting it to its owner",
+                path.display()
+            );
+            std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        }
+        Ok(())
+    }
+
+    /// Listen on `path`, replacing a socket left behind by a server that is
+    /// no longer running.
+    pub(super) fn bind(path: &Path) -> io::Result<UnixListener> {
+        if path.exists() {
+            if UnixStream::connect(path).is_ok() {
+                return Err(io::Error::new(
+                    io::ErrorKind::AddrInUse,
+                    format!("{} is already being served", path.display()),
+                ));
+            }
+            std::fs::remove_file(path)?;
+        }
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        let listener = UnixListener::bind(path)?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o600))?;
+        listener.set_nonblocking(true)?;
+        Ok(listener)
+    }
+
+    /// Accept connections until the server stops, then wait for them.
+    pub(super) fn serve(listener: UnixListener, server: Server) {
+        let mut connections: Vec<JoinHandle<()>> = Vec::new();
+        while !server.stopping() {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let server = server.clone();
+                    connections.retain(|connection| !connection.is_finished());
+                    connections.push(std::thread::spawn(move || {
+                        if let Err(e) = connection(stream, &server) {
+                            tracing::debug!("JSON-RPC connection closed: {}", e);
+                        }
+                    }));
+                }
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    std::thread::sleep(POLL_INTERVAL)
+                }
+                Err(e) => {
+                    tracing::warn!("JSON-RPC accept failed: {}", e);
+                    std::thread::sleep(POLL_INTERVAL);
+                }
+            }
+        }
+        for connection in connections {
+            let _ = connection.join();
+        }
+    }
+
+    fn connection(stream: UnixStream, server: &Server) -> io::Result<()> {
+        stream.set_nonblocking(false)?;
+        stream.set_read_timeout(Some(POLL_INTERVAL))?;
+        let mut writer = stream.try_clone()?;
+        let mut reader = BufReader::new(stream);
+        let mut session = Session {
+            authenticated: false,
+            requests: 0,
+        };
+        let mut line = Vec::new();
+
+        while !server.stopping() {
+            // A timeout keeps what was read so far in `line`
+            match reader.read_until(b'\n', &mut line) {
+                Ok(0) => return Ok(()),
+                Ok(_) if line.ends_with(b"\n") => {}
+                Ok(_) => return Ok(()),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                    ) =>
+                {
+                    if line.len() > MAX_LINE_BYTES {
+                        return Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "request line too long",
+                        ));
+                    }
+                    continue;
+                }
+                Err(e) => return Err(e),
+            }
+            if line.len() > MAX_LINE_BYTES {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "request line too long",
+                ));
+            }
+            if line.iter().all(u8::is_ascii_whitespace) {
+                line.clear();
+                continue;
+            }
+
+            let (response, close) = handle_line(server, &mut session, &line);
+            line.clear();
+            let mut reply = serde_json::to_vec(&response).map_err(io::Error::other)?;
+            reply.push(b'\n');
+            writer.write_all(&reply)?;
+            if close {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn server(max_requests: usize) -> Server {
+        Server {
+            state: Weak::new(),
+            stop: Arc::new(AtomicBool::new(false)),
+            token: Arc::new("secret".to_string()),
+            max_requests,
+        }
+    }
+
+    fn line(method: &str, params: Value) -> Vec<u8> {
+        serde_json::to_vec(&RpcRequest::new(1, method, params)).unwrap()
+    }
+
+    #[test]
+    fn test_requests_need_the_token_first() {
+        let server = server(10);
+        let mut session = Session {
+            authenticated: false,
+            requests: 0,
+        };
+        let (response, close) = handle_line(&server, &mut session, &line("status", Value::Null));
+        assert_eq!(response.error.unwrap().code, RpcError::UNAUTHORIZED);
+        assert!(close);
+
+        let wrong = line(rpc::AUTHENTICATE, json!({"token": "guess"}));
+        let (response, close) = handle_line(&server, &mut session, &wrong);
+        assert_eq!(response.error.unwrap().code, RpcError::UNAUTHORIZED);
+        assert!(close && !session.authenticated);
+
+        let right = line(rpc::AUTHENTICATE, json!({"token": "secret"}));
+        let (response, close) = handle_line(&server, &mut session, &right);
+        assert_eq!(response.result, Some(json!({"authenticated": true})));
+        assert!(!close && session.authenticated);
+
+        // Authenticated parse errors keep the connection open
+        let (response, close) = handle_line(&server, &mut session, b"{not json");
+        assert_eq!(response.error.unwrap().code, RpcError::PARSE_ERROR);
+        assert!(!close);
+        let (response, _) = handle_line(&server, &mut session, br#"{"id": 2}"#);
+        assert_eq!(response.error.unwrap().code, RpcError::INVALID_REQUEST);
+    }
+
+    #[test]
+    fn test_request_limit_closes_the_connection() {
+        let server = server(1);
+        let mut session = Session {
+            authenticated: false,
+            requests: 0,
+        };
+        let auth = line(rpc::AUTHENTICATE, json!({"token": "secret"}));
+        assert!(!handle_line(&server, &mut session, &auth).1);
+        let (response, close) = handle_line(&server, &mut session, &auth);
+        assert_eq!(response.error.unwrap().code, RpcError::LIMIT_REACHED);
+        assert!(close);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_token_file_is_private_and_reused() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rpc.token");
+        let token = load_or_create_token(&path).unwrap();
+        assert_eq!(token.len(), 32);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o644)).unwrap();
+        assert_eq!(load_or_create_token(&path).unwrap(), token);
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+}
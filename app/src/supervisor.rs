@@ -0,0 +1,248 @@
+//! Registry entries for the services [`SystemState`](crate::SystemState)
+//! owns directly.
+//!
+//! The shims do no work of their own; they give the [`ServiceRegistry`]
+//! each service's name and dependencies so it can report status and order
+//! start-up and shutdown. The llama server is the exception: the registry
+//! owns its process so stopping the registry kills it, and it starts in the
+//! background so a model download doesn't hold up boot.
+
+use lucastra_config::LlmConfig;
+use lucastra_llm::{ModelSpec, ServerManager, ServerOptions};
+use lucastra_services::{Service, ServiceError, ServiceHealth, ServiceRegistry, ServiceResult};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+use std::time::Duration;
+
+/// Registry name of the auto-started llama server.
+pub const LLM_SERVER: &str = "llm-server";
+
+/// Built-in services and what each depends on.
+const CORE_SERVICES: [(&str, &[&str]); 5] = [
+    ("filesystem", &[]),
+    ("devices", &["filesystem"]),
+    ("input", &[]),
+    ("search", &["filesystem"]),
+    ("llm", &["search"]),
+];
+
+/// Stand-in for a service owned by `SystemState`.
+pub struct ServiceShim {
+    name: &'static str,
+    dependencies: &'static [&'static str],
+}
+
+impl ServiceShim {
+    pub fn new(name: &'static str, dependencies: &'static [&'static str]) -> Self {
+        Self { name, dependencies }
+    }
+}
+
+impl Service for ServiceShim {
+    fn name(&self) -> &str {
+        self.name
+    }
+
+    fn start(&mut self) -> ServiceResult<()> {
+        Ok(())
+    }
+
+    fn dependencies(&self) -> Vec<String> {
+        self.dependencies.iter().map(|d| d.to_string()).collect()
+    }
+}
+
+/// Registry with a shim for every core service, all started.
+pub fn core_registry() -> ServiceResult<ServiceRegistry> {
+    let mut registry = ServiceRegistry::new();
+    for (name, dependencies) in CORE_SERVICES {
+        registry.register(Box::new(ServiceShim::new(name, dependencies)))?;
+    }
+    registry.start_all()?;
+    Ok(registry)
+}
+
+/// Where a start running in the background has got to.
+#[derive(Debug, Clone, PartialEq)]
+enum Startup {
+    Idle,
+    Starting(String),
+    Failed(String),
+}
+
+/// Shared control of the auto-started llama server. Starts run on a
+/// background thread, downloading the model if needed, and report their
+/// progress through [`health`](Self::health).
+#[derive(Clone)]
+pub struct LlmServerHandle {
+    manager: Arc<Mutex<ServerManager>>,
+    startup: Arc<Mutex<Startup>>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl LlmServerHandle {
+    pub fn new(manager: ServerManager) -> Self {
+        let startup = Arc::new(Mutex::new(Startup::Idle));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let progress = startup.clone();
+        let manager = manager
+            .with_cancel(cancel.clone())
+            .with_progress(move |progress_made| {
+                let detail = match progress_made.total {
+                    Some(total) => format!(
+                        "downloading model: {} / {} MB",
+                        progress_made.downloaded >> 20,
+                        total >> 20
+                    ),
+                    None => format!("downloading model: {} MB", progress_made.downloaded >> 20),
+                };
+                tracing::debug!("{}", detail);
+                *lock(&progress) = Startup::Starting(detail);
+            });
+        Self {
+            manager: Arc::new(Mutex::new(manager)),
+            startup,
+            cancel,
+        }
+    }
+
+    /// Start the server on a background thread unless a start is already
+    /// under way.
+    pub fn start(&self) -> ServiceResult<()> {
+        if !self.begin("starting") {
+            return Ok(());
+        }
+        self.cancel.store(false, Ordering::Relaxed);
+        let handle = self.clone();
+        std::thread::Builder::new()
+            .name("llm-server-start".to_string())
+            .spawn(move || handle.run(ServerManager::start))
+            .map(drop)
+            .map_err(|e| {
+                *lock(&self.startup) = Startup::Failed(e.to_string());
+                ServiceError::Failed(e.to_string())
+            })
+    }
+
+    /// Restart the server on this thread, unless a start is already under
+    /// way.
+    pub fn restart(&self) {
+        if self.begin("restarting") {
+            self.run(ServerManager::restart);
+        }
+    }
+
+    /// Stop the server, abandoning a start in progress. A start checks the
+    /// cancel flag at least every few hundred milliseconds, even mid-download,
+    /// so waiting for it to let go of the manager is brief.
+    pub fn stop(&self) -> ServiceResult<()> {
+        self.cancel.store(true, Ordering::Relaxed);
+        self.manager
+            .lock()
+            .map_err(|_| ServiceError::Failed("server manager poisoned".to_string()))?
+            .stop()
+            .map_err(|e| ServiceError::Failed(e.to_string()))
+    }
+
+    /// Degraded while starting, with download progress when there is any.
+    pub fn health(&self) -> ServiceHealth {
+        match lock(&self.startup).clone() {
+            Startup::Starting(detail) => return ServiceHealth::Degraded(detail),
+            Startup::Failed(error) => return ServiceHealth::Unhealthy(error),
+            Startup::Idle => {}
+        }
+        let Ok(manager) = self.manager.try_lock() else {
+            return ServiceHealth::Degraded("busy".to_string());
+        };
+        if manager.is_healthy() {
+            ServiceHealth::Healthy
+        } else {
+            ServiceHealth::Unhealthy(format!(
+                "no answer on {}/health",
+                manager.options().endpoint
+            ))
+        }
+    }
+
+    /// Mark a start as under way; false if one already is.
+    fn begin(&self, detail: &str) -> bool {
+        let mut startup = lock(&self.startup);
+        if matches!(*startup, Startup::Starting(_)) {
+            return false;
+        }
+        *startup = Startup::Starting(detail.to_string());
+        true
+    }
+
+    fn run(&self, start: fn(&mut ServerManager) -> lucastra_llm::ServerResult<()>) {
+        let result = match self.manager.lock() {
+            Ok(_) if self.cancel.load(Ordering::Relaxed) => Ok(()),
+            Ok(mut manager) => start(&mut manager).map_err(|e| e.to_string()),
+            Err(_) => Err("server manager poisoned".to_string()),
+        };
+        *lock(&self.startup) = match result {
+            Ok(()) => Startup::Idle,
+            Err(e) => {
+                tracing::warn!("LLM server not started: {}", e);
+                Startup::Failed(e)
+            }
+        };
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The local llama server, started from `llm` settings, as a registry
+/// entry.
+pub struct LlmServerService {
+    handle: LlmServerHandle,
+}
+
+impl LlmServerService {
+    pub fn new(manager: ServerManager) -> Self {
+        Self {
+            handle: LlmServerHandle::new(manager),
+        }
+    }
+
+    /// Handle shared with the health monitor so it can restart the server.
+    pub fn handle(&self) -> LlmServerHandle {
+        self.handle.clone()
+    }
+
+    /// Server for `config`, keeping models in `models_dir` and its output in
+    /// `log_file`.
+    pub fn from_config(config: &LlmConfig, models_dir: PathBuf, log_file: Option<PathBuf>) -> Self {
+        let model = ModelSpec::for_size(&config.model_size, &config.quantization);
+        let options = ServerOptions {
+            binary: config.server_binary.clone(),
+            auto_download: config.auto_download,
+            use_gpu: config.use_gpu,
+            startup_timeout: Duration::from_secs(config.startup_timeout_secs),
+            log_file,
+            ..ServerOptions::new(config.server_url.clone(), models_dir, model)
+        };
+        Self::new(ServerManager::new(options))
+    }
+}
+
+impl Service for LlmServerService {
+    fn name(&self) -> &str {
+        LLM_SERVER
+    }
+
+    fn start(&mut self) -> ServiceResult<()> {
+        self.handle.start()
+    }
+
+    fn stop(&mut self) -> ServiceResult<()> {
+        self.handle.stop()
+    }
+
+    fn health(&self) -> ServiceHealth {
+        self.handle.health()
+    }
+}
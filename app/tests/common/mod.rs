@@ -0,0 +1,19 @@
+//! Helpers shared by the integration tests.
+
+use lucastra_app::SystemState;
+use lucastra_config::Config;
+use std::sync::OnceLock;
+use tempfile::TempDir;
+
+/// State booted from the default config with `auto_start` off and data in a
+/// temp dir, so tests never read the user's config or start an LLM server.
+/// The config is handed over directly instead of through
+/// `LUCASTRA_CONFIG_HOME`, which other tests set and clear concurrently.
+pub fn test_state() -> SystemState {
+    static DATA: OnceLock<TempDir> = OnceLock::new();
+    let data = DATA.get_or_init(|| tempfile::tempdir().expect("create temp data dir"));
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    config.storage.data_dir = data.path().to_path_buf();
+    SystemState::with_config(config).expect("Failed to create SystemState")
+}
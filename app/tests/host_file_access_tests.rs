@@ -1,17 +1,19 @@
-use lucastra_app::SystemState;
+mod common;
+
+use common::test_state;
 use lucastra_config::SecurityConfig;
 use lucastra_tools::file_access::{FileAccessTool, FileAccessValidator, FileOperation};
+use lucastra_tools::Tool;
 use std::fs;
 use std::path::PathBuf;
+use tempfile::TempDir;
 
 #[test]
 fn test_host_file_access_integration() {
-    let state = SystemState::new().expect("Failed to create SystemState");
+    let state = test_state();
 
     // Create test directory
-    let test_dir = std::env::temp_dir().join("lucastra_hfa_test");
-    let _ = fs::remove_dir_all(&test_dir);
-    fs::create_dir_all(&test_dir).unwrap();
+    let _test_dir = TempDir::new().unwrap();
 
     // Test that we can create validator and check paths
     let config = &state.config.security;
@@ -22,9 +24,6 @@ fn test_host_file_access_integration() {
         config.allow_host_write,
         config.allow_usb,
     );
-
-    // Cleanup
-    let _ = fs::remove_dir_all(&test_dir);
 }
 
 #[test]
@@ -57,7 +56,7 @@ fn test_file_access_validator_with_security_config() {
 
 #[test]
 fn test_file_access_tool_execution() {
-    let state = SystemState::new().expect("Failed to create SystemState");
+    let state = test_state();
 
     // Create test directory
     let test_dir = std::env::temp_dir().join("lucastra_fat_test");
@@ -147,3 +146,47 @@ fn test_security_config_whitelist_paths() {
         assert!(path.is_absolute() || path.to_string_lossy().contains("~"));
     }
 }
+
+#[test]
+fn test_denied_delete_keeps_file() {
+    let mut state = test_state();
+
+    let temp = TempDir::new().unwrap();
+    let test_dir = temp.path().canonicalize().unwrap();
+    let test_file = test_dir.join("keep.txt");
+    fs::write(&test_file, "important").unwrap();
+
+    state.config.security.allowed_host_dirs = vec![test_dir.display().to_string()];
+    state.config.security.allow_host_write = true;
+    state.config.security.require_write_approval = true;
+
+    let result = state.execute_tool(Tool::HostFileAccess {
+        operation: FileOperation::Delete,
+        path: test_file.display().to_string(),
+        dest_path: None,
+    });
+    assert!(result.is_pending());
+    assert!(test_file.exists());
+    let token = result.approval_token.unwrap();
+    assert_eq!(state.pending_approvals().len(), 1);
+
+    let result = state.deny(&token);
+    assert!(result.success);
+    assert!(test_file.exists());
+    assert!(state.pending_approvals().is_empty());
+
+    let audit_log = lucastra_config::get_logs_dir()
+        .unwrap()
+        .join("file_access_audit.log");
+    let audit = fs::read_to_string(audit_log).unwrap();
+    let entry = audit
+        .lines()
+        .rev()
+        .find(|line| line.contains("keep.txt"))
+        .expect("denial should be audited");
+    assert!(entry.contains(r#""user_approved":false"#));
+
+    // A denied token cannot be approved afterwards
+    assert!(!state.approve(&token).success);
+    assert!(test_file.exists());
+}
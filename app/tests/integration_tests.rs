@@ -1,10 +1,18 @@
 use lucastra_app::SystemState;
+use lucastra_compat::Fat32FileSystem;
 use lucastra_config::Config;
-use serde_json::to_string_pretty;
+use lucastra_core::{
+    Command, CommandPayload, DeviceEvent, DeviceInfo, DeviceType, ErrorCode, LuCastraError,
+    ResponsePayload,
+};
+use lucastra_devices::{DeviceEnumerator, DeviceManager};
+use lucastra_tools::{InstallMethod, Tool};
+use std::collections::HashMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 fn unique_temp_dir(prefix: &str) -> PathBuf {
     let nanos = SystemTime::now()
@@ -15,7 +23,7 @@ fn unique_temp_dir(prefix: &str) -> PathBuf {
 }
 
 fn ensure_config_home_with_default() -> PathBuf {
-    // Create an isolated temp config directory with a default config.json
+    // Create an isolated temp config directory with a default config.toml
     let temp_dir = unique_temp_dir("lucastra_system_state_test");
     let _ = fs::remove_dir_all(&temp_dir);
 
@@ -26,12 +34,12 @@ fn ensure_config_home_with_default() -> PathBuf {
     let data_dir = temp_dir.join("data");
     fs::create_dir_all(&data_dir).expect("Failed to create data dir");
 
-    let cfg = Config::default();
-    let cfg_path = temp_dir.join("config.json");
-    fs::write(&cfg_path, to_string_pretty(&cfg).expect("serialize config"))
-        .expect("write config.json");
-
     env::set_var("LUCASTRA_CONFIG_HOME", &temp_dir);
+
+    // Tests must not launch or download an LLM server
+    let mut cfg = Config::default();
+    cfg.llm.auto_start = false;
+    cfg.save().expect("write config.toml");
     temp_dir
 }
 
@@ -46,10 +54,39 @@ fn test_system_state_initialization() {
     assert!(!state.config.llm.server_url.is_empty());
     assert!(!state.config.storage.data_dir.as_os_str().is_empty());
 
+    let status = state.service_registry.status();
+    assert!(status.iter().any(|s| s.name == "search"));
+    assert!(status
+        .iter()
+        .all(|s| s.state == lucastra_services::ServiceState::Running));
+
     let _ = fs::remove_dir_all(temp_dir);
     env::remove_var("LUCASTRA_CONFIG_HOME");
 }
 
+#[test]
+fn test_metrics_totals_are_saved_at_shutdown() {
+    let temp_dir = ensure_config_home_with_default();
+    let state = SystemState::new().unwrap();
+    for _ in 0..3 {
+        state.metrics.record_command();
+    }
+    let path = state
+        .metrics
+        .state_path()
+        .expect("metrics persist by default")
+        .to_path_buf();
+    drop(state);
+
+    // The next launch starts its session at zero on top of these totals
+    let next = lucastra_app::Metrics::persistent(path);
+    assert_eq!(next.snapshot().command_count, 0);
+    assert!(next.lifetime().command_count >= 3);
+    assert!(next.lifetime().sessions >= 2);
+
+    let _ = fs::remove_dir_all(temp_dir);
+}
+
 #[test]
 fn test_config_persistence_roundtrip() {
     use lucastra_config::Config;
@@ -122,3 +159,1217 @@ fn test_filesystem_operations() {
     let _ = fs::remove_dir_all(temp_dir);
     env::remove_var("LUCASTRA_CONFIG_HOME");
 }
+
+fn command(payload: CommandPayload) -> Command {
+    Command {
+        id: "cmd-1".to_string(),
+        payload,
+    }
+}
+
+#[test]
+fn test_file_commands_round_trip() {
+    let temp_dir = ensure_config_home_with_default();
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let written = state.handle_command(command(CommandPayload::WriteFile {
+        path: "/mnt/root/notes/todo.txt".to_string(),
+        content: b"ship it".to_vec(),
+    }));
+    assert!(
+        matches!(written.payload, ResponsePayload::Success(ref msg) if msg.contains("7 bytes"))
+    );
+
+    let read = state.handle_command(command(CommandPayload::ReadFile {
+        path: "/mnt/root/notes/todo.txt".to_string(),
+    }));
+    assert_eq!(read.command_id, "cmd-1");
+    assert!(matches!(read.payload, ResponsePayload::Content(ref data) if data == b"ship it"));
+
+    let listed = state.handle_command(command(CommandPayload::ListFiles {
+        path: "/mnt/root/notes".to_string(),
+    }));
+    match listed.payload {
+        ResponsePayload::Files(files) => {
+            assert_eq!(files.len(), 1);
+            assert_eq!(files[0].path, "/mnt/root/notes/todo.txt");
+            assert_eq!(files[0].size, 7);
+        }
+        other => panic!("expected files, got {:?}", other),
+    }
+
+    // Failures come back as error payloads rather than Err
+    let missing = state.handle_command(command(CommandPayload::ReadFile {
+        path: "/mnt/root/missing.txt".to_string(),
+    }));
+    assert!(matches!(missing.payload, ResponsePayload::Error { .. }));
+    let unmounted = state.handle_command(command(CommandPayload::Unmount {
+        mount_point: "/mnt/nowhere".to_string(),
+    }));
+    assert!(matches!(unmounted.payload, ResponsePayload::Error { .. }));
+
+    assert_eq!(state.metrics.snapshot().command_count, 5);
+
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_execute_tool_enforces_role() {
+    let temp_dir = ensure_config_home_with_default();
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+    state.config.security.role = "reader".to_string();
+
+    let result = state.execute_tool(Tool::Install {
+        program: "rust".to_string(),
+        method: InstallMethod::Command {
+            cmd: "rustc".to_string(),
+            args: vec!["--version".to_string()],
+        },
+        dry_run: false,
+    });
+    assert!(!result.success);
+    assert_eq!(result.tool, "permission_denied");
+
+    state.config.security.enable_rbac = false;
+    let result = state.execute_tool(Tool::Read {
+        path: "/mnt/root/missing.txt".to_string(),
+        offset: None,
+        length: None,
+    });
+    assert_eq!(result.tool, "read");
+
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_downloads_wait_for_approval() {
+    let temp_dir = ensure_config_home_with_default();
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+    let download = |sha256: Option<&str>| Tool::Install {
+        program: "app".to_string(),
+        method: InstallMethod::Download {
+            url: "https://example.com/setup.exe".to_string(),
+            installer_args: vec![],
+            sha256: sha256.map(str::to_string),
+        },
+        dry_run: false,
+    };
+
+    // Installing needs admin, which the default role is not
+    let result = state.execute_tool(download(Some(&"ab".repeat(32))));
+    assert!(!result.success);
+    assert!(result.output.contains("may not use the install tool"));
+    state.config.security.role = "admin".to_string();
+
+    let result = state.execute_tool(download(None));
+    assert!(!result.success);
+    assert!(result.output.contains("need a sha256 checksum"));
+    assert!(state.pending_installs().is_empty());
+
+    let result = state.execute_tool(download(Some(&"ab".repeat(32))));
+    assert!(!result.success);
+    let token = result.approval_token.expect("download should be queued");
+    assert_eq!(state.pending_installs().len(), 1);
+    assert!(state.pending_approvals().is_empty());
+
+    let result = state.deny(&token);
+    assert!(result.success);
+    assert!(result.output.contains("cancelled"));
+    assert!(state.pending_installs().is_empty());
+    assert!(!state.approve(&token).success);
+
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_execute_tools_from_json_calculate() {
+    let temp_dir = ensure_config_home_with_default();
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let results = state.execute_tools_from_json(
+        r#"[
+            {"tool":"Calculate","params":{"expression":"2*pi"}},
+            {"tool":"Calculate","params":{"expression":"5 / 0"}},
+            {"tool":"Calculate","params":{"expression":"2 + * 3"}}
+        ]"#,
+    );
+    assert_eq!(results.len(), 3);
+    assert!(results.iter().all(|r| r.tool == "calculate"));
+
+    assert!(results[0].success);
+    let output: serde_json::Value = serde_json::from_str(&results[0].output).unwrap();
+    assert_eq!(output["expression"], "2 * pi");
+    let value = output["result"].as_f64().unwrap();
+    assert!((value - 2.0 * std::f64::consts::PI).abs() < 1e-9);
+
+    assert!(!results[1].success);
+    assert_eq!(results[1].output, "Division by zero");
+
+    assert!(!results[2].success);
+    assert!(results[2].output.starts_with("Parse error:"));
+
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+/// Reports whatever device list the test last set.
+struct FakeEnumerator(Mutex<Vec<DeviceInfo>>);
+
+impl DeviceEnumerator for FakeEnumerator {
+    fn enumerate(&self) -> Vec<DeviceInfo> {
+        self.0.lock().unwrap().clone()
+    }
+}
+
+#[test]
+fn test_removed_device_is_dropped_from_search_index() {
+    let temp_dir = ensure_config_home_with_default();
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let stick = DeviceInfo {
+        path: "/dev/sdx1".to_string(),
+        device_type: DeviceType::BlockDevice,
+        name: "USB Storage".to_string(),
+        size_bytes: Some(1024),
+        mounted: true,
+        mount_point: Some("/media/stick".to_string()),
+    };
+    let enumerator = Arc::new(FakeEnumerator(Mutex::new(vec![stick])));
+    state.device_manager = DeviceManager::new().with_enumerator(enumerator.clone());
+    state.device_manager.scan().unwrap();
+    state.device_manager.watch(Duration::from_millis(10));
+    state
+        .search_service
+        .index_document("/media/stick/notes.txt", "hotplug notes")
+        .unwrap();
+
+    enumerator.0.lock().unwrap().clear();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    let mut events = Vec::new();
+    while events.is_empty() && Instant::now() < deadline {
+        std::thread::sleep(Duration::from_millis(10));
+        events = state.process_device_events();
+    }
+
+    assert!(matches!(&events[..], [DeviceEvent::Removed(d)] if d.path == "/dev/sdx1"));
+    assert!(!state
+        .search_service
+        .paths()
+        .iter()
+        .any(|path| path.starts_with("/media/stick")));
+
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_mount_usb_fat32_volume_and_read_through_tools() {
+    let temp_dir = ensure_config_home_with_default();
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let image = lucastra_compat::fixtures::sample_image();
+    let stick = DeviceInfo {
+        path: "/dev/sdz1".to_string(),
+        device_type: DeviceType::BlockDevice,
+        name: "USB Storage".to_string(),
+        size_bytes: Some(image.len() as u64),
+        mounted: false,
+        mount_point: None,
+    };
+    let open_image = |_: &str| Fat32FileSystem::from_bytes(image.clone());
+    let device_path = stick.path.clone();
+    state.device_manager =
+        DeviceManager::new().with_enumerator(Arc::new(FakeEnumerator(Mutex::new(vec![stick]))));
+    state.device_manager.scan().unwrap();
+
+    state.config.security.allow_usb = false;
+    assert!(matches!(
+        state.mount_block_device_with(&device_path, "/mnt/usb0", open_image),
+        Err(LuCastraError::PermissionDenied(_))
+    ));
+
+    state.config.security.allow_usb = true;
+    state
+        .mount_block_device_with(&device_path, "/mnt/usb0", open_image)
+        .expect("mount FAT32 image");
+    assert!(
+        state
+            .device_manager
+            .get_device(&device_path)
+            .unwrap()
+            .mounted
+    );
+
+    let result = state.execute_tool(Tool::Read {
+        path: "/mnt/usb0/readme.txt".to_string(),
+        offset: None,
+        length: None,
+    });
+    assert!(result.success, "{}", result.output);
+    assert_eq!(result.output, "Welcome to LucAstra");
+
+    let response = state.handle_command(command(CommandPayload::Unmount {
+        mount_point: "/mnt/usb0".to_string(),
+    }));
+    assert!(matches!(response.payload, ResponsePayload::Success(_)));
+    assert!(
+        !state
+            .device_manager
+            .get_device(&device_path)
+            .unwrap()
+            .mounted
+    );
+
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_config_file_edit_reloads_llm_service() {
+    use lucastra_config::ConfigEvent;
+
+    let temp_dir = ensure_config_home_with_default();
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let mut edited = state.config.clone();
+    edited.llm.server_url = "http://127.0.0.1:9999".to_string();
+    // Written behind the state's back, as an editor would
+    edited.save().expect("write config.toml");
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let mut reloaded = false;
+    while !reloaded && Instant::now() < deadline {
+        reloaded = state
+            .process_config_events()
+            .iter()
+            .any(|event| matches!(event, ConfigEvent::LlmChanged(_)));
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    assert!(reloaded, "config edit was not picked up");
+    assert_eq!(state.config.llm.server_url, "http://127.0.0.1:9999");
+    assert_eq!(state.llm_service.endpoint(), "http://127.0.0.1:9999");
+
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_auto_start_adopts_running_llm_server() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    // Stands in for a llamafile server someone already started
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream
+                .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\nConnection: close\r\n\r\nok");
+        }
+    });
+
+    let temp_dir = ensure_config_home_with_default();
+    let mut config = Config::default();
+    config.llm.auto_start = true;
+    config.llm.server_url = format!("http://{}", addr);
+    config.save().expect("write config.toml");
+
+    let state = SystemState::new().expect("Failed to create SystemState");
+    // The server starts in the background, reporting degraded until then
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let server = loop {
+        let server = state
+            .service_registry
+            .status()
+            .into_iter()
+            .find(|s| s.name == "llm-server")
+            .expect("llm-server registered");
+        if !matches!(
+            server.health,
+            Some(lucastra_services::ServiceHealth::Degraded(_))
+        ) || Instant::now() > deadline
+        {
+            break server;
+        }
+        std::thread::sleep(Duration::from_millis(20));
+    };
+    assert_eq!(server.state, lucastra_services::ServiceState::Running);
+    assert_eq!(
+        server.health,
+        Some(lucastra_services::ServiceHealth::Healthy)
+    );
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_failed_llm_server_start_shows_in_health() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("free port")
+        .port();
+    let temp_dir = ensure_config_home_with_default();
+    let mut config = Config::default();
+    config.llm.auto_start = true;
+    config.llm.server_url = format!("http://127.0.0.1:{}", port);
+    config.llm.server_binary = Some(temp_dir.join("missing-llamafile"));
+    config.save().expect("write config.toml");
+
+    let state = SystemState::new().expect("boot should not wait for the server");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let health = loop {
+        let health = state
+            .service_registry
+            .status()
+            .into_iter()
+            .find(|s| s.name == "llm-server")
+            .and_then(|s| s.health);
+        match health {
+            Some(lucastra_services::ServiceHealth::Degraded(_)) if Instant::now() < deadline => {
+                std::thread::sleep(Duration::from_millis(20))
+            }
+            health => break health,
+        }
+    };
+    assert!(
+        matches!(&health, Some(lucastra_services::ServiceHealth::Unhealthy(e)) if e.contains("missing-llamafile")),
+        "{:?}",
+        health
+    );
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+/// Stands in for llamafile, answering every completion with `answer`.
+/// Returns its address and the prompts it was sent.
+fn serve_llm(answer: &'static str) -> (std::net::SocketAddr, std::sync::mpsc::Receiver<String>) {
+    serve_llm_after(answer, Duration::ZERO)
+}
+
+/// [`serve_llm`], taking `delay` to answer once a prompt has arrived.
+fn serve_llm_after(
+    answer: &'static str,
+    delay: Duration,
+) -> (std::net::SocketAddr, std::sync::mpsc::Receiver<String>) {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    let (prompts, received) = std::sync::mpsc::channel();
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            let mut line = String::new();
+            while reader.read_line(&mut line).unwrap_or(0) > 2 {
+                if let Some((name, value)) = line.split_once(':') {
+                    if name.eq_ignore_ascii_case("content-length") {
+                        content_length = value.trim().parse().unwrap_or(0);
+                    }
+                }
+                line.clear();
+            }
+            let mut body = vec![0u8; content_length];
+            let _ = reader.read_exact(&mut body);
+            if let Ok(request) = serde_json::from_slice::<serde_json::Value>(&body) {
+                let _ = prompts.send(request["prompt"].as_str().unwrap_or_default().to_string());
+            }
+            std::thread::sleep(delay);
+            let answer = serde_json::json!({"choices": [{"text": answer}]}).to_string();
+            let _ = reader.get_mut().write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    answer.len(),
+                    answer
+                )
+                .as_bytes(),
+            );
+        }
+    });
+    (addr, received)
+}
+
+#[test]
+fn test_off_corpus_query_is_answered_without_context() {
+    let (addr, _prompts) = serve_llm("I don't know.");
+
+    let temp_dir = ensure_config_home_with_default();
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    config.llm.server_url = format!("http://{}", addr);
+    config.save().expect("write config.toml");
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let (_, request) = state
+        .prepare_query("quantum chromodynamics lattice", Some(true), None, &[])
+        .unwrap();
+    assert_eq!(request.context.map(|c| c.len()), Some(0));
+
+    let response = state.handle_command(command(CommandPayload::Query {
+        text: "quantum chromodynamics lattice".to_string(),
+        use_rag: Some(true),
+        conversation_id: None,
+        attachments: Vec::new(),
+    }));
+    match response.payload {
+        ResponsePayload::RagAnswer {
+            text,
+            sources,
+            used_context,
+        } => {
+            assert_eq!(text, "I don't know.");
+            assert!(sources.is_empty());
+            assert!(!used_context);
+        }
+        other => panic!("expected a RAG answer, got {:?}", other),
+    }
+
+    // The answer's tokens are tracked and saved
+    assert_eq!(state.usage.lock().unwrap().today().requests, 1);
+    let saved =
+        lucastra_llm::UsageTracker::load(state.config.storage.data_dir.join("usage.json")).unwrap();
+    assert_eq!(saved.today().requests, 1);
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+/// Spans and events seen by a [`SpanCapture`].
+#[derive(Default)]
+struct Captured {
+    /// Name, parent span's name and fields of each span, in creation order.
+    spans: Vec<(String, Option<String>, HashMap<String, String>)>,
+    /// Message of each event and the spans it was logged in, innermost first.
+    events: Vec<(String, Vec<usize>)>,
+}
+
+impl Captured {
+    fn span(&self, name: &str) -> (&Option<String>, &HashMap<String, String>) {
+        let (_, parent, fields) = self
+            .spans
+            .iter()
+            .find(|(span, _, _)| span == name)
+            .unwrap_or_else(|| panic!("no {} span", name));
+        (parent, fields)
+    }
+}
+
+/// Layer recording spans, their fields and the span scope of events.
+#[derive(Clone, Default)]
+struct SpanCapture(Arc<Mutex<Captured>>);
+
+struct FieldVisitor<'a>(&'a mut HashMap<String, String>);
+
+impl tracing::field::Visit for FieldVisitor<'_> {
+    fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl<S> tracing_subscriber::Layer<S> for SpanCapture
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let span = ctx.span(id).expect("new span");
+        let mut fields = HashMap::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        let mut captured = self.0.lock().unwrap();
+        span.extensions_mut().insert(captured.spans.len());
+        captured.spans.push((
+            span.name().to_string(),
+            span.parent().map(|parent| parent.name().to_string()),
+            fields,
+        ));
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let span = ctx.span(id).expect("recorded span");
+        let index = *span.extensions().get::<usize>().expect("captured span");
+        values.record(&mut FieldVisitor(
+            &mut self.0.lock().unwrap().spans[index].2,
+        ));
+    }
+
+    fn on_event(&self, event: &tracing::Event<'_>, ctx: tracing_subscriber::layer::Context<'_, S>) {
+        let mut fields = HashMap::new();
+        event.record(&mut FieldVisitor(&mut fields));
+        let scope = ctx
+            .event_scope(event)
+            .into_iter()
+            .flatten()
+            .filter_map(|span| span.extensions().get::<usize>().copied())
+            .collect();
+        let message = fields.remove("message").unwrap_or_default();
+        self.0.lock().unwrap().events.push((message, scope));
+    }
+}
+
+#[test]
+fn test_query_and_tool_call_are_traced() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    let (addr, _prompts) = serve_llm("LucAstra is an operating system [1].");
+    let temp_dir = ensure_config_home_with_default();
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    config.llm.server_url = format!("http://{}", addr);
+    config.save().expect("write config.toml");
+
+    let capture = SpanCapture::default();
+    let subscriber = tracing_subscriber::registry().with(capture.clone());
+    let (state, result) = tracing::subscriber::with_default(subscriber, || {
+        let mut state = SystemState::new().expect("Failed to create SystemState");
+        state.handle_command(Command {
+            id: "trace-1".to_string(),
+            payload: CommandPayload::Query {
+                text: "What is LucAstra?".to_string(),
+                use_rag: Some(true),
+                conversation_id: None,
+                attachments: Vec::new(),
+            },
+        });
+        let result = state.execute_tool(Tool::Calculate {
+            expression: "6 * 7".to_string(),
+        });
+        (state, result)
+    });
+    assert!(result.success);
+
+    let captured = capture.0.lock().unwrap();
+    let (parent, fields) = captured.span("handle_command");
+    assert_eq!(parent, &None);
+    assert_eq!(fields["request_id"], "trace-1");
+    assert_eq!(fields["command"], "query");
+
+    let (parent, fields) = captured.span("search");
+    assert_eq!(parent.as_deref(), Some("handle_command"));
+    assert_eq!(fields["query_len"], "17");
+    assert!(fields.contains_key("result_count"));
+
+    let (parent, fields) = captured.span("inference");
+    assert_eq!(parent.as_deref(), Some("handle_command"));
+    let llm_latency: u64 = fields["latency_ms"].parse().unwrap();
+
+    let (parent, fields) = captured.span("llm_complete");
+    assert_eq!(parent.as_deref(), Some("inference"));
+    assert_eq!(fields["provider"], "llamafile");
+    for field in ["model", "input_tokens", "output_tokens"] {
+        assert!(fields.contains_key(field), "llm_complete has no {}", field);
+    }
+
+    let (parent, fields) = captured.span("execute_tool");
+    assert_eq!(parent, &None);
+    assert_eq!(fields["tool"], "calculate");
+    assert_eq!(fields["success"], "true");
+    assert!(fields.contains_key("latency_ms"));
+
+    // Lines logged while answering carry the request id of their command
+    let (_, scope) = captured
+        .events
+        .iter()
+        .find(|(message, _)| message.starts_with("LLM inference request"))
+        .expect("inference logged");
+    assert!(scope.iter().any(
+        |&span| captured.spans[span].2.get("request_id").map(String::as_str) == Some("trace-1")
+    ));
+
+    // The dashboard reports the latency the trace recorded
+    let snapshot = state.metrics.snapshot();
+    assert_eq!(snapshot.llm_latency.samples, 1);
+    assert_eq!(snapshot.llm_latency.p50_ms, llm_latency);
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_unreachable_llm_answers_with_placeholders() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let temp_dir = ensure_config_home_with_default();
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    config.llm.server_url = format!("http://127.0.0.1:{}", port);
+    config.save().expect("write config.toml");
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let ask = |state: &mut SystemState| {
+        state
+            .handle_command(command(CommandPayload::Query {
+                text: "What is LucAstra?".to_string(),
+                use_rag: Some(true),
+                conversation_id: None,
+                attachments: Vec::new(),
+            }))
+            .payload
+    };
+    let answer = |payload| match payload {
+        ResponsePayload::RagAnswer { text, .. } => text,
+        other => panic!("expected a placeholder answer, got {:?}", other),
+    };
+    let first = answer(ask(&mut state));
+    assert!(first.starts_with("[placeholder]"));
+    assert!(first.contains("## User Query\nWhat is LucAstra?"));
+    assert!(state.llm_service.answered_offline());
+    assert_eq!(answer(ask(&mut state)), first);
+
+    config.llm.offline_placeholders = false;
+    state.update_config(config).unwrap();
+    assert!(matches!(
+        ask(&mut state),
+        ResponsePayload::LlmUnavailable(_)
+    ));
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+/// Provider whose API key is always rejected.
+struct RejectedKeyProvider;
+
+#[async_trait::async_trait]
+impl lucastra_llm::LLMProvider for RejectedKeyProvider {
+    fn name(&self) -> &str {
+        "rejected"
+    }
+
+    fn default_model(&self) -> &str {
+        "rejected-model"
+    }
+
+    async fn health_check(&self) -> lucastra_llm::ProviderResult<bool> {
+        Ok(true)
+    }
+
+    async fn complete(
+        &self,
+        _request: lucastra_llm::CompletionRequest,
+    ) -> lucastra_llm::ProviderResult<lucastra_llm::CompletionResponse> {
+        Err(lucastra_llm::ProviderError::AuthError(
+            "Invalid API key".to_string(),
+        ))
+    }
+}
+
+#[test]
+fn test_rejected_api_key_is_reported_as_auth_failure() {
+    let temp_dir = ensure_config_home_with_default();
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+    state.llm_service = lucastra_llm::LLMService::with_provider(
+        Box::new(RejectedKeyProvider),
+        "https://api.example.com",
+    );
+
+    let response = state.handle_command(command(CommandPayload::Query {
+        text: "What is LucAstra?".to_string(),
+        use_rag: Some(false),
+        conversation_id: None,
+        attachments: Vec::new(),
+    }));
+    match response.payload {
+        ResponsePayload::Error { code, message } => {
+            assert_eq!(code, ErrorCode::AuthFailed);
+            assert!(message.contains("Invalid API key"));
+        }
+        other => panic!("expected an auth error, got {:?}", other),
+    }
+
+    // The cause survives for callers matching on it
+    let error = state
+        .llm_service
+        .infer_blocking(lucastra_llm::InferenceRequest {
+            prompt: "Hello".to_string(),
+            max_tokens: None,
+            temperature: None,
+            context: None,
+            history: Vec::new(),
+        })
+        .unwrap_err();
+    assert!(matches!(
+        error.find::<lucastra_llm::ProviderError>(),
+        Some(lucastra_llm::ProviderError::AuthError(_))
+    ));
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_follow_up_queries_see_the_conversation() {
+    let (addr, prompts) = serve_llm("The scheduler runs tasks round-robin.");
+
+    let temp_dir = ensure_config_home_with_default();
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    config.llm.server_url = format!("http://{}", addr);
+    config.save().expect("write config.toml");
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let query = |text: &str, conversation_id: &str| {
+        command(CommandPayload::Query {
+            text: text.to_string(),
+            use_rag: None,
+            conversation_id: Some(conversation_id.to_string()),
+            attachments: Vec::new(),
+        })
+    };
+    state.handle_command(query("How does the scheduler work?", "chat-1"));
+    state.handle_command(query("And what about the second one?", "chat-1"));
+    state.handle_command(query("Unrelated question", "chat-2"));
+
+    let timeout = Duration::from_secs(5);
+    let first = prompts.recv_timeout(timeout).unwrap();
+    let second = prompts.recv_timeout(timeout).unwrap();
+    let other = prompts.recv_timeout(timeout).unwrap();
+    assert!(!first.contains("## Conversation So Far"));
+    assert!(second.contains(
+        "User: How does the scheduler work?\n\nAssistant: The scheduler runs tasks round-robin."
+    ));
+    assert!(second.ends_with("## User Query\nAnd what about the second one?\n\n## Answer"));
+    assert!(!other.contains("scheduler"));
+
+    state.handle_command(command(CommandPayload::EndConversation {
+        conversation_id: "chat-1".to_string(),
+    }));
+    state.handle_command(query("Start over", "chat-1"));
+    assert!(!prompts.recv_timeout(timeout).unwrap().contains("scheduler"));
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_browsed_pages_are_searchable() {
+    use std::io::{Read, Write};
+    use std::net::TcpListener;
+
+    const PAGE: &str = "<html><head><title>Harbour tides</title></head><body>\
+        <nav><a href=\"/\">Home</a></nav>\
+        <article><p>The harbour tide tables are published every Monday by the port authority.</p>\
+        <p>Spring tides reach their highest point two days after the full moon.</p></article>\
+        </body></html>";
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind");
+    let addr = listener.local_addr().expect("local addr");
+    std::thread::spawn(move || {
+        for mut stream in listener.incoming().flatten() {
+            let mut request = [0u8; 1024];
+            let _ = stream.read(&mut request);
+            let _ = stream.write_all(
+                format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: text/html\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    PAGE.len(),
+                    PAGE
+                )
+                .as_bytes(),
+            );
+        }
+    });
+    let url = format!("http://{}/tides", addr);
+
+    let temp_dir = ensure_config_home_with_default();
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    config.search.index_browsed_pages = true;
+    config.save().expect("write config.toml");
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+    let docs_before = state.search_service.doc_count();
+
+    let doc_id = state.browse(&url).unwrap();
+    assert_eq!(doc_id, Some(format!("web://{}", url)));
+    let results = state.search_service.search("spring tides", 5).unwrap();
+    assert_eq!(results[0].path, format!("web://{}", url));
+
+    // A second visit replaces the page instead of adding a copy
+    state.browse(&url).unwrap();
+    assert_eq!(state.search_service.doc_count(), docs_before + 1);
+
+    let (_, request) = state
+        .prepare_query(
+            "when are harbour tide tables published",
+            Some(true),
+            None,
+            &[],
+        )
+        .unwrap();
+    let context = request.context.unwrap();
+    assert_eq!(context[0].label(), url);
+
+    state.config.search.blocked_domains = vec!["127.0.0.1".to_string()];
+    assert_eq!(state.browse(&url).unwrap(), None);
+    state.config.search.blocked_domains.clear();
+    state.config.search.index_browsed_pages = false;
+    assert_eq!(state.browse(&url).unwrap(), None);
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_dashboard_reports_each_panel() {
+    let temp_dir = ensure_config_home_with_default();
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    // Nothing listens on the discard port
+    config.llm.server_url = "http://127.0.0.1:9".to_string();
+    config.save().expect("write config.toml");
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let dashboard = state.dashboard();
+    assert!(dashboard.devices.is_ok());
+    assert!(dashboard
+        .mounts
+        .iter()
+        .any(|m| m.mount_point == "/mnt/root"));
+    let search = dashboard.search.unwrap();
+    assert_eq!(search.doc_count, state.search_service.doc_count());
+    assert!(search.index_bytes.unwrap() > 0);
+    assert_eq!(dashboard.llm.endpoint, "http://127.0.0.1:9");
+    assert_eq!(dashboard.llm.model, "7b (4bit)");
+
+    let response = state.handle_command(command(CommandPayload::TestLlmConnection));
+    match response.payload {
+        ResponsePayload::Error { code, message } => {
+            assert_eq!(code, ErrorCode::LlmUnavailable);
+            assert_eq!(
+                message,
+                "LLM server at http://127.0.0.1:9 didn't pass its health check"
+            )
+        }
+        other => panic!("expected an error, got {:?}", other),
+    }
+    assert_eq!(state.dashboard().metrics.command_count, 1);
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_index_path_stays_inside_allowed_dirs() {
+    let temp_dir = ensure_config_home_with_default();
+    let docs = temp_dir.join("docs");
+    let outside = temp_dir.join("outside");
+    fs::create_dir_all(&docs).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    fs::write(docs.join("notes.md"), "quarterly onboarding checklist").unwrap();
+    fs::write(outside.join("secret.txt"), "not for the index").unwrap();
+
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    config.security.allowed_host_dirs = vec![docs.display().to_string()];
+    config.save().expect("write config.toml");
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+    let docs_before = state.search_service.doc_count();
+
+    let path = docs.display().to_string();
+    let response = state.handle_command(command(CommandPayload::IndexPath { path: path.clone() }));
+    match response.payload {
+        ResponsePayload::Success(text) => {
+            assert_eq!(text, format!("Indexed 1 files from {} (0 skipped)", path))
+        }
+        other => panic!("expected success, got {:?}", other),
+    }
+    assert_eq!(state.search_service.doc_count(), docs_before + 1);
+
+    let response = state.handle_command(command(CommandPayload::IndexPath {
+        path: outside.display().to_string(),
+    }));
+    assert!(matches!(
+        response.payload,
+        ResponsePayload::Error {
+            code: ErrorCode::PermissionDenied,
+            ..
+        }
+    ));
+    assert_eq!(state.search_service.doc_count(), docs_before + 1);
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[test]
+fn test_attachments_stay_inside_allowed_dirs() {
+    let temp_dir = ensure_config_home_with_default();
+    let docs = temp_dir.join("docs");
+    let outside = temp_dir.join("outside");
+    fs::create_dir_all(&docs).unwrap();
+    fs::create_dir_all(&outside).unwrap();
+    fs::write(docs.join("plan.txt"), "ship the harbour release on friday").unwrap();
+    fs::write(outside.join("secret.txt"), "not for the model").unwrap();
+
+    let mut config = Config::default();
+    config.llm.auto_start = false;
+    config.security.allowed_host_dirs = vec![docs.display().to_string()];
+    config.save().expect("write config.toml");
+    let mut state = SystemState::new().expect("Failed to create SystemState");
+
+    let attachment = state.read_attachment(&docs.join("plan.txt")).unwrap();
+    assert_eq!(attachment.text, "ship the harbour release on friday");
+    assert!(matches!(
+        state.read_attachment(&outside.join("secret.txt")),
+        Err(LuCastraError::PermissionDenied(_))
+    ));
+
+    // Attachments are sources even without search, ahead of any results
+    let (_, request) = state
+        .prepare_query(
+            "when do we ship?",
+            None,
+            None,
+            std::slice::from_ref(&attachment),
+        )
+        .unwrap();
+    let context = request.context.unwrap();
+    assert_eq!(context.len(), 1);
+    assert!(context[0].is_attachment());
+    assert_eq!(context[0].path, attachment.name);
+
+    drop(state);
+    let _ = fs::remove_dir_all(temp_dir);
+    env::remove_var("LUCASTRA_CONFIG_HOME");
+}
+
+#[cfg(all(feature = "rpc", unix))]
+mod rpc {
+    use super::*;
+    use lucastra_app::RpcServer;
+    use lucastra_core::{Response, RpcError, RpcRequest, RpcResponse};
+    use serde_json::{json, Value};
+    use std::io::{BufRead, BufReader, Write};
+    use std::os::unix::net::UnixStream;
+
+    /// A client connected to `server`'s socket.
+    struct Client {
+        reader: BufReader<UnixStream>,
+        writer: UnixStream,
+        next_id: u64,
+    }
+
+    impl Client {
+        fn connect(server: &RpcServer) -> Self {
+            let stream = UnixStream::connect(server.socket_path()).expect("connect");
+            stream
+                .set_read_timeout(Some(Duration::from_secs(10)))
+                .unwrap();
+            Self {
+                writer: stream.try_clone().unwrap(),
+                reader: BufReader::new(stream),
+                next_id: 0,
+            }
+        }
+
+        fn authenticated(server: &RpcServer) -> Self {
+            let token = fs::read_to_string(server.token_path()).unwrap();
+            let mut client = Self::connect(server);
+            let response = client.call("authenticate", json!({ "token": token }));
+            assert_eq!(response.result, Some(json!({"authenticated": true})));
+            client
+        }
+
+        fn call(&mut self, method: &str, params: Value) -> RpcResponse {
+            self.next_id += 1;
+            let request = RpcRequest::new(self.next_id, method, params);
+            let mut line = serde_json::to_vec(&request).unwrap();
+            line.push(b'\n');
+            self.writer.write_all(&line).unwrap();
+            self.read().expect("a response")
+        }
+
+        fn read(&mut self) -> Option<RpcResponse> {
+            let mut line = String::new();
+            match self.reader.read_line(&mut line).unwrap() {
+                0 => None,
+                _ => Some(serde_json::from_str(&line).unwrap()),
+            }
+        }
+    }
+
+    fn start() -> (PathBuf, Arc<Mutex<SystemState>>, RpcServer) {
+        let temp_dir = ensure_config_home_with_default();
+        let state = SystemState::new().expect("Failed to create SystemState");
+        let mut config = state.config.rpc.clone();
+        config.socket_path = Some(temp_dir.join("rpc.sock"));
+        let state = Arc::new(Mutex::new(state));
+        let server = RpcServer::start(&state, &config).expect("start the RPC server");
+        (temp_dir, state, server)
+    }
+
+    fn command_response(response: RpcResponse) -> Response {
+        serde_json::from_value(response.result.expect("a result")).unwrap()
+    }
+
+    #[test]
+    fn test_search_round_trip() {
+        let (temp_dir, state, server) = start();
+        state
+            .lock()
+            .unwrap()
+            .search_service
+            .index_document("/notes/rpc.md", "scripting lucastra over a socket")
+            .unwrap();
+
+        let mut client = Client::authenticated(&server);
+        let response = client.call("search", json!({"query": "socket"}));
+        assert_eq!(response.id, json!(2));
+        match command_response(response).payload {
+            ResponsePayload::SearchResults(page) => {
+                assert_eq!(page.results[0].path, "/notes/rpc.md")
+            }
+            other => panic!("expected search results, got {:?}", other),
+        }
+
+        let response = client.call("reboot", Value::Null);
+        assert_eq!(response.error.unwrap().code, RpcError::METHOD_NOT_FOUND);
+        let response = client.call(
+            "execute_tool",
+            json!({"tool": "Search", "params": {"query": "socket"}}),
+        );
+        assert_eq!(response.result.unwrap()["success"], json!(true));
+
+        drop(client);
+        drop(server);
+        drop(state);
+        let _ = fs::remove_dir_all(temp_dir);
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_connections_without_the_token_are_closed() {
+        let (temp_dir, state, server) = start();
+
+        let mut client = Client::connect(&server);
+        let response = client.call("status", Value::Null);
+        assert_eq!(response.error.unwrap().code, RpcError::UNAUTHORIZED);
+        assert!(client.read().is_none(), "connection should be closed");
+
+        let mut client = Client::connect(&server);
+        let response = client.call("authenticate", json!({"token": "guess"}));
+        assert_eq!(response.error.unwrap().code, RpcError::UNAUTHORIZED);
+        assert!(client.read().is_none(), "connection should be closed");
+
+        drop(server);
+        drop(state);
+        let _ = fs::remove_dir_all(temp_dir);
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_concurrent_clients_get_their_own_responses() {
+        let (temp_dir, state, server) = start();
+
+        std::thread::scope(|scope| {
+            for client_id in 0..4 {
+                let server = &server;
+                scope.spawn(move || {
+                    let mut client = Client::authenticated(server);
+                    for n in 0..25 {
+                        let message = format!("client {} message {}", client_id, n);
+                        let response = client.call("echo", json!({ "message": message }));
+                        assert_eq!(response.id, json!(client.next_id));
+                        match command_response(response).payload {
+                            ResponsePayload::Success(text) => {
+                                assert_eq!(text, format!("Echo: {}", message))
+                            }
+                            other => panic!("expected an echo, got {:?}", other),
+                        }
+                    }
+                });
+            }
+        });
+
+        drop(server);
+        drop(state);
+        let _ = fs::remove_dir_all(temp_dir);
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_slow_query_does_not_hold_the_state() {
+        let (addr, prompts) = serve_llm_after("Slowly.", Duration::from_secs(2));
+        let (temp_dir, state, server) = start();
+        state.lock().unwrap().llm_service =
+            lucastra_llm::LLMService::new(format!("http://{}", addr));
+
+        std::thread::scope(|scope| {
+            let query = scope.spawn(|| {
+                let mut client = Client::authenticated(&server);
+                client.call("query", json!({"text": "take your time", "use_rag": false}))
+            });
+
+            prompts
+                .recv_timeout(Duration::from_secs(10))
+                .expect("the query reaches the LLM");
+            assert!(
+                state.try_lock().is_ok(),
+                "the state should be free while the LLM answers"
+            );
+
+            match command_response(query.join().unwrap()).payload {
+                ResponsePayload::Success(text) => assert_eq!(text, "Slowly."),
+                other => panic!("expected an answer, got {:?}", other),
+            }
+        });
+
+        drop(server);
+        drop(state);
+        let _ = fs::remove_dir_all(temp_dir);
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_server_stops_with_the_system_state() {
+        let (temp_dir, state, server) = start();
+        let socket = server.socket_path().to_path_buf();
+        let mut client = Client::authenticated(&server);
+
+        drop(state);
+        assert!(client.read().is_none(), "connection should be closed");
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while socket.exists() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        assert!(!socket.exists(), "socket should be removed");
+
+        drop(server);
+        let _ = fs::remove_dir_all(temp_dir);
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+}
@@ -1,8 +1,10 @@
-use lucastra_app::SystemState;
+mod common;
+
+use common::test_state;
 
 #[test]
 fn test_search_service_integration() {
-    let state = SystemState::new().expect("Failed to create SystemState");
+    let state = test_state();
 
     // Test that the search service is initialized
     assert!(state.config.search.max_results > 0);
@@ -10,7 +12,7 @@ fn test_search_service_integration() {
 
 #[test]
 fn test_llm_service_integration() {
-    let state = SystemState::new().expect("Failed to create SystemState");
+    let state = test_state();
 
     // Test that the LLM service is accessible
     let server_url = &state.config.llm.server_url;
@@ -20,7 +22,7 @@ fn test_llm_service_integration() {
 
 #[test]
 fn test_rag_pipeline_ready() {
-    let state = SystemState::new().expect("Failed to create SystemState");
+    let state = test_state();
 
     // Verify that RAG components are available
     let has_search = state.config.search.max_results > 0;
@@ -34,7 +36,7 @@ fn test_rag_pipeline_ready() {
 
 #[test]
 fn test_document_indexing() {
-    let state = SystemState::new().expect("Failed to create SystemState");
+    let state = test_state();
 
     // The system state initializes with example documents
     // This test verifies that document indexing can occur
@@ -46,7 +48,7 @@ fn test_document_indexing() {
 
 #[test]
 fn test_search_configuration() {
-    let state = SystemState::new().expect("Failed to create SystemState");
+    let state = test_state();
 
     // Verify search config has proper defaults
     assert!(
@@ -0,0 +1,751 @@
+//! A forgiving HTML tree for text extraction.
+//!
+//! The document is read in one pass by a small state machine: comments,
+//! doctypes and the contents of `<script>`/`<style>` are skipped wherever
+//! they appear, and unclosed `<p>`, `<li>` and table cells are closed the way
+//! browsers do. The tree then yields the full page text, the main content
+//! region and the tables.
+
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+
+/// Elements whose contents are never text.
+const RAW_TEXT: [&str; 5] = ["script", "style", "noscript", "template", "svg"];
+
+/// Elements that can't have children.
+const VOID: [&str; 14] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source",
+    "track", "wbr",
+];
+
+/// Elements that start on a new line.
+const BLOCK: [&str; 31] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "caption",
+    "dd",
+    "div",
+    "dl",
+    "dt",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hr",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "table",
+    "tr",
+    "ul",
+];
+
+/// Elements that are page furniture rather than content.
+const UNLIKELY_TAGS: [&str; 7] = [
+    "nav", "footer", "aside", "header", "form", "button", "select",
+];
+
+/// Widest table rendering, in characters.
+pub const TABLE_MAX_WIDTH: usize = 100;
+
+/// Narrowest a column is squeezed to when a table is too wide.
+const MIN_COLUMN_WIDTH: usize = 3;
+
+const COLUMN_SEPARATOR: &str = " | ";
+
+/// Paragraphs shorter than this don't count towards a region's score.
+const MIN_PARAGRAPH_CHARS: usize = 25;
+
+fn unlikely_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(
+            r"banner|breadcrumb|comment|cookie|consent|footer|masthead|menu|modal|nav|newsletter|popup|promo|related|share|sidebar|social|sponsor|subscribe",
+        )
+        .unwrap()
+    })
+}
+
+fn positive_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r"article|body|content|entry|main|page|post|story|text").unwrap()
+    })
+}
+
+fn class_and_id_pattern() -> &'static Regex {
+    static PATTERN: OnceLock<Regex> = OnceLock::new();
+    PATTERN.get_or_init(|| {
+        Regex::new(r#"(?i)\b(?:class|id)\s*=\s*(?:"([^"]*)"|'([^']*)'|([^\s>]+))"#).unwrap()
+    })
+}
+
+/// A table rendered as aligned plain-text columns
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RenderedTable {
+    pub caption: Option<String>,
+    /// Cell text, row by row
+    pub rows: Vec<Vec<String>>,
+    /// Leading rows made only of `<th>` cells
+    pub header_rows: usize,
+    /// The rows at [`TABLE_MAX_WIDTH`]
+    pub text: String,
+}
+
+impl RenderedTable {
+    pub fn new(caption: Option<String>, rows: Vec<Vec<String>>, header_rows: usize) -> Self {
+        let mut table = Self {
+            caption,
+            rows,
+            header_rows,
+            text: String::new(),
+        };
+        table.text = table.render(TABLE_MAX_WIDTH);
+        table
+    }
+
+    /// Rows with each column padded to its widest cell. When that is wider
+    /// than `max_width`, the widest columns are narrowed and their cells cut
+    /// short with `…`.
+    pub fn render(&self, max_width: usize) -> String {
+        let columns = self.rows.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return String::new();
+        }
+        let mut widths = vec![0; columns];
+        for row in &self.rows {
+            for (width, cell) in widths.iter_mut().zip(row) {
+                *width = (*width).max(cell.chars().count());
+            }
+        }
+
+        let separators = COLUMN_SEPARATOR.len() * (columns - 1);
+        while widths.iter().sum::<usize>() + separators > max_width {
+            let Some(widest) = widths
+                .iter_mut()
+                .filter(|w| **w > MIN_COLUMN_WIDTH)
+                .max_by_key(|w| **w)
+            else {
+                break;
+            };
+            *widest -= 1;
+        }
+
+        let mut lines = Vec::new();
+        if let Some(caption) = &self.caption {
+            lines.push(caption.clone());
+        }
+        for (index, row) in self.rows.iter().enumerate() {
+            let cells: Vec<String> = widths
+                .iter()
+                .enumerate()
+                .map(|(column, &width)| {
+                    let cell = row.get(column).map(String::as_str).unwrap_or("");
+                    format!("{:<width$}", fit(cell, width), width = width)
+                })
+                .collect();
+            lines.push(cells.join(COLUMN_SEPARATOR).trim_end().to_string());
+            if index + 1 == self.header_rows {
+                let rule: Vec<String> = widths.iter().map(|&w| "-".repeat(w)).collect();
+                lines.push(rule.join("-+-"));
+            }
+        }
+        lines.join("\n")
+    }
+}
+
+/// `text` cut to `width` characters, ending in `…` when shortened
+fn fit(text: &str, width: usize) -> String {
+    if text.chars().count() <= width {
+        return text.to_string();
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+#[derive(Debug)]
+struct Node {
+    /// Lowercase tag name; empty for text and the root
+    tag: String,
+    /// Lowercase class and id values, for scoring
+    class_id: String,
+    /// Text of a text node, entities decoded
+    text: String,
+    children: Vec<usize>,
+    parent: usize,
+}
+
+/// Parsed document. Nodes are stored parents first, so every child has a
+/// larger index than its parent.
+#[derive(Debug)]
+pub(crate) struct Document {
+    nodes: Vec<Node>,
+}
+
+enum Token<'a> {
+    Open {
+        name: String,
+        attrs: &'a str,
+        self_closing: bool,
+    },
+    Close(String),
+    Text(&'a str),
+}
+
+impl Document {
+    pub(crate) fn parse(html: &str) -> Self {
+        let mut document = Document {
+            nodes: vec![Node {
+                tag: String::new(),
+                class_id: String::new(),
+                text: String::new(),
+                children: Vec::new(),
+                parent: 0,
+            }],
+        };
+        let mut open = vec![0];
+        for token in tokenize(html) {
+            match token {
+                Token::Open {
+                    name,
+                    attrs,
+                    self_closing,
+                } => {
+                    close_implied(&document, &mut open, &name);
+                    let class_id = class_and_id(attrs);
+                    let void = self_closing || VOID.contains(&name.as_str());
+                    let parent = *open.last().unwrap_or(&0);
+                    let index = document.push(parent, name, class_id, String::new());
+                    if !void {
+                        open.push(index);
+                    }
+                }
+                Token::Close(name) => {
+                    if let Some(at) = open.iter().rposition(|&i| document.nodes[i].tag == name) {
+                        if at > 0 {
+                            open.truncate(at);
+                        }
+                    }
+                }
+                Token::Text(text) => {
+                    let parent = *open.last().unwrap_or(&0);
+                    document.push(parent, String::new(), String::new(), decode(text));
+                }
+            }
+        }
+        document
+    }
+
+    fn push(&mut self, parent: usize, tag: String, class_id: String, text: String) -> usize {
+        let index = self.nodes.len();
+        self.nodes.push(Node {
+            tag,
+            class_id,
+            text,
+            children: Vec::new(),
+            parent,
+        });
+        self.nodes[parent].children.push(index);
+        index
+    }
+
+    /// All visible text, one block per line, with tables aligned
+    pub(crate) fn text(&self) -> String {
+        let mut text = TextBuilder::default();
+        self.render(0, &mut text, &|index| self.nodes[index].tag == "head");
+        text.finish()
+    }
+
+    /// Text of the region that looks like the page's main content, or all
+    /// text when no region stands out
+    pub(crate) fn main_text(&self) -> String {
+        let unlikely = self.unlikely();
+        match self.main_region(&unlikely) {
+            Some(region) => {
+                let mut text = TextBuilder::default();
+                self.render(region, &mut text, &|index| unlikely[index]);
+                text.finish()
+            }
+            None => self.text(),
+        }
+    }
+
+    /// Every table that isn't nested in another one
+    pub(crate) fn tables(&self) -> Vec<RenderedTable> {
+        (0..self.nodes.len())
+            .filter(|&i| self.nodes[i].tag == "table" && self.enclosing_table(i).is_none())
+            .map(|i| self.table(i))
+            .collect()
+    }
+
+    /// Append the text under `index`, leaving out nodes `skip` selects
+    fn render(&self, index: usize, out: &mut TextBuilder, skip: &dyn Fn(usize) -> bool) {
+        if skip(index) {
+            return;
+        }
+        let node = &self.nodes[index];
+        match node.tag.as_str() {
+            "" if index != 0 => out.push_text(&node.text),
+            "br" => out.line_break(),
+            "table" => {
+                out.line_break();
+                out.push_block(&self.table(index).text);
+                out.line_break();
+            }
+            "pre" => {
+                out.line_break();
+                out.push_block(&self.raw_text(index));
+                out.line_break();
+            }
+            tag => {
+                let block = BLOCK.contains(&tag);
+                if block {
+                    out.line_break();
+                }
+                for &child in &node.children {
+                    self.render(child, out, skip);
+                }
+                if block {
+                    out.line_break();
+                }
+            }
+        }
+    }
+
+    /// Text under `index` with whitespace kept as written
+    fn raw_text(&self, index: usize) -> String {
+        let node = &self.nodes[index];
+        if node.tag.is_empty() && index != 0 {
+            return node.text.clone();
+        }
+        node.children.iter().map(|&c| self.raw_text(c)).collect()
+    }
+
+    /// Text under `index` on one line
+    fn inline_text(&self, index: usize) -> String {
+        let mut text = TextBuilder::default();
+        self.render(index, &mut text, &|_| false);
+        text.finish()
+            .split_whitespace()
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    fn enclosing_table(&self, mut index: usize) -> Option<usize> {
+        while index != 0 {
+            index = self.nodes[index].parent;
+            if self.nodes[index].tag == "table" {
+                return Some(index);
+            }
+        }
+        None
+    }
+
+    fn table(&self, table: usize) -> RenderedTable {
+        let caption = self.nodes[table]
+            .children
+            .iter()
+            .find(|&&c| self.nodes[c].tag == "caption")
+            .map(|&c| self.inline_text(c))
+            .filter(|caption| !caption.is_empty());
+
+        let mut rows = Vec::new();
+        let mut header_rows = 0;
+        let mut in_header = true;
+        for tr in (table + 1..self.nodes.len())
+            .filter(|&i| self.nodes[i].tag == "tr" && self.enclosing_table(i) == Some(table))
+        {
+            let cells: Vec<usize> = self.nodes[tr]
+                .children
+                .iter()
+                .copied()
+                .filter(|&c| matches!(self.nodes[c].tag.as_str(), "td" | "th"))
+                .collect();
+            if cells.is_empty() {
+                continue;
+            }
+            in_header = in_header && cells.iter().all(|&c| self.nodes[c].tag == "th");
+            if in_header {
+                header_rows += 1;
+            }
+            rows.push(cells.iter().map(|&c| self.inline_text(c)).collect());
+        }
+        RenderedTable::new(caption, rows, header_rows)
+    }
+
+    /// Which nodes are navigation, banners and other furniture, along with
+    /// everything inside them
+    fn unlikely(&self) -> Vec<bool> {
+        let mut unlikely = vec![false; self.nodes.len()];
+        for (index, node) in self.nodes.iter().enumerate().skip(1) {
+            let furniture = UNLIKELY_TAGS.contains(&node.tag.as_str())
+                || (unlikely_pattern().is_match(&node.class_id)
+                    && !positive_pattern().is_match(&node.class_id)
+                    && !matches!(node.tag.as_str(), "body" | "article" | "main"));
+            unlikely[index] = unlikely[node.parent] || furniture;
+        }
+        unlikely
+    }
+
+    /// Readability-style choice of the main content: paragraphs score their
+    /// parent and grandparent by length and commas, and the best-scoring
+    /// element, discounted by how much of its text is links, wins.
+    fn main_region(&self, unlikely: &[bool]) -> Option<usize> {
+        let count = self.nodes.len();
+        let mut text_len = vec![0usize; count];
+        let mut link_len = vec![0usize; count];
+        let mut commas = vec![0usize; count];
+        // Children come after parents, so a reverse pass sees them first
+        for index in (1..count).rev() {
+            let node = &self.nodes[index];
+            if node.tag.is_empty() {
+                text_len[index] = node
+                    .text
+                    .split_whitespace()
+                    .map(|w| w.chars().count())
+                    .sum();
+                commas[index] = node.text.matches(',').count();
+            }
+            if node.tag == "a" {
+                link_len[index] = text_len[index];
+            }
+            if !unlikely[index] {
+                let parent = node.parent;
+                text_len[parent] += text_len[index];
+                link_len[parent] += link_len[index];
+                commas[parent] += commas[index];
+            }
+        }
+
+        let mut scores: Vec<Option<f64>> = vec![None; count];
+        for (index, node) in self.nodes.iter().enumerate() {
+            if !matches!(node.tag.as_str(), "p" | "pre")
+                || unlikely[index]
+                || text_len[index] < MIN_PARAGRAPH_CHARS
+            {
+                continue;
+            }
+            let score = 1.0 + commas[index] as f64 + (text_len[index] as f64 / 100.0).min(3.0);
+            let parent = node.parent;
+            let grandparent = self.nodes[parent].parent;
+            for (ancestor, share) in [(parent, 1.0), (grandparent, 0.5)] {
+                if ancestor == 0 || unlikely[ancestor] {
+                    continue;
+                }
+                let base = scores[ancestor].unwrap_or_else(|| self.initial_score(ancestor));
+                scores[ancestor] = Some(base + score * share);
+            }
+        }
+
+        scores
+            .iter()
+            .enumerate()
+            .filter_map(|(index, score)| {
+                let link_density = link_len[index] as f64 / text_len[index].max(1) as f64;
+                score.map(|score| (index, score * (1.0 - link_density)))
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(index, _)| index)
+    }
+
+    fn initial_score(&self, index: usize) -> f64 {
+        let node = &self.nodes[index];
+        let by_tag = match node.tag.as_str() {
+            "article" | "main" => 10.0,
+            "div" | "section" => 5.0,
+            "pre" | "td" | "blockquote" => 3.0,
+            "ol" | "ul" | "li" | "dl" | "address" => -3.0,
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" | "th" => -5.0,
+            _ => 0.0,
+        };
+        let by_class = if positive_pattern().is_match(&node.class_id) {
+            25.0
+        } else {
+            0.0
+        };
+        by_tag + by_class
+    }
+}
+
+/// Close elements an opening `name` tag implicitly ends, like an open `<p>`
+/// before another `<p>`.
+fn close_implied(document: &Document, open: &mut Vec<usize>, name: &str) {
+    let (closes, boundaries): (&[&str], &[&str]) = match name {
+        "p" | "div" | "ul" | "ol" | "table" | "pre" | "blockquote" | "h1" | "h2" | "h3" | "h4"
+        | "h5" | "h6" | "section" | "article" => (&["p"], &["div", "td", "th", "li"]),
+        "li" => (&["li"], &["ul", "ol"]),
+        "td" | "th" => (&["td", "th"], &["tr", "table"]),
+        "tr" => (&["tr"], &["table"]),
+        "tbody" | "thead" | "tfoot" => (&["tbody", "thead", "tfoot", "tr"], &["table"]),
+        _ => return,
+    };
+    for at in (1..open.len()).rev() {
+        let tag = document.nodes[open[at]].tag.as_str();
+        if boundaries.contains(&tag) {
+            return;
+        }
+        if closes.contains(&tag) {
+            open.truncate(at);
+            return;
+        }
+    }
+}
+
+/// Lowercase class and id values in a tag's attributes
+fn class_and_id(attrs: &str) -> String {
+    class_and_id_pattern()
+        .captures_iter(attrs)
+        .filter_map(|cap| cap.get(1).or(cap.get(2)).or(cap.get(3)))
+        .map(|value| value.as_str().to_ascii_lowercase())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Split the document into tags and text, skipping comments, declarations
+/// and raw-text elements such as scripts.
+fn tokenize(html: &str) -> Vec<Token<'_>> {
+    // ASCII lowercasing keeps byte offsets, so searches can use this copy
+    let lower = html.to_ascii_lowercase();
+    let bytes = html.as_bytes();
+    let mut tokens = Vec::new();
+    let mut pos = 0;
+    let mut text_start = 0;
+
+    while let Some(offset) = html[pos..].find('<') {
+        let lt = pos + offset;
+        let next = bytes.get(lt + 1).copied();
+        let skip_to = |pattern: &str, from: usize| {
+            lower[from..]
+                .find(pattern)
+                .map_or(html.len(), |i| from + i + pattern.len())
+        };
+
+        let (end, token) = if lower[lt..].starts_with("<!--") {
+            (skip_to("-->", lt + 4), None)
+        } else if matches!(next, Some(b'!') | Some(b'?')) {
+            (skip_to(">", lt), None)
+        } else if next == Some(b'/') && bytes.get(lt + 2).is_some_and(u8::is_ascii_alphabetic) {
+            let end = tag_end(html, lt);
+            let name = tag_name(&lower[lt + 2..end]);
+            (end, Some(Token::Close(name)))
+        } else if next.is_some_and(|b| b.is_ascii_alphabetic()) {
+            let end = tag_end(html, lt);
+            let inner = &html[lt + 1..end.saturating_sub(1).max(lt + 1)];
+            let name = tag_name(&lower[lt + 1..end]);
+            let attrs = &inner[name.len().min(inner.len())..];
+            let self_closing = inner.trim_end().ends_with('/');
+            if RAW_TEXT.contains(&name.as_str()) && !self_closing {
+                // Everything up to the matching end tag is not markup
+                let close = format!("</{}", name);
+                let after = lower[end..]
+                    .find(&close)
+                    .map_or(html.len(), |i| tag_end(html, end + i));
+                (after, None)
+            } else {
+                (
+                    end,
+                    Some(Token::Open {
+                        name,
+                        attrs,
+                        self_closing,
+                    }),
+                )
+            }
+        } else {
+            // A `<` that doesn't start a tag is text
+            pos = lt + 1;
+            continue;
+        };
+
+        if text_start < lt {
+            tokens.push(Token::Text(&html[text_start..lt]));
+        }
+        tokens.extend(token);
+        pos = end;
+        text_start = end;
+    }
+    if text_start < html.len() {
+        tokens.push(Token::Text(&html[text_start..]));
+    }
+    tokens
+}
+
+/// Index just past the `>` ending the tag at `start`, skipping `>` inside
+/// quoted attribute values
+fn tag_end(html: &str, start: usize) -> usize {
+    let mut quote = None;
+    for (i, c) in html[start..].char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return start + i + 1,
+            _ => {}
+        }
+    }
+    html.len()
+}
+
+fn tag_name(tag: &str) -> String {
+    tag.chars()
+        .take_while(|c| c.is_ascii_alphanumeric() || *c == '-')
+        .collect()
+}
+
+/// Decode the common named entities and numeric references
+fn decode(text: &str) -> String {
+    if !text.contains('&') {
+        return text.to_string();
+    }
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(amp) = rest.find('&') {
+        out.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let entity = rest[1..]
+            .find(';')
+            .filter(|&end| end <= 10)
+            .map(|end| &rest[1..=end]);
+        let decoded = entity.and_then(|entity| match entity {
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "amp" => Some('&'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            "nbsp" => Some(' '),
+            "mdash" => Some('—'),
+            "ndash" => Some('–'),
+            "hellip" => Some('…'),
+            "lsquo" => Some('‘'),
+            "rsquo" => Some('’'),
+            "ldquo" => Some('“'),
+            "rdquo" => Some('”'),
+            "lsaquo" => Some('‹'),
+            "rsaquo" => Some('›'),
+            "copy" => Some('©'),
+            _ => {
+                let number = entity.strip_prefix('#')?;
+                let code = match number.strip_prefix(['x', 'X']) {
+                    Some(hex) => u32::from_str_radix(hex, 16).ok()?,
+                    None => number.parse().ok()?,
+                };
+                char::from_u32(code)
+            }
+        });
+        match (entity, decoded) {
+            (Some(entity), Some(c)) => {
+                out.push(c);
+                rest = &rest[entity.len() + 2..];
+            }
+            _ => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Joins text runs, collapsing whitespace and starting blocks on new lines
+#[derive(Default)]
+struct TextBuilder {
+    out: String,
+}
+
+impl TextBuilder {
+    fn push_text(&mut self, text: &str) {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        if words.is_empty() {
+            if !text.is_empty() && !self.out.ends_with([' ', '\n']) && !self.out.is_empty() {
+                self.out.push(' ');
+            }
+            return;
+        }
+        if text.starts_with(char::is_whitespace) && !self.out.ends_with([' ', '\n']) {
+            self.out.push(' ');
+        }
+        self.out.push_str(&words.join(" "));
+        if text.ends_with(char::is_whitespace) {
+            self.out.push(' ');
+        }
+    }
+
+    /// Text whose lines are kept as they are, like a table
+    fn push_block(&mut self, block: &str) {
+        self.out.push_str(block);
+    }
+
+    fn line_break(&mut self) {
+        if !self.out.is_empty() && !self.out.ends_with('\n') {
+            self.out.push('\n');
+        }
+    }
+
+    /// Lines with outer whitespace trimmed and blank lines dropped
+    fn finish(self) -> String {
+        self.out
+            .lines()
+            .map(str::trim_end)
+            .map(|line| line.trim_start_matches(' '))
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scripts_and_comments_on_the_same_line() {
+        let html = r#"<p>Before</p><script>if (a < b) { document.write("<p>x</p>"); }</script><p>After <!-- <b>hidden</b> --> it</p><STYLE>p { color: red }</STYLE>"#;
+        assert_eq!(Document::parse(html).text(), "Before\nAfter it");
+    }
+
+    #[test]
+    fn test_implied_end_tags_and_entities() {
+        let html = "<ul><li>One &amp; two<li>Three&#8212;four</ul><p>a &lt; b<p>c&nbsp;d &bogus; &";
+        assert_eq!(
+            Document::parse(html).text(),
+            "One & two\nThree—four\na < b\nc d &bogus; &"
+        );
+    }
+
+    #[test]
+    fn test_table_columns_are_aligned_and_capped() {
+        let table = RenderedTable::new(
+            None,
+            vec![
+                vec!["Name".to_string(), "Description".to_string()],
+                vec!["a".to_string(), "a much longer description".to_string()],
+            ],
+            1,
+        );
+        assert_eq!(
+            table.text,
+            "Name | Description\n\
+             -----+--------------------------\n\
+             a    | a much longer description"
+        );
+        assert_eq!(
+            table.render(16),
+            "Name | Descript…\n-----+----------\na    | a much l…"
+        );
+    }
+}
@@ -0,0 +1,39 @@
+//! HTML files for the search indexer.
+
+use crate::HtmlParser;
+use lucastra_core::{LuCastraError, Result};
+use lucastra_search::{ContentExtractor, SourceFormat};
+use std::path::Path;
+
+/// Indexes HTML files by their title and main content, leaving out markup,
+/// scripts and navigation as pages opened in the browser are.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HtmlExtractor;
+
+impl ContentExtractor for HtmlExtractor {
+    fn format(&self) -> SourceFormat {
+        SourceFormat::Html
+    }
+
+    fn handles(&self, path: &Path, head: &[u8]) -> bool {
+        if SourceFormat::of_path(path) == SourceFormat::Html {
+            return true;
+        }
+        let start = String::from_utf8_lossy(&head[..head.len().min(64)])
+            .trim_start()
+            .to_ascii_lowercase();
+        start.starts_with("<!doctype html") || start.starts_with("<html")
+    }
+
+    fn extract(&self, bytes: &[u8]) -> Result<String> {
+        let html = std::str::from_utf8(bytes)
+            .map_err(|_| LuCastraError::ServiceError("HTML is not UTF-8".to_string()))?;
+        let content = HtmlParser::parse(html);
+        let body = if content.main_text.is_empty() {
+            &content.text
+        } else {
+            &content.main_text
+        };
+        Ok(format!("{}\n\n{}", content.title, body))
+    }
+}
@@ -3,11 +3,23 @@
 //! A minimal HTTP client and HTML renderer for text-based web browsing.
 //! Features: HTTP GET, basic HTML parsing, tabs, history, bookmarks.
 
+mod dom;
+pub mod extract;
+pub mod store;
+
 use regex::Regex;
+use reqwest::header::{HeaderMap, HeaderValue, ACCEPT, ACCEPT_LANGUAGE, CONTENT_TYPE, LOCATION};
+use reqwest::Url;
 use serde::{Deserialize, Serialize};
-use std::collections::VecDeque;
+use std::io::Read;
+use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
 
+pub use dom::{RenderedTable, TABLE_MAX_WIDTH};
+pub use extract::HtmlExtractor;
+pub use store::{Bookmark, BrowserStore, HistoryEntry};
+
 #[derive(Debug, Error)]
 pub enum BrowserError {
     #[error("Network error: {0}")]
@@ -21,6 +33,15 @@ pub enum BrowserError {
 
     #[error("IO error: {0}")]
     IoError(#[from] std::io::Error),
+
+    #[error("Response is larger than {limit} bytes")]
+    TooLarge { limit: u64 },
+
+    #[error("More than {0} redirects")]
+    TooManyRedirects(usize),
+
+    #[error("{0}")]
+    Blocked(String),
 }
 
 pub type BrowserResult<T> = Result<T, BrowserError>;
@@ -29,51 +50,267 @@ pub type BrowserResult<T> = Result<T, BrowserError>;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct HtmlContent {
     pub title: String,
+    /// All visible text, one block per line
     pub text: String,
+    /// Text of the article or other main content, without navigation,
+    /// banners and footers; the full text when no region stands out
+    #[serde(default)]
+    pub main_text: String,
+    /// Tables on the page, also shown aligned within `text`
+    #[serde(default)]
+    pub tables: Vec<RenderedTable>,
     pub links: Vec<Link>,
     pub images: Vec<String>,
+    /// `href` of the page's `<base>` tag, which links resolve against
+    #[serde(default)]
+    pub base_href: Option<String>,
 }
 
 /// A hyperlink found in HTML
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Link {
     pub text: String,
+    /// Target as written in the page
     pub href: String,
 }
 
+/// Schemes of links that run code or open another app instead of a page
+const NON_NAVIGABLE_SCHEMES: [&str; 4] = ["javascript:", "mailto:", "tel:", "data:"];
+
+impl Link {
+    /// Whether following the link would load a page
+    pub fn is_navigable(&self) -> bool {
+        let href = self.href.trim().to_ascii_lowercase();
+        !NON_NAVIGABLE_SCHEMES
+            .iter()
+            .any(|scheme| href.starts_with(scheme))
+    }
+
+    /// Absolute URL of the link on a page at `base`, without its fragment
+    pub fn resolve(&self, base: &str) -> String {
+        self.resolve_with(base, false)
+    }
+
+    /// Absolute URL of the link on a page at `base`. Links that aren't
+    /// navigable, or that can't be resolved, are returned as written.
+    pub fn resolve_with(&self, base: &str, keep_fragment: bool) -> String {
+        let href = self.href.trim();
+        if !self.is_navigable() {
+            return href.to_string();
+        }
+        match Url::parse(base).and_then(|base| base.join(href)) {
+            Ok(mut url) => {
+                if !keep_fragment {
+                    url.set_fragment(None);
+                }
+                url.to_string()
+            }
+            Err(_) => href.to_string(),
+        }
+    }
+}
+
+/// Redirects followed before giving up.
+pub const DEFAULT_MAX_REDIRECTS: usize = 5;
+
+/// Largest response body read.
+pub const DEFAULT_MAX_BODY_BYTES: u64 = 5 * 1024 * 1024;
+
+const ACCEPT_TYPES: &str = "text/html,application/xhtml+xml,text/plain;q=0.9,*/*;q=0.5";
+const ACCEPT_LANGUAGES: &str = "en-US,en;q=0.8";
+
+/// A fetched page after following redirects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FetchResult {
+    /// URL the content came from
+    pub final_url: String,
+    /// URLs that redirected, in the order they were visited
+    pub redirects: Vec<String>,
+    pub status: u16,
+    pub content_type: Option<String>,
+    pub body: Body,
+}
+
+impl FetchResult {
+    /// Whether the status is 2xx
+    pub fn is_success(&self) -> bool {
+        (200..300).contains(&self.status)
+    }
+}
+
+/// Response body, kept only when it can be shown as text
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub enum Body {
+    Text(String),
+    /// Images, archives and other content the browser can't show
+    Binary {
+        size: u64,
+    },
+}
+
+/// Decides whether a URL may be requested; `Err` carries the reason.
+type HopCheck = Arc<dyn Fn(&str) -> Result<(), String> + Send + Sync>;
+
 /// HTTP client for fetching pages
+#[derive(Clone)]
 pub struct HttpClient {
     user_agent: String,
+    max_redirects: usize,
+    max_body_bytes: u64,
+    timeout: Duration,
+    hop_check: Option<HopCheck>,
 }
 
 impl HttpClient {
     pub fn new() -> Self {
         Self {
             user_agent: "LucAstra-Browser/1.0".to_string(),
+            max_redirects: DEFAULT_MAX_REDIRECTS,
+            max_body_bytes: DEFAULT_MAX_BODY_BYTES,
+            timeout: Duration::from_secs(10),
+            hop_check: None,
         }
     }
 
-    /// Fetch HTML from a URL (blocking)
-    pub fn get(&self, url: &str) -> BrowserResult<String> {
+    pub fn with_max_redirects(mut self, max_redirects: usize) -> Self {
+        self.max_redirects = max_redirects;
+        self
+    }
+
+    pub fn with_max_body_bytes(mut self, max_body_bytes: u64) -> Self {
+        self.max_body_bytes = max_body_bytes;
+        self
+    }
+
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Check the URL and every redirect target with `check` before it is
+    /// requested; a rejected hop fails the fetch with
+    /// [`BrowserError::Blocked`].
+    pub fn with_hop_check(
+        mut self,
+        check: impl Fn(&str) -> Result<(), String> + Send + Sync + 'static,
+    ) -> Self {
+        self.hop_check = Some(Arc::new(check));
+        self
+    }
+
+    /// Fetch a URL (blocking), following redirects. Error statuses such as
+    /// 404 are returned as results; only transport failures are errors.
+    pub fn get(&self, url: &str) -> BrowserResult<FetchResult> {
         // Validate URL format
         if !url.starts_with("http://") && !url.starts_with("https://") {
             return Err(BrowserError::InvalidUrl(
                 "URL must start with http:// or https://".to_string(),
             ));
         }
+        let mut current = Url::parse(url).map_err(|e| BrowserError::InvalidUrl(e.to_string()))?;
 
-        // Use reqwest synchronously
-        let client = reqwest::blocking::Client::new();
-        let response = client
-            .get(url)
-            .header("User-Agent", self.user_agent.clone())
-            .timeout(std::time::Duration::from_secs(10))
-            .send()
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT, HeaderValue::from_static(ACCEPT_TYPES));
+        headers.insert(ACCEPT_LANGUAGE, HeaderValue::from_static(ACCEPT_LANGUAGES));
+        // Redirects are followed here so each hop can be recorded
+        let client = reqwest::blocking::Client::builder()
+            .user_agent(self.user_agent.clone())
+            .default_headers(headers)
+            .redirect(reqwest::redirect::Policy::none())
+            .timeout(self.timeout)
+            .build()
             .map_err(|e| BrowserError::NetworkError(e.to_string()))?;
 
+        let mut redirects = Vec::new();
+        loop {
+            if let Some(check) = &self.hop_check {
+                check(current.as_str()).map_err(BrowserError::Blocked)?;
+            }
+            let response = client
+                .get(current.clone())
+                .send()
+                .map_err(|e| BrowserError::NetworkError(e.to_string()))?;
+
+            let location = response
+                .headers()
+                .get(LOCATION)
+                .and_then(|location| location.to_str().ok());
+            if let (true, Some(location)) = (response.status().is_redirection(), location) {
+                if redirects.len() >= self.max_redirects {
+                    return Err(BrowserError::TooManyRedirects(self.max_redirects));
+                }
+                let next = current
+                    .join(location)
+                    .map_err(|e| BrowserError::InvalidUrl(e.to_string()))?;
+                redirects.push(current.to_string());
+                current = next;
+                continue;
+            }
+
+            return self.read_response(response, redirects);
+        }
+    }
+
+    fn read_response(
+        &self,
+        response: reqwest::blocking::Response,
+        redirects: Vec<String>,
+    ) -> BrowserResult<FetchResult> {
+        let final_url = response.url().to_string();
+        let status = response.status().as_u16();
+        let content_type = response
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(|value| value.to_string());
+        let limit = self.max_body_bytes;
+        let too_large = BrowserError::TooLarge { limit };
+
+        let declared_len = response.content_length();
+        if declared_len.is_some_and(|len| len > limit) {
+            return Err(too_large);
+        }
+        // No need to download what won't be shown when its size is known
+        if let (Some(size), Some(false)) = (declared_len, content_type.as_deref().map(is_text_type))
+        {
+            return Ok(FetchResult {
+                final_url,
+                redirects,
+                status,
+                content_type,
+                body: Body::Binary { size },
+            });
+        }
+
+        // Read one byte past the cap to tell a body at the limit from a
+        // larger one without a Content-Length
+        let mut bytes = Vec::new();
         response
-            .text()
-            .map_err(|e| BrowserError::NetworkError(e.to_string()))
+            .take(limit + 1)
+            .read_to_end(&mut bytes)
+            .map_err(|e| BrowserError::NetworkError(e.to_string()))?;
+        if bytes.len() as u64 > limit {
+            return Err(too_large);
+        }
+
+        let is_text = match content_type.as_deref() {
+            Some(content_type) => is_text_type(content_type),
+            None => !bytes.contains(&0) && std::str::from_utf8(&bytes).is_ok(),
+        };
+        let body = if is_text {
+            Body::Text(String::from_utf8_lossy(&bytes).into_owned())
+        } else {
+            Body::Binary {
+                size: bytes.len() as u64,
+            }
+        };
+        Ok(FetchResult {
+            final_url,
+            redirects,
+            status,
+            content_type,
+            body,
+        })
     }
 }
 
@@ -83,6 +320,30 @@ impl Default for HttpClient {
     }
 }
 
+/// Host name in `url`, if it parses and has one
+pub fn host_of(url: &str) -> Option<String> {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+}
+
+/// Whether a `Content-Type` value is something the browser can show
+fn is_text_type(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    mime.starts_with("text/")
+        || mime.ends_with("+xml")
+        || mime.ends_with("+json")
+        || matches!(
+            mime.as_str(),
+            "application/xml" | "application/json" | "application/javascript"
+        )
+}
+
 /// Simple HTML parser and renderer
 pub struct HtmlParser;
 
@@ -90,18 +351,34 @@ impl HtmlParser {
     /// Parse HTML content into text, links, images
     pub fn parse(html: &str) -> HtmlContent {
         let title = Self::extract_title(html);
-        let text = Self::extract_text(html);
+        let document = dom::Document::parse(html);
+        let text = document.text();
+        let main_text = document.main_text();
+        let tables = document.tables();
         let links = Self::extract_links(html);
         let images = Self::extract_images(html);
+        let base_href = Self::extract_base_href(html);
 
         HtmlContent {
             title,
             text,
+            main_text,
+            tables,
             links,
             images,
+            base_href,
         }
     }
 
+    /// Extract the href of a <base> tag
+    fn extract_base_href(html: &str) -> Option<String> {
+        let base_pattern = Regex::new(r#"<base\s+[^>]*href=["']([^"']+)["'][^>]*>"#).unwrap();
+        base_pattern
+            .captures(html)
+            .and_then(|cap| cap.get(1))
+            .map(|href| href.as_str().trim().to_string())
+    }
+
     /// Extract page title from <title> tag
     fn extract_title(html: &str) -> String {
         if let Some(start) = html.find("<title>") {
@@ -115,58 +392,6 @@ impl HtmlParser {
         "Untitled".to_string()
     }
 
-    /// Extract plain text from HTML (strip tags)
-    fn extract_text(html: &str) -> String {
-        let mut text = String::new();
-        let mut in_tag = false;
-        let mut in_script = false;
-        let mut in_style = false;
-
-        for line in html.lines() {
-            // Skip script and style tags
-            if line.contains("<script") {
-                in_script = true;
-            }
-            if line.contains("</script>") {
-                in_script = false;
-            }
-            if line.contains("<style") {
-                in_style = true;
-            }
-            if line.contains("</style>") {
-                in_style = false;
-            }
-
-            if in_script || in_style {
-                continue;
-            }
-
-            for ch in line.chars() {
-                match ch {
-                    '<' => in_tag = true,
-                    '>' => {
-                        in_tag = false;
-                        if !text.ends_with('\n') {
-                            text.push(' ');
-                        }
-                    }
-                    _ if !in_tag => text.push(ch),
-                    _ => {}
-                }
-            }
-            text.push('\n');
-        }
-
-        // Decode HTML entities and normalize whitespace
-        let text = Self::decode_entities(&text);
-        let lines: Vec<&str> = text
-            .lines()
-            .map(|l| l.trim())
-            .filter(|l| !l.is_empty())
-            .collect();
-        lines.join("\n")
-    }
-
     /// Extract links from <a> tags
     fn extract_links(html: &str) -> Vec<Link> {
         let mut links = Vec::new();
@@ -198,25 +423,21 @@ impl HtmlParser {
 
         images
     }
-
-    /// Decode basic HTML entities
-    fn decode_entities(text: &str) -> String {
-        text.replace("&lt;", "<")
-            .replace("&gt;", ">")
-            .replace("&amp;", "&")
-            .replace("&quot;", "\"")
-            .replace("&apos;", "'")
-            .replace("&#39;", "'")
-            .replace("&nbsp;", " ")
-    }
 }
 
 /// Browser tab
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Tab {
+    /// URL of the loaded page, after any redirects
     pub url: String,
     pub content: Option<HtmlContent>,
-    pub history: VecDeque<String>,
+    /// Pages to return to with back(), most recent last
+    pub back_stack: Vec<String>,
+    /// Pages left with back(), most recent last
+    pub forward_stack: Vec<String>,
+    /// HTTP status of the loaded page
+    #[serde(default)]
+    pub status: Option<u16>,
 }
 
 impl Tab {
@@ -224,29 +445,134 @@ impl Tab {
         Self {
             url,
             content: None,
-            history: VecDeque::new(),
+            back_stack: Vec::new(),
+            forward_stack: Vec::new(),
+            status: None,
         }
     }
 
-    /// Load URL in this tab
+    /// Load URL in this tab as a new page, which can't go forward any more.
     pub fn load(&mut self, url: String, client: &HttpClient) -> BrowserResult<()> {
-        let html = client.get(&url)?;
-        let content = HtmlParser::parse(&html);
-
-        self.history.push_front(self.url.clone());
-        self.url = url;
-        self.content = Some(content);
-
+        let previous = self.loaded_url();
+        self.show(&url, client)?;
+        self.back_stack.extend(previous);
+        self.forward_stack.clear();
         Ok(())
     }
 
     /// Go back in history
     pub fn back(&mut self, client: &HttpClient) -> BrowserResult<()> {
-        if let Some(prev_url) = self.history.pop_front() {
-            self.load(prev_url, client)?;
+        let Some(url) = self.back_stack.pop() else {
+            return Ok(());
+        };
+        let current = self.loaded_url();
+        if let Err(e) = self.show(&url, client) {
+            self.back_stack.push(url);
+            return Err(e);
+        }
+        self.forward_stack.extend(current);
+        Ok(())
+    }
+
+    /// Go forward to the page left with back()
+    pub fn forward(&mut self, client: &HttpClient) -> BrowserResult<()> {
+        let Some(url) = self.forward_stack.pop() else {
+            return Ok(());
+        };
+        let current = self.loaded_url();
+        if let Err(e) = self.show(&url, client) {
+            self.forward_stack.push(url);
+            return Err(e);
+        }
+        self.back_stack.extend(current);
+        Ok(())
+    }
+
+    pub fn can_go_back(&self) -> bool {
+        !self.back_stack.is_empty()
+    }
+
+    pub fn can_go_forward(&self) -> bool {
+        !self.forward_stack.is_empty()
+    }
+
+    /// Title of the loaded page, or its URL
+    pub fn title(&self) -> &str {
+        match &self.content {
+            Some(content) if content.title != "Untitled" => &content.title,
+            _ => &self.url,
         }
+    }
+
+    /// URL of the page shown, if one was loaded; a new tab's placeholder
+    /// URL is not worth going back to
+    fn loaded_url(&self) -> Option<String> {
+        self.content.as_ref().map(|_| self.url.clone())
+    }
+
+    /// Fetch `url` and show it without touching the navigation stacks.
+    /// Binary content isn't rendered; the page text describes its type and
+    /// size instead.
+    fn show(&mut self, url: &str, client: &HttpClient) -> BrowserResult<()> {
+        let fetched = client.get(url)?;
+        let content = match &fetched.body {
+            Body::Text(html) => HtmlParser::parse(html),
+            Body::Binary { size } => HtmlContent {
+                title: fetched.final_url.clone(),
+                text: format!(
+                    "Binary content ({}, {} bytes) can't be displayed",
+                    fetched.content_type.as_deref().unwrap_or("unknown type"),
+                    size
+                ),
+                main_text: String::new(),
+                tables: Vec::new(),
+                links: Vec::new(),
+                images: Vec::new(),
+                base_href: None,
+            },
+        };
+
+        self.url = fetched.final_url;
+        self.status = Some(fetched.status);
+        self.content = Some(content);
+
         Ok(())
     }
+
+    /// Host name of the loaded page, if its URL has one
+    pub fn host(&self) -> Option<String> {
+        host_of(&self.url)
+    }
+
+    /// URL links on the page resolve against: the page's `<base>` tag,
+    /// itself relative to the page URL, or the page URL
+    pub fn base_url(&self) -> String {
+        let base_href = self.content.as_ref().and_then(|c| c.base_href.as_deref());
+        match base_href {
+            Some(href) => Link {
+                text: String::new(),
+                href: href.to_string(),
+            }
+            .resolve_with(&self.url, true),
+            None => self.url.clone(),
+        }
+    }
+
+    /// Absolute URL of link `index` on the page
+    pub fn link_url(&self, index: usize) -> BrowserResult<String> {
+        let link = self
+            .content
+            .as_ref()
+            .and_then(|content| content.links.get(index))
+            .ok_or_else(|| BrowserError::InvalidUrl(format!("No link {}", index)))?;
+        if !link.is_navigable() {
+            return Err(BrowserError::InvalidUrl(format!(
+                "{} can't be opened in the browser",
+                link.href
+            )));
+        }
+        Ok(link.resolve(&self.base_url()))
+    }
 }
 
 /// Browser state with tabs and bookmarks
@@ -254,7 +580,11 @@ impl Tab {
 pub struct Browser {
     pub tabs: Vec<Tab>,
     pub active_tab: usize,
-    pub bookmarks: Vec<String>,
+    pub bookmarks: Vec<Bookmark>,
+    /// Pages visited in any tab, oldest first
+    pub visits: Vec<HistoryEntry>,
+    /// Where bookmarks and visits are saved; `None` keeps them in memory
+    store: Option<BrowserStore>,
 }
 
 impl Browser {
@@ -263,9 +593,22 @@ impl Browser {
             tabs: vec![Tab::new("about:blank".to_string())],
             active_tab: 0,
             bookmarks: Vec::new(),
+            visits: Vec::new(),
+            store: None,
         }
     }
 
+    /// Browser saving to `store`, starting with the bookmarks and visits
+    /// saved there
+    pub fn with_store(store: BrowserStore) -> BrowserResult<Self> {
+        Ok(Self {
+            bookmarks: store.load_bookmarks()?,
+            visits: store.load_history()?,
+            store: Some(store),
+            ..Self::new()
+        })
+    }
+
     /// Get active tab (mutable)
     pub fn current_tab_mut(&mut self) -> Option<&mut Tab> {
         self.tabs.get_mut(self.active_tab)
@@ -299,12 +642,21 @@ impl Browser {
         }
     }
 
-    /// Add URL to bookmarks
-    pub fn bookmark(&mut self) {
-        if let Some(tab) = self.current_tab() {
-            if !self.bookmarks.contains(&tab.url) {
-                self.bookmarks.push(tab.url.clone());
-            }
+    /// Add the current page to bookmarks
+    pub fn bookmark(&mut self) -> BrowserResult<()> {
+        let Some(tab) = self.current_tab() else {
+            return Ok(());
+        };
+        if self.bookmarks.iter().any(|b| b.url == tab.url) {
+            return Ok(());
+        }
+        self.bookmarks.push(Bookmark {
+            url: tab.url.clone(),
+            title: tab.title().to_string(),
+        });
+        match &self.store {
+            Some(store) => store.save_bookmarks(&self.bookmarks),
+            None => Ok(()),
         }
     }
 
@@ -313,16 +665,50 @@ impl Browser {
         if let Some(tab) = self.current_tab_mut() {
             tab.load(url, client)?;
         }
+        self.record_visit();
         Ok(())
     }
 
     /// Go back in current tab
     pub fn back(&mut self, client: &HttpClient) -> BrowserResult<()> {
         if let Some(tab) = self.current_tab_mut() {
+            if !tab.can_go_back() {
+                return Ok(());
+            }
             tab.back(client)?;
         }
+        self.record_visit();
+        Ok(())
+    }
+
+    /// Go forward in current tab
+    pub fn forward(&mut self, client: &HttpClient) -> BrowserResult<()> {
+        if let Some(tab) = self.current_tab_mut() {
+            if !tab.can_go_forward() {
+                return Ok(());
+            }
+            tab.forward(client)?;
+        }
+        self.record_visit();
         Ok(())
     }
+
+    /// Add the current page to the visit history. A history file that
+    /// can't be written doesn't stop browsing.
+    fn record_visit(&mut self) {
+        let Some(tab) = self.current_tab() else {
+            return;
+        };
+        let visit = HistoryEntry::now(tab.url.clone(), tab.title().to_string());
+        if let Some(store) = &self.store {
+            if let Err(e) = store.append_visit(&visit) {
+                tracing::warn!("Failed to save browser history: {}", e);
+            }
+        }
+        self.visits.push(visit);
+        let excess = self.visits.len().saturating_sub(store::MAX_HISTORY);
+        self.visits.drain(..excess);
+    }
 }
 
 impl Default for Browser {
@@ -389,10 +775,98 @@ mod tests {
         let tab = Tab::new("https://example.com".to_string());
         browser.tabs[0] = tab;
 
-        browser.bookmark();
+        browser.bookmark().unwrap();
         assert!(browser
             .bookmarks
-            .contains(&"https://example.com".to_string()));
+            .iter()
+            .any(|b| b.url == "https://example.com"));
+    }
+
+    fn temp_dir(name: &str) -> std::path::PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("lucastra_browser_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[test]
+    fn test_back_and_forward() {
+        let (base, _) = serve(vec![
+            ("/a", Reply::ok("text/html", "<title>A</title>")),
+            ("/b", Reply::ok("text/html", "<title>B</title>")),
+            ("/c", Reply::ok("text/html", "<title>C</title>")),
+            ("/d", Reply::ok("text/html", "<title>D</title>")),
+        ]);
+        let client = HttpClient::new();
+        let url = |path: &str| format!("{}{}", base, path);
+        let mut browser = Browser::new();
+        let current = |browser: &Browser| browser.current_tab().unwrap().url.clone();
+
+        for path in ["/a", "/b", "/c"] {
+            browser.navigate(url(path), &client).unwrap();
+        }
+        assert_eq!(current(&browser), url("/c"));
+
+        browser.back(&client).unwrap();
+        assert_eq!(current(&browser), url("/b"));
+        browser.back(&client).unwrap();
+        assert_eq!(current(&browser), url("/a"));
+        // The blank start page isn't part of the history
+        assert!(!browser.current_tab().unwrap().can_go_back());
+        browser.back(&client).unwrap();
+        assert_eq!(current(&browser), url("/a"));
+
+        browser.forward(&client).unwrap();
+        assert_eq!(current(&browser), url("/b"));
+        assert_eq!(browser.current_tab().unwrap().title(), "B");
+
+        // A new page drops the forward history
+        browser.navigate(url("/d"), &client).unwrap();
+        let tab = browser.current_tab().unwrap();
+        assert!(!tab.can_go_forward());
+        assert_eq!(tab.back_stack, vec![url("/a"), url("/b")]);
+
+        let visited: Vec<_> = browser.visits.iter().map(|v| v.title.as_str()).collect();
+        assert_eq!(visited, vec!["A", "B", "C", "B", "A", "B", "D"]);
+    }
+
+    #[test]
+    fn test_bookmarks_and_history_persist() {
+        let dir = temp_dir("store");
+        let (base, _) = serve(vec![(
+            "/home",
+            Reply::ok("text/html", "<title>Home</title>"),
+        )]);
+        let client = HttpClient::new();
+
+        let mut browser = Browser::with_store(BrowserStore::new(dir.clone())).unwrap();
+        browser.navigate(format!("{}/home", base), &client).unwrap();
+        browser.bookmark().unwrap();
+        browser.bookmark().unwrap();
+
+        let reopened = Browser::with_store(BrowserStore::new(dir.clone())).unwrap();
+        assert_eq!(
+            reopened.bookmarks,
+            vec![Bookmark {
+                url: format!("{}/home", base),
+                title: "Home".to_string(),
+            }]
+        );
+        assert_eq!(reopened.visits, browser.visits);
+
+        // A damaged history line doesn't lose the others
+        std::fs::write(
+            dir.join(store::HISTORY_FILE),
+            format!(
+                "not json\n{}\n",
+                serde_json::to_string(&browser.visits[0]).unwrap()
+            ),
+        )
+        .unwrap();
+        let reopened = Browser::with_store(BrowserStore::new(dir.clone())).unwrap();
+        assert_eq!(reopened.visits.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
     }
 
     #[test]
@@ -401,4 +875,332 @@ mod tests {
         let result = client.get("invalid-url");
         assert!(result.is_err());
     }
+
+    #[test]
+    fn test_link_resolution() {
+        let cases = [
+            // (base, href, resolved)
+            (
+                "https://example.com/docs/guide.html",
+                "/about",
+                "https://example.com/about",
+            ),
+            (
+                "https://example.com/docs/guide.html",
+                "intro.html",
+                "https://example.com/docs/intro.html",
+            ),
+            (
+                "https://example.com/docs/guide.html",
+                "./intro.html",
+                "https://example.com/docs/intro.html",
+            ),
+            (
+                "https://example.com/docs/guide",
+                "../index.html",
+                "https://example.com/index.html",
+            ),
+            (
+                "https://example.com/docs/",
+                "../index.html",
+                "https://example.com/index.html",
+            ),
+            (
+                "https://example.com/docs",
+                "page",
+                "https://example.com/page",
+            ),
+            (
+                "https://example.com/docs/",
+                "page",
+                "https://example.com/docs/page",
+            ),
+            (
+                "https://example.com/a/b/c",
+                "../../../../x",
+                "https://example.com/x",
+            ),
+            (
+                "https://example.com/a",
+                "//cdn.example.org/lib.js",
+                "https://cdn.example.org/lib.js",
+            ),
+            (
+                "http://example.com/a",
+                "//cdn.example.org/lib.js",
+                "http://cdn.example.org/lib.js",
+            ),
+            (
+                "http://example.com/a",
+                "https://other.org/x#top",
+                "https://other.org/x",
+            ),
+            (
+                "https://example.com/a?q=1",
+                "?q=2",
+                "https://example.com/a?q=2",
+            ),
+            (
+                "https://example.com/a?q=1",
+                "#section",
+                "https://example.com/a?q=1",
+            ),
+            (
+                "https://example.com/a",
+                " b c ",
+                "https://example.com/b%20c",
+            ),
+            (
+                "https://example.com/a",
+                "javascript:void(0)",
+                "javascript:void(0)",
+            ),
+            (
+                "https://example.com/a",
+                "mailto:me@example.com",
+                "mailto:me@example.com",
+            ),
+            ("about:blank", "/about", "/about"),
+        ];
+        for (base, href, expected) in cases {
+            let link = Link {
+                text: String::new(),
+                href: href.to_string(),
+            };
+            assert_eq!(link.resolve(base), expected, "{} on {}", href, base);
+        }
+
+        let link = Link {
+            text: String::new(),
+            href: "guide.html#install".to_string(),
+        };
+        assert_eq!(
+            link.resolve_with("https://example.com/docs/", true),
+            "https://example.com/docs/guide.html#install"
+        );
+    }
+
+    #[test]
+    fn test_tab_link_urls() {
+        let html = r#"<html><head><base href="/v2/"></head><body>
+            <a href="start.html">Start</a>
+            <a href="JavaScript:alert(1)">Script</a>
+            </body></html>"#;
+        let mut tab = Tab::new("https://docs.example.com/v1/index.html".to_string());
+        tab.content = Some(HtmlParser::parse(html));
+
+        assert_eq!(tab.base_url(), "https://docs.example.com/v2/");
+        assert_eq!(
+            tab.link_url(0).unwrap(),
+            "https://docs.example.com/v2/start.html"
+        );
+        assert!(!tab.content.as_ref().unwrap().links[1].is_navigable());
+        assert!(matches!(tab.link_url(1), Err(BrowserError::InvalidUrl(_))));
+        assert!(matches!(tab.link_url(2), Err(BrowserError::InvalidUrl(_))));
+
+        tab.content = Some(HtmlParser::parse(r#"<a href="next">Next</a>"#));
+        assert_eq!(tab.link_url(0).unwrap(), "https://docs.example.com/v1/next");
+    }
+
+    /// A canned response served by [`serve`].
+    struct Reply {
+        status: u16,
+        headers: Vec<(&'static str, String)>,
+        body: Vec<u8>,
+        /// Send a Content-Length header
+        sized: bool,
+    }
+
+    impl Reply {
+        fn ok(content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+            Self {
+                status: 200,
+                headers: vec![("Content-Type", content_type.to_string())],
+                body: body.into(),
+                sized: true,
+            }
+        }
+
+        fn redirect(status: u16, location: &str) -> Self {
+            Self {
+                status,
+                headers: vec![("Location", location.to_string())],
+                body: Vec::new(),
+                sized: true,
+            }
+        }
+    }
+
+    /// Serve `routes` by request path on a local port until the test ends.
+    /// Returns the base URL and the request heads received.
+    fn serve(
+        routes: Vec<(&'static str, Reply)>,
+    ) -> (String, std::sync::Arc<std::sync::Mutex<Vec<String>>>) {
+        use std::io::{BufRead, BufReader, Write};
+        use std::net::TcpListener;
+        use std::sync::{Arc, Mutex};
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let seen = requests.clone();
+
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    head.push_str(&line);
+                    line.clear();
+                }
+                let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+                seen.lock().unwrap().push(head);
+
+                let not_found = Reply {
+                    status: 404,
+                    ..Reply::ok("text/html", "<title>Not Found</title>")
+                };
+                let reply = routes
+                    .iter()
+                    .find(|(route, _)| *route == path)
+                    .map(|(_, reply)| reply)
+                    .unwrap_or(&not_found);
+                let mut response =
+                    format!("HTTP/1.1 {} Status\r\nConnection: close\r\n", reply.status);
+                for (name, value) in &reply.headers {
+                    response.push_str(&format!("{}: {}\r\n", name, value));
+                }
+                if reply.sized {
+                    response.push_str(&format!("Content-Length: {}\r\n", reply.body.len()));
+                }
+                response.push_str("\r\n");
+                // The client may hang up early on oversized bodies
+                let _ = stream.write_all(response.as_bytes());
+                let _ = stream.write_all(&reply.body);
+            }
+        });
+        (base, requests)
+    }
+
+    #[test]
+    fn test_follows_redirect_chain() {
+        let (base, requests) = serve(vec![
+            ("/start", Reply::redirect(302, "/middle")),
+            ("/middle", Reply::redirect(301, "end?x=1")),
+            (
+                "/end?x=1",
+                Reply::ok("text/html; charset=utf-8", "<title>End</title>Arrived"),
+            ),
+        ]);
+
+        let fetched = HttpClient::new().get(&format!("{}/start", base)).unwrap();
+        assert_eq!(fetched.final_url, format!("{}/end?x=1", base));
+        assert_eq!(
+            fetched.redirects,
+            vec![format!("{}/start", base), format!("{}/middle", base)]
+        );
+        assert_eq!(fetched.status, 200);
+        assert!(matches!(&fetched.body, Body::Text(html) if html.contains("Arrived")));
+
+        let first = requests.lock().unwrap()[0].to_ascii_lowercase();
+        assert!(first.contains("accept: text/html"));
+        assert!(first.contains("accept-language: en-us"));
+        assert!(first.contains("user-agent: lucastra-browser"));
+
+        // Tab history shows where the page ended up
+        let mut tab = Tab::new("about:blank".to_string());
+        tab.load(format!("{}/start", base), &HttpClient::new())
+            .unwrap();
+        assert_eq!(tab.url, format!("{}/end?x=1", base));
+        assert_eq!(tab.content.unwrap().title, "End");
+    }
+
+    #[test]
+    fn test_redirect_limit() {
+        let (base, _) = serve(vec![
+            ("/loop", Reply::redirect(302, "/loop")),
+            ("/a", Reply::redirect(307, "/b")),
+            ("/b", Reply::ok("text/plain", "done")),
+        ]);
+
+        let result = HttpClient::new().get(&format!("{}/loop", base));
+        assert!(matches!(
+            result,
+            Err(BrowserError::TooManyRedirects(DEFAULT_MAX_REDIRECTS))
+        ));
+
+        let client = HttpClient::new().with_max_redirects(0);
+        assert!(matches!(
+            client.get(&format!("{}/a", base)),
+            Err(BrowserError::TooManyRedirects(0))
+        ));
+        assert!(HttpClient::new()
+            .with_max_redirects(1)
+            .get(&format!("{}/a", base))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_oversized_responses() {
+        let big = vec![b'a'; 2048];
+        let (base, _) = serve(vec![
+            ("/sized", Reply::ok("text/plain", big.clone())),
+            (
+                "/streamed",
+                Reply {
+                    sized: false,
+                    ..Reply::ok("text/plain", big)
+                },
+            ),
+            ("/fits", Reply::ok("text/plain", vec![b'a'; 1024])),
+        ]);
+        let client = HttpClient::new().with_max_body_bytes(1024);
+
+        for path in ["/sized", "/streamed"] {
+            assert!(matches!(
+                client.get(&format!("{}{}", base, path)),
+                Err(BrowserError::TooLarge { limit: 1024 })
+            ));
+        }
+        assert!(client.get(&format!("{}/fits", base)).is_ok());
+    }
+
+    #[test]
+    fn test_binary_content_and_error_statuses() {
+        let (base, _) = serve(vec![
+            (
+                "/logo.png",
+                Reply::ok("image/png", vec![0x89, b'P', b'N', b'G', 0]),
+            ),
+            (
+                "/blob",
+                Reply {
+                    headers: Vec::new(),
+                    ..Reply::ok("", vec![0, 1, 2])
+                },
+            ),
+        ]);
+        let client = HttpClient::new();
+
+        let image = client.get(&format!("{}/logo.png", base)).unwrap();
+        assert_eq!(image.content_type.as_deref(), Some("image/png"));
+        assert_eq!(image.body, Body::Binary { size: 5 });
+
+        // Without a Content-Type the bytes decide
+        let blob = client.get(&format!("{}/blob", base)).unwrap();
+        assert_eq!(blob.body, Body::Binary { size: 3 });
+
+        let missing = client.get(&format!("{}/missing", base)).unwrap();
+        assert_eq!(missing.status, 404);
+        assert!(!missing.is_success());
+
+        let mut tab = Tab::new("about:blank".to_string());
+        tab.load(format!("{}/logo.png", base), &client).unwrap();
+        assert!(tab.content.unwrap().text.contains("image/png, 5 bytes"));
+        assert_eq!(tab.status, Some(200));
+    }
 }
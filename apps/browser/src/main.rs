@@ -1,13 +1,22 @@
-use lucastra_browser::{Browser, HttpClient};
+use lucastra_browser::{Browser, BrowserStore, HttpClient};
 use std::io::{self, Write};
 
 fn main() {
     println!("LucAstra Lightweight Browser v0.1.0");
-    println!("Commands: open <url>, tab <url>, close, back, bookmark, bookmarks, tabs, exit");
+    println!(
+        "Commands: open <url>, link <n>, tab <url>, close, back, forward, bookmark, bookmarks, \
+         history [n], tabs, exit"
+    );
     println!("Example: open https://www.example.com\n");
 
     let client = HttpClient::new();
-    let mut browser = Browser::new();
+    let mut browser = match BrowserStore::default_dir().map(BrowserStore::new) {
+        Some(store) => Browser::with_store(store).unwrap_or_else(|e| {
+            println!("Bookmarks and history unavailable: {}", e);
+            Browser::new()
+        }),
+        None => Browser::new(),
+    };
 
     loop {
         print!("> ");
@@ -36,6 +45,18 @@ fn main() {
             continue;
         }
 
+        if input.eq_ignore_ascii_case("forward") {
+            match browser.forward(&client) {
+                Ok(_) => {
+                    if let Some(tab) = browser.current_tab() {
+                        println!("Forward to: {}", tab.url);
+                    }
+                }
+                Err(e) => println!("Error: {}", e),
+            }
+            continue;
+        }
+
         if input.eq_ignore_ascii_case("tabs") {
             for (i, tab) in browser.tabs.iter().enumerate() {
                 let marker = if i == browser.active_tab { "*" } else { " " };
@@ -45,9 +66,13 @@ fn main() {
         }
 
         if input.eq_ignore_ascii_case("bookmark") {
-            browser.bookmark();
-            if let Some(tab) = browser.current_tab() {
-                println!("Bookmarked: {}", tab.url);
+            match browser.bookmark() {
+                Ok(_) => {
+                    if let Some(tab) = browser.current_tab() {
+                        println!("Bookmarked: {}", tab.url);
+                    }
+                }
+                Err(e) => println!("Error: {}", e),
             }
             continue;
         }
@@ -56,8 +81,8 @@ fn main() {
             if browser.bookmarks.is_empty() {
                 println!("No bookmarks");
             } else {
-                for (i, url) in browser.bookmarks.iter().enumerate() {
-                    println!("[{}] {}", i, url);
+                for (i, bookmark) in browser.bookmarks.iter().enumerate() {
+                    println!("[{}] {} ({})", i, bookmark.title, bookmark.url);
                 }
             }
             continue;
@@ -71,29 +96,41 @@ fn main() {
             "open" => {
                 if parts.len() > 1 {
                     let url = parts[1..].join(" ");
-                    println!("Loading {}...", url);
-                    match browser.navigate(url.clone(), &client) {
-                        Ok(_) => {
-                            if let Some(tab) = browser.current_tab() {
-                                if let Some(content) = &tab.content {
-                                    println!("\n=== {} ===\n", content.title);
-                                    println!("{}\n", content.text);
-
-                                    if !content.links.is_empty() {
-                                        println!("\nLinks:");
-                                        for (i, link) in content.links.iter().enumerate().take(10) {
-                                            println!("[{}] {} ({})", i, link.text, link.href);
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        Err(e) => println!("Error: {}", e),
-                    }
+                    open(&mut browser, &client, url);
                 } else {
                     println!("Usage: open <url>");
                 }
             }
+            "link" => {
+                let index = parts.get(1).and_then(|n| n.parse::<usize>().ok());
+                match index {
+                    Some(index) => match browser.current_tab().map(|tab| tab.link_url(index)) {
+                        Some(Ok(url)) => open(&mut browser, &client, url),
+                        Some(Err(e)) => println!("Error: {}", e),
+                        None => println!("No page open"),
+                    },
+                    None => println!("Usage: link <n>"),
+                }
+            }
+            "history" => match parts.get(1).map(|n| n.parse::<usize>()) {
+                None => {
+                    if browser.visits.is_empty() {
+                        println!("No history");
+                    }
+                    // Most recent first, numbered for `history <n>`
+                    for (i, visit) in browser.visits.iter().rev().enumerate().take(20) {
+                        println!("[{}] {} ({})", i, visit.title, visit.url);
+                    }
+                }
+                Some(Ok(n)) => match browser.visits.iter().rev().nth(n) {
+                    Some(visit) => {
+                        let url = visit.url.clone();
+                        open(&mut browser, &client, url);
+                    }
+                    None => println!("No history entry {}", n),
+                },
+                Some(Err(_)) => println!("Usage: history [n]"),
+            },
             "tab" => {
                 if parts.len() > 1 {
                     let url = parts[1..].join(" ");
@@ -112,8 +149,46 @@ fn main() {
                 }
             }
             _ => println!(
-                "Unknown command. Try: open, tab, close, back, bookmark, bookmarks, tabs, exit"
+                "Unknown command. Try: open, link, tab, close, back, forward, bookmark, bookmarks, \
+                 history, tabs, exit"
             ),
         }
     }
 }
+
+/// Load `url` in the current tab and print the page.
+fn open(browser: &mut Browser, client: &HttpClient, url: String) {
+    println!("Loading {}...", url);
+    if let Err(e) = browser.navigate(url, client) {
+        println!("Error: {}", e);
+        return;
+    }
+    let Some(tab) = browser.current_tab() else {
+        return;
+    };
+    if let Some(status) = tab.status.filter(|s| !(200..300).contains(s)) {
+        println!("HTTP {} from {}", status, tab.url);
+    }
+    if let Some(content) = &tab.content {
+        println!("\n=== {} ===\n", content.title);
+        // Fall back to the whole page when no article region stands out
+        if content.main_text.is_empty() {
+            println!("{}\n", content.text);
+        } else {
+            println!("{}\n", content.main_text);
+        }
+
+        if !content.links.is_empty() {
+            let base = tab.base_url();
+            println!("\nLinks (open with 'link <n>'):");
+            for (i, link) in content.links.iter().enumerate().take(10) {
+                let target = if link.is_navigable() {
+                    link.resolve(&base)
+                } else {
+                    format!("{}, not navigable", link.href)
+                };
+                println!("[{}] {} ({})", i, link.text, target);
+            }
+        }
+    }
+}
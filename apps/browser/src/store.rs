@@ -0,0 +1,122 @@
+//! Bookmarks and visit history kept across sessions.
+//!
+//! Bookmarks are rewritten as a whole to `bookmarks.json` whenever they
+//! change. Visits are appended to `history.jsonl`, one JSON object per
+//! line, so recording one never rewrites the file.
+
+use crate::{BrowserError, BrowserResult};
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+pub const BOOKMARKS_FILE: &str = "bookmarks.json";
+pub const HISTORY_FILE: &str = "history.jsonl";
+
+/// Most recent visits read back from the history file.
+pub const MAX_HISTORY: usize = 1000;
+
+/// A saved page
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Bookmark {
+    pub url: String,
+    pub title: String,
+}
+
+/// A page loaded in any tab
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub url: String,
+    pub title: String,
+    /// Unix milliseconds of the visit
+    pub timestamp: u64,
+}
+
+impl HistoryEntry {
+    pub fn now(url: String, title: String) -> Self {
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0);
+        Self {
+            url,
+            title,
+            timestamp,
+        }
+    }
+}
+
+/// Directory holding the bookmarks and history files
+#[derive(Debug, Clone)]
+pub struct BrowserStore {
+    dir: PathBuf,
+}
+
+impl BrowserStore {
+    pub fn new(dir: PathBuf) -> Self {
+        Self { dir }
+    }
+
+    /// Default location, `~/.lucastra/data/browser`
+    pub fn default_dir() -> Option<PathBuf> {
+        lucastra_config::get_data_dir()
+            .ok()
+            .map(|dir| dir.join("browser"))
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Saved bookmarks; none when the file doesn't exist yet
+    pub fn load_bookmarks(&self) -> BrowserResult<Vec<Bookmark>> {
+        match fs::read_to_string(self.dir.join(BOOKMARKS_FILE)) {
+            Ok(contents) => serde_json::from_str(&contents)
+                .map_err(|e| BrowserError::ParseError(format!("{}: {}", BOOKMARKS_FILE, e))),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Vec::new()),
+            Err(e) => Err(e.into()),
+        }
+    }
+
+    pub fn save_bookmarks(&self, bookmarks: &[Bookmark]) -> BrowserResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        let json = serde_json::to_string_pretty(bookmarks)
+            .map_err(|e| BrowserError::ParseError(e.to_string()))?;
+        // Write a sibling first so a crash can't leave half a file
+        let path = self.dir.join(BOOKMARKS_FILE);
+        let temp = path.with_extension("json.tmp");
+        fs::write(&temp, json)?;
+        fs::rename(&temp, &path)?;
+        Ok(())
+    }
+
+    /// The last [`MAX_HISTORY`] visits, oldest first. Lines that can't be
+    /// parsed are skipped.
+    pub fn load_history(&self) -> BrowserResult<Vec<HistoryEntry>> {
+        let contents = match fs::read_to_string(self.dir.join(HISTORY_FILE)) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+        let mut visits: Vec<HistoryEntry> = contents
+            .lines()
+            .filter_map(|line| serde_json::from_str(line).ok())
+            .collect();
+        let excess = visits.len().saturating_sub(MAX_HISTORY);
+        visits.drain(..excess);
+        Ok(visits)
+    }
+
+    pub fn append_visit(&self, visit: &HistoryEntry) -> BrowserResult<()> {
+        fs::create_dir_all(&self.dir)?;
+        let line =
+            serde_json::to_string(visit).map_err(|e| BrowserError::ParseError(e.to_string()))?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(self.dir.join(HISTORY_FILE))?;
+        writeln!(file, "{}", line)?;
+        Ok(())
+    }
+}
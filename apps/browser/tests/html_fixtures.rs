@@ -0,0 +1,142 @@
+//! Extraction checks against saved pages.
+
+use lucastra_browser::{HtmlExtractor, HtmlParser, TABLE_MAX_WIDTH};
+use lucastra_search::{Indexer, SearchService};
+use std::path::Path;
+use std::sync::Arc;
+
+const NEWS_ARTICLE: &str = include_str!("fixtures/news_article.html");
+const WIKI_TABLE: &str = include_str!("fixtures/wiki_table.html");
+
+#[test]
+fn test_news_article_main_text() {
+    let content = HtmlParser::parse(NEWS_ARTICLE);
+
+    assert_eq!(
+        content.title,
+        "City council approves new bike lanes | The Daily Ledger"
+    );
+
+    let main = &content.main_text;
+    assert!(main.starts_with("The city council voted 7–2 on Tuesday"));
+    assert!(main.contains("Supporters said the lanes"));
+    // The paragraph after the inline ad script
+    assert!(main.contains("Opponents, including several business owners"));
+    assert!(main.ends_with("“This is the most important transport decision we have made in a decade,” the mayor said after the vote."));
+
+    for boilerplate in [
+        "cookies",
+        "Sport",
+        "Share on social",
+        "Most read",
+        "newsletter",
+        "Related",
+        "All rights reserved",
+        "Editor's note",
+        "renderAd",
+    ] {
+        assert!(
+            !main.contains(boilerplate),
+            "main text kept {:?}",
+            boilerplate
+        );
+    }
+}
+
+#[test]
+fn test_news_article_full_text_keeps_page_chrome() {
+    let content = HtmlParser::parse(NEWS_ARTICLE);
+
+    assert!(content.text.contains("Sport"));
+    assert!(content.text.contains("Most read"));
+    assert!(content.text.contains("News › Local"));
+    assert!(!content.text.contains("gtag"));
+    assert!(!content.text.contains("cookie-banner {"));
+    assert!(!content.text.contains("Editor's note"));
+}
+
+#[test]
+fn test_wiki_table_rows() {
+    let content = HtmlParser::parse(WIKI_TABLE);
+
+    assert_eq!(content.tables.len(), 1);
+    let table = &content.tables[0];
+    assert_eq!(table.caption.as_deref(), Some("Largest lakes by area"));
+    assert_eq!(table.header_rows, 1);
+    assert_eq!(table.rows.len(), 5);
+    assert_eq!(table.rows[0], ["Rank", "Name", "Area (km2)", "Countries"]);
+    assert_eq!(table.rows[1][1], "Caspian Sea[1]");
+    // Row with unclosed cells and an unclosed row
+    assert_eq!(
+        table.rows[3],
+        ["3", "Victoria", "68,870", "Uganda, Kenya, Tanzania"]
+    );
+    assert_eq!(
+        table.rows[4],
+        ["4", "Huron", "59,600", "Canada, United States"]
+    );
+}
+
+#[test]
+fn test_wiki_table_rendered_aligned() {
+    let content = HtmlParser::parse(WIKI_TABLE);
+    let table = &content.tables[0];
+
+    let lines: Vec<&str> = table.text.lines().collect();
+    assert_eq!(lines[0], "Largest lakes by area");
+    assert!(lines[2].starts_with("-----+-"));
+
+    let offsets = |line: &str| -> Vec<usize> {
+        line.match_indices(['|', '+'])
+            .map(|(i, _)| line[..i].chars().count())
+            .collect()
+    };
+    let header = offsets(lines[1]);
+    assert_eq!(header.len(), 3);
+    for line in &lines[2..] {
+        assert_eq!(offsets(line), header, "misaligned row {:?}", line);
+        assert!(line.chars().count() <= TABLE_MAX_WIDTH);
+    }
+
+    // The rendered table takes the table's place in the page text
+    assert!(content.text.contains(&table.text));
+    assert!(content
+        .text
+        .starts_with("The following table lists the largest lakes"));
+    assert!(content
+        .text
+        .ends_with("Areas are approximate and vary with the season & water level."));
+}
+
+#[test]
+fn test_wiki_table_narrow_render_truncates() {
+    let content = HtmlParser::parse(WIKI_TABLE);
+    let narrow = content.tables[0].render(40);
+
+    for line in narrow.lines().skip(1) {
+        assert!(line.chars().count() <= 40, "too wide: {:?}", line);
+    }
+    assert!(narrow.contains('…'));
+}
+
+#[test]
+fn test_indexed_html_keeps_only_main_content() {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/beekeeping_guide.html");
+    let service = SearchService::new(None);
+    let summary = Indexer::new()
+        .with_extractor(Arc::new(HtmlExtractor))
+        .index_path(&path, &service)
+        .unwrap();
+    assert_eq!(summary.files_indexed, 1);
+
+    let results = service.search("\"hive frames\"", 5).unwrap();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].format.as_deref(), Some("html"));
+    assert!(results[0]
+        .snippet
+        .contains("Inspect the **hive** **frames** weekly"));
+    // Navigation, footer and script text aren't indexed
+    for boilerplate in ["shop", "copyright", "tracking"] {
+        assert!(service.search(boilerplate, 5).unwrap().is_empty());
+    }
+}
@@ -1,9 +1,15 @@
 //! LucAstra Calculator - Native arithmetic app
 //!
-//! Provides basic calculator operations: +, -, *, /, with support for
-//! function calls (sin, cos, sqrt, etc.) and expression parsing.
+//! Provides basic calculator operations: +, -, *, /, ^, with support for
+//! function calls (sin, cos, sqrt, atan2(y, x), etc.), scientific notation
+//! and expression parsing.
+//!
+//! Results can be stored with `name = expr` and used in later expressions,
+//! alongside the constants `pi` and `e`, `ans` (the last result) and the
+//! memory register `mem`.
 
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use thiserror::Error;
 
 #[derive(Debug, Error)]
@@ -19,16 +25,72 @@ pub enum CalcError {
 
     #[error("Math domain error: {0}")]
     DomainError(String),
+
+    #[error("Undefined variable: {0}")]
+    UndefinedVariable(String),
 }
 
 pub type CalcResult<T> = Result<T, CalcError>;
 
+/// Name of the memory register used by the memory keys.
+pub const MEMORY: &str = "mem";
+
+/// Name that always holds the last result.
+pub const ANSWER: &str = "ans";
+
+const FUNCTIONS: [&str; 13] = [
+    "sqrt", "sin", "cos", "tan", "abs", "ln", "log", "exp", "floor", "ceil", "round", "atan2",
+    "pow",
+];
+
+/// Functions taking two comma-separated arguments in parentheses.
+const BINARY_FUNCTIONS: [&str; 2] = ["atan2", "pow"];
+
+/// Unit the trigonometric functions take and return angles in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AngleMode {
+    #[default]
+    Radians,
+    Degrees,
+}
+
+impl AngleMode {
+    fn to_radians(self, angle: f64) -> f64 {
+        match self {
+            AngleMode::Radians => angle,
+            AngleMode::Degrees => angle.to_radians(),
+        }
+    }
+
+    /// `radians` in this unit
+    fn in_mode(self, radians: f64) -> f64 {
+        match self {
+            AngleMode::Radians => radians,
+            AngleMode::Degrees => radians.to_degrees(),
+        }
+    }
+}
+
+/// Built-in constants, which can't be reassigned.
+fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" => Some(std::f64::consts::PI),
+        "e" => Some(std::f64::consts::E),
+        _ => None,
+    }
+}
+
 /// Calculator state and history
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Calculator {
     pub accumulator: f64,
     pub display: String,
     pub history: Vec<String>,
+    /// Assigned variables and the memory register.
+    #[serde(default)]
+    pub variables: HashMap<String, f64>,
+    #[serde(default)]
+    pub angle_mode: AngleMode,
 }
 
 impl Default for Calculator {
@@ -37,6 +99,8 @@ impl Default for Calculator {
             accumulator: 0.0,
             display: "0".to_string(),
             history: Vec::new(),
+            variables: HashMap::new(),
+            angle_mode: AngleMode::default(),
         }
     }
 }
@@ -46,24 +110,101 @@ impl Calculator {
         Self::default()
     }
 
-    /// Parse and evaluate an expression (e.g., "2 + 3 * 4")
+    /// Parse and evaluate an expression (e.g., "2 + 3 * 4"), or assign one
+    /// to variables (e.g., "x = y = 2 * pi")
     pub fn eval(&mut self, expr: &str) -> CalcResult<f64> {
         let expr = expr.trim();
-        let result = self.parse_expression(expr)?;
+        let tokens = self.tokenize(expr)?;
+
+        // Leading `name =` pairs are targets; assignments chain right to left
+        let mut targets = Vec::new();
+        let mut start = 0;
+        while tokens.get(start + 1).is_some_and(|t| t == "=") {
+            let name = &tokens[start];
+            Self::check_assignable(name)?;
+            targets.push(name.clone());
+            start += 2;
+        }
+
+        let (result, pos) = self.parse_additive(&tokens, start)?;
+        if let Some(token) = tokens.get(pos) {
+            return Err(CalcError::ParseError(format!(
+                "Unexpected token: {}",
+                token
+            )));
+        }
+        for name in targets {
+            self.variables.insert(name, result);
+        }
         self.accumulator = result;
         self.display = format!("{}", result);
         self.history.push(format!("{} = {}", expr, result));
         Ok(result)
     }
 
-    /// Basic expression parser supporting +, -, *, /, parentheses, and functions
-    fn parse_expression(&self, expr: &str) -> CalcResult<f64> {
-        // Tokenize
-        let tokens = self.tokenize(expr)?;
+    /// The expression with its tokens evenly spaced, e.g. `2*(3+ 4)` as
+    /// `2 * (3 + 4)`
+    pub fn normalize(&self, expr: &str) -> CalcResult<String> {
+        let tokens = self.tokenize(expr.trim())?;
+        let mut normalized = String::new();
+        let mut previous: Option<&str> = None;
+        for token in &tokens {
+            let token = token.as_str();
+            let space = match previous {
+                None => false,
+                Some(prev) => {
+                    let unary_sign = matches!(prev, "+" | "-")
+                        && !Self::ends_operand(normalized.trim_end_matches(['+', '-']).trim_end());
+                    !(prev == "("
+                        || unary_sign
+                        || matches!(token, ")" | ",")
+                        || (token == "(" && FUNCTIONS.contains(&prev)))
+                }
+            };
+            if space {
+                normalized.push(' ');
+            }
+            normalized.push_str(token);
+            previous = Some(token);
+        }
+        Ok(normalized)
+    }
 
-        // Simple recursive descent parser
-        let (result, _) = self.parse_additive(&tokens, 0)?;
-        Ok(result)
+    /// Whether normalized text so far ends with a value, so that a `+` or
+    /// `-` after it is binary
+    fn ends_operand(text: &str) -> bool {
+        text.ends_with(|c: char| c.is_alphanumeric() || c == '_' || c == '.' || c == ')')
+    }
+
+    /// Whether `name` can be assigned: an identifier that isn't a number,
+    /// function, constant or `ans`
+    fn check_assignable(name: &str) -> CalcResult<()> {
+        let identifier = name.starts_with(|c: char| c.is_alphabetic() || c == '_')
+            && name.chars().all(|c| c.is_alphanumeric() || c == '_');
+        if !identifier || name.parse::<f64>().is_ok() {
+            return Err(CalcError::InvalidOp(format!("Can't assign to {}", name)));
+        }
+        if FUNCTIONS.contains(&name) || constant(name).is_some() || name == ANSWER {
+            return Err(CalcError::InvalidOp(format!(
+                "Can't assign to built-in {}",
+                name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Value of a constant, `ans` or an assigned variable
+    fn lookup(&self, name: &str) -> CalcResult<f64> {
+        if let Some(value) = constant(name) {
+            return Ok(value);
+        }
+        if name == ANSWER {
+            return Ok(self.accumulator);
+        }
+        self.variables
+            .get(name)
+            .copied()
+            .ok_or_else(|| CalcError::UndefinedVariable(name.to_string()))
     }
 
     /// Tokenize an expression into numbers, operators, functions, and parentheses
@@ -73,7 +214,9 @@ impl Calculator {
 
         for ch in expr.chars() {
             match ch {
-                '+' | '-' | '*' | '/' | '(' | ')' => {
+                // The sign of an exponent, as in `1.5e-3`
+                '+' | '-' if Self::is_exponent_prefix(&current) => current.push(ch),
+                '+' | '-' | '*' | '/' | '^' | '(' | ')' | '=' | ',' => {
                     if !current.is_empty() {
                         tokens.push(current.clone());
                         current.clear();
@@ -97,6 +240,15 @@ impl Calculator {
         Ok(tokens)
     }
 
+    /// Whether `token` is a mantissa waiting for its exponent, like `1.5e`
+    fn is_exponent_prefix(token: &str) -> bool {
+        let Some(mantissa) = token.strip_suffix(['e', 'E']) else {
+            return false;
+        };
+        mantissa.starts_with(|c: char| c.is_ascii_digit() || c == '.')
+            && mantissa.parse::<f64>().is_ok()
+    }
+
     /// Parse addition and subtraction (lowest precedence)
     fn parse_additive(&self, tokens: &[String], mut pos: usize) -> CalcResult<(f64, usize)> {
         let (mut left, new_pos) = self.parse_multiplicative(tokens, pos)?;
@@ -162,14 +314,34 @@ impl Calculator {
 
         match tokens[pos].as_str() {
             "-" => {
-                let (val, new_pos) = self.parse_primary(tokens, pos + 1)?;
+                let (val, new_pos) = self.parse_unary(tokens, pos + 1)?;
                 Ok((-val, new_pos))
             }
-            "+" => self.parse_primary(tokens, pos + 1),
-            _ => self.parse_primary(tokens, pos),
+            "+" => self.parse_unary(tokens, pos + 1),
+            _ => self.parse_power(tokens, pos),
         }
     }
 
+    /// Parse exponentiation, which is right-associative and binds tighter
+    /// than unary minus (`-2^2 = -4`, `2^3^2 = 512`)
+    fn parse_power(&self, tokens: &[String], pos: usize) -> CalcResult<(f64, usize)> {
+        let (base, pos) = self.parse_primary(tokens, pos)?;
+        if tokens.get(pos).is_some_and(|t| t == "^") {
+            let (exponent, pos) = self.parse_unary(tokens, pos + 1)?;
+            return Ok((Self::power(base, exponent)?, pos));
+        }
+        Ok((base, pos))
+    }
+
+    fn power(base: f64, exponent: f64) -> CalcResult<f64> {
+        if base < 0.0 && exponent.fract() != 0.0 {
+            return Err(CalcError::DomainError(
+                "fractional power of negative".to_string(),
+            ));
+        }
+        Ok(base.powf(exponent))
+    }
+
     /// Parse primary terms: numbers, functions, and parenthesized expressions
     fn parse_primary(&self, tokens: &[String], pos: usize) -> CalcResult<(f64, usize)> {
         if pos >= tokens.len() {
@@ -186,50 +358,92 @@ impl Calculator {
                 }
                 Ok((val, new_pos + 1))
             }
-            // Math functions
+            name if FUNCTIONS.contains(&name) => self.parse_function(name, tokens, pos + 1),
+            // Number, or a name to look up
+            token => match token.parse::<f64>() {
+                Ok(n) => Ok((n, pos + 1)),
+                Err(_) if token.starts_with(|c: char| c.is_alphabetic() || c == '_') => {
+                    self.lookup(token).map(|n| (n, pos + 1))
+                }
+                Err(_) => Err(CalcError::ParseError(format!("Invalid token: {}", token))),
+            },
+        }
+    }
+
+    /// Parse the arguments of function `name` starting at `pos` and apply it.
+    /// One-argument functions take a primary term, as in `sqrt 16` or
+    /// `sqrt(16)`; two-argument ones need `(a, b)`.
+    fn parse_function(
+        &self,
+        name: &str,
+        tokens: &[String],
+        pos: usize,
+    ) -> CalcResult<(f64, usize)> {
+        if !BINARY_FUNCTIONS.contains(&name) {
+            let (val, new_pos) = self.parse_primary(tokens, pos)?;
+            return Ok((self.apply(name, val)?, new_pos));
+        }
+
+        let expect = |pos: usize, token: &str| {
+            if tokens.get(pos).is_some_and(|t| t == token) {
+                Ok(pos + 1)
+            } else {
+                Err(CalcError::ParseError(format!(
+                    "Expected '{}' in {}(a, b)",
+                    token, name
+                )))
+            }
+        };
+        let pos = expect(pos, "(")?;
+        let (a, pos) = self.parse_additive(tokens, pos)?;
+        let pos = expect(pos, ",")?;
+        let (b, pos) = self.parse_additive(tokens, pos)?;
+        let pos = expect(pos, ")")?;
+
+        let result = match name {
+            "atan2" => self.angle_mode.in_mode(a.atan2(b)),
+            _ => Self::power(a, b)?,
+        };
+        Ok((result, pos))
+    }
+
+    /// Apply a one-argument function
+    fn apply(&self, name: &str, val: f64) -> CalcResult<f64> {
+        let mode = self.angle_mode;
+        Ok(match name {
             "sqrt" => {
-                let (val, new_pos) = self.parse_primary(tokens, pos + 1)?;
                 if val < 0.0 {
                     return Err(CalcError::DomainError("sqrt of negative".to_string()));
                 }
-                Ok((val.sqrt(), new_pos))
-            }
-            "sin" => {
-                let (val, new_pos) = self.parse_primary(tokens, pos + 1)?;
-                Ok((val.sin(), new_pos))
-            }
-            "cos" => {
-                let (val, new_pos) = self.parse_primary(tokens, pos + 1)?;
-                Ok((val.cos(), new_pos))
-            }
-            "tan" => {
-                let (val, new_pos) = self.parse_primary(tokens, pos + 1)?;
-                Ok((val.tan(), new_pos))
-            }
-            "abs" => {
-                let (val, new_pos) = self.parse_primary(tokens, pos + 1)?;
-                Ok((val.abs(), new_pos))
+                val.sqrt()
             }
+            "sin" => mode.to_radians(val).sin(),
+            "cos" => mode.to_radians(val).cos(),
+            "tan" => mode.to_radians(val).tan(),
+            "abs" => val.abs(),
             "ln" => {
-                let (val, new_pos) = self.parse_primary(tokens, pos + 1)?;
                 if val <= 0.0 {
                     return Err(CalcError::DomainError("ln of non-positive".to_string()));
                 }
-                Ok((val.ln(), new_pos))
+                val.ln()
             }
             "log" => {
-                let (val, new_pos) = self.parse_primary(tokens, pos + 1)?;
                 if val <= 0.0 {
                     return Err(CalcError::DomainError("log of non-positive".to_string()));
                 }
-                Ok((val.log10(), new_pos))
+                val.log10()
             }
-            // Number
-            token => token
-                .parse::<f64>()
-                .map(|n| (n, pos + 1))
-                .map_err(|_| CalcError::ParseError(format!("Invalid token: {}", token))),
-        }
+            "exp" => val.exp(),
+            "floor" => val.floor(),
+            "ceil" => val.ceil(),
+            "round" => val.round(),
+            _ => return Err(CalcError::InvalidOp(format!("Unknown function: {}", name))),
+        })
+    }
+
+    /// Switch the unit of the trigonometric functions
+    pub fn set_angle_mode(&mut self, mode: AngleMode) {
+        self.angle_mode = mode;
     }
 
     /// Clear accumulator and display
@@ -242,6 +456,29 @@ impl Calculator {
     pub fn history(&self) -> &[String] {
         &self.history
     }
+
+    /// Value of an assigned variable
+    pub fn variable(&self, name: &str) -> Option<f64> {
+        self.variables.get(name).copied()
+    }
+
+    /// Memory store (MS): keep the last result in `mem`
+    pub fn memory_store(&mut self) {
+        self.variables.insert(MEMORY.to_string(), self.accumulator);
+    }
+
+    /// Memory recall (MR): show the value in `mem`, 0 when empty
+    pub fn memory_recall(&mut self) -> f64 {
+        let value = self.variable(MEMORY).unwrap_or(0.0);
+        self.accumulator = value;
+        self.display = format!("{}", value);
+        value
+    }
+
+    /// Memory clear (MC): empty `mem`
+    pub fn memory_clear(&mut self) {
+        self.variables.remove(MEMORY);
+    }
 }
 
 #[cfg(test)]
@@ -312,4 +549,124 @@ mod tests {
         assert_eq!(calc.history.len(), 2);
         assert!(calc.history[0].contains("= 5"));
     }
+
+    #[test]
+    fn test_chained_assignments() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.eval("x = 5").unwrap(), 5.0);
+        assert_eq!(calc.eval("y = z = x * 2").unwrap(), 10.0);
+        assert_eq!(calc.variable("y"), Some(10.0));
+        assert_eq!(calc.variable("z"), Some(10.0));
+        assert_eq!(calc.eval("x + y + z").unwrap(), 25.0);
+        assert_eq!(calc.eval("ans / 5").unwrap(), 5.0);
+        assert!(matches!(calc.eval("pi = 3"), Err(CalcError::InvalidOp(_))));
+        assert!(matches!(calc.eval("2 = 3"), Err(CalcError::InvalidOp(_))));
+    }
+
+    #[test]
+    fn test_undefined_variable() {
+        let mut calc = Calculator::new();
+        match calc.eval("w + 1") {
+            Err(CalcError::UndefinedVariable(name)) => assert_eq!(name, "w"),
+            other => panic!("expected undefined variable, got {:?}", other),
+        }
+        // A failed assignment stores nothing
+        assert!(calc.eval("v = w * 2").is_err());
+        assert_eq!(calc.variable("v"), None);
+    }
+
+    #[test]
+    fn test_constants() {
+        let mut calc = Calculator::new();
+        let result = calc.eval("2 * pi").unwrap();
+        assert!((result - 2.0 * std::f64::consts::PI).abs() < 1e-9);
+        let result = calc.eval("ln e").unwrap();
+        assert!((result - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_power_operator() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.eval("2^3^2").unwrap(), 512.0);
+        assert_eq!(calc.eval("-2^2").unwrap(), -4.0);
+        assert_eq!(calc.eval("(-2)^2").unwrap(), 4.0);
+        assert_eq!(calc.eval("2^-1").unwrap(), 0.5);
+        assert_eq!(calc.eval("2 * 3^2 + 1").unwrap(), 19.0);
+        assert_eq!(calc.eval("pow(2, 10)").unwrap(), 1024.0);
+        assert!(matches!(
+            calc.eval("(-8)^0.5"),
+            Err(CalcError::DomainError(_))
+        ));
+        assert!(calc.eval("2^").is_err());
+    }
+
+    #[test]
+    fn test_scientific_notation() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.eval("1e3 + 1").unwrap(), 1001.0);
+        assert_eq!(calc.eval("1.5e-3 * 2").unwrap(), 0.003);
+        assert_eq!(calc.eval("2E+2-1").unwrap(), 199.0);
+        // `e` on its own is still the constant
+        assert!((calc.eval("e-1").unwrap() - (std::f64::consts::E - 1.0)).abs() < 1e-9);
+        calc.eval("x1e = 4").unwrap();
+        assert_eq!(calc.eval("x1e-1").unwrap(), 3.0);
+    }
+
+    #[test]
+    fn test_additional_functions() {
+        let mut calc = Calculator::new();
+        let result = calc.eval("atan2(1, 1)").unwrap();
+        assert!((result - std::f64::consts::FRAC_PI_4).abs() < 1e-9);
+        assert_eq!(calc.eval("floor 2.7 + ceil 2.1").unwrap(), 5.0);
+        assert_eq!(calc.eval("round(-2.5)").unwrap(), -3.0);
+        assert!((calc.eval("exp 1").unwrap() - std::f64::consts::E).abs() < 1e-9);
+        assert_eq!(calc.eval("atan2(0, 1 + 1) + 1").unwrap(), 1.0);
+        assert!(matches!(
+            calc.eval("atan2 1"),
+            Err(CalcError::ParseError(_))
+        ));
+        assert!(matches!(calc.eval("pow(2)"), Err(CalcError::ParseError(_))));
+    }
+
+    #[test]
+    fn test_angle_modes() {
+        let mut calc = Calculator::new();
+        assert!((calc.eval("sin 90").unwrap() - 90f64.sin()).abs() < 1e-9);
+
+        calc.set_angle_mode(AngleMode::Degrees);
+        assert!((calc.eval("sin 90").unwrap() - 1.0).abs() < 1e-9);
+        assert!((calc.eval("cos 180").unwrap() + 1.0).abs() < 1e-9);
+        assert!((calc.eval("atan2(1, 1)").unwrap() - 45.0).abs() < 1e-9);
+
+        calc.set_angle_mode(AngleMode::Radians);
+        assert!((calc.eval("sin(pi / 2)").unwrap() - 1.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_normalize() {
+        let calc = Calculator::new();
+        assert_eq!(calc.normalize(" 2*(3+ 4)").unwrap(), "2 * (3 + 4)");
+        assert_eq!(calc.normalize("-2^-1").unwrap(), "-2 ^ -1");
+        assert_eq!(
+            calc.normalize("atan2( 1,1 )- 1e-3").unwrap(),
+            "atan2(1, 1) - 1e-3"
+        );
+        assert_eq!(calc.normalize("x=sqrt 16").unwrap(), "x = sqrt 16");
+    }
+
+    #[test]
+    fn test_memory_keys() {
+        let mut calc = Calculator::new();
+        assert_eq!(calc.memory_recall(), 0.0);
+        calc.eval("6 * 7").unwrap();
+        calc.memory_store();
+        calc.eval("1 + 1").unwrap();
+        assert_eq!(calc.eval("mem + ans").unwrap(), 44.0);
+        assert_eq!(calc.memory_recall(), 42.0);
+        calc.memory_clear();
+        assert!(matches!(
+            calc.eval("mem"),
+            Err(CalcError::UndefinedVariable(_))
+        ));
+    }
 }
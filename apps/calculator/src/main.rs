@@ -1,10 +1,12 @@
-use lucastra_calculator::Calculator;
+use lucastra_calculator::{AngleMode, Calculator};
 
 fn main() {
     let mut calc = Calculator::new();
 
     println!("LucAstra Calculator v0.1.0");
-    println!("Type expressions like '2 + 3 * 4' or 'sin 0.5'");
+    println!("Type expressions like '2 + 3 * 4' or 'sin 0.5', or assign with 'x = 2 * pi'");
+    println!("'ms', 'mr' and 'mc' store, recall and clear memory");
+    println!("'deg' and 'rad' switch the unit of sin, cos, tan and atan2");
     println!("Type 'exit' to quit\n");
 
     loop {
@@ -27,6 +29,34 @@ fn main() {
             continue;
         }
 
+        if input.eq_ignore_ascii_case("ms") {
+            calc.memory_store();
+            println!("Stored {}", calc.accumulator);
+            continue;
+        }
+
+        if input.eq_ignore_ascii_case("mr") {
+            println!("Result: {}", calc.memory_recall());
+            continue;
+        }
+
+        if input.eq_ignore_ascii_case("mc") {
+            calc.memory_clear();
+            println!("Memory cleared");
+            continue;
+        }
+
+        if input.eq_ignore_ascii_case("deg") || input.eq_ignore_ascii_case("rad") {
+            let mode = if input.eq_ignore_ascii_case("deg") {
+                AngleMode::Degrees
+            } else {
+                AngleMode::Radians
+            };
+            calc.set_angle_mode(mode);
+            println!("Angles in {:?}", mode);
+            continue;
+        }
+
         if input.eq_ignore_ascii_case("history") {
             if calc.history().is_empty() {
                 println!("No history");
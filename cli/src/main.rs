@@ -1,14 +1,33 @@
 //! CLI commands for interactive LucAstra usage.
 
-use clap::{Parser, Subcommand};
+use chrono::{DateTime, Datelike, Utc};
+use clap::{Args, Parser, Subcommand};
+use futures::StreamExt;
+use lucastra_browser::HtmlExtractor;
+use lucastra_core::{rpc, slash, ChatInput, CommandPayload, RpcRequest, RpcResponse, SlashAction};
+use lucastra_i18n::{t, Locale};
 use lucastra_llm::{
-    conversation::{Conversation, Message, Role},
-    providers::{create_provider, CompletionRequest, EmbeddingRequest, ProviderConfig},
-    rate_limit::RateLimiter,
+    conversation::{Conversation, ExportFormat, Message, Role},
+    conversation_store::ConversationStore,
+    prompt_template::{standard_variables, PromptRegistry},
+    providers::{
+        create_provider, CompletionRequest, CompletionResponse, EmbeddingRequest, ProviderConfig,
+        ProviderError, StopReason,
+    },
+    rate_limit::{estimate_tokens, RateLimiter, RateLimiters, RequestClass},
+    EmbeddingCache, EmbeddingOutcome, EmbeddingPipeline, LLMProvider, UsageTotals, UsageTracker,
+};
+use lucastra_search::{
+    chunk_metadata, vector::VectorIndex, ChunkStrategy, Chunker, EmbeddingModelInfo, Indexer,
+    MetadataFilter, SearchService, VectorSearchResult, PAGE_BREAK,
 };
-use lucastra_search::vector::VectorIndex;
+use lucastra_tools::file_access::{AuditFilter, AuditLog, FileOperation};
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+/// File name of the semantic index, kept beside the keyword index.
+const VECTOR_INDEX_FILE: &str = "vector_index.bin";
 
 #[derive(Parser)]
 #[command(name = "lucastra")]
@@ -17,9 +36,13 @@ struct Cli {
     #[command(subcommand)]
     command: Commands,
 
-    /// Path to provider config file
+    /// Path to a provider config file (default: [providers] in config.toml)
     #[arg(short, long, global = true)]
     config: Option<PathBuf>,
+
+    /// Provider entry in config.toml to use (default: providers.default)
+    #[arg(long, global = true, conflicts_with = "config")]
+    provider: Option<String>,
 }
 
 #[derive(Subcommand)]
@@ -36,6 +59,24 @@ enum Commands {
         /// Enable streaming responses
         #[arg(short, long)]
         stream: bool,
+
+        /// Resume a stored conversation by id (saved back on exit)
+        #[arg(long)]
+        conversation: Option<String>,
+
+        /// List stored conversations with their titles and exit
+        #[arg(long)]
+        list_conversations: bool,
+
+        /// Prompt template for the system prompt (default: llm.system_prompt_template)
+        #[arg(long)]
+        prompt: Option<String>,
+
+        #[command(flatten)]
+        transcript: TranscriptArgs,
+
+        #[command(flatten)]
+        titles: TitleArgs,
     },
 
     /// Generate embeddings for text or files
@@ -66,23 +107,48 @@ enum Commands {
         #[arg(short, long, default_value = "0.0")]
         threshold: f32,
 
-        /// Path to vector index
+        /// Path to vector index (default: vector_index.bin in storage.data_dir)
         #[arg(short, long)]
         index: Option<PathBuf>,
+
+        /// Only return documents whose metadata matches (repeatable): "dir=~/Documents/projects"
+        /// for files below a directory, "modified=2026-10" for a month or day, or any KEY=VALUE
+        /// such as "format=pdf"
+        #[arg(long = "filter", value_name = "KEY=VALUE", value_parser = parse_filter)]
+        filters: Vec<(String, String)>,
     },
 
     /// Index documents for semantic search
     Index {
         /// Directory or file to index
-        path: PathBuf,
+        #[arg(required_unless_present_any = ["reindex", "profile"])]
+        path: Option<PathBuf>,
 
-        /// Output index path
+        /// Index the root of this [[search.profiles]] entry, removing
+        /// documents its patterns now exclude
+        #[arg(long, conflicts_with_all = ["path", "reindex"])]
+        profile: Option<String>,
+
+        /// Re-embed the semantic index's snippets with the current embedding
+        /// model instead of indexing files
+        #[arg(long, conflicts_with_all = ["output", "extensions"])]
+        reindex: bool,
+
+        /// Directory for the keyword and semantic indexes (default: storage.data_dir)
         #[arg(short, long)]
         output: Option<PathBuf>,
 
         /// File extensions to include (e.g., "txt,md,rs")
         #[arg(short, long)]
         extensions: Option<String>,
+
+        /// How files are split before embedding: fixed, paragraph or markdown
+        #[arg(long, default_value = "fixed")]
+        chunking: ChunkStrategy,
+
+        /// Embedding requests to run at once
+        #[arg(long, default_value = "4")]
+        concurrency: usize,
     },
 
     /// Show provider health and status
@@ -90,28 +156,163 @@ enum Commands {
         /// Show detailed provider information
         #[arg(short, long)]
         verbose: bool,
+
+        /// Show token usage and cost for today and this month
+        #[arg(long)]
+        usage: bool,
+    },
+
+    /// Show host file access audit entries
+    Audit {
+        /// Number of most recent matching entries to show
+        #[arg(short = 'n', long, default_value = "20")]
+        last: usize,
+
+        /// Only this operation: read, write, move, copy, delete or list
+        #[arg(long)]
+        operation: Option<FileOperation>,
+
+        /// Only failed operations
+        #[arg(long, conflicts_with = "succeeded")]
+        failed: bool,
+
+        /// Only successful operations
+        #[arg(long)]
+        succeeded: bool,
+
+        /// Only paths starting with this prefix
+        #[arg(long)]
+        path_prefix: Option<String>,
+
+        /// Only entries at or after this RFC 3339 time
+        #[arg(long)]
+        since: Option<DateTime<Utc>>,
+
+        /// Only entries at or before this RFC 3339 time
+        #[arg(long)]
+        until: Option<DateTime<Utc>>,
+
+        /// Audit log to read (default: logs/file_access_audit.log)
+        #[arg(long)]
+        log: Option<PathBuf>,
+    },
+
+    /// Call a method of the running LucAstra's JSON-RPC API
+    Rpc {
+        /// Method, e.g. search, list_devices or execute_tool
+        method: String,
+
+        /// Params as JSON, e.g. '{"query": "notes"}'
+        params: Option<String>,
+    },
+
+    /// Manage config.toml
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
     },
 }
 
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// List the backups of config.toml, or restore one
+    Restore {
+        /// Backup to restore, 1 being the newest
+        backup: Option<usize>,
+    },
+}
+
+/// Import/export options for chat transcripts.
+#[derive(Args)]
+struct TranscriptArgs {
+    /// Load the session from a transcript file before chatting
+    #[arg(long)]
+    import: Option<PathBuf>,
+
+    /// Write the session to a transcript file on exit
+    #[arg(long)]
+    export: Option<PathBuf>,
+
+    /// Transcript format: json, markdown or openai (default: from file extension)
+    #[arg(long)]
+    format: Option<ExportFormat>,
+}
+
+impl TranscriptArgs {
+    fn format_for(&self, path: &std::path::Path) -> ExportFormat {
+        self.format
+            .unwrap_or_else(|| match path.extension().and_then(|e| e.to_str()) {
+                Some("md") | Some("markdown") => ExportFormat::Markdown,
+                _ => ExportFormat::Json,
+            })
+    }
+}
+
+/// Conversation title options for chat.
+#[derive(Args)]
+struct TitleArgs {
+    /// Title the conversation, replacing any existing title
+    #[arg(long)]
+    title: Option<String>,
+
+    /// Title new conversations with the start of the first message instead
+    /// of asking the provider
+    #[arg(long)]
+    no_auto_title: bool,
+}
+
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let cli = Cli::parse();
+    // The OS language until config.toml says otherwise
+    lucastra_i18n::set_current(Locale::resolve("auto"));
 
-    // Load provider config
-    let config = if let Some(config_path) = cli.config {
-        let config_str = std::fs::read_to_string(&config_path)?;
-        serde_json::from_str(&config_str)?
-    } else {
-        ProviderConfig::default()
+    // Needs no provider, and must work while the config can't be used
+    if let Commands::Config { action } = &cli.command {
+        return config_command(action);
+    }
+
+    // Load provider config, falling back to the [providers] table of config.toml
+    let config: ProviderConfig = match cli.config {
+        Some(config_path) => {
+            let config_str = std::fs::read_to_string(&config_path)?;
+            serde_json::from_str(&config_str)?
+        }
+        None => {
+            let app_config = lucastra_config::Config::load()?;
+            lucastra_llm::wire_log::install(lucastra_llm::WireLog::from_config(&app_config.llm));
+            lucastra_i18n::set_current(Locale::resolve(&app_config.gui.language));
+            app_config.providers.resolve(cli.provider.as_deref())?
+        }
     };
 
     match cli.command {
         Commands::Chat {
             message,
-            max_messages,
+            // Not enforced yet; see chat_loop
+            max_messages: _,
             stream,
+            conversation,
+            list_conversations,
+            prompt,
+            transcript,
+            titles,
         } => {
-            chat_command(config, message, max_messages, stream).await?;
+            if list_conversations {
+                list_conversations_command()?;
+            } else {
+                let system_prompt = chat_system_prompt(prompt)?;
+                chat_command(
+                    config,
+                    message,
+                    system_prompt,
+                    stream,
+                    conversation,
+                    transcript,
+                    titles,
+                )
+                .await?;
+            }
         }
         Commands::Embed { text, file, output } => {
             embed_command(config, text, file, output).await?;
@@ -121,19 +322,61 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             top_k,
             threshold,
             index,
+            filters,
         } => {
-            search_command(query, top_k, threshold, index).await?;
+            let filter = metadata_filter(filters)?;
+            search_command(config, query, top_k, threshold, index, filter).await?;
         }
         Commands::Index {
             path,
+            profile,
+            reindex,
             output,
             extensions,
+            chunking,
+            concurrency,
         } => {
-            index_command(config, path, output, extensions).await?;
+            if reindex {
+                reindex_command(config, concurrency).await?;
+            } else {
+                let target = match (path, profile) {
+                    (Some(path), _) => IndexTarget::Path(path),
+                    (None, profile) => IndexTarget::Profile(profile.unwrap_or_default()),
+                };
+                index_command(config, target, output, extensions, chunking, concurrency).await?;
+            }
         }
-        Commands::Status { verbose } => {
-            status_command(config, verbose).await?;
+        Commands::Status { verbose, usage } => {
+            status_command(config, verbose, usage).await?;
         }
+        Commands::Audit {
+            last,
+            operation,
+            failed,
+            succeeded,
+            path_prefix,
+            since,
+            until,
+            log,
+        } => {
+            let filter = AuditFilter {
+                since,
+                until,
+                operation,
+                success: match (failed, succeeded) {
+                    (true, _) => Some(false),
+                    (_, true) => Some(true),
+                    _ => None,
+                },
+                path_prefix,
+                limit: Some(last),
+            };
+            audit_command(log, filter)?;
+        }
+        Commands::Rpc { method, params } => {
+            rpc_command(&method, params.as_deref())?;
+        }
+        Commands::Config { .. } => unreachable!("handled before loading providers"),
     }
 
     Ok(())
@@ -142,38 +385,171 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
 async fn chat_command(
     config: ProviderConfig,
     initial_message: Option<String>,
-    _max_messages: usize,
+    system_prompt: String,
     stream: bool,
+    conversation_id: Option<String>,
+    transcript: TranscriptArgs,
+    titles: TitleArgs,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🤖 LucAstra Chat (provider: {})", config.provider);
-    println!("Type 'exit' or 'quit' to end the conversation.\n");
+    println!("{}", t!("cli.chat_header", provider = config.provider));
+    println!("{}\n", t!("cli.chat_hint"));
 
     let provider = create_provider(config.clone()).await?;
-    let mut conversation = Conversation::new(Some(
-        "You are LucAstra, a helpful AI assistant integrated into an augmented operating system."
-            .to_string(),
-    ));
-    let rate_limiter = RateLimiter::new(10); // 10 requests per minute
 
+    // Resume a stored conversation, or start a new one under the requested id
+    let store = match conversation_id {
+        Some(_) => Some(ConversationStore::new(
+            lucastra_config::get_data_dir()?.join("conversations"),
+        )?),
+        None => None,
+    };
+    // An imported transcript takes precedence and is stored under the requested id
+    let mut conversation = match (&store, &transcript.import, conversation_id) {
+        (_, Some(path), id) => {
+            let data = std::fs::read_to_string(path)?;
+            let mut conversation = Conversation::import(&data, transcript.format_for(path))?;
+            println!(
+                "{}\n",
+                t!(
+                    "cli.imported",
+                    count = conversation.len(),
+                    path = path.display()
+                )
+            );
+            if let Some(id) = id {
+                conversation.id = id;
+            }
+            conversation
+        }
+        (Some(store), None, Some(id)) if store.exists(&id) => {
+            let conversation = store.load(&id)?;
+            println!(
+                "{}\n",
+                t!("cli.resumed", id = id, count = conversation.len())
+            );
+            conversation
+        }
+        (_, None, Some(id)) => Conversation::with_id(id, Some(system_prompt)),
+        (_, None, None) => Conversation::new(Some(system_prompt)),
+    };
+    if let Some(title) = &titles.title {
+        conversation.set_title(title);
+    }
+    let rate_limiter =
+        RateLimiters::from_config(&config).limiter(&config.provider, RequestClass::Completion);
+    let mut usage = usage_tracker(&config)?;
+
+    let session = ChatSession {
+        config: &config,
+        provider: provider.as_ref(),
+        rate_limiter: &rate_limiter,
+        provider_name: &config.provider,
+        stream,
+        auto_title: !titles.no_auto_title,
+    };
+    let result = chat_loop(initial_message, &session, &mut conversation, &mut usage).await;
+
+    if let Err(e) = usage.save() {
+        eprintln!("{}", t!("cli.usage_not_saved", error = e));
+    }
+
+    // Write the conversation back even if the loop ended with an error
+    if let Some(store) = &store {
+        store.save(&conversation)?;
+        println!("{}", t!("cli.conversation_saved", id = conversation.id));
+    }
+    if let Some(path) = &transcript.export {
+        std::fs::write(path, conversation.export(transcript.format_for(path))?)?;
+        println!("{}", t!("cli.transcript_exported", path = path.display()));
+    }
+
+    result
+}
+
+/// Render the system prompt from template `name`, or the one configured in
+/// `llm.system_prompt_template`.
+fn chat_system_prompt(name: Option<String>) -> Result<String, Box<dyn std::error::Error>> {
+    let name = match name {
+        Some(name) => name,
+        None => lucastra_config::Config::load()?.llm.system_prompt_template,
+    };
+    let registry = PromptRegistry::load(&lucastra_config::get_prompts_dir()?);
+    Ok(registry.render(&name, &standard_variables())?)
+}
+
+/// Print the stored conversations, most recent first.
+fn list_conversations_command() -> Result<(), Box<dyn std::error::Error>> {
+    let store = ConversationStore::new(lucastra_config::get_data_dir()?.join("conversations"))?;
+    let summaries = store.list()?;
+    let locale = lucastra_i18n::current();
+    if summaries.is_empty() {
+        println!("{}", t!("cli.no_conversations"));
+    }
+    for summary in summaries {
+        let updated = chrono::DateTime::from_timestamp(summary.updated_at, 0)
+            .map(|t| locale.format_datetime(&t))
+            .unwrap_or_else(|| "-".to_string());
+        println!("{}  {}  {}", summary.id, updated, summary.title);
+    }
+    Ok(())
+}
+
+fn config_command(action: &ConfigAction) -> Result<(), Box<dyn std::error::Error>> {
+    match action {
+        ConfigAction::Restore {
+            backup: Some(number),
+        } => {
+            lucastra_config::Config::restore_backup(*number).map_err(|e| e.to_string())?;
+            println!(
+                "{}",
+                t!(
+                    "cli.backup_restored",
+                    number = number,
+                    path = lucastra_config::get_config_file_path()?.display()
+                )
+            );
+        }
+        ConfigAction::Restore { backup: None } => {
+            let backups = lucastra_config::Config::backups()?;
+            let locale = lucastra_i18n::current();
+            if backups.is_empty() {
+                println!("{}", t!("cli.no_backups"));
+            }
+            for backup in backups {
+                let saved = backup
+                    .saved_at
+                    .and_then(|t| chrono::DateTime::from_timestamp(t, 0))
+                    .map(|t| locale.format_datetime(&t))
+                    .unwrap_or_else(|| "-".to_string());
+                println!("{}  {}  {}", backup.number, saved, backup.path.display());
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn chat_loop(
+    initial_message: Option<String>,
+    session: &ChatSession<'_>,
+    conversation: &mut Conversation,
+    usage: &mut UsageTracker,
+) -> Result<(), Box<dyn std::error::Error>> {
     // Send initial message if provided
     if let Some(msg) = initial_message {
-        handle_user_message(
-            &msg,
-            provider.as_ref(),
-            &mut conversation,
-            &rate_limiter,
-            stream,
-        )
-        .await?;
+        handle_user_message(&msg, session, conversation, usage).await?;
     }
 
     // Interactive loop
     loop {
-        print!("You: ");
+        print!("{}", t!("cli.you"));
         io::stdout().flush()?;
 
         let mut input = String::new();
-        io::stdin().read_line(&mut input)?;
+        if io::stdin().read_line(&mut input)? == 0 {
+            // EOF
+            println!();
+            break;
+        }
         let input = input.trim();
 
         if input.is_empty() {
@@ -181,18 +557,19 @@ async fn chat_command(
         }
 
         if input == "exit" || input == "quit" {
-            println!("Goodbye! 👋");
+            println!("{}", t!("cli.goodbye"));
             break;
         }
 
-        handle_user_message(
-            input,
-            provider.as_ref(),
-            &mut conversation,
-            &rate_limiter,
-            stream,
-        )
-        .await?;
+        match slash::parse_input(input) {
+            Ok(ChatInput::Message(text)) => {
+                handle_user_message(&text, session, conversation, usage).await?
+            }
+            Ok(ChatInput::Command(action)) => {
+                run_slash_command(action, session, conversation).await;
+            }
+            Err(e) => eprintln!("⚠️  {}\n", e),
+        }
 
         // Trim conversation to max messages (TODO: implement proper trimming)
         // if conversation.messages().len() > max_messages {
@@ -203,33 +580,82 @@ async fn chat_command(
     Ok(())
 }
 
+/// What stays fixed for every message of a chat.
+struct ChatSession<'a> {
+    config: &'a ProviderConfig,
+    provider: &'a dyn lucastra_llm::providers::LLMProvider,
+    rate_limiter: &'a RateLimiter,
+    /// Configured provider name, used to price usage.
+    provider_name: &'a str,
+    stream: bool,
+    /// Ask the provider to title the conversation after its first exchange.
+    auto_title: bool,
+}
+
 async fn handle_user_message(
     message: &str,
-    provider: &dyn lucastra_llm::providers::LLMProvider,
+    session: &ChatSession<'_>,
     conversation: &mut Conversation,
-    rate_limiter: &RateLimiter,
-    _stream: bool,
+    usage: &mut UsageTracker,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let ChatSession {
+        config: _,
+        provider,
+        rate_limiter,
+        provider_name,
+        stream,
+        auto_title,
+    } = *session;
+
     conversation.add_message(Message {
         role: Role::User,
         content: message.to_string(),
         timestamp: chrono::Utc::now().timestamp(),
     });
 
-    // Rate limiting
-    rate_limiter.acquire().await;
-
-    // Generate completion
+    // Generate completion from the structured history
+    let messages = conversation.messages();
     let request = CompletionRequest {
-        prompt: conversation.to_prompt(),
         max_tokens: Some(512),
         temperature: Some(0.7),
         ..Default::default()
     };
 
-    let response = provider.complete(request).await?;
+    // Budget for the prompt plus the longest possible answer
+    let cost = messages
+        .iter()
+        .map(|m| estimate_tokens(&m.content))
+        .sum::<u32>()
+        + request.max_tokens.unwrap_or(0) as u32;
+    rate_limiter.acquire(cost).await;
+
+    let prompt: String = messages.iter().map(|m| m.content.as_str()).collect();
+    let response = if stream && provider.supports_streaming() {
+        print!("\n{}", t!("cli.assistant"));
+        io::stdout().flush()?;
 
-    println!("\n🤖 LucAstra: {}\n", response.content);
+        let mut chunks = provider.complete_chat_stream(&messages, request).await?;
+        let mut content = String::new();
+        while let Some(chunk) = chunks.next().await {
+            let chunk = chunk?;
+            print!("{}", chunk.delta);
+            io::stdout().flush()?;
+            content.push_str(&chunk.delta);
+        }
+        println!("\n");
+        // Streams don't report token counts, so usage is estimated
+        CompletionResponse {
+            content,
+            stop_reason: StopReason::Complete,
+            tokens_used: None,
+            model: Some(provider.default_model().to_string()),
+        }
+    } else {
+        let response = provider.complete_chat(&messages, request).await?;
+        println!("\n{}{}\n", t!("cli.assistant"), response.content);
+        response
+    };
+    usage.record_completion(provider_name, &prompt, &response);
 
     conversation.add_message(Message {
         role: Role::Assistant,
@@ -237,9 +663,69 @@ async fn handle_user_message(
         timestamp: chrono::Utc::now().timestamp(),
     });
 
+    if conversation.title().is_none() {
+        if auto_title {
+            rate_limiter.acquire(estimate_tokens(message) + 24).await;
+            conversation.ensure_title(Some(provider)).await;
+        } else {
+            conversation.ensure_title(None).await;
+        }
+    }
+
     Ok(())
 }
 
+/// Carry out a slash command typed in chat. Commands for the running system,
+/// such as `/devices`, need the GUI.
+async fn run_slash_command(
+    action: SlashAction,
+    session: &ChatSession<'_>,
+    conversation: &mut Conversation,
+) {
+    match action {
+        SlashAction::Help => println!("{}\n", slash::help_text()),
+        SlashAction::Clear => {
+            conversation.clear();
+            println!("{}\n", t!("cli.cleared"));
+        }
+        SlashAction::Run(CommandPayload::Status) => {
+            println!(
+                "{}\n",
+                t!(
+                    "cli.chat_status",
+                    provider = session.provider_name,
+                    count = conversation.len(),
+                    streaming = session.stream
+                )
+            );
+        }
+        SlashAction::Run(CommandPayload::Search { query, .. }) => {
+            let searched = search_command(
+                session.config.clone(),
+                query,
+                5,
+                0.0,
+                None,
+                MetadataFilter::new(),
+            )
+            .await;
+            match searched {
+                Ok(()) => println!(),
+                Err(e) => eprintln!("{}\n", t!("cli.search_failed", error = e)),
+            }
+        }
+        SlashAction::Run(CommandPayload::ReadFile { path }) => {
+            match std::fs::read_to_string(&path) {
+                Ok(content) => println!("{}:\n{}\n", path, content),
+                Err(e) => eprintln!("{}\n", t!("cli.cant_read", path = path, error = e)),
+            }
+        }
+        SlashAction::Run(_) | SlashAction::Settings => {
+            eprintln!("{}\n", t!("cli.gui_only"));
+        }
+    }
+}
+
 async fn embed_command(
     config: ProviderConfig,
     text: Option<String>,
@@ -250,7 +736,7 @@ async fn embed_command(
 
     if !provider.supports_embeddings() {
         return Err(format!(
-            "Provider '{}' does not support embeddings. Try using OpenAI provider.",
+            "Provider '{}' does not support embeddings. Try the openai or llamafile provider.",
             provider.name()
         )
         .into());
@@ -264,9 +750,10 @@ async fn embed_command(
         return Err("Either --text or --file must be provided".into());
     };
 
+    // Let the provider pick its own embedding model
     let request = EmbeddingRequest {
         texts: vec![content],
-        model: Some(provider.default_model().to_string()),
+        model: None,
     };
 
     println!("📊 Generating embeddings...");
@@ -285,73 +772,768 @@ async fn embed_command(
     Ok(())
 }
 
+/// Parse a `--filter key=value` argument.
+fn parse_filter(arg: &str) -> Result<(String, String), String> {
+    match arg.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => {
+            Ok((key.trim().to_string(), value.trim().to_string()))
+        }
+        _ => Err(format!("expected KEY=VALUE, got '{}'", arg)),
+    }
+}
+
+/// The filter for `--filter` arguments. `dir` matches files anywhere below
+/// the directory and `modified` dates by prefix; other keys match exactly.
+fn metadata_filter(
+    filters: Vec<(String, String)>,
+) -> Result<MetadataFilter, Box<dyn std::error::Error>> {
+    let mut filter = MetadataFilter::new();
+    for (key, value) in filters {
+        filter = match key.as_str() {
+            "dir" => {
+                let dir = lucastra_config::expand_allowed_dir(&value);
+                // Indexed paths are canonical, so the prefix must be too
+                let dir = std::fs::canonicalize(&dir).or_else(|_| std::path::absolute(&dir))?;
+                let mut prefix = dir.to_string_lossy().into_owned();
+                if !prefix.ends_with(std::path::MAIN_SEPARATOR) {
+                    prefix.push(std::path::MAIN_SEPARATOR);
+                }
+                filter.with_prefix(key, prefix)
+            }
+            "modified" => filter.with_prefix(key, value),
+            _ => filter.with_equals(key, value),
+        };
+    }
+    Ok(filter)
+}
+
 async fn search_command(
+    config: ProviderConfig,
     query: String,
-    _top_k: usize,
-    _threshold: f32,
-    _index_path: Option<PathBuf>,
+    top_k: usize,
+    threshold: f32,
+    index_path: Option<PathBuf>,
+    filter: MetadataFilter,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    // TODO: Load index from disk
-    let _index = VectorIndex::new(); // Default dimension
+    let index_path = match index_path {
+        Some(path) => path,
+        None => configured_vector_index()?,
+    };
+    let mut index = VectorIndex::load(&index_path)?;
 
     println!("🔍 Searching for: {}", query);
-    println!("   (Index loading not yet implemented)");
+    println!(
+        "   Index: {} ({} documents)",
+        index_path.display(),
+        index.len()
+    );
+
+    let results = search_index(config, query, top_k, threshold, &mut index, &filter).await?;
+    if results.is_empty() {
+        println!("\nNo results above threshold {:.2}", threshold);
+        return Ok(());
+    }
+
+    println!();
+    for (rank, result) in results.iter().enumerate() {
+        println!(
+            "{}. {} (score: {:.3})",
+            rank + 1,
+            result.path.display(),
+            result.score
+        );
+        if let Some(format) = result.metadata.get("format") {
+            match result.metadata.get("page") {
+                Some(page) => println!("   [{}, page {}]", format, page),
+                None => println!("   [{}]", format),
+            }
+        }
+        if !result.snippet.is_empty() {
+            println!("   {}", result.snippet.replace(['\n', PAGE_BREAK], " "));
+        }
+    }
 
     Ok(())
 }
 
+/// The `top_k` documents of `index` closest to `query` that pass `filter`
+/// and score at least `threshold`.
+async fn search_index(
+    config: ProviderConfig,
+    query: String,
+    top_k: usize,
+    threshold: f32,
+    index: &mut VectorIndex,
+    filter: &MetadataFilter,
+) -> Result<Vec<VectorSearchResult>, Box<dyn std::error::Error>> {
+    let provider = create_provider(config).await?;
+    if !provider.supports_embeddings() {
+        return Err(format!(
+            "Provider '{}' does not support embeddings, which semantic search requires.",
+            provider.name()
+        )
+        .into());
+    }
+
+    let response = provider
+        .embed(EmbeddingRequest {
+            texts: vec![query],
+            model: None,
+        })
+        .await?;
+    let query_embedding = response
+        .embeddings
+        .into_iter()
+        .next()
+        .ok_or("Provider returned no embedding for the query")?;
+    index.use_embedding_model(embedding_model_info(
+        provider.as_ref(),
+        query_embedding.len(),
+    ));
+
+    Ok(index
+        .search_filtered(&query_embedding, top_k, filter)
+        .map_err(|e| e.to_string())?
+        .into_iter()
+        .filter(|r| r.score >= threshold)
+        .collect())
+}
+
+/// What `lucastra index` crawls.
+enum IndexTarget {
+    Path(PathBuf),
+    /// The root of the named index profile
+    Profile(String),
+}
+
 async fn index_command(
-    _config: ProviderConfig,
-    path: PathBuf,
-    _output: Option<PathBuf>,
-    _extensions: Option<String>,
+    config: ProviderConfig,
+    target: IndexTarget,
+    output: Option<PathBuf>,
+    extensions: Option<String>,
+    chunking: ChunkStrategy,
+    concurrency: usize,
 ) -> Result<(), Box<dyn std::error::Error>> {
+    let app_config = lucastra_config::Config::load()?;
+    let vector_path = output
+        .as_deref()
+        .unwrap_or(&app_config.storage.data_dir)
+        .join(VECTOR_INDEX_FILE);
+    let index_path = output.unwrap_or_else(|| app_config.storage.data_dir.join("search_index"));
+    let profiles = app_config.search.profile_matchers();
+
+    // Only crawl the data directory, the host directories the user allowed
+    // and the roots of enabled index profiles
+    let mut allowed_roots = vec![app_config.storage.data_dir.clone()];
+    allowed_roots.extend(app_config.security.resolved_allowed_dirs());
+    allowed_roots.extend(
+        profiles
+            .iter()
+            .filter(|profile| profile.profile().enabled)
+            .map(|profile| profile.root().to_path_buf()),
+    );
+
+    let path = match target {
+        IndexTarget::Path(path) => path,
+        IndexTarget::Profile(name) => {
+            let profile = app_config
+                .search
+                .profile(&name)
+                .ok_or_else(|| format!("No index profile named \"{}\" in config.toml", name))?;
+            if !profile.enabled {
+                return Err(format!("Index profile \"{}\" is disabled", name).into());
+            }
+            profile.root
+        }
+    };
+
+    let mut indexer = Indexer::new()
+        .with_allowed_roots(allowed_roots)
+        .with_profiles(profiles)
+        .with_extractor(Arc::new(HtmlExtractor));
+    if let Some(extensions) = extensions {
+        indexer = indexer.with_extensions(
+            extensions
+                .split(',')
+                .map(|e| e.trim().to_string())
+                .filter(|e| !e.is_empty())
+                .collect(),
+        );
+    }
+
     println!("📚 Indexing documents from: {}", path.display());
-    println!("   (Document indexing not yet implemented)");
+
+    let service = SearchService::new(Some(index_path.clone()))
+        .with_tokenizer(lucastra_search::Tokenizer::from_config(&app_config.search));
+    let summary = indexer.index_path(&path, &service)?;
+    let removed = indexer.remove_excluded(&service);
+    service.save()?;
+
+    println!(
+        "✅ Indexed {} files ({} bytes), skipped {}",
+        summary.files_indexed, summary.bytes_indexed, summary.files_skipped
+    );
+    if summary.extraction_failures > 0 {
+        println!(
+            "   ⚠️  Could not extract text from {} files",
+            summary.extraction_failures
+        );
+    }
+    if !removed.is_empty() {
+        println!(
+            "   Removed {} documents their profile now excludes",
+            removed.len()
+        );
+    }
+    println!(
+        "   Index: {} ({} documents total)",
+        index_path.display(),
+        service.doc_count()
+    );
+
+    let provider = create_provider(config.clone()).await?;
+    if !provider.supports_embeddings() {
+        println!(
+            "   Skipping semantic index: provider '{}' does not support embeddings",
+            provider.name()
+        );
+        // Excluded documents still leave the semantic index it can't rebuild
+        if !removed.is_empty() && vector_path.exists() {
+            let mut vector_index = VectorIndex::load(&vector_path)?;
+            if vector_index.retain(|path| !removed.iter().any(|r| path == Path::new(r))) > 0 {
+                vector_index.save(&vector_path)?;
+            }
+        }
+        return Ok(());
+    }
+
+    // Rebuild the vector index from every indexed document so re-runs don't duplicate chunks
+    let chunker = Chunker::new(chunking);
+    let documents = service.documents();
+    let chunks: Vec<_> = documents
+        .iter()
+        .flat_map(|(doc_path, content)| {
+            chunker.chunk(content).into_iter().map(move |chunk| {
+                let metadata = chunk_metadata(Path::new(doc_path), content, &chunk);
+                (doc_path, chunk, metadata)
+            })
+        })
+        .collect();
+
+    let provider: Arc<dyn LLMProvider> = Arc::from(provider);
+    let outcome = embed_chunks(
+        &config,
+        provider.clone(),
+        concurrency,
+        chunks
+            .iter()
+            .map(|(_, chunk, _)| chunk.text.clone())
+            .collect(),
+    )
+    .await?;
+    for failed in &outcome.failed {
+        let (doc_path, _, _) = &chunks[failed.index];
+        println!(
+            "   ⚠️  Failed to embed chunk of {}: {}",
+            doc_path, failed.error
+        );
+    }
+
+    let mut vector_index = VectorIndex::new();
+    if let Some(dimensions) = outcome.embeddings.iter().flatten().map(Vec::len).next() {
+        vector_index.use_embedding_model(embedding_model_info(provider.as_ref(), dimensions));
+    }
+    for ((doc_path, chunk, metadata), embedding) in chunks.iter().zip(outcome.embeddings) {
+        if let Some(embedding) = embedding {
+            vector_index.add_chunk(PathBuf::from(doc_path), embedding, chunk, metadata.clone())?;
+        }
+    }
+
+    vector_index.save(&vector_path)?;
+    println!(
+        "   Semantic index: {} ({} chunks)",
+        vector_path.display(),
+        vector_index.len()
+    );
+
+    Ok(())
+}
+
+/// Re-embed the snippets of the semantic index with the current embedding
+/// model. The index is only replaced if every snippet was embedded.
+async fn reindex_command(
+    config: ProviderConfig,
+    concurrency: usize,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let vector_path = configured_vector_index()?;
+    let index = VectorIndex::load(&vector_path)?;
+    let provider: Arc<dyn LLMProvider> = Arc::from(create_provider(config.clone()).await?);
+    if !provider.supports_embeddings() {
+        return Err(format!(
+            "Provider '{}' does not support embeddings, which the semantic index requires.",
+            provider.name()
+        )
+        .into());
+    }
+
+    println!(
+        "🔁 Re-embedding {} chunks of {}",
+        index.len(),
+        vector_path.display()
+    );
+    if let Some(model) = index.embedding_model() {
+        println!("   Built with: {}", model);
+    }
+    let snippets = index.snippets().into_iter().map(str::to_string).collect();
+    let outcome = embed_chunks(&config, provider.clone(), concurrency, snippets).await?;
+    if !outcome.failed.is_empty() {
+        return Err(format!(
+            "{} chunks failed to embed (first: {}); the index was left unchanged",
+            outcome.failed.len(),
+            outcome.failed[0].error
+        )
+        .into());
+    }
+
+    let embeddings: Vec<Vec<f32>> = outcome.embeddings.into_iter().flatten().collect();
+    let dimensions = embeddings.first().map_or(0, Vec::len);
+    let model = embedding_model_info(provider.as_ref(), dimensions);
+    index
+        .reembedded(model.clone(), embeddings)?
+        .save(&vector_path)?;
+    println!("✅ Semantic index now uses {}", model);
 
     Ok(())
 }
 
+/// Embed `texts` through the embedding cache, printing progress and saving
+/// token usage.
+async fn embed_chunks(
+    config: &ProviderConfig,
+    provider: Arc<dyn LLMProvider>,
+    concurrency: usize,
+    texts: Vec<String>,
+) -> Result<EmbeddingOutcome, Box<dyn std::error::Error>> {
+    let embedding_limiter =
+        RateLimiters::from_config(config).limiter(&config.provider, RequestClass::Embedding);
+    let cache = EmbeddingCache::new(lucastra_config::get_data_dir()?.join("embedding_cache"))?;
+    let usage = Arc::new(Mutex::new(usage_tracker(config)?));
+    let pipeline = EmbeddingPipeline::new(provider)
+        .with_cache(cache)
+        .with_concurrency(concurrency)
+        .with_rate_limiter(embedding_limiter)
+        .with_usage_tracker(usage.clone());
+    let empty = texts.is_empty();
+    let outcome = pipeline
+        .embed(texts, |progress| {
+            print!(
+                "\r   Embedding chunks: {}/{}",
+                progress.completed, progress.total
+            );
+            let _ = io::stdout().flush();
+        })
+        .await;
+    if !empty {
+        println!();
+    }
+    if let Some(Err(e)) = usage.lock().ok().map(|usage| usage.save()) {
+        eprintln!("   ⚠️  Could not save usage: {}", e);
+    }
+    println!(
+        "   Embedded {} new, {} from cache",
+        outcome.embedded, outcome.from_cache
+    );
+    Ok(outcome)
+}
+
+/// The semantic index `lucastra index` writes without `--output`.
+fn configured_vector_index() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let app_config = lucastra_config::Config::load()?;
+    Ok(app_config.storage.data_dir.join(VECTOR_INDEX_FILE))
+}
+
+/// What `provider` embeds with, for recording in and checking against a
+/// vector index.
+fn embedding_model_info(provider: &dyn LLMProvider, dimensions: usize) -> EmbeddingModelInfo {
+    EmbeddingModelInfo::new(provider.name(), provider.embedding_model(), dimensions)
+}
+
+/// Usage tracker backed by `usage.json` in the data directory, priced from
+/// the configured provider and its fallbacks.
+fn usage_tracker(config: &ProviderConfig) -> Result<UsageTracker, Box<dyn std::error::Error>> {
+    let mut tracker = UsageTracker::load(lucastra_config::get_data_dir()?.join("usage.json"))?;
+    for provider_config in std::iter::once(config).chain(&config.fallbacks) {
+        tracker = tracker.with_pricing(&provider_config.provider, provider_config.pricing.clone());
+    }
+    Ok(tracker)
+}
+
+/// The configured embedding model next to the one the semantic index was
+/// built with.
+fn print_embedding_models(provider: &dyn LLMProvider) -> Result<(), Box<dyn std::error::Error>> {
+    let configured = format!("{}/{}", provider.name(), provider.embedding_model());
+    let vector_path = configured_vector_index()?;
+    if !vector_path.exists() {
+        println!("Embedding model: {} (no semantic index yet)", configured);
+        return Ok(());
+    }
+
+    let indexed = match VectorIndex::load(&vector_path) {
+        Ok(index) => index.embedding_model().cloned(),
+        Err(e) => {
+            println!("Embedding model: {} (index unreadable: {})", configured, e);
+            return Ok(());
+        }
+    };
+    match indexed {
+        Some(model)
+            if model.provider == provider.name() && model.name == provider.embedding_model() =>
+        {
+            println!("Embedding model: {} | index: {} ✓", configured, model);
+        }
+        Some(model) => println!(
+            "Embedding model: {} | index: {} ⚠️  run `lucastra-cli index --reindex`",
+            configured, model
+        ),
+        None => println!(
+            "Embedding model: {} | index: unknown (built before models were recorded)",
+            configured
+        ),
+    }
+    Ok(())
+}
+
+fn print_usage_totals(label: &str, totals: &UsageTotals) {
+    let mut line = format!(
+        "  {}: {} requests, {} tokens ({} in / {} out), ${:.4}",
+        label,
+        totals.requests,
+        totals.tokens(),
+        totals.input_tokens,
+        totals.output_tokens,
+        totals.cost_usd
+    );
+    if totals.estimated_requests > 0 {
+        line.push_str(&format!(" [{} estimated]", totals.estimated_requests));
+    }
+    println!("{}", line);
+}
+
+/// Print the models `provider` offers, warning when its configured model
+/// isn't one of them.
+async fn print_available_models(provider: &dyn LLMProvider) {
+    let models = match provider.list_models().await {
+        Ok(models) => models,
+        Err(ProviderError::UnsupportedError(_)) => {
+            println!("\nAvailable models: not listed by {}", provider.name());
+            return;
+        }
+        Err(e) => {
+            println!("\nAvailable models: unknown ({})", e);
+            return;
+        }
+    };
+
+    println!("\nAvailable models ({}):", models.len());
+    for model in &models {
+        match model.context_length {
+            Some(tokens) => println!("  {} ({} tokens)", model.id, tokens),
+            None => println!("  {}", model.id),
+        }
+    }
+    if !models.is_empty() && !models.iter().any(|m| m.id == provider.default_model()) {
+        println!(
+            "⚠️  Configured model '{}' is not one of them",
+            provider.default_model()
+        );
+    }
+}
+
 async fn status_command(
     config: ProviderConfig,
     verbose: bool,
+    usage: bool,
 ) -> Result<(), Box<dyn std::error::Error>> {
-    println!("🏥 LucAstra Provider Status\n");
+    println!("{}\n", t!("cli.status_header"));
 
     let provider = create_provider(config.clone()).await?;
 
-    println!("Provider: {}", provider.name());
-    println!("Model: {}", provider.default_model());
+    let mark = |supported: bool| if supported { "✓" } else { "✗" };
+    println!("{}", t!("cli.provider", name = provider.name()));
+    println!("{}", t!("cli.model", name = provider.default_model()));
     println!(
-        "Streaming: {}",
-        if provider.supports_streaming() {
-            "✓"
-        } else {
-            "✗"
-        }
+        "{}",
+        t!(
+            "cli.streaming",
+            supported = mark(provider.supports_streaming())
+        )
     );
     println!(
-        "Embeddings: {}",
-        if provider.supports_embeddings() {
-            "✓"
-        } else {
-            "✗"
-        }
+        "{}",
+        t!(
+            "cli.embeddings",
+            supported = mark(provider.supports_embeddings())
+        )
     );
 
-    print!("Health: ");
+    print_embedding_models(provider.as_ref())?;
+
+    print!("{}", t!("cli.health"));
     io::stdout().flush()?;
 
     match provider.health_check().await {
-        Ok(true) => println!("✅ Online"),
-        Ok(false) => println!("⚠️  Degraded"),
-        Err(e) => println!("❌ Offline ({})", e),
+        Ok(true) => println!("{}", t!("cli.online")),
+        Ok(false) => println!("{}", t!("cli.degraded")),
+        Err(e) => println!("{}", t!("cli.offline", error = e)),
+    }
+
+    let limiters = RateLimiters::from_config(&config);
+    let mut printed_header = false;
+    for provider_config in std::iter::once(&config).chain(&config.fallbacks) {
+        for class in [RequestClass::Completion, RequestClass::Embedding] {
+            let budget = provider_config.rate_limits.budget(class);
+            if budget == Default::default() {
+                continue;
+            }
+            if !printed_header {
+                println!("\nRate limits:");
+                printed_header = true;
+            }
+            let limit =
+                |value: Option<u32>| value.map_or("unlimited".to_string(), |v| v.to_string());
+            let saturation = limiters
+                .limiter(&provider_config.provider, class)
+                .saturation();
+            println!(
+                "  {} {}: {} req/min, {} tokens/min ({:.0}% used)",
+                provider_config.provider,
+                class,
+                limit(budget.requests_per_min),
+                limit(budget.tokens_per_min),
+                saturation.max() * 100.0
+            );
+        }
+    }
+
+    if usage {
+        let tracker = usage_tracker(&config)?;
+        let today = chrono::Local::now().date_naive();
+        let month_start = today.with_day(1).unwrap_or(today);
+
+        println!("\nUsage:");
+        print_usage_totals("Today", &tracker.today());
+        print_usage_totals("This month", &tracker.this_month());
+        for (provider, model, totals) in tracker.breakdown(month_start, today) {
+            print_usage_totals(&format!("  {} / {}", provider, model), &totals);
+        }
+        if tracker.this_month().estimated_requests > 0 {
+            println!("  Estimated counts come from character lengths, not provider reports.");
+        }
     }
 
     if verbose {
+        print_available_models(provider.as_ref()).await;
         println!("\nConfiguration:");
         println!("{:#?}", config);
     }
 
     Ok(())
 }
+
+fn audit_command(
+    log: Option<PathBuf>,
+    filter: AuditFilter,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let log_path = match log {
+        Some(path) => path,
+        None => lucastra_config::get_logs_dir()?.join("file_access_audit.log"),
+    };
+    let result = AuditLog::new(&log_path).query(&filter)?;
+
+    if result.entries.is_empty() {
+        println!("No matching audit entries in {}", log_path.display());
+    }
+    for entry in &result.entries {
+        let mut line = format!(
+            "{} {:<6} {} {}",
+            entry.timestamp,
+            entry.operation.map(|op| op.to_string()).unwrap_or_default(),
+            if entry.success { "ok  " } else { "FAIL" },
+            entry.source_path
+        );
+        if let Some(dest) = &entry.dest_path {
+            line.push_str(&format!(" -> {}", dest));
+        }
+        if let (Some(tool), Some(role)) = (&entry.tool, &entry.role) {
+            line.push_str(&format!(" [{} denied for {}]", tool, role));
+        } else if !entry.user_approved {
+            line.push_str(" [not approved]");
+        }
+        if let Some(error) = &entry.error_msg {
+            line.push_str(&format!(" ({})", error));
+        }
+        println!("{}", line);
+    }
+
+    if result.skipped > 0 {
+        eprintln!("Skipped {} malformed line(s)", result.skipped);
+    }
+    Ok(())
+}
+
+/// Call `method` over the JSON-RPC socket and print its result.
+fn rpc_command(method: &str, params: Option<&str>) -> Result<(), Box<dyn std::error::Error>> {
+    let params = match params {
+        Some(params) => {
+            serde_json::from_str(params).map_err(|e| format!("params must be JSON: {}", e))?
+        }
+        None => serde_json::Value::Null,
+    };
+    let socket = lucastra_config::Config::load()?.rpc.resolved_socket_path();
+    let token_path = lucastra_config::get_rpc_token_path()?;
+    let token = std::fs::read_to_string(&token_path).map_err(|e| {
+        format!(
+            "Can't read the API token at {}: {} (is rpc.enabled set?)",
+            token_path.display(),
+            e
+        )
+    })?;
+
+    let response = rpc_call(&socket, token.trim(), method, params)?;
+    match (response.result, response.error) {
+        (_, Some(error)) => Err(error.into()),
+        (result, None) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&result.unwrap_or_default())?
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Authenticate on `socket`, then send one request and return its response.
+#[cfg(unix)]
+fn rpc_call(
+    socket: &std::path::Path,
+    token: &str,
+    method: &str,
+    params: serde_json::Value,
+) -> Result<RpcResponse, Box<dyn std::error::Error>> {
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixStream;
+
+    let stream = UnixStream::connect(socket)
+        .map_err(|e| format!("Can't connect to {}: {}", socket.display(), e))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut call = |request: RpcRequest| -> Result<RpcResponse, Box<dyn std::error::Error>> {
+        writeln!(writer, "{}", serde_json::to_string(&request)?)?;
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            return Err("The server closed the connection".into());
+        }
+        Ok(serde_json::from_str(&line)?)
+    };
+
+    let auth = call(RpcRequest::new(
+        1,
+        rpc::AUTHENTICATE,
+        serde_json::json!({ "token": token }),
+    ))?;
+    if let Some(error) = auth.error {
+        return Err(error.into());
+    }
+    call(RpcRequest::new(2, method, params))
+}
+
+#[cfg(not(unix))]
+fn rpc_call(
+    _socket: &std::path::Path,
+    _token: &str,
+    _method: &str,
+    _params: serde_json::Value,
+) -> Result<RpcResponse, Box<dyn std::error::Error>> {
+    Err("The JSON-RPC API needs Unix domain sockets; named pipes aren't supported yet".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn mock_config() -> ProviderConfig {
+        ProviderConfig {
+            provider: "mock".to_string(),
+            dimensions: Some(8),
+            ..Default::default()
+        }
+    }
+
+    async fn index_files(paths: &[PathBuf]) -> VectorIndex {
+        let provider = create_provider(mock_config()).await.unwrap();
+        let mut index = VectorIndex::exact();
+        for path in paths {
+            let content = fs::read_to_string(path).unwrap();
+            for chunk in Chunker::new(ChunkStrategy::default()).chunk(&content) {
+                let embedding = provider
+                    .embed(EmbeddingRequest {
+                        texts: vec![chunk.text.clone()],
+                        model: None,
+                    })
+                    .await
+                    .unwrap()
+                    .embeddings
+                    .remove(0);
+                let metadata = chunk_metadata(path, &content, &chunk);
+                index
+                    .add_chunk(path.clone(), embedding, &chunk, metadata)
+                    .unwrap();
+            }
+        }
+        index
+    }
+
+    #[tokio::test]
+    async fn test_search_filters_narrow_by_dir_and_modified() {
+        let temp = tempfile::tempdir().unwrap();
+        let root = temp.path().canonicalize().unwrap();
+        let mut paths = Vec::new();
+        for dir in ["projects", "other"] {
+            fs::create_dir(root.join(dir)).unwrap();
+            let path = root.join(dir).join("plan.txt");
+            fs::write(&path, "The launch plan for next quarter.").unwrap();
+            paths.push(path);
+        }
+        let mut index = index_files(&paths).await;
+
+        let search = |filter: String| {
+            let cli =
+                Cli::try_parse_from(["lucastra", "search", "launch plan", "--filter", &filter])
+                    .unwrap();
+            let Commands::Search { query, filters, .. } = cli.command else {
+                panic!("expected the search command");
+            };
+            (query, metadata_filter(filters).unwrap())
+        };
+
+        let (query, filter) = search(format!("dir={}", root.join("projects").display()));
+        let results = search_index(mock_config(), query, 5, -1.0, &mut index, &filter)
+            .await
+            .unwrap();
+        let found: Vec<_> = results.iter().map(|r| r.path.clone()).collect();
+        assert_eq!(found, vec![paths[0].clone()]);
+
+        let (query, filter) = search(format!("modified={}", chrono::Local::now().format("%Y-%m")));
+        let results = search_index(mock_config(), query, 5, -1.0, &mut index, &filter)
+            .await
+            .unwrap();
+        assert_eq!(results.len(), 2);
+
+        let (query, filter) = search("modified=1999-01".to_string());
+        let results = search_index(mock_config(), query, 5, -1.0, &mut index, &filter)
+            .await
+            .unwrap();
+        assert!(results.is_empty());
+    }
+}
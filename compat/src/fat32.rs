@@ -0,0 +1,197 @@
+//! Read-only FAT32 filesystem driver for USB sticks and SD cards.
+//!
+//! Wraps [`FAT32Reader`] over a [`BlockDevice`]. Paths are virtual paths
+//! under the mount point (e.g. `/mnt/usb0/DOCS/notes.txt`); names match
+//! case-insensitively, as on FAT.
+
+use crate::loader::{FAT32Reader, FatDirEntry};
+use lucastra_core::{LuCastraError, Result};
+use lucastra_hal::{BlockDevice, FileBlockDevice, FileMetadata, FileSystemDriver, MockBlockDevice};
+use std::path::Path;
+use std::sync::Mutex;
+
+const SECTOR_SIZE: usize = 512;
+
+fn fat_error(message: impl std::fmt::Display) -> LuCastraError {
+    LuCastraError::FilesystemError(format!("FAT32: {}", message))
+}
+
+fn metadata(entry: &FatDirEntry) -> FileMetadata {
+    if entry.is_dir {
+        FileMetadata::dir()
+    } else {
+        FileMetadata::file(entry.size as u64)
+    }
+}
+
+/// Read-only FAT32 driver.
+pub struct Fat32FileSystem {
+    reader: FAT32Reader,
+    device: Mutex<Box<dyn BlockDevice + Send>>,
+    mount_point: Option<String>,
+}
+
+impl Fat32FileSystem {
+    /// Open a FAT32 volume from a device node or image file.
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        Self::from_device(Box::new(FileBlockDevice::open(path, SECTOR_SIZE)?))
+    }
+
+    /// Use an in-memory FAT32 image.
+    pub fn from_bytes(image: Vec<u8>) -> Result<Self> {
+        Self::from_device(Box::new(MockBlockDevice::from_bytes(image, SECTOR_SIZE)))
+    }
+
+    pub fn from_device(mut device: Box<dyn BlockDevice + Send>) -> Result<Self> {
+        let reader = FAT32Reader::from_device(device.as_mut())?;
+        Ok(Self {
+            reader,
+            device: Mutex::new(device),
+            mount_point: None,
+        })
+    }
+
+    /// Run `f` with the reader and exclusive access to the device.
+    fn with_device<T>(
+        &self,
+        f: impl FnOnce(&FAT32Reader, &mut dyn BlockDevice) -> Result<T>,
+    ) -> Result<T> {
+        let mut device = self
+            .device
+            .lock()
+            .map_err(|_| fat_error("device lock poisoned"))?;
+        f(&self.reader, device.as_mut())
+    }
+
+    /// Path relative to the volume root.
+    fn relative<'p>(&self, path: &'p str) -> Result<&'p str> {
+        let mount_point = self
+            .mount_point
+            .as_deref()
+            .ok_or_else(|| fat_error("filesystem is not mounted"))?;
+        path.strip_prefix(mount_point)
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+            .ok_or_else(|| fat_error(format!("{} is not under {}", path, mount_point)))
+    }
+}
+
+impl FileSystemDriver for Fat32FileSystem {
+    fn name(&self) -> &str {
+        "fat32"
+    }
+
+    fn mount(&mut self, path: &str) -> Result<()> {
+        tracing::info!("Mounting FAT32 volume at {}", path);
+        self.mount_point = Some(path.trim_end_matches('/').to_string());
+        Ok(())
+    }
+
+    fn unmount(&mut self) -> Result<()> {
+        tracing::info!("Unmounting FAT32 volume");
+        self.mount_point = None;
+        Ok(())
+    }
+
+    fn list_files(&self, path: &str) -> Result<Vec<String>> {
+        Ok(self
+            .list_entries(path)?
+            .into_iter()
+            .map(|(path, _)| path)
+            .collect())
+    }
+
+    fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let relative = self.relative(path)?;
+        self.with_device(|reader, device| reader.read_file(device, relative))
+    }
+
+    fn write_file(&mut self, path: &str, _data: &[u8]) -> Result<()> {
+        Err(fat_error(format!("volume is read-only: {}", path)))
+    }
+
+    fn stat(&self, path: &str) -> Result<FileMetadata> {
+        let relative = self.relative(path)?;
+        self.with_device(|reader, device| reader.lookup(device, relative))
+            .map(|entry| metadata(&entry))
+    }
+
+    fn list_entries(&self, path: &str) -> Result<Vec<(String, FileMetadata)>> {
+        let relative = self.relative(path)?;
+        let children = self.with_device(|reader, device| {
+            let dir = reader.lookup(device, relative)?;
+            if !dir.is_dir {
+                return Err(fat_error(format!("not a directory: {}", path)));
+            }
+            reader.read_dir(device, dir.first_cluster)
+        })?;
+
+        let base = path.trim_end_matches('/');
+        let mut entries: Vec<(String, FileMetadata)> = children
+            .iter()
+            .map(|entry| (format!("{}/{}", base, entry.name), metadata(entry)))
+            .collect();
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+
+    fn is_mounted(&self) -> bool {
+        self.mount_point.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::{five_kb, sample_image};
+
+    fn mounted() -> Fat32FileSystem {
+        let mut fs = Fat32FileSystem::from_bytes(sample_image()).unwrap();
+        fs.mount("/mnt/usb0").unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_lists_directories_without_volume_label() {
+        let fs = mounted();
+        assert_eq!(
+            fs.list_files("/mnt/usb0").unwrap(),
+            vec!["/mnt/usb0/DOCS", "/mnt/usb0/README.TXT"]
+        );
+        assert_eq!(
+            fs.list_files("/mnt/usb0/docs/").unwrap(),
+            vec!["/mnt/usb0/docs/Meeting notes.txt", "/mnt/usb0/docs/NESTED"]
+        );
+        assert!(fs.stat("/mnt/usb0/DOCS/nested").unwrap().is_dir);
+        assert_eq!(
+            fs.stat("/mnt/usb0/DOCS/NESTED/big file.bin").unwrap().size,
+            5 * 1024
+        );
+    }
+
+    #[test]
+    fn test_reads_files_through_driver() {
+        let fs = mounted();
+        assert_eq!(
+            fs.read_file("/mnt/usb0/readme.txt").unwrap(),
+            b"Welcome to LucAstra"
+        );
+        assert_eq!(
+            fs.read_file("/mnt/usb0/DOCS/NESTED/Big file.bin").unwrap(),
+            five_kb()
+        );
+    }
+
+    #[test]
+    fn test_rejects_writes_and_non_fat_volumes() {
+        let mut fs = mounted();
+        assert!(fs.write_file("/mnt/usb0/new.txt", b"x").is_err());
+        assert!(fs.read_file("/mnt/usb0/missing.txt").is_err());
+        assert!(fs.read_file("/mnt/usb0/DOCS").is_err());
+        assert!(fs.read_file("/mnt/other/README.TXT").is_err());
+
+        let mut not_fat = vec![0u8; 1024];
+        not_fat[510] = 0x55;
+        not_fat[511] = 0xAA;
+        assert!(Fat32FileSystem::from_bytes(not_fat).is_err());
+    }
+}
@@ -0,0 +1,257 @@
+//! Generated FAT32 and ELF images for tests.
+
+/// A file or directory to place in a generated image.
+pub enum Node {
+    File(&'static str, Vec<u8>),
+    Dir(&'static str, Vec<Node>),
+}
+
+pub const SECTOR: usize = 512;
+/// Two sectors per cluster so small files still span several clusters.
+pub const CLUSTER: usize = 2 * SECTOR;
+const RESERVED_SECTORS: usize = 32;
+const END_OF_CHAIN: u32 = 0x0FFF_FFFF;
+
+struct Builder {
+    /// FAT entries, starting with the two reserved ones
+    fat: Vec<u32>,
+    /// Contents of clusters 2, 3, ...
+    clusters: Vec<[u8; CLUSTER]>,
+}
+
+impl Builder {
+    /// Allocate a chain big enough for `len` bytes (at least one cluster).
+    fn reserve(&mut self, len: usize) -> u32 {
+        let count = len.div_ceil(CLUSTER).max(1);
+        let first = self.fat.len() as u32;
+        for i in 0..count {
+            let next = if i + 1 == count {
+                END_OF_CHAIN
+            } else {
+                first + i as u32 + 1
+            };
+            self.fat.push(next);
+            self.clusters.push([0; CLUSTER]);
+        }
+        first
+    }
+
+    fn fill(&mut self, first: u32, data: &[u8]) {
+        for (i, chunk) in data.chunks(CLUSTER).enumerate() {
+            let cluster = first as usize - 2 + i;
+            self.clusters[cluster][..chunk.len()].copy_from_slice(chunk);
+        }
+    }
+
+    /// Write a directory and everything below it; returns its first cluster.
+    fn write_dir(&mut self, nodes: &[Node], parent: Option<u32>) -> u32 {
+        let dot_entries = if parent.is_some() { 2 } else { 1 };
+        let count = dot_entries
+            + nodes
+                .iter()
+                .map(|node| name_entries(node_name(node), 0, 0, 0).len())
+                .sum::<usize>();
+        let first = self.reserve(count * 32);
+
+        let mut entries = match parent {
+            Some(parent) => vec![
+                short_entry(b".          ", 0x10, first, 0),
+                short_entry(b"..         ", 0x10, parent, 0),
+            ],
+            None => vec![short_entry(b"LUCASTRA   ", 0x08, 0, 0)],
+        };
+        for node in nodes {
+            match node {
+                Node::File(name, data) => {
+                    let cluster = if data.is_empty() {
+                        0
+                    } else {
+                        let cluster = self.reserve(data.len());
+                        self.fill(cluster, data);
+                        cluster
+                    };
+                    entries.extend(name_entries(name, 0x20, cluster, data.len() as u32));
+                }
+                Node::Dir(name, children) => {
+                    // The root is written as cluster 0 in `..` entries
+                    let cluster = self.write_dir(children, Some(parent.map_or(0, |_| first)));
+                    entries.extend(name_entries(name, 0x10, cluster, 0));
+                }
+            }
+        }
+        self.fill(first, &entries.concat());
+        first
+    }
+}
+
+fn node_name(node: &Node) -> &'static str {
+    match node {
+        Node::File(name, _) | Node::Dir(name, _) => name,
+    }
+}
+
+fn short_entry(name: &[u8; 11], attr: u8, cluster: u32, size: u32) -> [u8; 32] {
+    let mut entry = [0u8; 32];
+    entry[..11].copy_from_slice(name);
+    entry[11] = attr;
+    entry[20..22].copy_from_slice(&((cluster >> 16) as u16).to_le_bytes());
+    entry[26..28].copy_from_slice(&(cluster as u16).to_le_bytes());
+    entry[28..32].copy_from_slice(&size.to_le_bytes());
+    entry
+}
+
+/// 8.3 entry for `name`, preceded by long name entries unless `name` is
+/// already an upper-case 8.3 name.
+fn name_entries(name: &str, attr: u8, cluster: u32, size: u32) -> Vec<[u8; 32]> {
+    let (base, ext) = name.rsplit_once('.').unwrap_or((name, ""));
+    let is_short = base.len() <= 8
+        && ext.len() <= 3
+        && name
+            .chars()
+            .all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '.');
+
+    let mut short = *b"           ";
+    let mut entries = Vec::new();
+    if is_short {
+        short[..base.len()].copy_from_slice(base.as_bytes());
+        short[8..8 + ext.len()].copy_from_slice(ext.as_bytes());
+    } else {
+        let alias: Vec<u8> = base
+            .bytes()
+            .filter(u8::is_ascii_alphanumeric)
+            .map(|b| b.to_ascii_uppercase())
+            .take(6)
+            .collect();
+        short[..alias.len()].copy_from_slice(&alias);
+        short[alias.len()..alias.len() + 2].copy_from_slice(b"~1");
+        let ext: Vec<u8> = ext
+            .bytes()
+            .map(|b| b.to_ascii_uppercase())
+            .take(3)
+            .collect();
+        short[8..8 + ext.len()].copy_from_slice(&ext);
+        entries.extend(long_entries(name));
+    }
+    entries.push(short_entry(&short, attr, cluster, size));
+    entries
+}
+
+/// Long name entries for `name`, last part first as stored on disk.
+fn long_entries(name: &str) -> Vec<[u8; 32]> {
+    let mut units: Vec<u16> = name.encode_utf16().collect();
+    if !units.len().is_multiple_of(13) {
+        units.push(0);
+    }
+    units.resize(units.len().div_ceil(13) * 13, 0xFFFF);
+
+    let parts = units.len() / 13;
+    (0..parts)
+        .rev()
+        .map(|part| {
+            let mut entry = [0u8; 32];
+            entry[0] = (part + 1) as u8 | if part + 1 == parts { 0x40 } else { 0 };
+            entry[11] = 0x0F;
+            let offsets = (1..11)
+                .step_by(2)
+                .chain((14..26).step_by(2))
+                .chain((28..32).step_by(2));
+            for (unit, offset) in units[part * 13..(part + 1) * 13].iter().zip(offsets) {
+                entry[offset..offset + 2].copy_from_slice(&unit.to_le_bytes());
+            }
+            entry
+        })
+        .collect()
+}
+
+/// Build a FAT32 volume containing `root`.
+pub fn fat32_image(root: &[Node]) -> Vec<u8> {
+    let mut builder = Builder {
+        fat: vec![0x0FFF_FFF8, END_OF_CHAIN],
+        clusters: Vec::new(),
+    };
+    let root_cluster = builder.write_dir(root, None);
+
+    let fat_sectors = (builder.fat.len() * 4).div_ceil(SECTOR);
+    let data_start = (RESERVED_SECTORS + 2 * fat_sectors) * SECTOR;
+    let mut image = vec![0u8; data_start + builder.clusters.len() * CLUSTER];
+
+    image[0..3].copy_from_slice(&[0xEB, 0x58, 0x90]);
+    image[3..11].copy_from_slice(b"LUCASTRA");
+    image[11..13].copy_from_slice(&(SECTOR as u16).to_le_bytes());
+    image[13] = (CLUSTER / SECTOR) as u8;
+    image[14..16].copy_from_slice(&(RESERVED_SECTORS as u16).to_le_bytes());
+    image[16] = 2;
+    image[21] = 0xF8;
+    let total_sectors = (image.len() / SECTOR) as u32;
+    image[32..36].copy_from_slice(&total_sectors.to_le_bytes());
+    image[36..40].copy_from_slice(&(fat_sectors as u32).to_le_bytes());
+    image[44..48].copy_from_slice(&root_cluster.to_le_bytes());
+    image[510] = 0x55;
+    image[511] = 0xAA;
+
+    for copy in 0..2 {
+        let start = (RESERVED_SECTORS + copy * fat_sectors) * SECTOR;
+        for (i, entry) in builder.fat.iter().enumerate() {
+            image[start + i * 4..start + i * 4 + 4].copy_from_slice(&entry.to_le_bytes());
+        }
+    }
+    for (i, cluster) in builder.clusters.iter().enumerate() {
+        let start = data_start + i * CLUSTER;
+        image[start..start + CLUSTER].copy_from_slice(cluster);
+    }
+    image
+}
+
+/// 5 KB of non-repeating-per-cluster bytes.
+pub fn five_kb() -> Vec<u8> {
+    (0..5 * 1024).map(|i| (i % 251) as u8).collect()
+}
+
+/// `README.TXT`, `Meeting notes.txt` inside `DOCS/`, and a 5 KB
+/// `DOCS/NESTED/Big file.bin`.
+pub fn sample_image() -> Vec<u8> {
+    fat32_image(&[
+        Node::File("README.TXT", b"Welcome to LucAstra".to_vec()),
+        Node::Dir(
+            "DOCS",
+            vec![
+                Node::File("Meeting notes.txt", b"Agenda: ship FAT32".to_vec()),
+                Node::Dir("NESTED", vec![Node::File("Big file.bin", five_kb())]),
+            ],
+        ),
+    ])
+}
+
+/// Load address of [`elf_image`].
+const ELF_BASE: u64 = 0x40_0000;
+/// Entry point of [`elf_image`]: the first byte after the headers.
+pub const ELF_ENTRY: u64 = ELF_BASE + 64 + 56;
+
+/// x86_64 executable with one `PT_LOAD` segment covering the whole file,
+/// with `code` placed at the entry point.
+pub fn elf_image(code: &[u8]) -> Vec<u8> {
+    let mut image = vec![0u8; 64 + 56];
+    image[0..4].copy_from_slice(b"\x7fELF");
+    image[4] = 2; // 64-bit
+    image[5] = 1; // little-endian
+    image[6] = 1;
+    image[16..18].copy_from_slice(&2u16.to_le_bytes()); // ET_EXEC
+    image[18..20].copy_from_slice(&0x3Eu16.to_le_bytes()); // x86_64
+    image[24..32].copy_from_slice(&ELF_ENTRY.to_le_bytes());
+    image[32..40].copy_from_slice(&64u64.to_le_bytes());
+    image[52..54].copy_from_slice(&64u16.to_le_bytes());
+    image[54..56].copy_from_slice(&56u16.to_le_bytes());
+    image[56..58].copy_from_slice(&1u16.to_le_bytes());
+    image.extend_from_slice(code);
+
+    let len = image.len() as u64;
+    let header = &mut image[64..120];
+    header[0..4].copy_from_slice(&1u32.to_le_bytes()); // PT_LOAD
+    header[4..8].copy_from_slice(&5u32.to_le_bytes()); // R+X
+    header[16..24].copy_from_slice(&ELF_BASE.to_le_bytes());
+    header[24..32].copy_from_slice(&ELF_BASE.to_le_bytes());
+    header[32..40].copy_from_slice(&len.to_le_bytes());
+    header[40..48].copy_from_slice(&len.to_le_bytes());
+    header[48..56].copy_from_slice(&0x1000u64.to_le_bytes());
+    image
+}
@@ -5,12 +5,19 @@
 //!
 //! Features:
 //! - `relibc`: Enable relibc compatibility layer (experimental)
+//! - `fixtures`: Expose the generated disk images used by tests
 
+pub mod fat32;
+#[cfg(any(test, feature = "fixtures"))]
+pub mod fixtures;
 pub mod libreoffice;
 pub mod loader;
+pub mod process;
 pub mod syscall;
 
-pub use syscall::SyscallHandler;
+pub use fat32::Fat32FileSystem;
+pub use process::{Pid, Process, ProcessState, ProcessTable};
+pub use syscall::{syscall_name, MemoryFs, ProcessMemory, SyscallFs, SyscallHandler};
 
 use lucastra_core::Result;
 
@@ -29,9 +36,19 @@ pub fn is_relibc_enabled() -> bool {
 mod tests {
     use super::*;
 
+    /// Handler whose memory holds `path` as a C string at 0x1000 and has
+    /// room for buffers from 0x2000.
+    fn handler_with_path(fs: impl SyscallFs + Send + 'static, path: &str) -> SyscallHandler {
+        let mut memory = ProcessMemory::new(0x1000, 0x2000);
+        assert!(memory.write(0x1000, format!("{}\0", path).as_bytes()));
+        SyscallHandler::with_fs(fs).with_memory(memory)
+    }
+
     #[test]
     fn test_syscall_handler_open_close() {
-        let mut handler = SyscallHandler::new();
+        let mut fs = MemoryFs::new();
+        fs.write("/etc/hosts", b"127.0.0.1 localhost").unwrap();
+        let mut handler = handler_with_path(fs, "/etc/hosts");
 
         // Test open
         let fd = handler.handle_syscall(2, &[0x1000, 0, 0]).unwrap();
@@ -40,11 +57,18 @@ mod tests {
         // Test close
         let result = handler.handle_syscall(3, &[fd as u64]).unwrap();
         assert_eq!(result, 0);
+
+        // Missing files and unmapped pointers fail with ENOENT and EFAULT
+        let mut handler = handler_with_path(MemoryFs::new(), "/missing");
+        assert_eq!(handler.handle_syscall(2, &[0x1000, 0, 0]).unwrap(), -2);
+        assert_eq!(handler.handle_syscall(2, &[0x9000, 0, 0]).unwrap(), -14);
     }
 
     #[test]
     fn test_syscall_handler_dup() {
-        let mut handler = SyscallHandler::new();
+        let mut fs = MemoryFs::new();
+        fs.write("/etc/hosts", b"").unwrap();
+        let mut handler = handler_with_path(fs, "/etc/hosts");
 
         // Open file
         let fd1 = handler.handle_syscall(2, &[0x1000, 0, 0]).unwrap();
@@ -91,13 +115,77 @@ mod tests {
         assert!(reader.boot_sector().is_some());
     }
 
+    #[test]
+    fn test_fat32_reader_follows_cluster_chains() {
+        use crate::fixtures::{five_kb, sample_image, CLUSTER};
+        use crate::loader::FAT32Reader;
+        use lucastra_hal::MockBlockDevice;
+
+        let mut device = MockBlockDevice::from_bytes(sample_image(), 512);
+        let reader = FAT32Reader::from_device(&mut device).unwrap();
+
+        let file = reader
+            .lookup(&mut device, "DOCS/NESTED/Big file.bin")
+            .unwrap();
+        assert_eq!(file.size, 5 * 1024);
+
+        let chain = reader
+            .read_cluster_chain(&mut device, file.first_cluster)
+            .unwrap();
+        assert_eq!(chain.len(), 5 * 1024 / CLUSTER);
+        assert_eq!(
+            reader.read_fat_entry(&mut device, chain[0]).unwrap(),
+            chain[1]
+        );
+        assert!(reader.read_fat_entry(&mut device, chain[4]).unwrap() >= 0x0FFF_FFF8);
+
+        let data = reader
+            .read_file(&mut device, "/docs/nested/BIG FILE.BIN")
+            .unwrap();
+        assert_eq!(data, five_kb());
+    }
+
+    #[test]
+    fn test_fat32_reader_parses_short_and_long_names() {
+        use crate::fixtures::sample_image;
+        use crate::loader::FAT32Reader;
+        use lucastra_hal::MockBlockDevice;
+
+        let mut device = MockBlockDevice::from_bytes(sample_image(), 512);
+        let reader = FAT32Reader::from_device(&mut device).unwrap();
+
+        let root = reader.boot_sector().unwrap().root_cluster;
+        let names: Vec<String> = reader
+            .read_dir(&mut device, root)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.name)
+            .collect();
+        assert_eq!(names, vec!["README.TXT", "DOCS"]);
+
+        let docs = reader.lookup(&mut device, "DOCS").unwrap();
+        let entries = reader.read_dir(&mut device, docs.first_cluster).unwrap();
+        assert_eq!(entries[0].name, "Meeting notes.txt");
+        assert!(entries[1].is_dir);
+        assert_eq!(
+            reader
+                .read_file(&mut device, "DOCS/Meeting notes.txt")
+                .unwrap(),
+            b"Agenda: ship FAT32"
+        );
+        assert!(reader.read_file(&mut device, "DOCS/missing").is_err());
+    }
+
     #[test]
     fn test_syscall_handler_read_write_file() {
         let mut handler = SyscallHandler::new();
 
         // Store file data for read test
         let test_content = b"Hello, relibc!".to_vec();
-        handler.write_file("/test.txt", test_content.clone());
+        handler
+            .write_file("/test.txt", test_content.clone())
+            .unwrap();
+        *handler.memory_mut() = ProcessMemory::new(0x2000, 0x1000);
 
         // Open file directly (not via syscall pointer address)
         let fd = handler.open_file("/test.txt", 0);
@@ -115,9 +203,203 @@ mod tests {
             .handle_syscall(0, &[fd as u64, 0x2000, 1024])
             .unwrap();
         assert_eq!(all_read as usize, test_content.len());
+        assert_eq!(
+            handler.memory().read(0x2000, test_content.len()).unwrap(),
+            &test_content[..]
+        );
 
         // Close
         let result = handler.handle_syscall(3, &[fd as u64]).unwrap();
         assert_eq!(result, 0);
     }
+
+    #[test]
+    fn test_syscalls_read_from_mounted_filesystem() {
+        use lucastra_fs::FilesystemManager;
+        use lucastra_hal::filesystem::MockFileSystem;
+        use lucastra_hal::FileSystemDriver;
+
+        let content = b"LucAstra reads real files through relibc.\n";
+        let mut mock = MockFileSystem::new();
+        mock.write_file("/mnt/root/docs/readme.txt", content)
+            .unwrap();
+        let mut filesystem = FilesystemManager::new();
+        filesystem.mount("/mnt/root", mock).unwrap();
+
+        let mut handler = handler_with_path(filesystem, "/mnt/root/docs/readme.txt");
+        let fd = handler.handle_syscall(2, &[0x1000, 0, 0]).unwrap();
+        assert!(fd > 2);
+
+        let read = handler
+            .handle_syscall(0, &[fd as u64, 0x2000, 4096])
+            .unwrap();
+        assert_eq!(read as usize, content.len());
+        assert_eq!(
+            handler.memory().read(0x2000, content.len()).unwrap(),
+            content
+        );
+
+        // At EOF further reads return 0
+        assert_eq!(
+            handler.handle_syscall(0, &[fd as u64, 0x2000, 16]).unwrap(),
+            0
+        );
+
+        // fstat reports the file size at st_size
+        assert_eq!(handler.handle_syscall(5, &[fd as u64, 0x2000]).unwrap(), 0);
+        let size = handler.memory().read(0x2000 + 48, 8).unwrap();
+        assert_eq!(
+            i64::from_le_bytes(size.try_into().unwrap()),
+            content.len() as i64
+        );
+    }
+
+    #[test]
+    fn test_syscall_write_updates_files_and_stdout() {
+        let mut handler = handler_with_path(MemoryFs::new(), "/tmp/out.txt");
+        assert!(handler.memory_mut().write(0x2000, b"hello world"));
+
+        // O_WRONLY | O_CREAT
+        let fd = handler.handle_syscall(2, &[0x1000, 0o101, 0o644]).unwrap();
+        assert_eq!(
+            handler.handle_syscall(1, &[fd as u64, 0x2000, 5]).unwrap(),
+            5
+        );
+        assert_eq!(
+            handler.handle_syscall(1, &[fd as u64, 0x2005, 6]).unwrap(),
+            6
+        );
+
+        assert_eq!(handler.handle_syscall(8, &[fd as u64, 0, 0]).unwrap(), 0);
+        assert_eq!(
+            handler.handle_syscall(0, &[fd as u64, 0x2100, 64]).unwrap(),
+            11
+        );
+        assert_eq!(handler.memory().read(0x2100, 11).unwrap(), b"hello world");
+
+        // stdout is forwarded to tracing and reports the full count
+        assert_eq!(handler.handle_syscall(1, &[1, 0x2000, 11]).unwrap(), 11);
+        assert_eq!(handler.handle_syscall(1, &[7, 0x2000, 11]).unwrap(), -9);
+    }
+
+    #[test]
+    fn test_lseek_rejects_offsets_before_the_start() {
+        let mut handler = handler_with_path(MemoryFs::new(), "/tmp/out.txt");
+        assert!(handler.memory_mut().write(0x2000, b"hello"));
+        let fd = handler.handle_syscall(2, &[0x1000, 0o101, 0o644]).unwrap() as u64;
+        assert_eq!(handler.handle_syscall(1, &[fd, 0x2000, 5]).unwrap(), 5);
+
+        // SEEK_CUR and SEEK_END may not go below zero, and the offset stays
+        assert_eq!(
+            handler.handle_syscall(8, &[fd, -6i64 as u64, 1]).unwrap(),
+            -22
+        );
+        assert_eq!(
+            handler.handle_syscall(8, &[fd, -6i64 as u64, 2]).unwrap(),
+            -22
+        );
+        assert_eq!(
+            handler.handle_syscall(8, &[fd, -6i64 as u64, 0]).unwrap(),
+            -22
+        );
+        assert_eq!(handler.handle_syscall(8, &[fd, 0, 9]).unwrap(), -22);
+        assert_eq!(
+            handler.handle_syscall(8, &[fd, -2i64 as u64, 2]).unwrap(),
+            3
+        );
+        assert_eq!(handler.handle_syscall(8, &[fd, 0, 1]).unwrap(), 3);
+    }
+
+    #[test]
+    fn test_write_past_the_size_limit_fails_with_efbig() {
+        let mut handler = handler_with_path(MemoryFs::new(), "/tmp/out.txt");
+        assert!(handler.memory_mut().write(0x2000, b"hello"));
+        let fd = handler.handle_syscall(2, &[0x1000, 0o101, 0o644]).unwrap() as u64;
+
+        // Far past the end, and where offset + count overflows
+        assert_eq!(
+            handler.handle_syscall(8, &[fd, 1 << 40, 0]).unwrap(),
+            1 << 40
+        );
+        assert_eq!(handler.handle_syscall(1, &[fd, 0x2000, 5]).unwrap(), -27);
+        let last = i64::MAX as u64;
+        assert_eq!(
+            handler.handle_syscall(8, &[fd, last, 0]).unwrap(),
+            last as i64
+        );
+        assert_eq!(handler.handle_syscall(1, &[fd, 0x2000, 5]).unwrap(), -27);
+
+        assert_eq!(handler.handle_syscall(8, &[fd, 0, 0]).unwrap(), 0);
+        assert_eq!(handler.handle_syscall(1, &[fd, 0x2000, 5]).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_directory_syscall_sequence() {
+        let mut handler = handler_with_path(MemoryFs::new(), "/work");
+        let memory = handler.memory_mut();
+        assert!(memory.write(0x1100, b"notes.txt\0"));
+        assert!(memory.write(0x1200, b"first line\n"));
+
+        // mkdir("/work"), then again fails with EEXIST
+        assert_eq!(handler.handle_syscall(83, &[0x1000, 0o755]).unwrap(), 0);
+        assert_eq!(handler.handle_syscall(83, &[0x1000, 0o755]).unwrap(), -17);
+
+        // openat(dirfd, "notes.txt", O_WRONLY | O_CREAT) resolves under /work
+        let dir = handler.handle_syscall(2, &[0x1000, 0o200000, 0]).unwrap();
+        let fd = handler
+            .handle_syscall(257, &[dir as u64, 0x1100, 0o101, 0o644])
+            .unwrap();
+        assert!(fd > dir);
+        assert_eq!(
+            handler.handle_syscall(1, &[fd as u64, 0x1200, 11]).unwrap(),
+            11
+        );
+
+        // fstat reports the written size and a regular file mode
+        assert_eq!(handler.handle_syscall(5, &[fd as u64, 0x1800]).unwrap(), 0);
+        let stat = handler.memory().read(0x1800, 56).unwrap();
+        assert_eq!(
+            u32::from_le_bytes(stat[24..28].try_into().unwrap()),
+            0o100644
+        );
+        assert_eq!(i64::from_le_bytes(stat[48..56].try_into().unwrap()), 11);
+
+        // getdents64 returns one 8-byte aligned record for notes.txt
+        let read = handler
+            .handle_syscall(217, &[dir as u64, 0x2000, 512])
+            .unwrap();
+        assert_eq!(read, 32);
+        let record = handler.memory().read(0x2000, 32).unwrap();
+        assert_eq!(u16::from_le_bytes([record[16], record[17]]), 32);
+        assert_eq!(record[18], 8);
+        assert_eq!(&record[19..29], b"notes.txt\0");
+        assert_eq!(
+            handler
+                .handle_syscall(217, &[dir as u64, 0x2000, 512])
+                .unwrap(),
+            0
+        );
+
+        // unlink("/work/notes.txt") removes it from the listing
+        assert!(handler.memory_mut().write(0x1100, b"/work/notes.txt\0"));
+        assert_eq!(handler.handle_syscall(87, &[0x1100]).unwrap(), 0);
+        assert_eq!(handler.handle_syscall(87, &[0x1100]).unwrap(), -2);
+        assert_eq!(handler.handle_syscall(87, &[0x1000]).unwrap(), -21);
+        handler.handle_syscall(8, &[dir as u64, 0, 0]).unwrap();
+        assert_eq!(
+            handler
+                .handle_syscall(217, &[dir as u64, 0x2000, 512])
+                .unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn test_unknown_syscalls_are_named() {
+        assert_eq!(syscall_name(217), Some("getdents64"));
+        assert_eq!(syscall_name(9999), None);
+
+        let mut handler = SyscallHandler::new();
+        assert_eq!(handler.handle_syscall(61, &[1, 0, 0, 0]).unwrap(), -38);
+    }
 }
@@ -3,7 +3,8 @@
 //! Provides a minimal launcher for LibreOffice documents within the LucAstra
 //! compatibility layer, with file I/O sandboxed through HostFileAccess.
 
-use lucastra_core::Result;
+use crate::process::{Pid, ProcessTable};
+use lucastra_core::{LuCastraError, Result};
 use std::path::PathBuf;
 
 /// LibreOffice launch configuration.
@@ -63,8 +64,10 @@ impl LibreOfficeLauncher {
         Self { config }
     }
 
-    /// Launch LibreOffice with the configured document.
-    pub fn launch(&self) -> Result<()> {
+    /// Launch LibreOffice with the configured document and return its pid.
+    ///
+    /// The document path is passed as `argv[1]`.
+    pub fn launch(&self, processes: &mut ProcessTable) -> Result<Pid> {
         self.config.validate()?;
 
         tracing::info!(
@@ -74,15 +77,19 @@ impl LibreOfficeLauncher {
             self.config.sandbox_enabled
         );
 
-        // Placeholder: in a real implementation, this would:
-        // 1. Set up a syscall handler with HostFileAccess delegation
-        // 2. Load the ELF binary (LibreOffice executable)
-        // 3. Pass document path as argv[1]
-        // 4. Jump to entry point with sandbox context
-
-        tracing::info!("LibreOffice launch requested (syscall handler integration pending)");
-
-        Ok(())
+        let binary = std::fs::read(&self.config.executable_path).map_err(|e| {
+            LuCastraError::SyscallError(format!(
+                "Failed to read {}: {}",
+                self.config.executable_path.display(),
+                e
+            ))
+        })?;
+        let executable = self.config.executable_path.to_string_lossy();
+        let document = self.config.document_path.to_string_lossy();
+        let pid = processes.spawn(&binary, &[&executable, &document], &[])?;
+
+        tracing::info!("LibreOffice started as pid {}", pid);
+        Ok(pid)
     }
 }
 
@@ -127,4 +134,22 @@ mod tests {
         assert!(result.is_err());
         assert!(result.unwrap_err().to_string().contains("not found"));
     }
+
+    #[test]
+    fn test_libreoffice_launch_spawns_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let executable = dir.path().join("soffice");
+        std::fs::write(&executable, crate::fixtures::elf_image(b"soffice")).unwrap();
+
+        let launcher = LibreOfficeLauncher::new(LibreOfficeConfig::new(
+            executable.clone(),
+            PathBuf::from("/home/user/document.odt"),
+        ));
+        let mut processes = ProcessTable::new();
+        let pid = launcher.launch(&mut processes).unwrap();
+
+        let process = processes.get(pid).unwrap();
+        assert_eq!(process.argv()[0], executable.to_string_lossy());
+        assert_eq!(process.argv()[1], "/home/user/document.odt");
+    }
 }
@@ -3,7 +3,17 @@
 //! This module provides minimal ELF parsing, FAT32 reading, and binary loading
 //! for relibc-compiled binaries.
 
-use lucastra_core::Result;
+use lucastra_core::{LuCastraError, Result};
+use lucastra_hal::BlockDevice;
+
+const ATTR_VOLUME_ID: u8 = 0x08;
+const ATTR_DIRECTORY: u8 = 0x10;
+const ATTR_LONG_NAME: u8 = 0x0F;
+const DIR_ENTRY_SIZE: usize = 32;
+/// FAT entries at or above this end a cluster chain.
+const END_OF_CHAIN: u32 = 0x0FFF_FFF8;
+/// Upper bound on chain length so a corrupt, cyclic FAT cannot hang a read.
+const MAX_CHAIN_LEN: usize = 1 << 22;
 
 /// FAT32 Boot Sector structure (minimal).
 #[repr(C)]
@@ -28,6 +38,38 @@ pub struct FAT32BootSector {
     pub fsinfo_sector: u16,
 }
 
+/// A file or directory in a FAT32 directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FatDirEntry {
+    /// Long file name if present, otherwise the 8.3 name as `NAME.EXT`
+    pub name: String,
+    pub is_dir: bool,
+    pub first_cluster: u32,
+    pub size: u32,
+}
+
+fn fat_error(message: impl std::fmt::Display) -> LuCastraError {
+    LuCastraError::FilesystemError(format!("FAT32: {}", message))
+}
+
+/// Read `len` bytes at byte `offset` of `device`.
+fn read_bytes(device: &mut dyn BlockDevice, offset: u64, len: usize) -> Result<Vec<u8>> {
+    let sector_size = device.sector_size();
+    let first = offset / sector_size as u64;
+    let skip = (offset % sector_size as u64) as usize;
+    let count = (skip + len).div_ceil(sector_size);
+
+    let mut buffer = vec![0u8; count * sector_size];
+    for (i, sector) in buffer.chunks_exact_mut(sector_size).enumerate() {
+        if device.read_sector(first + i as u64, sector)? < sector_size {
+            return Err(fat_error(format!("read past end of device at {}", offset)));
+        }
+    }
+    buffer.drain(..skip);
+    buffer.truncate(len);
+    Ok(buffer)
+}
+
 /// Minimal FAT32 filesystem parser.
 pub struct FAT32Reader {
     boot_sector: Option<FAT32BootSector>,
@@ -38,6 +80,23 @@ impl FAT32Reader {
         Self { boot_sector: None }
     }
 
+    /// Parse the boot sector of the FAT32 volume on `device`.
+    pub fn from_device(device: &mut dyn BlockDevice) -> Result<Self> {
+        let mut reader = Self::new();
+        reader.parse_boot_sector(&read_bytes(device, 0, 512)?)?;
+        let valid = reader.boot_sector().is_some_and(|bs| {
+            bs.bytes_per_sector >= 512
+                && bs.sectors_per_cluster > 0
+                && bs.num_fats > 0
+                && bs.sectors_per_fat_32 > 0
+                && bs.root_cluster >= 2
+        });
+        if !valid {
+            return Err(fat_error("not a FAT32 volume"));
+        }
+        Ok(reader)
+    }
+
     /// Parse a FAT32 boot sector from raw bytes.
     pub fn parse_boot_sector(&mut self, data: &[u8]) -> Result<()> {
         if data.len() < 90 {
@@ -107,6 +166,237 @@ impl FAT32Reader {
             bs.hidden_sectors + bs.reserved_sectors as u32 + (bs.num_fats as u32 * fat_size)
         })
     }
+
+    /// Size of a cluster in bytes.
+    pub fn bytes_per_cluster(&self) -> Option<u64> {
+        self.boot_sector
+            .as_ref()
+            .map(|bs| bs.bytes_per_sector as u64 * bs.sectors_per_cluster as u64)
+    }
+
+    /// Byte offset of the FAT entry for `cluster`, relative to the start of
+    /// the volume (hidden sectors are not counted when reading a partition).
+    pub fn fat_entry_offset(&self, cluster: u32) -> Option<u64> {
+        self.boot_sector
+            .as_ref()
+            .map(|bs| bs.reserved_sectors as u64 * bs.bytes_per_sector as u64 + cluster as u64 * 4)
+    }
+
+    /// Byte offset of the first byte of `cluster`, relative to the start of
+    /// the volume. Data clusters are numbered from 2.
+    pub fn cluster_offset(&self, cluster: u32) -> Option<u64> {
+        let bs = self.boot_sector.as_ref()?;
+        let data_start =
+            bs.reserved_sectors as u64 + bs.num_fats as u64 * bs.sectors_per_fat_32 as u64;
+        let sector = data_start + (cluster.checked_sub(2)? as u64) * bs.sectors_per_cluster as u64;
+        Some(sector * bs.bytes_per_sector as u64)
+    }
+
+    fn not_parsed() -> LuCastraError {
+        fat_error("boot sector not parsed")
+    }
+
+    /// Next cluster after `cluster`, or a value >= `0x0FFFFFF8` at the end
+    /// of the chain.
+    pub fn read_fat_entry(&self, device: &mut dyn BlockDevice, cluster: u32) -> Result<u32> {
+        let offset = self
+            .fat_entry_offset(cluster)
+            .ok_or_else(Self::not_parsed)?;
+        let entry = read_bytes(device, offset, 4)?;
+        Ok(u32::from_le_bytes([entry[0], entry[1], entry[2], entry[3]]) & 0x0FFF_FFFF)
+    }
+
+    /// Clusters of the chain starting at `start_cluster`, in order.
+    pub fn read_cluster_chain(
+        &self,
+        device: &mut dyn BlockDevice,
+        start_cluster: u32,
+    ) -> Result<Vec<u32>> {
+        let mut clusters = Vec::new();
+        let mut cluster = start_cluster;
+        while (2..END_OF_CHAIN).contains(&cluster) {
+            if clusters.len() >= MAX_CHAIN_LEN {
+                return Err(fat_error("cluster chain too long"));
+            }
+            clusters.push(cluster);
+            cluster = self.read_fat_entry(device, cluster)?;
+        }
+        Ok(clusters)
+    }
+
+    /// Contents of the chain starting at `start_cluster`, truncated to `len`
+    /// when given.
+    fn read_chain_data(
+        &self,
+        device: &mut dyn BlockDevice,
+        start_cluster: u32,
+        len: Option<usize>,
+    ) -> Result<Vec<u8>> {
+        let cluster_size = self.bytes_per_cluster().ok_or_else(Self::not_parsed)? as usize;
+
+        let mut data = Vec::new();
+        for cluster in self.read_cluster_chain(device, start_cluster)? {
+            if len.is_some_and(|len| data.len() >= len) {
+                break;
+            }
+            let offset = self.cluster_offset(cluster).ok_or_else(Self::not_parsed)?;
+            data.extend(read_bytes(device, offset, cluster_size)?);
+        }
+        if let Some(len) = len {
+            if data.len() < len {
+                return Err(fat_error("file is shorter than its directory entry"));
+            }
+            data.truncate(len);
+        }
+        Ok(data)
+    }
+
+    /// Entries of the directory starting at `cluster`, without `.` and `..`.
+    pub fn read_dir(&self, device: &mut dyn BlockDevice, cluster: u32) -> Result<Vec<FatDirEntry>> {
+        // Some tools point `..` at cluster 0 when the parent is the root
+        let cluster = if cluster == 0 {
+            self.root_cluster()?
+        } else {
+            cluster
+        };
+        let data = self.read_chain_data(device, cluster, None)?;
+        Ok(parse_dir_entries(&data))
+    }
+
+    fn root_cluster(&self) -> Result<u32> {
+        self.boot_sector
+            .as_ref()
+            .map(|bs| bs.root_cluster)
+            .ok_or_else(Self::not_parsed)
+    }
+
+    /// Find the entry for a `/`-separated path relative to the root.
+    /// Names match case-insensitively; the root itself is a directory entry
+    /// with an empty name.
+    pub fn lookup(&self, device: &mut dyn BlockDevice, path: &str) -> Result<FatDirEntry> {
+        let mut entry = FatDirEntry {
+            name: String::new(),
+            is_dir: true,
+            first_cluster: self.root_cluster()?,
+            size: 0,
+        };
+
+        for part in path.split('/').filter(|p| !p.is_empty()) {
+            if !entry.is_dir {
+                return Err(fat_error(format!("not a directory: {}", path)));
+            }
+            entry = self
+                .read_dir(device, entry.first_cluster)?
+                .into_iter()
+                .find(|e| e.name.eq_ignore_ascii_case(part))
+                .ok_or_else(|| LuCastraError::FileNotFound(path.to_string()))?;
+        }
+        Ok(entry)
+    }
+
+    /// Read a whole file by its path relative to the root.
+    pub fn read_file(&self, device: &mut dyn BlockDevice, path: &str) -> Result<Vec<u8>> {
+        let entry = self.lookup(device, path)?;
+        if entry.is_dir {
+            return Err(fat_error(format!("is a directory: {}", path)));
+        }
+        if entry.size == 0 {
+            return Ok(Vec::new());
+        }
+        self.read_chain_data(device, entry.first_cluster, Some(entry.size as usize))
+    }
+}
+
+/// Parse raw directory data, joining long file name entries with the 8.3
+/// entry that follows them.
+fn parse_dir_entries(data: &[u8]) -> Vec<FatDirEntry> {
+    let mut entries = Vec::new();
+    let mut long_name: Vec<(u8, [u16; 13])> = Vec::new();
+
+    for raw in data.chunks_exact(DIR_ENTRY_SIZE) {
+        match raw[0] {
+            0x00 => break,
+            0xE5 => {
+                long_name.clear();
+                continue;
+            }
+            _ => {}
+        }
+
+        let attr = raw[11];
+        if attr & 0x3F == ATTR_LONG_NAME {
+            long_name.push((raw[0] & 0x1F, long_name_chars(raw)));
+            continue;
+        }
+        if attr & ATTR_VOLUME_ID != 0 {
+            long_name.clear();
+            continue;
+        }
+
+        let name = if long_name.is_empty() {
+            short_name(raw)
+        } else {
+            long_name.sort_by_key(|(order, _)| *order);
+            let units: Vec<u16> = long_name
+                .iter()
+                .flat_map(|(_, chars)| chars.iter().copied())
+                .take_while(|&c| c != 0x0000 && c != 0xFFFF)
+                .collect();
+            String::from_utf16_lossy(&units)
+        };
+        long_name.clear();
+        if name == "." || name == ".." {
+            continue;
+        }
+
+        let cluster_hi = u16::from_le_bytes([raw[20], raw[21]]) as u32;
+        let cluster_lo = u16::from_le_bytes([raw[26], raw[27]]) as u32;
+        entries.push(FatDirEntry {
+            name,
+            is_dir: attr & ATTR_DIRECTORY != 0,
+            first_cluster: (cluster_hi << 16) | cluster_lo,
+            size: u32::from_le_bytes([raw[28], raw[29], raw[30], raw[31]]),
+        });
+    }
+    entries
+}
+
+/// `NAME.EXT` from an 8.3 entry, honouring the lowercase flags in byte 12.
+fn short_name(raw: &[u8]) -> String {
+    let decode = |bytes: &[u8], lower: bool| {
+        let mut part: String = bytes.iter().map(|&b| b as char).collect();
+        part.truncate(part.trim_end().len());
+        if lower {
+            part.make_ascii_lowercase();
+        }
+        part
+    };
+
+    let mut base = raw[..8].to_vec();
+    // 0x05 stands in for a leading 0xE5, which marks deleted entries
+    if base[0] == 0x05 {
+        base[0] = 0xE5;
+    }
+    let base = decode(&base, raw[12] & 0x08 != 0);
+    let ext = decode(&raw[8..11], raw[12] & 0x10 != 0);
+    if ext.is_empty() {
+        base
+    } else {
+        format!("{}.{}", base, ext)
+    }
+}
+
+/// The 13 UTF-16 units stored in a long file name entry.
+fn long_name_chars(raw: &[u8]) -> [u16; 13] {
+    let mut chars = [0u16; 13];
+    let offsets = (1..11)
+        .step_by(2)
+        .chain((14..26).step_by(2))
+        .chain((28..32).step_by(2));
+    for (slot, offset) in chars.iter_mut().zip(offsets) {
+        *slot = u16::from_le_bytes([raw[offset], raw[offset + 1]]);
+    }
+    chars
 }
 
 impl Default for FAT32Reader {
@@ -115,6 +405,13 @@ impl Default for FAT32Reader {
     }
 }
 
+const PT_LOAD: u32 = 1;
+const PROGRAM_HEADER_SIZE: usize = 56;
+
+fn elf_error(message: impl std::fmt::Display) -> LuCastraError {
+    LuCastraError::SyscallError(format!("ELF: {}", message))
+}
+
 /// Minimal ELF header validation and parsing.
 #[repr(C)]
 #[derive(Debug, Clone, Copy)]
@@ -190,9 +487,7 @@ impl ElfLoader {
             abi_version: data[8],
             e_type: u16::from_le_bytes([data[16], data[17]]),
             e_machine: u16::from_le_bytes([data[18], data[19]]),
-            e_entry: u64::from_le_bytes([
-                data[32], data[33], data[34], data[35], data[36], data[37], data[38], data[39],
-            ]),
+            e_entry: u64::from_le_bytes(data[24..32].try_into().unwrap()),
         };
 
         tracing::info!(
@@ -211,19 +506,55 @@ impl ElfLoader {
     pub fn parse_program_headers(&mut self, data: &[u8]) -> Result<()> {
         self.parse_header(data)?;
 
-        let _header = self.elf_header.as_ref().unwrap();
+        let u16_at = |at: usize| u16::from_le_bytes([data[at], data[at + 1]]) as usize;
+        let u64_at = |at: usize| u64::from_le_bytes(data[at..at + 8].try_into().unwrap());
+        let phoff = u64_at(32) as usize;
+        let phentsize = u16_at(54);
+        let phnum = u16_at(56);
+
+        if phnum > 0 && phentsize < PROGRAM_HEADER_SIZE {
+            return Err(elf_error(format!(
+                "program header entries too small: {}",
+                phentsize
+            )));
+        }
+        let table_end = phentsize
+            .checked_mul(phnum)
+            .and_then(|len| phoff.checked_add(len));
+        if table_end.is_none_or(|end| end > data.len()) {
+            return Err(elf_error("program header table out of bounds"));
+        }
 
-        // Program header offset is typically at offset 0x20 for 64-bit
-        // For now, simplified: assume first program header at fixed offset
-        // Real ELF parsing requires e_phoff and e_phentsize from header
+        self.program_headers = (0..phnum)
+            .map(|i| {
+                let at = phoff + i * phentsize;
+                ELFProgramHeader {
+                    p_type: u32::from_le_bytes(data[at..at + 4].try_into().unwrap()),
+                    p_flags: u32::from_le_bytes(data[at + 4..at + 8].try_into().unwrap()),
+                    p_offset: u64_at(at + 8),
+                    p_vaddr: u64_at(at + 16),
+                    p_paddr: u64_at(at + 24),
+                    p_filesz: u64_at(at + 32),
+                    p_memsz: u64_at(at + 40),
+                    p_align: u64_at(at + 48),
+                }
+            })
+            .collect();
         tracing::debug!(
-            "ELF has {} program header entries (stub parsing)",
-            "0 (not implemented)"
+            "ELF has {} program header entries",
+            self.program_headers.len()
         );
 
         Ok(())
     }
 
+    /// `PT_LOAD` segments, which make up the process image.
+    pub fn load_segments(&self) -> impl Iterator<Item = &ELFProgramHeader> {
+        self.program_headers
+            .iter()
+            .filter(|header| header.p_type == PT_LOAD)
+    }
+
     /// Get the entry point address.
     pub fn entry_point(&self) -> Option<u64> {
         self.elf_header.as_ref().map(|h| h.e_entry)
@@ -236,10 +567,9 @@ impl ElfLoader {
 
     /// Load an ELF binary and return entry point.
     pub fn load(&mut self, data: &[u8]) -> Result<usize> {
-        self.parse_header(data)?;
         self.parse_program_headers(data)?;
         tracing::info!("Loading ELF binary ({} bytes)", data.len());
-        // Return entry point; real implementation would allocate memory and copy segments
+        // Segments are copied into memory by `ProcessTable::spawn`
         Ok(self.entry_point().unwrap_or(0x1000) as usize)
     }
 }
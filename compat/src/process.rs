@@ -0,0 +1,412 @@
+//! Processes for loaded binaries.
+//!
+//! Each [`Process`] owns a [`SyscallHandler`], so its file descriptors and
+//! memory are separate from every other process. All processes in a
+//! [`ProcessTable`] share one filesystem.
+
+use crate::loader::ElfLoader;
+use crate::syscall::{MemoryFs, ProcessMemory, SyscallFs, SyscallHandler};
+use lucastra_core::{LuCastraError, Result};
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+/// Process identifier.
+pub type Pid = u32;
+
+const PAGE_SIZE: u64 = 0x1000;
+/// Stack mapped directly above the image.
+const STACK_SIZE: usize = 64 * 1024;
+/// Where the stack goes for images without loadable segments.
+const DEFAULT_BASE: u64 = 0x40_0000;
+/// Largest span the segments and stack may cover. The whole span is
+/// allocated up front, so an untrusted header must not pick its size.
+const MAX_IMAGE_SIZE: usize = 1 << 30;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessState {
+    Running,
+    Exited(i32),
+}
+
+/// A loaded binary with its own descriptors and memory.
+pub struct Process {
+    pid: Pid,
+    argv: Vec<String>,
+    envp: Vec<String>,
+    entry_point: u64,
+    stack_pointer: u64,
+    handler: SyscallHandler,
+}
+
+impl Process {
+    pub fn pid(&self) -> Pid {
+        self.pid
+    }
+
+    pub fn argv(&self) -> &[String] {
+        &self.argv
+    }
+
+    pub fn envp(&self) -> &[String] {
+        &self.envp
+    }
+
+    pub fn entry_point(&self) -> u64 {
+        self.entry_point
+    }
+
+    /// Initial stack pointer, at `argc` followed by the argv and envp arrays.
+    pub fn stack_pointer(&self) -> u64 {
+        self.stack_pointer
+    }
+
+    pub fn state(&self) -> ProcessState {
+        match self.handler.exit_status() {
+            Some(code) => ProcessState::Exited(code),
+            None => ProcessState::Running,
+        }
+    }
+
+    pub fn handler(&self) -> &SyscallHandler {
+        &self.handler
+    }
+
+    pub fn handler_mut(&mut self) -> &mut SyscallHandler {
+        &mut self.handler
+    }
+
+    /// Run a syscall on behalf of this process.
+    pub fn syscall(&mut self, syscall_num: u64, args: &[u64]) -> Result<i64> {
+        if let ProcessState::Exited(code) = self.state() {
+            return Err(LuCastraError::SyscallError(format!(
+                "process {} already exited with status {}",
+                self.pid, code
+            )));
+        }
+        self.handler.handle_syscall(syscall_num, args)
+    }
+}
+
+/// All processes started through the compatibility layer.
+pub struct ProcessTable {
+    processes: BTreeMap<Pid, Process>,
+    next_pid: Pid,
+    fs: Arc<Mutex<dyn SyscallFs + Send>>,
+}
+
+impl ProcessTable {
+    /// Table whose processes share an empty [`MemoryFs`].
+    pub fn new() -> Self {
+        Self::with_fs(MemoryFs::new())
+    }
+
+    /// Table whose processes share `fs`.
+    pub fn with_fs(fs: impl SyscallFs + Send + 'static) -> Self {
+        Self {
+            processes: BTreeMap::new(),
+            next_pid: 1,
+            fs: Arc::new(Mutex::new(fs)),
+        }
+    }
+
+    /// Load `elf_bytes` into a new process and return its pid.
+    pub fn spawn(&mut self, elf_bytes: &[u8], argv: &[&str], envp: &[&str]) -> Result<Pid> {
+        let mut loader = ElfLoader::new();
+        let entry_point = loader.load(elf_bytes)? as u64;
+        let (mut memory, stack_top) = load_image(&loader, elf_bytes)?;
+        let stack_pointer = push_args(&mut memory, stack_top, argv, envp)?;
+
+        let pid = self.next_pid;
+        self.next_pid += 1;
+        let handler = SyscallHandler::with_fs(Arc::clone(&self.fs)).with_memory(memory);
+        self.processes.insert(
+            pid,
+            Process {
+                pid,
+                argv: argv.iter().map(|s| s.to_string()).collect(),
+                envp: envp.iter().map(|s| s.to_string()).collect(),
+                entry_point,
+                stack_pointer,
+                handler,
+            },
+        );
+        tracing::info!(
+            "Spawned process {} ({}) at entry 0x{:x}",
+            pid,
+            argv.first().copied().unwrap_or("?"),
+            entry_point
+        );
+        Ok(pid)
+    }
+
+    pub fn get(&self, pid: Pid) -> Option<&Process> {
+        self.processes.get(&pid)
+    }
+
+    pub fn get_mut(&mut self, pid: Pid) -> Option<&mut Process> {
+        self.processes.get_mut(&pid)
+    }
+
+    /// Pids of every process not yet waited for.
+    pub fn pids(&self) -> Vec<Pid> {
+        self.processes.keys().copied().collect()
+    }
+
+    /// Run a syscall on behalf of `pid`.
+    pub fn syscall(&mut self, pid: Pid, syscall_num: u64, args: &[u64]) -> Result<i64> {
+        self.get_mut(pid)
+            .ok_or_else(|| no_such_process(pid))?
+            .syscall(syscall_num, args)
+    }
+
+    /// Status of `pid`. An exited process is removed from the table, as
+    /// with `waitpid`; a running one stays.
+    pub fn wait(&mut self, pid: Pid) -> Result<ProcessState> {
+        let state = self.get(pid).ok_or_else(|| no_such_process(pid))?.state();
+        if let ProcessState::Exited(code) = state {
+            self.processes.remove(&pid);
+            tracing::debug!("Reaped process {} (status {})", pid, code);
+        }
+        Ok(state)
+    }
+}
+
+impl Default for ProcessTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn no_such_process(pid: Pid) -> LuCastraError {
+    LuCastraError::SyscallError(format!("No such process: {}", pid))
+}
+
+/// Copy the `PT_LOAD` segments into one region with the stack above them.
+/// Returns the memory and the top of the stack.
+fn load_image(loader: &ElfLoader, data: &[u8]) -> Result<(ProcessMemory, u64)> {
+    let segments: Vec<_> = loader.load_segments().collect();
+    let start = segments
+        .iter()
+        .map(|s| s.p_vaddr)
+        .min()
+        .unwrap_or(DEFAULT_BASE);
+    let end = segments
+        .iter()
+        .map(|s| s.p_vaddr.saturating_add(s.p_memsz))
+        .max()
+        .unwrap_or(DEFAULT_BASE);
+    let too_large = || LuCastraError::SyscallError("ELF image too large".to_string());
+    let base = start - start % PAGE_SIZE;
+    let stack_bottom = end
+        .checked_next_multiple_of(PAGE_SIZE)
+        .ok_or_else(too_large)?;
+    let size = usize::try_from(stack_bottom - base)
+        .ok()
+        .and_then(|image| image.checked_add(STACK_SIZE))
+        .filter(|&size| size <= MAX_IMAGE_SIZE)
+        .ok_or_else(too_large)?;
+
+    let mut memory = ProcessMemory::new(base, size);
+    for segment in segments {
+        let offset = segment.p_offset as usize;
+        let bytes = offset
+            .checked_add(segment.p_filesz as usize)
+            .and_then(|end| data.get(offset..end))
+            .ok_or_else(|| LuCastraError::SyscallError("ELF segment out of bounds".to_string()))?;
+        if !memory.write(segment.p_vaddr, bytes) {
+            return Err(LuCastraError::SyscallError(
+                "ELF segment does not fit in its memory".to_string(),
+            ));
+        }
+    }
+    Ok((memory, stack_bottom + STACK_SIZE as u64))
+}
+
+/// Lay out argc, argv and envp at the top of the stack as the System V ABI
+/// expects, returning the new stack pointer.
+fn push_args(
+    memory: &mut ProcessMemory,
+    stack_top: u64,
+    argv: &[&str],
+    envp: &[&str],
+) -> Result<u64> {
+    let overflow = || LuCastraError::SyscallError("arguments do not fit on the stack".to_string());
+
+    let mut sp = stack_top;
+    let mut push_str = |memory: &mut ProcessMemory, s: &str| -> Result<u64> {
+        sp = sp.checked_sub(s.len() as u64 + 1).ok_or_else(overflow)?;
+        if !memory.write(sp, &[s.as_bytes(), &[0]].concat()) {
+            return Err(overflow());
+        }
+        Ok(sp)
+    };
+    let argv_ptrs = argv
+        .iter()
+        .map(|arg| push_str(memory, arg))
+        .collect::<Result<Vec<_>>>()?;
+    let envp_ptrs = envp
+        .iter()
+        .map(|var| push_str(memory, var))
+        .collect::<Result<Vec<_>>>()?;
+
+    // argc, argv..., NULL, envp..., NULL, AT_NULL auxv pair
+    let mut words = vec![argv.len() as u64];
+    words.extend(&argv_ptrs);
+    words.push(0);
+    words.extend(&envp_ptrs);
+    words.extend([0, 0, 0]);
+    let table: Vec<u8> = words.iter().flat_map(|w| w.to_le_bytes()).collect();
+
+    let lowest_string = envp_ptrs
+        .iter()
+        .chain(&argv_ptrs)
+        .copied()
+        .min()
+        .unwrap_or(stack_top);
+    let sp = lowest_string
+        .checked_sub(table.len() as u64)
+        .map(|sp| sp - sp % 16)
+        .ok_or_else(overflow)?;
+    if !memory.write(sp, &table) {
+        return Err(overflow());
+    }
+    Ok(sp)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fixtures::elf_image;
+
+    #[test]
+    fn test_processes_have_separate_fd_tables() {
+        let mut table = ProcessTable::new();
+        let first = table.spawn(&elf_image(b"first"), &["first"], &[]).unwrap();
+        let second = table
+            .spawn(&elf_image(b"second"), &["second"], &[])
+            .unwrap();
+        assert_ne!(first, second);
+
+        table
+            .get_mut(first)
+            .unwrap()
+            .handler_mut()
+            .write_file("/shared.txt", b"hi".to_vec())
+            .unwrap();
+        let fd = table
+            .get_mut(first)
+            .unwrap()
+            .handler_mut()
+            .open_file("/shared.txt", 0);
+        assert_eq!(fd, 3);
+
+        assert!(table
+            .get(first)
+            .unwrap()
+            .handler()
+            .fd_table()
+            .get(3)
+            .is_some());
+        assert!(table
+            .get(second)
+            .unwrap()
+            .handler()
+            .fd_table()
+            .get(3)
+            .is_none());
+        assert_eq!(table.syscall(second, 3, &[3]).unwrap(), -9);
+        assert_eq!(table.syscall(first, 3, &[3]).unwrap(), 0);
+
+        // The filesystem is shared, but fd numbers are per process
+        let process = table.get_mut(second).unwrap();
+        assert_eq!(process.handler_mut().open_file("/shared.txt", 0), 3);
+        let buf = process.stack_pointer() - 64;
+        assert_eq!(process.syscall(0, &[3, buf, 16]).unwrap(), 2);
+        assert_eq!(process.handler().memory().read(buf, 2).unwrap(), b"hi");
+    }
+
+    #[test]
+    fn test_hostile_headers_are_rejected() {
+        // Offsets of p_vaddr, p_filesz and p_memsz in the program header
+        let set = |image: &mut Vec<u8>, at: usize, value: u64| {
+            image[64 + at..64 + at + 8].copy_from_slice(&value.to_le_bytes());
+        };
+        let mut table = ProcessTable::new();
+        let rejected = |table: &mut ProcessTable, image: &[u8]| match table
+            .spawn(image, &["hostile"], &[])
+            .unwrap_err()
+        {
+            LuCastraError::SyscallError(message) => message,
+            e => panic!("unexpected error: {}", e),
+        };
+
+        // The end of the segment saturates at the top of the address space
+        let mut image = elf_image(b"code");
+        set(&mut image, 40, u64::MAX);
+        assert_eq!(rejected(&mut table, &image), "ELF image too large");
+
+        // A span of about 128 TB would be allocated up front
+        let mut image = elf_image(b"code");
+        set(&mut image, 16, 0x7fff_ffff_0000);
+        set(&mut image, 40, 0x7fff_ffff_0000);
+        assert_eq!(rejected(&mut table, &image), "ELF image too large");
+
+        // More file bytes than the segment and stack have room for
+        let mut image = elf_image(&vec![0x90; 2 * STACK_SIZE]);
+        set(&mut image, 40, 1);
+        assert_eq!(
+            rejected(&mut table, &image),
+            "ELF segment does not fit in its memory"
+        );
+        assert!(table.pids().is_empty());
+    }
+
+    #[test]
+    fn test_exit_codes_propagate_through_wait() {
+        let mut table = ProcessTable::new();
+        let ok = table.spawn(&elf_image(b"ok"), &["ok"], &[]).unwrap();
+        let failing = table.spawn(&elf_image(b"fail"), &["fail"], &[]).unwrap();
+
+        assert_eq!(table.wait(ok).unwrap(), ProcessState::Running);
+
+        table.syscall(failing, 60, &[3]).unwrap();
+        table.syscall(ok, 231, &[0]).unwrap();
+        assert!(table.syscall(ok, 1, &[1, 0, 0]).is_err());
+
+        assert_eq!(table.wait(failing).unwrap(), ProcessState::Exited(3));
+        assert_eq!(table.wait(ok).unwrap(), ProcessState::Exited(0));
+        assert!(table.wait(ok).is_err());
+        assert!(table.pids().is_empty());
+    }
+
+    #[test]
+    fn test_spawn_loads_segments_and_arguments() {
+        let mut table = ProcessTable::new();
+        let pid = table
+            .spawn(
+                &elf_image(b"code"),
+                &["soffice", "doc.odt"],
+                &["HOME=/home/me"],
+            )
+            .unwrap();
+        let process = table.get(pid).unwrap();
+        let memory = process.handler().memory();
+
+        assert_eq!(process.entry_point(), crate::fixtures::ELF_ENTRY);
+        assert_eq!(memory.read(process.entry_point(), 4).unwrap(), b"code");
+
+        let sp = process.stack_pointer();
+        assert_eq!(sp % 16, 0);
+        let word = |at: u64| u64::from_le_bytes(memory.read(at, 8).unwrap().try_into().unwrap());
+        assert_eq!(word(sp), 2);
+        assert_eq!(memory.read_c_string(word(sp + 8)).unwrap(), "soffice");
+        assert_eq!(memory.read_c_string(word(sp + 16)).unwrap(), "doc.odt");
+        assert_eq!(word(sp + 24), 0);
+        assert_eq!(
+            memory.read_c_string(word(sp + 32)).unwrap(),
+            "HOME=/home/me"
+        );
+
+        assert!(table.spawn(b"not an elf", &[], &[]).is_err());
+    }
+}
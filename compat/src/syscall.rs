@@ -3,8 +3,314 @@
 //! Maps common Linux syscalls to LucAstra kernel operations.
 //! Focuses on file I/O, device I/O, and basic process control.
 
-use lucastra_core::Result;
-use std::collections::HashMap;
+use lucastra_core::{command::FileEntry, LuCastraError, Result};
+use lucastra_fs::FilesystemManager;
+use std::collections::{BTreeSet, HashMap};
+use std::ops::Range;
+use std::sync::{Arc, Mutex, MutexGuard};
+
+const ENOENT: i64 = -2;
+const EIO: i64 = -5;
+const EBADF: i64 = -9;
+const EFAULT: i64 = -14;
+const EEXIST: i64 = -17;
+const ENOTDIR: i64 = -20;
+const EISDIR: i64 = -21;
+const EINVAL: i64 = -22;
+const EFBIG: i64 = -27;
+const ENOSYS: i64 = -38;
+
+/// Largest size `write` grows a file to; files are held in memory.
+const MAX_FILE_SIZE: usize = 1 << 30;
+
+/// `dirfd` value meaning "relative to the working directory".
+const AT_FDCWD: i32 = -100;
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+
+const O_ACCMODE: i32 = 0o3;
+const O_CREAT: i32 = 0o100;
+const O_TRUNC: i32 = 0o1000;
+
+/// Longest path `open` will read from process memory, including the NUL.
+const PATH_MAX: usize = 4096;
+/// Size of `struct stat` on x86_64 Linux.
+const STAT_SIZE: usize = 144;
+/// Bytes of `struct linux_dirent64` before `d_name`.
+const DIRENT_HEADER: usize = 19;
+
+/// Linux x86_64 syscall numbers and names, for logging.
+const SYSCALL_NAMES: &[(u64, &str)] = &[
+    (0, "read"),
+    (1, "write"),
+    (2, "open"),
+    (3, "close"),
+    (4, "stat"),
+    (5, "fstat"),
+    (6, "lstat"),
+    (7, "poll"),
+    (8, "lseek"),
+    (9, "mmap"),
+    (10, "mprotect"),
+    (11, "munmap"),
+    (12, "brk"),
+    (13, "rt_sigaction"),
+    (14, "rt_sigprocmask"),
+    (16, "ioctl"),
+    (17, "pread64"),
+    (18, "pwrite64"),
+    (19, "readv"),
+    (20, "writev"),
+    (21, "access"),
+    (22, "pipe"),
+    (32, "dup"),
+    (33, "dup2"),
+    (39, "getpid"),
+    (41, "socket"),
+    (56, "clone"),
+    (57, "fork"),
+    (59, "execve"),
+    (60, "exit"),
+    (61, "wait4"),
+    (62, "kill"),
+    (63, "uname"),
+    (72, "fcntl"),
+    (78, "getdents"),
+    (79, "getcwd"),
+    (80, "chdir"),
+    (82, "rename"),
+    (83, "mkdir"),
+    (84, "rmdir"),
+    (87, "unlink"),
+    (89, "readlink"),
+    (96, "gettimeofday"),
+    (102, "getuid"),
+    (158, "arch_prctl"),
+    (186, "gettid"),
+    (202, "futex"),
+    (217, "getdents64"),
+    (218, "set_tid_address"),
+    (228, "clock_gettime"),
+    (231, "exit_group"),
+    (257, "openat"),
+    (262, "newfstatat"),
+    (302, "prlimit64"),
+    (318, "getrandom"),
+];
+
+/// Name of a Linux x86_64 syscall, if it is in [`SYSCALL_NAMES`].
+pub fn syscall_name(syscall_num: u64) -> Option<&'static str> {
+    SYSCALL_NAMES
+        .iter()
+        .find(|(num, _)| *num == syscall_num)
+        .map(|(_, name)| *name)
+}
+
+/// File operations the syscall layer needs from a filesystem.
+pub trait SyscallFs {
+    fn stat(&self, path: &str) -> Result<FileEntry>;
+    fn read(&self, path: &str) -> Result<Vec<u8>>;
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<()>;
+    /// Direct children of a directory.
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>>;
+    fn mkdir(&mut self, path: &str) -> Result<()>;
+    fn unlink(&mut self, path: &str) -> Result<()>;
+}
+
+impl SyscallFs for FilesystemManager {
+    fn stat(&self, path: &str) -> Result<FileEntry> {
+        FilesystemManager::stat(self, path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.read_file(path)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        self.write_file(path, data)
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>> {
+        self.list_files(path)
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<()> {
+        self.create_dir(path)
+    }
+
+    fn unlink(&mut self, path: &str) -> Result<()> {
+        self.delete_file(path)
+    }
+}
+
+/// Lets several processes share one filesystem.
+impl<T: SyscallFs + ?Sized> SyscallFs for Arc<Mutex<T>> {
+    fn stat(&self, path: &str) -> Result<FileEntry> {
+        lock(self)?.stat(path)
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        lock(self)?.read(path)
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        lock(self)?.write(path, data)
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>> {
+        lock(self)?.list(path)
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<()> {
+        lock(self)?.mkdir(path)
+    }
+
+    fn unlink(&mut self, path: &str) -> Result<()> {
+        lock(self)?.unlink(path)
+    }
+}
+
+fn lock<T: ?Sized>(fs: &Mutex<T>) -> Result<MutexGuard<'_, T>> {
+    fs.lock()
+        .map_err(|_| LuCastraError::FilesystemError("filesystem lock poisoned".to_string()))
+}
+
+/// In-memory files, for tests and programs that need no real filesystem.
+///
+/// `/` and every parent of a stored file count as directories.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryFs {
+    files: HashMap<String, Vec<u8>>,
+    dirs: BTreeSet<String>,
+}
+
+impl MemoryFs {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        path == "/"
+            || self.dirs.contains(path)
+            || self
+                .files
+                .keys()
+                .chain(&self.dirs)
+                .any(|p| p.starts_with(&prefix))
+    }
+}
+
+fn not_found(path: &str) -> LuCastraError {
+    LuCastraError::FileNotFound(path.to_string())
+}
+
+impl SyscallFs for MemoryFs {
+    fn stat(&self, path: &str) -> Result<FileEntry> {
+        let (is_dir, size) = match self.files.get(path) {
+            Some(data) => (false, data.len() as u64),
+            None if self.is_dir(path) => (true, 0),
+            None => return Err(not_found(path)),
+        };
+        Ok(FileEntry {
+            path: path.to_string(),
+            is_dir,
+            size,
+            modified: None,
+        })
+    }
+
+    fn read(&self, path: &str) -> Result<Vec<u8>> {
+        self.files.get(path).cloned().ok_or_else(|| not_found(path))
+    }
+
+    fn write(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        self.files.insert(path.to_string(), data.to_vec());
+        Ok(())
+    }
+
+    fn list(&self, path: &str) -> Result<Vec<FileEntry>> {
+        if !self.is_dir(path) {
+            return Err(not_found(path));
+        }
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        let children: BTreeSet<String> = self
+            .files
+            .keys()
+            .chain(&self.dirs)
+            .filter_map(|p| p.strip_prefix(&prefix))
+            .filter_map(|rest| rest.split('/').next())
+            .filter(|name| !name.is_empty())
+            .map(|name| format!("{}{}", prefix, name))
+            .collect();
+        children.iter().map(|child| self.stat(child)).collect()
+    }
+
+    fn mkdir(&mut self, path: &str) -> Result<()> {
+        self.dirs.insert(path.to_string());
+        Ok(())
+    }
+
+    fn unlink(&mut self, path: &str) -> Result<()> {
+        self.files
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| not_found(path))
+    }
+}
+
+/// A process's address space as one contiguous region starting at `base`.
+///
+/// Syscalls read pathnames and buffers from it and copy results into it.
+#[derive(Debug, Default, Clone)]
+pub struct ProcessMemory {
+    base: u64,
+    bytes: Vec<u8>,
+}
+
+impl ProcessMemory {
+    /// Zeroed memory covering `base..base + size`.
+    pub fn new(base: u64, size: usize) -> Self {
+        Self {
+            base,
+            bytes: vec![0; size],
+        }
+    }
+
+    pub fn base(&self) -> u64 {
+        self.base
+    }
+
+    fn range(&self, addr: u64, len: usize) -> Option<Range<usize>> {
+        let start = usize::try_from(addr.checked_sub(self.base)?).ok()?;
+        let end = start.checked_add(len)?;
+        (end <= self.bytes.len()).then_some(start..end)
+    }
+
+    /// `len` bytes at `addr`, or `None` if any of them is unmapped.
+    pub fn read(&self, addr: u64, len: usize) -> Option<&[u8]> {
+        self.range(addr, len).map(|range| &self.bytes[range])
+    }
+
+    /// Copy `data` to `addr`. Returns `false` if it does not fit.
+    pub fn write(&mut self, addr: u64, data: &[u8]) -> bool {
+        match self.range(addr, data.len()) {
+            Some(range) => {
+                self.bytes[range].copy_from_slice(data);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// NUL-terminated string at `addr`.
+    pub fn read_c_string(&self, addr: u64) -> Option<String> {
+        let start = self.range(addr, 0)?.start;
+        let end = self.bytes.len().min(start + PATH_MAX);
+        let len = self.bytes[start..end].iter().position(|&b| b == 0)?;
+        String::from_utf8(self.bytes[start..start + len].to_vec()).ok()
+    }
+}
 
 /// File descriptor table for a process.
 pub struct FileDescriptorTable {
@@ -98,24 +404,56 @@ impl Default for FileDescriptorTable {
 /// Syscall handler for POSIX compatibility.
 pub struct SyscallHandler {
     fd_table: FileDescriptorTable,
-    // In-memory file storage for testing (path -> content)
-    pub file_data: HashMap<String, Vec<u8>>,
+    fs: Box<dyn SyscallFs + Send>,
+    memory: ProcessMemory,
+    exit_status: Option<i32>,
 }
 
 impl SyscallHandler {
+    /// Handler backed by an empty [`MemoryFs`].
     pub fn new() -> Self {
+        Self::with_fs(MemoryFs::new())
+    }
+
+    /// Handler whose file syscalls go to `fs`.
+    pub fn with_fs(fs: impl SyscallFs + Send + 'static) -> Self {
         Self {
             fd_table: FileDescriptorTable::new(),
-            file_data: HashMap::new(),
+            fs: Box::new(fs),
+            memory: ProcessMemory::default(),
+            exit_status: None,
         }
     }
 
-    /// Store file data for read testing.
-    pub fn write_file(&mut self, path: &str, data: Vec<u8>) {
-        self.file_data.insert(path.to_string(), data);
+    /// Use `memory` as the address space syscall pointers refer to.
+    pub fn with_memory(mut self, memory: ProcessMemory) -> Self {
+        self.memory = memory;
+        self
+    }
+
+    pub fn memory(&self) -> &ProcessMemory {
+        &self.memory
+    }
+
+    pub fn memory_mut(&mut self) -> &mut ProcessMemory {
+        &mut self.memory
+    }
+
+    pub fn fd_table(&self) -> &FileDescriptorTable {
+        &self.fd_table
+    }
+
+    /// Status passed to `exit`, once the program has called it.
+    pub fn exit_status(&self) -> Option<i32> {
+        self.exit_status
+    }
+
+    /// Store a file in the backing filesystem.
+    pub fn write_file(&mut self, path: &str, data: Vec<u8>) -> Result<()> {
+        self.fs.write(path, &data)
     }
 
-    /// Open a file by registered path (for testing/direct calls).
+    /// Open a file by path without going through process memory.
     pub fn open_file(&mut self, path: &str, flags: i32) -> i32 {
         self.fd_table.open(path, flags)
     }
@@ -123,116 +461,160 @@ impl SyscallHandler {
     /// Handle a syscall by number and arguments.
     /// Returns the syscall result or an error.
     pub fn handle_syscall(&mut self, syscall_num: u64, args: &[u64]) -> Result<i64> {
+        let arg = |i: usize| args.get(i).copied().unwrap_or(0);
         match syscall_num {
             // open(const char *path, int flags, mode_t mode)
             2 => {
-                let path_ptr = args.first().copied().unwrap_or(0) as usize;
-                let flags = args.get(1).copied().unwrap_or(0) as i32;
-                // Mock: just return a new fd
-                let path = format!("/path/{}", path_ptr);
-                let fd = self.fd_table.open(&path, flags);
-                tracing::debug!("syscall: open({}) -> fd {}", path, fd);
-                Ok(fd as i64)
+                let path = match self.path_arg(AT_FDCWD, arg(0)) {
+                    Ok(path) => path,
+                    Err(errno) => return Ok(errno),
+                };
+                let flags = arg(1) as i32;
+                let result = self.open(&path, flags);
+                tracing::debug!("syscall: open({}, 0x{:x}) -> {}", path, flags, result);
+                Ok(result)
+            }
+            // openat(int dirfd, const char *path, int flags, mode_t mode)
+            257 => {
+                let path = match self.path_arg(arg(0) as i32, arg(1)) {
+                    Ok(path) => path,
+                    Err(errno) => return Ok(errno),
+                };
+                let flags = arg(2) as i32;
+                let result = self.open(&path, flags);
+                tracing::debug!("syscall: openat({}, 0x{:x}) -> {}", path, flags, result);
+                Ok(result)
+            }
+            // mkdir(const char *path, mode_t mode)
+            83 => {
+                let path = match self.path_arg(AT_FDCWD, arg(0)) {
+                    Ok(path) => path,
+                    Err(errno) => return Ok(errno),
+                };
+                let result = if self.fs.stat(&path).is_ok() {
+                    EEXIST
+                } else if self.fs.mkdir(&path).is_err() {
+                    EIO
+                } else {
+                    0
+                };
+                tracing::debug!("syscall: mkdir({}) -> {}", path, result);
+                Ok(result)
+            }
+            // unlink(const char *path)
+            87 => {
+                let path = match self.path_arg(AT_FDCWD, arg(0)) {
+                    Ok(path) => path,
+                    Err(errno) => return Ok(errno),
+                };
+                let result = match self.fs.stat(&path) {
+                    Err(_) => ENOENT,
+                    Ok(entry) if entry.is_dir => EISDIR,
+                    Ok(_) if self.fs.unlink(&path).is_err() => EIO,
+                    Ok(_) => 0,
+                };
+                tracing::debug!("syscall: unlink({}) -> {}", path, result);
+                Ok(result)
+            }
+            // getdents64(int fd, struct linux_dirent64 *dirp, size_t count)
+            217 => {
+                let (fd, buf, count) = (arg(0) as i32, arg(1), arg(2) as usize);
+                let result = self.getdents64(fd, buf, count);
+                tracing::debug!("syscall: getdents64(fd={}) -> {}", fd, result);
+                Ok(result)
             }
             // read(int fd, void *buf, size_t count)
             0 => {
-                let fd = args.first().copied().unwrap_or(0) as i32;
-                let count = args.get(2).copied().unwrap_or(0) as usize;
-
-                if let Some(desc) = self.fd_table.get_mut(fd) {
-                    if let Some(data) = self.file_data.get(&desc.path) {
-                        let start = desc.offset as usize;
-                        let end = (start + count).min(data.len());
-                        let bytes_read = (end - start).max(0);
-                        desc.offset += bytes_read as u64;
-                        tracing::debug!(
-                            "syscall: read(fd={}, count={}) -> {} bytes",
-                            fd,
-                            count,
-                            bytes_read
-                        );
-                        return Ok(bytes_read as i64);
-                    }
-                }
-                tracing::debug!("syscall: read(fd={}) -> 0 (not found or EOF)", fd);
-                Ok(0)
+                let (fd, buf, count) = (arg(0) as i32, arg(1), arg(2) as usize);
+                let result = self.read(fd, buf, count);
+                tracing::debug!("syscall: read(fd={}, count={}) -> {}", fd, count, result);
+                Ok(result)
             }
             // write(int fd, const void *buf, size_t count)
             1 => {
-                let fd = args.first().copied().unwrap_or(0) as i32;
-                let count = args.get(2).copied().unwrap_or(0) as usize;
-
-                if let Some(desc) = self.fd_table.get(fd) {
-                    tracing::debug!(
-                        "syscall: write(fd={}, path={}, count={})",
-                        fd,
-                        desc.path,
-                        count
-                    );
-                    Ok(count as i64)
-                } else {
-                    tracing::debug!("syscall: write(fd={}) -> EBADF", fd);
-                    Ok(-9) // EBADF
-                }
+                let (fd, buf, count) = (arg(0) as i32, arg(1), arg(2) as usize);
+                let result = self.write(fd, buf, count);
+                tracing::debug!("syscall: write(fd={}, count={}) -> {}", fd, count, result);
+                Ok(result)
             }
             // close(int fd)
             3 => {
-                let fd = args.first().copied().unwrap_or(0) as i32;
-                let result = if self.fd_table.close(fd) { 0 } else { -9 };
+                let fd = arg(0) as i32;
+                let result = if self.fd_table.close(fd) { 0 } else { EBADF };
                 tracing::debug!("syscall: close(fd={}) -> {}", fd, result);
                 Ok(result)
             }
+            // stat(const char *path, struct stat *buf)
+            4 => {
+                let path = match self.path_arg(AT_FDCWD, arg(0)) {
+                    Ok(path) => path,
+                    Err(errno) => return Ok(errno),
+                };
+                let result = self.stat(&path, arg(1));
+                tracing::debug!("syscall: stat({}) -> {}", path, result);
+                Ok(result)
+            }
+            // fstat(int fd, struct stat *buf)
+            5 => {
+                let fd = arg(0) as i32;
+                let Some(path) = self.fd_table.get(fd).map(|desc| desc.path.clone()) else {
+                    return Ok(EBADF);
+                };
+                let result = self.stat(&path, arg(1));
+                tracing::debug!("syscall: fstat(fd={}) -> {}", fd, result);
+                Ok(result)
+            }
             // lseek(int fd, off_t offset, int whence)
             8 => {
-                let fd = args.first().copied().unwrap_or(0) as i32;
-                let offset = args.get(1).copied().unwrap_or(0) as i64;
-                let whence = args.get(2).copied().unwrap_or(0) as i32;
-
-                if let Some(desc) = self.fd_table.get_mut(fd) {
-                    match whence {
-                        0 => desc.offset = offset as u64,                        // SEEK_SET
-                        1 => desc.offset = (desc.offset as i64 + offset) as u64, // SEEK_CUR
-                        2 => {
-                            // SEEK_END
-                            if let Some(data) = self.file_data.get(&desc.path) {
-                                desc.offset = (data.len() as i64 + offset) as u64;
-                            }
-                        }
-                        _ => {}
-                    }
-                    tracing::debug!(
-                        "syscall: lseek(fd={}, offset={}, whence={}) -> {}",
-                        fd,
-                        offset,
-                        whence,
-                        desc.offset
-                    );
-                    Ok(desc.offset as i64)
-                } else {
-                    Ok(-9)
+                let fd = arg(0) as i32;
+                let offset = arg(1) as i64;
+                let whence = arg(2) as i32;
+
+                let Some(path) = self.fd_table.get(fd).map(|desc| desc.path.clone()) else {
+                    return Ok(EBADF);
+                };
+                let end = self.fs.stat(&path).map(|entry| entry.size as i64);
+                let desc = self.fd_table.get_mut(fd).expect("fd checked above");
+                let target = match whence {
+                    0 => Some(offset),                                     // SEEK_SET
+                    1 => (desc.offset as i64).checked_add(offset),         // SEEK_CUR
+                    2 => end.ok().and_then(|end| end.checked_add(offset)), // SEEK_END
+                    _ => None,
+                };
+                // Offsets before the start of the file are invalid
+                match target.and_then(|target| u64::try_from(target).ok()) {
+                    Some(target) => desc.offset = target,
+                    None => return Ok(EINVAL),
                 }
+                tracing::debug!(
+                    "syscall: lseek(fd={}, offset={}, whence={}) -> {}",
+                    fd,
+                    offset,
+                    whence,
+                    desc.offset
+                );
+                Ok(desc.offset as i64)
             }
             // dup(int oldfd)
             32 => {
-                let fd = args.first().copied().unwrap_or(0) as i32;
+                let fd = arg(0) as i32;
                 if let Some(new_fd) = self.fd_table.dup(fd) {
                     tracing::debug!("syscall: dup({}) -> {}", fd, new_fd);
                     Ok(new_fd as i64)
                 } else {
-                    Ok(-9)
+                    Ok(EBADF)
                 }
             }
             // ioctl(int fd, unsigned long request, ...)
             16 => {
-                let fd = args.first().copied().unwrap_or(0) as i32;
-                let request = args.get(1).copied().unwrap_or(0);
-                tracing::debug!("syscall: ioctl(fd={}, request=0x{:x}) -> 0", fd, request);
+                tracing::debug!("syscall: ioctl(fd={}, request=0x{:x}) -> 0", arg(0), arg(1));
                 Ok(0)
             }
-            // exit(int status)
-            60 => {
-                let status = args.first().copied().unwrap_or(0) as i32;
+            // exit(int status), exit_group(int status)
+            60 | 231 => {
+                let status = arg(0) as i32;
                 tracing::info!("syscall: exit with status {}", status);
+                self.exit_status = Some(status);
                 Ok(0)
             }
             // mmap (stub)
@@ -242,16 +624,197 @@ impl SyscallHandler {
             }
             // brk (stub)
             12 => {
-                let addr = args.first().copied().unwrap_or(0);
+                let addr = arg(0);
                 tracing::debug!("syscall: brk(0x{:x}) -> 0x{:x}", addr, addr);
                 Ok(addr as i64)
             }
             _ => {
-                tracing::debug!("syscall: unknown syscall {}", syscall_num);
-                Ok(-38) // ENOSYS
+                tracing::debug!(
+                    "syscall: unimplemented syscall {} ({})",
+                    syscall_num,
+                    syscall_name(syscall_num).unwrap_or("unknown")
+                );
+                Ok(ENOSYS)
+            }
+        }
+    }
+
+    /// Read the path at `ptr` and make it absolute, relative paths being
+    /// resolved against `dirfd` (or `/` for [`AT_FDCWD`]). Errors are errnos.
+    fn path_arg(&self, dirfd: i32, ptr: u64) -> std::result::Result<String, i64> {
+        let path = self.memory.read_c_string(ptr).ok_or(EFAULT)?;
+        if path.starts_with('/') || dirfd == AT_FDCWD {
+            return Ok(join_path("/", &path));
+        }
+        let dir = &self.fd_table.get(dirfd).ok_or(EBADF)?.path;
+        match self.fs.stat(dir) {
+            Ok(entry) if entry.is_dir => Ok(join_path(dir, &path)),
+            _ => Err(ENOTDIR),
+        }
+    }
+
+    fn open(&mut self, path: &str, flags: i32) -> i64 {
+        let exists = self.fs.stat(path).is_ok();
+        let writable = flags & O_ACCMODE != 0;
+        if !exists && flags & O_CREAT == 0 {
+            return ENOENT;
+        }
+        if (!exists || (writable && flags & O_TRUNC != 0)) && self.fs.write(path, &[]).is_err() {
+            return EIO;
+        }
+        self.fd_table.open(path, flags) as i64
+    }
+
+    fn read(&mut self, fd: i32, buf: u64, count: usize) -> i64 {
+        let Some(desc) = self.fd_table.get(fd) else {
+            return EBADF;
+        };
+        if fd == 0 || desc.path == "stdin" {
+            return 0;
+        }
+        match self.fs.stat(&desc.path) {
+            Ok(entry) if entry.is_dir => return EISDIR,
+            Ok(_) => {}
+            Err(_) => return ENOENT,
+        }
+        let Ok(data) = self.fs.read(&desc.path) else {
+            return EIO;
+        };
+
+        let start = (desc.offset as usize).min(data.len());
+        let end = start.saturating_add(count).min(data.len());
+        if !self.memory.write(buf, &data[start..end]) {
+            return EFAULT;
+        }
+        if let Some(desc) = self.fd_table.get_mut(fd) {
+            desc.offset = end as u64;
+        }
+        (end - start) as i64
+    }
+
+    fn write(&mut self, fd: i32, buf: u64, count: usize) -> i64 {
+        let Some(desc) = self.fd_table.get(fd) else {
+            return EBADF;
+        };
+        let Some(bytes) = self.memory.read(buf, count) else {
+            return EFAULT;
+        };
+
+        match desc.path.as_str() {
+            "stdout" => {
+                tracing::info!("stdout: {}", String::from_utf8_lossy(bytes).trim_end());
+                return count as i64;
+            }
+            "stderr" => {
+                tracing::warn!("stderr: {}", String::from_utf8_lossy(bytes).trim_end());
+                return count as i64;
+            }
+            _ => {}
+        }
+
+        let path = desc.path.clone();
+        let end = usize::try_from(desc.offset)
+            .ok()
+            .and_then(|offset| offset.checked_add(count))
+            .filter(|&end| end <= MAX_FILE_SIZE);
+        let Some(end) = end else {
+            return EFBIG;
+        };
+        let offset = end - count;
+        let bytes = bytes.to_vec();
+        if self.fs.stat(&path).is_ok_and(|entry| entry.is_dir) {
+            return EISDIR;
+        }
+        let mut data = self.fs.read(&path).unwrap_or_default();
+        if data.len() < end {
+            data.resize(end, 0);
+        }
+        data[offset..end].copy_from_slice(&bytes);
+        if self.fs.write(&path, &data).is_err() {
+            return EIO;
+        }
+        if let Some(desc) = self.fd_table.get_mut(fd) {
+            desc.offset += count as u64;
+        }
+        count as i64
+    }
+
+    /// Copy `linux_dirent64` records for the directory open at `fd` into
+    /// `buf`. The fd offset is the index of the next entry to return.
+    fn getdents64(&mut self, fd: i32, buf: u64, count: usize) -> i64 {
+        let Some(desc) = self.fd_table.get(fd) else {
+            return EBADF;
+        };
+        let entries = match self.fs.stat(&desc.path) {
+            Ok(entry) if entry.is_dir => match self.fs.list(&desc.path) {
+                Ok(entries) => entries,
+                Err(_) => return EIO,
+            },
+            Ok(_) => return ENOTDIR,
+            Err(_) => return ENOENT,
+        };
+
+        let first = desc.offset as usize;
+        let mut out = Vec::new();
+        let mut next = first;
+        for (index, entry) in entries.iter().enumerate().skip(first) {
+            let name = entry.path.rsplit('/').next().unwrap_or(&entry.path);
+            let reclen = (DIRENT_HEADER + name.len() + 1).next_multiple_of(8);
+            if out.len() + reclen > count {
+                break;
+            }
+            let mut record = vec![0u8; reclen];
+            // Inode 0 marks a deleted entry, so number from 1
+            record[0..8].copy_from_slice(&(index as u64 + 1).to_le_bytes());
+            record[8..16].copy_from_slice(&(index as i64 + 1).to_le_bytes());
+            record[16..18].copy_from_slice(&(reclen as u16).to_le_bytes());
+            record[18] = if entry.is_dir { DT_DIR } else { DT_REG };
+            record[DIRENT_HEADER..DIRENT_HEADER + name.len()].copy_from_slice(name.as_bytes());
+            out.extend(record);
+            next = index + 1;
+        }
+
+        if out.is_empty() && next < entries.len() {
+            return EINVAL;
+        }
+        if !self.memory.write(buf, &out) {
+            return EFAULT;
+        }
+        if let Some(desc) = self.fd_table.get_mut(fd) {
+            desc.offset = next as u64;
+        }
+        out.len() as i64
+    }
+
+    /// Fill the `struct stat` at `buf` with the mode and size of `path`.
+    fn stat(&mut self, path: &str, buf: u64) -> i64 {
+        let Ok(entry) = self.fs.stat(path) else {
+            return ENOENT;
+        };
+        let mode: u32 = if entry.is_dir { 0o040755 } else { 0o100644 };
+        let mut stat = [0u8; STAT_SIZE];
+        stat[24..28].copy_from_slice(&mode.to_le_bytes());
+        stat[48..56].copy_from_slice(&(entry.size as i64).to_le_bytes());
+        if !self.memory.write(buf, &stat) {
+            return EFAULT;
+        }
+        0
+    }
+}
+
+/// Join `path` onto `base`, resolving `.` and `..` components.
+fn join_path(base: &str, path: &str) -> String {
+    let mut parts: Vec<&str> = Vec::new();
+    for part in base.split('/').chain(path.split('/')) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                parts.pop();
             }
+            part => parts.push(part),
         }
     }
+    format!("/{}", parts.join("/"))
 }
 
 impl Default for SyscallHandler {
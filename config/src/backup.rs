@@ -0,0 +1,172 @@
+//! Crash-safe config saves and the backups they keep.
+//!
+//! Saves write `config.toml.tmp`, sync it and rename it over `config.toml`,
+//! so a crash leaves either the old file or the new one. The file being
+//! replaced is kept as `config.toml.bak.1`, pushing older backups to
+//! `.bak.2` and `.bak.3`. A config file that no longer parses is moved
+//! aside on load and the newest backup that does parse takes its place.
+
+use crate::{get_config_file_path, Config, ConfigError, Result};
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Backups kept of the config file.
+pub const CONFIG_BACKUPS: usize = 3;
+
+/// An earlier version of the config file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConfigBackup {
+    /// 1 for the newest backup, up to [`CONFIG_BACKUPS`].
+    pub number: usize,
+    pub path: PathBuf,
+    /// When this version was saved, in Unix seconds.
+    pub saved_at: Option<i64>,
+}
+
+impl Config {
+    /// Backups of the config file, newest first.
+    pub fn backups() -> Result<Vec<ConfigBackup>> {
+        Ok(backups(&get_config_file_path()?))
+    }
+
+    /// Make backup `number` (1 = newest) the config file again. The file it
+    /// replaces becomes the newest backup, so a restore can be undone.
+    pub fn restore_backup(number: usize) -> Result<Self> {
+        let path = backup_path(&get_config_file_path()?, number);
+        if !path.is_file() {
+            return Err(ConfigError::NoBackup(number));
+        }
+        let mut config = Self::read_from(&path)?;
+        for error in config.check(true) {
+            tracing::warn!("Invalid value {} in backup; using a corrected value", error);
+        }
+        config.save()?;
+        tracing::info!("Restored config from {}", path.display());
+        Ok(config)
+    }
+
+    /// The config to use when the file at `path` can't be parsed: the newest
+    /// backup that parses, or the defaults. The broken file is kept as
+    /// `config.toml.corrupt-<unix time>`.
+    pub(crate) fn recover(path: &Path, error: ConfigError) -> Result<Self> {
+        let corrupt = sibling(path, &format!("corrupt-{}", unix_now()));
+        tracing::error!(
+            "Config file {} is unreadable ({}); moving it to {}",
+            path.display(),
+            error,
+            corrupt.display()
+        );
+        fs::rename(path, &corrupt)?;
+
+        for backup in backups(path) {
+            match fs::read_to_string(&backup.path)
+                .map_err(ConfigError::from)
+                .and_then(|contents| Ok((Self::from_toml(&contents)?, contents)))
+            {
+                Ok((config, contents)) => {
+                    tracing::error!("Config restored from {}", backup.path.display());
+                    write_atomically(path, &contents)?;
+                    return Ok(config);
+                }
+                Err(e) => {
+                    tracing::warn!("Config backup {} is unusable: {}", backup.path.display(), e)
+                }
+            }
+        }
+
+        tracing::error!("No usable config backup; using the default config");
+        let config = Config::default();
+        config.save()?;
+        Ok(config)
+    }
+}
+
+/// Whether reading a config failed because of what the file holds rather
+/// than being unable to open it.
+pub(crate) fn is_corrupt(error: &ConfigError) -> bool {
+    match error {
+        ConfigError::Parse(_) => true,
+        ConfigError::Read(e) => e.kind() == io::ErrorKind::InvalidData,
+        _ => false,
+    }
+}
+
+/// Replace the file at `path` with `contents`, first keeping the current
+/// file as the newest backup unless it already holds `contents`.
+pub(crate) fn save(path: &Path, contents: &str) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    match fs::read(path) {
+        Ok(current) if current != contents.as_bytes() => rotate(path)?,
+        Ok(_) => {}
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e),
+    }
+    write_atomically(path, contents)
+}
+
+/// Shift backups up one number, dropping the oldest, and keep the file at
+/// `path` as backup 1.
+fn rotate(path: &Path) -> io::Result<()> {
+    for number in (1..CONFIG_BACKUPS).rev() {
+        let from = backup_path(path, number);
+        if from.exists() {
+            fs::rename(from, backup_path(path, number + 1))?;
+        }
+    }
+    let newest = backup_path(path, 1);
+    fs::copy(path, &newest)?;
+    // Keep the modification time, i.e. when this version was saved
+    let saved = fs::metadata(path)?.modified()?;
+    fs::File::options()
+        .write(true)
+        .open(&newest)?
+        .set_modified(saved)
+}
+
+fn write_atomically(path: &Path, contents: &str) -> io::Result<()> {
+    let tmp = sibling(path, "tmp");
+    let mut file = fs::File::create(&tmp)?;
+    file.write_all(contents.as_bytes())?;
+    file.sync_all()?;
+    drop(file);
+    fs::rename(&tmp, path)
+}
+
+fn backups(path: &Path) -> Vec<ConfigBackup> {
+    (1..=CONFIG_BACKUPS)
+        .map(|number| (number, backup_path(path, number)))
+        .filter(|(_, path)| path.is_file())
+        .map(|(number, path)| ConfigBackup {
+            number,
+            saved_at: fs::metadata(&path)
+                .and_then(|m| m.modified())
+                .ok()
+                .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                .map(|d| d.as_secs() as i64),
+            path,
+        })
+        .collect()
+}
+
+fn backup_path(path: &Path, number: usize) -> PathBuf {
+    sibling(path, &format!("bak.{}", number))
+}
+
+/// `path` with `.suffix` appended to its file name.
+fn sibling(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.file_name().unwrap_or_default().to_os_string();
+    name.push(".");
+    name.push(suffix);
+    path.with_file_name(name)
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,387 @@
+//! Background job settings and schedules.
+//!
+//! `[jobs]` turns the scheduler on or off and holds one optional
+//! `[jobs.entries.<name>]` table per built-in job. A job without an entry
+//! runs on its default schedule. Schedules are either an interval,
+//! `every 30s` (units `s`, `m`, `h`, `d`), or a five-field cron
+//! expression, `minute hour day-of-month month day-of-week`, in local
+//! time: `0 4 * * *` runs daily at 04:00, `*/15 9-17 * * 1-5` every
+//! quarter hour during office hours.
+
+use chrono::{DateTime, Datelike, Local, NaiveDate, NaiveTime, TimeZone, Timelike};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Applies file changes the auto-index watcher saw to the search index.
+pub const REINDEX_CHANGED_FILES: &str = "reindex-changed-files";
+/// Deletes cached embeddings older than `embedding_cache_max_age_days`.
+pub const EMBEDDING_CACHE_EVICT: &str = "embedding-cache-evict";
+/// Rotates the file access audit log once it is past its size limit.
+pub const AUDIT_ROTATE: &str = "audit-rotate";
+/// Writes metrics to `metrics.export_dir` while `metrics.export_to_file` is on.
+pub const METRICS_EXPORT: &str = "metrics-export";
+
+/// Every built-in job.
+pub const JOB_NAMES: [&str; 4] = [
+    REINDEX_CHANGED_FILES,
+    EMBEDDING_CACHE_EVICT,
+    AUDIT_ROTATE,
+    METRICS_EXPORT,
+];
+
+/// Background job scheduler configuration
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobsConfig {
+    /// Run jobs on their schedules (read at startup)
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Cached embeddings older than this are deleted by embedding-cache-evict
+    #[serde(default = "default_embedding_cache_max_age_days")]
+    pub embedding_cache_max_age_days: u32,
+
+    /// Settings by job name; jobs without an entry use their defaults
+    #[serde(default)]
+    pub entries: BTreeMap<String, JobConfig>,
+}
+
+/// Settings for one job
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JobConfig {
+    /// Run on the schedule; a disabled job only runs when asked to
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+
+    /// Interval or cron expression (default: the job's own schedule)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<String>,
+
+    /// Seconds a run may take before it is reported as timed out
+    #[serde(default = "default_job_timeout_secs")]
+    pub timeout_secs: u64,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_embedding_cache_max_age_days() -> u32 {
+    30
+}
+
+fn default_job_timeout_secs() -> u64 {
+    300
+}
+
+impl Default for JobsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            embedding_cache_max_age_days: default_embedding_cache_max_age_days(),
+            entries: BTreeMap::new(),
+        }
+    }
+}
+
+impl Default for JobConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            schedule: None,
+            timeout_secs: default_job_timeout_secs(),
+        }
+    }
+}
+
+impl JobsConfig {
+    /// Settings for job `name`: its entry, or the defaults.
+    pub fn job(&self, name: &str) -> JobConfig {
+        self.entries.get(name).cloned().unwrap_or_default()
+    }
+}
+
+/// Schedule of a built-in job without one configured. metrics-export has
+/// none; it follows `metrics.export_interval_secs`.
+pub fn default_schedule(name: &str) -> Option<&'static str> {
+    match name {
+        REINDEX_CHANGED_FILES => Some("every 30s"),
+        EMBEDDING_CACHE_EVICT => Some("0 4 * * *"),
+        AUDIT_ROTATE => Some("every 1h"),
+        _ => None,
+    }
+}
+
+/// When a job runs.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Schedule {
+    /// Each time this much has passed since the previous run was due.
+    Every(Duration),
+    /// At the local times matching a cron expression.
+    Cron(CronSpec),
+}
+
+impl Schedule {
+    /// How long after `now` the job is next due; `None` if never again.
+    pub fn next_delay(&self, now: DateTime<Local>) -> Option<Duration> {
+        match self {
+            Schedule::Every(interval) => Some(*interval),
+            Schedule::Cron(cron) => cron
+                .next_after(now)
+                .and_then(|next| (next - now).to_std().ok()),
+        }
+    }
+}
+
+impl FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let spec = spec.trim();
+        match spec.strip_prefix("every ") {
+            Some(interval) => parse_interval(interval.trim()).map(Schedule::Every),
+            None => CronSpec::from_str(spec).map(Schedule::Cron),
+        }
+    }
+}
+
+impl fmt::Display for Schedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Schedule::Every(interval) => write!(f, "every {}s", interval.as_secs()),
+            Schedule::Cron(cron) => f.write_str(&cron.spec),
+        }
+    }
+}
+
+/// `30s`, `15m`, `2h` or `1d`.
+fn parse_interval(interval: &str) -> Result<Duration, String> {
+    let invalid = || format!("\"{}\" is not an interval such as 30s or 15m", interval);
+    let unit_at = interval
+        .find(|c: char| !c.is_ascii_digit())
+        .ok_or_else(invalid)?;
+    let count: u64 = interval[..unit_at].parse().map_err(|_| invalid())?;
+    let unit = match &interval[unit_at..] {
+        "s" => 1,
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    if count == 0 {
+        return Err("the interval must be longer than 0".to_string());
+    }
+    count
+        .checked_mul(unit)
+        .map(Duration::from_secs)
+        .ok_or_else(invalid)
+}
+
+/// A five-field cron expression. Each field is `*`, a number, a range
+/// `a-b`, any of those with a step (`*/15`, `1-9/2`), or a comma-separated
+/// list of them. As in cron, when both day fields are restricted a day
+/// matching either one is due.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CronSpec {
+    spec: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+/// Days searched for the next run: long enough to reach a 29 February.
+const MAX_SEARCH_DAYS: u32 = 8 * 366;
+
+impl CronSpec {
+    /// The first minute strictly after `now` the expression matches.
+    pub fn next_after(&self, now: DateTime<Local>) -> Option<DateTime<Local>> {
+        let now = now.naive_local();
+        let start = now.with_second(0)?.with_nanosecond(0)? + chrono::Duration::minutes(1);
+        let mut day = start.date();
+        for _ in 0..MAX_SEARCH_DAYS {
+            if self.day_matches(day) {
+                let from = if day == start.date() {
+                    start.time()
+                } else {
+                    NaiveTime::MIN
+                };
+                for hour in from.hour()..24 {
+                    if !has(self.hours, hour) {
+                        continue;
+                    }
+                    let first = if hour == from.hour() {
+                        from.minute()
+                    } else {
+                        0
+                    };
+                    for minute in (first..60).filter(|&m| has(self.minutes, m)) {
+                        // Skips times a daylight saving change leaves out
+                        let time = day.and_hms_opt(hour, minute, 0)?;
+                        if let Some(due) = Local.from_local_datetime(&time).earliest() {
+                            return Some(due);
+                        }
+                    }
+                }
+            }
+            day = day.succ_opt()?;
+        }
+        None
+    }
+
+    fn day_matches(&self, day: NaiveDate) -> bool {
+        if !has(self.months, day.month()) {
+            return false;
+        }
+        let by_date = has(self.days, day.day());
+        let by_weekday = has(self.weekdays, day.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (false, false) => by_date || by_weekday,
+            _ => by_date && by_weekday,
+        }
+    }
+}
+
+impl FromStr for CronSpec {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = spec.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "\"{}\" is neither \"every <interval>\" nor a cron expression with five fields",
+                spec
+            ));
+        };
+        let mut weekdays = parse_field(weekday, "day of week", 0, 7)?;
+        // 7 is Sunday too
+        if has(weekdays, 7) {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            spec: fields.join(" "),
+            minutes: parse_field(minute, "minute", 0, 59)?,
+            hours: parse_field(hour, "hour", 0, 23)?,
+            days: parse_field(day, "day of month", 1, 31)?,
+            months: parse_field(month, "month", 1, 12)?,
+            weekdays,
+            any_day: day.starts_with('*'),
+            any_weekday: weekday.starts_with('*'),
+        })
+    }
+}
+
+/// Whether `value` is in the bit set `set`.
+fn has(set: u64, value: u32) -> bool {
+    set & (1 << value) != 0
+}
+
+/// The values one cron field matches, as a bit set.
+fn parse_field(field: &str, name: &str, min: u32, max: u32) -> Result<u64, String> {
+    let invalid = |item: &str| {
+        format!(
+            "{} \"{}\" must be *, a number from {} to {}, a range or a step",
+            name, item, min, max
+        )
+    };
+    let mut set = 0;
+    for item in field.split(',') {
+        let (range, step) = match item.split_once('/') {
+            Some((range, step)) => (range, step.parse().map_err(|_| invalid(item))?),
+            None => (item, 1),
+        };
+        let (first, last) = match range.split_once('-') {
+            _ if range == "*" => (min, max),
+            Some((first, last)) => (
+                first.parse().map_err(|_| invalid(item))?,
+                last.parse().map_err(|_| invalid(item))?,
+            ),
+            None => {
+                let value = range.parse().map_err(|_| invalid(item))?;
+                // `5/10` means from 5 to the end in steps of 10
+                (value, if step > 1 { max } else { value })
+            }
+        };
+        if step == 0 || first < min || last > max || first > last {
+            return Err(invalid(item));
+        }
+        for value in (first..=last).step_by(step as usize) {
+            set |= 1 << value;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn local(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(year, month, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_intervals_parse() {
+        let every = |spec: &str| spec.parse::<Schedule>();
+        assert_eq!(
+            every("every 30s"),
+            Ok(Schedule::Every(Duration::from_secs(30)))
+        );
+        assert_eq!(
+            every(" every 2h "),
+            Ok(Schedule::Every(Duration::from_secs(7200)))
+        );
+        assert_eq!(every("every 1d").unwrap().to_string(), "every 86400s");
+        for bad in ["every 0s", "every 5", "every m", "every 3w", "every -1s"] {
+            assert!(every(bad).is_err(), "{} parsed", bad);
+        }
+    }
+
+    #[test]
+    fn test_cron_finds_the_next_matching_minute() {
+        // Wednesday 10 January 2024, 10:07:30
+        let now = local(2024, 1, 10, 10, 7) + chrono::Duration::seconds(30);
+        let next = |spec: &str| spec.parse::<CronSpec>().unwrap().next_after(now).unwrap();
+
+        assert_eq!(next("* * * * *"), local(2024, 1, 10, 10, 8));
+        assert_eq!(next("*/15 * * * *"), local(2024, 1, 10, 10, 15));
+        assert_eq!(next("0 4 * * *"), local(2024, 1, 11, 4, 0));
+        assert_eq!(next("30 9-17/4 * * *"), local(2024, 1, 10, 13, 30));
+        assert_eq!(next("0 0 * * 0"), local(2024, 1, 14, 0, 0));
+        assert_eq!(next("0 0 * * 7"), local(2024, 1, 14, 0, 0));
+        // Either day field matches once both are restricted
+        assert_eq!(next("0 12 20 * 5"), local(2024, 1, 12, 12, 0));
+        assert_eq!(next("0 0 29 2 *"), local(2024, 2, 29, 0, 0));
+        assert_eq!(
+            "0 0 30 2 *".parse::<CronSpec>().unwrap().next_after(now),
+            None
+        );
+
+        let schedule: Schedule = "0 4 * * *".parse().unwrap();
+        assert_eq!(
+            schedule.next_delay(now),
+            Some(Duration::from_secs(17 * 3600 + 52 * 60 + 30))
+        );
+        assert_eq!(schedule.to_string(), "0 4 * * *");
+    }
+
+    #[test]
+    fn test_bad_cron_expressions_say_which_field_is_wrong() {
+        let error = |spec: &str| spec.parse::<Schedule>().unwrap_err();
+        assert!(error("0 4 * *").contains("five fields"));
+        assert!(error("60 * * * *").starts_with("minute \"60\""));
+        assert!(error("0 24 * * *").starts_with("hour"));
+        assert!(error("0 0 0 * *").starts_with("day of month"));
+        assert!(error("0 0 * 1-13 *").starts_with("month"));
+        assert!(error("0 0 * * mon").starts_with("day of week"));
+        assert!(error("*/0 * * * *").starts_with("minute"));
+        assert!(error("5-1 * * * *").starts_with("minute"));
+    }
+}
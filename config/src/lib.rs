@@ -1,9 +1,25 @@
+use lucastra_core::compat::{self, UnsupportedVersion};
+use lucastra_core::LuCastraError;
 use serde::{Deserialize, Serialize};
-use std::{env, path::PathBuf};
+use std::{collections::BTreeMap, env, path::PathBuf};
 use thiserror::Error;
 
+pub mod backup;
+pub mod jobs;
 pub mod observability;
+pub mod profile;
+pub mod providers;
+pub mod shortcuts;
+pub mod watcher;
+pub use backup::{ConfigBackup, CONFIG_BACKUPS};
+pub use jobs::{JobConfig, JobsConfig, Schedule};
 pub use observability::{MetricsConfig, TracingConfig};
+pub use profile::{IndexProfile, ProfileMatcher};
+pub use providers::{
+    Budget, ModelPrice, ProviderConfig, ProvidersConfig, RateLimits, RequestClass,
+};
+pub use shortcuts::{Chord, ShortcutRegistry};
+pub use watcher::{ConfigEvent, ConfigWatcher};
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -18,12 +34,73 @@ pub enum ConfigError {
 
     #[error("Config directory not found")]
     NoConfigDir,
+
+    #[error("Failed to watch config: {0}")]
+    Watch(#[from] notify::Error),
+
+    #[error("Invalid config: {}", .0.iter().map(|e| e.to_string()).collect::<Vec<_>>().join("; "))]
+    Invalid(Vec<ConfigValidationError>),
+
+    #[error(transparent)]
+    UnsupportedVersion(#[from] UnsupportedVersion),
+
+    #[error("No config backup {0}")]
+    NoBackup(usize),
+
+    #[error("no provider named '{name}' in config (known: {known})")]
+    UnknownProvider { name: String, known: String },
+}
+
+impl From<ConfigError> for LuCastraError {
+    fn from(error: ConfigError) -> Self {
+        LuCastraError::Config(Box::new(error))
+    }
+}
+
+/// A config value outside its allowed range or set of choices.
+#[derive(Debug, Clone, PartialEq, Error)]
+#[error("{field}: {message}")]
+pub struct ConfigValidationError {
+    /// Dotted path of the offending field, e.g. `llm.temperature`
+    pub field: &'static str,
+    pub message: String,
 }
 
+const MODEL_SIZES: [&str; 3] = ["7b", "13b", "70b"];
+const QUANTIZATIONS: [&str; 3] = ["none", "4bit", "8bit"];
+const THEMES: [&str; 3] = ["dark", "light", "auto"];
+/// Smallest window the GUI can be set to.
+pub const MIN_WINDOW_WIDTH: u32 = 320;
+pub const MIN_WINDOW_HEIGHT: u32 = 240;
+/// Range `gui.font_size` is kept in.
+pub const MIN_FONT_SIZE: u16 = 8;
+pub const MAX_FONT_SIZE: u16 = 32;
+/// Range `gui.ui_scale` is kept in.
+pub const MIN_UI_SCALE: f32 = 0.5;
+pub const MAX_UI_SCALE: f32 = 3.0;
+
+/// System prompt template used when none is configured.
+pub const DEFAULT_SYSTEM_PROMPT_TEMPLATE: &str = "assistant";
+
+/// Characters kept of each string in a wire log body.
+pub const DEFAULT_DEBUG_LOG_PROMPT_CHARS: usize = 2000;
+
+/// Search results returned when the caller doesn't ask for a number.
+pub const DEFAULT_MAX_RESULTS: usize = 10;
+
+/// Words left out of the search index unless configured otherwise.
+pub const DEFAULT_STOPWORDS: [&str; 15] = [
+    "the", "a", "an", "and", "or", "is", "in", "at", "to", "for", "of", "on", "with", "by", "from",
+];
+
 pub type Result<T> = std::result::Result<T, ConfigError>;
 
+/// Version of the config file layout, written as its first line
+/// (`schema_version = 1`). Files without one are version 1.
+pub const CONFIG_VERSION: u32 = 1;
+
 /// Main configuration structure for LucAstra
-#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Config {
     #[serde(default)]
     pub llm: LlmConfig,
@@ -43,14 +120,27 @@ pub struct Config {
     #[serde(default)]
     pub advanced: AdvancedConfig,
 
+    #[serde(default)]
+    pub tools: ToolsConfig,
+
     #[serde(default)]
     pub tracing: TracingConfig,
 
     #[serde(default)]
     pub metrics: MetricsConfig,
+
+    /// Named LLM providers for the CLI and embedding pipeline.
+    #[serde(default)]
+    pub providers: ProvidersConfig,
+
+    #[serde(default)]
+    pub rpc: RpcConfig,
+
+    #[serde(default)]
+    pub jobs: JobsConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct LlmConfig {
     /// LLM server URL (default: http://localhost:8000)
     #[serde(default = "default_llm_url")]
@@ -87,9 +177,49 @@ pub struct LlmConfig {
     /// Temperature (0.0-2.0)
     #[serde(default = "default_temperature")]
     pub temperature: f32,
+
+    /// llamafile or llama-server executable for `auto_start`; searched for
+    /// in the models directory and PATH when unset
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub server_binary: Option<PathBuf>,
+
+    /// Seconds an auto-started server may take to load its model
+    #[serde(default = "default_startup_timeout_secs")]
+    pub startup_timeout_secs: u64,
+
+    /// Seconds between server health checks (0 disables monitoring)
+    #[serde(default = "default_health_check_interval_secs")]
+    pub health_check_interval_secs: u64,
+
+    /// Most messages of a conversation kept as context for its next query
+    #[serde(default = "default_context_messages")]
+    pub context_messages: usize,
+
+    /// Answer with placeholders from the mock provider while the server is
+    /// unreachable, instead of failing queries
+    #[serde(default = "default_true")]
+    pub offline_placeholders: bool,
+
+    /// Prompt template, from the built-in ones or `data/prompts`, that
+    /// becomes the system prompt
+    #[serde(default = "default_system_prompt_template")]
+    pub system_prompt_template: String,
+
+    /// Write provider requests and responses, secrets masked, to
+    /// `logs/llm_wire.jsonl` for debugging
+    #[serde(default = "default_false")]
+    pub debug_log_requests: bool,
+
+    /// Characters of each prompt or other body string kept in the wire log
+    #[serde(default = "default_debug_log_prompt_chars")]
+    pub debug_log_prompt_chars: usize,
+
+    /// Size in MB at which the wire log is rotated to `llm_wire.jsonl.1`
+    #[serde(default = "default_debug_log_max_size_mb")]
+    pub debug_log_max_size_mb: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct StorageConfig {
     /// Root data directory (default: ~/.lucastra/data)
     #[serde(default = "default_data_dir")]
@@ -108,7 +238,7 @@ pub struct StorageConfig {
     pub auto_index: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SearchConfig {
     /// Use vector search (requires LanceDB)
     #[serde(default = "default_false")]
@@ -129,9 +259,89 @@ pub struct SearchConfig {
     /// Embedding model name
     #[serde(default = "default_embedding_model")]
     pub embedding_model: String,
+
+    /// Normalized score (0.0-1.0) a document needs to be used as RAG context
+    #[serde(default = "default_min_rag_score")]
+    pub min_rag_score: f32,
+
+    /// Estimated tokens of RAG context allowed in one prompt
+    #[serde(default = "default_rag_token_budget")]
+    pub rag_token_budget: usize,
+
+    /// Match word forms by their stem ("indexing" finds "index")
+    #[serde(default = "default_true")]
+    pub stemming: bool,
+
+    /// Words left out of the search index
+    #[serde(default = "default_stopwords")]
+    pub stopwords: Vec<String>,
+
+    /// Tokenize as versions without stemming and Unicode support did
+    #[serde(default = "default_false")]
+    pub legacy_tokenizer: bool,
+
+    /// Add pages opened in the browser to the index as `web://<url>`
+    #[serde(default = "default_false")]
+    pub index_browsed_pages: bool,
+
+    /// Domains whose pages are never indexed, subdomains included
+    #[serde(default)]
+    pub blocked_domains: Vec<String>,
+
+    /// Which files below particular directories get indexed
+    #[serde(default)]
+    pub profiles: Vec<IndexProfile>,
+}
+
+impl SearchConfig {
+    /// Whether pages from `host` are kept out of the index: it is one of
+    /// `blocked_domains` or a subdomain of one.
+    pub fn blocks_domain(&self, host: &str) -> bool {
+        self.blocked_domains
+            .iter()
+            .any(|domain| domain_matches(host, domain))
+    }
+
+    /// The index profile called `name`, its root with ~ expanded.
+    pub fn profile(&self, name: &str) -> Option<IndexProfile> {
+        self.profiles
+            .iter()
+            .find(|profile| profile.name == name)
+            .map(|profile| IndexProfile {
+                root: expand_allowed_dir(&profile.root.to_string_lossy()),
+                ..profile.clone()
+            })
+    }
+
+    /// Compiled index profiles for the indexer, roots with ~ expanded.
+    /// Profiles with invalid patterns are left out.
+    pub fn profile_matchers(&self) -> Vec<ProfileMatcher> {
+        self.profiles
+            .iter()
+            .filter_map(|profile| self.profile(&profile.name))
+            .filter_map(|profile| {
+                profile
+                    .matcher()
+                    .map_err(|e| tracing::warn!("Ignoring {}", e))
+                    .ok()
+            })
+            .collect()
+    }
+}
+
+/// Whether `host` is `domain` or one of its subdomains, ignoring case and
+/// trailing dots.
+pub fn domain_matches(host: &str, domain: &str) -> bool {
+    let host = host.trim_end_matches('.').to_ascii_lowercase();
+    let domain = domain.trim().trim_matches('.').to_ascii_lowercase();
+    !domain.is_empty()
+        && (host == domain
+            || host
+                .strip_suffix(domain.as_str())
+                .is_some_and(|sub| sub.ends_with('.')))
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct GuiConfig {
     /// Window width
     #[serde(default = "default_window_width")]
@@ -141,14 +351,36 @@ pub struct GuiConfig {
     #[serde(default = "default_window_height")]
     pub window_height: u32,
 
+    /// Window position on the desktop, saved when the window moves; left to
+    /// the window system when unset. Negative on monitors left of or above
+    /// the primary one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_x: Option<i32>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub window_y: Option<i32>,
+
+    /// Whether the window was maximized when last closed
+    #[serde(default = "default_false")]
+    pub window_maximized: bool,
+
     /// Theme: "dark", "light", "auto"
     #[serde(default = "default_theme")]
     pub theme: String,
 
-    /// Font size
+    /// Size of body text; headings and captions are sized relative to it
     #[serde(default = "default_font_size")]
     pub font_size: u16,
 
+    /// Scale of the whole interface, padding and spacing included
+    #[serde(default = "default_ui_scale")]
+    pub ui_scale: f32,
+
+    /// Language of the interface, e.g. "en" or "de"; "auto" follows the OS
+    /// locale. Languages without a translation fall back to English
+    #[serde(default = "default_language")]
+    pub language: String,
+
     /// Enable animations
     #[serde(default = "default_true")]
     pub animations: bool,
@@ -156,9 +388,14 @@ pub struct GuiConfig {
     /// Message history limit
     #[serde(default = "default_message_history")]
     pub message_history_limit: usize,
+
+    /// Keyboard shortcut chords by action, e.g. `focus_search = "ctrl+k"`;
+    /// actions not listed keep their defaults
+    #[serde(default)]
+    pub shortcuts: BTreeMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct SecurityConfig {
     /// Enable RBAC permission system
     #[serde(default = "default_true")]
@@ -199,9 +436,61 @@ pub struct SecurityConfig {
     /// Allowed host directories (expand ~ and env vars)
     #[serde(default = "default_allowed_dirs")]
     pub allowed_host_dirs: Vec<String>,
+
+    /// Ask the user before host write, move, copy or delete operations run
+    #[serde(default = "default_true")]
+    pub require_write_approval: bool,
+
+    /// Seconds a pending approval stays valid
+    #[serde(default = "default_approval_ttl_secs")]
+    pub approval_ttl_secs: u64,
+
+    /// Size in MB at which the file access audit log is rotated
+    #[serde(default = "default_audit_log_max_mb")]
+    pub audit_log_max_mb: u64,
+
+    /// Role applied to tool calls when RBAC is enabled: "reader", "writer" or "admin".
+    /// Defaults to "writer"; only "admin" may run the install tool.
+    #[serde(default = "default_role")]
+    pub role: String,
+
+    /// Per-role overrides of the built-in permissions, keyed by role name
+    #[serde(default)]
+    pub role_permissions: BTreeMap<String, RolePermissions>,
+
+    /// Package managers the install tool may run, by executable name
+    #[serde(default = "default_allowed_installers")]
+    pub allowed_installers: Vec<String>,
+
+    /// Allow tools to make network requests at all
+    #[serde(default = "default_true")]
+    pub allow_network: bool,
+
+    /// Domains the fetch tool may request, subdomains included; "*" allows
+    /// any and an empty list none
+    #[serde(default)]
+    pub allowed_domains: Vec<String>,
+
+    /// Hosts the install tool may download installers from without a
+    /// SHA-256 checksum, subdomains included
+    #[serde(default)]
+    pub allowed_download_hosts: Vec<String>,
+}
+
+/// Tools and file operations a role may use.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RolePermissions {
+    /// Tool names: "search", "read", "write", "delete", "install", "host_file_access",
+    /// "calculate", "fetch"
+    #[serde(default)]
+    pub tools: Vec<String>,
+
+    /// File operations: "read", "write", "move", "copy", "delete", "list"
+    #[serde(default)]
+    pub operations: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct AdvancedConfig {
     /// Enable telemetry (opt-in)
     #[serde(default = "default_false")]
@@ -224,11 +513,62 @@ pub struct AdvancedConfig {
     pub worker_threads: usize,
 }
 
+/// Local JSON-RPC API (needs the `rpc` feature of lucastra-app)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcConfig {
+    /// Accept connections on the socket
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Unix socket to listen on (default: lucastra.sock in the config dir)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub socket_path: Option<PathBuf>,
+
+    /// Requests one connection may send before it is closed
+    #[serde(default = "default_rpc_max_requests")]
+    pub max_requests_per_connection: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolsConfig {
+    /// Most bytes of a file one read tool call returns
+    #[serde(default = "default_max_read_bytes")]
+    pub max_read_bytes: usize,
+}
+
 // Default value functions
+fn default_rpc_max_requests() -> usize {
+    1000
+}
+
 fn default_llm_url() -> String {
     "http://localhost:8000".to_string()
 }
 
+fn default_health_check_interval_secs() -> u64 {
+    30
+}
+
+fn default_context_messages() -> usize {
+    20
+}
+
+fn default_system_prompt_template() -> String {
+    DEFAULT_SYSTEM_PROMPT_TEMPLATE.to_string()
+}
+
+fn default_startup_timeout_secs() -> u64 {
+    120
+}
+
+fn default_debug_log_prompt_chars() -> usize {
+    DEFAULT_DEBUG_LOG_PROMPT_CHARS
+}
+
+fn default_debug_log_max_size_mb() -> u32 {
+    10
+}
+
 fn default_model_size() -> String {
     "7b".to_string()
 }
@@ -278,7 +618,19 @@ fn default_bm25_b() -> f32 {
 }
 
 fn default_max_results() -> usize {
-    10
+    DEFAULT_MAX_RESULTS
+}
+
+fn default_min_rag_score() -> f32 {
+    0.2
+}
+
+fn default_rag_token_budget() -> usize {
+    768
+}
+
+fn default_stopwords() -> Vec<String> {
+    DEFAULT_STOPWORDS.iter().map(|w| w.to_string()).collect()
 }
 
 fn default_embedding_model() -> String {
@@ -301,6 +653,23 @@ fn default_font_size() -> u16 {
     16
 }
 
+fn default_ui_scale() -> f32 {
+    1.0
+}
+
+/// Whether `language` looks like `en`, `de-AT` or `pt_BR`.
+fn is_language_tag(language: &str) -> bool {
+    let primary = language.split(['-', '_']).next().unwrap_or_default();
+    (2..=8).contains(&primary.len())
+        && language
+            .split(['-', '_'])
+            .all(|part| !part.is_empty() && part.chars().all(|c| c.is_ascii_alphanumeric()))
+}
+
+fn default_language() -> String {
+    "auto".to_string()
+}
+
 fn default_message_history() -> usize {
     1000
 }
@@ -328,7 +697,8 @@ fn default_allowed_dirs() -> Vec<String> {
     }
 }
 
-fn expand_allowed_dir(path: &str) -> PathBuf {
+/// `path` with a leading `~` replaced by the home directory.
+pub fn expand_allowed_dir(path: &str) -> PathBuf {
     if path == "~" {
         if let Some(home) = dirs::home_dir() {
             return home;
@@ -344,6 +714,31 @@ fn expand_allowed_dir(path: &str) -> PathBuf {
     PathBuf::from(path)
 }
 
+fn default_approval_ttl_secs() -> u64 {
+    300
+}
+
+fn default_audit_log_max_mb() -> u64 {
+    10
+}
+
+fn default_max_read_bytes() -> usize {
+    64 * 1024
+}
+
+fn default_role() -> String {
+    "writer".to_string()
+}
+
+fn default_allowed_installers() -> Vec<String> {
+    [
+        "winget", "choco", "scoop", "apt", "apt-get", "dnf", "brew", "pip", "pip3", "cargo", "npm",
+    ]
+    .iter()
+    .map(|installer| installer.to_string())
+    .collect()
+}
+
 fn default_true() -> bool {
     true
 }
@@ -364,6 +759,15 @@ impl Default for LlmConfig {
             streaming: true,
             max_tokens: default_max_tokens(),
             temperature: default_temperature(),
+            server_binary: None,
+            startup_timeout_secs: default_startup_timeout_secs(),
+            health_check_interval_secs: default_health_check_interval_secs(),
+            context_messages: default_context_messages(),
+            offline_placeholders: true,
+            system_prompt_template: default_system_prompt_template(),
+            debug_log_requests: false,
+            debug_log_prompt_chars: default_debug_log_prompt_chars(),
+            debug_log_max_size_mb: default_debug_log_max_size_mb(),
         }
     }
 }
@@ -387,6 +791,14 @@ impl Default for SearchConfig {
             bm25_b: default_bm25_b(),
             max_results: default_max_results(),
             embedding_model: default_embedding_model(),
+            min_rag_score: default_min_rag_score(),
+            rag_token_budget: default_rag_token_budget(),
+            stemming: true,
+            stopwords: default_stopwords(),
+            legacy_tokenizer: false,
+            index_browsed_pages: false,
+            blocked_domains: Vec::new(),
+            profiles: Vec::new(),
         }
     }
 }
@@ -396,14 +808,48 @@ impl Default for GuiConfig {
         Self {
             window_width: default_window_width(),
             window_height: default_window_height(),
+            window_x: None,
+            window_y: None,
+            window_maximized: false,
             theme: default_theme(),
             font_size: default_font_size(),
+            ui_scale: default_ui_scale(),
+            language: default_language(),
             animations: true,
             message_history_limit: default_message_history(),
+            shortcuts: BTreeMap::new(),
         }
     }
 }
 
+impl Default for ToolsConfig {
+    fn default() -> Self {
+        Self {
+            max_read_bytes: default_max_read_bytes(),
+        }
+    }
+}
+
+impl Default for RpcConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            socket_path: None,
+            max_requests_per_connection: default_rpc_max_requests(),
+        }
+    }
+}
+
+impl RpcConfig {
+    /// The socket to listen on, `socket_path` or `lucastra.sock` in the
+    /// config dir.
+    pub fn resolved_socket_path(&self) -> PathBuf {
+        self.socket_path
+            .clone()
+            .unwrap_or_else(|| resolve_config_dir().join("lucastra.sock"))
+    }
+}
+
 impl Default for SecurityConfig {
     fn default() -> Self {
         Self {
@@ -417,6 +863,15 @@ impl Default for SecurityConfig {
             allow_usb: false,
             auto_sync_documents: false,
             allowed_host_dirs: default_allowed_dirs(),
+            require_write_approval: true,
+            approval_ttl_secs: default_approval_ttl_secs(),
+            audit_log_max_mb: default_audit_log_max_mb(),
+            role: default_role(),
+            role_permissions: BTreeMap::new(),
+            allowed_installers: default_allowed_installers(),
+            allow_network: true,
+            allowed_domains: vec![],
+            allowed_download_hosts: vec![],
         }
     }
 }
@@ -429,6 +884,16 @@ impl SecurityConfig {
             .map(|d| expand_allowed_dir(d))
             .collect()
     }
+
+    /// Whether tools may request pages from `host`: network access is on and
+    /// the host is covered by `allowed_domains`.
+    pub fn allows_domain(&self, host: &str) -> bool {
+        self.allow_network
+            && self
+                .allowed_domains
+                .iter()
+                .any(|domain| domain.trim() == "*" || domain_matches(host, domain))
+    }
 }
 
 impl Default for AdvancedConfig {
@@ -444,15 +909,25 @@ impl Default for AdvancedConfig {
 }
 
 impl Config {
-    /// Load configuration from file, or create default if not found
+    /// Load configuration from file, or create default if not found.
+    ///
+    /// Out-of-range values are logged and replaced by the nearest valid
+    /// value (or the default, for unknown choices). A file that doesn't
+    /// parse is replaced by its newest usable backup, or the defaults.
     pub fn load() -> Result<Self> {
         ensure_base_dirs()?;
         let config_path = get_config_file_path()?;
 
         if config_path.exists() {
             tracing::info!("Loading config from: {}", config_path.display());
-            let contents = std::fs::read_to_string(&config_path)?;
-            let config: Config = toml::from_str(&contents)?;
+            let mut config = match Self::read_from(&config_path) {
+                Ok(config) => config,
+                Err(e) if backup::is_corrupt(&e) => Self::recover(&config_path, e)?,
+                Err(e) => return Err(e),
+            };
+            for error in config.check(true) {
+                tracing::warn!("Invalid config value {}; using a corrected value", error);
+            }
             Ok(config)
         } else {
             tracing::info!(
@@ -465,17 +940,46 @@ impl Config {
         }
     }
 
-    /// Save configuration to file
-    pub fn save(&self) -> Result<()> {
-        let config_path = get_config_file_path()?;
+    /// Parse the config file at `path` as is, without checking values.
+    pub fn read_from(path: &std::path::Path) -> Result<Self> {
+        Self::from_toml(&std::fs::read_to_string(path)?)
+    }
 
-        // Ensure parent directory exists
-        if let Some(parent) = config_path.parent() {
-            std::fs::create_dir_all(parent)?;
+    /// Parse config file contents. Files from a newer LucAstra fail with
+    /// [`ConfigError::UnsupportedVersion`].
+    pub fn from_toml(contents: &str) -> Result<Self> {
+        let mut table: toml::Table = toml::from_str(contents)?;
+        if let Some(version) = table.remove(compat::VERSION_FIELD) {
+            let found = version
+                .as_integer()
+                .and_then(|v| u64::try_from(v).ok())
+                .ok_or_else(|| {
+                    <toml::de::Error as serde::de::Error>::custom(format!(
+                        "{} must be a positive number",
+                        compat::VERSION_FIELD
+                    ))
+                })?;
+            compat::check_version("config", found, CONFIG_VERSION)?;
         }
+        Ok(table.try_into()?)
+    }
+
+    /// Config file contents, headed by the schema version.
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(format!(
+            "{} = {}\n\n{}",
+            compat::VERSION_FIELD,
+            CONFIG_VERSION,
+            toml::to_string_pretty(self)?
+        ))
+    }
 
-        let contents = toml::to_string_pretty(self)?;
-        std::fs::write(&config_path, contents)?;
+    /// Save configuration to file, keeping the previous file as a backup
+    /// (see [`backup`]). Invalid configs are rejected.
+    pub fn save(&self) -> Result<()> {
+        self.validate().map_err(ConfigError::Invalid)?;
+        let config_path = get_config_file_path()?;
+        backup::save(&config_path, &self.to_toml()?)?;
         tracing::info!("Config saved to: {}", config_path.display());
         Ok(())
     }
@@ -485,6 +989,312 @@ impl Config {
         *self = Self::load()?;
         Ok(())
     }
+
+    /// Check ranges and choices, reporting every problem found.
+    pub fn validate(&self) -> std::result::Result<(), Vec<ConfigValidationError>> {
+        let errors = self.clone().check(false);
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(errors)
+        }
+    }
+
+    /// Find invalid values, replacing them when `repair` is set.
+    fn check(&mut self, repair: bool) -> Vec<ConfigValidationError> {
+        let mut errors = Vec::new();
+        let mut invalid = |field, message: String| {
+            errors.push(ConfigValidationError { field, message });
+            repair
+        };
+
+        let temperature = self.llm.temperature;
+        if !(0.0..=2.0).contains(&temperature)
+            && invalid(
+                "llm.temperature",
+                format!("must be between 0.0 and 2.0, got {}", temperature),
+            )
+        {
+            self.llm.temperature = if temperature.is_nan() {
+                default_temperature()
+            } else {
+                temperature.clamp(0.0, 2.0)
+            };
+        }
+        if self.llm.max_tokens == 0
+            && invalid("llm.max_tokens", "must be greater than 0".to_string())
+        {
+            self.llm.max_tokens = default_max_tokens();
+        }
+        if self.llm.startup_timeout_secs == 0
+            && invalid(
+                "llm.startup_timeout_secs",
+                "must be greater than 0".to_string(),
+            )
+        {
+            self.llm.startup_timeout_secs = default_startup_timeout_secs();
+        }
+        if self.llm.context_messages == 0
+            && invalid("llm.context_messages", "must be greater than 0".to_string())
+        {
+            self.llm.context_messages = default_context_messages();
+        }
+        if self.llm.debug_log_prompt_chars == 0
+            && invalid(
+                "llm.debug_log_prompt_chars",
+                "must be greater than 0".to_string(),
+            )
+        {
+            self.llm.debug_log_prompt_chars = default_debug_log_prompt_chars();
+        }
+        if self.llm.debug_log_max_size_mb == 0
+            && invalid(
+                "llm.debug_log_max_size_mb",
+                "must be greater than 0".to_string(),
+            )
+        {
+            self.llm.debug_log_max_size_mb = default_debug_log_max_size_mb();
+        }
+        let template = &self.llm.system_prompt_template;
+        if (template.is_empty() || template.contains(['/', '\\']))
+            && invalid(
+                "llm.system_prompt_template",
+                format!("must be a template name, got \"{}\"", template),
+            )
+        {
+            self.llm.system_prompt_template = default_system_prompt_template();
+        }
+        if !MODEL_SIZES.contains(&self.llm.model_size.as_str())
+            && invalid("llm.model_size", one_of(&MODEL_SIZES, &self.llm.model_size))
+        {
+            self.llm.model_size = default_model_size();
+        }
+        if !QUANTIZATIONS.contains(&self.llm.quantization.as_str())
+            && invalid(
+                "llm.quantization",
+                one_of(&QUANTIZATIONS, &self.llm.quantization),
+            )
+        {
+            self.llm.quantization = default_quantization();
+        }
+        if !THEMES.contains(&self.gui.theme.as_str())
+            && invalid("gui.theme", one_of(&THEMES, &self.gui.theme))
+        {
+            self.gui.theme = default_theme();
+        }
+        if self.gui.window_width < MIN_WINDOW_WIDTH
+            && invalid(
+                "gui.window_width",
+                format!(
+                    "must be at least {}, got {}",
+                    MIN_WINDOW_WIDTH, self.gui.window_width
+                ),
+            )
+        {
+            self.gui.window_width = MIN_WINDOW_WIDTH;
+        }
+        if self.gui.window_height < MIN_WINDOW_HEIGHT
+            && invalid(
+                "gui.window_height",
+                format!(
+                    "must be at least {}, got {}",
+                    MIN_WINDOW_HEIGHT, self.gui.window_height
+                ),
+            )
+        {
+            self.gui.window_height = MIN_WINDOW_HEIGHT;
+        }
+        let font_size = self.gui.font_size;
+        if !(MIN_FONT_SIZE..=MAX_FONT_SIZE).contains(&font_size)
+            && invalid(
+                "gui.font_size",
+                format!(
+                    "must be between {} and {}, got {}",
+                    MIN_FONT_SIZE, MAX_FONT_SIZE, font_size
+                ),
+            )
+        {
+            self.gui.font_size = font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE);
+        }
+        let ui_scale = self.gui.ui_scale;
+        if !(MIN_UI_SCALE..=MAX_UI_SCALE).contains(&ui_scale)
+            && invalid(
+                "gui.ui_scale",
+                format!(
+                    "must be between {} and {}, got {}",
+                    MIN_UI_SCALE, MAX_UI_SCALE, ui_scale
+                ),
+            )
+        {
+            self.gui.ui_scale = if ui_scale.is_nan() {
+                default_ui_scale()
+            } else {
+                ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE)
+            };
+        }
+        if !is_language_tag(&self.gui.language)
+            && invalid(
+                "gui.language",
+                format!(
+                    "must be \"auto\" or a language tag such as \"en\" or \"de-AT\", got {:?}",
+                    self.gui.language
+                ),
+            )
+        {
+            self.gui.language = default_language();
+        }
+        if self.gui.message_history_limit == 0
+            && invalid(
+                "gui.message_history_limit",
+                "must be greater than 0".to_string(),
+            )
+        {
+            self.gui.message_history_limit = default_message_history();
+        }
+        if let Err(problems) = ShortcutRegistry::from_overrides(&self.gui.shortcuts) {
+            let repaired = problems
+                .into_iter()
+                .fold(false, |_, problem| invalid("gui.shortcuts", problem));
+            if repaired {
+                self.gui.shortcuts.clear();
+            }
+        }
+        if !is_positive(self.search.bm25_k1)
+            && invalid(
+                "search.bm25_k1",
+                format!("must be greater than 0, got {}", self.search.bm25_k1),
+            )
+        {
+            self.search.bm25_k1 = default_bm25_k1();
+        }
+        if !is_positive(self.search.bm25_b)
+            && invalid(
+                "search.bm25_b",
+                format!("must be greater than 0, got {}", self.search.bm25_b),
+            )
+        {
+            self.search.bm25_b = default_bm25_b();
+        }
+        if self.search.max_results == 0
+            && invalid("search.max_results", "must be greater than 0".to_string())
+        {
+            self.search.max_results = default_max_results();
+        }
+        let min_rag_score = self.search.min_rag_score;
+        if !(0.0..=1.0).contains(&min_rag_score)
+            && invalid(
+                "search.min_rag_score",
+                format!("must be between 0.0 and 1.0, got {}", min_rag_score),
+            )
+        {
+            self.search.min_rag_score = if min_rag_score.is_nan() {
+                default_min_rag_score()
+            } else {
+                min_rag_score.clamp(0.0, 1.0)
+            };
+        }
+        if self.search.rag_token_budget == 0
+            && invalid(
+                "search.rag_token_budget",
+                "must be greater than 0".to_string(),
+            )
+        {
+            self.search.rag_token_budget = default_rag_token_budget();
+        }
+        let mut names = std::collections::HashSet::new();
+        self.search.profiles.retain(|profile| {
+            let problem = if profile.name.trim().is_empty() {
+                Some("names must not be empty".to_string())
+            } else if !names.insert(profile.name.clone()) {
+                Some(format!(
+                    "\"{}\" is used by more than one profile",
+                    profile.name
+                ))
+            } else if profile.root.as_os_str().is_empty() {
+                Some(format!("{} needs a root directory", profile.name))
+            } else if profile.max_file_size == 0 {
+                Some(format!(
+                    "{}: max_file_size must be greater than 0",
+                    profile.name
+                ))
+            } else {
+                profile.matcher().err().map(|e| e.to_string())
+            };
+            // Repairing drops the profile
+            problem.is_none_or(|message| !invalid("search.profiles", message))
+        });
+        if self.tools.max_read_bytes == 0
+            && invalid("tools.max_read_bytes", "must be greater than 0".to_string())
+        {
+            self.tools.max_read_bytes = default_max_read_bytes();
+        }
+        if self.rpc.max_requests_per_connection == 0
+            && invalid(
+                "rpc.max_requests_per_connection",
+                "must be greater than 0".to_string(),
+            )
+        {
+            self.rpc.max_requests_per_connection = default_rpc_max_requests();
+        }
+        if self.jobs.embedding_cache_max_age_days == 0
+            && invalid(
+                "jobs.embedding_cache_max_age_days",
+                "must be greater than 0".to_string(),
+            )
+        {
+            self.jobs.embedding_cache_max_age_days =
+                JobsConfig::default().embedding_cache_max_age_days;
+        }
+        let now = chrono::Local::now();
+        self.jobs.entries.retain(|name, job| {
+            let problem = if !jobs::JOB_NAMES.contains(&name.as_str()) {
+                Some(format!(
+                    "unknown job \"{}\"; jobs are {}",
+                    name,
+                    jobs::JOB_NAMES.join(", ")
+                ))
+            } else if job.timeout_secs == 0 {
+                Some(format!("{}: timeout_secs must be greater than 0", name))
+            } else {
+                match job.schedule.as_deref().map(str::parse::<Schedule>) {
+                    Some(Err(e)) => Some(format!("{}: {}", name, e)),
+                    Some(Ok(schedule)) if schedule.next_delay(now).is_none() => {
+                        Some(format!("{}: \"{}\" never comes round", name, schedule))
+                    }
+                    _ => None,
+                }
+            };
+            // Repairing puts the job back on its defaults
+            problem.is_none_or(|message| !invalid("jobs.entries", message))
+        });
+        if !self.providers.entries.contains_key(&self.providers.default)
+            && invalid(
+                "providers.default",
+                format!(
+                    "must name an entry in [providers.entries], got \"{}\"",
+                    self.providers.default
+                ),
+            )
+        {
+            self.providers = match self.providers.entries.keys().next() {
+                Some(first) => ProvidersConfig {
+                    default: first.clone(),
+                    ..self.providers.clone()
+                },
+                None => ProvidersConfig::default(),
+            };
+        }
+        errors
+    }
+}
+
+/// Greater than zero, and not NaN.
+fn is_positive(value: f32) -> bool {
+    value > 0.0
+}
+
+fn one_of(choices: &[&str], value: &str) -> String {
+    format!("must be one of {}, got \"{}\"", choices.join(", "), value)
 }
 
 /// Get the configuration directory (~/.lucastra)
@@ -502,11 +1312,21 @@ pub fn get_data_dir() -> Result<PathBuf> {
     Ok(resolve_config_dir().join("data"))
 }
 
+/// Get the prompt templates directory (~/.lucastra/data/prompts)
+pub fn get_prompts_dir() -> Result<PathBuf> {
+    Ok(get_data_dir()?.join("prompts"))
+}
+
 /// Get the logs directory (~/.lucastra/logs)
 pub fn get_logs_dir() -> Result<PathBuf> {
     Ok(resolve_config_dir().join("logs"))
 }
 
+/// Get the file holding the JSON-RPC API token (~/.lucastra/rpc.token)
+pub fn get_rpc_token_path() -> Result<PathBuf> {
+    Ok(resolve_config_dir().join("rpc.token"))
+}
+
 /// Get the models directory (~/.lucastra/models)
 pub fn get_models_dir() -> Result<PathBuf> {
     Ok(resolve_config_dir().join("models"))
@@ -541,6 +1361,7 @@ mod tests {
         assert_eq!(config.llm.server_url, "http://localhost:8000");
         assert_eq!(config.llm.model_size, "7b");
         assert!(config.llm.auto_start);
+        assert_eq!(config.security.role, "writer");
     }
 
     #[test]
@@ -563,6 +1384,80 @@ mod tests {
         assert_eq!(config.llm.model_size, "13b");
     }
 
+    #[test]
+    fn test_v1_config_still_loads() {
+        let config = Config::from_toml(include_str!("../tests/fixtures/v1/config.toml")).unwrap();
+        assert!(config.validate().is_ok());
+        assert!(!config.llm.use_gpu);
+        assert_eq!(config.security.allowed_host_dirs.len(), 2);
+        assert_eq!(config.advanced.worker_threads, 4);
+        assert_eq!(config.rpc, RpcConfig::default());
+
+        let saved = config.to_toml().unwrap();
+        assert!(saved.starts_with("schema_version = 1\n"));
+        assert_eq!(Config::from_toml(&saved).unwrap(), config);
+    }
+
+    #[test]
+    fn test_window_position_is_optional_in_old_files() {
+        let config = Config::from_toml(include_str!("../tests/fixtures/v1/config.toml")).unwrap();
+        assert_eq!(config.gui.window_width, 1280);
+        assert_eq!((config.gui.window_x, config.gui.window_y), (None, None));
+        assert!(!config.gui.window_maximized);
+        assert!(!config.to_toml().unwrap().contains("window_x"));
+
+        let toml_str = r#"
+            [gui]
+            window_width = 1024
+            window_height = 700
+            window_x = -1600
+            window_y = 120
+            window_maximized = true
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.gui.window_x, Some(-1600));
+        assert_eq!(config.gui.window_y, Some(120));
+        assert!(config.gui.window_maximized);
+        assert_eq!(
+            Config::from_toml(&config.to_toml().unwrap()).unwrap(),
+            config
+        );
+    }
+
+    #[test]
+    fn test_newer_config_is_refused() {
+        let contents = "schema_version = 2\n\n[llm]\nmodel_size = \"7b\"\n";
+        assert!(matches!(
+            Config::from_toml(contents),
+            Err(ConfigError::UnsupportedVersion(e)) if e.found == 2
+        ));
+        assert!(matches!(
+            Config::from_toml("schema_version = \"one\""),
+            Err(ConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_role_permissions_roundtrip() {
+        let mut config = Config::default();
+        config.security.role = "reader".to_string();
+        config.security.role_permissions.insert(
+            "reader".to_string(),
+            RolePermissions {
+                tools: vec!["search".to_string()],
+                operations: vec!["list".to_string()],
+            },
+        );
+
+        let toml_str = toml::to_string(&config).unwrap();
+        let loaded: Config = toml::from_str(&toml_str).unwrap();
+        assert_eq!(loaded.security.role, "reader");
+        assert_eq!(
+            loaded.security.role_permissions["reader"],
+            config.security.role_permissions["reader"]
+        );
+    }
+
     #[test]
     fn test_env_override_config_dir() {
         let _guard = ENV_LOCK.lock().unwrap();
@@ -612,6 +1507,329 @@ mod tests {
         std::fs::remove_dir_all(temp.path().join("models")).ok();
     }
 
+    #[test]
+    fn test_validate_reports_every_violation() {
+        let toml_str = r#"
+            [llm]
+            temperature = 9.5
+            quantization = "2bit"
+
+            [gui]
+            window_width = 100
+        "#;
+        let config: Config = toml::from_str(toml_str).unwrap();
+
+        let errors = config.validate().unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|e| e.field).collect();
+        assert_eq!(
+            fields,
+            vec!["llm.temperature", "llm.quantization", "gui.window_width"]
+        );
+        assert!(errors[1].to_string().contains("none, 4bit, 8bit"));
+        assert!(Config::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_conflicting_shortcuts_are_rejected() {
+        let toml_str = r#"
+            [gui.shortcuts]
+            focus_input = "ctrl+p"
+            open_settings = "Ctrl+P"
+        "#;
+        let mut config: Config = toml::from_str(toml_str).unwrap();
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string(),
+            "gui.shortcuts: \"ctrl+p\" is bound to both focus_input and open_settings"
+        );
+
+        assert_eq!(config.check(true).len(), 1);
+        assert!(config.gui.shortcuts.is_empty());
+    }
+
+    #[test]
+    fn test_load_clamps_and_save_rejects_invalid_values() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        env::set_var("LUCASTRA_CONFIG_HOME", temp.path());
+        std::fs::write(
+            temp.path().join("config.toml"),
+            "[llm]\ntemperature = 9.5\nmodel_size = \"giant\"\nmax_tokens = 0\n\n[gui]\nfont_size = 200\nui_scale = 0.1\nlanguage = \"en US\"\n",
+        )
+        .unwrap();
+
+        let mut config = Config::load().unwrap();
+        assert_eq!(config.llm.temperature, 2.0);
+        assert_eq!(config.llm.model_size, "7b");
+        assert_eq!(config.llm.max_tokens, 2048);
+        assert_eq!(config.gui.font_size, MAX_FONT_SIZE);
+        assert_eq!(config.gui.ui_scale, MIN_UI_SCALE);
+        assert_eq!(config.gui.language, "auto");
+
+        config.gui.theme = "neon".to_string();
+        match config.save() {
+            Err(ConfigError::Invalid(errors)) => assert_eq!(errors[0].field, "gui.theme"),
+            other => panic!("expected invalid config, got {:?}", other),
+        }
+
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_truncated_config_is_recovered_from_backup() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        env::set_var("LUCASTRA_CONFIG_HOME", temp.path());
+
+        let mut config = Config::default();
+        config.llm.server_url = "http://localhost:9100".to_string();
+        config.save().unwrap();
+        config.llm.server_url = "http://localhost:9200".to_string();
+        config.save().unwrap();
+        assert_eq!(Config::backups().unwrap().len(), 1);
+
+        // A crash mid-write used to leave half a file behind
+        let path = temp.path().join("config.toml");
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::write(&path, &contents[..contents.len() / 2]).unwrap();
+
+        let loaded = Config::load().unwrap();
+        assert_eq!(loaded.llm.server_url, "http://localhost:9100");
+        assert_eq!(Config::read_from(&path).unwrap(), loaded);
+        let corrupt = std::fs::read_dir(temp.path())
+            .unwrap()
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_string_lossy()
+                    .starts_with("config.toml.corrupt-")
+            })
+            .count();
+        assert_eq!(corrupt, 1);
+
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_backups_are_rotated_and_restorable() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        env::set_var("LUCASTRA_CONFIG_HOME", temp.path());
+
+        let mut config = Config::default();
+        for port in 9001..=9006 {
+            config.llm.server_url = format!("http://localhost:{}", port);
+            config.save().unwrap();
+        }
+        // Saving unchanged contents keeps the backups as they are
+        config.save().unwrap();
+
+        let backups = Config::backups().unwrap();
+        assert_eq!(backups.len(), CONFIG_BACKUPS);
+        assert_eq!(
+            backups.iter().map(|b| b.number).collect::<Vec<_>>(),
+            vec![1, 2, 3]
+        );
+        assert!(backups.iter().all(|b| b.saved_at.is_some()));
+        assert!(!temp.path().join("config.toml.bak.4").exists());
+        assert!(!temp.path().join("config.toml.tmp").exists());
+
+        let restored = Config::restore_backup(3).unwrap();
+        assert_eq!(restored.llm.server_url, "http://localhost:9003");
+        assert_eq!(
+            Config::load().unwrap().llm.server_url,
+            "http://localhost:9003"
+        );
+        let undo = Config::read_from(&temp.path().join("config.toml.bak.1")).unwrap();
+        assert_eq!(undo.llm.server_url, "http://localhost:9006");
+        assert!(matches!(
+            Config::restore_backup(4),
+            Err(ConfigError::NoBackup(4))
+        ));
+
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_providers_roundtrip_without_resolved_keys() {
+        let _guard = ENV_LOCK.lock().unwrap();
+        let temp = tempfile::tempdir().unwrap();
+        env::set_var("LUCASTRA_CONFIG_HOME", temp.path());
+        env::set_var("LUCASTRA_TEST_OPENAI_KEY", "sk-from-env");
+
+        let mut config = Config::default();
+        config.providers.default = "openai".to_string();
+        config.providers.entries.insert(
+            "openai".to_string(),
+            ProviderConfig {
+                provider: "openai".to_string(),
+                api_key_env: Some("LUCASTRA_TEST_OPENAI_KEY".to_string()),
+                model: Some("gpt-4o-mini".to_string()),
+                ..Default::default()
+            },
+        );
+        config.save().unwrap();
+
+        let saved = std::fs::read_to_string(temp.path().join("config.toml")).unwrap();
+        let loaded = Config::load().unwrap();
+        assert_eq!(loaded.providers, config.providers);
+        assert_eq!(loaded.providers.entries.len(), 2);
+
+        let openai = loaded.providers.resolve(None).unwrap();
+        assert_eq!(
+            openai
+                .resolve_api_key_with(|name| env::var(name).ok(), |_| None)
+                .as_deref(),
+            Some("sk-from-env")
+        );
+        loaded.save().unwrap();
+        let resaved = std::fs::read_to_string(temp.path().join("config.toml")).unwrap();
+        assert_eq!(resaved, saved);
+        assert!(!resaved.contains("sk-from-env"));
+
+        env::remove_var("LUCASTRA_TEST_OPENAI_KEY");
+        env::remove_var("LUCASTRA_CONFIG_HOME");
+    }
+
+    #[test]
+    fn test_providers_default_must_name_an_entry() {
+        let mut config = Config::default();
+        config.providers.default = "missing".to_string();
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors[0].field, "providers.default");
+
+        config.check(true);
+        assert_eq!(config.providers.default, "llamafile");
+    }
+
+    #[test]
+    fn test_rpc_request_limit_must_be_positive() {
+        let mut config = Config::default();
+        assert!(!config.rpc.enabled);
+        config.rpc.max_requests_per_connection = 0;
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors[0].field, "rpc.max_requests_per_connection");
+
+        config.check(true);
+        assert_eq!(config.rpc.max_requests_per_connection, 1000);
+
+        config.rpc.socket_path = Some(PathBuf::from("/run/lucastra.sock"));
+        assert_eq!(
+            config.rpc.resolved_socket_path(),
+            PathBuf::from("/run/lucastra.sock")
+        );
+    }
+
+    #[test]
+    fn test_job_entries_parse_and_validate() {
+        let toml_str = r#"
+            [jobs.entries.audit-rotate]
+            schedule = "0 3 * * *"
+
+            [jobs.entries.metrics-export]
+            enabled = false
+
+            [jobs.entries.reindex-changed-files]
+            schedule = "every 0s"
+
+            [jobs.entries.defragment]
+            schedule = "every 1h"
+        "#;
+        let mut config: Config = toml::from_str(toml_str).unwrap();
+        assert!(config.jobs.enabled);
+        assert_eq!(
+            config.jobs.job("audit-rotate").schedule.as_deref(),
+            Some("0 3 * * *")
+        );
+        assert!(!config.jobs.job("metrics-export").enabled);
+        assert_eq!(
+            config.jobs.job("embedding-cache-evict"),
+            JobConfig::default()
+        );
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 2);
+        assert!(errors.iter().all(|e| e.field == "jobs.entries"));
+        assert!(errors[0].message.starts_with("unknown job \"defragment\""));
+        assert!(errors[1].message.starts_with("reindex-changed-files:"));
+
+        config.check(true);
+        assert_eq!(config.jobs.entries.len(), 2);
+        assert_eq!(config.jobs.job("reindex-changed-files").schedule, None);
+        let reparsed = Config::from_toml(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(reparsed.jobs, config.jobs);
+    }
+
+    #[test]
+    fn test_blocked_domains_include_subdomains() {
+        let search = SearchConfig {
+            blocked_domains: vec!["Example.com".to_string(), " ".to_string()],
+            ..SearchConfig::default()
+        };
+
+        assert!(search.blocks_domain("example.com"));
+        assert!(search.blocks_domain("news.EXAMPLE.com"));
+        assert!(search.blocks_domain("example.com."));
+        assert!(!search.blocks_domain("notexample.com"));
+        assert!(!search.blocks_domain("example.org"));
+        assert!(!SearchConfig::default().blocks_domain("example.com"));
+    }
+
+    #[test]
+    fn test_index_profiles_parse_and_validate() {
+        let toml_str = r#"
+            [[search.profiles]]
+            name = "docs"
+            root = "~/Documents"
+            exclude = ["node_modules/", "*.csv"]
+
+            [[search.profiles]]
+            name = "broken"
+            root = "/srv"
+            include = ["[z-a"]
+        "#;
+        let mut config: Config = toml::from_str(toml_str).unwrap();
+        assert_eq!(config.search.profiles.len(), 2);
+        assert!(config.search.profiles[0].enabled);
+        assert_eq!(
+            config.search.profiles[0].max_file_size,
+            profile::DEFAULT_MAX_FILE_SIZE
+        );
+        let docs = config.search.profile("docs").unwrap();
+        assert!(docs.root.starts_with(dirs::home_dir().unwrap()));
+        assert_eq!(config.search.profile_matchers().len(), 1);
+
+        let errors = config.validate().unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].field, "search.profiles");
+        assert!(errors[0].message.contains("broken"));
+
+        config.check(true);
+        assert_eq!(config.search.profiles.len(), 1);
+        let reparsed = Config::from_toml(&config.to_toml().unwrap()).unwrap();
+        assert_eq!(reparsed.search.profiles, config.search.profiles);
+    }
+
+    #[test]
+    fn test_allowed_domains() {
+        let mut security = SecurityConfig::default();
+        assert!(!security.allows_domain("example.com"));
+
+        security.allowed_domains = vec!["docs.rs".to_string()];
+        assert!(security.allows_domain("docs.rs"));
+        assert!(security.allows_domain("www.Docs.rs"));
+        assert!(!security.allows_domain("example.com"));
+
+        security.allowed_domains.push("*".to_string());
+        assert!(security.allows_domain("example.com"));
+
+        security.allow_network = false;
+        assert!(!security.allows_domain("docs.rs"));
+    }
+
     #[test]
     fn test_resolved_allowed_dirs_expands_tilde() {
         let cfg = SecurityConfig {
@@ -2,7 +2,7 @@ use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 
 /// Tracing configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct TracingConfig {
     /// Log level: "error", "warn", "info", "debug", "trace"
     #[serde(default = "default_log_level")]
@@ -20,7 +20,7 @@ pub struct TracingConfig {
     #[serde(default = "default_max_log_size")]
     pub max_log_size_mb: u32,
 
-    /// Number of rotated log files to keep
+    /// Number of log files to keep, including the one being written
     #[serde(default = "default_log_files_keep")]
     pub log_files_keep: u32,
 
@@ -34,7 +34,7 @@ pub struct TracingConfig {
 }
 
 /// Metrics configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct MetricsConfig {
     /// Enable metrics collection
     #[serde(default = "default_true")]
@@ -51,6 +51,23 @@ pub struct MetricsConfig {
     /// Metrics export interval in seconds
     #[serde(default = "default_metrics_interval")]
     pub export_interval_secs: u32,
+
+    /// Number of exports to keep; older files are deleted
+    #[serde(default = "default_metrics_files_keep")]
+    pub export_files_keep: u32,
+
+    /// Address to serve `/metrics` on, e.g. "127.0.0.1:9464"
+    /// (needs the `metrics-http` feature)
+    #[serde(default)]
+    pub http_addr: Option<String>,
+
+    /// Keep lifetime totals in `data/metrics_state.json` across restarts
+    #[serde(default = "default_true")]
+    pub persist: bool,
+
+    /// Seconds between saves of the lifetime totals
+    #[serde(default = "default_persist_interval")]
+    pub persist_interval_secs: u32,
 }
 
 // Default value functions
@@ -90,6 +107,14 @@ fn default_metrics_interval() -> u32 {
     60 // 60 seconds
 }
 
+fn default_metrics_files_keep() -> u32 {
+    24
+}
+
+fn default_persist_interval() -> u32 {
+    300
+}
+
 impl Default for TracingConfig {
     fn default() -> Self {
         Self {
@@ -111,6 +136,10 @@ impl Default for MetricsConfig {
             export_to_file: false,
             export_dir: default_metrics_dir(),
             export_interval_secs: default_metrics_interval(),
+            export_files_keep: default_metrics_files_keep(),
+            http_addr: None,
+            persist: true,
+            persist_interval_secs: default_persist_interval(),
         }
     }
 }
@@ -134,6 +163,8 @@ mod tests {
         assert!(config.enabled);
         assert!(!config.export_to_file);
         assert_eq!(config.export_interval_secs, 60);
+        assert_eq!(config.export_files_keep, 24);
+        assert!(config.http_addr.is_none());
     }
 
     #[test]
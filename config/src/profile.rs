@@ -0,0 +1,225 @@
+//! Index profiles: which files below a directory get indexed.
+//!
+//! A profile names a root directory and gitignore-style patterns relative
+//! to it. A pattern without a `/` matches a file or directory name at any
+//! depth (`node_modules`, `*.csv`); one with a `/` matches from the root
+//! (`build/**`, `/notes.md`). A trailing `/` matches directories only.
+//! Excluding a directory excludes everything below it.
+
+use globset::{GlobBuilder, GlobSet, GlobSetBuilder};
+use lucastra_core::{LuCastraError, Result};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Default maximum size of a single indexed file (1 MB).
+pub const DEFAULT_MAX_FILE_SIZE: u64 = 1024 * 1024;
+
+/// Files to index below `root`.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct IndexProfile {
+    pub name: String,
+    pub root: PathBuf,
+    /// Files to index; every file when empty.
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Files and directories never indexed, even when included.
+    #[serde(default)]
+    pub exclude: Vec<String>,
+    /// Files larger than this many bytes are skipped.
+    #[serde(default = "default_max_file_size")]
+    pub max_file_size: u64,
+    /// Disabled profiles index nothing below their root.
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_max_file_size() -> u64 {
+    DEFAULT_MAX_FILE_SIZE
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl IndexProfile {
+    /// Enabled profile indexing every file below `root`.
+    pub fn new(name: impl Into<String>, root: impl Into<PathBuf>) -> Self {
+        Self {
+            name: name.into(),
+            root: root.into(),
+            include: Vec::new(),
+            exclude: Vec::new(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            enabled: true,
+        }
+    }
+
+    pub fn with_include(mut self, pattern: impl Into<String>) -> Self {
+        self.include.push(pattern.into());
+        self
+    }
+
+    pub fn with_exclude(mut self, pattern: impl Into<String>) -> Self {
+        self.exclude.push(pattern.into());
+        self
+    }
+
+    /// Compile the patterns. Fails on the first invalid one.
+    pub fn matcher(&self) -> Result<ProfileMatcher> {
+        let include = if self.include.is_empty() {
+            None
+        } else {
+            Some(self.patterns(&self.include)?.0)
+        };
+        let (exclude, exclude_dirs) = self.patterns(&self.exclude)?;
+        Ok(ProfileMatcher {
+            // Indexed paths are canonical, so the root must be too
+            root: std::fs::canonicalize(&self.root).unwrap_or_else(|_| self.root.clone()),
+            profile: self.clone(),
+            include,
+            exclude,
+            exclude_dirs,
+        })
+    }
+
+    /// Globs for `patterns`: those matching anything, and those matching
+    /// directories only.
+    fn patterns(&self, patterns: &[String]) -> Result<(GlobSet, GlobSet)> {
+        let mut any = GlobSetBuilder::new();
+        let mut dirs = GlobSetBuilder::new();
+        for pattern in patterns {
+            let dir_only = pattern.ends_with('/');
+            let trimmed = pattern.trim().trim_end_matches('/');
+            let glob = match trimmed.strip_prefix('/') {
+                Some(anchored) => anchored.to_string(),
+                None if trimmed.contains('/') => trimmed.to_string(),
+                None => format!("**/{}", trimmed),
+            };
+            if trimmed.is_empty() || glob.is_empty() {
+                return Err(self.invalid(pattern, "pattern is empty"));
+            }
+            let glob = GlobBuilder::new(&glob)
+                .literal_separator(true)
+                .build()
+                .map_err(|e| self.invalid(pattern, e))?;
+            if dir_only {
+                dirs.add(glob);
+            } else {
+                any.add(glob);
+            }
+        }
+        let build = |set: GlobSetBuilder| set.build().map_err(|e| self.invalid("", e));
+        Ok((build(any)?, build(dirs)?))
+    }
+
+    fn invalid(&self, pattern: &str, error: impl std::fmt::Display) -> LuCastraError {
+        LuCastraError::ConfigError(format!(
+            "index profile {}: invalid pattern \"{}\": {}",
+            self.name, pattern, error
+        ))
+    }
+}
+
+/// An [`IndexProfile`] with its patterns compiled.
+#[derive(Debug, Clone)]
+pub struct ProfileMatcher {
+    profile: IndexProfile,
+    root: PathBuf,
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+    exclude_dirs: GlobSet,
+}
+
+impl ProfileMatcher {
+    pub fn profile(&self) -> &IndexProfile {
+        &self.profile
+    }
+
+    /// The profile's root, canonicalized when it exists.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Whether `path` is the root or below it.
+    pub fn contains(&self, path: &Path) -> bool {
+        path.starts_with(&self.root)
+    }
+
+    /// Whether the profile keeps `path`, a file or (with `is_dir`) a
+    /// directory below its root, out of the index. Paths outside the root
+    /// aren't its concern.
+    pub fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return false;
+        };
+        if !self.profile.enabled {
+            return true;
+        }
+
+        let mut prefix = PathBuf::new();
+        let mut components = relative.components().peekable();
+        while let Some(component) = components.next() {
+            prefix.push(component);
+            let last = components.peek().is_none();
+            if self.exclude.is_match(&prefix)
+                || ((!last || is_dir) && self.exclude_dirs.is_match(&prefix))
+            {
+                return true;
+            }
+        }
+        !is_dir
+            && self
+                .include
+                .as_ref()
+                .is_some_and(|include| !include.is_match(relative))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_patterns_follow_gitignore_rules() {
+        let matcher = IndexProfile::new("docs", "/docs")
+            .with_include("*.md")
+            .with_include("src/**/*.rs")
+            .with_exclude("node_modules")
+            .with_exclude("/build/")
+            .with_exclude("drafts/*.md")
+            .matcher()
+            .unwrap();
+        let excluded = |path: &str| matcher.excludes(Path::new(path), false);
+
+        assert!(!excluded("/docs/notes.md"));
+        assert!(!excluded("/docs/deep/down/notes.md"));
+        assert!(!excluded("/docs/src/app/main.rs"));
+        assert!(excluded("/docs/main.rs"));
+        assert!(excluded("/docs/node_modules/pkg/README.md"));
+        assert!(excluded("/docs/web/node_modules/README.md"));
+        assert!(excluded("/docs/build/out.md"));
+        assert!(!excluded("/docs/web/build/out.md"));
+        assert!(excluded("/docs/drafts/idea.md"));
+        assert!(!excluded("/docs/old/drafts/idea.md"));
+        assert!(matcher.excludes(Path::new("/docs/build"), true));
+        assert!(!matcher.excludes(Path::new("/docs/src"), true));
+        assert!(!excluded("/elsewhere/main.rs"));
+
+        let disabled = IndexProfile {
+            enabled: false,
+            ..IndexProfile::new("off", "/off")
+        };
+        assert!(disabled
+            .matcher()
+            .unwrap()
+            .excludes(Path::new("/off/a.md"), false));
+        assert!(IndexProfile::new("bad", "/x")
+            .with_exclude("[z-a")
+            .matcher()
+            .is_err());
+        assert!(IndexProfile::new("bad", "/x")
+            .with_exclude("/")
+            .matcher()
+            .is_err());
+    }
+}
@@ -0,0 +1,220 @@
+//! The `[providers]` table of config.toml: LLM provider entries with their
+//! rate limits and prices.
+
+use crate::{ConfigError, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+
+/// Provider configuration from config file.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProviderConfig {
+    pub provider: String,
+    pub api_key: Option<String>,
+    /// Environment variable holding the API key, used when `api_key` is unset.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_env: Option<String>,
+    /// OS keyring entry holding the API key, used when neither `api_key` nor
+    /// `api_key_env` yields one. Needs LucAstra built with the `keyring`
+    /// feature.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub api_key_keyring: Option<String>,
+    pub endpoint: Option<String>,
+    pub model: Option<String>,
+    pub temperature: Option<f32>,
+    pub max_tokens: Option<usize>,
+    pub timeout_secs: Option<u64>,
+    /// Embedding size of the `mock` provider; other providers have their own.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dimensions: Option<usize>,
+    /// Providers tried in order when this one is unreachable.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub fallbacks: Vec<ProviderConfig>,
+    /// Request and token budgets per request class; unlimited by default.
+    #[serde(default)]
+    pub rate_limits: RateLimits,
+    /// Prices by model name (`"*"` matches any model) for usage tracking.
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub pricing: HashMap<String, ModelPrice>,
+}
+
+impl Default for ProviderConfig {
+    fn default() -> Self {
+        Self {
+            provider: "llamafile".to_string(),
+            api_key: None,
+            api_key_env: None,
+            api_key_keyring: None,
+            endpoint: Some("http://localhost:8000".to_string()),
+            model: None,
+            temperature: Some(0.7),
+            max_tokens: Some(256),
+            timeout_secs: Some(30),
+            dimensions: None,
+            fallbacks: Vec::new(),
+            rate_limits: RateLimits::default(),
+            pricing: HashMap::new(),
+        }
+    }
+}
+
+impl ProviderConfig {
+    /// The API key to use: `api_key`, then the `api_key_env` variable looked
+    /// up with `env`, then the `api_key_keyring` entry looked up with
+    /// `keyring`. Keys found indirectly are never stored in the config, so
+    /// saving it can't leak them.
+    pub fn resolve_api_key_with(
+        &self,
+        env: impl Fn(&str) -> Option<String>,
+        keyring: impl Fn(&str) -> Option<String>,
+    ) -> Option<String> {
+        self.api_key
+            .clone()
+            .or_else(|| self.api_key_env.as_deref().and_then(&env))
+            .or_else(|| self.api_key_keyring.as_deref().and_then(&keyring))
+            .filter(|key| !key.is_empty())
+    }
+}
+
+/// Named provider entries, the `[providers]` table of config.toml.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProvidersConfig {
+    /// Entry used when no provider is named.
+    #[serde(default = "default_provider_name")]
+    pub default: String,
+    #[serde(default)]
+    pub entries: BTreeMap<String, ProviderConfig>,
+}
+
+fn default_provider_name() -> String {
+    "llamafile".to_string()
+}
+
+impl Default for ProvidersConfig {
+    fn default() -> Self {
+        Self {
+            default: default_provider_name(),
+            entries: BTreeMap::from([(default_provider_name(), ProviderConfig::default())]),
+        }
+    }
+}
+
+impl ProvidersConfig {
+    /// The entry called `name`, or the default entry when `name` is `None`.
+    pub fn resolve(&self, name: Option<&str>) -> Result<ProviderConfig> {
+        let name = name.unwrap_or(&self.default);
+        self.entries
+            .get(name)
+            .cloned()
+            .ok_or_else(|| ConfigError::UnknownProvider {
+                name: name.to_string(),
+                known: self.entries.keys().cloned().collect::<Vec<_>>().join(", "),
+            })
+    }
+}
+
+/// Requests and tokens allowed per minute; `None` means unlimited.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Budget {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_min: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tokens_per_min: Option<u32>,
+}
+
+/// Budgets for each request class of one provider.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimits {
+    #[serde(default)]
+    pub completion: Budget,
+    #[serde(default)]
+    pub embedding: Budget,
+}
+
+impl RateLimits {
+    pub fn budget(&self, class: RequestClass) -> Budget {
+        match class {
+            RequestClass::Completion => self.completion,
+            RequestClass::Embedding => self.embedding,
+        }
+    }
+}
+
+/// Kind of API call; providers limit these separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RequestClass {
+    Completion,
+    Embedding,
+}
+
+impl fmt::Display for RequestClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RequestClass::Completion => write!(f, "completion"),
+            RequestClass::Embedding => write!(f, "embedding"),
+        }
+    }
+}
+
+/// Prices in US dollars per 1,000 tokens.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ModelPrice {
+    #[serde(default)]
+    pub input_per_1k: f64,
+    #[serde(default)]
+    pub output_per_1k: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_1k + output_tokens as f64 * self.output_per_1k)
+            / 1000.0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_api_key_resolution_order() {
+        let env = |name: &str| (name == "TEST_KEY").then(|| "from-env".to_string());
+        let keyring = |entry: &str| (entry == "openai").then(|| "from-keyring".to_string());
+        let mut config = ProviderConfig {
+            provider: "openai".to_string(),
+            api_key: Some("explicit".to_string()),
+            api_key_env: Some("TEST_KEY".to_string()),
+            api_key_keyring: Some("openai".to_string()),
+            ..Default::default()
+        };
+
+        let resolve = |config: &ProviderConfig| config.resolve_api_key_with(env, keyring);
+        assert_eq!(resolve(&config).as_deref(), Some("explicit"));
+        config.api_key = None;
+        assert_eq!(resolve(&config).as_deref(), Some("from-env"));
+        config.api_key_env = Some("UNSET_KEY".to_string());
+        assert_eq!(resolve(&config).as_deref(), Some("from-keyring"));
+        config.api_key_keyring = None;
+        assert_eq!(resolve(&config), None);
+    }
+
+    #[test]
+    fn test_providers_resolve_by_name() {
+        let mut providers = ProvidersConfig::default();
+        providers.entries.insert(
+            "cloud".to_string(),
+            ProviderConfig {
+                provider: "anthropic".to_string(),
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(providers.resolve(None).unwrap().provider, "llamafile");
+        assert_eq!(
+            providers.resolve(Some("cloud")).unwrap().provider,
+            "anthropic"
+        );
+        assert!(providers.resolve(Some("missing")).is_err());
+    }
+}
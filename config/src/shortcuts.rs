@@ -0,0 +1,354 @@
+//! Keyboard shortcuts for the GUI, configured under `[gui.shortcuts]`.
+//!
+//! Each action has a default chord; the table overrides them by action
+//! name with strings such as `"ctrl+k"`, `"ctrl+shift+p"` or `"f5"`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+/// Named keys a chord may end with, besides single characters and F1-F24.
+const NAMED_KEYS: [&str; 15] = [
+    "escape",
+    "enter",
+    "tab",
+    "space",
+    "backspace",
+    "delete",
+    "insert",
+    "up",
+    "down",
+    "left",
+    "right",
+    "home",
+    "end",
+    "pageup",
+    "pagedown",
+];
+
+/// A key with the modifiers held for it, e.g. `ctrl+shift+p`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Chord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    /// The Windows, Command or Super key
+    pub logo: bool,
+    /// Lowercase character such as `k` or `,`, or a key name such as
+    /// `escape` or `f5`
+    pub key: String,
+}
+
+impl Chord {
+    /// Chord for `key` with no modifiers.
+    pub fn key(key: &str) -> Self {
+        Self {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            logo: false,
+            key: key.to_lowercase(),
+        }
+    }
+}
+
+impl FromStr for Chord {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut chord = Chord::key("");
+        let parts: Vec<String> = s.split('+').map(|p| p.trim().to_lowercase()).collect();
+        let (key, modifiers) = parts
+            .split_last()
+            .ok_or_else(|| format!("\"{}\" has no key", s))?;
+
+        for modifier in modifiers {
+            let held = match modifier.as_str() {
+                "ctrl" | "control" => &mut chord.ctrl,
+                "alt" | "option" => &mut chord.alt,
+                "shift" => &mut chord.shift,
+                "cmd" | "super" | "logo" | "meta" | "win" => &mut chord.logo,
+                "" => return Err(format!("\"{}\" has an empty part", s)),
+                other => return Err(format!("\"{}\" is not a modifier", other)),
+            };
+            if *held {
+                return Err(format!("\"{}\" repeats {}", s, modifier));
+            }
+            *held = true;
+        }
+
+        chord.key = match key.as_str() {
+            "" => return Err(format!("\"{}\" has no key", s)),
+            "esc" => "escape".to_string(),
+            "return" => "enter".to_string(),
+            "del" => "delete".to_string(),
+            "arrowup" => "up".to_string(),
+            "arrowdown" => "down".to_string(),
+            "arrowleft" => "left".to_string(),
+            "arrowright" => "right".to_string(),
+            "comma" => ",".to_string(),
+            "plus" => "+".to_string(),
+            key if key.chars().count() == 1
+                || NAMED_KEYS.contains(&key)
+                || is_function_key(key) =>
+            {
+                key.to_string()
+            }
+            "ctrl" | "control" | "alt" | "option" | "shift" | "cmd" | "super" | "logo" | "meta"
+            | "win" => return Err(format!("\"{}\" has no key", s)),
+            other => return Err(format!("\"{}\" is not a key", other)),
+        };
+        Ok(chord)
+    }
+}
+
+impl fmt::Display for Chord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let modifiers = [
+            (self.ctrl, "ctrl+"),
+            (self.alt, "alt+"),
+            (self.shift, "shift+"),
+            (self.logo, "cmd+"),
+        ];
+        for (held, name) in modifiers {
+            if held {
+                f.write_str(name)?;
+            }
+        }
+        f.write_str(&self.key)
+    }
+}
+
+/// `f1` through `f24`
+fn is_function_key(key: &str) -> bool {
+    key.strip_prefix('f')
+        .and_then(|n| n.parse::<u8>().ok())
+        .is_some_and(|n| (1..=24).contains(&n))
+}
+
+/// Something a shortcut can do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ShortcutAction {
+    /// Key in `[gui.shortcuts]`
+    pub name: &'static str,
+    pub default_chord: &'static str,
+    /// Also fires while a text field has focus
+    pub global: bool,
+}
+
+/// Every action, with its default chord.
+pub const SHORTCUT_ACTIONS: [ShortcutAction; 10] = [
+    ShortcutAction {
+        name: "focus_input",
+        default_chord: "ctrl+l",
+        global: true,
+    },
+    ShortcutAction {
+        name: "focus_search",
+        default_chord: "ctrl+k",
+        global: true,
+    },
+    ShortcutAction {
+        name: "open_settings",
+        default_chord: "ctrl+,",
+        global: true,
+    },
+    ShortcutAction {
+        name: "close_settings",
+        default_chord: "escape",
+        global: false,
+    },
+    ShortcutAction {
+        name: "send_message",
+        default_chord: "ctrl+enter",
+        global: false,
+    },
+    ShortcutAction {
+        name: "new_tab",
+        default_chord: "ctrl+t",
+        global: true,
+    },
+    ShortcutAction {
+        name: "close_tab",
+        default_chord: "ctrl+w",
+        global: true,
+    },
+    ShortcutAction {
+        name: "reopen_tab",
+        default_chord: "ctrl+shift+t",
+        global: true,
+    },
+    ShortcutAction {
+        name: "next_tab",
+        default_chord: "ctrl+tab",
+        global: true,
+    },
+    ShortcutAction {
+        name: "previous_tab",
+        default_chord: "ctrl+shift+tab",
+        global: true,
+    },
+];
+
+/// An action bound to a chord.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub action: ShortcutAction,
+    pub chord: Chord,
+}
+
+/// Chords for every action: the defaults with `[gui.shortcuts]` applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ShortcutRegistry {
+    bindings: Vec<Binding>,
+}
+
+impl ShortcutRegistry {
+    /// Registry with the default chords.
+    pub fn new() -> Self {
+        Self::from_overrides(&BTreeMap::new()).expect("default shortcuts are valid")
+    }
+
+    /// Apply `overrides`, keyed by action name, to the defaults. Unknown
+    /// actions, unparseable chords and chords bound to two actions are
+    /// all reported.
+    pub fn from_overrides(overrides: &BTreeMap<String, String>) -> Result<Self, Vec<String>> {
+        let mut errors = Vec::new();
+        for name in overrides.keys() {
+            if !SHORTCUT_ACTIONS.iter().any(|action| action.name == name) {
+                errors.push(format!("unknown action \"{}\"", name));
+            }
+        }
+
+        let mut bindings: Vec<Binding> = Vec::new();
+        for action in SHORTCUT_ACTIONS {
+            let chord = overrides
+                .get(action.name)
+                .map(String::as_str)
+                .unwrap_or(action.default_chord);
+            let chord = match chord.parse::<Chord>() {
+                Ok(chord) => chord,
+                Err(e) => {
+                    errors.push(format!("{}: {}", action.name, e));
+                    continue;
+                }
+            };
+            if let Some(other) = bindings.iter().find(|b| b.chord == chord) {
+                errors.push(format!(
+                    "\"{}\" is bound to both {} and {}",
+                    chord, other.action.name, action.name
+                ));
+                continue;
+            }
+            bindings.push(Binding { action, chord });
+        }
+
+        if errors.is_empty() {
+            Ok(Self { bindings })
+        } else {
+            Err(errors)
+        }
+    }
+
+    pub fn bindings(&self) -> &[Binding] {
+        &self.bindings
+    }
+
+    /// Action bound to `chord`. While a text field has focus only global
+    /// actions fire, so typing isn't taken over.
+    pub fn action_for(&self, chord: &Chord, text_focused: bool) -> Option<&'static str> {
+        self.bindings
+            .iter()
+            .find(|binding| binding.chord == *chord && (binding.action.global || !text_focused))
+            .map(|binding| binding.action.name)
+    }
+}
+
+impl Default for ShortcutRegistry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_chords() {
+        let chord: Chord = "ctrl+shift+p".parse().unwrap();
+        assert!(chord.ctrl && chord.shift && !chord.alt && !chord.logo);
+        assert_eq!(chord.key, "p");
+        assert_eq!(chord.to_string(), "ctrl+shift+p");
+
+        assert_eq!("F5".parse::<Chord>().unwrap(), Chord::key("f5"));
+        assert_eq!("Esc".parse::<Chord>().unwrap(), Chord::key("escape"));
+        assert_eq!(" Ctrl + , ".parse::<Chord>().unwrap().to_string(), "ctrl+,");
+        assert_eq!("cmd+comma".parse::<Chord>().unwrap().to_string(), "cmd+,");
+    }
+
+    #[test]
+    fn test_invalid_chords() {
+        assert_eq!(
+            "ctrl+".parse::<Chord>().unwrap_err(),
+            "\"ctrl+\" has no key"
+        );
+        assert_eq!(
+            "ctrl+shift".parse::<Chord>().unwrap_err(),
+            "\"ctrl+shift\" has no key"
+        );
+        assert_eq!("".parse::<Chord>().unwrap_err(), "\"\" has no key");
+        assert_eq!(
+            "hyper+k".parse::<Chord>().unwrap_err(),
+            "\"hyper\" is not a modifier"
+        );
+        assert_eq!(
+            "ctrl+f25".parse::<Chord>().unwrap_err(),
+            "\"f25\" is not a key"
+        );
+        assert!("ctrl+ctrl+k".parse::<Chord>().is_err());
+    }
+
+    #[test]
+    fn test_registry_defaults_and_focus() {
+        let registry = ShortcutRegistry::new();
+        let ctrl_k = "ctrl+k".parse().unwrap();
+        let escape = Chord::key("escape");
+
+        assert_eq!(registry.action_for(&ctrl_k, false), Some("focus_search"));
+        assert_eq!(registry.action_for(&ctrl_k, true), Some("focus_search"));
+        assert_eq!(registry.action_for(&escape, false), Some("close_settings"));
+        assert_eq!(registry.action_for(&escape, true), None);
+        assert_eq!(registry.action_for(&Chord::key("k"), false), None);
+        let ctrl_shift_tab = "ctrl+shift+tab".parse().unwrap();
+        assert_eq!(
+            registry.action_for(&ctrl_shift_tab, true),
+            Some("previous_tab")
+        );
+    }
+
+    #[test]
+    fn test_registry_overrides_and_conflicts() {
+        let mut overrides = BTreeMap::new();
+        overrides.insert("focus_search".to_string(), "ctrl+shift+f".to_string());
+        let registry = ShortcutRegistry::from_overrides(&overrides).unwrap();
+        assert_eq!(
+            registry.action_for(&"ctrl+shift+f".parse().unwrap(), false),
+            Some("focus_search")
+        );
+        assert_eq!(registry.action_for(&"ctrl+k".parse().unwrap(), false), None);
+
+        // Conflicts with another action's default
+        overrides.insert("send_message".to_string(), "Ctrl+L".to_string());
+        overrides.insert("open_dashboard".to_string(), "f5".to_string());
+        overrides.insert("close_settings".to_string(), "ctrl+".to_string());
+        assert_eq!(
+            ShortcutRegistry::from_overrides(&overrides).unwrap_err(),
+            vec![
+                "unknown action \"open_dashboard\"",
+                "close_settings: \"ctrl+\" has no key",
+                "\"ctrl+l\" is bound to both focus_input and send_message",
+            ]
+        );
+    }
+}
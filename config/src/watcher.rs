@@ -0,0 +1,313 @@
+//! Watches the config file and reports what changed.
+//!
+//! Edits are debounced on a background thread, then the file is parsed and
+//! validated. A valid edit is compared section by section with the last
+//! good config and each changed section is sent as a [`ConfigEvent`]; an
+//! invalid one is sent as [`ConfigEvent::Rejected`] and the last good
+//! config is kept.
+
+use crate::{
+    AdvancedConfig, Config, ConfigError, GuiConfig, JobsConfig, LlmConfig, MetricsConfig,
+    ProvidersConfig, Result, SearchConfig, SecurityConfig, StorageConfig, ToolsConfig,
+    TracingConfig,
+};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Quiet period after the last write before the file is re-read.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+
+/// A section of the config that changed on disk, with its new value.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ConfigEvent {
+    LlmChanged(LlmConfig),
+    StorageChanged(StorageConfig),
+    SearchChanged(SearchConfig),
+    GuiChanged(GuiConfig),
+    SecurityChanged(SecurityConfig),
+    AdvancedChanged(AdvancedConfig),
+    ToolsChanged(ToolsConfig),
+    TracingChanged(TracingConfig),
+    MetricsChanged(MetricsConfig),
+    ProvidersChanged(ProvidersConfig),
+    JobsChanged(JobsConfig),
+    /// The file was edited but can't be used; the previous config stays.
+    Rejected(String),
+}
+
+impl Config {
+    /// One event per section of `self` that differs from `old`.
+    pub fn changes_since(&self, old: &Config) -> Vec<ConfigEvent> {
+        let mut events = Vec::new();
+        if self.llm != old.llm {
+            events.push(ConfigEvent::LlmChanged(self.llm.clone()));
+        }
+        if self.storage != old.storage {
+            events.push(ConfigEvent::StorageChanged(self.storage.clone()));
+        }
+        if self.search != old.search {
+            events.push(ConfigEvent::SearchChanged(self.search.clone()));
+        }
+        if self.gui != old.gui {
+            events.push(ConfigEvent::GuiChanged(self.gui.clone()));
+        }
+        if self.security != old.security {
+            events.push(ConfigEvent::SecurityChanged(self.security.clone()));
+        }
+        if self.advanced != old.advanced {
+            events.push(ConfigEvent::AdvancedChanged(self.advanced.clone()));
+        }
+        if self.tools != old.tools {
+            events.push(ConfigEvent::ToolsChanged(self.tools.clone()));
+        }
+        if self.tracing != old.tracing {
+            events.push(ConfigEvent::TracingChanged(self.tracing.clone()));
+        }
+        if self.metrics != old.metrics {
+            events.push(ConfigEvent::MetricsChanged(self.metrics.clone()));
+        }
+        if self.providers != old.providers {
+            events.push(ConfigEvent::ProvidersChanged(self.providers.clone()));
+        }
+        if self.jobs != old.jobs {
+            events.push(ConfigEvent::JobsChanged(self.jobs.clone()));
+        }
+        events
+    }
+}
+
+enum Signal {
+    Touched,
+    Stop,
+}
+
+/// Watches one config file and reports debounced, validated changes.
+pub struct ConfigWatcher {
+    watcher: Option<RecommendedWatcher>,
+    signals: Sender<Signal>,
+    events: Receiver<ConfigEvent>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl ConfigWatcher {
+    /// Watch `path`, reporting changes relative to `current`.
+    pub fn start(path: PathBuf, current: Config) -> Result<Self> {
+        Self::with_debounce(path, current, DEFAULT_DEBOUNCE)
+    }
+
+    pub fn with_debounce(path: PathBuf, current: Config, debounce: Duration) -> Result<Self> {
+        let (signal_tx, signal_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+
+        // Editors often save by replacing the file, so watch its directory
+        let dir = path
+            .parent()
+            .map(Path::to_path_buf)
+            .ok_or(ConfigError::NoConfigDir)?;
+        let file_name = path.file_name().map(|name| name.to_os_string());
+        let notify_tx = signal_tx.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) => {
+                    if event
+                        .paths
+                        .iter()
+                        .any(|p| p.file_name().map(|n| n.to_os_string()) == file_name)
+                    {
+                        let _ = notify_tx.send(Signal::Touched);
+                    }
+                }
+                Err(e) => warn!("Config watcher error: {}", e),
+            })?;
+        watcher.watch(&dir, RecursiveMode::NonRecursive)?;
+        info!("Watching {} for changes", path.display());
+
+        let worker = std::thread::Builder::new()
+            .name("lucastra-config-watcher".to_string())
+            .spawn(move || reload_loop(path, current, signal_rx, event_tx, debounce))?;
+
+        Ok(Self {
+            watcher: Some(watcher),
+            signals: signal_tx,
+            events: event_rx,
+            worker: Some(worker),
+        })
+    }
+
+    /// Take all reported events without blocking.
+    pub fn drain(&self) -> Vec<ConfigEvent> {
+        self.events.try_iter().collect()
+    }
+
+    /// Stop watching and join the background thread.
+    pub fn stop(&mut self) {
+        self.watcher.take();
+        let _ = self.signals.send(Signal::Stop);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for ConfigWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Re-read `path` once it has been quiet for `debounce` after a write.
+fn reload_loop(
+    path: PathBuf,
+    mut current: Config,
+    signals: Receiver<Signal>,
+    events: Sender<ConfigEvent>,
+    debounce: Duration,
+) {
+    let mut touched: Option<Instant> = None;
+    // Contents last acted on, so one save seen twice is reported once
+    let mut last_read: Option<String> = None;
+
+    loop {
+        let timeout = touched
+            .map(|at| (at + debounce).saturating_duration_since(Instant::now()))
+            .unwrap_or(Duration::from_secs(3600));
+
+        match signals.recv_timeout(timeout) {
+            Ok(Signal::Touched) => {
+                touched = Some(Instant::now());
+                continue;
+            }
+            Ok(Signal::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+        if touched.take().is_none() || !path.exists() {
+            continue;
+        }
+
+        let contents = match std::fs::read_to_string(&path) {
+            Ok(contents) => contents,
+            Err(e) => {
+                warn!("Could not read {}: {}", path.display(), e);
+                continue;
+            }
+        };
+        // Empty while an editor truncates the file before writing it out
+        if contents.trim().is_empty() || last_read.as_ref() == Some(&contents) {
+            continue;
+        }
+        let loaded = Config::from_toml(&contents).and_then(|config| {
+            config
+                .validate()
+                .map(|_| config)
+                .map_err(ConfigError::Invalid)
+        });
+        last_read = Some(contents);
+        let reported = match loaded {
+            Ok(config) => {
+                let changes = config.changes_since(&current);
+                current = config;
+                changes
+            }
+            Err(e) => {
+                warn!("Ignoring edit to {}: {}", path.display(), e);
+                vec![ConfigEvent::Rejected(e.to_string())]
+            }
+        };
+        for event in reported {
+            if events.send(event).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn wait_for_events(watcher: &ConfigWatcher, timeout: Duration) -> Vec<ConfigEvent> {
+        let deadline = Instant::now() + timeout;
+        let mut events = Vec::new();
+        while Instant::now() < deadline && events.is_empty() {
+            events.extend(watcher.drain());
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        events
+    }
+
+    #[test]
+    fn test_changes_since_reports_changed_sections() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.llm.server_url = "http://localhost:9000".to_string();
+        new.security.allow_usb = true;
+
+        let events = new.changes_since(&old);
+        assert_eq!(events.len(), 2);
+        assert!(
+            matches!(&events[0], ConfigEvent::LlmChanged(llm) if llm.server_url.ends_with(":9000"))
+        );
+        assert!(matches!(&events[1], ConfigEvent::SecurityChanged(s) if s.allow_usb));
+        assert!(old.changes_since(&old).is_empty());
+    }
+
+    #[test]
+    fn test_edits_are_reported_and_invalid_edits_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let config = Config::default();
+        fs::write(&path, toml::to_string_pretty(&config).unwrap()).unwrap();
+        let watcher =
+            ConfigWatcher::with_debounce(path.clone(), config.clone(), Duration::from_millis(100))
+                .unwrap();
+
+        let mut edited = config.clone();
+        edited.llm.server_url = "http://localhost:9000".to_string();
+        fs::write(&path, toml::to_string_pretty(&edited).unwrap()).unwrap();
+        let events = wait_for_events(&watcher, Duration::from_secs(5));
+        assert_eq!(events, vec![ConfigEvent::LlmChanged(edited.llm.clone())]);
+
+        let mut invalid = edited.clone();
+        invalid.llm.temperature = 9.5;
+        fs::write(&path, toml::to_string_pretty(&invalid).unwrap()).unwrap();
+        let events = wait_for_events(&watcher, Duration::from_secs(5));
+        assert!(matches!(&events[..], [ConfigEvent::Rejected(e)] if e.contains("llm.temperature")));
+
+        // Fixing the edit is compared with the last good config, not the rejected one
+        edited.search.max_results = 3;
+        fs::write(&path, toml::to_string_pretty(&edited).unwrap()).unwrap();
+        let events = wait_for_events(&watcher, Duration::from_secs(5));
+        assert_eq!(events, vec![ConfigEvent::SearchChanged(edited.search)]);
+    }
+
+    #[test]
+    fn test_truncated_and_repeated_writes_are_skipped() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config.toml");
+        let config = Config::default();
+        fs::write(&path, toml::to_string_pretty(&config).unwrap()).unwrap();
+        let watcher =
+            ConfigWatcher::with_debounce(path.clone(), config.clone(), Duration::from_millis(100))
+                .unwrap();
+
+        // As an editor truncates the file before writing it out
+        fs::write(&path, "").unwrap();
+        assert!(wait_for_events(&watcher, Duration::from_secs(1)).is_empty());
+
+        let mut invalid = config.clone();
+        invalid.llm.temperature = 9.5;
+        let contents = toml::to_string_pretty(&invalid).unwrap();
+        fs::write(&path, &contents).unwrap();
+        let events = wait_for_events(&watcher, Duration::from_secs(5));
+        assert!(matches!(&events[..], [ConfigEvent::Rejected(_)]));
+
+        // The same save seen again isn't rejected twice
+        fs::write(&path, &contents).unwrap();
+        assert!(wait_for_events(&watcher, Duration::from_secs(1)).is_empty());
+    }
+}
@@ -1,12 +1,18 @@
-use serde::{Deserialize, Serialize};
+use crate::compat::{self, SchemaError, SCHEMA_VERSION};
+use crate::error::{ErrorCode, LuCastraError};
+use serde::{Deserialize, Serialize, Serializer};
+use serde_json::Value;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Serialized with a `schema_version`; older JSON is upgraded when read
+/// (see [`compat`]).
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "Value")]
 pub struct Command {
     pub id: String,
     pub payload: CommandPayload,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CommandPayload {
     /// List devices (USB, input devices)
     ListDevices,
@@ -29,15 +35,45 @@ pub enum CommandPayload {
     /// Write file contents
     WriteFile { path: String, content: Vec<u8> },
 
-    /// Search filesystem (BM25)
-    Search { query: String },
+    /// Search filesystem (BM25). Returns `limit` results (the configured
+    /// maximum when unset) starting `offset` results into the ranking.
+    Search {
+        query: String,
+        #[serde(default)]
+        offset: usize,
+        #[serde(default)]
+        limit: Option<usize>,
+    },
 
-    /// Query the LLM (with optional search context)
-    Query { text: String, use_rag: Option<bool> },
+    /// Index a host file or directory into the search index. Only paths
+    /// inside the allowed host directories are crawled.
+    IndexPath { path: String },
+
+    /// Query the LLM (with optional search context). Queries naming a
+    /// conversation see its earlier turns and add their answer to it.
+    /// Attachments are given to the model as sources ahead of any search
+    /// results.
+    Query {
+        text: String,
+        use_rag: Option<bool>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        conversation_id: Option<String>,
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        attachments: Vec<Attachment>,
+    },
+
+    /// Forget a conversation's turns
+    EndConversation { conversation_id: String },
 
     /// Get system status
     Status,
 
+    /// Check now whether the LLM server answers
+    TestLlmConnection,
+
+    /// Start a background job now instead of waiting for its schedule
+    RunJob { name: String },
+
     /// Shutdown system
     Shutdown,
 
@@ -45,21 +81,154 @@ pub enum CommandPayload {
     Echo { message: String },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl CommandPayload {
+    /// Short name of the command, for logs and traces.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CommandPayload::ListDevices => "list_devices",
+            CommandPayload::Mount { .. } => "mount",
+            CommandPayload::Unmount { .. } => "unmount",
+            CommandPayload::ListFiles { .. } => "list_files",
+            CommandPayload::ReadFile { .. } => "read_file",
+            CommandPayload::WriteFile { .. } => "write_file",
+            CommandPayload::Search { .. } => "search",
+            CommandPayload::IndexPath { .. } => "index_path",
+            CommandPayload::Query { .. } => "query",
+            CommandPayload::EndConversation { .. } => "end_conversation",
+            CommandPayload::Status => "status",
+            CommandPayload::TestLlmConnection => "test_llm_connection",
+            CommandPayload::RunJob { .. } => "run_job",
+            CommandPayload::Shutdown => "shutdown",
+            CommandPayload::Echo { .. } => "echo",
+        }
+    }
+}
+
+/// Serialized with a `schema_version`, like [`Command`].
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "Value")]
 pub struct Response {
     pub command_id: String,
     pub payload: ResponsePayload,
 }
 
+/// [`Command`] as written, with its schema version. Borrows when
+/// serializing and owns when deserializing.
+#[derive(Serialize, Deserialize)]
+struct CommandJson<S, P> {
+    #[serde(default)]
+    schema_version: u32,
+    id: S,
+    payload: P,
+}
+
+/// [`Response`] as written, with its schema version.
+#[derive(Serialize, Deserialize)]
+struct ResponseJson<S, P> {
+    #[serde(default)]
+    schema_version: u32,
+    command_id: S,
+    payload: P,
+}
+
+impl Serialize for Command {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        CommandJson {
+            schema_version: SCHEMA_VERSION,
+            id: &self.id,
+            payload: &self.payload,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl TryFrom<Value> for Command {
+    type Error = SchemaError;
+
+    fn try_from(value: Value) -> Result<Self, SchemaError> {
+        let json: CommandJson<String, CommandPayload> =
+            serde_json::from_value(compat::upgrade_command(value)?)
+                .map_err(|e| SchemaError::invalid("command", e))?;
+        Ok(Self {
+            id: json.id,
+            payload: json.payload,
+        })
+    }
+}
+
+impl Serialize for Response {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ResponseJson {
+            schema_version: SCHEMA_VERSION,
+            command_id: &self.command_id,
+            payload: &self.payload,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl TryFrom<Value> for Response {
+    type Error = SchemaError;
+
+    fn try_from(value: Value) -> Result<Self, SchemaError> {
+        let json: ResponseJson<String, ResponsePayload> =
+            serde_json::from_value(compat::upgrade_response(value)?)
+                .map_err(|e| SchemaError::invalid("response", e))?;
+        Ok(Self {
+            command_id: json.command_id,
+            payload: json.payload,
+        })
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum ResponsePayload {
     Devices(Vec<String>),
     Files(Vec<FileEntry>),
     Content(Vec<u8>),
-    SearchResults(Vec<SearchResult>),
+    SearchResults(SearchPage),
     Status(String),
     Success(String),
-    Error(String),
+    /// Answer to a query that asked for search context, with the sources
+    /// it cited. `used_context` is false when nothing relevant was found and
+    /// the model answered without documents.
+    RagAnswer {
+        text: String,
+        sources: Vec<SourceRef>,
+        used_context: bool,
+    },
+    /// The command failed. `code` says why in a form clients can match on;
+    /// `message` is for people.
+    Error {
+        code: ErrorCode,
+        message: String,
+    },
+    /// Text generated so far by a streaming query; several may arrive
+    /// before the final [`ResponsePayload::Finished`].
+    Partial(String),
+    /// End of a streaming query. `stop_reason` is the LLM's, e.g. `"length"`
+    /// when generation hit the token limit.
+    Finished {
+        stop_reason: String,
+        /// Sources the streamed answer cited, as in [`ResponsePayload::RagAnswer`].
+        #[serde(default)]
+        sources: Vec<SourceRef>,
+        /// Whether search context was given to the model.
+        #[serde(default)]
+        used_context: bool,
+    },
+    /// A query couldn't reach the LLM; says why.
+    LlmUnavailable(String),
+}
+
+impl ResponsePayload {
+    /// [`ResponsePayload::Error`] for `error`, with its code.
+    pub fn error(error: &LuCastraError) -> Self {
+        Self::Error {
+            code: error.code(),
+            message: error.to_string(),
+        }
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -67,6 +236,9 @@ pub struct FileEntry {
     pub path: String,
     pub is_dir: bool,
     pub size: u64,
+    /// Last modification as Unix seconds, when the driver knows it.
+    #[serde(default)]
+    pub modified: Option<u64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,4 +246,104 @@ pub struct SearchResult {
     pub path: String,
     pub score: f32,
     pub snippet: String,
+    /// 1-based line where the highlighted match starts, if any term matched.
+    #[serde(default)]
+    pub line_number: Option<usize>,
+    /// Format the text was extracted from (`pdf`, `docx`, `html`); `None`
+    /// for plain text.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+    /// 1-based page of the match in paged documents such as PDFs.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<usize>,
+}
+
+impl SearchResult {
+    /// Where in its document the result is, e.g. `pdf, page 3`; `None` for
+    /// plain text.
+    pub fn location(&self) -> Option<String> {
+        match (&self.format, self.page) {
+            (Some(format), Some(page)) => Some(format!("{}, page {}", format, page)),
+            (Some(format), None) => Some(format.clone()),
+            (None, Some(page)) => Some(format!("page {}", page)),
+            (None, None) => None,
+        }
+    }
+}
+
+/// One page of a ranked search.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct SearchPage {
+    pub results: Vec<SearchResult>,
+    /// Documents matching the query across all pages.
+    pub total_hits: usize,
+    /// Rank of the first result, counting from 0.
+    pub offset: usize,
+}
+
+/// Prefix of search documents indexed from web pages; the page URL follows.
+pub const WEB_DOC_PREFIX: &str = "web://";
+
+/// Text the user attached to a query, e.g. a dropped file or a long paste.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Attachment {
+    /// Path of an attached file, or a short name for pasted text.
+    pub name: String,
+    pub text: String,
+}
+
+/// Where the text of a [`SourceRef`] came from.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SourceOrigin {
+    /// Retrieved from the search index.
+    #[default]
+    Index,
+    /// Attached to the query by the user.
+    Attachment,
+}
+
+/// A search result or attachment given to the LLM as a numbered source.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SourceRef {
+    pub path: String,
+    pub score: f32,
+    pub snippet: String,
+    #[serde(default, skip_serializing_if = "SourceOrigin::is_index")]
+    pub origin: SourceOrigin,
+}
+
+impl SourceOrigin {
+    fn is_index(&self) -> bool {
+        *self == SourceOrigin::Index
+    }
+}
+
+impl SourceRef {
+    /// URL of a web page source.
+    pub fn web_url(&self) -> Option<&str> {
+        self.path.strip_prefix(WEB_DOC_PREFIX)
+    }
+
+    /// How the source is shown in prompts and citations: the URL of a web
+    /// page, otherwise the file path or attachment name.
+    pub fn label(&self) -> &str {
+        self.web_url().unwrap_or(&self.path)
+    }
+
+    /// Whether the user attached this source rather than search finding it.
+    pub fn is_attachment(&self) -> bool {
+        self.origin == SourceOrigin::Attachment
+    }
+}
+
+impl From<&SearchResult> for SourceRef {
+    fn from(result: &SearchResult) -> Self {
+        Self {
+            path: result.path.clone(),
+            score: result.score,
+            snippet: result.snippet.clone(),
+            origin: SourceOrigin::Index,
+        }
+    }
 }
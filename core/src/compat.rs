@@ -0,0 +1,229 @@
+//! Schema versions of serialized commands, responses and saved files.
+//!
+//! Serialized [`Command`](crate::Command)s and [`Response`](crate::Response)s
+//! carry a `schema_version` field, and saved files record theirs in a
+//! header. Data without a version is version 1. Readers upgrade older JSON
+//! to the current shape one version at a time before deserializing it, so
+//! old clients and old files keep working as the types change; versions
+//! newer than this build fail with [`UnsupportedVersion`].
+//!
+//! Fields added later take serde defaults and renamed fields keep their old
+//! name as an alias; only changes serde can't express need an upgrade step
+//! here.
+
+use serde_json::Value;
+use thiserror::Error;
+
+/// Version of the [`Command`](crate::Command) and
+/// [`Response`](crate::Response) JSON. Version 2 made
+/// `ResponsePayload::SearchResults` a page instead of a list; version 3
+/// gave `ResponsePayload::Error` a code.
+pub const SCHEMA_VERSION: u32 = 3;
+
+/// Key the version is stored under.
+pub const VERSION_FIELD: &str = "schema_version";
+
+/// Data written by a newer LucAstra than this one.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{format} has schema version {found}, but this build reads up to version {supported}")]
+pub struct UnsupportedVersion {
+    /// What was being read, e.g. `conversation`
+    pub format: &'static str,
+    pub found: u64,
+    pub supported: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SchemaError {
+    #[error(transparent)]
+    UnsupportedVersion(#[from] UnsupportedVersion),
+
+    #[error("invalid {format}: {message}")]
+    Invalid {
+        format: &'static str,
+        message: String,
+    },
+}
+
+impl SchemaError {
+    pub fn invalid(format: &'static str, message: impl ToString) -> Self {
+        Self::Invalid {
+            format,
+            message: message.to_string(),
+        }
+    }
+}
+
+/// `found` as a version this build reads, or why it can't.
+pub fn check_version(
+    format: &'static str,
+    found: u64,
+    supported: u32,
+) -> Result<u32, UnsupportedVersion> {
+    u32::try_from(found)
+        .ok()
+        .filter(|version| (1..=supported).contains(version))
+        .ok_or(UnsupportedVersion {
+            format,
+            found,
+            supported,
+        })
+}
+
+/// The `schema_version` of a JSON object, 1 when it has none.
+pub fn json_version(
+    format: &'static str,
+    value: &Value,
+    supported: u32,
+) -> Result<u32, SchemaError> {
+    match value.get(VERSION_FIELD) {
+        None => Ok(1),
+        Some(version) => {
+            let found = version.as_u64().ok_or_else(|| {
+                SchemaError::invalid(format, format!("{} must be a number", VERSION_FIELD))
+            })?;
+            Ok(check_version(format, found, supported)?)
+        }
+    }
+}
+
+/// Bring a serialized [`Command`](crate::Command) to the current shape.
+/// Commands haven't changed in a way serde can't read yet.
+pub fn upgrade_command(value: Value) -> Result<Value, SchemaError> {
+    json_version("command", &value, SCHEMA_VERSION)?;
+    Ok(value)
+}
+
+/// Bring a serialized [`Response`](crate::Response) to the current shape.
+pub fn upgrade_response(mut value: Value) -> Result<Value, SchemaError> {
+    let version = json_version("response", &value, SCHEMA_VERSION)?;
+    if version < 2 {
+        // `SearchResults([...])` became `SearchResults({"results": [...], ...})`
+        if let Some(results) = value.pointer_mut("/payload/SearchResults") {
+            if results.is_array() {
+                let total_hits = results.as_array().map_or(0, Vec::len);
+                *results = serde_json::json!({
+                    "results": results.take(),
+                    "total_hits": total_hits,
+                    "offset": 0,
+                });
+            }
+        }
+    }
+    if version < 3 {
+        // `Error("...")` became `Error({"code": ..., "message": "..."})`
+        if let Some(error) = value.pointer_mut("/payload/Error") {
+            if error.is_string() {
+                *error = serde_json::json!({
+                    "code": crate::ErrorCode::Internal,
+                    "message": error.take(),
+                });
+            }
+        }
+    }
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Command, CommandPayload, Response, ResponsePayload};
+    use serde_json::json;
+
+    const V1_COMMANDS: &str = include_str!("../tests/fixtures/v1/commands.json");
+    const V1_RESPONSES: &str = include_str!("../tests/fixtures/v1/responses.json");
+
+    #[test]
+    fn test_v1_commands_still_deserialize() {
+        let commands: Vec<Command> = serde_json::from_str(V1_COMMANDS).unwrap();
+        assert_eq!(commands.len(), 11);
+        assert_eq!(
+            commands[6].payload,
+            CommandPayload::Search {
+                query: "kernel".to_string(),
+                offset: 0,
+                limit: None,
+            }
+        );
+        assert_eq!(
+            commands[7].payload,
+            CommandPayload::Query {
+                text: "What is in my notes?".to_string(),
+                use_rag: Some(true),
+                conversation_id: None,
+                attachments: Vec::new(),
+            }
+        );
+    }
+
+    #[test]
+    fn test_v1_responses_are_upgraded() {
+        let responses: Vec<Response> = serde_json::from_str(V1_RESPONSES).unwrap();
+        assert_eq!(responses.len(), 7);
+        match &responses[3].payload {
+            ResponsePayload::SearchResults(page) => {
+                assert_eq!(page.results.len(), 2);
+                assert_eq!(page.total_hits, 2);
+                assert_eq!(page.offset, 0);
+                assert_eq!(page.results[1].path, "/mnt/root/todo.txt");
+                assert_eq!(page.results[1].line_number, None);
+            }
+            other => panic!("unexpected payload: {:?}", other),
+        }
+        match &responses[1].payload {
+            ResponsePayload::Files(files) => assert_eq!(files[0].modified, None),
+            other => panic!("unexpected payload: {:?}", other),
+        }
+        match &responses[6].payload {
+            ResponsePayload::Error { code, message } => {
+                assert_eq!(*code, crate::ErrorCode::Internal);
+                assert_eq!(message, "device not found: /dev/usb0");
+            }
+            other => panic!("unexpected payload: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_current_messages_are_versioned() {
+        let command = Command {
+            id: "cmd-1".to_string(),
+            payload: CommandPayload::Status,
+        };
+        let json = serde_json::to_value(&command).unwrap();
+        assert_eq!(json[VERSION_FIELD], SCHEMA_VERSION);
+        let response = Response {
+            command_id: "cmd-1".to_string(),
+            payload: ResponsePayload::SearchResults(Default::default()),
+        };
+        let json = serde_json::to_string(&response).unwrap();
+        assert!(matches!(
+            serde_json::from_str::<Response>(&json).unwrap().payload,
+            ResponsePayload::SearchResults(page) if page.results.is_empty()
+        ));
+    }
+
+    #[test]
+    fn test_newer_versions_are_refused() {
+        let future = json!({"schema_version": 99, "id": "x", "payload": {"Teleport": {}}});
+        assert_eq!(
+            Command::try_from(future).unwrap_err(),
+            SchemaError::UnsupportedVersion(UnsupportedVersion {
+                format: "command",
+                found: 99,
+                supported: SCHEMA_VERSION,
+            })
+        );
+        let error = serde_json::from_value::<Response>(
+            json!({"schema_version": 4, "command_id": "x", "payload": {"Status": "ok"}}),
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("schema version 4"));
+
+        let bad = json!({"schema_version": "two", "id": "x", "payload": "Status"});
+        assert!(matches!(
+            Command::try_from(bad),
+            Err(SchemaError::Invalid { .. })
+        ));
+        assert!(check_version("index", 0, 2).is_err());
+    }
+}
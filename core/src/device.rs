@@ -17,3 +17,18 @@ pub struct DeviceInfo {
     pub mounted: bool,
     pub mount_point: Option<String>,
 }
+
+/// A device appearing or disappearing between scans.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum DeviceEvent {
+    Added(DeviceInfo),
+    Removed(DeviceInfo),
+}
+
+impl DeviceEvent {
+    pub fn device(&self) -> &DeviceInfo {
+        match self {
+            DeviceEvent::Added(device) | DeviceEvent::Removed(device) => device,
+        }
+    }
+}
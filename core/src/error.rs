@@ -1,5 +1,10 @@
+use serde::{Deserialize, Serialize};
+use std::fmt;
 use thiserror::Error;
 
+/// An error from another crate, kept whole so callers can downcast to it.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
 #[derive(Debug, Error)]
 pub enum LuCastraError {
     #[error("device not found: {0}")]
@@ -11,6 +16,9 @@ pub enum LuCastraError {
     #[error("filesystem error: {0}")]
     FilesystemError(String),
 
+    #[error("file not found: {0}")]
+    FileNotFound(String),
+
     #[error("input error: {0}")]
     InputError(String),
 
@@ -20,11 +28,202 @@ pub enum LuCastraError {
     #[error("service error: {0}")]
     ServiceError(String),
 
+    #[error("permission denied: {0}")]
+    PermissionDenied(String),
+
     #[error("config error: {0}")]
     ConfigError(String),
 
     #[error("compat/syscall error: {0}")]
     SyscallError(String),
+
+    #[error("LLM unavailable: {0}")]
+    LlmUnavailable(String),
+
+    #[error(transparent)]
+    UnsupportedVersion(#[from] crate::compat::UnsupportedVersion),
+
+    /// A `ProviderError` from `lucastra-llm`, with the code its kind maps to.
+    #[error("LLM provider error: {source}")]
+    Provider {
+        code: ErrorCode,
+        #[source]
+        source: BoxError,
+    },
+
+    /// A `VectorError` from `lucastra-search`.
+    #[error("vector index error: {0}")]
+    Vector(#[source] BoxError),
+
+    /// A `ToolError` from `lucastra-tools`, with the code its kind maps to.
+    #[error("tool error: {source}")]
+    Tool {
+        code: ErrorCode,
+        #[source]
+        source: BoxError,
+    },
+
+    /// A `ConfigError` from `lucastra-config`.
+    #[error("config error: {0}")]
+    Config(#[source] BoxError),
+
+    /// `source`, saying what was being done when it happened. Added with
+    /// [`Context::context`].
+    #[error("{context}: {source}")]
+    Context {
+        context: String,
+        #[source]
+        source: Box<LuCastraError>,
+    },
+}
+
+impl LuCastraError {
+    /// What kind of failure this is.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::DeviceNotFound(_) | Self::FileNotFound(_) => ErrorCode::NotFound,
+            Self::InvalidCommand(_) | Self::UnsupportedVersion(_) => ErrorCode::InvalidRequest,
+            Self::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            Self::ConfigError(_) | Self::Config(_) => ErrorCode::Config,
+            Self::LlmUnavailable(_) => ErrorCode::LlmUnavailable,
+            Self::Provider { code, .. } | Self::Tool { code, .. } => *code,
+            Self::Context { source, .. } => source.code(),
+            Self::DeviceIoError(_)
+            | Self::FilesystemError(_)
+            | Self::InputError(_)
+            | Self::ServiceError(_)
+            | Self::SyscallError(_)
+            | Self::Vector(_) => ErrorCode::Internal,
+        }
+    }
+
+    /// This error as the source of one saying what was being done.
+    pub fn context(self, context: impl Into<String>) -> Self {
+        Self::Context {
+            context: context.into(),
+            source: Box::new(self),
+        }
+    }
+
+    /// The first error of type `E` among this one and its sources, for
+    /// matching on the cause behind added context.
+    pub fn find<E: std::error::Error + 'static>(&self) -> Option<&E> {
+        let mut error: Option<&(dyn std::error::Error + 'static)> = Some(self);
+        while let Some(current) = error {
+            if let Some(found) = current.downcast_ref::<E>() {
+                return Some(found);
+            }
+            error = current.source();
+        }
+        None
+    }
+}
+
+/// Category of a failed command, sent with its message so clients can
+/// react to the cause, e.g. with a translated explanation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// The LLM couldn't be reached.
+    LlmUnavailable,
+    /// The LLM provider rejected the credentials.
+    AuthFailed,
+    /// The LLM provider's rate limit was reached.
+    RateLimited,
+    PermissionDenied,
+    NotFound,
+    /// The command or its arguments are wrong.
+    InvalidRequest,
+    /// The configuration is invalid or couldn't be read or written.
+    Config,
+    /// The provider or tool doesn't support what was asked.
+    Unsupported,
+    /// Anything else, including codes added by newer versions.
+    #[serde(other)]
+    Internal,
+}
+
+impl ErrorCode {
+    /// The code as sent, e.g. `llm_unavailable`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::LlmUnavailable => "llm_unavailable",
+            Self::AuthFailed => "auth_failed",
+            Self::RateLimited => "rate_limited",
+            Self::PermissionDenied => "permission_denied",
+            Self::NotFound => "not_found",
+            Self::InvalidRequest => "invalid_request",
+            Self::Config => "config",
+            Self::Unsupported => "unsupported",
+            Self::Internal => "internal",
+        }
+    }
+}
+
+impl fmt::Display for ErrorCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
 pub type Result<T> = std::result::Result<T, LuCastraError>;
+
+/// Say what was being done when a result failed, keeping its error as the
+/// source: `index_path(path).context(format!("indexing {}", path))`.
+pub trait Context<T> {
+    fn context(self, context: impl Into<String>) -> Result<T>;
+
+    /// Like [`Context::context`], building the text only on failure.
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T>;
+}
+
+impl<T, E: Into<LuCastraError>> Context<T> for std::result::Result<T, E> {
+    fn context(self, context: impl Into<String>) -> Result<T> {
+        self.with_context(|| context)
+    }
+
+    fn with_context<C: Into<String>>(self, context: impl FnOnce() -> C) -> Result<T> {
+        self.map_err(|e| e.into().context(context()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_context_keeps_the_code_and_cause() {
+        let io = std::io::Error::other("disk on fire");
+        let error = Err::<(), _>(LuCastraError::Config(Box::new(io)))
+            .context("loading /etc/lucastra.toml")
+            .context("starting up")
+            .unwrap_err();
+
+        assert_eq!(
+            error.to_string(),
+            "starting up: loading /etc/lucastra.toml: config error: disk on fire"
+        );
+        assert_eq!(error.code(), ErrorCode::Config);
+        assert_eq!(
+            error.find::<std::io::Error>().map(|e| e.to_string()),
+            Some("disk on fire".to_string())
+        );
+        assert!(error.find::<crate::compat::UnsupportedVersion>().is_none());
+    }
+
+    #[test]
+    fn test_unknown_codes_read_as_internal() {
+        assert_eq!(
+            serde_json::from_str::<ErrorCode>("\"auth_failed\"").unwrap(),
+            ErrorCode::AuthFailed
+        );
+        assert_eq!(
+            serde_json::from_str::<ErrorCode>("\"quota_exceeded\"").unwrap(),
+            ErrorCode::Internal
+        );
+        assert_eq!(
+            serde_json::to_string(&ErrorCode::LlmUnavailable).unwrap(),
+            format!("\"{}\"", ErrorCode::LlmUnavailable)
+        );
+    }
+}
@@ -1,14 +1,23 @@
 use serde::{Deserialize, Serialize};
 
 pub mod command;
+pub mod compat;
 pub mod device;
 pub mod error;
 pub mod input;
+pub mod rpc;
+pub mod slash;
 
-pub use command::{Command, CommandPayload, Response, ResponsePayload};
-pub use device::{DeviceInfo, DeviceType};
-pub use error::{LuCastraError, Result};
+pub use command::{
+    Attachment, Command, CommandPayload, Response, ResponsePayload, SearchPage, SourceOrigin,
+    SourceRef, WEB_DOC_PREFIX,
+};
+pub use compat::{SchemaError, UnsupportedVersion, SCHEMA_VERSION};
+pub use device::{DeviceEvent, DeviceInfo, DeviceType};
+pub use error::{BoxError, Context, ErrorCode, LuCastraError, Result};
 pub use input::{InputEvent, InputEventType, KeyCode};
+pub use rpc::{RpcError, RpcRequest, RpcResponse};
+pub use slash::{ChatInput, SlashAction, SlashCommand, SlashError};
 
 #[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SystemState {
@@ -0,0 +1,196 @@
+//! Messages of the local JSON-RPC 2.0 API, one JSON object per line.
+//!
+//! Methods are the snake_case names of [`CommandPayload`] variants, taking
+//! the variant's fields as named params: `search` with `{"query": "notes"}`
+//! runs [`CommandPayload::Search`]. [`EXECUTE_TOOL`] runs a tool call and
+//! [`AUTHENTICATE`], which must come first, presents the API token.
+
+use crate::CommandPayload;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+pub const JSONRPC_VERSION: &str = "2.0";
+
+/// Method taking `{"token": "..."}`; every other method is refused until it
+/// succeeds.
+pub const AUTHENTICATE: &str = "authenticate";
+
+/// Method taking a tool call as its params, e.g.
+/// `{"tool": "Search", "params": {"query": "notes"}}`.
+pub const EXECUTE_TOOL: &str = "execute_tool";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcRequest {
+    pub jsonrpc: String,
+    #[serde(default)]
+    pub id: Value,
+    pub method: String,
+    #[serde(default)]
+    pub params: Value,
+}
+
+impl RpcRequest {
+    pub fn new(id: u64, method: impl Into<String>, params: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id: id.into(),
+            method: method.into(),
+            params,
+        }
+    }
+}
+
+/// Reply to one request; exactly one of `result` and `error` is set.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RpcResponse {
+    pub jsonrpc: String,
+    pub id: Value,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<RpcError>,
+}
+
+impl RpcResponse {
+    pub fn result(id: Value, result: Value) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn error(id: Value, error: RpcError) -> Self {
+        Self {
+            jsonrpc: JSONRPC_VERSION.to_string(),
+            id,
+            result: None,
+            error: Some(error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, Error)]
+#[error("{message} (code {code})")]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+}
+
+impl RpcError {
+    /// The line isn't JSON.
+    pub const PARSE_ERROR: i64 = -32700;
+    /// The JSON isn't a JSON-RPC 2.0 request.
+    pub const INVALID_REQUEST: i64 = -32600;
+    pub const METHOD_NOT_FOUND: i64 = -32601;
+    pub const INVALID_PARAMS: i64 = -32602;
+    /// The command ran and failed.
+    pub const COMMAND_FAILED: i64 = -32000;
+    /// No valid token was presented; the connection is closed.
+    pub const UNAUTHORIZED: i64 = -32001;
+    /// The connection used up its requests; it is closed.
+    pub const LIMIT_REACHED: i64 = -32002;
+
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// The command `method` names, built from `params`. `null` or `{}` params
+/// suit commands without fields.
+pub fn command_payload(method: &str, params: Value) -> Result<CommandPayload, RpcError> {
+    let not_found = || {
+        RpcError::new(
+            RpcError::METHOD_NOT_FOUND,
+            format!("Unknown method {}", method),
+        )
+    };
+    let snake_case = method.chars().all(|c| c.is_ascii_lowercase() || c == '_');
+    if !snake_case || method.split('_').any(str::is_empty) {
+        return Err(not_found());
+    }
+
+    let variant: String = method.split('_').map(capitalize).collect();
+    let value = match params {
+        Value::Null => Value::String(variant),
+        Value::Object(fields) if fields.is_empty() => Value::String(variant),
+        params => serde_json::json!({ variant: params }),
+    };
+    serde_json::from_value(value).map_err(|e| {
+        if e.to_string().starts_with("unknown variant") {
+            not_found()
+        } else {
+            RpcError::new(
+                RpcError::INVALID_PARAMS,
+                format!("Invalid params for {}: {}", method, e),
+            )
+        }
+    })
+}
+
+/// `list` -> `List`
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    chars
+        .next()
+        .map(|first| first.to_ascii_uppercase().to_string() + chars.as_str())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_methods_map_to_commands() {
+        assert_eq!(
+            command_payload("list_devices", Value::Null),
+            Ok(CommandPayload::ListDevices)
+        );
+        assert_eq!(
+            command_payload("test_llm_connection", json!({})),
+            Ok(CommandPayload::TestLlmConnection)
+        );
+        assert_eq!(
+            command_payload("search", json!({"query": "notes", "limit": 3})),
+            Ok(CommandPayload::Search {
+                query: "notes".to_string(),
+                offset: 0,
+                limit: Some(3),
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_methods_and_bad_params() {
+        for method in ["reboot", "Search", "search_", ""] {
+            let error = command_payload(method, Value::Null).unwrap_err();
+            assert_eq!(error.code, RpcError::METHOD_NOT_FOUND, "{}", method);
+        }
+        let error = command_payload("search", json!({"limit": 3})).unwrap_err();
+        assert_eq!(error.code, RpcError::INVALID_PARAMS);
+        assert!(error.message.contains("missing field `query`"));
+        let error = command_payload("read_file", Value::Null).unwrap_err();
+        assert_eq!(error.code, RpcError::INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_messages_are_json_rpc() {
+        let request = RpcRequest::new(7, "status", Value::Null);
+        assert_eq!(
+            serde_json::to_value(&request).unwrap(),
+            json!({"jsonrpc": "2.0", "id": 7, "method": "status", "params": null})
+        );
+        let response = RpcResponse::error(json!(7), RpcError::new(RpcError::UNAUTHORIZED, "no"));
+        assert_eq!(
+            serde_json::to_string(&response).unwrap(),
+            r#"{"jsonrpc":"2.0","id":7,"error":{"code":-32001,"message":"no"}}"#
+        );
+    }
+}
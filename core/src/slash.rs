@@ -0,0 +1,406 @@
+//! Slash commands typed into a chat box, such as `/search foo` or `/status`.
+//!
+//! [`SLASH_COMMANDS`] is the one list of commands; parsing, completion and
+//! `/help` are all driven from it.
+
+use crate::CommandPayload;
+use thiserror::Error;
+
+/// What a slash command asks for.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SlashAction {
+    /// Run a command against the system.
+    Run(CommandPayload),
+    /// Clear the conversation.
+    Clear,
+    /// Open the settings.
+    Settings,
+    /// List the commands.
+    Help,
+}
+
+/// What a line typed into the chat box is.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChatInput {
+    /// Text for the model.
+    Message(String),
+    Command(SlashAction),
+}
+
+/// What follows a command's name.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Arg {
+    None,
+    /// The rest of the line, as typed.
+    Rest,
+    /// One word, quoted if it has spaces.
+    Word,
+}
+
+/// A command in the registry.
+#[derive(Debug, Clone, Copy)]
+pub struct SlashCommand {
+    /// Name without the slash
+    pub name: &'static str,
+    /// Argument placeholder for help, e.g. `<query>`
+    pub usage: &'static str,
+    pub description: &'static str,
+    arg: Arg,
+    action: fn(String) -> SlashAction,
+}
+
+impl SlashCommand {
+    /// `/name <arg>`
+    pub fn synopsis(&self) -> String {
+        if self.usage.is_empty() {
+            format!("/{}", self.name)
+        } else {
+            format!("/{} {}", self.name, self.usage)
+        }
+    }
+}
+
+/// Every slash command, in help order.
+pub static SLASH_COMMANDS: [SlashCommand; 7] = [
+    SlashCommand {
+        name: "devices",
+        usage: "",
+        description: "List connected devices",
+        arg: Arg::None,
+        action: |_| SlashAction::Run(CommandPayload::ListDevices),
+    },
+    SlashCommand {
+        name: "search",
+        usage: "<query>",
+        description: "Search indexed documents",
+        arg: Arg::Rest,
+        action: |query| {
+            SlashAction::Run(CommandPayload::Search {
+                query,
+                offset: 0,
+                limit: None,
+            })
+        },
+    },
+    SlashCommand {
+        name: "read",
+        usage: "<path>",
+        description: "Show a file",
+        arg: Arg::Word,
+        action: |path| SlashAction::Run(CommandPayload::ReadFile { path }),
+    },
+    SlashCommand {
+        name: "status",
+        usage: "",
+        description: "Show system status",
+        arg: Arg::None,
+        action: |_| SlashAction::Run(CommandPayload::Status),
+    },
+    SlashCommand {
+        name: "clear",
+        usage: "",
+        description: "Clear the conversation",
+        arg: Arg::None,
+        action: |_| SlashAction::Clear,
+    },
+    SlashCommand {
+        name: "settings",
+        usage: "",
+        description: "Open the settings",
+        arg: Arg::None,
+        action: |_| SlashAction::Settings,
+    },
+    SlashCommand {
+        name: "help",
+        usage: "",
+        description: "List these commands",
+        arg: Arg::None,
+        action: |_| SlashAction::Help,
+    },
+];
+
+/// Why a slash command couldn't be parsed.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum SlashError {
+    #[error("Unknown command /{name}. {}", suggest(.suggestion))]
+    Unknown {
+        name: String,
+        suggestion: Option<&'static str>,
+    },
+
+    #[error("/{0} needs an argument: {1}")]
+    MissingArgument(&'static str, String),
+
+    #[error("Too many arguments; usage: {0} (quote arguments with spaces)")]
+    TooManyArguments(String),
+
+    #[error("Unclosed quote in \"{0}\"")]
+    UnclosedQuote(String),
+}
+
+fn suggest(suggestion: &Option<&'static str>) -> String {
+    match suggestion {
+        Some(name) => format!("Did you mean /{}?", name),
+        None => format!("Commands: {}", command_names().join(", ")),
+    }
+}
+
+fn command_names() -> Vec<String> {
+    SLASH_COMMANDS
+        .iter()
+        .map(|command| format!("/{}", command.name))
+        .collect()
+}
+
+/// Read a line from the chat box. Lines not starting with `/` are messages;
+/// `//` sends a message that starts with a single slash.
+pub fn parse_input(input: &str) -> Result<ChatInput, SlashError> {
+    let input = input.trim();
+    let Some(line) = input.strip_prefix('/') else {
+        return Ok(ChatInput::Message(input.to_string()));
+    };
+    if line.starts_with('/') {
+        return Ok(ChatInput::Message(line.to_string()));
+    }
+
+    let (name, rest) = line
+        .split_once(char::is_whitespace)
+        .map(|(name, rest)| (name, rest.trim()))
+        .unwrap_or((line, ""));
+    let Some(command) = find(name) else {
+        return Err(SlashError::Unknown {
+            name: name.to_string(),
+            suggestion: closest(name),
+        });
+    };
+
+    let arg = match command.arg {
+        Arg::None if rest.is_empty() => String::new(),
+        Arg::None => return Err(SlashError::TooManyArguments(command.synopsis())),
+        Arg::Rest if rest.is_empty() => {
+            return Err(SlashError::MissingArgument(
+                command.name,
+                command.synopsis(),
+            ))
+        }
+        Arg::Rest => rest.to_string(),
+        Arg::Word => {
+            let mut words = split_words(rest)?.into_iter();
+            match (words.next(), words.next()) {
+                (Some(_), Some(_)) => return Err(SlashError::TooManyArguments(command.synopsis())),
+                (Some(word), None) if !word.is_empty() => word,
+                _ => {
+                    return Err(SlashError::MissingArgument(
+                        command.name,
+                        command.synopsis(),
+                    ))
+                }
+            }
+        }
+    };
+    Ok(ChatInput::Command((command.action)(arg)))
+}
+
+/// Commands whose names start with what has been typed after the `/`, for
+/// completion. Empty once the name is followed by a space.
+pub fn completions(input: &str) -> Vec<&'static SlashCommand> {
+    match input.trim_start().strip_prefix('/') {
+        Some(prefix) if !prefix.contains(char::is_whitespace) && !prefix.starts_with('/') => {
+            let prefix = prefix.to_lowercase();
+            SLASH_COMMANDS
+                .iter()
+                .filter(|command| command.name.starts_with(&prefix))
+                .collect()
+        }
+        _ => Vec::new(),
+    }
+}
+
+/// One line per command for `/help`.
+pub fn help_text() -> String {
+    let width = SLASH_COMMANDS
+        .iter()
+        .map(|command| command.synopsis().len())
+        .max()
+        .unwrap_or(0);
+    SLASH_COMMANDS
+        .iter()
+        .map(|command| {
+            format!(
+                "{:width$}  {}",
+                command.synopsis(),
+                command.description,
+                width = width
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn find(name: &str) -> Option<&'static SlashCommand> {
+    let name = name.to_lowercase();
+    SLASH_COMMANDS.iter().find(|command| command.name == name)
+}
+
+/// Nearest command within two edits of `name`.
+fn closest(name: &str) -> Option<&'static str> {
+    let name = name.to_lowercase();
+    SLASH_COMMANDS
+        .iter()
+        .map(|command| (edit_distance(&name, command.name), command.name))
+        .filter(|(distance, _)| *distance <= 2)
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, name)| name)
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+/// Split `text` on whitespace, keeping `"double"` or `'single'` quoted runs
+/// together.
+fn split_words(text: &str) -> Result<Vec<String>, SlashError> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote: Option<char> = None;
+    for c in text.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.get_or_insert_with(String::new).push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                word.get_or_insert_with(String::new);
+            }
+            None if c.is_whitespace() => words.extend(word.take()),
+            None => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(SlashError::UnclosedQuote(text.to_string()));
+    }
+    words.extend(word);
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(payload: CommandPayload) -> Result<ChatInput, SlashError> {
+        Ok(ChatInput::Command(SlashAction::Run(payload)))
+    }
+
+    #[test]
+    fn test_plain_text_passes_through() {
+        assert_eq!(
+            parse_input("  what is /status for? "),
+            Ok(ChatInput::Message("what is /status for?".to_string()))
+        );
+        assert_eq!(
+            parse_input("//etc is a directory"),
+            Ok(ChatInput::Message("/etc is a directory".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_commands_and_arguments() {
+        assert_eq!(parse_input("/devices"), run(CommandPayload::ListDevices));
+        assert_eq!(
+            parse_input("/Status"),
+            run(CommandPayload::Status),
+            "names are case-insensitive"
+        );
+        assert_eq!(
+            parse_input("/clear"),
+            Ok(ChatInput::Command(SlashAction::Clear))
+        );
+        // The query is the rest of the line, quotes and all
+        assert_eq!(
+            parse_input("/search  \"model size\" -draft"),
+            run(CommandPayload::Search {
+                query: "\"model size\" -draft".to_string(),
+                offset: 0,
+                limit: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_quoted_paths() {
+        assert_eq!(
+            parse_input("/read \"my notes/todo list.txt\""),
+            run(CommandPayload::ReadFile {
+                path: "my notes/todo list.txt".to_string()
+            })
+        );
+        assert_eq!(
+            parse_input("/read 'it''s.txt'"),
+            run(CommandPayload::ReadFile {
+                path: "its.txt".to_string()
+            })
+        );
+        assert_eq!(
+            parse_input("/read \"unfinished"),
+            Err(SlashError::UnclosedQuote("\"unfinished".to_string()))
+        );
+        assert_eq!(
+            parse_input("/read my notes.txt").unwrap_err().to_string(),
+            "Too many arguments; usage: /read <path> (quote arguments with spaces)"
+        );
+    }
+
+    #[test]
+    fn test_missing_arguments() {
+        assert_eq!(
+            parse_input("/search   ").unwrap_err().to_string(),
+            "/search needs an argument: /search <query>"
+        );
+        for input in ["/read", "/read \"\""] {
+            assert!(matches!(
+                parse_input(input),
+                Err(SlashError::MissingArgument("read", _))
+            ));
+        }
+        assert!(matches!(
+            parse_input("/status now"),
+            Err(SlashError::TooManyArguments(_))
+        ));
+    }
+
+    #[test]
+    fn test_unknown_commands_suggest_or_list() {
+        assert_eq!(
+            parse_input("/serch foo").unwrap_err().to_string(),
+            "Unknown command /serch. Did you mean /search?"
+        );
+        assert_eq!(
+            parse_input("/reboot").unwrap_err().to_string(),
+            "Unknown command /reboot. Commands: /devices, /search, /read, /status, /clear, \
+             /settings, /help"
+        );
+    }
+
+    #[test]
+    fn test_completions_and_help() {
+        let names: Vec<_> = completions("/s").iter().map(|c| c.name).collect();
+        assert_eq!(names, ["search", "status", "settings"]);
+        assert_eq!(completions("/").len(), SLASH_COMMANDS.len());
+        assert!(completions("/search foo").is_empty());
+        assert!(completions("hello").is_empty());
+
+        let help = help_text();
+        assert_eq!(help.lines().count(), SLASH_COMMANDS.len());
+        assert!(help.contains("/search <query>  Search indexed documents"));
+    }
+}
@@ -1,60 +1,251 @@
-use lucastra_core::{DeviceInfo, DeviceType, Result};
-use std::collections::HashMap;
-use tracing::info;
+mod scan;
+
+#[cfg(target_os = "linux")]
+pub use scan::LinuxDeviceScanner;
+
+use lucastra_core::{DeviceEvent, DeviceInfo, DeviceType, Result};
+use lucastra_hal::{RemovableMediaDetector, SystemRemovableMedia};
+use std::collections::{HashMap, VecDeque};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// Default interval between hotplug polls.
+pub const DEFAULT_HOTPLUG_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Source of the devices currently attached.
+pub trait DeviceEnumerator: Send + Sync {
+    fn enumerate(&self) -> Vec<DeviceInfo>;
+}
+
+/// Platform scan plus removable volumes reported by a detector.
+pub struct SystemEnumerator {
+    detector: Arc<dyn RemovableMediaDetector>,
+}
+
+impl SystemEnumerator {
+    pub fn new(detector: Arc<dyn RemovableMediaDetector>) -> Self {
+        Self { detector }
+    }
+}
+
+impl DeviceEnumerator for SystemEnumerator {
+    fn enumerate(&self) -> Vec<DeviceInfo> {
+        let mut devices = scan::platform_devices();
+
+        // Removable volumes the platform scan may not cover (e.g. macOS)
+        for removable in self.detector.removable_devices() {
+            if devices.iter().any(|d| d.path == removable.device) {
+                continue;
+            }
+            let mount_point = removable
+                .mount_point
+                .map(|p| p.to_string_lossy().into_owned());
+            devices.push(DeviceInfo {
+                path: removable.device,
+                device_type: DeviceType::BlockDevice,
+                name: removable.name,
+                size_bytes: removable.size_bytes,
+                mounted: mount_point.is_some(),
+                mount_point,
+            });
+        }
+        devices
+    }
+}
+
+fn by_path(devices: Vec<DeviceInfo>) -> HashMap<String, DeviceInfo> {
+    devices
+        .into_iter()
+        .map(|device| (device.path.clone(), device))
+        .collect()
+}
+
+/// Devices in `found` but not `known` are added; the reverse are removed.
+fn diff(
+    known: &HashMap<String, DeviceInfo>,
+    found: &HashMap<String, DeviceInfo>,
+) -> Vec<DeviceEvent> {
+    let added = found
+        .iter()
+        .filter(|(path, _)| !known.contains_key(*path))
+        .map(|(_, device)| DeviceEvent::Added(device.clone()));
+    let removed = known
+        .iter()
+        .filter(|(path, _)| !found.contains_key(*path))
+        .map(|(_, device)| DeviceEvent::Removed(device.clone()));
+    added.chain(removed).collect()
+}
+
+/// Background thread that re-enumerates devices on an interval and sends
+/// each device list that differs from the one before. The manager diffs
+/// them against its own map, which scans may have changed since.
+struct HotplugWatcher {
+    stop: Sender<()>,
+    snapshots: Receiver<HashMap<String, DeviceInfo>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl HotplugWatcher {
+    fn start(
+        enumerator: Arc<dyn DeviceEnumerator>,
+        mut last: HashMap<String, DeviceInfo>,
+        interval: Duration,
+    ) -> Self {
+        let (stop_tx, stop_rx) = mpsc::channel();
+        let (snapshot_tx, snapshot_rx) = mpsc::channel();
+
+        let worker = std::thread::spawn(move || {
+            while let Err(RecvTimeoutError::Timeout) = stop_rx.recv_timeout(interval) {
+                let found = by_path(enumerator.enumerate());
+                if diff(&last, &found).is_empty() {
+                    continue;
+                }
+                if snapshot_tx.send(found.clone()).is_err() {
+                    return;
+                }
+                last = found;
+            }
+        });
+
+        Self {
+            stop: stop_tx,
+            snapshots: snapshot_rx,
+            worker: Some(worker),
+        }
+    }
+}
+
+impl Drop for HotplugWatcher {
+    fn drop(&mut self) {
+        let _ = self.stop.send(());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
 
 /// Device manager service: enumerates USB and input devices.
 pub struct DeviceManager {
     devices: HashMap<String, DeviceInfo>,
+    enumerator: Arc<dyn DeviceEnumerator>,
+    /// Hotplug events applied but not yet taken with [`DeviceManager::next_event`].
+    events: VecDeque<DeviceEvent>,
+    watcher: Option<HotplugWatcher>,
 }
 
 impl DeviceManager {
     pub fn new() -> Self {
         Self {
             devices: HashMap::new(),
+            enumerator: Arc::new(SystemEnumerator::new(Arc::new(SystemRemovableMedia))),
+            events: VecDeque::new(),
+            watcher: None,
         }
     }
 
-    /// Scan for USB block devices and input devices (polling-based for MVP).
+    /// Use `detector` to find removable block devices.
+    pub fn with_detector(self, detector: Arc<dyn RemovableMediaDetector>) -> Self {
+        self.with_enumerator(Arc::new(SystemEnumerator::new(detector)))
+    }
+
+    /// Use `enumerator` for scans and hotplug polling.
+    pub fn with_enumerator(mut self, enumerator: Arc<dyn DeviceEnumerator>) -> Self {
+        self.enumerator = enumerator;
+        self
+    }
+
+    /// Enumerate attached block and input devices, logging any that were
+    /// plugged in or removed since the previous scan.
     pub fn scan(&mut self) -> Result<()> {
         info!("Scanning for devices...");
 
-        // Mock USB device detection. In a real OS, enumerate /proc/scsi, lsblk, or libusb.
-        let usb_device = DeviceInfo {
-            path: "/dev/usb0".to_string(),
-            device_type: DeviceType::BlockDevice,
-            name: "USB Storage".to_string(),
-            size_bytes: Some(1024 * 1024 * 1024), // 1GB mock
-            mounted: false,
-            mount_point: None,
-        };
-        self.devices.insert("/dev/usb0".to_string(), usb_device);
+        let found = by_path(self.enumerator.enumerate());
+        for event in diff(&self.devices, &found) {
+            self.apply(event);
+        }
+        // Refresh sizes and mount points of devices that stayed
+        self.devices.extend(found);
 
-        // Mock keyboard device.
-        let kbd_device = DeviceInfo {
-            path: "/dev/input/kbd0".to_string(),
-            device_type: DeviceType::InputDevice,
-            name: "Keyboard".to_string(),
-            size_bytes: None,
-            mounted: false,
-            mount_point: None,
-        };
-        self.devices
-            .insert("/dev/input/kbd0".to_string(), kbd_device);
+        info!("Device scan complete: {} devices found", self.devices.len());
+        Ok(())
+    }
 
-        // Mock mouse device.
-        let mouse_device = DeviceInfo {
-            path: "/dev/input/mouse0".to_string(),
-            device_type: DeviceType::InputDevice,
-            name: "Mouse".to_string(),
-            size_bytes: None,
-            mounted: false,
-            mount_point: None,
+    /// Poll for hotplug changes every `interval` in the background. Changes
+    /// are picked up by [`DeviceManager::poll_events`].
+    pub fn watch(&mut self, interval: Duration) {
+        if self.watcher.is_none() {
+            info!("Watching for device changes every {:?}", interval);
+            self.watcher = Some(HotplugWatcher::start(
+                self.enumerator.clone(),
+                self.devices.clone(),
+                interval,
+            ));
+        }
+    }
+
+    /// Stop the hotplug poller.
+    pub fn stop_watching(&mut self) {
+        self.watcher.take();
+    }
+
+    pub fn is_watching(&self) -> bool {
+        self.watcher.is_some()
+    }
+
+    /// Apply hotplug changes reported by the poller and buffer them. The
+    /// latest device list is diffed against the current map, so devices a
+    /// scan already picked up aren't reported again.
+    pub fn poll_events(&mut self) {
+        let Some(found) = self
+            .watcher
+            .as_ref()
+            .and_then(|watcher| watcher.snapshots.try_iter().last())
+        else {
+            return;
         };
-        self.devices
-            .insert("/dev/input/mouse0".to_string(), mouse_device);
+        for event in diff(&self.devices, &found) {
+            let applied = self.apply(event);
+            self.events.push_back(applied);
+        }
+    }
 
-        info!("Device scan complete: {} devices found", self.devices.len());
-        Ok(())
+    /// Retrieve the next buffered device event (non-blocking).
+    pub fn next_event(&mut self) -> Option<DeviceEvent> {
+        self.events.pop_front()
+    }
+
+    /// Check if there are pending device events.
+    pub fn has_events(&self) -> bool {
+        !self.events.is_empty()
+    }
+
+    /// Update the device map for `event`. A removed device that was mounted
+    /// is unmounted first; the returned event keeps its last mount point.
+    fn apply(&mut self, event: DeviceEvent) -> DeviceEvent {
+        match event {
+            DeviceEvent::Added(device) => {
+                info!("Device added: {} ({})", device.path, device.name);
+                self.devices.insert(device.path.clone(), device.clone());
+                DeviceEvent::Added(device)
+            }
+            DeviceEvent::Removed(device) => {
+                let last = self.devices.get(&device.path).cloned().unwrap_or(device);
+                if last.mounted {
+                    warn!(
+                        "Mounted device {} was removed; unmounting {}",
+                        last.path,
+                        last.mount_point.as_deref().unwrap_or("?")
+                    );
+                    let _ = self.unmount_device(&last.path);
+                }
+                info!("Device removed: {}", last.path);
+                self.devices.remove(&last.path);
+                DeviceEvent::Removed(last)
+            }
+        }
     }
 
     /// List all detected devices.
@@ -104,3 +295,152 @@ impl Default for DeviceManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_hal::RemovableDevice;
+    use std::path::{Path, PathBuf};
+
+    struct FakeDetector(Vec<RemovableDevice>);
+
+    impl RemovableMediaDetector for FakeDetector {
+        fn is_removable(&self, path: &Path) -> bool {
+            self.0
+                .iter()
+                .any(|d| d.mount_point.as_ref().is_some_and(|m| path.starts_with(m)))
+        }
+
+        fn removable_devices(&self) -> Vec<RemovableDevice> {
+            self.0.clone()
+        }
+    }
+
+    #[test]
+    fn test_scan_reports_detected_removable_devices() {
+        let detector = FakeDetector(vec![RemovableDevice {
+            device: "/dev/sdz1".to_string(),
+            name: "Cruzer Blade".to_string(),
+            size_bytes: Some(16 * 1024 * 1024 * 1024),
+            mount_point: Some(PathBuf::from("/media/me/CRUZER")),
+        }]);
+        let mut manager = DeviceManager::new().with_detector(Arc::new(detector));
+        manager.scan().unwrap();
+
+        let stick = manager.get_device("/dev/sdz1").unwrap();
+        assert_eq!(stick.name, "Cruzer Blade");
+        assert!(stick.mounted);
+        assert_eq!(stick.mount_point.as_deref(), Some("/media/me/CRUZER"));
+    }
+
+    #[test]
+    fn test_scan_drops_unplugged_devices() {
+        let mut manager =
+            DeviceManager::new().with_detector(Arc::new(FakeDetector(vec![RemovableDevice {
+                device: "E:\\".to_string(),
+                name: "Removable Drive (E:)".to_string(),
+                size_bytes: None,
+                mount_point: None,
+            }])));
+        manager.scan().unwrap();
+        assert!(manager.get_device("E:\\").is_ok());
+
+        manager = manager.with_detector(Arc::new(FakeDetector(Vec::new())));
+        manager.scan().unwrap();
+        assert!(manager.get_device("E:\\").is_err());
+    }
+
+    #[test]
+    fn test_rescan_picks_up_new_devices() {
+        let mut manager = DeviceManager::new().with_detector(Arc::new(FakeDetector(Vec::new())));
+        manager.scan().unwrap();
+        let before = manager.list_devices().unwrap().len();
+        assert!(manager.get_device("/dev/input/kbd0").is_ok());
+
+        manager = manager.with_detector(Arc::new(FakeDetector(vec![RemovableDevice {
+            device: "/dev/sdz1".to_string(),
+            name: "Removable Storage".to_string(),
+            size_bytes: None,
+            mount_point: None,
+        }])));
+        manager.scan().unwrap();
+        assert_eq!(manager.list_devices().unwrap().len(), before + 1);
+    }
+
+    /// Reports whatever device list the test last set.
+    struct FakeEnumerator(std::sync::Mutex<Vec<DeviceInfo>>);
+
+    impl DeviceEnumerator for FakeEnumerator {
+        fn enumerate(&self) -> Vec<DeviceInfo> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    fn block_device(path: &str) -> DeviceInfo {
+        DeviceInfo {
+            path: path.to_string(),
+            device_type: DeviceType::BlockDevice,
+            name: "USB Storage".to_string(),
+            size_bytes: Some(1024),
+            mounted: false,
+            mount_point: None,
+        }
+    }
+
+    #[test]
+    fn test_watch_reports_hotplug_and_unmounts_removed_devices() {
+        let enumerator = Arc::new(FakeEnumerator(std::sync::Mutex::new(vec![block_device(
+            "/dev/sdx1",
+        )])));
+        let mut manager = DeviceManager::new().with_enumerator(enumerator.clone());
+        manager.scan().unwrap();
+        manager.mount_device("/dev/sdx1", "/media/stick").unwrap();
+        manager.watch(Duration::from_millis(10));
+
+        *enumerator.0.lock().unwrap() = vec![block_device("/dev/sdy1")];
+
+        let mut events = Vec::new();
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while events.len() < 2 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+            manager.poll_events();
+            while let Some(event) = manager.next_event() {
+                events.push(event);
+            }
+        }
+        manager.stop_watching();
+
+        assert!(events
+            .iter()
+            .any(|e| matches!(e, DeviceEvent::Added(d) if d.path == "/dev/sdy1")));
+        let removed = events
+            .iter()
+            .find_map(|e| match e {
+                DeviceEvent::Removed(d) => Some(d),
+                _ => None,
+            })
+            .expect("removal event");
+        assert_eq!(removed.mount_point.as_deref(), Some("/media/stick"));
+        assert!(manager.get_device("/dev/sdx1").is_err());
+        assert!(manager.get_device("/dev/sdy1").is_ok());
+    }
+
+    #[test]
+    fn test_devices_found_by_a_scan_are_not_reported_again() {
+        let enumerator = Arc::new(FakeEnumerator(std::sync::Mutex::new(vec![block_device(
+            "/dev/sdx1",
+        )])));
+        let mut manager = DeviceManager::new().with_enumerator(enumerator.clone());
+        manager.scan().unwrap();
+        manager.watch(Duration::from_millis(10));
+
+        *enumerator.0.lock().unwrap() = vec![block_device("/dev/sdx1"), block_device("/dev/sdy1")];
+        manager.scan().unwrap();
+        std::thread::sleep(Duration::from_millis(200));
+        manager.poll_events();
+        manager.stop_watching();
+
+        assert!(manager.next_event().is_none());
+        assert!(manager.get_device("/dev/sdy1").is_ok());
+    }
+}
@@ -0,0 +1,324 @@
+//! Platform device enumeration for [`DeviceManager::scan`](crate::DeviceManager::scan).
+
+use lucastra_core::DeviceInfo;
+
+/// Devices currently attached to this machine.
+#[cfg(any(test, feature = "mock-devices"))]
+pub(crate) fn platform_devices() -> Vec<DeviceInfo> {
+    mock_devices()
+}
+
+#[cfg(all(not(any(test, feature = "mock-devices")), target_os = "linux"))]
+pub(crate) fn platform_devices() -> Vec<DeviceInfo> {
+    LinuxDeviceScanner::system().scan()
+}
+
+#[cfg(all(not(any(test, feature = "mock-devices")), windows))]
+pub(crate) fn platform_devices() -> Vec<DeviceInfo> {
+    windows_volumes()
+}
+
+#[cfg(all(
+    not(any(test, feature = "mock-devices")),
+    not(any(target_os = "linux", windows))
+))]
+pub(crate) fn platform_devices() -> Vec<DeviceInfo> {
+    Vec::new()
+}
+
+/// Fixed device list so tests do not depend on the host's hardware.
+#[cfg(any(test, feature = "mock-devices"))]
+fn mock_devices() -> Vec<DeviceInfo> {
+    use lucastra_core::DeviceType;
+
+    vec![
+        DeviceInfo {
+            path: "/dev/usb0".to_string(),
+            device_type: DeviceType::BlockDevice,
+            name: "USB Storage".to_string(),
+            size_bytes: Some(1024 * 1024 * 1024),
+            mounted: false,
+            mount_point: None,
+        },
+        DeviceInfo {
+            path: "/dev/input/kbd0".to_string(),
+            device_type: DeviceType::InputDevice,
+            name: "Keyboard".to_string(),
+            size_bytes: None,
+            mounted: false,
+            mount_point: None,
+        },
+        DeviceInfo {
+            path: "/dev/input/mouse0".to_string(),
+            device_type: DeviceType::InputDevice,
+            name: "Mouse".to_string(),
+            size_bytes: None,
+            mounted: false,
+            mount_point: None,
+        },
+    ]
+}
+
+#[cfg(target_os = "linux")]
+pub use linux::LinuxDeviceScanner;
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use lucastra_core::{DeviceInfo, DeviceType};
+    use std::collections::HashMap;
+    use std::fs;
+    use std::path::PathBuf;
+
+    /// Reads block devices from `/proc/partitions` and `/sys/block`, mount
+    /// points from `/proc/mounts` and input devices from `/dev/input/by-id`.
+    #[derive(Debug, Clone)]
+    pub struct LinuxDeviceScanner {
+        partitions: PathBuf,
+        sys_block: PathBuf,
+        mounts: PathBuf,
+        input_by_id: PathBuf,
+    }
+
+    impl LinuxDeviceScanner {
+        pub fn new(
+            partitions: impl Into<PathBuf>,
+            sys_block: impl Into<PathBuf>,
+            mounts: impl Into<PathBuf>,
+            input_by_id: impl Into<PathBuf>,
+        ) -> Self {
+            Self {
+                partitions: partitions.into(),
+                sys_block: sys_block.into(),
+                mounts: mounts.into(),
+                input_by_id: input_by_id.into(),
+            }
+        }
+
+        pub fn system() -> Self {
+            Self::new(
+                "/proc/partitions",
+                "/sys/block",
+                "/proc/mounts",
+                "/dev/input/by-id",
+            )
+        }
+
+        pub fn scan(&self) -> Vec<DeviceInfo> {
+            let mut devices = self.block_devices();
+            devices.extend(self.input_devices());
+            devices
+        }
+
+        /// Device node to mount point, resolving `/dev/disk/by-*` links.
+        fn mount_points(&self) -> HashMap<String, String> {
+            let contents = fs::read_to_string(&self.mounts).unwrap_or_default();
+            contents
+                .lines()
+                .filter_map(|line| {
+                    let mut fields = line.split_whitespace();
+                    let device = fields.next()?;
+                    let mount_point = fields.next()?.replace("\\040", " ");
+                    let device = fs::canonicalize(device)
+                        .map(|p| p.to_string_lossy().into_owned())
+                        .unwrap_or_else(|_| device.to_string());
+                    Some((device, mount_point))
+                })
+                .collect()
+        }
+
+        /// Model of the disk `name` belongs to, looked up in `/sys/block`.
+        fn model(&self, name: &str) -> Option<String> {
+            let disk_dir = if self.sys_block.join(name).is_dir() {
+                self.sys_block.join(name)
+            } else {
+                fs::read_dir(&self.sys_block)
+                    .ok()?
+                    .filter_map(|e| e.ok())
+                    .map(|e| e.path())
+                    .find(|disk| disk.join(name).is_dir())?
+            };
+            fs::read_to_string(disk_dir.join("device").join("model"))
+                .ok()
+                .map(|model| model.trim().to_string())
+                .filter(|model| !model.is_empty())
+        }
+
+        fn block_devices(&self) -> Vec<DeviceInfo> {
+            let contents = fs::read_to_string(&self.partitions).unwrap_or_default();
+            let mounts = self.mount_points();
+
+            contents
+                .lines()
+                .filter_map(|line| {
+                    let fields: Vec<&str> = line.split_whitespace().collect();
+                    let [_major, _minor, blocks, name] = fields[..] else {
+                        return None;
+                    };
+                    let blocks: u64 = blocks.parse().ok()?;
+                    // Skip empty loop devices and RAM disks
+                    if blocks == 0 || name.starts_with("loop") || name.starts_with("ram") {
+                        return None;
+                    }
+
+                    let path = format!("/dev/{}", name);
+                    let mount_point = mounts.get(&path).cloned();
+                    Some(DeviceInfo {
+                        name: self.model(name).unwrap_or_else(|| name.to_string()),
+                        device_type: DeviceType::BlockDevice,
+                        size_bytes: Some(blocks * 1024),
+                        mounted: mount_point.is_some(),
+                        mount_point,
+                        path,
+                    })
+                })
+                .collect()
+        }
+
+        fn input_devices(&self) -> Vec<DeviceInfo> {
+            let Ok(entries) = fs::read_dir(&self.input_by_id) else {
+                return Vec::new();
+            };
+            let mut devices: Vec<DeviceInfo> = entries
+                .filter_map(|e| e.ok())
+                .filter_map(|entry| {
+                    let file_name = entry.file_name().to_string_lossy().into_owned();
+                    // Each device also has legacy mouseN/jsN links; keep the evdev one
+                    let (id, _kind) = file_name.split_once("-event-")?;
+                    let id = id
+                        .trim_start_matches("usb-")
+                        .trim_start_matches("platform-");
+                    Some(DeviceInfo {
+                        path: entry.path().to_string_lossy().into_owned(),
+                        device_type: DeviceType::InputDevice,
+                        name: id.replace('_', " "),
+                        size_bytes: None,
+                        mounted: false,
+                        mount_point: None,
+                    })
+                })
+                .collect();
+            devices.sort_by(|a, b| a.path.cmp(&b.path));
+            devices
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_scan_reads_sizes_mounts_and_inputs() {
+            let root = tempfile::tempdir().unwrap();
+            let sys_block = root.path().join("block");
+            fs::create_dir_all(sys_block.join("sdb").join("sdb1")).unwrap();
+            fs::create_dir_all(sys_block.join("sdb").join("device")).unwrap();
+            fs::write(
+                sys_block.join("sdb").join("device").join("model"),
+                "Cruzer Blade\n",
+            )
+            .unwrap();
+            fs::create_dir_all(sys_block.join("sda").join("sda1")).unwrap();
+
+            fs::write(
+                root.path().join("partitions"),
+                "major minor  #blocks  name\n\n\
+                 8        0  1000000 sda\n\
+                 8        1   999000 sda1\n\
+                 8       16    15360 sdb\n\
+                 8       17    15000 sdb1\n\
+                 7        0        0 loop0\n",
+            )
+            .unwrap();
+            fs::write(
+                root.path().join("mounts"),
+                "/dev/sda1 / ext4 rw 0 0\n/dev/sdb1 /media/me/MY\\040STICK vfat rw 0 0\n",
+            )
+            .unwrap();
+
+            let by_id = root.path().join("by-id");
+            fs::create_dir_all(&by_id).unwrap();
+            for link in [
+                "usb-Logitech_USB_Receiver-event-kbd",
+                "usb-Logitech_USB_Receiver-event-mouse",
+                "usb-Logitech_USB_Receiver-mouse",
+            ] {
+                fs::write(by_id.join(link), "").unwrap();
+            }
+
+            let scanner = LinuxDeviceScanner::new(
+                root.path().join("partitions"),
+                &sys_block,
+                root.path().join("mounts"),
+                &by_id,
+            );
+            let devices = scanner.scan();
+            let find = |path: &str| devices.iter().find(|d| d.path == path).unwrap();
+
+            assert_eq!(devices.len(), 6);
+            assert!(!devices.iter().any(|d| d.path == "/dev/loop0"));
+
+            let stick = find("/dev/sdb1");
+            assert_eq!(stick.name, "Cruzer Blade");
+            assert_eq!(stick.size_bytes, Some(15000 * 1024));
+            assert_eq!(stick.mount_point.as_deref(), Some("/media/me/MY STICK"));
+            assert!(find("/dev/sda1").mounted);
+            assert!(!find("/dev/sda").mounted);
+            assert_eq!(find("/dev/sda").name, "sda");
+
+            let inputs: Vec<_> = devices
+                .iter()
+                .filter(|d| d.device_type == DeviceType::InputDevice)
+                .collect();
+            assert_eq!(inputs.len(), 2);
+            assert_eq!(inputs[0].name, "Logitech USB Receiver");
+        }
+    }
+}
+
+#[cfg(all(not(any(test, feature = "mock-devices")), windows))]
+fn windows_volumes() -> Vec<DeviceInfo> {
+    use lucastra_core::DeviceType;
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::{
+        GetDiskFreeSpaceExW, GetLogicalDrives, GetVolumeInformationW,
+    };
+
+    // SAFETY: GetLogicalDrives takes no arguments
+    let mask = unsafe { GetLogicalDrives() };
+    (0..26u8)
+        .filter(|bit| mask & (1 << bit) != 0)
+        .map(|bit| {
+            let root = format!("{}:\\", (b'A' + bit) as char);
+            let wide: Vec<u16> = root.encode_utf16().chain(std::iter::once(0)).collect();
+            let root_ptr = PCWSTR::from_raw(wide.as_ptr());
+
+            let mut total_bytes = 0u64;
+            // SAFETY: `wide` is NUL-terminated and outlives both calls, and the
+            // out-pointers reference live locals
+            let size = unsafe { GetDiskFreeSpaceExW(root_ptr, None, Some(&mut total_bytes), None) }
+                .ok()
+                .map(|_| total_bytes);
+
+            let mut label = [0u16; 261];
+            let label = unsafe {
+                GetVolumeInformationW(root_ptr, Some(&mut label), None, None, None, None)
+            }
+            .ok()
+            .map(|_| {
+                let len = label.iter().position(|&c| c == 0).unwrap_or(label.len());
+                String::from_utf16_lossy(&label[..len])
+            })
+            .filter(|label| !label.is_empty())
+            .unwrap_or_else(|| "Local Disk".to_string());
+
+            DeviceInfo {
+                name: format!("{} ({})", label, &root[..2]),
+                path: root.clone(),
+                device_type: DeviceType::BlockDevice,
+                size_bytes: size,
+                mounted: size.is_some(),
+                mount_point: size.map(|_| root),
+            }
+        })
+        .collect()
+}
@@ -33,10 +33,7 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             provider: "openai".to_string(),
             api_key: Some(api_key),
             model: Some("gpt-4o-mini".to_string()),
-            endpoint: None,
-            temperature: Some(0.7),
-            max_tokens: Some(256),
-            timeout_secs: Some(30),
+            ..Default::default()
         };
 
         let provider = create_provider(config).await?;
@@ -161,12 +158,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("4. Testing Llamafile Provider...");
     let llamafile_config = ProviderConfig {
         provider: "llamafile".to_string(),
-        api_key: None,
         endpoint: Some("http://localhost:8000".to_string()),
-        model: None,
-        temperature: Some(0.7),
-        max_tokens: Some(256),
-        timeout_secs: Some(30),
+        ..Default::default()
     };
 
     let llamafile = create_provider(llamafile_config).await?;
@@ -1,17 +1,45 @@
-use lucastra_core::{command::FileEntry, Result};
-use lucastra_hal::FileSystemDriver;
-use std::collections::HashMap;
+use lucastra_core::{command::FileEntry, LuCastraError, Result};
+use lucastra_hal::{FileMetadata, FileSystemDriver};
+use std::collections::BTreeMap;
+use std::time::UNIX_EPOCH;
 use tracing::info;
 
+/// A mounted filesystem as reported by [`FilesystemManager::list_mounts`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MountInfo {
+    pub mount_point: String,
+    pub driver: String,
+    pub mounted: bool,
+}
+
 /// Filesystem manager: handles mounting, file I/O, and virtual filesystem abstractions.
 pub struct FilesystemManager {
-    mount_points: HashMap<String, Box<dyn FileSystemDriver + Send>>,
+    mount_points: BTreeMap<String, Box<dyn FileSystemDriver + Send>>,
+}
+
+/// Strip trailing slashes, keeping `/` itself.
+fn normalize(path: &str) -> &str {
+    let trimmed = path.trim_end_matches('/');
+    if trimmed.is_empty() && path.starts_with('/') {
+        "/"
+    } else {
+        trimmed
+    }
+}
+
+/// Whether `path` is `mount_point` or lies beneath it, matching whole components.
+fn is_under(path: &str, mount_point: &str) -> bool {
+    mount_point == "/"
+        || path == mount_point
+        || path
+            .strip_prefix(mount_point)
+            .is_some_and(|rest| rest.starts_with('/'))
 }
 
 impl FilesystemManager {
     pub fn new() -> Self {
         Self {
-            mount_points: HashMap::new(),
+            mount_points: BTreeMap::new(),
         }
     }
 
@@ -21,6 +49,7 @@ impl FilesystemManager {
         mount_point: &str,
         driver: T,
     ) -> Result<()> {
+        let mount_point = normalize(mount_point);
         let mut driver = Box::new(driver);
         driver.mount(mount_point)?;
         self.mount_points.insert(mount_point.to_string(), driver);
@@ -28,71 +57,164 @@ impl FilesystemManager {
         Ok(())
     }
 
-    /// Unmount a filesystem.
+    /// Unmount a filesystem. Fails while other filesystems are mounted beneath it.
     pub fn unmount(&mut self, mount_point: &str) -> Result<()> {
+        let mount_point = normalize(mount_point);
+        if !self.mount_points.contains_key(mount_point) {
+            return Err(LuCastraError::FilesystemError(format!(
+                "Mount point not found: {}",
+                mount_point
+            )));
+        }
+
+        if let Some(child) = self
+            .mount_points
+            .keys()
+            .find(|other| other.as_str() != mount_point && is_under(other, mount_point))
+        {
+            return Err(LuCastraError::FilesystemError(format!(
+                "Cannot unmount {}: {} is still mounted beneath it",
+                mount_point, child
+            )));
+        }
+
         if let Some(mut driver) = self.mount_points.remove(mount_point) {
             driver.unmount()?;
             info!("Filesystem unmounted from {}", mount_point);
-            Ok(())
-        } else {
-            Err(lucastra_core::LuCastraError::FilesystemError(format!(
-                "Mount point not found: {}",
-                mount_point
-            )))
         }
+        Ok(())
+    }
+
+    /// Mounted filesystems, ordered by mount point.
+    pub fn list_mounts(&self) -> Vec<MountInfo> {
+        self.mount_points
+            .iter()
+            .map(|(mount_point, driver)| MountInfo {
+                mount_point: mount_point.clone(),
+                driver: driver.name().to_string(),
+                mounted: driver.is_mounted(),
+            })
+            .collect()
+    }
+
+    /// Mount point whose driver handles `path`: the longest matching prefix.
+    fn resolve_mount_point(&self, path: &str) -> Result<String> {
+        let path = normalize(path);
+        self.mount_points
+            .iter()
+            .filter(|(mount_point, driver)| is_under(path, mount_point) && driver.is_mounted())
+            .max_by_key(|(mount_point, _)| mount_point.len())
+            .map(|(mount_point, _)| mount_point.clone())
+            .ok_or_else(|| {
+                LuCastraError::FilesystemError(format!("No filesystem mounted for path: {}", path))
+            })
     }
 
     /// Resolve a path to the appropriate filesystem driver.
     fn resolve_driver(&self, path: &str) -> Result<&dyn FileSystemDriver> {
-        for (mount_point, driver) in &self.mount_points {
-            if path.starts_with(mount_point) && driver.is_mounted() {
-                return Ok(driver.as_ref());
-            }
-        }
-        Err(lucastra_core::LuCastraError::FilesystemError(format!(
-            "No filesystem mounted for path: {}",
-            path
-        )))
+        let mount_point = self.resolve_mount_point(path)?;
+        Ok(self.mount_points[&mount_point].as_ref())
     }
 
     /// Resolve a path mutably to the appropriate filesystem driver.
     fn resolve_driver_mut(&mut self, path: &str) -> Result<&mut dyn FileSystemDriver> {
-        for (mount_point, driver) in &mut self.mount_points {
-            if path.starts_with(mount_point) && driver.is_mounted() {
-                return Ok(driver.as_mut());
-            }
-        }
-        Err(lucastra_core::LuCastraError::FilesystemError(format!(
-            "No filesystem mounted for path: {}",
-            path
-        )))
+        let mount_point = self.resolve_mount_point(path)?;
+        let driver = self.mount_points.get_mut(&mount_point).ok_or_else(|| {
+            LuCastraError::FilesystemError(format!("Mount point not found: {}", mount_point))
+        })?;
+        Ok(driver.as_mut())
     }
 
     /// List files in a directory.
     pub fn list_files(&self, path: &str) -> Result<Vec<FileEntry>> {
         let driver = self.resolve_driver(path)?;
-        let files = driver.list_files(path)?;
-        Ok(files
+        Ok(driver
+            .list_entries(path)?
             .into_iter()
-            .map(|f| FileEntry {
-                path: f,
-                is_dir: false,
-                size: 0,
-            })
+            .map(|(path, metadata)| to_entry(path, &metadata))
             .collect())
     }
 
+    /// Metadata for a single path.
+    pub fn stat(&self, path: &str) -> Result<FileEntry> {
+        let driver = self.resolve_driver(path)?;
+        let metadata = driver.stat(path)?;
+        Ok(to_entry(path.to_string(), &metadata))
+    }
+
+    /// List a directory and its subdirectories. `max_depth` counts levels
+    /// below `path`: 1 lists only its direct entries.
+    pub fn list_recursive(&self, path: &str, max_depth: usize) -> Result<Vec<FileEntry>> {
+        let mut entries = Vec::new();
+        if max_depth > 0 {
+            self.collect_entries(path, max_depth, &mut entries)?;
+        }
+        Ok(entries)
+    }
+
+    fn collect_entries(
+        &self,
+        path: &str,
+        depth_left: usize,
+        entries: &mut Vec<FileEntry>,
+    ) -> Result<()> {
+        for entry in self.list_files(path)? {
+            let descend = entry.is_dir && depth_left > 1;
+            let dir = entry.path.clone();
+            entries.push(entry);
+            if descend {
+                self.collect_entries(&dir, depth_left - 1, entries)?;
+            }
+        }
+        Ok(())
+    }
+
     /// Read file contents.
     pub fn read_file(&self, path: &str) -> Result<Vec<u8>> {
         let driver = self.resolve_driver(path)?;
         driver.read_file(path)
     }
 
+    /// Up to `len` bytes of a file starting at `offset`.
+    pub fn read_range(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let driver = self.resolve_driver(path)?;
+        driver.read_range(path, offset, len)
+    }
+
     /// Write file contents.
     pub fn write_file(&mut self, path: &str, content: &[u8]) -> Result<()> {
         let driver = self.resolve_driver_mut(path)?;
         driver.write_file(path, content)
     }
+
+    /// Delete a file.
+    pub fn delete_file(&mut self, path: &str) -> Result<()> {
+        let driver = self.resolve_driver_mut(path)?;
+        driver.delete_file(path)
+    }
+
+    /// Create a directory.
+    pub fn create_dir(&mut self, path: &str) -> Result<()> {
+        let driver = self.resolve_driver_mut(path)?;
+        driver.create_dir(path)
+    }
+
+    /// Whether the driver that serves `path` writes to the host filesystem.
+    pub fn is_host_backed(&self, path: &str) -> Result<bool> {
+        Ok(self.resolve_driver(path)?.is_host_backed())
+    }
+}
+
+fn to_entry(path: String, metadata: &FileMetadata) -> FileEntry {
+    FileEntry {
+        path,
+        is_dir: metadata.is_dir,
+        size: metadata.size,
+        modified: metadata
+            .modified
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map(|d| d.as_secs()),
+    }
 }
 
 impl Default for FilesystemManager {
@@ -100,3 +222,157 @@ impl Default for FilesystemManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_hal::filesystem::MockFileSystem;
+
+    /// Driver that answers every read with its own tag.
+    struct TaggedFileSystem {
+        tag: &'static str,
+        mounted: bool,
+    }
+
+    impl TaggedFileSystem {
+        fn new(tag: &'static str) -> Self {
+            Self {
+                tag,
+                mounted: false,
+            }
+        }
+    }
+
+    impl FileSystemDriver for TaggedFileSystem {
+        fn name(&self) -> &str {
+            self.tag
+        }
+
+        fn mount(&mut self, _path: &str) -> Result<()> {
+            self.mounted = true;
+            Ok(())
+        }
+
+        fn unmount(&mut self) -> Result<()> {
+            self.mounted = false;
+            Ok(())
+        }
+
+        fn list_files(&self, _path: &str) -> Result<Vec<String>> {
+            Ok(Vec::new())
+        }
+
+        fn read_file(&self, _path: &str) -> Result<Vec<u8>> {
+            Ok(self.tag.as_bytes().to_vec())
+        }
+
+        fn write_file(&mut self, _path: &str, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+
+        fn is_mounted(&self) -> bool {
+            self.mounted
+        }
+    }
+
+    fn nested() -> FilesystemManager {
+        let mut fs = FilesystemManager::new();
+        fs.mount("/mnt/usb/boot/", TaggedFileSystem::new("boot"))
+            .unwrap();
+        fs.mount("/mnt", TaggedFileSystem::new("mnt")).unwrap();
+        fs.mount("/mnt/usb", TaggedFileSystem::new("usb")).unwrap();
+        fs
+    }
+
+    fn served_by(fs: &FilesystemManager, path: &str) -> String {
+        String::from_utf8(fs.read_file(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_longest_prefix_wins() {
+        let fs = nested();
+        assert_eq!(served_by(&fs, "/mnt/usb/boot/kernel"), "boot");
+        assert_eq!(served_by(&fs, "/mnt/usb/photo.jpg"), "usb");
+        assert_eq!(served_by(&fs, "/mnt/usb/"), "usb");
+        assert_eq!(served_by(&fs, "/mnt/notes.txt"), "mnt");
+    }
+
+    #[test]
+    fn test_matches_whole_components_only() {
+        let fs = nested();
+        assert_eq!(served_by(&fs, "/mnt/usbstick/file"), "mnt");
+        assert_eq!(served_by(&fs, "/mnt/usb/bootloader"), "usb");
+        assert!(fs.read_file("/mntx/file").is_err());
+    }
+
+    #[test]
+    fn test_list_mounts() {
+        let fs = nested();
+        let mounts = fs.list_mounts();
+        let points: Vec<&str> = mounts.iter().map(|m| m.mount_point.as_str()).collect();
+        assert_eq!(points, vec!["/mnt", "/mnt/usb", "/mnt/usb/boot"]);
+        assert_eq!(mounts[1].driver, "usb");
+        assert!(mounts.iter().all(|m| m.mounted));
+    }
+
+    fn mock_tree() -> FilesystemManager {
+        let mut mock = MockFileSystem::new();
+        mock.write_file("/data/readme.md", b"hello").unwrap();
+        mock.write_file("/data/src/main.rs", b"fn main() {}")
+            .unwrap();
+        mock.write_file("/data/src/deep/mod.rs", b"").unwrap();
+        mock.set_metadata(
+            "/data/readme.md",
+            FileMetadata {
+                size: 5,
+                is_dir: false,
+                modified: Some(UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000)),
+            },
+        );
+
+        let mut fs = FilesystemManager::new();
+        fs.mount("/data", mock).unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_list_files_reports_metadata() {
+        let fs = mock_tree();
+        let entries = fs.list_files("/data").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].path, "/data/readme.md");
+        assert_eq!(entries[0].size, 5);
+        assert_eq!(entries[0].modified, Some(1_700_000_000));
+        assert!(entries[1].is_dir);
+        assert!(fs.stat("/data/src").unwrap().is_dir);
+    }
+
+    #[test]
+    fn test_list_recursive_respects_depth() {
+        let fs = mock_tree();
+        let paths = |depth| -> Vec<String> {
+            fs.list_recursive("/data", depth)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.path)
+                .collect()
+        };
+
+        assert_eq!(paths(1), vec!["/data/readme.md", "/data/src"]);
+        assert_eq!(paths(2).len(), 4);
+        assert_eq!(paths(3).len(), 5);
+        assert!(paths(3).contains(&"/data/src/deep/mod.rs".to_string()));
+        assert!(paths(0).is_empty());
+    }
+
+    #[test]
+    fn test_unmount_with_child_mount_fails() {
+        let mut fs = nested();
+        let err = fs.unmount("/mnt/usb").unwrap_err();
+        assert!(err.to_string().contains("/mnt/usb/boot"));
+
+        fs.unmount("/mnt/usb/boot").unwrap();
+        fs.unmount("/mnt/usb/").unwrap();
+        assert_eq!(served_by(&fs, "/mnt/usb/photo.jpg"), "mnt");
+    }
+}
@@ -0,0 +1,248 @@
+//! Files and long pastes waiting to go out with the next query.
+//!
+//! Dropping a file on the window attaches it, once the host file whitelist
+//! allows reading it. Pasting text of [`PASTE_MIN_LINES`] lines or
+//! [`PASTE_MIN_CHARS`] characters attaches it too, rather than filling the
+//! input. Each attachment shows as a chip above the input until it is
+//! removed or sent.
+
+use lucastra_core::{Attachment, LuCastraError};
+use lucastra_i18n::t;
+use std::path::Path;
+
+/// Pasted text with at least this many lines becomes an attachment.
+pub const PASTE_MIN_LINES: usize = 8;
+
+/// Pasted text with at least this many characters becomes an attachment.
+pub const PASTE_MIN_CHARS: usize = 1000;
+
+/// Where an attachment came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChipKind {
+    File,
+    /// The `number`th paste, counting from 1.
+    Paste {
+        number: usize,
+        lines: usize,
+    },
+}
+
+/// An attachment shown above the chat input.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Chip {
+    pub id: usize,
+    pub kind: ChipKind,
+    pub attachment: Attachment,
+}
+
+impl Chip {
+    /// Text of the chip: a file's name, or how long a paste is.
+    pub fn label(&self) -> String {
+        match self.kind {
+            ChipKind::File => Path::new(&self.attachment.name).file_name().map_or_else(
+                || self.attachment.name.clone(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+            ChipKind::Paste { lines, .. } => t!("gui.attachments.pasted", lines = lines),
+        }
+    }
+}
+
+/// Attachments for the next query, and whether files are being dragged
+/// over the window.
+#[derive(Debug, Default)]
+pub struct Attachments {
+    chips: Vec<Chip>,
+    next_id: usize,
+    pastes: usize,
+    hovering: bool,
+}
+
+impl Attachments {
+    /// Files are being dragged over the window.
+    pub fn hover(&mut self) {
+        self.hovering = true;
+    }
+
+    /// The drag left the window without dropping anything.
+    pub fn end_hover(&mut self) {
+        self.hovering = false;
+    }
+
+    pub fn is_hovering(&self) -> bool {
+        self.hovering
+    }
+
+    /// Attach the file at `path`, as `read` from it. A file attached again
+    /// replaces the earlier copy. Fails with the text of a toast saying why
+    /// the file wasn't attached.
+    pub fn drop_file(
+        &mut self,
+        path: &Path,
+        read: lucastra_core::Result<Attachment>,
+    ) -> Result<usize, String> {
+        self.hovering = false;
+        let attachment = read.map_err(|e| match e {
+            LuCastraError::PermissionDenied(reason) => {
+                t!(
+                    "gui.attachments.denied",
+                    path = path.display(),
+                    reason = reason
+                )
+            }
+            e => t!("gui.attachments.failed", path = path.display(), error = e),
+        })?;
+        self.chips
+            .retain(|chip| chip.kind != ChipKind::File || chip.attachment.name != attachment.name);
+        Ok(self.push(ChipKind::File, attachment))
+    }
+
+    /// Attach pasted `text`.
+    pub fn paste(&mut self, text: String) -> usize {
+        self.pastes += 1;
+        let kind = ChipKind::Paste {
+            number: self.pastes,
+            lines: text.lines().count(),
+        };
+        let attachment = Attachment {
+            name: format!("pasted text {}", self.pastes),
+            text,
+        };
+        self.push(kind, attachment)
+    }
+
+    /// Take attachment `id` back off; false if there is none.
+    pub fn remove(&mut self, id: usize) -> bool {
+        let before = self.chips.len();
+        self.chips.retain(|chip| chip.id != id);
+        self.chips.len() < before
+    }
+
+    pub fn chips(&self) -> &[Chip] {
+        &self.chips
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.chips.is_empty()
+    }
+
+    /// Everything attached, in the order it was added, for sending. Leaves
+    /// nothing attached.
+    pub fn take(&mut self) -> Vec<Attachment> {
+        self.chips.drain(..).map(|chip| chip.attachment).collect()
+    }
+
+    fn push(&mut self, kind: ChipKind, attachment: Attachment) -> usize {
+        self.next_id += 1;
+        self.chips.push(Chip {
+            id: self.next_id,
+            kind,
+            attachment,
+        });
+        self.next_id
+    }
+}
+
+/// Whether pasted `text` is long enough to attach rather than type in.
+pub fn is_long_paste(text: &str) -> bool {
+    text.lines().count() >= PASTE_MIN_LINES || text.chars().count() >= PASTE_MIN_CHARS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_tools::file_access::{FileAccessTool, FileAccessValidator};
+    use std::fs;
+
+    fn file(name: &str, text: &str) -> lucastra_core::Result<Attachment> {
+        Ok(Attachment {
+            name: name.to_string(),
+            text: text.to_string(),
+        })
+    }
+
+    #[test]
+    fn test_attachments_are_added_removed_and_taken_in_order() {
+        let mut attachments = Attachments::default();
+        attachments.hover();
+        assert!(attachments.is_hovering());
+
+        let plan = attachments
+            .drop_file(Path::new("/docs/plan.txt"), file("/docs/plan.txt", "v1"))
+            .unwrap();
+        assert!(!attachments.is_hovering());
+        let log = attachments.paste("line\n".repeat(PASTE_MIN_LINES));
+        let notes = attachments
+            .drop_file(Path::new("/docs/notes.md"), file("/docs/notes.md", "n"))
+            .unwrap();
+        // Dropping a file again replaces it at the end
+        attachments
+            .drop_file(Path::new("/docs/plan.txt"), file("/docs/plan.txt", "v2"))
+            .unwrap();
+
+        let labels: Vec<String> = attachments.chips().iter().map(Chip::label).collect();
+        assert_eq!(labels, ["8 pasted lines", "notes.md", "plan.txt"]);
+        assert!(!attachments.remove(plan));
+        assert!(attachments.remove(notes));
+        assert!(!attachments.remove(notes));
+
+        let sent = attachments.take();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(sent[0].name, "pasted text 1");
+        assert_eq!(sent[1].text, "v2");
+        assert!(attachments.is_empty());
+        assert!(!attachments.remove(log));
+    }
+
+    #[test]
+    fn test_dropping_a_file_outside_allowed_dirs_shows_a_permission_toast() {
+        let base = std::env::temp_dir().join(format!("lucastra_attach_{}", std::process::id()));
+        let allowed = base.join("allowed");
+        fs::create_dir_all(&allowed).unwrap();
+        fs::write(allowed.join("plan.txt"), "ship on friday").unwrap();
+        fs::write(base.join("secret.txt"), "keep out").unwrap();
+        let tool = FileAccessTool::new(
+            FileAccessValidator::new(vec![allowed.canonicalize().unwrap()], true, false, false),
+            base.join("audit.log"),
+        );
+        // As SystemState::read_attachment reports whitelist failures
+        let read = |path: &Path| {
+            tool.read_text(path, 1024)
+                .map(|(text, _)| Attachment {
+                    name: path.display().to_string(),
+                    text,
+                })
+                .map_err(|e| LuCastraError::PermissionDenied(e.to_string()))
+        };
+
+        let mut attachments = Attachments::default();
+        let inside = allowed.join("plan.txt");
+        assert!(attachments.drop_file(&inside, read(&inside)).is_ok());
+        let outside = base.join("secret.txt");
+        let toast = attachments.drop_file(&outside, read(&outside)).unwrap_err();
+        assert!(toast.starts_with(&format!(
+            "Can't attach {}: permission denied",
+            outside.display()
+        )));
+        assert_eq!(attachments.chips().len(), 1);
+
+        let failed = attachments
+            .drop_file(
+                &outside,
+                Err(LuCastraError::FilesystemError(
+                    "not a text file".to_string(),
+                )),
+            )
+            .unwrap_err();
+        assert!(failed.ends_with("not a text file"));
+        let _ = fs::remove_dir_all(base);
+    }
+
+    #[test]
+    fn test_only_long_pastes_become_attachments() {
+        assert!(!is_long_paste("a short question"));
+        assert!(!is_long_paste(&"line\n".repeat(PASTE_MIN_LINES - 1)));
+        assert!(is_long_paste(&"line\n".repeat(PASTE_MIN_LINES)));
+        assert!(is_long_paste(&"x".repeat(PASTE_MIN_CHARS)));
+    }
+}
@@ -0,0 +1,424 @@
+//! System status dashboard: devices, filesystems, the search index, the LLM,
+//! metrics and background jobs, each in its own panel.
+
+use crate::file_browser::format_size;
+use crate::scale::UiScale;
+use crate::theme::ThemePalette;
+use crate::Message;
+use iced::widget::{button, column, container, row, scrollable, text, Column};
+use iced::{Alignment, Element, Length};
+use lucastra_app::{Dashboard, JobOutcome, JobStatus, MetricsSnapshot};
+use lucastra_core::{DeviceType, Response};
+use lucastra_i18n::t;
+use lucastra_llm::{HealthState, HealthStatus};
+
+#[derive(Debug, Clone)]
+pub enum DashboardAction {
+    /// Take a fresh snapshot.
+    Refresh,
+    TestConnection,
+    /// Mount the block device at this path.
+    Mount(String),
+    /// Unmount the volume at this mount point.
+    Unmount(String),
+    /// Start the background job with this name now.
+    RunJob(String),
+    /// A mount, unmount, connection test or job request finished.
+    Replied(Response),
+    Close,
+}
+
+/// The dashboard as last refreshed.
+pub struct DashboardView {
+    pub data: Dashboard,
+    /// Outcome of the last mount, unmount, connection test or job request
+    pub notice: Option<String>,
+    /// A connection test is running
+    pub testing: bool,
+}
+
+impl DashboardView {
+    pub fn new(data: Dashboard) -> Self {
+        Self {
+            data,
+            notice: None,
+            testing: false,
+        }
+    }
+}
+
+/// Where the dashboard mounts a device, e.g. `/mnt/usb/sdb1` for `/dev/sdb1`.
+pub fn mount_point_for(device_path: &str) -> String {
+    let name = device_path
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default();
+    format!("/mnt/usb/{}", name)
+}
+
+pub fn view(
+    dashboard: &DashboardView,
+    palette: ThemePalette,
+    scale: UiScale,
+) -> Element<'_, Message> {
+    let data = &dashboard.data;
+    let action = |action| Message::Dashboard(action);
+
+    let header = row![
+        text(t!("gui.dashboard.title"))
+            .size(scale.heading())
+            .width(Length::Fill),
+        button(text(t!("gui.dashboard.refresh")).size(scale.label()))
+            .on_press(action(DashboardAction::Refresh)),
+        button(text(t!("gui.dashboard.close")).size(scale.label()))
+            .on_press(action(DashboardAction::Close)),
+    ]
+    .spacing(10)
+    .align_items(Alignment::Center);
+
+    let mut devices = Column::new().spacing(4);
+    match &data.devices {
+        Ok(list) if list.is_empty() => {
+            devices = devices.push(text(t!("gui.dashboard.no_devices")).size(scale.label()))
+        }
+        Ok(list) => {
+            for device in list {
+                let mut line = row![text(format!(
+                    "{} ({}){}",
+                    device.name,
+                    device.path,
+                    device
+                        .size_bytes
+                        .map(|size| format!(", {}", format_size(size)))
+                        .unwrap_or_default()
+                ))
+                .size(scale.label())
+                .width(Length::Fill)]
+                .spacing(10)
+                .align_items(Alignment::Center);
+                match (&device.mount_point, device.device_type) {
+                    (Some(mount_point), _) if device.mounted => {
+                        line = line.push(
+                            text(t!("gui.dashboard.mounted_at", path = mount_point))
+                                .size(scale.label()),
+                        );
+                        line = line.push(
+                            button(text(t!("gui.dashboard.unmount")).size(scale.label()))
+                                .on_press(action(DashboardAction::Unmount(mount_point.clone()))),
+                        );
+                    }
+                    (_, DeviceType::BlockDevice) => {
+                        line = line.push(
+                            button(text(t!("gui.dashboard.mount")).size(scale.label()))
+                                .on_press(action(DashboardAction::Mount(device.path.clone()))),
+                        );
+                    }
+                    _ => {}
+                }
+                devices = devices.push(line);
+            }
+        }
+        Err(e) => devices = devices.push(panel_error(palette, scale, e)),
+    }
+
+    let mut mounts = Column::new().spacing(4);
+    for mount in &data.mounts {
+        let state = if mount.mounted {
+            String::new()
+        } else {
+            format!(" ({})", t!("gui.dashboard.not_mounted"))
+        };
+        mounts = mounts.push(
+            text(format!("{}  {}{}", mount.mount_point, mount.driver, state)).size(scale.label()),
+        );
+    }
+
+    let locale = lucastra_i18n::current();
+    let search: Element<'_, Message> = match &data.search {
+        Ok(stats) => column![
+            text(t!(
+                "gui.dashboard.documents",
+                count = locale.integer(stats.doc_count as u64)
+            ))
+            .size(scale.label()),
+            text(t!(
+                "gui.dashboard.index_size",
+                size = stats
+                    .index_bytes
+                    .map(format_size)
+                    .unwrap_or_else(|| t!("gui.dashboard.in_memory"))
+            ))
+            .size(scale.label()),
+        ]
+        .spacing(4)
+        .into(),
+        Err(e) => panel_error(palette, scale, e),
+    };
+
+    let llm = &data.llm;
+    let test_button = if dashboard.testing {
+        button(text(t!("gui.dashboard.testing")).size(scale.label()))
+    } else {
+        button(text(t!("gui.dashboard.test_connection")).size(scale.label()))
+            .on_press(action(DashboardAction::TestConnection))
+    };
+    let mut llm_panel = column![
+        text(t!("gui.dashboard.provider", name = llm.provider)).size(scale.label()),
+        text(t!("gui.dashboard.model", name = llm.model)).size(scale.label()),
+        text(t!("gui.dashboard.endpoint", url = llm.endpoint)).size(scale.label()),
+        text(t!(
+            "gui.dashboard.health",
+            status = health_text(llm.health.as_ref())
+        ))
+        .size(scale.label()),
+    ]
+    .spacing(4);
+    if let Some(checked) = llm.health.as_ref().and_then(|health| health.last_checked) {
+        llm_panel = llm_panel.push(
+            text(t!(
+                "gui.dashboard.last_checked",
+                time = locale.datetime(checked)
+            ))
+            .size(scale.label()),
+        );
+    }
+    let llm_panel = llm_panel.push(test_button);
+
+    let metrics = metrics_panel(&data.metrics, scale);
+
+    let mut jobs = Column::new().spacing(4);
+    if data.jobs.is_empty() {
+        jobs = jobs.push(text(t!("gui.dashboard.jobs_off")).size(scale.label()));
+    }
+    for job in &data.jobs {
+        let mut run = button(text(t!("gui.dashboard.run_now")).size(scale.label()));
+        if !job.running {
+            run = run.on_press(action(DashboardAction::RunJob(job.name.clone())));
+        }
+        let mut status = text(job_text(job)).size(scale.label());
+        if !job.is_healthy() {
+            status = status.style(iced::theme::Text::Color(palette.field_error));
+        }
+        jobs = jobs.push(
+            row![
+                column![
+                    text(t!(
+                        "gui.dashboard.job",
+                        name = job.name,
+                        schedule = job
+                            .schedule
+                            .clone()
+                            .unwrap_or_else(|| t!("gui.dashboard.on_demand"))
+                    ))
+                    .size(scale.label()),
+                    status,
+                ]
+                .spacing(2)
+                .width(Length::Fill),
+                run,
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+    }
+
+    let mut content = column![
+        header,
+        section(scale, t!("gui.dashboard.devices"), devices),
+        section(scale, t!("gui.dashboard.filesystems"), mounts),
+        section(scale, t!("gui.dashboard.search_index"), search),
+        section(scale, t!("gui.dashboard.llm"), llm_panel),
+        section(scale, t!("gui.dashboard.metrics"), metrics),
+        section(scale, t!("gui.dashboard.jobs"), jobs),
+    ]
+    .spacing(16)
+    .padding(20);
+    if let Some(notice) = &dashboard.notice {
+        content = content.push(text(notice).size(scale.label()));
+    }
+
+    container(scrollable(content))
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(10)
+        .into()
+}
+
+fn section<'a>(
+    scale: UiScale,
+    title: String,
+    body: impl Into<Element<'a, Message>>,
+) -> Element<'a, Message> {
+    column![text(title).size(scale.subheading()), body.into()]
+        .spacing(6)
+        .into()
+}
+
+/// A panel's error, shown in place of its contents.
+fn panel_error<'a>(palette: ThemePalette, scale: UiScale, error: &str) -> Element<'a, Message> {
+    text(t!("gui.dashboard.unavailable", error = error))
+        .size(scale.label())
+        .style(iced::theme::Text::Color(palette.field_error))
+        .into()
+}
+
+fn metrics_panel(metrics: &MetricsSnapshot, scale: UiScale) -> Column<'_, Message> {
+    let locale = lucastra_i18n::current();
+    let count = |value| locale.integer(value);
+    let lifetime = &metrics.lifetime;
+    column![
+        text(t!(
+            "gui.dashboard.commands",
+            count = count(metrics.command_count)
+        ))
+        .size(scale.label()),
+        text(t!(
+            "gui.dashboard.tool_success",
+            rate = tool_success_rate(metrics)
+        ))
+        .size(scale.label()),
+        text(t!(
+            "gui.dashboard.search_latency",
+            ms = count(metrics.average_search_latency_ms),
+            count = count(metrics.search_queries)
+        ))
+        .size(scale.label()),
+        text(t!(
+            "gui.dashboard.llm_requests",
+            count = count(metrics.llm_requests),
+            tokens = count(metrics.llm_tokens)
+        ))
+        .size(scale.label()),
+        text(t!(
+            "gui.dashboard.lifetime",
+            commands = count(lifetime.command_count),
+            tools = count(lifetime.tool_success_count + lifetime.tool_failure_count),
+            requests = count(lifetime.llm_requests),
+            sessions = count(lifetime.sessions)
+        ))
+        .size(scale.label()),
+    ]
+    .spacing(4)
+}
+
+/// `healthy`, `down (3 failed checks)`, or `not monitored`
+fn health_text(health: Option<&HealthStatus>) -> String {
+    match health {
+        None => t!("gui.dashboard.not_monitored"),
+        Some(health) if health.last_checked.is_none() => t!("gui.dashboard.not_checked"),
+        Some(health) if health.consecutive_failures > 0 => t!(
+            "gui.dashboard.failed_checks",
+            state = state_text(health.state),
+            count = lucastra_i18n::current().integer(u64::from(health.consecutive_failures))
+        ),
+        Some(health) => state_text(health.state),
+    }
+}
+
+fn state_text(state: HealthState) -> String {
+    match state {
+        HealthState::Healthy => t!("gui.dashboard.state.healthy"),
+        HealthState::Degraded => t!("gui.dashboard.state.degraded"),
+        HealthState::Down => t!("gui.dashboard.state.down"),
+    }
+}
+
+/// `running`, `not run yet`, or when the last run started and how it went
+fn job_text(job: &JobStatus) -> String {
+    if job.running {
+        return t!("gui.dashboard.job_running");
+    }
+    let Some(run) = &job.last_run else {
+        return t!("gui.dashboard.job_not_run");
+    };
+    let locale = lucastra_i18n::current();
+    let outcome = match &run.outcome {
+        JobOutcome::Succeeded(summary) => summary.clone(),
+        JobOutcome::Failed(error) => t!("gui.dashboard.job_failed", error = error),
+        JobOutcome::TimedOut(timeout) => t!(
+            "gui.dashboard.job_timed_out",
+            seconds = locale.integer(timeout.as_secs())
+        ),
+        JobOutcome::Panicked(message) => t!("gui.dashboard.job_crashed", error = message),
+    };
+    t!(
+        "gui.dashboard.job_last_run",
+        time = locale.datetime(run.started_at),
+        outcome = outcome
+    )
+}
+
+/// `92% of 25 calls`, or `no tool calls yet`
+fn tool_success_rate(metrics: &MetricsSnapshot) -> String {
+    let calls = metrics.tool_success_count + metrics.tool_failure_count;
+    if calls == 0 {
+        return t!("gui.dashboard.no_tool_calls");
+    }
+    let percent = metrics.tool_success_count as f64 * 100.0 / calls as f64;
+    let locale = lucastra_i18n::current();
+    t!(
+        "gui.dashboard.success_rate",
+        percent = locale.number(percent, 0),
+        count = locale.integer(calls)
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_app::Metrics;
+
+    #[test]
+    fn test_mount_points_use_the_device_name() {
+        assert_eq!(mount_point_for("/dev/sdb1"), "/mnt/usb/sdb1");
+        assert_eq!(
+            mount_point_for(r"\\.\PhysicalDrive1"),
+            "/mnt/usb/PhysicalDrive1"
+        );
+        assert_eq!(mount_point_for("/media/usb/"), "/mnt/usb/usb");
+    }
+
+    #[test]
+    fn test_metric_and_health_text() {
+        let metrics = Metrics::new();
+        assert_eq!(tool_success_rate(&metrics.snapshot()), "no tool calls yet");
+        for _ in 0..3 {
+            metrics.record_tool_success();
+        }
+        metrics.record_tool_failure();
+        assert_eq!(tool_success_rate(&metrics.snapshot()), "75% of 4 calls");
+
+        assert_eq!(health_text(None), "not monitored");
+        let down = HealthStatus {
+            state: HealthState::Down,
+            consecutive_failures: 3,
+            recoveries: 0,
+            last_checked: Some(1),
+        };
+        assert_eq!(health_text(Some(&down)), "down (3 failed checks)");
+    }
+
+    #[test]
+    fn test_job_text() {
+        let mut job = JobStatus {
+            name: "audit-rotate".to_string(),
+            schedule: Some("every 3600s".to_string()),
+            running: false,
+            last_run: None,
+            runs: 0,
+            failures: 0,
+            skipped: 0,
+        };
+        assert_eq!(job_text(&job), "not run yet");
+        job.last_run = Some(lucastra_app::JobRun {
+            started_at: 0,
+            duration: std::time::Duration::from_secs(1),
+            outcome: JobOutcome::TimedOut(std::time::Duration::from_secs(300)),
+            manual: false,
+        });
+        assert!(job_text(&job).ends_with(": timed out after 300 s"));
+        job.running = true;
+        assert_eq!(job_text(&job), "running");
+    }
+}
@@ -0,0 +1,287 @@
+//! File browser panel.
+//!
+//! Lists directories with the file manager app and previews the selected
+//! file. Copy, move and delete don't happen here: the GUI runs them as host
+//! file access tools so the security whitelist, approvals and audit log
+//! apply to them like to any other tool.
+
+use lucastra_file_manager::{FileEntry, FileManager, FileOpResult};
+use lucastra_i18n::t;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Largest file shown in the preview pane.
+pub const PREVIEW_MAX_BYTES: u64 = 1024 * 1024;
+
+/// Bytes of a binary file shown as hex.
+const HEX_PREVIEW_BYTES: usize = 256;
+
+/// What the preview pane shows for a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Preview {
+    Text(String),
+    /// Not UTF-8: the size and a hex dump of the first bytes.
+    Binary {
+        size: u64,
+        hex: String,
+    },
+    TooLarge {
+        size: u64,
+    },
+}
+
+impl Preview {
+    /// Preview of the file at `path`.
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let size = fs::metadata(path)?.len();
+        if size > PREVIEW_MAX_BYTES {
+            return Ok(Preview::TooLarge { size });
+        }
+        Ok(match String::from_utf8(fs::read(path)?) {
+            Ok(text) => Preview::Text(text),
+            Err(e) => {
+                let bytes = e.into_bytes();
+                Preview::Binary {
+                    size,
+                    hex: hex_dump(&bytes[..bytes.len().min(HEX_PREVIEW_BYTES)]),
+                }
+            }
+        })
+    }
+
+    /// Text for the preview pane.
+    pub fn text(&self) -> String {
+        match self {
+            Preview::Text(text) => text.clone(),
+            Preview::Binary { size, hex } => {
+                format!(
+                    "{}\n\n{}",
+                    t!("gui.files.binary", size = format_size(*size)),
+                    hex
+                )
+            }
+            Preview::TooLarge { size } => t!(
+                "gui.files.too_large",
+                size = format_size(*size),
+                limit = format_size(PREVIEW_MAX_BYTES)
+            ),
+        }
+    }
+}
+
+/// Browser state: the listing, the selected entry and its preview.
+pub struct FileBrowser {
+    manager: FileManager,
+    selected: Option<usize>,
+    preview: Option<Preview>,
+    /// Destination for copy and move as typed, relative to the current
+    /// directory unless absolute.
+    pub destination: String,
+}
+
+impl FileBrowser {
+    pub fn open(dir: PathBuf) -> FileOpResult<Self> {
+        Ok(Self {
+            manager: FileManager::new(dir)?,
+            selected: None,
+            preview: None,
+            destination: String::new(),
+        })
+    }
+
+    pub fn current_dir(&self) -> &Path {
+        &self.manager.current_dir
+    }
+
+    pub fn entries(&self) -> &[FileEntry] {
+        self.manager.list()
+    }
+
+    pub fn selected(&self) -> Option<usize> {
+        self.selected
+    }
+
+    pub fn preview(&self) -> Option<&Preview> {
+        self.preview.as_ref()
+    }
+
+    /// Path of the selected entry, unless it is the `..` link.
+    pub fn selected_path(&self) -> Option<PathBuf> {
+        let entry = self.manager.get_entry(self.selected?)?;
+        (entry.name != "..").then(|| entry.path.clone())
+    }
+
+    /// Select an entry and preview it if it's a file.
+    pub fn select(&mut self, index: usize) -> Result<(), String> {
+        let Some(entry) = self.manager.get_entry(index) else {
+            return Ok(());
+        };
+        self.selected = Some(index);
+        self.preview = None;
+        if !entry.is_dir {
+            let path = entry.path.clone();
+            let preview = Preview::load(&path)
+                .map_err(|e| t!("gui.files.cant_preview", path = path.display(), error = e))?;
+            self.preview = Some(preview);
+        }
+        Ok(())
+    }
+
+    /// Move the selection up (negative) or down the listing.
+    pub fn move_selection(&mut self, delta: isize) -> Result<(), String> {
+        let last = self.entries().len().checked_sub(1);
+        let Some(last) = last else {
+            return Ok(());
+        };
+        let index = match self.selected {
+            Some(current) => current.saturating_add_signed(delta).min(last),
+            None => 0,
+        };
+        self.select(index)
+    }
+
+    /// Enter the selected directory; files are already previewed.
+    pub fn activate(&mut self, index: usize) -> Result<(), String> {
+        let Some(entry) = self.manager.get_entry(index) else {
+            return Ok(());
+        };
+        if !entry.is_dir {
+            return self.select(index);
+        }
+        let path = entry.path.clone();
+        self.navigate(&path)
+    }
+
+    /// Go to the parent directory.
+    pub fn up(&mut self) -> Result<(), String> {
+        match self.current_dir().parent() {
+            Some(parent) => {
+                let parent = parent.to_path_buf();
+                self.navigate(&parent)
+            }
+            None => Ok(()),
+        }
+    }
+
+    /// Re-read the current directory, e.g. after a file operation.
+    pub fn refresh(&mut self) -> Result<(), String> {
+        self.selected = None;
+        self.preview = None;
+        self.manager.refresh().map_err(|e| {
+            t!(
+                "gui.files.cant_list",
+                path = self.manager.current_dir.display(),
+                error = e
+            )
+        })
+    }
+
+    /// Where a copy or move of `source` should go: the typed destination,
+    /// inside it when it is a directory.
+    pub fn destination_for(&self, source: &Path) -> Option<PathBuf> {
+        let typed = self.destination.trim();
+        if typed.is_empty() {
+            return None;
+        }
+        let dest = self.current_dir().join(typed);
+        match source.file_name() {
+            Some(name) if dest.is_dir() => Some(dest.join(name)),
+            _ => Some(dest),
+        }
+    }
+
+    fn navigate(&mut self, path: &Path) -> Result<(), String> {
+        let previous = self.current_dir().to_path_buf();
+        if let Err(e) = self.manager.navigate(path) {
+            // A directory that can't be read leaves the listing where it was
+            self.manager.current_dir = previous;
+            self.manager.history.pop();
+            self.manager.refresh().ok();
+            return Err(t!("gui.cant_open", path = path.display(), error = e));
+        }
+        self.selected = None;
+        self.preview = None;
+        Ok(())
+    }
+}
+
+/// `1.5 KB`-style size.
+pub fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KB", "MB", "GB"];
+    let mut size = bytes as f64;
+    let mut unit = 0;
+    while size >= 1024.0 && unit < UNITS.len() - 1 {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{} B", bytes)
+    } else {
+        format!(
+            "{} {}",
+            lucastra_i18n::current().number(size, 1),
+            UNITS[unit]
+        )
+    }
+}
+
+/// Offset, hex bytes and printable ASCII, 16 bytes per line.
+fn hex_dump(bytes: &[u8]) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(line, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!("{:08x}  {:<47}  {}", line * 16, hex.join(" "), ascii)
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_preview_text_binary_and_large_files() {
+        let dir = std::env::temp_dir().join(format!("lucastra_gui_preview_{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+
+        let text = dir.join("notes.txt");
+        fs::write(&text, "héllo").unwrap();
+        assert_eq!(
+            Preview::load(&text).unwrap(),
+            Preview::Text("héllo".to_string())
+        );
+
+        let binary = dir.join("blob.bin");
+        fs::write(&binary, [0xff, 0x00, b'A']).unwrap();
+        assert_eq!(
+            Preview::load(&binary).unwrap(),
+            Preview::Binary {
+                size: 3,
+                hex: format!("00000000  {:<47}  ..A", "ff 00 41"),
+            }
+        );
+
+        let large = dir.join("large.txt");
+        fs::write(&large, vec![b'a'; PREVIEW_MAX_BYTES as usize + 1]).unwrap();
+        assert!(matches!(
+            Preview::load(&large).unwrap(),
+            Preview::TooLarge { .. }
+        ));
+
+        assert!(Preview::load(&dir.join("missing.txt")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
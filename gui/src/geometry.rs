@@ -0,0 +1,409 @@
+//! Window geometry: the size, position and maximized state kept in `gui`.
+//!
+//! Resizes and moves are followed by a [`GeometryTracker`] and saved once the
+//! window has been still for [`SAVE_DELAY`], and again on exit. On the next
+//! launch the saved geometry is [clamped](WindowGeometry::clamped) to the
+//! monitors attached then, so a window last seen on a disconnected monitor
+//! opens on one that is there.
+
+use lucastra_config::GuiConfig;
+use std::time::{Duration, Instant};
+
+/// How long the window must be left alone before its geometry is saved.
+pub const SAVE_DELAY: Duration = Duration::from_secs(1);
+
+/// Where Windows reports minimized windows to be.
+const MINIMIZED_POSITION: i32 = -32000;
+
+/// A monitor's area in desktop coordinates. Monitors left of or above the
+/// primary one have negative coordinates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Monitor {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub primary: bool,
+}
+
+impl Monitor {
+    /// Area of the part of the `width` × `height` rectangle at `(x, y)` on
+    /// this monitor.
+    fn overlap(&self, (x, y): (i32, i32), width: u32, height: u32) -> i64 {
+        let span = |start: i32, len: u32, monitor_start: i32, monitor_len: u32| {
+            let end =
+                (i64::from(start) + i64::from(len)).min(monitor_start as i64 + monitor_len as i64);
+            (end - i64::from(start.max(monitor_start))).max(0)
+        };
+        span(x, width, self.x, self.width) * span(y, height, self.y, self.height)
+    }
+}
+
+/// Size, position and maximized state of the window.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowGeometry {
+    pub width: u32,
+    pub height: u32,
+    /// Top left corner; `None` leaves placement to the window system.
+    pub position: Option<(i32, i32)>,
+    pub maximized: bool,
+}
+
+impl WindowGeometry {
+    pub fn from_config(gui: &GuiConfig) -> Self {
+        Self {
+            width: gui.window_width,
+            height: gui.window_height,
+            position: gui.window_x.zip(gui.window_y),
+            maximized: gui.window_maximized,
+        }
+    }
+
+    pub fn apply_to(&self, gui: &mut GuiConfig) {
+        gui.window_width = self.width;
+        gui.window_height = self.height;
+        gui.window_x = self.position.map(|(x, _)| x);
+        gui.window_y = self.position.map(|(_, y)| y);
+        gui.window_maximized = self.maximized;
+    }
+
+    /// This geometry fitted onto `monitors`: shrunk to the monitor it is
+    /// mostly on and moved fully onto it. A window on none of them is
+    /// centered on the primary monitor. Unchanged when no monitors are known.
+    pub fn clamped(self, monitors: &[Monitor]) -> Self {
+        let primary = monitors
+            .iter()
+            .find(|monitor| monitor.primary)
+            .or(monitors.first());
+        let Some(primary) = primary else {
+            return self;
+        };
+
+        let on = self.position.and_then(|position| {
+            monitors
+                .iter()
+                .map(|monitor| (monitor, monitor.overlap(position, self.width, self.height)))
+                .filter(|(_, overlap)| *overlap > 0)
+                .max_by_key(|(_, overlap)| *overlap)
+                .map(|(monitor, _)| monitor)
+        });
+        let monitor = on.unwrap_or(primary);
+        let width = self.width.min(monitor.width);
+        let height = self.height.min(monitor.height);
+        let clamp = |start: i32, len: u32, monitor_start: i32, monitor_len: u32| {
+            let last = monitor_start as i64 + i64::from(monitor_len - len);
+            i64::from(start).clamp(monitor_start as i64, last) as i32
+        };
+        let center = |len: u32, monitor_start: i32, monitor_len: u32| {
+            (monitor_start as i64 + i64::from((monitor_len - len) / 2)) as i32
+        };
+
+        let position = match (self.position, on) {
+            (Some((x, y)), Some(_)) => Some((
+                clamp(x, width, monitor.x, monitor.width),
+                clamp(y, height, monitor.y, monitor.height),
+            )),
+            (Some(_), None) => Some((
+                center(width, monitor.x, monitor.width),
+                center(height, monitor.y, monitor.height),
+            )),
+            (None, _) => None,
+        };
+        Self {
+            width,
+            height,
+            position,
+            maximized: self.maximized,
+        }
+    }
+}
+
+/// Follows the window's resizes and moves and says when its geometry should
+/// be saved.
+///
+/// A maximized window fills its monitor, so the size and position reported
+/// while it is maximized aren't kept: restoring it goes back to the last
+/// ones it had before. Whether it is maximized is fetched after each change
+/// and reported with [`maximized`](Self::maximized), which is when reported
+/// size and position are taken.
+#[derive(Debug, Clone)]
+pub struct GeometryTracker {
+    /// Geometry as last saved.
+    saved: WindowGeometry,
+    /// Geometry to save once the window settles.
+    current: WindowGeometry,
+    reported_size: Option<(u32, u32)>,
+    reported_position: Option<(i32, i32)>,
+    changed_at: Option<Instant>,
+}
+
+impl GeometryTracker {
+    /// Track a window opened with `saved`.
+    pub fn new(saved: WindowGeometry) -> Self {
+        Self {
+            saved,
+            current: saved,
+            reported_size: None,
+            reported_position: None,
+            changed_at: None,
+        }
+    }
+
+    pub fn resized(&mut self, width: u32, height: u32, now: Instant) {
+        // Minimized windows report a zero size
+        if width > 0 && height > 0 {
+            self.reported_size = Some((width, height));
+            self.changed_at = Some(now);
+        }
+    }
+
+    pub fn moved(&mut self, x: i32, y: i32, now: Instant) {
+        if x > MINIMIZED_POSITION && y > MINIMIZED_POSITION {
+            self.reported_position = Some((x, y));
+            self.changed_at = Some(now);
+        }
+    }
+
+    pub fn maximized(&mut self, maximized: bool, now: Instant) {
+        let size = self.reported_size.take();
+        let position = self.reported_position.take();
+        if !maximized {
+            if let Some((width, height)) = size {
+                self.current.width = width;
+                self.current.height = height;
+            }
+            self.current.position = position.or(self.current.position);
+        }
+        self.current.maximized = maximized;
+        self.changed_at = Some(now);
+    }
+
+    /// The geometry to save, once the window has been left alone for
+    /// [`SAVE_DELAY`] and only if it differs from what was saved.
+    pub fn due(&mut self, now: Instant) -> Option<WindowGeometry> {
+        match self.changed_at {
+            Some(changed) if now.duration_since(changed) >= SAVE_DELAY => self.flush(),
+            _ => None,
+        }
+    }
+
+    /// The geometry to save right away, e.g. on exit, if it changed.
+    pub fn flush(&mut self) -> Option<WindowGeometry> {
+        self.changed_at = None;
+        if self.current == self.saved {
+            return None;
+        }
+        self.saved = self.current;
+        Some(self.current)
+    }
+}
+
+/// Monitors attached right now. Empty where they can't be listed, e.g. on
+/// Wayland, which doesn't let windows choose their position anyway.
+///
+/// Called before the window exists, so on Windows the process isn't DPI
+/// aware yet and the bounds come back in the same scaled units as window
+/// positions.
+#[cfg(target_os = "linux")]
+pub fn monitors() -> Vec<Monitor> {
+    use x11rb::connection::Connection;
+    use x11rb::protocol::randr::ConnectionExt as _;
+
+    let Ok((connection, screen)) = x11rb::connect(None) else {
+        return Vec::new();
+    };
+    let root = connection.setup().roots[screen].root;
+    let reply = match connection.randr_get_monitors(root, true) {
+        Ok(cookie) => cookie.reply(),
+        Err(e) => {
+            tracing::debug!("Can't list monitors: {}", e);
+            return Vec::new();
+        }
+    };
+    match reply {
+        Ok(reply) => reply
+            .monitors
+            .iter()
+            .map(|monitor| Monitor {
+                x: monitor.x.into(),
+                y: monitor.y.into(),
+                width: monitor.width.into(),
+                height: monitor.height.into(),
+                primary: monitor.primary,
+            })
+            .collect(),
+        Err(e) => {
+            tracing::debug!("Can't list monitors: {}", e);
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(windows)]
+pub fn monitors() -> Vec<Monitor> {
+    use windows::Win32::Foundation::{BOOL, LPARAM, RECT};
+    use windows::Win32::Graphics::Gdi::{
+        EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR, MONITORINFO, MONITORINFOF_PRIMARY,
+    };
+
+    unsafe extern "system" fn collect(
+        monitor: HMONITOR,
+        _hdc: HDC,
+        _rect: *mut RECT,
+        data: LPARAM,
+    ) -> BOOL {
+        // SAFETY: `data` is the `Vec` passed to `EnumDisplayMonitors` below,
+        // which outlives the enumeration
+        let monitors = unsafe { &mut *(data.0 as *mut Vec<Monitor>) };
+        let mut info = MONITORINFO {
+            cbSize: std::mem::size_of::<MONITORINFO>() as u32,
+            ..Default::default()
+        };
+        // SAFETY: `info` is a MONITORINFO with `cbSize` set
+        if unsafe { GetMonitorInfoW(monitor, &mut info) }.as_bool() {
+            // The work area leaves out the taskbar
+            let area = info.rcWork;
+            monitors.push(Monitor {
+                x: area.left,
+                y: area.top,
+                width: (area.right - area.left).max(0) as u32,
+                height: (area.bottom - area.top).max(0) as u32,
+                primary: info.dwFlags & MONITORINFOF_PRIMARY != 0,
+            });
+        }
+        true.into()
+    }
+
+    let mut monitors: Vec<Monitor> = Vec::new();
+    // SAFETY: `collect` only runs during the call and `monitors` outlives it
+    unsafe {
+        EnumDisplayMonitors(
+            HDC::default(),
+            None,
+            Some(collect),
+            LPARAM(&mut monitors as *mut Vec<Monitor> as isize),
+        );
+    }
+    monitors
+}
+
+#[cfg(not(any(target_os = "linux", windows)))]
+pub fn monitors() -> Vec<Monitor> {
+    Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PRIMARY: Monitor = Monitor {
+        x: 0,
+        y: 0,
+        width: 1920,
+        height: 1080,
+        primary: true,
+    };
+    /// A smaller monitor left of the primary one.
+    const LEFT: Monitor = Monitor {
+        x: -1280,
+        y: 200,
+        width: 1280,
+        height: 720,
+        primary: false,
+    };
+
+    fn window(width: u32, height: u32, x: i32, y: i32) -> WindowGeometry {
+        WindowGeometry {
+            width,
+            height,
+            position: Some((x, y)),
+            maximized: false,
+        }
+    }
+
+    #[test]
+    fn test_windows_are_clamped_to_attached_monitors() {
+        let monitors = [LEFT, PRIMARY];
+        let clamp = |geometry: WindowGeometry| geometry.clamped(&monitors);
+
+        // Fully on a monitor, including one with negative coordinates
+        assert_eq!(clamp(window(800, 600, 100, 50)), window(800, 600, 100, 50));
+        assert_eq!(
+            clamp(window(800, 600, -1000, 250)),
+            window(800, 600, -1000, 250)
+        );
+        // Hanging off an edge: moved back onto the monitor it is mostly on
+        assert_eq!(
+            clamp(window(800, 600, 1500, 900)),
+            window(800, 600, 1120, 480)
+        );
+        assert_eq!(
+            clamp(window(800, 600, -1400, 100)),
+            window(800, 600, -1280, 200)
+        );
+        // Larger than its monitor: shrunk to fit
+        assert_eq!(
+            clamp(window(1600, 900, -1300, 200)),
+            window(1280, 720, -1280, 200)
+        );
+        // Saved on a monitor that is gone: centered on the primary one
+        assert_eq!(
+            clamp(window(800, 600, 2500, 100)),
+            window(800, 600, 560, 240)
+        );
+        assert_eq!(
+            window(1280, 800, -1300, 300).clamped(&[PRIMARY]),
+            window(1280, 800, 320, 140)
+        );
+
+        let unplaced = WindowGeometry {
+            width: 2560,
+            height: 1440,
+            position: None,
+            maximized: true,
+        };
+        assert_eq!(
+            unplaced.clamped(&monitors),
+            WindowGeometry {
+                width: 1920,
+                height: 1080,
+                ..unplaced
+            }
+        );
+        // Nothing known about the monitors: left alone
+        assert_eq!(
+            window(800, 600, 5000, 5000).clamped(&[]),
+            window(800, 600, 5000, 5000)
+        );
+    }
+
+    #[test]
+    fn test_geometry_is_saved_once_the_window_settles() {
+        let start = Instant::now();
+        let later = |millis| start + Duration::from_millis(millis);
+        let mut tracker = GeometryTracker::new(window(1280, 800, 0, 0));
+
+        tracker.resized(1000, 700, later(0));
+        tracker.moved(-900, 300, later(100));
+        tracker.maximized(false, later(200));
+        assert_eq!(tracker.due(later(900)), None);
+        assert_eq!(tracker.due(later(1200)), Some(window(1000, 700, -900, 300)));
+        assert_eq!(tracker.due(later(5000)), None);
+
+        // Maximizing keeps the size and position to restore to
+        tracker.resized(1920, 1080, later(6000));
+        tracker.moved(0, 0, later(6000));
+        tracker.maximized(true, later(6100));
+        let maximized = WindowGeometry {
+            maximized: true,
+            ..window(1000, 700, -900, 300)
+        };
+        assert_eq!(tracker.flush(), Some(maximized));
+
+        // Minimizing isn't a change
+        tracker.resized(0, 0, later(8000));
+        tracker.moved(-32000, -32000, later(8000));
+        tracker.maximized(true, later(8100));
+        assert_eq!(tracker.flush(), None);
+    }
+}
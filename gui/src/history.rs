@@ -0,0 +1,260 @@
+//! Chat history kept across sessions.
+//!
+//! Messages are appended to `chat_history.json` one JSON object per line as
+//! they are sent, so a crash loses at most the message being written. Once
+//! the file holds twice the history limit it is rewritten with only the
+//! most recent messages.
+
+use serde::{Deserialize, Serialize};
+use std::fs::{self, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use lucastra_core::SourceRef;
+
+/// File name under the data directory.
+pub const HISTORY_FILE: &str = "chat_history.json";
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ChatMessage {
+    pub role: String,
+    pub content: String,
+    /// Sources an assistant answer cited, shown as links below it.
+    #[serde(default)]
+    pub sources: Vec<SourceRef>,
+    /// Unix milliseconds when the message was written, ordering messages
+    /// from different sessions.
+    #[serde(default)]
+    pub timestamp: u64,
+    /// Show the content as written instead of rendering its Markdown.
+    #[serde(skip)]
+    pub show_raw: bool,
+}
+
+impl ChatMessage {
+    pub fn new(role: &str, content: impl Into<String>) -> Self {
+        Self {
+            role: role.to_string(),
+            content: content.into(),
+            sources: Vec::new(),
+            timestamp: now_millis(),
+            show_raw: false,
+        }
+    }
+
+    pub fn system(content: impl Into<String>) -> Self {
+        Self::new("system", content)
+    }
+}
+
+/// Messages read back from disk.
+#[derive(Debug, Default)]
+pub struct LoadedHistory {
+    /// The most recent messages, oldest first.
+    pub messages: Vec<ChatMessage>,
+    /// Where an unreadable history file was moved, if there was one.
+    pub moved_aside: Option<PathBuf>,
+}
+
+/// Append-only JSON Lines file of chat messages.
+pub struct HistoryStore {
+    path: PathBuf,
+    limit: usize,
+    /// Messages currently in the file.
+    lines: usize,
+}
+
+impl HistoryStore {
+    /// Store at `path` keeping the last `limit` messages.
+    pub fn new(path: PathBuf, limit: usize) -> Self {
+        Self {
+            path,
+            limit,
+            lines: 0,
+        }
+    }
+
+    /// Default location, `~/.lucastra/data/chat_history.json`.
+    pub fn default_path() -> Option<PathBuf> {
+        lucastra_config::get_data_dir()
+            .ok()
+            .map(|dir| dir.join(HISTORY_FILE))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn set_limit(&mut self, limit: usize) {
+        self.limit = limit;
+    }
+
+    /// Read the last `limit` messages in timestamp order. A file that
+    /// can't be parsed is renamed aside so the next append starts afresh.
+    pub fn load(&mut self) -> io::Result<LoadedHistory> {
+        let contents = match fs::read_to_string(&self.path) {
+            Ok(contents) => contents,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(LoadedHistory::default()),
+            Err(e) => return Err(e),
+        };
+
+        let parsed: serde_json::Result<Vec<ChatMessage>> = contents
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(serde_json::from_str)
+            .collect();
+        let mut messages = match parsed {
+            Ok(messages) => messages,
+            Err(e) => {
+                let aside = self
+                    .path
+                    .with_extension(format!("json.corrupt-{}", now_millis()));
+                tracing::warn!(
+                    "Chat history {} is corrupted ({}); moving it to {}",
+                    self.path.display(),
+                    e,
+                    aside.display()
+                );
+                fs::rename(&self.path, &aside)?;
+                self.lines = 0;
+                return Ok(LoadedHistory {
+                    messages: Vec::new(),
+                    moved_aside: Some(aside),
+                });
+            }
+        };
+
+        self.lines = messages.len();
+        messages.sort_by_key(|m| m.timestamp);
+        let excess = messages.len().saturating_sub(self.limit);
+        messages.drain(..excess);
+        Ok(LoadedHistory {
+            messages,
+            moved_aside: None,
+        })
+    }
+
+    /// Add a message to the file, compacting it when it has grown to twice
+    /// the limit.
+    pub fn append(&mut self, message: &ChatMessage) -> io::Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(message)?)?;
+        self.lines += 1;
+
+        if self.lines > self.limit.saturating_mul(2) {
+            self.compact()?;
+        }
+        Ok(())
+    }
+
+    /// Delete every saved message.
+    pub fn clear(&mut self) -> io::Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+        self.lines = 0;
+        Ok(())
+    }
+
+    /// Rewrite the file with only the last `limit` messages.
+    pub fn compact(&mut self) -> io::Result<()> {
+        let kept = self.load()?.messages;
+        let mut contents = String::new();
+        for message in &kept {
+            contents.push_str(&serde_json::to_string(message)?);
+            contents.push('\n');
+        }
+        // Write a sibling first so a crash can't leave a half-written history
+        let temp = self.path.with_extension("json.tmp");
+        fs::write(&temp, contents)?;
+        fs::rename(&temp, &self.path)?;
+        self.lines = kept.len();
+        Ok(())
+    }
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "lucastra_gui_history_{}_{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn message(content: &str, timestamp: u64) -> ChatMessage {
+        ChatMessage {
+            timestamp,
+            ..ChatMessage::new("user", content)
+        }
+    }
+
+    #[test]
+    fn test_reload_keeps_last_messages_in_order() {
+        let dir = temp_dir("reload");
+        let path = dir.join(HISTORY_FILE);
+
+        let mut store = HistoryStore::new(path.clone(), 3);
+        // A second session wrote an earlier message after this one
+        for (content, timestamp) in [("b", 2), ("c", 3), ("a", 1), ("d", 4), ("e", 5)] {
+            store.append(&message(content, timestamp)).unwrap();
+        }
+
+        let loaded = HistoryStore::new(path.clone(), 3).load().unwrap();
+        let contents: Vec<_> = loaded.messages.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["c", "d", "e"]);
+        assert!(loaded.moved_aside.is_none());
+
+        // Going past twice the limit compacts the file to the limit
+        store.append(&message("f", 6)).unwrap();
+        store.append(&message("g", 7)).unwrap();
+        let lines = fs::read_to_string(&path).unwrap().lines().count();
+        assert_eq!(lines, 3);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_corrupted_file_is_moved_aside() {
+        let dir = temp_dir("corrupt");
+        let path = dir.join(HISTORY_FILE);
+        fs::write(
+            &path,
+            "{\"role\": \"user\", \"content\": \"hi\"}\nnot json\n",
+        )
+        .unwrap();
+
+        let mut store = HistoryStore::new(path.clone(), 10);
+        let loaded = store.load().unwrap();
+        assert!(loaded.messages.is_empty());
+        let aside = loaded.moved_aside.unwrap();
+        assert!(aside.exists());
+        assert!(!path.exists());
+
+        store.append(&message("fresh", 1)).unwrap();
+        assert_eq!(store.load().unwrap().messages.len(), 1);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
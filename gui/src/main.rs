@@ -1,43 +1,168 @@
+mod attachments;
+mod dashboard;
+mod file_browser;
+mod geometry;
+mod history;
+mod markdown;
+mod onboarding;
+mod scale;
+mod settings;
+mod shortcuts;
+mod tabs;
+mod theme;
+mod transcript;
+
+use attachments::Attachments;
+use dashboard::{DashboardAction, DashboardView};
+use file_browser::FileBrowser;
+use geometry::{GeometryTracker, WindowGeometry};
+use history::{ChatMessage, HistoryStore};
+use iced::keyboard::{key::Named, Key, Modifiers};
 use iced::widget::{
     button, checkbox, column, container, pick_list, row, scrollable, text, text_input, Column,
 };
-use iced::{Alignment, Color, Element, Length, Sandbox, Settings, Size};
-use lucastra_app::SystemState;
-use lucastra_config::{self, Config};
-use lucastra_core::{Command, CommandPayload, ResponsePayload};
-use tracing_subscriber::{layer::SubscriberExt, EnvFilter};
+use iced::{
+    executor, window, Alignment, Application, Element, Length, Point, Settings, Size, Subscription,
+    Theme,
+};
+use lucastra_app::{observability::init_tracing, CommandBus, RpcServer, SystemState};
+use lucastra_config::{self, Config, ConfigEvent, ProviderConfig, ShortcutRegistry};
+use lucastra_core::{
+    slash, ChatInput, Command, CommandPayload, DeviceEvent, DeviceType, ErrorCode, Response,
+    ResponsePayload, SearchPage, SlashAction,
+};
+use lucastra_i18n::{t, Locale};
+use lucastra_llm::providers::create_provider;
+use lucastra_tools::{file_access::FileOperation, Tool, ToolResult};
+use onboarding::{ConnectionTest, Wizard, WizardAction};
+use scale::UiScale;
+use settings::{SettingChange, SettingsForm, SettingsSection};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+use tabs::{ClosedTab, Tabs, CONVERSATION_PREFIX};
+use theme::ThemePalette;
+
+/// Widget ids shortcuts move focus to.
+const CHAT_INPUT: &str = "chat-input";
+const HISTORY_FILTER: &str = "history-filter";
+const TAB_TITLE: &str = "tab-title";
+
+/// How often the open dashboard takes a fresh snapshot.
+const DASHBOARD_REFRESH: Duration = Duration::from_secs(3);
 
 #[derive(Debug, Clone)]
 pub enum Message {
     InputChanged(String),
+    /// Show only messages containing this text.
+    FilterHistory(String),
+    /// Put text on the system clipboard.
+    CopyText(String),
+    /// Switch a message between rendered Markdown and its raw text.
+    ToggleRaw(usize),
+    ExportPathChanged(String),
+    /// Save the chat as Markdown to the export path.
+    ExportTranscript,
     SendMessage,
+    /// Clear the chat and start a conversation without the earlier turns.
+    NewChat,
+    /// Open a chat tab with a new conversation.
+    NewTab,
+    /// A click on a tab; a double click renames it.
+    TabClicked(usize),
+    /// Close a tab, asking first while it waits for a reply.
+    CloseTab(usize),
+    /// Close the tab the confirmation asked about.
+    ConfirmCloseTab,
+    /// Keep the tab the confirmation asked about open.
+    KeepTab,
+    ReopenTab(ClosedTab),
+    /// Show the next tab, or the previous one if set.
+    CycleTab(bool),
+    TabTitleChanged(String),
+    FinishRename,
+    /// The chat input after a paste, before the pasted text is checked.
+    InputPasted(String),
+    /// Text just pasted, with the input as it was before the paste.
+    ClipboardRead(String, Option<String>),
+    /// Files are being dragged over the window.
+    FileHovered,
+    FilesHoveredLeft,
+    /// A file dropped on the window, to attach to the next query.
+    FileDropped(PathBuf),
+    RemoveAttachment(usize),
+    /// Fill in a slash command picked from the completion list.
+    CompleteCommand(&'static str),
+    /// Search indexed documents for the input text.
+    Search,
+    /// Show the page of the current search starting at this offset.
+    SearchPage(usize),
+    CloseSearch,
+    ResponseReceived(Response),
+    Cancel(String),
+    /// Toggles the "…" shown on replies still being generated.
+    Blink,
+    /// Periodic tick so device and config changes surface without input.
+    PollEvents,
     OpenFileManager,
+    Files(FileAction),
+    OpenDashboard,
+    Dashboard(DashboardAction),
+    OpenWizard,
+    Wizard(WizardAction),
+    /// Show the file behind a cited source.
+    OpenSource(String),
     OpenSettings,
     CloseSettings,
     SaveSettings,
+    /// Put a settings section back to its defaults.
+    ResetSection(SettingsSection),
+    /// Models the default provider offers, or why they couldn't be listed.
+    ModelsListed(Result<Vec<String>, String>),
+    /// A key press, and whether a text field had focus and took it.
+    KeyPressed(Key, Modifiers, bool),
+    /// Put the cursor in the chat input.
+    FocusInput,
+    /// Put the cursor in the message filter.
+    FocusSearch,
     ClearError,
     DismissToast(usize),
+    ApproveOperation(String),
+    DenyOperation(String),
     UpdateSetting(SettingChange),
+    WindowResized(u32, u32),
+    WindowMoved(i32, i32),
+    /// Whether the window is maximized, fetched after it moved or resized.
+    WindowMaximized(bool),
+    /// The window is being closed; saves its geometry first.
+    WindowCloseRequested,
 }
 
 #[derive(Debug, Clone)]
-pub enum SettingChange {
-    ServerUrl(String),
-    ModelSize(String),
-    Temperature(String),
-    MaxTokens(String),
-    Theme(String),
-    AutoStart(bool),
-    UseGpu(bool),
-    WindowWidth(String),
-    WindowHeight(String),
-    FontSize(String),
+pub enum FileAction {
+    /// Select an entry, previewing it if it's a file.
+    Select(usize),
+    /// Open a directory entry.
+    Open(usize),
+    /// Move the selection by this many entries.
+    MoveSelection(isize),
+    /// Open the selected entry.
+    OpenSelected,
+    Up,
+    DestinationChanged(String),
+    Copy,
+    Move,
+    Delete,
+    Close,
 }
 
+/// Results of the last search, one page at a time.
 #[derive(Debug, Clone)]
-pub struct ChatMessage {
-    pub role: String,
-    pub content: String,
+struct SearchView {
+    query: String,
+    /// Results per page.
+    limit: usize,
+    page: SearchPage,
 }
 
 #[derive(Debug, Clone)]
@@ -47,21 +172,61 @@ pub struct NoticeToast {
 }
 
 pub struct App {
-    system_state: SystemState,
+    system_state: Arc<Mutex<SystemState>>,
+    /// Runs queries in the background so the window stays responsive.
+    bus: CommandBus,
+    /// Whether the "…" indicator is currently shown.
+    blink: bool,
     chat_input: String,
-    chat_history: Vec<ChatMessage>,
+    /// Files and long pastes to send with the next query.
+    attachments: Attachments,
+    /// Chats, each with its own messages and conversation.
+    tabs: Tabs,
+    /// Conversation of the tab being renamed, and the title typed so far.
+    renaming: Option<(String, String)>,
+    /// Conversation of a busy tab waiting for confirmation to close.
+    confirm_close: Option<String>,
+    /// Most messages per tab kept in memory, and on disk for the first.
+    history_limit: usize,
+    /// Text messages must contain to be shown.
+    history_filter: String,
+    /// Where "Export transcript" writes, as typed.
+    export_path: String,
+    search: Option<SearchView>,
     command_counter: usize,
+    /// Whether answers currently come from the placeholder model.
+    llm_offline: bool,
     settings_open: bool,
-    temp_config: Config,
+    /// Colors for `gui.theme` as last saved.
+    palette: ThemePalette,
+    /// Text sizes for `gui.font_size` and `gui.ui_scale` as last saved.
+    scale: UiScale,
+    /// Open instead of the chat when set.
+    file_browser: Option<FileBrowser>,
+    /// Open instead of the chat and file browser when set.
+    dashboard: Option<DashboardView>,
+    /// The setup wizard; shown instead of everything else when set.
+    wizard: Option<Wizard>,
+    /// Chords for `gui.shortcuts` as last saved.
+    shortcuts: ShortcutRegistry,
+    /// Window size and position, saved to `gui` as they change.
+    geometry: GeometryTracker,
+    /// Serves the JSON-RPC API while the window is open, if enabled.
+    _rpc: Option<RpcServer>,
+    settings: SettingsForm,
     error: Option<String>,
     notices: Vec<NoticeToast>,
     next_notice_id: usize,
 }
 
-impl Sandbox for App {
+impl Application for App {
+    type Executor = executor::Default;
     type Message = Message;
+    type Theme = Theme;
+    /// Whether config.toml was created on this start.
+    type Flags = bool;
 
-    fn new() -> Self {
+    fn new(first_run: bool) -> (Self, iced::Command<Message>) {
         let system_state = match SystemState::new() {
             Ok(state) => state,
             Err(e) => {
@@ -70,43 +235,181 @@ impl Sandbox for App {
             }
         };
 
-        let temp_config = system_state.get_config().clone();
+        let config = system_state.get_config().clone();
+        let palette = ThemePalette::from_config(&config.gui.theme);
+        let scale = UiScale::from_config(&config.gui);
+        let shortcuts = shortcut_registry(&config);
+        let geometry = WindowGeometry::from_config(&config.gui);
+        let history_limit = config.gui.message_history_limit;
+        let export_path = config
+            .security
+            .resolved_allowed_dirs()
+            .first()
+            .map(|dir| dir.join("lucastra-transcript.md").display().to_string())
+            .unwrap_or_default();
+        let mut history_store =
+            HistoryStore::default_path().map(|path| HistoryStore::new(path, history_limit));
+        let mut notices = Vec::new();
+        let mut chat_history = match history_store.as_mut().map(|store| store.load()) {
+            Some(Ok(loaded)) => {
+                if let Some(aside) = loaded.moved_aside {
+                    notices.push(NoticeToast {
+                        id: 0,
+                        message: t!("gui.history_moved_aside", path = aside.display()),
+                    });
+                }
+                loaded.messages
+            }
+            Some(Err(e)) => {
+                tracing::warn!("Failed to load chat history: {}", e);
+                Vec::new()
+            }
+            None => Vec::new(),
+        };
+        chat_history.push(ChatMessage::system(t!("gui.welcome")));
+        let mut tabs = Tabs::new(chat_history, history_store);
+        tabs.remember_saved(
+            system_state
+                .saved_conversations(CONVERSATION_PREFIX)
+                .into_iter()
+                .map(|summary| ClosedTab {
+                    title: summary.title,
+                    conversation_id: summary.id,
+                }),
+        );
+        let system_state = Arc::new(Mutex::new(system_state));
+        if let Err(e) = SystemState::start_jobs(&system_state) {
+            tracing::warn!("Background jobs not started: {}", e);
+        }
+        let bus = CommandBus::start(system_state.clone()).expect("Failed to start command bus");
+        let rpc = config.rpc.enabled.then(|| {
+            RpcServer::start(&system_state, &config.rpc)
+                .map_err(|e| tracing::warn!("JSON-RPC API not started: {}", e))
+                .ok()
+        });
 
-        Self {
+        let app = Self {
             system_state,
+            bus,
+            blink: true,
             chat_input: String::new(),
-            chat_history: vec![ChatMessage {
-                role: "system".to_string(),
-                content: "Welcome to LucAstra OS! Ask me anything.".to_string(),
-            }],
+            attachments: Attachments::default(),
+            tabs,
+            renaming: None,
+            confirm_close: None,
+            history_limit,
+            history_filter: String::new(),
+            export_path,
+            search: None,
             command_counter: 0,
+            llm_offline: false,
             settings_open: false,
-            temp_config,
+            palette,
+            scale,
+            file_browser: None,
+            dashboard: None,
+            wizard: first_run.then(|| Wizard::new(&config)),
+            shortcuts,
+            geometry: GeometryTracker::new(geometry),
+            _rpc: rpc.flatten(),
+            settings: SettingsForm::new(config),
             error: None,
-            notices: Vec::new(),
-            next_notice_id: 0,
-        }
+            next_notice_id: notices.len(),
+            notices,
+        };
+        let command = if geometry.maximized {
+            window::maximize(window::Id::MAIN, true)
+        } else {
+            iced::Command::none()
+        };
+        (app, command)
     }
 
     fn title(&self) -> String {
-        "LucAstra OS - Desktop".to_string()
+        t!("gui.title")
     }
 
-    fn update(&mut self, message: Self::Message) {
+    fn theme(&self) -> Theme {
+        self.palette.iced_theme()
+    }
+
+    fn scale_factor(&self) -> f64 {
+        self.scale.factor()
+    }
+
+    fn update(&mut self, message: Self::Message) -> iced::Command<Message> {
+        self.notify_device_events();
+        self.notify_config_events();
+
         match message {
             Message::InputChanged(value) => {
                 self.chat_input = value;
             }
+            Message::InputPasted(contents) => {
+                // Only the clipboard tells what was pasted; control
+                // characters such as newlines are already stripped here
+                let before = std::mem::replace(&mut self.chat_input, contents);
+                return iced::clipboard::read(move |pasted| {
+                    Message::ClipboardRead(before.clone(), pasted)
+                });
+            }
+            Message::ClipboardRead(before, Some(pasted)) if attachments::is_long_paste(&pasted) => {
+                self.chat_input = before;
+                self.attachments.paste(pasted);
+            }
+            Message::ClipboardRead(..) => {}
+            Message::FileHovered => self.attachments.hover(),
+            Message::FilesHoveredLeft => self.attachments.end_hover(),
+            Message::FileDropped(path) => {
+                let read = self.state().read_attachment(&path);
+                if let Err(toast) = self.attachments.drop_file(&path, read) {
+                    self.push_notice(toast);
+                }
+            }
+            Message::RemoveAttachment(id) => {
+                self.attachments.remove(id);
+            }
+            Message::FilterHistory(filter) => {
+                self.history_filter = filter;
+            }
+            Message::CopyText(text) => {
+                self.push_notice(t!("gui.copied"));
+                return iced::clipboard::write(text);
+            }
+            Message::ToggleRaw(index) => {
+                if let Some(message) = self.tabs.active_mut().messages.get_mut(index) {
+                    message.show_raw = !message.show_raw;
+                }
+            }
+            Message::ExportPathChanged(path) => {
+                self.export_path = path;
+            }
+            Message::ExportTranscript => {
+                let path = PathBuf::from(self.export_path.trim());
+                let security = self.state().get_config().security.clone();
+                match transcript::export(&self.tabs.active().messages, &path, &security) {
+                    Ok(()) => self.push_notice(t!("gui.transcript_saved", path = path.display())),
+                    Err(e) => self.error = Some(e),
+                }
+            }
             Message::SendMessage => {
                 if self.chat_input.trim().is_empty() {
-                    return;
+                    return iced::Command::none();
                 }
 
-                let user_message = self.chat_input.clone();
-                self.chat_history.push(ChatMessage {
-                    role: "user".to_string(),
-                    content: user_message.clone(),
-                });
+                // Bad commands stay in the input to be fixed
+                let user_message = match slash::parse_input(&self.chat_input) {
+                    Ok(ChatInput::Message(text)) => text,
+                    Ok(ChatInput::Command(action)) => {
+                        self.chat_input.clear();
+                        return self.run_slash_command(action);
+                    }
+                    Err(e) => {
+                        self.error = Some(e.to_string());
+                        return iced::Command::none();
+                    }
+                };
+                self.push_message(ChatMessage::new("user", user_message.clone()));
                 self.chat_input.clear();
 
                 self.command_counter += 1;
@@ -115,71 +418,249 @@ impl Sandbox for App {
                     payload: CommandPayload::Query {
                         text: user_message,
                         use_rag: Some(true),
+                        conversation_id: Some(self.tabs.active().conversation_id.clone()),
+                        attachments: self.attachments.take(),
                     },
                 };
 
-                let response = match self.system_state.handle_command(cmd) {
-                    Ok(resp) => match resp.payload {
-                        ResponsePayload::Success(text) => text,
-                        ResponsePayload::Status(status) => status,
-                        ResponsePayload::Devices(devices) => devices.join("\n"),
-                        ResponsePayload::Files(files) => files
-                            .iter()
-                            .map(|f| f.path.clone())
-                            .collect::<Vec<_>>()
-                            .join("\n"),
-                        ResponsePayload::Content(bytes) => {
-                            String::from_utf8_lossy(&bytes).to_string()
-                        }
-                        ResponsePayload::SearchResults(results) => results
-                            .iter()
-                            .map(|r| format!("{}: {}", r.path, r.snippet))
-                            .collect::<Vec<_>>()
-                            .join("\n"),
-                        ResponsePayload::Error(err) => format!("Error: {}", err),
-                    },
-                    Err(e) => {
-                        self.error = Some(format!("Command failed: {}", e));
-                        format!("System error: {}", e)
-                    }
+                // The reply streams into an empty assistant message
+                self.tabs.active_mut().start_reply(cmd.id.clone());
+                let responses = self.bus.send_streaming(cmd);
+                let stream = iced::futures::stream::unfold(responses, |mut responses| async {
+                    let response = responses.recv().await?;
+                    Some((response, responses))
+                });
+                return iced::Command::run(stream, Message::ResponseReceived);
+            }
+            Message::NewChat => self.new_chat(),
+            Message::NewTab => {
+                self.tabs.open();
+                return text_input::focus(text_input::Id::new(CHAT_INPUT));
+            }
+            Message::TabClicked(index) => {
+                self.finish_rename();
+                if self.tabs.click(index, Instant::now()) {
+                    let tab = &self.tabs[index];
+                    self.renaming = Some((tab.conversation_id.clone(), tab.title.clone()));
+                    return text_input::focus(text_input::Id::new(TAB_TITLE));
+                }
+            }
+            Message::CloseTab(index) => match self.tabs.get(index) {
+                Some(tab) if tab.is_busy() => {
+                    self.confirm_close = Some(tab.conversation_id.clone());
+                }
+                Some(_) => self.close_tab(index),
+                None => {}
+            },
+            Message::ConfirmCloseTab => {
+                let index = self
+                    .confirm_close
+                    .take()
+                    .and_then(|id| self.tabs.position(&id));
+                if let Some(index) = index {
+                    self.close_tab(index);
+                }
+            }
+            Message::KeepTab => {
+                self.confirm_close = None;
+            }
+            Message::ReopenTab(closed) => self.reopen_tab(closed),
+            Message::CycleTab(back) => {
+                self.finish_rename();
+                self.tabs.cycle(back);
+            }
+            Message::TabTitleChanged(title) => {
+                if let Some((_, draft)) = self.renaming.as_mut() {
+                    *draft = title;
+                }
+            }
+            Message::FinishRename => self.finish_rename(),
+            Message::CompleteCommand(name) => {
+                self.chat_input = format!("/{} ", name);
+                return text_input::focus(text_input::Id::new(CHAT_INPUT));
+            }
+            Message::Search => {
+                let query = self.chat_input.trim().to_string();
+                if !query.is_empty() {
+                    let limit = self.state().get_config().search.max_results;
+                    self.run_search(query, 0, limit);
+                }
+            }
+            Message::SearchPage(offset) => {
+                if let Some(search) = &self.search {
+                    let (query, limit) = (search.query.clone(), search.limit);
+                    self.run_search(query, offset, limit);
+                }
+            }
+            Message::CloseSearch => {
+                self.search = None;
+            }
+            Message::ResponseReceived(response) => {
+                // Cancelled requests were already reported; replies may
+                // arrive for a tab that isn't shown
+                let Some((tab, index)) = self.tabs.find_reply(&response.command_id) else {
+                    return iced::Command::none();
                 };
+                let limit = self.history_limit;
+                let chat = &mut self.tabs[tab];
+                let message = chat.pending[index].message;
 
-                self.chat_history.push(ChatMessage {
-                    role: "assistant".to_string(),
-                    content: response,
-                });
+                match response.payload {
+                    ResponsePayload::Partial(delta) => {
+                        chat.messages[message].content.push_str(&delta);
+                    }
+                    ResponsePayload::Finished {
+                        stop_reason,
+                        sources,
+                        used_context,
+                    } => {
+                        chat.messages[message].sources = sources;
+                        chat.finish_reply(index, limit);
+                        self.note_offline_answers(tab);
+                        if !used_context {
+                            self.note_answered_without_documents(tab);
+                        }
+                        if stop_reason == "length" {
+                            self.tabs[tab].push(ChatMessage::system(t!("gui.cut_off")), limit);
+                        }
+                    }
+                    ResponsePayload::RagAnswer {
+                        text,
+                        sources,
+                        used_context,
+                    } => {
+                        chat.messages[message].content = text;
+                        chat.messages[message].sources = sources;
+                        chat.finish_reply(index, limit);
+                        self.note_offline_answers(tab);
+                        if !used_context {
+                            self.note_answered_without_documents(tab);
+                        }
+                    }
+                    payload => {
+                        let text = response_text(payload);
+                        if chat.messages[message].content.is_empty() {
+                            chat.messages[message].content = text;
+                            chat.finish_reply(index, limit);
+                        } else {
+                            chat.finish_reply(index, limit);
+                            chat.push(ChatMessage::system(text), limit);
+                        }
+                    }
+                }
+            }
+            Message::Cancel(command_id) => {
+                self.bus.cancel(&command_id);
+                // Keep what was generated before the stop
+                let limit = self.history_limit;
+                if let Some((tab, index)) = self.tabs.find_reply(&command_id) {
+                    let chat = &mut self.tabs[tab];
+                    chat.finish_reply(index, limit);
+                    chat.push(ChatMessage::system(t!("gui.generation_stopped")), limit);
+                }
+            }
+            Message::Blink => {
+                self.blink = !self.blink;
+            }
+            Message::PollEvents => {
+                if let Some(geometry) = self.geometry.due(Instant::now()) {
+                    self.save_window_geometry(geometry);
+                }
             }
             Message::OpenFileManager => {
-                self.chat_history.push(ChatMessage {
-                    role: "system".to_string(),
-                    content: "File manager opened (placeholder).".to_string(),
+                let start = self.browser_start_dir();
+                match FileBrowser::open(start.clone()) {
+                    Ok(browser) => self.file_browser = Some(browser),
+                    Err(e) => {
+                        self.error = Some(t!("gui.cant_open", path = start.display(), error = e))
+                    }
+                }
+            }
+            Message::Files(action) => self.update_file_browser(action),
+            Message::OpenDashboard => {
+                let data = self.state().dashboard();
+                self.dashboard = Some(DashboardView::new(data));
+            }
+            Message::Dashboard(action) => return self.update_dashboard(action),
+            Message::OpenWizard => {
+                self.settings_open = false;
+                let wizard = Wizard::new(self.state().get_config());
+                self.wizard = Some(wizard);
+            }
+            Message::Wizard(action) => return self.update_wizard(action),
+            Message::OpenSource(path) => {
+                let read = self.state().handle_command(Command {
+                    id: format!("gui-source-{}", path),
+                    payload: CommandPayload::ReadFile { path: path.clone() },
                 });
-                self.push_notice("File manager opened (placeholder)");
+                let content = match read.payload {
+                    ResponsePayload::Content(bytes) => {
+                        format!("{}:\n{}", path, String::from_utf8_lossy(&bytes))
+                    }
+                    payload => t!("gui.cant_open", path = path, error = response_text(payload)),
+                };
+                // File contents aren't saved to the history
+                self.tabs
+                    .active_mut()
+                    .messages
+                    .push(ChatMessage::system(content));
             }
             Message::OpenSettings => {
                 self.settings_open = true;
-                self.temp_config = self.system_state.get_config().clone();
+                let config = self.state().get_config().clone();
+                let provider = config.providers.resolve(None);
+                self.settings.reload(config);
+                if let Ok(provider) = provider {
+                    return iced::Command::perform(list_models(provider), Message::ModelsListed);
+                }
+            }
+            Message::ModelsListed(Ok(models)) => self.settings.set_models(models),
+            Message::ModelsListed(Err(e)) => {
+                // Offline or not listable; the model is typed in instead
+                tracing::debug!("Can't list models: {}", e);
             }
             Message::CloseSettings => {
                 self.settings_open = false;
             }
+            Message::KeyPressed(key, modifiers, text_focused) => {
+                let action = shortcuts::chord_for(&key, modifiers)
+                    .and_then(|chord| self.shortcuts.action_for(&chord, text_focused));
+                if let Some(message) = action.and_then(|action| self.shortcut_message(action)) {
+                    return self.update(message);
+                }
+            }
+            Message::FocusInput => {
+                return text_input::focus(text_input::Id::new(CHAT_INPUT));
+            }
+            Message::FocusSearch => {
+                return text_input::focus(text_input::Id::new(HISTORY_FILTER));
+            }
             Message::SaveSettings => {
-                match self.system_state.update_config(self.temp_config.clone()) {
-                    Ok(_) => self.chat_history.push(ChatMessage {
-                        role: "system".to_string(),
-                        content: "Settings saved.".to_string(),
-                    }),
+                if !self.settings.is_valid() {
+                    self.error = Some(t!("gui.fix_settings"));
+                    return iced::Command::none();
+                }
+                if !self.settings.is_dirty() {
+                    return iced::Command::none();
+                }
+                let config = self.settings.draft().clone();
+                let saved = self.state().update_config(config.clone());
+                match saved {
+                    Ok(_) => {
+                        self.apply_saved_config(config);
+                        self.push_message(ChatMessage::system(t!("gui.settings_saved")));
+                    }
                     Err(e) => {
-                        self.error = Some(format!("Failed to save settings: {}", e));
-                        self.chat_history.push(ChatMessage {
-                            role: "system".to_string(),
-                            content: format!("Failed to save settings: {}", e),
-                        });
+                        self.error = Some(t!("gui.settings_save_failed", error = e));
+                        self.push_message(ChatMessage::system(format!(
+                            "Failed to save settings: {}",
+                            e
+                        )));
                     }
                 }
                 self.settings_open = false;
                 if self.error.is_none() {
-                    self.push_notice("Settings saved.");
+                    self.push_notice(t!("gui.settings_saved"));
                 }
             }
             Message::ClearError => {
@@ -188,94 +669,171 @@ impl Sandbox for App {
             Message::DismissToast(id) => {
                 self.notices.retain(|toast| toast.id != id);
             }
-            Message::UpdateSetting(change) => match change {
-                SettingChange::ServerUrl(url) => {
-                    self.temp_config.llm.server_url = url;
-                }
-                SettingChange::ModelSize(model) => {
-                    self.temp_config.llm.model_size = model;
-                }
-                SettingChange::Temperature(val) => {
-                    if let Ok(t) = val.parse::<f32>() {
-                        self.temp_config.llm.temperature = t;
-                    }
-                }
-                SettingChange::MaxTokens(val) => {
-                    if let Ok(tokens) = val.parse::<u32>() {
-                        self.temp_config.llm.max_tokens = tokens;
-                    }
-                }
-                SettingChange::Theme(theme) => {
-                    self.temp_config.gui.theme = theme;
-                }
-                SettingChange::AutoStart(enabled) => {
-                    self.temp_config.llm.auto_start = enabled;
-                }
-                SettingChange::UseGpu(enabled) => {
-                    self.temp_config.llm.use_gpu = enabled;
-                }
-                SettingChange::WindowWidth(val) => {
-                    if let Ok(width) = val.parse::<u32>() {
-                        self.temp_config.gui.window_width = width;
-                    }
-                }
-                SettingChange::WindowHeight(val) => {
-                    if let Ok(height) = val.parse::<u32>() {
-                        self.temp_config.gui.window_height = height;
-                    }
-                }
-                SettingChange::FontSize(val) => {
-                    if let Ok(size) = val.parse::<u16>() {
-                        self.temp_config.gui.font_size = size;
-                    }
+            Message::ApproveOperation(token) => {
+                let result = self.state().approve(&token);
+                self.push_notice(result.output);
+                self.refresh_file_browser();
+            }
+            Message::DenyOperation(token) => {
+                let result = self.state().deny(&token);
+                self.push_notice(result.output);
+            }
+            Message::UpdateSetting(change) => self.settings.apply(change),
+            Message::ResetSection(section) => self.settings.reset_section(section),
+            Message::WindowResized(width, height) => {
+                self.geometry.resized(width, height, Instant::now());
+                return window::fetch_maximized(window::Id::MAIN, Message::WindowMaximized);
+            }
+            Message::WindowMoved(x, y) => {
+                self.geometry.moved(x, y, Instant::now());
+                return window::fetch_maximized(window::Id::MAIN, Message::WindowMaximized);
+            }
+            Message::WindowMaximized(maximized) => {
+                self.geometry.maximized(maximized, Instant::now());
+            }
+            Message::WindowCloseRequested => {
+                if let Some(geometry) = self.geometry.flush() {
+                    self.save_window_geometry(geometry);
                 }
-            },
+                return window::close(window::Id::MAIN);
+            }
         }
+        iced::Command::none()
+    }
+
+    fn subscription(&self) -> Subscription<Message> {
+        let mut subscriptions =
+            vec![iced::time::every(Duration::from_secs(1)).map(|_| Message::PollEvents)];
+        if self.tabs.is_busy() {
+            subscriptions
+                .push(iced::time::every(Duration::from_millis(500)).map(|_| Message::Blink));
+        }
+        if self.file_browser.is_some() && !self.settings_open {
+            subscriptions.push(iced::keyboard::on_key_press(file_browser_key));
+        }
+        if self.dashboard.is_some() {
+            subscriptions.push(
+                iced::time::every(DASHBOARD_REFRESH)
+                    .map(|_| Message::Dashboard(DashboardAction::Refresh)),
+            );
+        }
+        subscriptions.push(iced::event::listen_with(shortcut_key));
+        subscriptions.push(iced::event::listen_with(window_event));
+        Subscription::batch(subscriptions)
     }
 
     fn view(&self) -> Element<'_, Self::Message> {
+        if let Some(wizard) = &self.wizard {
+            return onboarding::view(wizard, self.palette, self.scale);
+        }
         if self.settings_open {
             return self.view_settings();
         }
+        if let Some(dashboard) = &self.dashboard {
+            return dashboard::view(dashboard, self.palette, self.scale);
+        }
+        if let Some(browser) = &self.file_browser {
+            return self.view_file_browser(browser);
+        }
 
         let taskbar = container(
             row![
-                button(text("File Manager")).on_press(Message::OpenFileManager),
-                button(text("Settings")).on_press(Message::OpenSettings),
-                text("  |  LucAstra OS").size(14),
+                button(text(t!("gui.taskbar.new_chat")).size(self.scale.body()))
+                    .on_press(Message::NewChat),
+                button(text(t!("gui.taskbar.file_manager")).size(self.scale.body()))
+                    .on_press(Message::OpenFileManager),
+                button(text(t!("gui.taskbar.dashboard")).size(self.scale.body()))
+                    .on_press(Message::OpenDashboard),
+                button(text(t!("gui.taskbar.settings")).size(self.scale.body()))
+                    .on_press(Message::OpenSettings),
+                text("  |  LucAstra OS").size(self.scale.label()),
+                text_input(&t!("gui.taskbar.transcript_path"), &self.export_path)
+                    .on_input(Message::ExportPathChanged)
+                    .padding(6)
+                    .size(self.scale.label()),
+                button(text(t!("gui.taskbar.export")).size(self.scale.body()))
+                    .on_press(Message::ExportTranscript),
             ]
             .spacing(10)
             .align_items(Alignment::Center),
         )
         .padding(10)
         .width(Length::Fill)
-        .style(taskbar_style);
+        .style(taskbar_style(self.palette));
 
+        let tab = self.tabs.active();
         let mut chat_messages = Column::new().spacing(10).padding(10);
-        for msg in &self.chat_history {
-            let role_label = match msg.role.as_str() {
-                "user" => "You:",
-                "assistant" => "LucAstra:",
-                "system" => "System:",
-                _ => "Unknown:",
-            };
-            let message_color = match msg.role.as_str() {
-                "user" => Color::from_rgb(0.3, 0.5, 0.9),
-                "assistant" => Color::from_rgb(0.2, 0.8, 0.4),
-                "system" => Color::from_rgb(0.6, 0.6, 0.6),
-                _ => Color::WHITE,
-            };
+        let filter = self.history_filter.to_lowercase();
+        for (index, msg) in tab.messages.iter().enumerate() {
+            if !filter.is_empty() && !msg.content.to_lowercase().contains(&filter) {
+                continue;
+            }
+            let role_label = format!("{}:", transcript::role_name(&msg.role));
+            let message_color = self.palette.role_color(&msg.role);
 
-            chat_messages = chat_messages.push(
-                column![
-                    text(role_label).size(12).style(message_color),
-                    text(&msg.content).size(16),
-                ]
-                .spacing(2),
-            );
+            let pending = tab.pending.iter().find(|reply| reply.message == index);
+            // Replies are rendered once finished; while streaming their
+            // Markdown is incomplete anyway
+            let content: Element<'_, Message> = match pending {
+                Some(_) if self.blink => text(format!("{}…", msg.content))
+                    .size(self.scale.body())
+                    .into(),
+                None if msg.role == "assistant" && !msg.show_raw => {
+                    markdown::view(markdown::parse(&msg.content), self.scale, self.palette)
+                }
+                _ => text(&msg.content).size(self.scale.body()).into(),
+            };
+            let mut body = column![
+                text(role_label)
+                    .size(self.scale.caption())
+                    .style(message_color),
+                content
+            ]
+            .spacing(2);
+            if !msg.sources.is_empty() {
+                let mut links = row![text(t!("gui.chat.sources")).size(self.scale.caption())]
+                    .spacing(6)
+                    .align_items(Alignment::Center);
+                for source in &msg.sources {
+                    // Attachments aren't files LucAstra can open again
+                    let link: Element<'_, Message> = if source.is_attachment() {
+                        text(t!("gui.attachments.attached", name = source.label()))
+                            .size(self.scale.caption())
+                            .into()
+                    } else {
+                        button(text(source.label()).size(self.scale.caption()))
+                            .style(iced::theme::Button::Text)
+                            .on_press(Message::OpenSource(source.path.clone()))
+                            .into()
+                    };
+                    links = links.push(link);
+                }
+                body = body.push(links);
+            }
+            if let Some(reply) = pending {
+                body = body.push(
+                    button(text(t!("gui.chat.stop")).size(self.scale.label()))
+                        .on_press(Message::Cancel(reply.command_id.clone())),
+                );
+            } else if !msg.content.is_empty() {
+                body = body.push(message_actions(index, msg, self.scale));
+            }
+            chat_messages = chat_messages.push(body);
         }
 
-        let chat_scroll = scrollable(chat_messages).height(Length::Fill);
+        let history_filter = text_input(&t!("gui.chat.filter"), &self.history_filter)
+            .id(text_input::Id::new(HISTORY_FILTER))
+            .on_input(Message::FilterHistory)
+            .padding(6)
+            .size(self.scale.label());
+        let mut chat_scroll = column![
+            self.view_tabs(),
+            container(history_filter).padding([10, 10, 0, 10]),
+            scrollable(chat_messages).height(Length::Fill)
+        ];
+        if let Some(search) = &self.search {
+            chat_scroll = chat_scroll.push(view_search(search, self.scale));
+        }
         let toasts = self.build_toasts();
 
         let content = if let Some(toasts) = toasts {
@@ -293,36 +851,41 @@ impl Sandbox for App {
         };
 
         let input_row = row![
-            text_input("Type your message...", &self.chat_input)
+            text_input(&t!("gui.chat.input"), &self.chat_input)
+                .id(text_input::Id::new(CHAT_INPUT))
                 .on_input(Message::InputChanged)
+                .on_paste(Message::InputPasted)
                 .on_submit(Message::SendMessage)
                 .padding(10)
-                .size(16),
-            button(text("Send").size(16))
+                .size(self.scale.body()),
+            button(text(t!("gui.chat.send")).size(self.scale.body()))
                 .on_press(Message::SendMessage)
                 .padding(10),
+            button(text(t!("gui.chat.search")).size(self.scale.body()))
+                .on_press(Message::Search)
+                .padding(10),
         ]
         .spacing(10)
         .padding(10)
         .align_items(Alignment::Center);
 
-        let error_banner: Option<Element<Message>> = self.error.as_ref().map(|msg| {
-            container(
-                row![
-                    text("Error").style(iced::theme::Text::Color(Color::from_rgb(1.0, 0.8, 0.8))),
-                    text(msg).style(iced::theme::Text::Color(Color::WHITE)),
-                    button(text("Dismiss")).on_press(Message::ClearError),
-                ]
-                .spacing(10)
-                .align_items(Alignment::Center),
-            )
-            .padding(10)
-            .width(Length::Fill)
-            .style(error_banner_style)
-            .into()
-        });
+        let error_banner = self
+            .error
+            .as_deref()
+            .map(|msg| error_banner(self.palette, self.scale, msg));
 
-        let base = column![content, input_row, taskbar].spacing(0).into();
+        let mut base = column![].spacing(0);
+        if let Some(confirmation) = self.view_close_confirmation() {
+            base = base.push(confirmation);
+        }
+        let mut base = base.push(content);
+        if let Some(completions) = self.view_completions() {
+            base = base.push(completions);
+        }
+        if let Some(attachments) = self.view_attachments() {
+            base = base.push(attachments);
+        }
+        let base = base.push(input_row).push(taskbar).into();
 
         if let Some(banner) = error_banner {
             column![banner, base].into()
@@ -333,105 +896,946 @@ impl Sandbox for App {
 }
 
 impl App {
-    fn view_settings(&self) -> Element<'_, Message> {
-        let model_sizes = vec!["7b".to_string(), "13b".to_string(), "70b".to_string()];
+    /// A button per tab, with its own close button, then buttons to open a
+    /// new tab and to reopen a closed one. The tab being renamed shows its
+    /// title in a text field instead.
+    fn view_tabs(&self) -> Element<'_, Message> {
+        let active = self.tabs.active_index();
+        let mut bar = row![].spacing(6).align_items(Alignment::Center);
+        for (index, tab) in self.tabs.iter().enumerate() {
+            let draft = self
+                .renaming
+                .as_ref()
+                .filter(|(id, _)| *id == tab.conversation_id)
+                .map(|(_, draft)| draft);
+            let title: Element<'_, Message> = match draft {
+                Some(draft) => text_input(&tab.title, draft)
+                    .id(text_input::Id::new(TAB_TITLE))
+                    .on_input(Message::TabTitleChanged)
+                    .on_submit(Message::FinishRename)
+                    .padding(4)
+                    .size(self.scale.label())
+                    .width(Length::Fixed(160.0))
+                    .into(),
+                None => {
+                    let label = if tab.is_busy() {
+                        t!("gui.tabs.busy", title = tab.title)
+                    } else {
+                        tab.title.clone()
+                    };
+                    let style = if index == active {
+                        iced::theme::Button::Primary
+                    } else {
+                        iced::theme::Button::Secondary
+                    };
+                    button(text(label).size(self.scale.label()))
+                        .style(style)
+                        .padding([4, 10])
+                        .on_press(Message::TabClicked(index))
+                        .into()
+                }
+            };
+            bar = bar.push(
+                row![
+                    title,
+                    button(text("×").size(self.scale.label()))
+                        .style(iced::theme::Button::Text)
+                        .padding([4, 6])
+                        .on_press(Message::CloseTab(index)),
+                ]
+                .align_items(Alignment::Center),
+            );
+        }
+        bar = bar.push(
+            button(text(t!("gui.tabs.new")).size(self.scale.label()))
+                .style(iced::theme::Button::Text)
+                .padding([4, 10])
+                .on_press(Message::NewTab),
+        );
+        let closed = self.tabs.closed();
+        if !closed.is_empty() {
+            bar = bar.push(
+                pick_list(closed, None::<ClosedTab>, Message::ReopenTab)
+                    .placeholder(t!("gui.tabs.reopen"))
+                    .text_size(self.scale.label())
+                    .padding([4, 10]),
+            );
+        }
+        container(bar).padding([10, 10, 0, 10]).into()
+    }
 
-        let error_banner: Option<Element<Message>> = self.error.as_ref().map(|msg| {
+    /// Asks whether to close a tab still waiting for a reply.
+    fn view_close_confirmation(&self) -> Option<Element<'_, Message>> {
+        let index = self.tabs.position(self.confirm_close.as_deref()?)?;
+        let title = &self.tabs[index].title;
+        Some(
             container(
                 row![
-                    text("Error").style(iced::theme::Text::Color(Color::from_rgb(1.0, 0.8, 0.8))),
-                    text(msg).style(iced::theme::Text::Color(Color::WHITE)),
-                    button(text("Dismiss")).on_press(Message::ClearError),
+                    text(t!("gui.tabs.confirm_close", title = title)).size(self.scale.body()),
+                    button(text(t!("gui.tabs.close_anyway")).size(self.scale.body()))
+                        .on_press(Message::ConfirmCloseTab),
+                    button(text(t!("gui.tabs.keep_open")).size(self.scale.body()))
+                        .style(iced::theme::Button::Secondary)
+                        .on_press(Message::KeepTab),
                 ]
                 .spacing(10)
                 .align_items(Alignment::Center),
             )
             .padding(10)
             .width(Length::Fill)
-            .style(error_banner_style)
-            .into()
+            .style(error_banner_style(self.palette))
+            .into(),
+        )
+    }
+
+    /// Close tab `index`, stopping its replies. Its conversation is saved
+    /// once answered, so the tab can be reopened.
+    fn close_tab(&mut self, index: usize) {
+        let Some(tab) = self.tabs.close(index) else {
+            return;
+        };
+        for reply in &tab.pending {
+            self.bus.cancel(&reply.command_id);
+        }
+        if self
+            .renaming
+            .as_ref()
+            .is_some_and(|(id, _)| *id == tab.conversation_id)
+        {
+            self.renaming = None;
+        }
+        self.state().close_conversation(&tab.conversation_id);
+    }
+
+    /// Open a closed tab again with the turns saved for its conversation.
+    fn reopen_tab(&mut self, closed: ClosedTab) {
+        let restored = self.state().restore_conversation(&closed.conversation_id);
+        match restored {
+            Ok(messages) => {
+                self.tabs.reopen(closed, tabs::restored_messages(messages));
+            }
+            Err(e) => {
+                self.tabs.forget(&closed.conversation_id);
+                self.push_notice(t!(
+                    "gui.tabs.reopen_failed",
+                    title = closed.title,
+                    error = e
+                ));
+            }
+        }
+    }
+
+    /// Give the tab being renamed the title typed for it.
+    fn finish_rename(&mut self) {
+        if let Some((id, title)) = self.renaming.take() {
+            if let Some(index) = self.tabs.position(&id) {
+                self.tabs.rename(index, &title);
+            }
+        }
+    }
+
+    /// Attachments for the next query, each with a button to remove it, or
+    /// a hint while files are dragged over the window.
+    fn view_attachments(&self) -> Option<Element<'_, Message>> {
+        if self.attachments.is_empty() && !self.attachments.is_hovering() {
+            return None;
+        }
+        let mut chips = row![].spacing(6).align_items(Alignment::Center);
+        for chip in self.attachments.chips() {
+            let label = chip.label();
+            chips = chips.push(
+                button(
+                    row![
+                        text(&label).size(self.scale.caption()),
+                        text("×").size(self.scale.caption())
+                    ]
+                    .spacing(4),
+                )
+                .style(iced::theme::Button::Secondary)
+                .padding([2, 8])
+                .on_press(Message::RemoveAttachment(chip.id)),
+            );
+        }
+        if self.attachments.is_hovering() {
+            chips = chips.push(
+                text(t!("gui.attachments.drop_hint"))
+                    .size(self.scale.caption())
+                    .style(self.palette.system),
+            );
+        }
+        Some(container(chips).padding([0, 10]).into())
+    }
+
+    /// Slash commands matching what's typed so far, one button each.
+    fn view_completions(&self) -> Option<Element<'_, Message>> {
+        let matches = slash::completions(&self.chat_input);
+        // Nothing left to complete once the whole name is typed
+        let typed = self
+            .chat_input
+            .trim()
+            .trim_start_matches('/')
+            .to_lowercase();
+        if matches.is_empty() || matches.iter().any(|command| command.name == typed) {
+            return None;
+        }
+        let mut list = Column::new().spacing(2);
+        for command in matches {
+            list = list.push(
+                button(
+                    text(format!("{}  {}", command.synopsis(), command.description))
+                        .size(self.scale.label()),
+                )
+                .style(iced::theme::Button::Text)
+                .on_press(Message::CompleteCommand(command.name)),
+            );
+        }
+        Some(container(list).padding([0, 10]).into())
+    }
+
+    /// Clear the current tab and end its conversation, so the next query
+    /// starts without the earlier turns.
+    fn new_chat(&mut self) {
+        for reply in &self.tabs.active().pending {
+            self.bus.cancel(&reply.command_id);
+        }
+        self.search = None;
+        let (conversation_id, cleared) = self.tabs.clear_active();
+        if let Err(e) = cleared {
+            self.error = Some(t!("gui.history_clear_failed", error = e));
+        }
+
+        self.command_counter += 1;
+        let ended = self.state().handle_command(Command {
+            id: format!("gui-cmd-{}", self.command_counter),
+            payload: CommandPayload::EndConversation { conversation_id },
+        });
+        if let ResponsePayload::Error { message, .. } = ended.payload {
+            tracing::warn!("Failed to end conversation: {}", message);
+        }
+    }
+
+    /// Carry out a slash command typed into the chat input.
+    fn run_slash_command(&mut self, action: SlashAction) -> iced::Command<Message> {
+        match action {
+            SlashAction::Help => {
+                // Like file contents, help isn't saved to the history
+                self.tabs
+                    .active_mut()
+                    .messages
+                    .push(ChatMessage::system(slash::help_text()));
+            }
+            SlashAction::Clear => self.new_chat(),
+            SlashAction::Settings => return self.update(Message::OpenSettings),
+            SlashAction::Run(CommandPayload::Search { query, .. }) => {
+                let limit = self.state().get_config().search.max_results;
+                self.run_search(query, 0, limit);
+            }
+            SlashAction::Run(payload) => {
+                self.command_counter += 1;
+                let response = self.state().handle_command(Command {
+                    id: format!("gui-cmd-{}", self.command_counter),
+                    payload,
+                });
+                self.tabs
+                    .active_mut()
+                    .messages
+                    .push(ChatMessage::system(response_text(response.payload)));
+            }
+        }
+        iced::Command::none()
+    }
+
+    /// Message for a shortcut action, unless it would do nothing right now.
+    fn shortcut_message(&self, action: &str) -> Option<Message> {
+        match action {
+            "focus_input" => Some(Message::FocusInput),
+            "focus_search" => Some(Message::FocusSearch),
+            // Opening again would discard unsaved edits
+            "open_settings" if !self.settings_open => Some(Message::OpenSettings),
+            "close_settings" if self.settings_open => Some(Message::CloseSettings),
+            "send_message" if !self.settings_open => Some(Message::SendMessage),
+            "new_tab" if !self.settings_open => Some(Message::NewTab),
+            "close_tab" if !self.settings_open => Some(Message::CloseTab(self.tabs.active_index())),
+            "reopen_tab" if !self.settings_open => self
+                .tabs
+                .closed()
+                .into_iter()
+                .next()
+                .map(Message::ReopenTab),
+            "next_tab" if !self.settings_open => Some(Message::CycleTab(false)),
+            "previous_tab" if !self.settings_open => Some(Message::CycleTab(true)),
+            _ => None,
+        }
+    }
+
+    /// Fetch a page of results for `query` and show it.
+    fn run_search(&mut self, query: String, offset: usize, limit: usize) {
+        self.command_counter += 1;
+        let response = self.state().handle_command(Command {
+            id: format!("gui-cmd-{}", self.command_counter),
+            payload: CommandPayload::Search {
+                query: query.clone(),
+                offset,
+                limit: Some(limit),
+            },
+        });
+        match response.payload {
+            ResponsePayload::SearchResults(page) => {
+                self.search = Some(SearchView { query, limit, page });
+            }
+            payload => self.error = Some(t!("gui.search_failed", error = response_text(payload))),
+        }
+    }
+
+    /// Where the file browser opens: the first allowed host directory that
+    /// exists, otherwise the working directory.
+    fn browser_start_dir(&self) -> PathBuf {
+        self.state()
+            .get_config()
+            .security
+            .resolved_allowed_dirs()
+            .into_iter()
+            .find(|dir| dir.is_dir())
+            .or_else(|| std::env::current_dir().ok())
+            .unwrap_or_else(|| PathBuf::from("."))
+    }
+
+    fn update_dashboard(&mut self, action: DashboardAction) -> iced::Command<Message> {
+        let payload = match action {
+            DashboardAction::Close => {
+                self.dashboard = None;
+                return iced::Command::none();
+            }
+            DashboardAction::Refresh => {
+                let data = self.state().dashboard();
+                if let Some(dashboard) = self.dashboard.as_mut() {
+                    dashboard.data = data;
+                }
+                return iced::Command::none();
+            }
+            DashboardAction::Replied(response) => {
+                let data = self.state().dashboard();
+                if let Some(dashboard) = self.dashboard.as_mut() {
+                    dashboard.data = data;
+                    dashboard.testing = false;
+                    dashboard.notice = Some(response_text(response.payload));
+                }
+                return iced::Command::none();
+            }
+            DashboardAction::TestConnection => {
+                if let Some(dashboard) = self.dashboard.as_mut() {
+                    dashboard.testing = true;
+                }
+                CommandPayload::TestLlmConnection
+            }
+            DashboardAction::Mount(device_path) => CommandPayload::Mount {
+                mount_point: dashboard::mount_point_for(&device_path),
+                device_path,
+            },
+            DashboardAction::Unmount(mount_point) => CommandPayload::Unmount { mount_point },
+            DashboardAction::RunJob(name) => CommandPayload::RunJob { name },
+        };
+
+        // Probes and mounts can be slow, so they go through the bus
+        self.command_counter += 1;
+        let response = self.bus.send(Command {
+            id: format!("gui-cmd-{}", self.command_counter),
+            payload,
+        });
+        iced::Command::perform(response, |response| {
+            Message::Dashboard(DashboardAction::Replied(response))
+        })
+    }
+
+    fn update_wizard(&mut self, action: WizardAction) -> iced::Command<Message> {
+        let Some(wizard) = self.wizard.as_mut() else {
+            return iced::Command::none();
+        };
+        match action {
+            WizardAction::ChooseProvider(provider) => wizard.choose_provider(provider),
+            WizardAction::EndpointChanged(url) => {
+                wizard.endpoint = url;
+                wizard.test = ConnectionTest::NotRun;
+            }
+            WizardAction::ApiKeyChanged(key) => {
+                wizard.api_key = key;
+                wizard.test = ConnectionTest::NotRun;
+            }
+            WizardAction::EnvVarChanged(name) => wizard.env_var = name,
+            WizardAction::StoreKey(storage) => wizard.key_storage = storage,
+            WizardAction::TestConnection => {
+                wizard.test = ConnectionTest::Running;
+                return iced::Command::perform(check_provider(wizard.test_config()), |result| {
+                    Message::Wizard(WizardAction::Tested(result))
+                });
+            }
+            WizardAction::Tested(result) => {
+                wizard.test = match result {
+                    Ok(true) => ConnectionTest::Passed,
+                    Ok(false) => ConnectionTest::Failed("health check failed".to_string()),
+                    Err(e) => ConnectionTest::Failed(e),
+                };
+            }
+            WizardAction::ToggleFolder(index, selected) => {
+                if let Some(folder) = wizard.folders.get_mut(index) {
+                    folder.selected = selected;
+                }
+            }
+            WizardAction::IndexFolders => {
+                if let Some(path) = wizard.start_indexing() {
+                    return self.index_folder(path);
+                }
+            }
+            WizardAction::Indexed(response) => {
+                if let Some(path) = wizard.indexed(response_text(response.payload)) {
+                    return self.index_folder(path);
+                }
+            }
+            WizardAction::Next => wizard.next(),
+            WizardAction::Back => wizard.back(),
+            WizardAction::Skip => {
+                // The config loaded at startup stays as it is
+                self.wizard = None;
+                self.push_notice(t!("gui.setup_skipped"));
+            }
+            WizardAction::Finish => self.finish_wizard(),
+        }
+        iced::Command::none()
+    }
+
+    /// Index one wizard folder in the background.
+    fn index_folder(&mut self, path: PathBuf) -> iced::Command<Message> {
+        self.command_counter += 1;
+        let response = self.bus.send(Command {
+            id: format!("gui-cmd-{}", self.command_counter),
+            payload: CommandPayload::IndexPath {
+                path: path.display().to_string(),
+            },
+        });
+        iced::Command::perform(response, |response| {
+            Message::Wizard(WizardAction::Indexed(response))
+        })
+    }
+
+    /// Save the wizard's config and close it, or show why it can't be saved.
+    fn finish_wizard(&mut self) {
+        let base = self.state().get_config().clone();
+        let Some(wizard) = self.wizard.as_mut() else {
+            return;
+        };
+        let config = match wizard.finish(&base, |entry, key| {
+            lucastra_llm::store_keyring_secret(entry, key).map_err(|e| e.to_string())
+        }) {
+            Ok(config) => config,
+            Err(e) => {
+                wizard.error = Some(e);
+                return;
+            }
+        };
+        let saved = self.state().update_config(config.clone());
+        match saved {
+            Ok(()) => {
+                self.wizard = None;
+                self.apply_saved_config(config);
+                self.push_notice(t!("gui.setup_complete"));
+            }
+            Err(e) => {
+                if let Some(wizard) = self.wizard.as_mut() {
+                    wizard.error = Some(t!("gui.settings_save_failed", error = e));
+                }
+            }
+        }
+    }
+
+    /// Bring the window in line with a config that was just saved.
+    fn apply_saved_config(&mut self, config: Config) {
+        self.palette = ThemePalette::from_config(&config.gui.theme);
+        self.scale = UiScale::from_config(&config.gui);
+        self.shortcuts = shortcut_registry(&config);
+        lucastra_i18n::set_current(Locale::resolve(&config.gui.language));
+        self.set_history_limit(config.gui.message_history_limit);
+        self.settings.reload(config);
+    }
+
+    fn update_file_browser(&mut self, action: FileAction) {
+        let Some(browser) = self.file_browser.as_mut() else {
+            return;
+        };
+        let result = match action {
+            FileAction::Select(index) => browser.select(index),
+            FileAction::Open(index) => browser.activate(index),
+            FileAction::MoveSelection(delta) => browser.move_selection(delta),
+            FileAction::OpenSelected => match browser.selected() {
+                Some(index) => browser.activate(index),
+                None => Ok(()),
+            },
+            FileAction::Up => browser.up(),
+            FileAction::DestinationChanged(destination) => {
+                browser.destination = destination;
+                Ok(())
+            }
+            FileAction::Copy => self.run_file_operation(FileOperation::Copy),
+            FileAction::Move => self.run_file_operation(FileOperation::Move),
+            FileAction::Delete => self.run_file_operation(FileOperation::Delete),
+            FileAction::Close => {
+                self.file_browser = None;
+                Ok(())
+            }
+        };
+        if let Err(e) = result {
+            self.error = Some(e);
+        }
+    }
+
+    /// Run `operation` on the selected entry as a host file access tool, so
+    /// the whitelist and write approvals apply.
+    fn run_file_operation(&mut self, operation: FileOperation) -> Result<(), String> {
+        let Some(browser) = &self.file_browser else {
+            return Ok(());
+        };
+        let Some(path) = browser.selected_path() else {
+            return Err(t!(
+                "gui.files.select_file",
+                operation = operation_verb(operation)
+            ));
+        };
+        let dest_path = if operation == FileOperation::Delete {
+            None
+        } else {
+            let dest = browser.destination_for(&path).ok_or_else(|| {
+                t!(
+                    "gui.files.enter_destination",
+                    operation = operation_verb(operation),
+                    path = path.display()
+                )
+            })?;
+            Some(dest.display().to_string())
+        };
+
+        let result: ToolResult = self.state().execute_tool(Tool::HostFileAccess {
+            operation,
+            path: path.display().to_string(),
+            dest_path,
         });
+        if result.success || result.approval_token.is_some() {
+            // Pending operations show up as approval toasts
+            if result.approval_token.is_none() {
+                self.push_notice(format!("{}: {}", path.display(), result.output));
+            }
+            self.refresh_file_browser();
+            Ok(())
+        } else {
+            Err(t!(
+                "gui.files.failed",
+                operation = operation_verb(operation),
+                path = path.display(),
+                error = result.output
+            ))
+        }
+    }
+
+    /// Re-list the browser's directory after files changed.
+    fn refresh_file_browser(&mut self) {
+        if let Some(browser) = self.file_browser.as_mut() {
+            if let Err(e) = browser.refresh() {
+                self.error = Some(e);
+            }
+        }
+    }
+
+    fn view_file_browser<'a>(&'a self, browser: &'a FileBrowser) -> Element<'a, Message> {
+        let header = row![
+            button(text(t!("gui.files.up")).size(self.scale.body()))
+                .on_press(Message::Files(FileAction::Up)),
+            text(browser.current_dir().display().to_string()).size(self.scale.body()),
+            button(text(t!("gui.files.close")).size(self.scale.body()))
+                .on_press(Message::Files(FileAction::Close)),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let mut listing = Column::new().spacing(2);
+        for (index, entry) in browser.entries().iter().enumerate() {
+            let label = if entry.is_dir {
+                format!("{}/", entry.name)
+            } else {
+                format!(
+                    "{}  ({})",
+                    entry.name,
+                    file_browser::format_size(entry.size)
+                )
+            };
+            let style = if browser.selected() == Some(index) {
+                iced::theme::Button::Primary
+            } else {
+                iced::theme::Button::Text
+            };
+            let action = if entry.is_dir {
+                FileAction::Open(index)
+            } else {
+                FileAction::Select(index)
+            };
+            listing = listing.push(
+                button(text(label).size(self.scale.label()))
+                    .style(style)
+                    .width(Length::Fill)
+                    .on_press(Message::Files(action)),
+            );
+        }
+
+        let preview = browser
+            .preview()
+            .map(|preview| preview.text())
+            .unwrap_or_else(|| t!("gui.files.preview_hint"));
+
+        let selected = browser.selected_path().is_some();
+        let file_action = |label: String, action: FileAction| {
+            button(text(label).size(self.scale.body()))
+                .on_press_maybe(selected.then_some(Message::Files(action)))
+        };
+        let actions = row![
+            text_input(&t!("gui.files.destination"), &browser.destination)
+                .on_input(|v| Message::Files(FileAction::DestinationChanged(v)))
+                .padding(8)
+                .size(self.scale.body()),
+            file_action(t!("gui.files.copy"), FileAction::Copy),
+            file_action(t!("gui.files.move"), FileAction::Move),
+            file_action(t!("gui.files.delete"), FileAction::Delete),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center);
+
+        let panes = row![
+            scrollable(listing).width(Length::FillPortion(2)),
+            scrollable(text(preview).size(self.scale.label())).width(Length::FillPortion(3)),
+        ]
+        .spacing(16)
+        .height(Length::Fill);
+
+        let mut body = row![column![header, panes, actions]
+            .spacing(12)
+            .padding(10)
+            .width(Length::Fill)]
+        .spacing(16);
+        if let Some(toasts) = self.build_toasts() {
+            body = body.push(
+                column![toasts]
+                    .width(Length::Shrink)
+                    .padding([10, 10, 10, 0])
+                    .align_items(Alignment::End),
+            );
+        }
+
+        match &self.error {
+            Some(msg) => column![error_banner(self.palette, self.scale, msg), body].into(),
+            None => body.into(),
+        }
+    }
+
+    /// A labelled settings row with `error`, if any, shown underneath.
+    fn setting_row<'a>(
+        &self,
+        label: String,
+        input: impl Into<Element<'a, Message>>,
+        error: Option<String>,
+    ) -> Element<'a, Message> {
+        let row = row![
+            text(label)
+                .size(self.scale.body())
+                .width(Length::Fixed(140.0)),
+            input.into()
+        ]
+        .spacing(10)
+        .padding(5);
+        match error {
+            Some(error) => column![
+                row,
+                text(error)
+                    .size(self.scale.label())
+                    .style(iced::theme::Text::Color(self.palette.field_error)),
+            ]
+            .spacing(2)
+            .into(),
+            None => row.into(),
+        }
+    }
+
+    /// Show `message` in the current tab and save it to the history.
+    fn push_message(&mut self, message: ChatMessage) {
+        let limit = self.history_limit;
+        self.tabs.active_mut().push(message, limit);
+    }
+
+    fn set_history_limit(&mut self, limit: usize) {
+        self.history_limit = limit;
+        self.tabs.set_history_limit(limit);
+    }
+
+    /// Say in tab `tab` that no indexed document was relevant to its last
+    /// query.
+    fn note_answered_without_documents(&mut self, tab: usize) {
+        let limit = self.history_limit;
+        self.tabs[tab].push(
+            ChatMessage::system(t!("gui.answered_without_documents")),
+            limit,
+        );
+    }
+
+    /// Say once per outage, in the tab answered, that answers are
+    /// placeholders.
+    fn note_offline_answers(&mut self, tab: usize) {
+        let offline = self.state().llm_service.answered_offline();
+        if offline && !self.llm_offline {
+            let limit = self.history_limit;
+            self.tabs[tab].push(ChatMessage::system(t!("gui.llm_offline")), limit);
+        }
+        self.llm_offline = offline;
+    }
+
+    fn state(&self) -> MutexGuard<'_, SystemState> {
+        self.system_state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    fn view_settings(&self) -> Element<'_, Message> {
+        let model_sizes = vec!["7b".to_string(), "13b".to_string(), "70b".to_string()];
+        let themes = vec!["dark".to_string(), "light".to_string(), "auto".to_string()];
+        let languages: Vec<String> = std::iter::once("auto")
+            .chain(lucastra_i18n::languages())
+            .map(String::from)
+            .collect();
+        let roles = vec![
+            "reader".to_string(),
+            "writer".to_string(),
+            "admin".to_string(),
+        ];
+
+        let error_banner = self
+            .error
+            .as_deref()
+            .map(|msg| error_banner(self.palette, self.scale, msg));
+
+        let on_model = |v| Message::UpdateSetting(SettingChange::Model(v));
+        let model: Element<'_, Message> = match self.settings.models() {
+            Some(models) => pick_list(
+                models.to_vec(),
+                Some(self.settings.model()).filter(|m| !m.is_empty()),
+                on_model,
+            )
+            .placeholder(t!("gui.settings.provider_default"))
+            .text_size(self.scale.body())
+            .into(),
+            None => text_input(&t!("gui.settings.provider_default"), &self.settings.model())
+                .on_input(on_model)
+                .size(self.scale.body())
+                .into(),
+        };
+
+        let title = if self.settings.is_dirty() {
+            t!("gui.settings.title_unsaved")
+        } else {
+            t!("gui.settings.title")
+        };
+        let mut other_errors = Column::new().spacing(2);
+        for error in self.settings.other_errors() {
+            other_errors = other_errors.push(
+                text(error)
+                    .size(self.scale.label())
+                    .style(iced::theme::Text::Color(self.palette.field_error)),
+            );
+        }
 
         let settings_content = column![
-            text("LucAstra Settings").size(24),
-            text("LLM Configuration").size(18),
+            text(title).size(self.scale.heading()),
+            other_errors,
+            section_header(t!("gui.settings.llm"), SettingsSection::Llm, self.scale),
             row![
-                text("Server URL:").width(Length::Fixed(140.0)),
-                text_input("http://localhost:8000", &self.temp_config.llm.server_url)
-                    .on_input(|v| Message::UpdateSetting(SettingChange::ServerUrl(v))),
+                text(t!("gui.settings.server_url"))
+                    .size(self.scale.body())
+                    .width(Length::Fixed(140.0)),
+                text_input(
+                    "http://localhost:8000",
+                    &self.settings.draft().llm.server_url
+                )
+                .on_input(|v| Message::UpdateSetting(SettingChange::ServerUrl(v)))
+                .size(self.scale.body()),
             ]
             .spacing(10)
             .padding(5),
-            row![
-                text("Model Size:").width(Length::Fixed(140.0)),
+            self.setting_row(t!("gui.settings.model"), model, None),
+            self.setting_row(
+                t!("gui.settings.model_size"),
                 pick_list(
                     model_sizes.clone(),
-                    Some(self.temp_config.llm.model_size.clone()),
+                    Some(self.settings.draft().llm.model_size.clone()),
                     |v| { Message::UpdateSetting(SettingChange::ModelSize(v)) }
-                ),
-            ]
-            .spacing(10)
-            .padding(5),
-            row![
-                text("Temperature:").width(Length::Fixed(140.0)),
-                text_input("0.7", &format!("{:.2}", self.temp_config.llm.temperature))
-                    .on_input(|v| Message::UpdateSetting(SettingChange::Temperature(v))),
-            ]
-            .spacing(10)
-            .padding(5),
-            row![
-                text("Max Tokens:").width(Length::Fixed(140.0)),
-                text_input("2048", &self.temp_config.llm.max_tokens.to_string())
-                    .on_input(|v| Message::UpdateSetting(SettingChange::MaxTokens(v))),
-            ]
-            .spacing(10)
-            .padding(5),
-            row![
-                text("Auto-start:").width(Length::Fixed(140.0)),
-                checkbox("", self.temp_config.llm.auto_start)
-                    .on_toggle(|v| Message::UpdateSetting(SettingChange::AutoStart(v))),
-            ]
-            .spacing(10)
-            .padding(5),
+                )
+                .text_size(self.scale.body()),
+                self.settings.error("llm.model_size"),
+            ),
+            self.setting_row(
+                t!("gui.settings.temperature"),
+                text_input(
+                    "0.7",
+                    &self.settings.text(
+                        "llm.temperature",
+                        format!("{:.2}", self.settings.draft().llm.temperature)
+                    )
+                )
+                .on_input(|v| Message::UpdateSetting(SettingChange::Temperature(v)))
+                .size(self.scale.body()),
+                self.settings.error("llm.temperature"),
+            ),
+            self.setting_row(
+                t!("gui.settings.max_tokens"),
+                text_input(
+                    "2048",
+                    &self
+                        .settings
+                        .text("llm.max_tokens", self.settings.draft().llm.max_tokens)
+                )
+                .on_input(|v| Message::UpdateSetting(SettingChange::MaxTokens(v)))
+                .size(self.scale.body()),
+                self.settings.error("llm.max_tokens"),
+            ),
             row![
-                text("GPU Acceleration:").width(Length::Fixed(140.0)),
-                checkbox("", self.temp_config.llm.use_gpu)
-                    .on_toggle(|v| Message::UpdateSetting(SettingChange::UseGpu(v))),
+                text(t!("gui.settings.auto_start"))
+                    .size(self.scale.body())
+                    .width(Length::Fixed(140.0)),
+                checkbox("", self.settings.draft().llm.auto_start)
+                    .on_toggle(|v| Message::UpdateSetting(SettingChange::AutoStart(v)))
+                    .text_size(self.scale.body()),
             ]
             .spacing(10)
             .padding(5),
-            text("GUI Configuration").size(18),
             row![
-                text("Theme:").width(Length::Fixed(140.0)),
-                text_input("dark", &self.temp_config.gui.theme)
-                    .on_input(|v| Message::UpdateSetting(SettingChange::Theme(v))),
+                text(t!("gui.settings.use_gpu"))
+                    .size(self.scale.body())
+                    .width(Length::Fixed(140.0)),
+                checkbox("", self.settings.draft().llm.use_gpu)
+                    .on_toggle(|v| Message::UpdateSetting(SettingChange::UseGpu(v)))
+                    .text_size(self.scale.body()),
             ]
             .spacing(10)
             .padding(5),
+            section_header(t!("gui.settings.gui"), SettingsSection::Gui, self.scale),
+            self.setting_row(
+                t!("gui.settings.theme"),
+                pick_list(themes, Some(self.settings.draft().gui.theme.clone()), |v| {
+                    Message::UpdateSetting(SettingChange::Theme(v))
+                })
+                .text_size(self.scale.body()),
+                self.settings.error("gui.theme"),
+            ),
+            self.setting_row(
+                t!("gui.settings.language"),
+                pick_list(
+                    languages,
+                    Some(self.settings.draft().gui.language.clone()),
+                    |v| { Message::UpdateSetting(SettingChange::Language(v)) }
+                )
+                .text_size(self.scale.body()),
+                self.settings.error("gui.language"),
+            ),
+            self.setting_row(
+                t!("gui.settings.window_width"),
+                text_input(
+                    "1280",
+                    &self
+                        .settings
+                        .text("gui.window_width", self.settings.draft().gui.window_width)
+                )
+                .on_input(|v| Message::UpdateSetting(SettingChange::WindowWidth(v)))
+                .size(self.scale.body()),
+                self.settings.error("gui.window_width"),
+            ),
+            self.setting_row(
+                t!("gui.settings.window_height"),
+                text_input(
+                    "800",
+                    &self
+                        .settings
+                        .text("gui.window_height", self.settings.draft().gui.window_height)
+                )
+                .on_input(|v| Message::UpdateSetting(SettingChange::WindowHeight(v)))
+                .size(self.scale.body()),
+                self.settings.error("gui.window_height"),
+            ),
+            self.setting_row(
+                t!("gui.settings.font_size"),
+                text_input(
+                    "16",
+                    &self
+                        .settings
+                        .text("gui.font_size", self.settings.draft().gui.font_size)
+                )
+                .on_input(|v| Message::UpdateSetting(SettingChange::FontSize(v)))
+                .size(self.scale.body()),
+                self.settings.error("gui.font_size"),
+            ),
+            self.setting_row(
+                t!("gui.settings.ui_scale"),
+                text_input(
+                    "1.0",
+                    &self
+                        .settings
+                        .text("gui.ui_scale", self.settings.draft().gui.ui_scale)
+                )
+                .on_input(|v| Message::UpdateSetting(SettingChange::UiScale(v)))
+                .size(self.scale.body()),
+                self.settings.error("gui.ui_scale"),
+            ),
+            section_header(
+                t!("gui.settings.storage"),
+                SettingsSection::Storage,
+                self.scale
+            ),
             row![
-                text("Window Width:").width(Length::Fixed(140.0)),
-                text_input("1280", &self.temp_config.gui.window_width.to_string())
-                    .on_input(|v| Message::UpdateSetting(SettingChange::WindowWidth(v))),
+                text(t!("gui.settings.auto_index"))
+                    .size(self.scale.body())
+                    .width(Length::Fixed(140.0)),
+                checkbox("", self.settings.draft().storage.auto_index)
+                    .on_toggle(|v| Message::UpdateSetting(SettingChange::AutoIndex(v)))
+                    .text_size(self.scale.body()),
             ]
             .spacing(10)
             .padding(5),
+            section_header(
+                t!("gui.settings.security"),
+                SettingsSection::Security,
+                self.scale
+            ),
             row![
-                text("Window Height:").width(Length::Fixed(140.0)),
-                text_input("800", &self.temp_config.gui.window_height.to_string())
-                    .on_input(|v| Message::UpdateSetting(SettingChange::WindowHeight(v))),
+                text(t!("gui.settings.enforce_roles"))
+                    .size(self.scale.body())
+                    .width(Length::Fixed(140.0)),
+                checkbox("", self.settings.draft().security.enable_rbac)
+                    .on_toggle(|v| Message::UpdateSetting(SettingChange::EnableRbac(v)))
+                    .text_size(self.scale.body()),
             ]
             .spacing(10)
             .padding(5),
             row![
-                text("Font Size:").width(Length::Fixed(140.0)),
-                text_input("16", &self.temp_config.gui.font_size.to_string())
-                    .on_input(|v| Message::UpdateSetting(SettingChange::FontSize(v))),
+                text(t!("gui.settings.tool_role"))
+                    .size(self.scale.body())
+                    .width(Length::Fixed(140.0)),
+                pick_list(
+                    roles,
+                    Some(self.settings.draft().security.role.clone()),
+                    |v| { Message::UpdateSetting(SettingChange::Role(v)) }
+                )
+                .text_size(self.scale.body()),
             ]
             .spacing(10)
             .padding(5),
             row![
-                button(text("Save")).on_press(Message::SaveSettings),
-                button(text("Cancel")).on_press(Message::CloseSettings),
+                button(text(t!("gui.settings.save")).size(self.scale.body()))
+                    .on_press_maybe(self.settings.can_save().then_some(Message::SaveSettings)),
+                button(text(t!("gui.settings.cancel")).size(self.scale.body()))
+                    .on_press(Message::CloseSettings),
+                button(text(t!("gui.settings.run_wizard")).size(self.scale.body()))
+                    .on_press(Message::OpenWizard),
             ]
             .spacing(10)
             .padding(10),
@@ -466,6 +1870,58 @@ impl App {
         }
     }
 
+    /// Toast for storage plugged in or pulled out since the last update.
+    fn notify_device_events(&mut self) {
+        let events = self.state().process_device_events();
+        for event in events {
+            match event {
+                DeviceEvent::Added(device) if device.device_type == DeviceType::BlockDevice => {
+                    self.push_notice(t!("gui.usb_connected", name = device.name));
+                }
+                DeviceEvent::Removed(device) if device.device_type == DeviceType::BlockDevice => {
+                    self.push_notice(t!("gui.usb_removed", name = device.name));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Toast for edits to config.toml picked up since the last update.
+    fn notify_config_events(&mut self) {
+        let events = self.state().process_config_events();
+        let mut reloaded = false;
+        for event in events {
+            match event {
+                ConfigEvent::Rejected(reason) => {
+                    self.error = Some(t!("gui.config_rejected", reason = reason));
+                }
+                _ => reloaded = true,
+            }
+        }
+        if reloaded {
+            let config = self.state().get_config().clone();
+            self.palette = ThemePalette::from_config(&config.gui.theme);
+            self.scale = UiScale::from_config(&config.gui);
+            self.shortcuts = shortcut_registry(&config);
+            lucastra_i18n::set_current(Locale::resolve(&config.gui.language));
+            self.push_notice(t!("gui.config_reloaded"));
+        }
+    }
+
+    /// Save `geometry` to `gui`, leaving the rest of the config as it is.
+    fn save_window_geometry(&mut self, geometry: WindowGeometry) {
+        let mut config = self.state().get_config().clone();
+        geometry.apply_to(&mut config.gui);
+        if let Err(e) = self.state().update_config(config.clone()) {
+            tracing::warn!("Failed to save window geometry: {}", e);
+            return;
+        }
+        // Keep the settings form from saving the old geometry back
+        if !self.settings.is_dirty() {
+            self.settings.reload(config);
+        }
+    }
+
     fn push_notice(&mut self, message: impl Into<String>) {
         let id = self.next_notice_id;
         self.next_notice_id += 1;
@@ -480,27 +1936,70 @@ impl App {
     }
 
     fn build_toasts(&self) -> Option<Column<'_, Message>> {
-        if self.notices.is_empty() {
+        let approvals: Vec<(String, String)> = {
+            let state = self.state();
+            let files = state
+                .pending_approvals()
+                .into_iter()
+                .map(|(token, request)| {
+                    let summary = format!("{} {}?", request.operation, request.path.display());
+                    (token, summary)
+                });
+            let installs = state
+                .pending_installs()
+                .into_iter()
+                .map(|(token, request)| (token, format!("{}?", request)));
+            files.chain(installs).collect()
+        };
+        if self.notices.is_empty() && approvals.is_empty() {
             return None;
         }
 
         let mut stack = Column::new().spacing(8).align_items(Alignment::End);
 
+        for (token, summary) in approvals {
+            stack = stack.push(
+                container(
+                    row![
+                        text(t!("gui.toast.confirm"))
+                            .size(self.scale.body())
+                            .style(iced::theme::Text::Color(self.palette.confirm_label)),
+                        text(summary)
+                            .size(self.scale.body())
+                            .style(iced::theme::Text::Color(self.palette.toast_text)),
+                        button(text(t!("gui.toast.allow")).size(self.scale.body()))
+                            .on_press(Message::ApproveOperation(token.clone())),
+                        button(text(t!("gui.toast.deny")).size(self.scale.body()))
+                            .on_press(Message::DenyOperation(token)),
+                    ]
+                    .spacing(8)
+                    .align_items(Alignment::Center),
+                )
+                .padding(8)
+                .width(Length::Shrink)
+                .style(toast_style(self.palette)),
+            );
+        }
+
         for notice in &self.notices {
             stack = stack.push(
                 container(
                     row![
-                        text("Info")
-                            .style(iced::theme::Text::Color(Color::from_rgb(0.8, 0.9, 1.0))),
-                        text(&notice.message).style(iced::theme::Text::Color(Color::WHITE)),
-                        button(text("Dismiss")).on_press(Message::DismissToast(notice.id)),
+                        text(t!("gui.toast.info"))
+                            .size(self.scale.body())
+                            .style(iced::theme::Text::Color(self.palette.toast_label)),
+                        text(&notice.message)
+                            .size(self.scale.body())
+                            .style(iced::theme::Text::Color(self.palette.toast_text)),
+                        button(text(t!("gui.toast.dismiss")).size(self.scale.body()))
+                            .on_press(Message::DismissToast(notice.id)),
                     ]
                     .spacing(8)
                     .align_items(Alignment::Center),
                 )
                 .padding(8)
                 .width(Length::Shrink)
-                .style(toast_style),
+                .style(toast_style(self.palette)),
             );
         }
 
@@ -508,62 +2007,290 @@ impl App {
     }
 }
 
-fn taskbar_style(_theme: &iced::Theme) -> container::Appearance {
-    container::Appearance {
-        background: Some(iced::Background::Color(Color::from_rgb(0.15, 0.15, 0.15))),
-        text_color: Some(Color::WHITE),
-        ..Default::default()
+/// Copy buttons for a message and for each code block in it, and the raw
+/// text toggle for assistant messages.
+fn message_actions(index: usize, message: &ChatMessage, scale: UiScale) -> Element<'_, Message> {
+    let content = &message.content;
+    let copy = |label: String, text_to_copy: String| {
+        button(text(label).size(scale.caption()))
+            .style(iced::theme::Button::Text)
+            .on_press(Message::CopyText(text_to_copy))
+    };
+    let mut actions = row![copy(t!("gui.chat.copy"), content.to_string())]
+        .spacing(6)
+        .align_items(Alignment::Center);
+    for (i, block) in transcript::code_blocks(content).into_iter().enumerate() {
+        let label = match &block.language {
+            Some(language) => t!("gui.chat.copy_block", language = language, number = i + 1),
+            None => t!("gui.chat.copy_code_block", number = i + 1),
+        };
+        actions = actions.push(copy(label, block.code));
     }
+    if message.role == "assistant" {
+        let label = if message.show_raw {
+            t!("gui.chat.formatted")
+        } else {
+            t!("gui.chat.raw")
+        };
+        actions = actions.push(
+            button(text(label).size(scale.caption()))
+                .style(iced::theme::Button::Text)
+                .on_press(Message::ToggleRaw(index)),
+        );
+    }
+    actions.into()
 }
 
-fn error_banner_style(_theme: &iced::Theme) -> container::Appearance {
-    container::Appearance {
-        background: Some(iced::Background::Color(Color::from_rgb(0.5, 0.1, 0.1))),
-        text_color: Some(Color::WHITE),
-        ..Default::default()
+/// Every key press, for [`ShortcutRegistry`] lookup. Presses a focused text
+/// field took are marked so only global shortcuts act on them.
+fn shortcut_key(event: iced::Event, status: iced::event::Status) -> Option<Message> {
+    match event {
+        iced::Event::Keyboard(iced::keyboard::Event::KeyPressed { key, modifiers, .. }) => Some(
+            Message::KeyPressed(key, modifiers, status == iced::event::Status::Captured),
+        ),
+        _ => None,
+    }
+}
+
+fn window_event(event: iced::Event, _status: iced::event::Status) -> Option<Message> {
+    match event {
+        iced::Event::Window(_, window::Event::Resized { width, height }) => {
+            Some(Message::WindowResized(width, height))
+        }
+        iced::Event::Window(_, window::Event::Moved { x, y }) => Some(Message::WindowMoved(x, y)),
+        iced::Event::Window(_, window::Event::CloseRequested) => {
+            Some(Message::WindowCloseRequested)
+        }
+        iced::Event::Window(_, window::Event::FileHovered(_)) => Some(Message::FileHovered),
+        iced::Event::Window(_, window::Event::FilesHoveredLeft) => Some(Message::FilesHoveredLeft),
+        iced::Event::Window(_, window::Event::FileDropped(path)) => {
+            Some(Message::FileDropped(path))
+        }
+        _ => None,
     }
 }
 
-fn toast_style(_theme: &iced::Theme) -> container::Appearance {
+/// Shortcuts from `gui.shortcuts`; the defaults if they don't validate.
+fn shortcut_registry(config: &Config) -> ShortcutRegistry {
+    ShortcutRegistry::from_overrides(&config.gui.shortcuts).unwrap_or_else(|errors| {
+        tracing::warn!("Ignoring gui.shortcuts: {}", errors.join("; "));
+        ShortcutRegistry::new()
+    })
+}
+
+/// Arrows move through the file browser, Enter opens and Backspace goes up.
+fn file_browser_key(key: Key, _modifiers: Modifiers) -> Option<Message> {
+    let action = match key {
+        Key::Named(Named::ArrowUp) => FileAction::MoveSelection(-1),
+        Key::Named(Named::ArrowDown) => FileAction::MoveSelection(1),
+        Key::Named(Named::Enter) => FileAction::OpenSelected,
+        Key::Named(Named::Backspace) => FileAction::Up,
+        _ => return None,
+    };
+    Some(Message::Files(action))
+}
+
+/// `operation` as a verb in the current language, e.g. "copy".
+fn operation_verb(operation: FileOperation) -> String {
+    match operation {
+        FileOperation::Copy => t!("gui.files.operation.copy"),
+        FileOperation::Move => t!("gui.files.operation.move"),
+        FileOperation::Delete => t!("gui.files.operation.delete"),
+        other => other.to_string(),
+    }
+}
+
+/// The current page of search results with buttons to move between pages.
+fn view_search(search: &SearchView, scale: UiScale) -> Element<'_, Message> {
+    let page = &search.page;
+    let summary = if page.results.is_empty() {
+        t!(
+            "gui.search.no_results",
+            query = search.query,
+            total = page.total_hits
+        )
+    } else {
+        t!(
+            "gui.search.showing",
+            first = page.offset + 1,
+            last = page.offset + page.results.len(),
+            total = page.total_hits,
+            query = search.query
+        )
+    };
+    let previous =
+        (page.offset > 0).then(|| Message::SearchPage(page.offset.saturating_sub(search.limit)));
+    let next = (page.offset + page.results.len() < page.total_hits)
+        .then(|| Message::SearchPage(page.offset + search.limit));
+
+    let mut panel = column![row![
+        text(summary).size(scale.label()),
+        button(text(t!("gui.search.previous")).size(scale.label())).on_press_maybe(previous),
+        button(text(t!("gui.search.next")).size(scale.label())).on_press_maybe(next),
+        button(text(t!("gui.search.close")).size(scale.label())).on_press(Message::CloseSearch),
+    ]
+    .spacing(10)
+    .align_items(Alignment::Center)]
+    .spacing(6)
+    .padding(10);
+    for result in &page.results {
+        panel = panel.push(
+            column![
+                button(text(&result.path).size(scale.label()))
+                    .style(iced::theme::Button::Text)
+                    .on_press(Message::OpenSource(result.path.clone())),
+                text(match result.location() {
+                    Some(location) => format!("[{}] {}", location, result.snippet),
+                    None => result.snippet.clone(),
+                })
+                .size(scale.caption()),
+            ]
+            .spacing(2),
+        );
+    }
+    scrollable(panel).height(Length::FillPortion(1)).into()
+}
+
+/// Text for a failed command: what to do about it when the code says,
+/// otherwise the message.
+fn error_text(code: ErrorCode, message: &str) -> String {
+    match code {
+        ErrorCode::LlmUnavailable => t!("gui.llm_unavailable"),
+        ErrorCode::AuthFailed => t!("gui.auth_failed"),
+        ErrorCode::RateLimited => t!("gui.rate_limited"),
+        ErrorCode::PermissionDenied => t!("gui.permission_denied", error = message),
+        ErrorCode::NotFound => t!("gui.not_found", error = message),
+        _ => t!("gui.error", error = message),
+    }
+}
+
+/// Chat text for a command response.
+fn response_text(payload: ResponsePayload) -> String {
+    match payload {
+        ResponsePayload::Success(text) => text,
+        ResponsePayload::Status(status) => status,
+        ResponsePayload::Devices(devices) => devices.join("\n"),
+        ResponsePayload::Files(files) => files
+            .iter()
+            .map(|f| f.path.clone())
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ResponsePayload::Content(bytes) => String::from_utf8_lossy(&bytes).to_string(),
+        ResponsePayload::SearchResults(page) => page
+            .results
+            .iter()
+            .map(|r| format!("{}: {}", r.path, r.snippet))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        ResponsePayload::Error { code, message } => error_text(code, &message),
+        ResponsePayload::LlmUnavailable(_) => t!("gui.llm_unavailable"),
+        ResponsePayload::Partial(text) => text,
+        ResponsePayload::RagAnswer { text, .. } => text,
+        ResponsePayload::Finished { stop_reason, .. } => t!("gui.finished", reason = stop_reason),
+    }
+}
+
+fn taskbar_style(palette: ThemePalette) -> container::Appearance {
     container::Appearance {
-        background: Some(iced::Background::Color(Color::from_rgb(0.1, 0.2, 0.35))),
-        text_color: Some(Color::WHITE),
+        background: Some(iced::Background::Color(palette.taskbar)),
+        text_color: Some(palette.taskbar_text),
         ..Default::default()
     }
 }
 
-fn main() -> iced::Result {
-    let logs_dir =
-        lucastra_config::get_logs_dir().unwrap_or_else(|_| std::path::PathBuf::from("./logs"));
-
-    std::fs::create_dir_all(&logs_dir).ok();
+/// A settings section title with a button restoring its defaults.
+fn section_header<'a>(
+    title: String,
+    section: SettingsSection,
+    scale: UiScale,
+) -> Element<'a, Message> {
+    row![
+        text(title).size(scale.subheading()).width(Length::Fill),
+        button(text(t!("gui.settings.reset")).size(scale.label()))
+            .on_press(Message::ResetSection(section)),
+    ]
+    .align_items(Alignment::Center)
+    .into()
+}
 
-    let file_appender = tracing_appender::rolling::daily(logs_dir, "lucastra-gui.log");
-    let (non_blocking, _guard) = tracing_appender::non_blocking(file_appender);
+/// The error bar shown above a view, with a button to dismiss it.
+fn error_banner(palette: ThemePalette, scale: UiScale, msg: &str) -> Element<'_, Message> {
+    container(
+        row![
+            text(t!("gui.toast.error"))
+                .size(scale.body())
+                .style(iced::theme::Text::Color(palette.error_label)),
+            text(msg)
+                .size(scale.body())
+                .style(iced::theme::Text::Color(palette.error_banner_text)),
+            button(text(t!("gui.toast.dismiss")).size(scale.body())).on_press(Message::ClearError),
+        ]
+        .spacing(10)
+        .align_items(Alignment::Center),
+    )
+    .padding(10)
+    .width(Length::Fill)
+    .style(error_banner_style(palette))
+    .into()
+}
 
-    let file_layer = tracing_subscriber::fmt::layer()
-        .with_writer(non_blocking)
-        .with_ansi(false);
+fn error_banner_style(palette: ThemePalette) -> container::Appearance {
+    container::Appearance {
+        background: Some(iced::Background::Color(palette.error_banner)),
+        text_color: Some(palette.error_banner_text),
+        ..Default::default()
+    }
+}
 
-    let stdout_layer = tracing_subscriber::fmt::layer().with_writer(std::io::stdout);
+fn toast_style(palette: ThemePalette) -> container::Appearance {
+    container::Appearance {
+        background: Some(iced::Background::Color(palette.toast)),
+        text_color: Some(palette.toast_text),
+        ..Default::default()
+    }
+}
 
-    let subscriber = tracing_subscriber::registry()
-        .with(EnvFilter::from_default_env())
-        .with(file_layer)
-        .with(stdout_layer);
+/// Ids of the models `provider` offers.
+async fn list_models(provider: ProviderConfig) -> Result<Vec<String>, String> {
+    let provider = create_provider(provider).await.map_err(|e| e.to_string())?;
+    let models = provider.list_models().await.map_err(|e| e.to_string())?;
+    Ok(models.into_iter().map(|model| model.id).collect())
+}
 
-    tracing::subscriber::set_global_default(subscriber).expect("Failed to set logger");
+/// Whether `provider` answers its health check.
+async fn check_provider(provider: ProviderConfig) -> Result<bool, String> {
+    let provider = create_provider(provider).await.map_err(|e| e.to_string())?;
+    provider.health_check().await.map_err(|e| e.to_string())
+}
 
+fn main() -> iced::Result {
+    // Config::load writes a default config.toml when there is none
+    let first_run = lucastra_config::get_config_file_path()
+        .map(|path| !path.exists())
+        .unwrap_or(false);
     let config = Config::load().unwrap_or_default();
+    let _guard = init_tracing(&config.tracing, "lucastra-gui.log").expect("Failed to set logger");
+    lucastra_i18n::set_current(Locale::resolve(&config.gui.language));
 
+    // Saved on a monitor that may since have been unplugged
+    let geometry = WindowGeometry::from_config(&config.gui).clamped(&geometry::monitors());
     let settings = Settings {
-        window: iced::window::Settings {
-            size: Size::new(
-                config.gui.window_width as f32,
-                config.gui.window_height as f32,
-            ),
+        window: window::Settings {
+            size: Size::new(geometry.width as f32, geometry.height as f32),
+            position: geometry
+                .position
+                .map_or(window::Position::Default, |(x, y)| {
+                    window::Position::Specific(Point::new(x as f32, y as f32))
+                }),
+            min_size: Some(Size::new(
+                lucastra_config::MIN_WINDOW_WIDTH as f32,
+                lucastra_config::MIN_WINDOW_HEIGHT as f32,
+            )),
+            // The geometry is saved before the window closes
+            exit_on_close_request: false,
             ..Default::default()
         },
+        flags: first_run,
         ..Default::default()
     };
 
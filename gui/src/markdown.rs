@@ -0,0 +1,380 @@
+//! Markdown in assistant messages.
+//!
+//! Messages are parsed into a flat list of [`Block`]s, which the chat then
+//! draws with ordinary widgets. Inline emphasis becomes plain text; only
+//! headings, lists, code blocks and rules change how a block looks.
+
+use crate::scale::UiScale;
+use crate::theme::ThemePalette;
+use crate::Message;
+use iced::font::Weight;
+use iced::widget::{column, container, horizontal_rule, row, scrollable, text, Column};
+use iced::{Element, Font, Length};
+use pulldown_cmark::{CodeBlockKind, Event, HeadingLevel, Parser, Tag, TagEnd};
+
+/// Code blocks longer than this scroll instead of growing the message.
+const CODE_MAX_LINES: usize = 20;
+
+/// Height of a scrolling code block.
+const CODE_MAX_HEIGHT: f32 = 320.0;
+
+/// Indent per list nesting level, in pixels.
+const LIST_INDENT: u16 = 16;
+
+/// One block of a rendered message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Block {
+    Heading {
+        /// 1 for `#` up to 6.
+        level: u8,
+        text: String,
+    },
+    Paragraph(String),
+    ListItem {
+        /// 0 for a top-level item.
+        depth: usize,
+        /// `•` or the item number, e.g. `2.`; empty for text after a
+        /// nested list.
+        marker: String,
+        text: String,
+    },
+    Code {
+        language: Option<String>,
+        code: String,
+    },
+    Rule,
+}
+
+/// Blocks of `markdown`. Any input parses: unclosed fences run to the end
+/// and stray syntax stays as text.
+pub fn parse(markdown: &str) -> Vec<Block> {
+    let mut builder = Builder::default();
+    for event in Parser::new(markdown) {
+        builder.event(event);
+    }
+    builder.finish()
+}
+
+#[derive(Default)]
+struct Builder {
+    blocks: Vec<Block>,
+    /// Inline text of the block being read.
+    text: String,
+    heading: Option<u8>,
+    code: Option<CodeBlock>,
+    /// Next number of each open list, `None` for bullet lists.
+    lists: Vec<Option<u64>>,
+    /// Markers of the open list items, innermost last.
+    items: Vec<String>,
+    quote_depth: usize,
+}
+
+struct CodeBlock {
+    language: Option<String>,
+    code: String,
+}
+
+impl Builder {
+    fn event(&mut self, event: Event<'_>) {
+        match event {
+            Event::Start(tag) => self.start(tag),
+            Event::End(tag) => self.end(tag),
+            Event::Text(content) => match self.code.as_mut() {
+                Some(code) => code.code.push_str(&content),
+                None => self.text.push_str(&content),
+            },
+            Event::Code(content) => {
+                self.text.push('`');
+                self.text.push_str(&content);
+                self.text.push('`');
+            }
+            Event::Html(content) | Event::InlineHtml(content) => self.text.push_str(&content),
+            Event::SoftBreak => self.text.push(' '),
+            Event::HardBreak => self.text.push('\n'),
+            Event::Rule => {
+                self.flush();
+                self.blocks.push(Block::Rule);
+            }
+            Event::TaskListMarker(done) => self.text.push_str(if done { "[x] " } else { "[ ] " }),
+            _ => {}
+        }
+    }
+
+    fn start(&mut self, tag: Tag<'_>) {
+        match tag {
+            Tag::Heading { level, .. } => {
+                self.flush();
+                self.heading = Some(heading_level(level));
+            }
+            Tag::CodeBlock(kind) => {
+                self.flush();
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => info
+                        .split_whitespace()
+                        .next()
+                        .map(|language| language.to_string()),
+                    CodeBlockKind::Indented => None,
+                };
+                self.code = Some(CodeBlock {
+                    language,
+                    code: String::new(),
+                });
+            }
+            Tag::List(start) => {
+                // Text before a nested list belongs to the parent item
+                self.flush();
+                self.lists.push(start);
+            }
+            Tag::Item => {
+                self.flush();
+                let marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        let marker = format!("{}.", number);
+                        *number += 1;
+                        marker
+                    }
+                    _ => "•".to_string(),
+                };
+                self.items.push(marker);
+            }
+            Tag::BlockQuote(_) => {
+                self.flush();
+                self.quote_depth += 1;
+            }
+            Tag::Paragraph => self.flush(),
+            _ => {}
+        }
+    }
+
+    fn end(&mut self, tag: TagEnd) {
+        match tag {
+            TagEnd::Heading(_) => {
+                let level = self.heading.take().unwrap_or(1);
+                let text = std::mem::take(&mut self.text).trim().to_string();
+                self.blocks.push(Block::Heading { level, text });
+            }
+            TagEnd::CodeBlock => {
+                if let Some(code) = self.code.take() {
+                    self.blocks.push(Block::Code {
+                        language: code.language,
+                        code: code.code.trim_end_matches('\n').to_string(),
+                    });
+                }
+            }
+            TagEnd::List(_) => {
+                self.flush();
+                self.lists.pop();
+            }
+            TagEnd::Item => {
+                self.flush();
+                self.items.pop();
+            }
+            TagEnd::BlockQuote(_) => {
+                self.flush();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            TagEnd::Paragraph => self.flush(),
+            _ => {}
+        }
+    }
+
+    /// End the current run of text as a paragraph or list item.
+    fn flush(&mut self) {
+        let text = std::mem::take(&mut self.text);
+        let text = text.trim();
+        if text.is_empty() {
+            return;
+        }
+        let text = if self.quote_depth > 0 {
+            format!("{} {}", ">".repeat(self.quote_depth), text)
+        } else {
+            text.to_string()
+        };
+        let depth = self.lists.len().saturating_sub(1);
+        match self.items.last_mut() {
+            Some(marker) => {
+                // Only the item's first block shows its marker
+                let marker = std::mem::take(marker);
+                self.blocks.push(Block::ListItem {
+                    depth,
+                    marker,
+                    text,
+                });
+            }
+            None => self.blocks.push(Block::Paragraph(text)),
+        }
+    }
+
+    fn finish(mut self) -> Vec<Block> {
+        self.flush();
+        if let Some(code) = self.code.take() {
+            self.blocks.push(Block::Code {
+                language: code.language,
+                code: code.code.trim_end_matches('\n').to_string(),
+            });
+        }
+        self.blocks
+    }
+}
+
+fn heading_level(level: HeadingLevel) -> u8 {
+    match level {
+        HeadingLevel::H1 => 1,
+        HeadingLevel::H2 => 2,
+        HeadingLevel::H3 => 3,
+        HeadingLevel::H4 => 4,
+        HeadingLevel::H5 => 5,
+        HeadingLevel::H6 => 6,
+    }
+}
+
+/// Widgets for `blocks`, with paragraphs at body size.
+pub fn view(
+    blocks: Vec<Block>,
+    scale: UiScale,
+    palette: ThemePalette,
+) -> Element<'static, Message> {
+    let bold = Font {
+        weight: Weight::Bold,
+        ..Font::DEFAULT
+    };
+    let mut content = Column::new().spacing(6);
+    for block in blocks {
+        let element: Element<'static, Message> = match block {
+            Block::Heading { level, text: title } => {
+                let size = match level {
+                    1 => scale.heading(),
+                    2 | 3 => scale.subheading(),
+                    _ => scale.body(),
+                };
+                text(title).size(size).font(bold).into()
+            }
+            Block::Paragraph(paragraph) => text(paragraph).size(scale.body()).into(),
+            Block::ListItem {
+                depth,
+                marker,
+                text: item,
+            } => row![
+                text(marker).size(scale.body()).width(Length::Fixed(24.0)),
+                text(item).size(scale.body()),
+            ]
+            .padding([0, 0, 0, LIST_INDENT * depth.min(8) as u16])
+            .into(),
+            Block::Code { language, code } => {
+                let lines = code.lines().count();
+                let code = text(code).size(scale.label()).font(Font::MONOSPACE);
+                let code: Element<'static, Message> = if lines > CODE_MAX_LINES {
+                    scrollable(code)
+                        .height(Length::Fixed(CODE_MAX_HEIGHT))
+                        .width(Length::Fill)
+                        .into()
+                } else {
+                    code.into()
+                };
+                let mut block = column![].spacing(4);
+                if let Some(language) = language {
+                    block = block.push(text(language).size(scale.caption()).style(palette.system));
+                }
+                container(block.push(code))
+                    .padding(8)
+                    .width(Length::Fill)
+                    .style(code_block_style(palette))
+                    .into()
+            }
+            Block::Rule => horizontal_rule(1).into(),
+        };
+        content = content.push(element);
+    }
+    content.into()
+}
+
+fn code_block_style(palette: ThemePalette) -> container::Appearance {
+    container::Appearance {
+        background: Some(iced::Background::Color(palette.code_background)),
+        border: iced::Border {
+            radius: 4.0.into(),
+            ..Default::default()
+        },
+        ..Default::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn item(depth: usize, marker: &str, text: &str) -> Block {
+        Block::ListItem {
+            depth,
+            marker: marker.to_string(),
+            text: text.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_representative_document() {
+        let markdown = "# Setup\n\
+                        Install the **server** with `cargo`:\n\
+                        \n\
+                        ```sh\n\
+                        cargo install llamafile\n\
+                        ```\n\
+                        \n\
+                        ## Steps\n\
+                        - Start it\n\
+                        - Configure:\n  \
+                          1. Model\n  \
+                          2. Port\n\
+                        \n\
+                        ---\n\
+                        > Done *soon*";
+
+        assert_eq!(
+            parse(markdown),
+            vec![
+                Block::Heading {
+                    level: 1,
+                    text: "Setup".to_string(),
+                },
+                Block::Paragraph("Install the server with `cargo`:".to_string()),
+                Block::Code {
+                    language: Some("sh".to_string()),
+                    code: "cargo install llamafile".to_string(),
+                },
+                Block::Heading {
+                    level: 2,
+                    text: "Steps".to_string(),
+                },
+                item(0, "•", "Start it"),
+                item(0, "•", "Configure:"),
+                item(1, "1.", "Model"),
+                item(1, "2.", "Port"),
+                Block::Rule,
+                Block::Paragraph("> Done soon".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_malformed_markdown() {
+        assert_eq!(
+            parse("```rust\nfn main() {"),
+            vec![Block::Code {
+                language: Some("rust".to_string()),
+                code: "fn main() {".to_string(),
+            }]
+        );
+        assert_eq!(
+            parse("**bold _mixed* [link]("),
+            vec![Block::Paragraph("*bold _mixed [link](".to_string())]
+        );
+        assert!(parse("").is_empty());
+
+        // Deep nesting and stray markers still parse
+        let nested: String = (0..200)
+            .map(|i| format!("{}- x\n", "  ".repeat(i)))
+            .collect();
+        assert!(!parse(&nested).is_empty());
+        assert!(!parse("> > > - ```\n#\n|a|b|\n|-|").is_empty());
+    }
+}
@@ -0,0 +1,749 @@
+//! First-run setup wizard: pick an LLM provider, choose folders to index,
+//! then write the resulting config.
+//!
+//! [`Wizard`] holds the choices and the step logic; the window sends its
+//! connection tests and indexing runs through the command bus.
+
+use crate::scale::UiScale;
+use crate::theme::ThemePalette;
+use crate::Message;
+use iced::widget::{
+    button, checkbox, column, container, progress_bar, radio, row, scrollable, text, text_input,
+    Column,
+};
+use iced::{Alignment, Element, Length};
+use lucastra_config::{Config, ProviderConfig};
+use lucastra_core::Response;
+use std::collections::VecDeque;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WizardStep {
+    Provider,
+    Folders,
+    Finish,
+}
+
+impl WizardStep {
+    fn number(self) -> usize {
+        match self {
+            WizardStep::Provider => 1,
+            WizardStep::Folders => 2,
+            WizardStep::Finish => 3,
+        }
+    }
+}
+
+/// Where answers come from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProviderChoice {
+    /// A llamafile server, usually started by LucAstra itself
+    Llamafile,
+    OpenAi,
+    Anthropic,
+}
+
+impl ProviderChoice {
+    const ALL: [ProviderChoice; 3] = [
+        ProviderChoice::Llamafile,
+        ProviderChoice::OpenAi,
+        ProviderChoice::Anthropic,
+    ];
+
+    /// Provider kind, also used as the `[providers.entries]` name and the
+    /// keyring entry for its key.
+    fn name(self) -> &'static str {
+        match self {
+            ProviderChoice::Llamafile => "llamafile",
+            ProviderChoice::OpenAi => "openai",
+            ProviderChoice::Anthropic => "anthropic",
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            ProviderChoice::Llamafile => "Local model (llamafile)",
+            ProviderChoice::OpenAi => "OpenAI",
+            ProviderChoice::Anthropic => "Anthropic",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|choice| choice.name() == name)
+    }
+
+    fn needs_key(self) -> bool {
+        self != ProviderChoice::Llamafile
+    }
+
+    fn default_env_var(self) -> &'static str {
+        match self {
+            ProviderChoice::OpenAi => "OPENAI_API_KEY",
+            ProviderChoice::Anthropic => "ANTHROPIC_API_KEY",
+            ProviderChoice::Llamafile => "",
+        }
+    }
+}
+
+/// How the config refers to a cloud provider's API key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyStorage {
+    /// `api_key_env`: the key stays in an environment variable
+    Env,
+    /// `api_key_keyring`: the key is saved to the OS keyring
+    Keyring,
+    /// `api_key`: the key is written to config.toml in plain text
+    Config,
+}
+
+/// An allowed host directory offered for indexing.
+#[derive(Debug, Clone)]
+pub struct FolderChoice {
+    /// As written in `security.allowed_host_dirs`, e.g. `~/Documents`
+    pub dir: String,
+    pub path: PathBuf,
+    pub selected: bool,
+}
+
+/// Outcome of the last connection test.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConnectionTest {
+    NotRun,
+    Running,
+    Passed,
+    Failed(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum WizardAction {
+    ChooseProvider(ProviderChoice),
+    EndpointChanged(String),
+    ApiKeyChanged(String),
+    EnvVarChanged(String),
+    StoreKey(KeyStorage),
+    TestConnection,
+    Tested(Result<bool, String>),
+    ToggleFolder(usize, bool),
+    IndexFolders,
+    /// One folder finished indexing.
+    Indexed(Response),
+    Next,
+    Back,
+    /// Close without writing anything.
+    Skip,
+    /// Write the config and close.
+    Finish,
+}
+
+/// Folders being indexed one after another.
+#[derive(Debug, Clone)]
+struct IndexRun {
+    queue: VecDeque<PathBuf>,
+    done: usize,
+    total: usize,
+}
+
+/// The wizard's choices so far.
+pub struct Wizard {
+    pub step: WizardStep,
+    pub provider: ProviderChoice,
+    /// llamafile server URL
+    pub endpoint: String,
+    /// Typed API key; only leaves memory for the keyring, or for
+    /// config.toml with [`KeyStorage::Config`].
+    pub api_key: String,
+    pub env_var: String,
+    pub key_storage: KeyStorage,
+    pub folders: Vec<FolderChoice>,
+    pub test: ConnectionTest,
+    index_run: Option<IndexRun>,
+    /// One line per indexed folder
+    pub index_log: Vec<String>,
+    /// Why the wizard can't go on, if it can't
+    pub error: Option<String>,
+}
+
+impl Wizard {
+    /// Start from `config`; every allowed host directory is offered, ticked.
+    pub fn new(config: &Config) -> Self {
+        let entry = config.providers.resolve(None).ok();
+        let provider = entry
+            .as_ref()
+            .and_then(|entry| ProviderChoice::from_name(&entry.provider))
+            .unwrap_or(ProviderChoice::Llamafile);
+        let env_var = entry
+            .and_then(|entry| entry.api_key_env)
+            .unwrap_or_else(|| provider.default_env_var().to_string());
+        let folders = config
+            .security
+            .allowed_host_dirs
+            .iter()
+            .zip(config.security.resolved_allowed_dirs())
+            .map(|(dir, path)| FolderChoice {
+                dir: dir.clone(),
+                path,
+                selected: true,
+            })
+            .collect();
+
+        Self {
+            step: WizardStep::Provider,
+            provider,
+            endpoint: config.llm.server_url.clone(),
+            api_key: String::new(),
+            env_var,
+            key_storage: KeyStorage::Env,
+            folders,
+            test: ConnectionTest::NotRun,
+            index_run: None,
+            index_log: Vec::new(),
+            error: None,
+        }
+    }
+
+    pub fn choose_provider(&mut self, provider: ProviderChoice) {
+        if provider == self.provider {
+            return;
+        }
+        // Keep a variable name the user typed, but follow the provider's default
+        if self.env_var.is_empty() || self.env_var == self.provider.default_env_var() {
+            self.env_var = provider.default_env_var().to_string();
+        }
+        self.provider = provider;
+        self.test = ConnectionTest::NotRun;
+        self.error = None;
+    }
+
+    /// Go to the next step if this one is complete, otherwise say why not.
+    pub fn next(&mut self) {
+        let blocked = match self.step {
+            WizardStep::Provider => self.provider_error(),
+            WizardStep::Folders if self.is_indexing() => {
+                Some("Wait for indexing to finish".to_string())
+            }
+            _ => None,
+        };
+        if let Some(error) = blocked {
+            self.error = Some(error);
+            return;
+        }
+        self.error = None;
+        self.step = match self.step {
+            WizardStep::Provider => WizardStep::Folders,
+            WizardStep::Folders | WizardStep::Finish => WizardStep::Finish,
+        };
+    }
+
+    pub fn back(&mut self) {
+        self.error = None;
+        self.step = match self.step {
+            WizardStep::Provider | WizardStep::Folders => WizardStep::Provider,
+            WizardStep::Finish => WizardStep::Folders,
+        };
+    }
+
+    /// What the provider step is missing.
+    fn provider_error(&self) -> Option<String> {
+        if !self.provider.needs_key() {
+            return self
+                .endpoint
+                .trim()
+                .is_empty()
+                .then(|| "Enter the llamafile server URL".to_string());
+        }
+        match self.key_storage {
+            KeyStorage::Env if self.env_var.trim().is_empty() => {
+                Some("Enter the environment variable that holds the API key".to_string())
+            }
+            KeyStorage::Keyring | KeyStorage::Config if self.api_key.trim().is_empty() => {
+                Some("Enter the API key".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    /// The provider entry as saved, based on `existing`: the key is only
+    /// referenced, unless it goes into config.toml on purpose.
+    fn provider_config(&self, existing: Option<&ProviderConfig>) -> ProviderConfig {
+        let mut entry = existing.cloned().unwrap_or_else(|| ProviderConfig {
+            provider: self.provider.name().to_string(),
+            endpoint: None,
+            ..ProviderConfig::default()
+        });
+        if !self.provider.needs_key() {
+            entry.endpoint = Some(self.endpoint.trim().to_string());
+            return entry;
+        }
+        entry.api_key = None;
+        entry.api_key_env = None;
+        entry.api_key_keyring = None;
+        match self.key_storage {
+            KeyStorage::Env => entry.api_key_env = Some(self.env_var.trim().to_string()),
+            KeyStorage::Keyring => entry.api_key_keyring = Some(self.provider.name().to_string()),
+            KeyStorage::Config => entry.api_key = Some(self.api_key.trim().to_string()),
+        }
+        entry
+    }
+
+    /// The provider entry for a connection test, using the typed key if any.
+    pub fn test_config(&self) -> ProviderConfig {
+        let mut entry = self.provider_config(None);
+        if self.provider.needs_key() && !self.api_key.trim().is_empty() {
+            entry.api_key = Some(self.api_key.trim().to_string());
+        }
+        entry
+    }
+
+    /// `base` with the wizard's provider and folders. The provider becomes
+    /// the default entry; unticked folders leave `allowed_host_dirs`.
+    pub fn to_config(&self, base: &Config) -> Config {
+        let mut config = base.clone();
+        if !self.provider.needs_key() {
+            config.llm.server_url = self.endpoint.trim().to_string();
+        }
+        let name = self.provider.name().to_string();
+        let entry = self.provider_config(base.providers.entries.get(&name));
+        config.providers.entries.insert(name.clone(), entry);
+        config.providers.default = name;
+        config.security.allowed_host_dirs = self
+            .folders
+            .iter()
+            .filter(|folder| folder.selected)
+            .map(|folder| folder.dir.clone())
+            .collect();
+        config
+    }
+
+    /// The config to save, after putting a keyring-stored key in place with
+    /// `store_secret(entry, key)`.
+    pub fn finish(
+        &self,
+        base: &Config,
+        store_secret: impl FnOnce(&str, &str) -> Result<(), String>,
+    ) -> Result<Config, String> {
+        if let Some(error) = self.provider_error() {
+            return Err(error);
+        }
+        if self.provider.needs_key() && self.key_storage == KeyStorage::Keyring {
+            store_secret(self.provider.name(), self.api_key.trim())
+                .map_err(|e| format!("Couldn't save the API key to the keyring: {}", e))?;
+        }
+        let config = self.to_config(base);
+        config.validate().map_err(|errors| {
+            errors
+                .iter()
+                .map(|e| e.to_string())
+                .collect::<Vec<_>>()
+                .join("; ")
+        })?;
+        Ok(config)
+    }
+
+    /// Queue the ticked folders and return the first to index.
+    pub fn start_indexing(&mut self) -> Option<PathBuf> {
+        let mut queue: VecDeque<PathBuf> = self
+            .folders
+            .iter()
+            .filter(|folder| folder.selected)
+            .map(|folder| folder.path.clone())
+            .collect();
+        self.index_log.clear();
+        let first = queue.pop_front()?;
+        self.index_run = Some(IndexRun {
+            total: queue.len() + 1,
+            queue,
+            done: 0,
+        });
+        Some(first)
+    }
+
+    /// Record how the last folder went and return the next one, if any.
+    pub fn indexed(&mut self, outcome: String) -> Option<PathBuf> {
+        let run = self.index_run.as_mut()?;
+        run.done += 1;
+        self.index_log.push(outcome);
+        run.queue.pop_front()
+    }
+
+    /// Folders indexed so far and in total, once indexing has started.
+    pub fn index_progress(&self) -> Option<(usize, usize)> {
+        self.index_run.as_ref().map(|run| (run.done, run.total))
+    }
+
+    pub fn is_indexing(&self) -> bool {
+        self.index_progress()
+            .is_some_and(|(done, total)| done < total)
+    }
+
+    /// How the key is kept, for the summary.
+    fn key_summary(&self) -> String {
+        match self.key_storage {
+            KeyStorage::Env => format!("read from ${}", self.env_var.trim()),
+            KeyStorage::Keyring => {
+                format!("saved to the OS keyring as \"{}\"", self.provider.name())
+            }
+            KeyStorage::Config => "stored in config.toml in plain text".to_string(),
+        }
+    }
+}
+
+pub fn view(wizard: &Wizard, palette: ThemePalette, scale: UiScale) -> Element<'_, Message> {
+    let action = |action| Message::Wizard(action);
+
+    let (title, body) = match wizard.step {
+        WizardStep::Provider => ("Choose an LLM provider", provider_step(wizard, scale)),
+        WizardStep::Folders => ("Choose folders to index", folders_step(wizard, scale)),
+        WizardStep::Finish => ("Review and save", finish_step(wizard, scale)),
+    };
+
+    let mut nav =
+        row![button(text("Skip setup").size(scale.body())).on_press(action(WizardAction::Skip))]
+            .spacing(10)
+            .align_items(Alignment::Center);
+    nav = nav.push(text("").width(Length::Fill));
+    if wizard.step != WizardStep::Provider {
+        nav =
+            nav.push(button(text("Back").size(scale.body())).on_press(action(WizardAction::Back)));
+    }
+    nav = match wizard.step {
+        WizardStep::Finish => nav.push(
+            button(text("Save and finish").size(scale.body()))
+                .on_press(action(WizardAction::Finish)),
+        ),
+        _ => nav.push(
+            button(text("Next").size(scale.body()))
+                .on_press_maybe((!wizard.is_indexing()).then_some(action(WizardAction::Next))),
+        ),
+    };
+
+    let mut content = column![
+        text("Welcome to LucAstra OS").size(scale.heading()),
+        text(format!("Step {} of 3: {}", wizard.step.number(), title)).size(scale.subheading()),
+        body,
+    ]
+    .spacing(16)
+    .padding(20);
+    if let Some(error) = &wizard.error {
+        content = content.push(
+            text(error)
+                .size(scale.label())
+                .style(iced::theme::Text::Color(palette.field_error)),
+        );
+    }
+    content = content.push(nav);
+
+    container(scrollable(content))
+        .width(Length::Fill)
+        .height(Length::Fill)
+        .padding(10)
+        .into()
+}
+
+fn provider_step(wizard: &Wizard, scale: UiScale) -> Element<'_, Message> {
+    let action = |action| Message::Wizard(action);
+
+    let mut choices = Column::new().spacing(6);
+    for choice in ProviderChoice::ALL {
+        choices = choices.push(
+            radio(choice.label(), choice, Some(wizard.provider), |choice| {
+                action(WizardAction::ChooseProvider(choice))
+            })
+            .text_size(scale.body()),
+        );
+    }
+
+    let mut details = Column::new().spacing(8);
+    if wizard.provider.needs_key() {
+        details = details.push(
+            row![
+                text("API key:")
+                    .size(scale.body())
+                    .width(Length::Fixed(140.0)),
+                text_input("sk-...", &wizard.api_key)
+                    .size(scale.body())
+                    .on_input(|key| Message::Wizard(WizardAction::ApiKeyChanged(key)))
+                    .secure(true),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+        let store = |label, storage| {
+            radio(label, storage, Some(wizard.key_storage), |storage| {
+                action(WizardAction::StoreKey(storage))
+            })
+            .text_size(scale.body())
+        };
+        details = details.push(text("Keep the key in:").size(scale.label()));
+        details = details.push(
+            row![
+                store("An environment variable", KeyStorage::Env),
+                text_input("OPENAI_API_KEY", &wizard.env_var)
+                    .size(scale.body())
+                    .on_input(|name| Message::Wizard(WizardAction::EnvVarChanged(name))),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+        details = details.push(store("The OS keyring", KeyStorage::Keyring));
+        details = details.push(store(
+            "config.toml, in plain text (not recommended)",
+            KeyStorage::Config,
+        ));
+    } else {
+        details = details.push(
+            row![
+                text("Server URL:")
+                    .size(scale.body())
+                    .width(Length::Fixed(140.0)),
+                text_input("http://localhost:8000", &wizard.endpoint)
+                    .size(scale.body())
+                    .on_input(|url| Message::Wizard(WizardAction::EndpointChanged(url))),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+    }
+
+    let test_button = if wizard.test == ConnectionTest::Running {
+        button(text("Testing...").size(scale.body()))
+    } else {
+        button(text("Test connection").size(scale.body()))
+            .on_press(action(WizardAction::TestConnection))
+    };
+    let test_result = match &wizard.test {
+        ConnectionTest::NotRun | ConnectionTest::Running => String::new(),
+        ConnectionTest::Passed => "Connected".to_string(),
+        ConnectionTest::Failed(reason) => format!("Not reachable: {}", reason),
+    };
+
+    column![
+        choices,
+        details,
+        row![test_button, text(test_result).size(scale.label())]
+            .spacing(10)
+            .align_items(Alignment::Center),
+    ]
+    .spacing(12)
+    .into()
+}
+
+fn folders_step(wizard: &Wizard, scale: UiScale) -> Element<'_, Message> {
+    let action = |action| Message::Wizard(action);
+
+    let mut folders = Column::new().spacing(6);
+    if wizard.folders.is_empty() {
+        folders = folders.push(text("No allowed folders are configured").size(scale.label()));
+    }
+    for (index, folder) in wizard.folders.iter().enumerate() {
+        let label = if folder.path.is_dir() {
+            folder.dir.clone()
+        } else {
+            format!("{} (not found)", folder.dir)
+        };
+        folders = folders.push(
+            checkbox(label, folder.selected)
+                .text_size(scale.body())
+                .on_toggle(move |selected| action(WizardAction::ToggleFolder(index, selected))),
+        );
+    }
+
+    let index_button = button(text("Index selected folders").size(scale.body())).on_press_maybe(
+        (!wizard.is_indexing() && wizard.folders.iter().any(|folder| folder.selected))
+            .then_some(action(WizardAction::IndexFolders)),
+    );
+    let mut content = column![
+        text("LucAstra may read and search these folders. Untick any you want to keep out.")
+            .size(scale.label()),
+        folders,
+        index_button,
+    ]
+    .spacing(12);
+    if let Some((done, total)) = wizard.index_progress() {
+        content = content.push(
+            row![
+                progress_bar(0.0..=total as f32, done as f32).height(Length::Fixed(12.0)),
+                text(format!("{}/{}", done, total)).size(scale.label()),
+            ]
+            .spacing(10)
+            .align_items(Alignment::Center),
+        );
+    }
+    for line in &wizard.index_log {
+        content = content.push(text(line).size(scale.label()));
+    }
+    content.into()
+}
+
+fn finish_step(wizard: &Wizard, scale: UiScale) -> Element<'_, Message> {
+    let mut summary =
+        column![text(format!("Provider: {}", wizard.provider.label())).size(scale.label())]
+            .spacing(6);
+    if wizard.provider.needs_key() {
+        summary =
+            summary.push(text(format!("API key: {}", wizard.key_summary())).size(scale.label()));
+    } else {
+        summary = summary
+            .push(text(format!("Server URL: {}", wizard.endpoint.trim())).size(scale.label()));
+    }
+    let folders: Vec<_> = wizard
+        .folders
+        .iter()
+        .filter(|folder| folder.selected)
+        .map(|folder| folder.dir.as_str())
+        .collect();
+    summary = summary.push(
+        text(if folders.is_empty() {
+            "Folders: none".to_string()
+        } else {
+            format!("Folders: {}", folders.join(", "))
+        })
+        .size(scale.label()),
+    );
+    summary = summary.push(text("You can run this setup again from Settings.").size(scale.label()));
+    summary.into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wizard() -> Wizard {
+        let mut config = Config::default();
+        config.security.allowed_host_dirs = vec!["/srv/docs".to_string(), "/srv/code".to_string()];
+        Wizard::new(&config)
+    }
+
+    #[test]
+    fn test_steps_advance_only_when_complete() {
+        let mut wizard = wizard();
+        assert_eq!(wizard.step, WizardStep::Provider);
+        assert_eq!(wizard.provider, ProviderChoice::Llamafile);
+
+        wizard.choose_provider(ProviderChoice::OpenAi);
+        assert_eq!(wizard.env_var, "OPENAI_API_KEY");
+        wizard.key_storage = KeyStorage::Keyring;
+        wizard.next();
+        assert_eq!(wizard.step, WizardStep::Provider);
+        assert_eq!(wizard.error.as_deref(), Some("Enter the API key"));
+
+        wizard.api_key = "sk-test".to_string();
+        wizard.next();
+        assert_eq!(wizard.step, WizardStep::Folders);
+        assert!(wizard.error.is_none());
+        wizard.back();
+        assert_eq!(wizard.step, WizardStep::Provider);
+        wizard.next();
+        wizard.next();
+        assert_eq!(wizard.step, WizardStep::Finish);
+        wizard.next();
+        assert_eq!(wizard.step, WizardStep::Finish);
+        wizard.back();
+        assert_eq!(wizard.step, WizardStep::Folders);
+    }
+
+    #[test]
+    fn test_indexing_reports_progress_and_blocks_next() {
+        let mut wizard = wizard();
+        wizard.next();
+        wizard.folders[0].selected = false;
+        wizard.folders.push(FolderChoice {
+            dir: "/srv/notes".to_string(),
+            path: PathBuf::from("/srv/notes"),
+            selected: true,
+        });
+
+        assert_eq!(wizard.start_indexing(), Some(PathBuf::from("/srv/code")));
+        assert_eq!(wizard.index_progress(), Some((0, 2)));
+        wizard.next();
+        assert_eq!(wizard.step, WizardStep::Folders);
+
+        let next = wizard.indexed("Indexed 3 files from /srv/code (0 skipped)".to_string());
+        assert_eq!(next, Some(PathBuf::from("/srv/notes")));
+        assert_eq!(wizard.indexed("Error: not found".to_string()), None);
+        assert_eq!(wizard.index_progress(), Some((2, 2)));
+        assert!(!wizard.is_indexing());
+        assert_eq!(wizard.index_log.len(), 2);
+        wizard.next();
+        assert_eq!(wizard.step, WizardStep::Finish);
+
+        for folder in &mut wizard.folders {
+            folder.selected = false;
+        }
+        assert_eq!(wizard.start_indexing(), None);
+    }
+
+    #[test]
+    fn test_untouched_wizard_keeps_the_default_config() {
+        let config = Config::default();
+        let wizard = Wizard::new(&config);
+        let finished = wizard.finish(&config, |_, _| unreachable!()).unwrap();
+        assert_eq!(finished, config);
+    }
+
+    #[test]
+    fn test_local_provider_and_folders_in_config() {
+        let mut wizard = wizard();
+        wizard.endpoint = " http://127.0.0.1:8080 ".to_string();
+        wizard.folders[1].selected = false;
+
+        let config = wizard
+            .finish(&Config::default(), |_, _| unreachable!())
+            .unwrap();
+        assert_eq!(config.llm.server_url, "http://127.0.0.1:8080");
+        assert_eq!(config.providers.default, "llamafile");
+        let entry = config.providers.resolve(None).unwrap();
+        assert_eq!(entry.endpoint.as_deref(), Some("http://127.0.0.1:8080"));
+        assert_eq!(config.security.allowed_host_dirs, ["/srv/docs"]);
+    }
+
+    #[test]
+    fn test_api_keys_are_referenced_not_written() {
+        let base = Config::default();
+        let mut wizard = wizard();
+        wizard.choose_provider(ProviderChoice::Anthropic);
+        wizard.api_key = "sk-ant-secret".to_string();
+
+        let config = wizard.finish(&base, |_, _| unreachable!()).unwrap();
+        let entry = config.providers.resolve(None).unwrap();
+        assert_eq!(entry.provider, "anthropic");
+        assert_eq!(entry.api_key_env.as_deref(), Some("ANTHROPIC_API_KEY"));
+        assert!(entry.api_key.is_none());
+        assert!(!toml::to_string(&config).unwrap().contains("sk-ant-secret"));
+        // The typed key is still used to test the connection
+        assert_eq!(
+            wizard.test_config().api_key.as_deref(),
+            Some("sk-ant-secret")
+        );
+
+        wizard.key_storage = KeyStorage::Keyring;
+        let mut stored = None;
+        let config = wizard
+            .finish(&base, |entry, key| {
+                stored = Some((entry.to_string(), key.to_string()));
+                Ok(())
+            })
+            .unwrap();
+        assert_eq!(
+            stored,
+            Some(("anthropic".to_string(), "sk-ant-secret".to_string()))
+        );
+        let entry = config.providers.resolve(None).unwrap();
+        assert_eq!(entry.api_key_keyring.as_deref(), Some("anthropic"));
+        assert!(entry.api_key.is_none() && entry.api_key_env.is_none());
+        assert!(!toml::to_string(&config).unwrap().contains("sk-ant-secret"));
+
+        let error = wizard
+            .finish(&base, |_, _| Err("no keyring".to_string()))
+            .unwrap_err();
+        assert_eq!(
+            error,
+            "Couldn't save the API key to the keyring: no keyring"
+        );
+
+        // Only when asked for explicitly
+        wizard.key_storage = KeyStorage::Config;
+        let config = wizard.finish(&base, |_, _| unreachable!()).unwrap();
+        let entry = config.providers.resolve(None).unwrap();
+        assert_eq!(entry.api_key.as_deref(), Some("sk-ant-secret"));
+    }
+}
@@ -0,0 +1,126 @@
+//! Text sizes and interface scale from `gui.font_size` and `gui.ui_scale`.
+//!
+//! Views size text by role rather than in pixels. [`UiScale::body`] is the
+//! configured font size and the other roles are fixed ratios of it, so the
+//! whole hierarchy follows the setting. `gui.ui_scale` is iced's scale
+//! factor: it grows padding and spacing along with the text.
+
+use lucastra_config::{GuiConfig, MAX_FONT_SIZE, MAX_UI_SCALE, MIN_FONT_SIZE, MIN_UI_SCALE};
+
+/// Text sizes for one base font size.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct UiScale {
+    base: u16,
+    factor: f64,
+}
+
+impl Default for UiScale {
+    fn default() -> Self {
+        Self::from_config(&GuiConfig::default())
+    }
+}
+
+impl UiScale {
+    /// Sizes for `gui`, with out of range values clamped.
+    pub fn from_config(gui: &GuiConfig) -> Self {
+        let factor = if gui.ui_scale.is_nan() {
+            1.0
+        } else {
+            gui.ui_scale.clamp(MIN_UI_SCALE, MAX_UI_SCALE)
+        };
+        Self {
+            base: gui.font_size.clamp(MIN_FONT_SIZE, MAX_FONT_SIZE),
+            factor: f64::from(factor),
+        }
+    }
+
+    /// Role names, sources and other fine print.
+    pub fn caption(&self) -> u16 {
+        self.relative(0.75)
+    }
+
+    /// Buttons, status lines and secondary text.
+    pub fn label(&self) -> u16 {
+        self.relative(0.875)
+    }
+
+    /// Messages, inputs and everything else.
+    pub fn body(&self) -> u16 {
+        self.base
+    }
+
+    /// Titles of sections within a page.
+    pub fn subheading(&self) -> u16 {
+        self.relative(1.125)
+    }
+
+    /// Page titles.
+    pub fn heading(&self) -> u16 {
+        self.relative(1.5)
+    }
+
+    /// iced's scale factor for the whole interface.
+    pub fn factor(&self) -> f64 {
+        self.factor
+    }
+
+    fn relative(&self, ratio: f32) -> u16 {
+        (f32::from(self.base) * ratio).round() as u16
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn scale(font_size: u16) -> UiScale {
+        UiScale::from_config(&GuiConfig {
+            font_size,
+            ..GuiConfig::default()
+        })
+    }
+
+    #[test]
+    fn test_sizes_stay_ordered_across_the_font_size_range() {
+        for font_size in MIN_FONT_SIZE..=MAX_FONT_SIZE {
+            let scale = scale(font_size);
+            let sizes = [
+                scale.caption(),
+                scale.label(),
+                scale.body(),
+                scale.subheading(),
+                scale.heading(),
+            ];
+            assert!(
+                sizes.windows(2).all(|pair| pair[0] < pair[1]),
+                "sizes for font size {} out of order: {:?}",
+                font_size,
+                sizes
+            );
+        }
+    }
+
+    #[test]
+    fn test_default_sizes_and_clamping() {
+        let default = UiScale::default();
+        assert_eq!(
+            (
+                default.caption(),
+                default.label(),
+                default.body(),
+                default.subheading(),
+                default.heading()
+            ),
+            (12, 14, 16, 18, 24)
+        );
+        assert_eq!(default.factor(), 1.0);
+
+        assert_eq!(scale(2).body(), MIN_FONT_SIZE);
+        assert_eq!(scale(400).body(), MAX_FONT_SIZE);
+        let huge = UiScale::from_config(&GuiConfig {
+            ui_scale: 10.0,
+            ..GuiConfig::default()
+        });
+        assert_eq!(huge.factor(), f64::from(MAX_UI_SCALE));
+    }
+}
@@ -0,0 +1,325 @@
+//! State of the settings form: the config being edited, the text typed into
+//! each field, and what stands in the way of saving it.
+
+use lucastra_config::{Config, GuiConfig, LlmConfig, SecurityConfig, StorageConfig};
+use std::collections::HashMap;
+use std::str::FromStr;
+
+#[derive(Debug, Clone)]
+pub enum SettingChange {
+    ServerUrl(String),
+    /// Model of the default provider; empty for the provider's default.
+    Model(String),
+    ModelSize(String),
+    Temperature(String),
+    MaxTokens(String),
+    Theme(String),
+    Language(String),
+    AutoStart(bool),
+    UseGpu(bool),
+    WindowWidth(String),
+    WindowHeight(String),
+    FontSize(String),
+    UiScale(String),
+    AutoIndex(bool),
+    EnableRbac(bool),
+    Role(String),
+}
+
+/// A group of settings with its own "Reset to defaults" button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SettingsSection {
+    Llm,
+    Gui,
+    Storage,
+    Security,
+}
+
+impl SettingsSection {
+    /// Config table the section edits, e.g. `llm`.
+    fn table(self) -> &'static str {
+        match self {
+            SettingsSection::Llm => "llm",
+            SettingsSection::Gui => "gui",
+            SettingsSection::Storage => "storage",
+            SettingsSection::Security => "security",
+        }
+    }
+}
+
+/// Fields with a row in the form; errors for other fields are listed at the
+/// top.
+const FORM_FIELDS: [&str; 9] = [
+    "llm.model_size",
+    "llm.temperature",
+    "llm.max_tokens",
+    "gui.theme",
+    "gui.window_width",
+    "gui.window_height",
+    "gui.font_size",
+    "gui.ui_scale",
+    "security.role",
+];
+
+/// The settings being edited against the config they started from.
+pub struct SettingsForm {
+    /// Config as last loaded or saved
+    saved: Config,
+    draft: Config,
+    /// Text as typed, keyed by config field, so unparseable input stays
+    /// visible next to its error.
+    inputs: HashMap<&'static str, String>,
+    /// Text that isn't a number, keyed by config field.
+    parse_errors: HashMap<&'static str, String>,
+    /// Models the default provider offers, once listed.
+    models: Option<Vec<String>>,
+}
+
+impl SettingsForm {
+    pub fn new(config: Config) -> Self {
+        Self {
+            saved: config.clone(),
+            draft: config,
+            inputs: HashMap::new(),
+            parse_errors: HashMap::new(),
+            models: None,
+        }
+    }
+
+    /// The config as edited so far.
+    pub fn draft(&self) -> &Config {
+        &self.draft
+    }
+
+    /// Model of the default provider as edited; empty when unset.
+    pub fn model(&self) -> String {
+        let providers = &self.draft.providers;
+        providers
+            .entries
+            .get(&providers.default)
+            .and_then(|entry| entry.model.clone())
+            .unwrap_or_default()
+    }
+
+    /// Models to offer for the Model field; `None` means it's typed in.
+    pub fn models(&self) -> Option<&[String]> {
+        self.models.as_deref()
+    }
+
+    /// Offer `models` for the Model field, or free text when there are none.
+    pub fn set_models(&mut self, models: Vec<String>) {
+        self.models = (!models.is_empty()).then_some(models);
+    }
+
+    pub fn apply(&mut self, change: SettingChange) {
+        let draft = &mut self.draft;
+        match change {
+            SettingChange::ServerUrl(url) => draft.llm.server_url = url,
+            SettingChange::Model(model) => {
+                let providers = &mut draft.providers;
+                if let Some(entry) = providers.entries.get_mut(&providers.default) {
+                    let model = model.trim();
+                    entry.model = (!model.is_empty()).then(|| model.to_string());
+                }
+            }
+            SettingChange::ModelSize(model) => draft.llm.model_size = model,
+            SettingChange::Theme(theme) => draft.gui.theme = theme,
+            SettingChange::Language(language) => draft.gui.language = language,
+            SettingChange::AutoStart(enabled) => draft.llm.auto_start = enabled,
+            SettingChange::UseGpu(enabled) => draft.llm.use_gpu = enabled,
+            SettingChange::AutoIndex(enabled) => draft.storage.auto_index = enabled,
+            SettingChange::EnableRbac(enabled) => draft.security.enable_rbac = enabled,
+            SettingChange::Role(role) => draft.security.role = role,
+            SettingChange::Temperature(raw) => {
+                self.set_number("llm.temperature", raw, |c, t| c.llm.temperature = t)
+            }
+            SettingChange::MaxTokens(raw) => {
+                self.set_number("llm.max_tokens", raw, |c, t| c.llm.max_tokens = t)
+            }
+            SettingChange::WindowWidth(raw) => {
+                self.set_number("gui.window_width", raw, |c, w| c.gui.window_width = w)
+            }
+            SettingChange::WindowHeight(raw) => {
+                self.set_number("gui.window_height", raw, |c, h| c.gui.window_height = h)
+            }
+            SettingChange::FontSize(raw) => {
+                self.set_number("gui.font_size", raw, |c, s| c.gui.font_size = s)
+            }
+            SettingChange::UiScale(raw) => {
+                self.set_number("gui.ui_scale", raw, |c, s| c.gui.ui_scale = s)
+            }
+        }
+    }
+
+    /// Apply `raw` to `field` if it parses as a number, keeping the text as
+    /// typed either way.
+    fn set_number<T: FromStr>(
+        &mut self,
+        field: &'static str,
+        raw: String,
+        apply: impl FnOnce(&mut Config, T),
+    ) {
+        match raw.trim().parse::<T>() {
+            Ok(value) => {
+                apply(&mut self.draft, value);
+                self.parse_errors.remove(field);
+            }
+            Err(_) => {
+                self.parse_errors
+                    .insert(field, format!("\"{}\" is not a valid number", raw));
+            }
+        }
+        self.inputs.insert(field, raw);
+    }
+
+    /// Put one section back to its default values.
+    pub fn reset_section(&mut self, section: SettingsSection) {
+        match section {
+            SettingsSection::Llm => self.draft.llm = LlmConfig::default(),
+            SettingsSection::Gui => self.draft.gui = GuiConfig::default(),
+            SettingsSection::Storage => self.draft.storage = StorageConfig::default(),
+            SettingsSection::Security => self.draft.security = SecurityConfig::default(),
+        }
+        let prefix = format!("{}.", section.table());
+        self.inputs.retain(|field, _| !field.starts_with(&prefix));
+        self.parse_errors
+            .retain(|field, _| !field.starts_with(&prefix));
+    }
+
+    /// Start over from `config`, e.g. once the draft has been saved.
+    pub fn reload(&mut self, config: Config) {
+        *self = Self::new(config);
+    }
+
+    /// Text to show in the input for `field`.
+    pub fn text(&self, field: &'static str, current: impl ToString) -> String {
+        self.inputs
+            .get(field)
+            .cloned()
+            .unwrap_or_else(|| current.to_string())
+    }
+
+    /// Why `field` can't be saved as it stands, if it can't.
+    pub fn error(&self, field: &'static str) -> Option<String> {
+        self.parse_errors.get(field).cloned().or_else(|| {
+            self.draft
+                .validate()
+                .err()?
+                .into_iter()
+                .find(|e| e.field == field)
+                .map(|e| e.message)
+        })
+    }
+
+    /// Problems with fields that have no row in the form, as `field: why`.
+    pub fn other_errors(&self) -> Vec<String> {
+        self.draft
+            .validate()
+            .err()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|e| !FORM_FIELDS.contains(&e.field))
+            .map(|e| e.to_string())
+            .collect()
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.parse_errors.is_empty() && self.draft.validate().is_ok()
+    }
+
+    /// Whether anything differs from the config the form started from.
+    /// Text that doesn't parse counts as a change.
+    pub fn is_dirty(&self) -> bool {
+        !self.parse_errors.is_empty() || self.draft != self.saved
+    }
+
+    pub fn can_save(&self) -> bool {
+        self.is_valid() && self.is_dirty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_invalid_input_blocks_save_until_fixed() {
+        let mut form = SettingsForm::new(Config::default());
+        assert!(!form.is_dirty() && !form.can_save());
+
+        form.apply(SettingChange::MaxTokens("abc".to_string()));
+        assert_eq!(form.text("llm.max_tokens", 0), "abc");
+        assert_eq!(
+            form.error("llm.max_tokens").as_deref(),
+            Some("\"abc\" is not a valid number")
+        );
+        assert!(form.is_dirty() && !form.can_save());
+
+        // Parses, but Config::validate rejects it
+        form.apply(SettingChange::Temperature("5".to_string()));
+        form.apply(SettingChange::MaxTokens("1024".to_string()));
+        assert!(form.error("llm.max_tokens").is_none());
+        assert!(form.error("llm.temperature").is_some());
+        assert!(!form.can_save());
+
+        form.apply(SettingChange::Temperature("0.3".to_string()));
+        assert!(form.is_valid() && form.can_save());
+        assert_eq!(form.draft().llm.max_tokens, 1024);
+    }
+
+    #[test]
+    fn test_changing_back_is_not_dirty() {
+        let mut form = SettingsForm::new(Config::default());
+        let use_gpu = form.draft().llm.use_gpu;
+        form.apply(SettingChange::UseGpu(!use_gpu));
+        assert!(form.can_save());
+        form.apply(SettingChange::UseGpu(use_gpu));
+        assert!(!form.is_dirty() && !form.can_save());
+    }
+
+    #[test]
+    fn test_reset_section_restores_defaults_and_clears_dirty_state() {
+        let mut form = SettingsForm::new(Config::default());
+        form.apply(SettingChange::WindowWidth("wide".to_string()));
+        form.apply(SettingChange::Theme("light".to_string()));
+        form.apply(SettingChange::AutoIndex(false));
+
+        form.reset_section(SettingsSection::Gui);
+        assert_eq!(form.text("gui.window_width", 1280), "1280");
+        assert!(form.error("gui.window_width").is_none());
+        assert_eq!(form.draft().gui, GuiConfig::default());
+        // Other sections keep their edits
+        assert!(form.can_save());
+
+        form.reset_section(SettingsSection::Storage);
+        assert!(!form.is_dirty());
+    }
+
+    #[test]
+    fn test_model_edits_the_default_provider() {
+        let mut form = SettingsForm::new(Config::default());
+        assert_eq!(form.model(), "");
+        assert!(form.models().is_none());
+
+        form.apply(SettingChange::Model("llama-3-8b".to_string()));
+        assert_eq!(form.model(), "llama-3-8b");
+        assert!(form.can_save());
+        form.apply(SettingChange::Model(" ".to_string()));
+        assert!(!form.is_dirty());
+
+        form.set_models(vec!["llama-3-8b".to_string()]);
+        assert_eq!(form.models().unwrap().len(), 1);
+        form.set_models(Vec::new());
+        assert!(form.models().is_none());
+    }
+
+    #[test]
+    fn test_reload_after_save() {
+        let mut form = SettingsForm::new(Config::default());
+        form.apply(SettingChange::Role("reader".to_string()));
+        let saved = form.draft().clone();
+        form.reload(saved);
+        assert!(!form.is_dirty());
+        assert_eq!(form.draft().security.role, "reader");
+    }
+}
@@ -0,0 +1,45 @@
+//! Keyboard shortcuts: key presses turned into chords for the
+//! [`ShortcutRegistry`](lucastra_config::ShortcutRegistry).
+
+use iced::keyboard::{Key, Modifiers};
+use lucastra_config::Chord;
+
+/// The chord for a key press, if the key can be part of one.
+pub fn chord_for(key: &Key, modifiers: Modifiers) -> Option<Chord> {
+    let name = match key {
+        Key::Character(c) if c.as_str() == "+" => "plus".to_string(),
+        Key::Character(c) => c.to_lowercase(),
+        // Debug names such as `Escape`, `ArrowUp` and `F5` parse as keys
+        Key::Named(named) => format!("{:?}", named),
+        Key::Unidentified => return None,
+    };
+    let mut chord: Chord = name.parse().ok()?;
+    chord.ctrl = modifiers.control();
+    chord.alt = modifiers.alt();
+    chord.shift = modifiers.shift();
+    chord.logo = modifiers.logo();
+    Some(chord)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use iced::keyboard::key::Named;
+
+    #[test]
+    fn test_key_presses_become_chords() {
+        let ctrl_shift = Modifiers::CTRL | Modifiers::SHIFT;
+        let chord = chord_for(&Key::Character("P".into()), ctrl_shift).unwrap();
+        assert_eq!(chord, "ctrl+shift+p".parse().unwrap());
+
+        let chord = chord_for(&Key::Named(Named::F5), Modifiers::empty()).unwrap();
+        assert_eq!(chord.to_string(), "f5");
+        let chord = chord_for(&Key::Named(Named::ArrowUp), Modifiers::ALT).unwrap();
+        assert_eq!(chord.to_string(), "alt+up");
+        let chord = chord_for(&Key::Character(",".into()), Modifiers::CTRL).unwrap();
+        assert_eq!(chord.to_string(), "ctrl+,");
+
+        assert!(chord_for(&Key::Named(Named::CapsLock), Modifiers::empty()).is_none());
+        assert!(chord_for(&Key::Unidentified, Modifiers::CTRL).is_none());
+    }
+}
@@ -0,0 +1,552 @@
+//! Chat tabs, each continuing its own conversation.
+//!
+//! A tab keeps its messages, the replies still streaming into them and the
+//! id of the conversation its queries continue, so answers in one tab never
+//! see another tab's turns. Closed tabs that were answered are remembered by
+//! conversation id and reopened from the conversation store. Only the tab
+//! opened at start saves to the chat history file.
+
+use crate::history::{ChatMessage, HistoryStore};
+use lucastra_i18n::t;
+use lucastra_llm::{Message, Role};
+use std::fmt;
+use std::io;
+use std::ops::{Index, IndexMut};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+/// A second click on a tab within this long renames it.
+pub const DOUBLE_CLICK: Duration = Duration::from_millis(400);
+
+/// Most closed tabs offered for reopening.
+pub const CLOSED_TABS_KEPT: usize = 20;
+
+/// Start of the conversation ids of chat tabs, telling them apart from
+/// other clients' conversations in the store.
+pub const CONVERSATION_PREFIX: &str = "gui-";
+
+/// A reply still being streamed into a tab's `messages[message]`.
+#[derive(Debug, Clone)]
+pub struct PendingReply {
+    pub command_id: String,
+    pub message: usize,
+}
+
+pub struct ChatTab {
+    pub title: String,
+    /// Queries from this tab continue this conversation.
+    pub conversation_id: String,
+    pub messages: Vec<ChatMessage>,
+    /// Replies sent on the bus and not yet finished.
+    pub pending: Vec<PendingReply>,
+    /// Where finished messages are saved; `None` for tabs other than the
+    /// first, whose turns the conversation store keeps.
+    history_store: Option<HistoryStore>,
+}
+
+impl ChatTab {
+    fn new(title: String, conversation_id: String) -> Self {
+        Self {
+            title,
+            conversation_id,
+            messages: Vec::new(),
+            pending: Vec::new(),
+            history_store: None,
+        }
+    }
+
+    /// Show `message` and save it to the history.
+    pub fn push(&mut self, message: ChatMessage, limit: usize) {
+        self.save(&message);
+        self.messages.push(message);
+        self.trim(limit);
+    }
+
+    /// Add the empty assistant message the reply to `command_id` streams
+    /// into.
+    pub fn start_reply(&mut self, command_id: String) {
+        self.messages
+            .push(ChatMessage::new("assistant", String::new()));
+        self.pending.push(PendingReply {
+            command_id,
+            message: self.messages.len() - 1,
+        });
+    }
+
+    /// Stop tracking the reply `pending[index]` and save it now that it is
+    /// complete.
+    pub fn finish_reply(&mut self, index: usize, limit: usize) {
+        let reply = self.pending.remove(index);
+        if let Some(message) = self.messages.get(reply.message).cloned() {
+            self.save(&message);
+        }
+        self.trim(limit);
+    }
+
+    /// Whether a reply is still being streamed.
+    pub fn is_busy(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Whether any query was answered, so the conversation store has
+    /// turns to reopen the tab from.
+    pub fn is_answered(&self) -> bool {
+        self.messages.iter().enumerate().any(|(index, message)| {
+            message.role == "assistant"
+                && !message.content.is_empty()
+                && !self.pending.iter().any(|reply| reply.message == index)
+        })
+    }
+
+    /// Forget the messages, here and in the history file, to start over
+    /// under `conversation_id`.
+    fn clear(&mut self, conversation_id: String) -> io::Result<()> {
+        self.messages.clear();
+        self.pending.clear();
+        self.conversation_id = conversation_id;
+        match self.history_store.as_mut() {
+            Some(store) => store.clear(),
+            None => Ok(()),
+        }
+    }
+
+    fn set_history_limit(&mut self, limit: usize) {
+        if let Some(store) = self.history_store.as_mut() {
+            store.set_limit(limit);
+        }
+        self.trim(limit);
+    }
+
+    /// Drop the oldest messages past `limit`, keeping replies still being
+    /// streamed.
+    fn trim(&mut self, limit: usize) {
+        let first_pending = self.pending.iter().map(|reply| reply.message).min();
+        let excess = self
+            .messages
+            .len()
+            .saturating_sub(limit)
+            .min(first_pending.unwrap_or(usize::MAX));
+        if excess == 0 {
+            return;
+        }
+        self.messages.drain(..excess);
+        for reply in &mut self.pending {
+            reply.message -= excess;
+        }
+    }
+
+    fn save(&mut self, message: &ChatMessage) {
+        if let Some(store) = self.history_store.as_mut() {
+            if let Err(e) = store.append(message) {
+                tracing::warn!(
+                    "Failed to save chat history to {}: {}",
+                    store.path().display(),
+                    e
+                );
+            }
+        }
+    }
+}
+
+/// A tab closed after it was answered, reopened from its saved
+/// conversation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClosedTab {
+    pub title: String,
+    pub conversation_id: String,
+}
+
+impl fmt::Display for ClosedTab {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.title)
+    }
+}
+
+/// The open tabs, which one is shown, and those that can be reopened.
+pub struct Tabs {
+    tabs: Vec<ChatTab>,
+    active: usize,
+    /// Oldest first.
+    closed: Vec<ClosedTab>,
+    /// Tabs opened so far, numbering their titles and conversation ids.
+    opened: usize,
+    /// Unix seconds at start, keeping conversation ids unique across runs.
+    session: u64,
+    last_click: Option<(usize, Instant)>,
+}
+
+impl Tabs {
+    /// One tab showing `messages`, saving new ones to `history_store`.
+    pub fn new(messages: Vec<ChatMessage>, history_store: Option<HistoryStore>) -> Self {
+        let session = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0);
+        let mut tabs = Self {
+            tabs: Vec::new(),
+            active: 0,
+            closed: Vec::new(),
+            opened: 0,
+            session,
+            last_click: None,
+        };
+        tabs.open();
+        tabs.tabs[0].messages = messages;
+        tabs.tabs[0].history_store = history_store;
+        tabs
+    }
+
+    /// Open a tab with a new conversation after the others and show it.
+    /// Returns its index.
+    pub fn open(&mut self) -> usize {
+        self.opened += 1;
+        let title = t!("gui.tabs.untitled", number = self.opened);
+        let tab = ChatTab::new(title, self.conversation_id(self.opened));
+        self.push(tab)
+    }
+
+    /// Open a tab continuing `closed`'s conversation, showing `messages`.
+    /// Returns its index.
+    pub fn reopen(&mut self, closed: ClosedTab, messages: Vec<ChatMessage>) -> usize {
+        self.closed
+            .retain(|tab| tab.conversation_id != closed.conversation_id);
+        let mut tab = ChatTab::new(closed.title, closed.conversation_id);
+        tab.messages = messages;
+        self.push(tab)
+    }
+
+    /// Close tab `index`, opening a new one if it was the last. Returns
+    /// the tab so its replies can be cancelled.
+    pub fn close(&mut self, index: usize) -> Option<ChatTab> {
+        if index >= self.tabs.len() {
+            return None;
+        }
+        let tab = self.tabs.remove(index);
+        if tab.is_answered() {
+            self.remember(ClosedTab {
+                title: tab.title.clone(),
+                conversation_id: tab.conversation_id.clone(),
+            });
+        }
+        if self.tabs.is_empty() {
+            self.open();
+        } else if index < self.active || self.active >= self.tabs.len() {
+            self.active -= 1;
+        }
+        self.last_click = None;
+        Some(tab)
+    }
+
+    /// Offer conversations saved by earlier runs for reopening, most
+    /// recent first, unless they are open already.
+    pub fn remember_saved(&mut self, saved: impl IntoIterator<Item = ClosedTab>) {
+        let saved: Vec<ClosedTab> = saved.into_iter().collect();
+        for closed in saved.into_iter().rev() {
+            if !self
+                .tabs
+                .iter()
+                .any(|tab| tab.conversation_id == closed.conversation_id)
+            {
+                self.remember(closed);
+            }
+        }
+    }
+
+    /// Stop offering the closed tab continuing `conversation_id`.
+    pub fn forget(&mut self, conversation_id: &str) {
+        self.closed
+            .retain(|tab| tab.conversation_id != conversation_id);
+    }
+
+    /// Closed tabs that can be reopened, most recently closed first.
+    pub fn closed(&self) -> Vec<ClosedTab> {
+        self.closed.iter().rev().cloned().collect()
+    }
+
+    pub fn select(&mut self, index: usize) {
+        if index < self.tabs.len() {
+            self.active = index;
+        }
+    }
+
+    /// Show the next tab, or the previous one going `back`, wrapping
+    /// around at either end.
+    pub fn cycle(&mut self, back: bool) {
+        let count = self.tabs.len();
+        self.active = if back {
+            (self.active + count - 1) % count
+        } else {
+            (self.active + 1) % count
+        };
+    }
+
+    /// Show tab `index` after a click on it at `now`. Returns whether the
+    /// click was the second of a double click.
+    pub fn click(&mut self, index: usize, now: Instant) -> bool {
+        self.select(index);
+        let double = matches!(
+            self.last_click,
+            Some((last, at)) if last == index && now.duration_since(at) <= DOUBLE_CLICK
+        );
+        // A third click starts a new double click
+        self.last_click = (!double).then_some((index, now));
+        double
+    }
+
+    /// Call tab `index` `title`. A blank title keeps the old one.
+    pub fn rename(&mut self, index: usize, title: &str) {
+        let title = title.trim();
+        if let Some(tab) = self.tabs.get_mut(index).filter(|_| !title.is_empty()) {
+            tab.title = title.to_string();
+        }
+    }
+
+    /// Clear the shown tab and give it a new conversation. Returns the
+    /// conversation it had.
+    pub fn clear_active(&mut self) -> (String, io::Result<()>) {
+        self.opened += 1;
+        let id = self.conversation_id(self.opened);
+        let tab = &mut self.tabs[self.active];
+        let ended = tab.conversation_id.clone();
+        let cleared = tab.clear(id);
+        (ended, cleared)
+    }
+
+    /// The tab and pending reply the response to `command_id` belongs to.
+    pub fn find_reply(&self, command_id: &str) -> Option<(usize, usize)> {
+        self.tabs.iter().enumerate().find_map(|(tab, chat)| {
+            chat.pending
+                .iter()
+                .position(|reply| reply.command_id == command_id)
+                .map(|reply| (tab, reply))
+        })
+    }
+
+    /// Whether any tab is waiting for a reply.
+    pub fn is_busy(&self) -> bool {
+        self.tabs.iter().any(ChatTab::is_busy)
+    }
+
+    pub fn set_history_limit(&mut self, limit: usize) {
+        for tab in &mut self.tabs {
+            tab.set_history_limit(limit);
+        }
+    }
+
+    pub fn active(&self) -> &ChatTab {
+        &self.tabs[self.active]
+    }
+
+    pub fn active_mut(&mut self) -> &mut ChatTab {
+        &mut self.tabs[self.active]
+    }
+
+    pub fn active_index(&self) -> usize {
+        self.active
+    }
+
+    pub fn get(&self, index: usize) -> Option<&ChatTab> {
+        self.tabs.get(index)
+    }
+
+    /// Index of the tab continuing `conversation_id`.
+    pub fn position(&self, conversation_id: &str) -> Option<usize> {
+        self.tabs
+            .iter()
+            .position(|tab| tab.conversation_id == conversation_id)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &ChatTab> {
+        self.tabs.iter()
+    }
+
+    fn push(&mut self, tab: ChatTab) -> usize {
+        self.tabs.push(tab);
+        self.active = self.tabs.len() - 1;
+        self.active
+    }
+
+    fn remember(&mut self, closed: ClosedTab) {
+        self.closed
+            .retain(|tab| tab.conversation_id != closed.conversation_id);
+        self.closed.push(closed);
+        let excess = self.closed.len().saturating_sub(CLOSED_TABS_KEPT);
+        self.closed.drain(..excess);
+    }
+
+    /// Id of the `n`th conversation of this run; unique to this process,
+    /// since other clients may share the system state over JSON-RPC, and
+    /// to this start, since conversations are saved.
+    fn conversation_id(&self, n: usize) -> String {
+        format!(
+            "{}{}-{}-{}",
+            CONVERSATION_PREFIX,
+            std::process::id(),
+            self.session,
+            n
+        )
+    }
+}
+
+impl Index<usize> for Tabs {
+    type Output = ChatTab;
+
+    fn index(&self, index: usize) -> &ChatTab {
+        &self.tabs[index]
+    }
+}
+
+impl IndexMut<usize> for Tabs {
+    fn index_mut(&mut self, index: usize) -> &mut ChatTab {
+        &mut self.tabs[index]
+    }
+}
+
+/// A reopened tab's saved turns as chat messages.
+pub fn restored_messages(messages: Vec<Message>) -> Vec<ChatMessage> {
+    messages
+        .into_iter()
+        .map(|message| {
+            let role = match message.role {
+                Role::System => "system",
+                Role::User => "user",
+                Role::Assistant => "assistant",
+            };
+            ChatMessage {
+                timestamp: u64::try_from(message.timestamp).unwrap_or(0) * 1000,
+                ..ChatMessage::new(role, message.content)
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A tab whose query was answered.
+    fn answered(tabs: &mut Tabs, question: &str) {
+        let tab = tabs.active_mut();
+        tab.push(ChatMessage::new("user", question), 100);
+        tab.start_reply(format!("cmd-{}", question));
+        tab.messages.last_mut().unwrap().content = "answer".to_string();
+        tab.finish_reply(0, 100);
+    }
+
+    fn titles(tabs: &Tabs) -> Vec<String> {
+        tabs.iter().map(|tab| tab.title.clone()).collect()
+    }
+
+    #[test]
+    fn test_active_tab_after_closing() {
+        let mut tabs = Tabs::new(Vec::new(), None);
+        tabs.open();
+        tabs.open();
+        tabs.open();
+        assert_eq!(titles(&tabs), ["Chat 1", "Chat 2", "Chat 3", "Chat 4"]);
+        assert_eq!(tabs.active_index(), 3);
+
+        // Closing the current, last tab shows the one before it
+        tabs.close(3);
+        assert_eq!(tabs.active_index(), 2);
+        assert_eq!(tabs.active().title, "Chat 3");
+
+        // Closing the current tab in the middle shows the next one
+        tabs.select(1);
+        tabs.close(1);
+        assert_eq!(tabs.active().title, "Chat 3");
+
+        // Closing a tab before the current one keeps showing it
+        tabs.close(0);
+        assert_eq!(tabs.active_index(), 0);
+        assert_eq!(tabs.active().title, "Chat 3");
+        assert!(tabs.close(5).is_none());
+
+        // Closing the only tab opens a fresh one
+        tabs.close(0);
+        assert_eq!(titles(&tabs), ["Chat 5"]);
+        assert_eq!(tabs.active_index(), 0);
+    }
+
+    #[test]
+    fn test_tabs_keep_their_own_history_and_conversation() {
+        let mut tabs = Tabs::new(vec![ChatMessage::system("welcome")], None);
+        let first = tabs.active().conversation_id.clone();
+        answered(&mut tabs, "about code?");
+        tabs.open();
+        let second = tabs.active().conversation_id.clone();
+        assert_ne!(first, second);
+        assert!(second.starts_with(CONVERSATION_PREFIX));
+        assert!(tabs.active().messages.is_empty());
+
+        // A reply streaming in a background tab is found there
+        tabs.active_mut().start_reply("cmd-docs".to_string());
+        tabs.select(0);
+        assert_eq!(tabs.find_reply("cmd-docs"), Some((1, 0)));
+        assert!(tabs.is_busy());
+        assert!(!tabs.active().is_busy());
+        assert_eq!(tabs.active().messages.len(), 3);
+
+        // Clearing a tab only touches that tab and gives it a new conversation
+        let (ended, cleared) = tabs.clear_active();
+        assert!(cleared.is_ok());
+        assert_eq!(ended, first);
+        assert!(tabs.active().messages.is_empty());
+        assert_ne!(tabs.active().conversation_id, first);
+        assert_eq!(tabs.position(&second), Some(1));
+        tabs.select(1);
+        assert_eq!(tabs.active().messages.len(), 1);
+    }
+
+    #[test]
+    fn test_answered_tabs_can_be_reopened() {
+        let mut tabs = Tabs::new(Vec::new(), None);
+        answered(&mut tabs, "about code?");
+        tabs.rename(0, "  Code  ");
+        tabs.rename(0, " ");
+        let code = tabs.active().conversation_id.clone();
+        tabs.open();
+        tabs.close(1);
+        tabs.close(0);
+        // Only the answered tab can be reopened
+        let closed = tabs.closed();
+        assert_eq!(closed.len(), 1);
+        assert_eq!(closed[0].to_string(), "Code");
+
+        tabs.remember_saved([
+            ClosedTab {
+                title: "Documents".to_string(),
+                conversation_id: "gui-1-1-1".to_string(),
+            },
+            closed[0].clone(),
+        ]);
+        assert_eq!(tabs.closed()[0].conversation_id, "gui-1-1-1");
+
+        let index = tabs.reopen(
+            closed[0].clone(),
+            vec![ChatMessage::new("user", "about code?")],
+        );
+        assert_eq!(tabs.active_index(), index);
+        assert_eq!(tabs.active().conversation_id, code);
+        assert_eq!(tabs.closed().len(), 1);
+    }
+
+    #[test]
+    fn test_cycling_and_double_clicks() {
+        let mut tabs = Tabs::new(Vec::new(), None);
+        tabs.open();
+        tabs.open();
+        tabs.cycle(false);
+        assert_eq!(tabs.active_index(), 0);
+        tabs.cycle(true);
+        tabs.cycle(true);
+        assert_eq!(tabs.active_index(), 1);
+
+        let now = Instant::now();
+        assert!(!tabs.click(0, now));
+        assert_eq!(tabs.active_index(), 0);
+        assert!(!tabs.click(2, now));
+        assert!(tabs.click(2, now + Duration::from_millis(100)));
+        assert!(!tabs.click(2, now + Duration::from_millis(200)));
+        assert!(!tabs.click(2, now + Duration::from_secs(1)));
+    }
+}
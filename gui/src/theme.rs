@@ -0,0 +1,151 @@
+//! Colors for the dark and light themes.
+
+use iced::{theme::Palette, Color, Theme};
+
+/// Every color the GUI draws with, for one theme.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ThemePalette {
+    pub background: Color,
+    pub text: Color,
+    /// Buttons and other accents.
+    pub primary: Color,
+    pub taskbar: Color,
+    pub taskbar_text: Color,
+    pub toast: Color,
+    pub toast_text: Color,
+    /// "Info" label on notices.
+    pub toast_label: Color,
+    /// "Confirm" label on approval requests.
+    pub confirm_label: Color,
+    pub error_banner: Color,
+    pub error_banner_text: Color,
+    /// "Error" label on the error banner.
+    pub error_label: Color,
+    /// Validation messages under settings.
+    pub field_error: Color,
+    pub user: Color,
+    pub assistant: Color,
+    pub system: Color,
+    /// Behind code blocks in messages.
+    pub code_background: Color,
+}
+
+impl ThemePalette {
+    pub const DARK: Self = Self {
+        background: Color::from_rgb(0.12, 0.12, 0.13),
+        text: Color::from_rgb(0.92, 0.92, 0.92),
+        primary: Color::from_rgb(0.36, 0.48, 0.9),
+        taskbar: Color::from_rgb(0.15, 0.15, 0.15),
+        taskbar_text: Color::WHITE,
+        toast: Color::from_rgb(0.1, 0.2, 0.35),
+        toast_text: Color::WHITE,
+        toast_label: Color::from_rgb(0.8, 0.9, 1.0),
+        confirm_label: Color::from_rgb(1.0, 0.85, 0.5),
+        error_banner: Color::from_rgb(0.5, 0.1, 0.1),
+        error_banner_text: Color::WHITE,
+        error_label: Color::from_rgb(1.0, 0.8, 0.8),
+        field_error: Color::from_rgb(0.9, 0.3, 0.3),
+        user: Color::from_rgb(0.3, 0.5, 0.9),
+        assistant: Color::from_rgb(0.2, 0.8, 0.4),
+        system: Color::from_rgb(0.6, 0.6, 0.6),
+        code_background: Color::from_rgb(0.18, 0.18, 0.2),
+    };
+
+    pub const LIGHT: Self = Self {
+        background: Color::from_rgb(0.97, 0.97, 0.98),
+        text: Color::from_rgb(0.1, 0.1, 0.12),
+        primary: Color::from_rgb(0.2, 0.35, 0.8),
+        taskbar: Color::from_rgb(0.88, 0.89, 0.91),
+        taskbar_text: Color::from_rgb(0.1, 0.1, 0.12),
+        toast: Color::from_rgb(0.85, 0.91, 0.98),
+        toast_text: Color::from_rgb(0.1, 0.15, 0.25),
+        toast_label: Color::from_rgb(0.15, 0.35, 0.65),
+        confirm_label: Color::from_rgb(0.7, 0.45, 0.0),
+        error_banner: Color::from_rgb(0.98, 0.86, 0.86),
+        error_banner_text: Color::from_rgb(0.35, 0.05, 0.05),
+        error_label: Color::from_rgb(0.7, 0.1, 0.1),
+        field_error: Color::from_rgb(0.75, 0.15, 0.15),
+        user: Color::from_rgb(0.15, 0.35, 0.75),
+        assistant: Color::from_rgb(0.1, 0.5, 0.25),
+        system: Color::from_rgb(0.4, 0.4, 0.42),
+        code_background: Color::from_rgb(0.91, 0.92, 0.94),
+    };
+
+    /// Palette for a `gui.theme` value. `"auto"` follows the OS preference
+    /// where it can be detected; anything else unknown is dark.
+    pub fn from_config(theme: &str) -> Self {
+        match theme {
+            "light" => Self::LIGHT,
+            "auto" if system_prefers_light() => Self::LIGHT,
+            _ => Self::DARK,
+        }
+    }
+
+    /// Label color for messages from `role`.
+    pub fn role_color(&self, role: &str) -> Color {
+        match role {
+            "user" => self.user,
+            "assistant" => self.assistant,
+            "system" => self.system,
+            _ => self.text,
+        }
+    }
+
+    /// iced theme drawing widgets with this palette.
+    pub fn iced_theme(&self) -> Theme {
+        let name = if *self == Self::LIGHT {
+            "LucAstra Light"
+        } else {
+            "LucAstra Dark"
+        };
+        Theme::custom(
+            name.to_string(),
+            Palette {
+                background: self.background,
+                text: self.text,
+                primary: self.primary,
+                success: self.assistant,
+                danger: self.field_error,
+            },
+        )
+    }
+}
+
+fn system_prefers_light() -> bool {
+    matches!(dark_light::detect(), dark_light::Mode::Light)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_palette_per_theme() {
+        let dark = ThemePalette::from_config("dark");
+        let light = ThemePalette::from_config("light");
+        assert_eq!(dark, ThemePalette::DARK);
+        assert_eq!(light, ThemePalette::LIGHT);
+        assert_eq!(ThemePalette::from_config("neon"), ThemePalette::DARK);
+
+        let auto = ThemePalette::from_config("auto");
+        assert!(auto == dark || auto == light);
+
+        let pairs = [
+            (dark.background, light.background),
+            (dark.text, light.text),
+            (dark.taskbar, light.taskbar),
+            (dark.toast, light.toast),
+            (dark.error_banner, light.error_banner),
+            (dark.code_background, light.code_background),
+            (dark.role_color("user"), light.role_color("user")),
+            (dark.role_color("assistant"), light.role_color("assistant")),
+            (dark.role_color("system"), light.role_color("system")),
+        ];
+        for (dark_color, light_color) in pairs {
+            assert_ne!(dark_color, light_color);
+        }
+        // Dark backgrounds are darker than their text, light ones lighter
+        assert!(dark.background.r < dark.text.r);
+        assert!(light.background.r > light.text.r);
+    }
+}
@@ -0,0 +1,181 @@
+//! Chat transcripts as Markdown, and the code blocks inside messages.
+
+use crate::history::ChatMessage;
+use lucastra_config::SecurityConfig;
+use lucastra_tools::file_access::{FileAccessValidator, FileOperation};
+use std::path::Path;
+
+const FENCE: &str = "```";
+
+/// A fenced code block in a message.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBlock {
+    /// Info string after the opening fence, e.g. `rust`.
+    pub language: Option<String>,
+    pub code: String,
+}
+
+/// Heading shown for messages from `role`.
+pub fn role_name(role: &str) -> &str {
+    match role {
+        "user" => "You",
+        "assistant" => "LucAstra",
+        "system" => "System",
+        other => other,
+    }
+}
+
+/// Triple-backtick code blocks in `content`, in order. A fence left open
+/// runs to the end of the message.
+pub fn code_blocks(content: &str) -> Vec<CodeBlock> {
+    let mut blocks = Vec::new();
+    let mut open: Option<CodeBlock> = None;
+    for line in content.lines() {
+        let trimmed = line.trim_start();
+        match open.as_mut() {
+            None => {
+                if let Some(info) = trimmed.strip_prefix(FENCE) {
+                    let info = info.trim();
+                    open = Some(CodeBlock {
+                        language: (!info.is_empty()).then(|| info.to_string()),
+                        code: String::new(),
+                    });
+                }
+            }
+            Some(_) if trimmed.trim_end() == FENCE => blocks.extend(open.take()),
+            Some(block) => {
+                if !block.code.is_empty() {
+                    block.code.push('\n');
+                }
+                block.code.push_str(line);
+            }
+        }
+    }
+    blocks.extend(open);
+    blocks
+}
+
+/// The messages as a Markdown document, one `##` section per message.
+/// Message text is kept as written; a code fence a message leaves open is
+/// closed so it can't swallow the messages after it.
+pub fn to_markdown(messages: &[ChatMessage]) -> String {
+    let mut markdown = String::from("# LucAstra transcript\n");
+    for message in messages {
+        markdown.push_str(&format!("\n## {}\n\n", role_name(&message.role)));
+        let content = message.content.trim_end();
+        markdown.push_str(content);
+        markdown.push('\n');
+        if has_open_fence(content) {
+            markdown.push_str(FENCE);
+            markdown.push('\n');
+        }
+        if !message.sources.is_empty() {
+            markdown.push_str("\nSources:\n");
+            for source in &message.sources {
+                // Angle brackets keep spaces and parentheses in paths intact
+                markdown.push_str(&format!("- <{}>", source.label()));
+                if source.is_attachment() {
+                    markdown.push_str(" (attached)");
+                }
+                markdown.push('\n');
+            }
+        }
+    }
+    markdown
+}
+
+/// Write the transcript to `path`, which must be inside one of the
+/// allowed host directories.
+pub fn export(
+    messages: &[ChatMessage],
+    path: &Path,
+    security: &SecurityConfig,
+) -> Result<(), String> {
+    // Exporting is the user's own action, so it doesn't need the host
+    // write permission granted to tools, only an allowed directory
+    let validator = FileAccessValidator::new(
+        security.resolved_allowed_dirs(),
+        security.allow_host_read,
+        true,
+        security.allow_usb,
+    );
+    validator
+        .validate_path(path, FileOperation::Write)
+        .map_err(|e| format!("Can't export to {}: {}", path.display(), e))?;
+    std::fs::write(path, to_markdown(messages))
+        .map_err(|e| format!("Can't export to {}: {}", path.display(), e))
+}
+
+fn has_open_fence(content: &str) -> bool {
+    let fences = content
+        .lines()
+        .filter(|line| line.trim_start().starts_with(FENCE))
+        .count();
+    fences % 2 == 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_core::{SourceOrigin, SourceRef};
+
+    #[test]
+    fn test_code_blocks_with_and_without_language() {
+        let content = "Try this:\n```rust\nfn main() {\n    println!(\"hi\");\n}\n```\nor\n```\nls -la\n```\nand\n```sh\necho open";
+        let blocks = code_blocks(content);
+        assert_eq!(
+            blocks,
+            vec![
+                CodeBlock {
+                    language: Some("rust".to_string()),
+                    code: "fn main() {\n    println!(\"hi\");\n}".to_string(),
+                },
+                CodeBlock {
+                    language: None,
+                    code: "ls -la".to_string(),
+                },
+                CodeBlock {
+                    language: Some("sh".to_string()),
+                    code: "echo open".to_string(),
+                },
+            ]
+        );
+        assert!(code_blocks("no code here").is_empty());
+    }
+
+    #[test]
+    fn test_markdown_keeps_fences_and_special_characters() {
+        let mut answer = ChatMessage::new(
+            "assistant",
+            "Use `<T>` & *generics* — naïve 😀:\n```rust\nlet x = \"# not a heading\";\n```",
+        );
+        answer.sources = vec![SourceRef {
+            path: "/docs/my notes (v2).md".to_string(),
+            score: 1.0,
+            snippet: String::new(),
+            origin: SourceOrigin::Index,
+        }];
+        let messages = vec![ChatMessage::new("user", "How do I write generics?"), answer];
+
+        assert_eq!(
+            to_markdown(&messages),
+            "# LucAstra transcript\n\
+             \n## You\n\nHow do I write generics?\n\
+             \n## LucAstra\n\n\
+             Use `<T>` & *generics* — naïve 😀:\n```rust\nlet x = \"# not a heading\";\n```\n\
+             \nSources:\n- </docs/my notes (v2).md>\n"
+        );
+    }
+
+    #[test]
+    fn test_unclosed_fence_is_closed_before_next_message() {
+        let messages = vec![
+            ChatMessage::new("assistant", "```python\nprint('cut off')"),
+            ChatMessage::system("Response cut off at max tokens."),
+        ];
+        let markdown = to_markdown(&messages);
+
+        assert!(markdown.contains("print('cut off')\n```\n\n## System\n"));
+        assert!(!has_open_fence(&markdown));
+    }
+}
@@ -1,4 +1,7 @@
-use lucastra_core::Result;
+use lucastra_core::{LuCastraError, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
 
 /// Block device abstraction (USB drives, disks).
 pub trait BlockDevice {
@@ -21,13 +24,18 @@ impl MockBlockDevice {
             sector_size,
         }
     }
+
+    /// Device whose contents are `data`, e.g. a disk image.
+    pub fn from_bytes(data: Vec<u8>, sector_size: usize) -> Self {
+        Self { data, sector_size }
+    }
 }
 
 impl BlockDevice for MockBlockDevice {
     fn read_sector(&mut self, sector: u64, buffer: &mut [u8]) -> Result<usize> {
         let offset = (sector as usize) * self.sector_size;
-        let to_read = buffer.len().min(self.data.len() - offset);
         if offset < self.data.len() {
+            let to_read = buffer.len().min(self.data.len() - offset);
             buffer[..to_read].copy_from_slice(&self.data[offset..offset + to_read]);
             Ok(to_read)
         } else {
@@ -37,8 +45,8 @@ impl BlockDevice for MockBlockDevice {
 
     fn write_sector(&mut self, sector: u64, buffer: &[u8]) -> Result<usize> {
         let offset = (sector as usize) * self.sector_size;
-        let to_write = buffer.len().min(self.data.len() - offset);
         if offset < self.data.len() {
+            let to_write = buffer.len().min(self.data.len() - offset);
             self.data[offset..offset + to_write].copy_from_slice(&buffer[..to_write]);
             Ok(to_write)
         } else {
@@ -54,3 +62,58 @@ impl BlockDevice for MockBlockDevice {
         (self.data.len() / self.sector_size) as u64
     }
 }
+
+/// Read-only block device backed by a device node or disk image file.
+pub struct FileBlockDevice {
+    file: File,
+    sector_size: usize,
+    total_sectors: u64,
+}
+
+impl FileBlockDevice {
+    pub fn open(path: impl AsRef<Path>, sector_size: usize) -> Result<Self> {
+        let path = path.as_ref();
+        let io_error =
+            |e: std::io::Error| LuCastraError::DeviceIoError(format!("{}: {}", path.display(), e));
+        let mut file = File::open(path).map_err(io_error)?;
+        // Device nodes report a zero length in metadata, so seek to the end instead
+        let len = file.seek(SeekFrom::End(0)).map_err(io_error)?;
+        Ok(Self {
+            file,
+            sector_size,
+            total_sectors: len / sector_size as u64,
+        })
+    }
+}
+
+impl BlockDevice for FileBlockDevice {
+    fn read_sector(&mut self, sector: u64, buffer: &mut [u8]) -> Result<usize> {
+        let io_error = |e: std::io::Error| LuCastraError::DeviceIoError(e.to_string());
+        self.file
+            .seek(SeekFrom::Start(sector * self.sector_size as u64))
+            .map_err(io_error)?;
+
+        let mut read = 0;
+        while read < buffer.len() {
+            match self.file.read(&mut buffer[read..]).map_err(io_error)? {
+                0 => break,
+                n => read += n,
+            }
+        }
+        Ok(read)
+    }
+
+    fn write_sector(&mut self, _sector: u64, _buffer: &[u8]) -> Result<usize> {
+        Err(LuCastraError::DeviceIoError(
+            "block device is opened read-only".to_string(),
+        ))
+    }
+
+    fn sector_size(&self) -> usize {
+        self.sector_size
+    }
+
+    fn total_sectors(&self) -> u64 {
+        self.total_sectors
+    }
+}
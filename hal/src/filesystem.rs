@@ -1,28 +1,136 @@
-use lucastra_core::Result;
+use lucastra_core::{LuCastraError, Result};
+use std::collections::{BTreeSet, HashMap};
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Component, Path, PathBuf};
+use std::time::SystemTime;
+
+/// Size, type and modification time of a file or directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileMetadata {
+    pub size: u64,
+    pub is_dir: bool,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileMetadata {
+    pub fn file(size: u64) -> Self {
+        Self {
+            size,
+            is_dir: false,
+            modified: None,
+        }
+    }
+
+    pub fn dir() -> Self {
+        Self {
+            size: 0,
+            is_dir: true,
+            modified: None,
+        }
+    }
+}
 
 /// Filesystem driver abstraction (FAT32, ext4, etc.).
 pub trait FileSystemDriver {
+    /// Short name of the driver type, shown in mount listings.
+    fn name(&self) -> &str {
+        "filesystem"
+    }
+
+    /// Whether writes reach the real host filesystem, which sandboxed tools
+    /// may not modify.
+    fn is_host_backed(&self) -> bool {
+        false
+    }
     fn mount(&mut self, path: &str) -> Result<()>;
     fn unmount(&mut self) -> Result<()>;
     fn list_files(&self, path: &str) -> Result<Vec<String>>;
     fn read_file(&self, path: &str) -> Result<Vec<u8>>;
     fn write_file(&mut self, path: &str, data: &[u8]) -> Result<()>;
     fn is_mounted(&self) -> bool;
+
+    /// Up to `len` bytes starting `offset` bytes into a file: fewer at the
+    /// end of the file and none past it. The default reads the whole file.
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let data = self.read_file(path)?;
+        Ok(byte_range(&data, offset, len).to_vec())
+    }
+
+    /// Remove a file. Read-only drivers keep the default, which refuses.
+    fn delete_file(&mut self, path: &str) -> Result<()> {
+        Err(LuCastraError::FilesystemError(format!(
+            "{} filesystem does not support deleting {}",
+            self.name(),
+            path
+        )))
+    }
+
+    /// Create a directory. Read-only drivers keep the default, which refuses.
+    fn create_dir(&mut self, path: &str) -> Result<()> {
+        Err(LuCastraError::FilesystemError(format!(
+            "{} filesystem does not support creating {}",
+            self.name(),
+            path
+        )))
+    }
+
+    /// Metadata for a path. The default reads the file to learn its size.
+    fn stat(&self, path: &str) -> Result<FileMetadata> {
+        self.read_file(path)
+            .map(|data| FileMetadata::file(data.len() as u64))
+    }
+
+    /// Entries of a directory with their metadata.
+    fn list_entries(&self, path: &str) -> Result<Vec<(String, FileMetadata)>> {
+        self.list_files(path)?
+            .into_iter()
+            .map(|entry| {
+                let metadata = self.stat(&entry)?;
+                Ok((entry, metadata))
+            })
+            .collect()
+    }
+}
+
+/// The part of `data` a [`FileSystemDriver::read_range`] call returns.
+pub fn byte_range(data: &[u8], offset: u64, len: usize) -> &[u8] {
+    let start = usize::try_from(offset).map_or(data.len(), |o| o.min(data.len()));
+    let end = start.saturating_add(len).min(data.len());
+    &data[start..end]
 }
 
 /// Mock filesystem for testing.
+///
+/// Directories exist implicitly for every path prefix of a stored file, or
+/// explicitly via [`MockFileSystem::set_metadata`].
 pub struct MockFileSystem {
     mounted: bool,
-    files: std::collections::HashMap<String, Vec<u8>>,
+    files: HashMap<String, Vec<u8>>,
+    metadata: HashMap<String, FileMetadata>,
 }
 
 impl MockFileSystem {
     pub fn new() -> Self {
         Self {
             mounted: false,
-            files: std::collections::HashMap::new(),
+            files: HashMap::new(),
+            metadata: HashMap::new(),
         }
     }
+
+    /// Override the metadata reported for `path` (e.g. to add an empty directory).
+    pub fn set_metadata(&mut self, path: &str, metadata: FileMetadata) {
+        self.metadata.insert(path.to_string(), metadata);
+    }
+
+    fn has_children(&self, path: &str) -> bool {
+        let prefix = format!("{}/", path.trim_end_matches('/'));
+        self.files
+            .keys()
+            .chain(self.metadata.keys())
+            .any(|p| p.starts_with(&prefix))
+    }
 }
 
 impl Default for MockFileSystem {
@@ -32,6 +140,10 @@ impl Default for MockFileSystem {
 }
 
 impl FileSystemDriver for MockFileSystem {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
     fn mount(&mut self, path: &str) -> Result<()> {
         tracing::info!("Mounting mock filesystem at {}", path);
         self.mounted = true;
@@ -44,14 +156,47 @@ impl FileSystemDriver for MockFileSystem {
         Ok(())
     }
 
-    fn list_files(&self, _path: &str) -> Result<Vec<String>> {
-        Ok(self.files.keys().cloned().collect())
+    /// Direct children of `path`, sorted.
+    fn list_files(&self, path: &str) -> Result<Vec<String>> {
+        let base = path.trim_end_matches('/');
+        let prefix = format!("{}/", base);
+        let children: BTreeSet<String> = self
+            .files
+            .keys()
+            .chain(self.metadata.keys())
+            .filter_map(|p| p.strip_prefix(&prefix))
+            .filter_map(|rest| rest.split('/').next())
+            .filter(|name| !name.is_empty())
+            .map(|name| format!("{}{}", prefix, name))
+            .collect();
+        Ok(children.into_iter().collect())
+    }
+
+    fn stat(&self, path: &str) -> Result<FileMetadata> {
+        if let Some(metadata) = self.metadata.get(path) {
+            return Ok(metadata.clone());
+        }
+        if let Some(data) = self.files.get(path) {
+            return Ok(FileMetadata::file(data.len() as u64));
+        }
+        if self.has_children(path) {
+            return Ok(FileMetadata::dir());
+        }
+        Err(LuCastraError::FileNotFound(path.to_string()))
     }
 
     fn read_file(&self, path: &str) -> Result<Vec<u8>> {
-        self.files.get(path).cloned().ok_or_else(|| {
-            lucastra_core::LuCastraError::FilesystemError(format!("File not found: {}", path))
-        })
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| LuCastraError::FileNotFound(path.to_string()))
+    }
+
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>> {
+        self.files
+            .get(path)
+            .map(|data| byte_range(data, offset, len).to_vec())
+            .ok_or_else(|| LuCastraError::FileNotFound(path.to_string()))
     }
 
     fn write_file(&mut self, path: &str, data: &[u8]) -> Result<()> {
@@ -59,7 +204,382 @@ impl FileSystemDriver for MockFileSystem {
         Ok(())
     }
 
+    fn delete_file(&mut self, path: &str) -> Result<()> {
+        self.metadata.remove(path);
+        self.files
+            .remove(path)
+            .map(|_| ())
+            .ok_or_else(|| LuCastraError::FileNotFound(path.to_string()))
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<()> {
+        self.set_metadata(path, FileMetadata::dir());
+        Ok(())
+    }
+
     fn is_mounted(&self) -> bool {
         self.mounted
     }
 }
+
+/// Filesystem driver backed by a directory on the host.
+///
+/// Paths passed to the driver are virtual paths under its mount point
+/// (e.g. `/mnt/host/notes.txt`) and are mapped onto `root`. Anything that
+/// would resolve outside `root`, via `..` or a symlink, is rejected.
+pub struct HostFileSystem {
+    root: PathBuf,
+    mount_point: Option<String>,
+}
+
+impl HostFileSystem {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            mount_point: None,
+        }
+    }
+
+    /// Host directory this driver exposes.
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// Map a virtual path to a host path inside the root.
+    fn resolve(&self, path: &str) -> Result<PathBuf> {
+        let mount_point = self.mount_point.as_deref().ok_or_else(|| {
+            LuCastraError::FilesystemError("Host filesystem is not mounted".to_string())
+        })?;
+        let relative = path
+            .strip_prefix(mount_point)
+            .filter(|rest| rest.is_empty() || rest.starts_with('/'))
+            .ok_or_else(|| {
+                LuCastraError::FilesystemError(format!("{} is not under {}", path, mount_point))
+            })?;
+
+        let mut host_path = self.root.clone();
+        for component in Path::new(relative).components() {
+            match component {
+                Component::Normal(part) => host_path.push(part),
+                Component::RootDir | Component::CurDir => {}
+                Component::ParentDir | Component::Prefix(_) => {
+                    return Err(escape_error(path));
+                }
+            }
+        }
+
+        // Symlinks are only caught once resolved, so check the real location
+        // of the deepest part that exists. symlink_metadata so a dangling
+        // symlink counts as existing and then fails to canonicalize, rather
+        // than being written through to wherever it points.
+        let root = fs::canonicalize(&self.root).map_err(|e| io_error(&self.root, e))?;
+        let mut existing = host_path.as_path();
+        while fs::symlink_metadata(existing).is_err() {
+            existing = existing.parent().ok_or_else(|| escape_error(path))?;
+        }
+        let real = fs::canonicalize(existing).map_err(|e| {
+            LuCastraError::FilesystemError(format!("{}: {}", existing.display(), e))
+        })?;
+        if !real.starts_with(&root) {
+            return Err(escape_error(path));
+        }
+
+        Ok(host_path)
+    }
+}
+
+fn host_metadata(metadata: &fs::Metadata) -> FileMetadata {
+    FileMetadata {
+        size: if metadata.is_dir() { 0 } else { metadata.len() },
+        is_dir: metadata.is_dir(),
+        modified: metadata.modified().ok(),
+    }
+}
+
+fn escape_error(path: &str) -> LuCastraError {
+    LuCastraError::FilesystemError(format!("Path escapes the mount root: {}", path))
+}
+
+fn io_error(path: &Path, e: std::io::Error) -> LuCastraError {
+    if e.kind() == std::io::ErrorKind::NotFound {
+        return LuCastraError::FileNotFound(path.display().to_string());
+    }
+    LuCastraError::FilesystemError(format!("{}: {}", path.display(), e))
+}
+
+impl FileSystemDriver for HostFileSystem {
+    fn name(&self) -> &str {
+        "host"
+    }
+
+    fn is_host_backed(&self) -> bool {
+        true
+    }
+
+    fn mount(&mut self, path: &str) -> Result<()> {
+        if !self.root.is_dir() {
+            return Err(LuCastraError::FilesystemError(format!(
+                "Host directory does not exist: {}",
+                self.root.display()
+            )));
+        }
+        tracing::info!("Mounting {} at {}", self.root.display(), path);
+        self.mount_point = Some(path.trim_end_matches('/').to_string());
+        Ok(())
+    }
+
+    fn unmount(&mut self) -> Result<()> {
+        tracing::info!("Unmounting host filesystem {}", self.root.display());
+        self.mount_point = None;
+        Ok(())
+    }
+
+    fn list_files(&self, path: &str) -> Result<Vec<String>> {
+        let dir = self.resolve(path)?;
+        let base = path.trim_end_matches('/');
+
+        let mut files = Vec::new();
+        for entry in fs::read_dir(&dir).map_err(|e| io_error(&dir, e))? {
+            let entry = entry.map_err(|e| io_error(&dir, e))?;
+            files.push(format!("{}/{}", base, entry.file_name().to_string_lossy()));
+        }
+        files.sort();
+        Ok(files)
+    }
+
+    fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let host_path = self.resolve(path)?;
+        fs::read(&host_path).map_err(|e| io_error(&host_path, e))
+    }
+
+    /// Reads only the requested bytes, so a slice of a huge file stays cheap.
+    fn read_range(&self, path: &str, offset: u64, len: usize) -> Result<Vec<u8>> {
+        let host_path = self.resolve(path)?;
+        let mut file = fs::File::open(&host_path).map_err(|e| io_error(&host_path, e))?;
+        let size = file.metadata().map_err(|e| io_error(&host_path, e))?.len();
+        let mut data = Vec::new();
+        file.seek(SeekFrom::Start(offset.min(size)))
+            .and_then(|_| file.take(len as u64).read_to_end(&mut data))
+            .map_err(|e| io_error(&host_path, e))?;
+        Ok(data)
+    }
+
+    fn write_file(&mut self, path: &str, data: &[u8]) -> Result<()> {
+        let host_path = self.resolve(path)?;
+        fs::write(&host_path, data).map_err(|e| io_error(&host_path, e))
+    }
+
+    fn delete_file(&mut self, path: &str) -> Result<()> {
+        let host_path = self.resolve(path)?;
+        fs::remove_file(&host_path).map_err(|e| io_error(&host_path, e))
+    }
+
+    fn create_dir(&mut self, path: &str) -> Result<()> {
+        let host_path = self.resolve(path)?;
+        fs::create_dir(&host_path).map_err(|e| io_error(&host_path, e))
+    }
+
+    fn stat(&self, path: &str) -> Result<FileMetadata> {
+        let host_path = self.resolve(path)?;
+        let metadata = fs::metadata(&host_path).map_err(|e| io_error(&host_path, e))?;
+        Ok(host_metadata(&metadata))
+    }
+
+    fn list_entries(&self, path: &str) -> Result<Vec<(String, FileMetadata)>> {
+        let dir = self.resolve(path)?;
+        let base = path.trim_end_matches('/');
+
+        let mut entries = Vec::new();
+        for entry in fs::read_dir(&dir).map_err(|e| io_error(&dir, e))? {
+            let entry = entry.map_err(|e| io_error(&dir, e))?;
+            let virtual_path = format!("{}/{}", base, entry.file_name().to_string_lossy());
+            // Resolving again rejects symlinks that leave the root
+            if let Ok(metadata) = self.stat(&virtual_path) {
+                entries.push((virtual_path, metadata));
+            }
+        }
+        entries.sort_by(|a, b| a.0.cmp(&b.0));
+        Ok(entries)
+    }
+
+    fn is_mounted(&self) -> bool {
+        self.mount_point.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn mounted(root: &Path) -> HostFileSystem {
+        let mut driver = HostFileSystem::new(root);
+        driver.mount("/mnt/host").unwrap();
+        driver
+    }
+
+    #[test]
+    fn test_read_write_roundtrip() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut driver = mounted(temp_dir.path());
+
+        driver.write_file("/mnt/host/notes.txt", b"hello").unwrap();
+        assert_eq!(driver.read_file("/mnt/host/notes.txt").unwrap(), b"hello");
+        assert_eq!(
+            fs::read(temp_dir.path().join("notes.txt")).unwrap(),
+            b"hello"
+        );
+
+        driver.create_dir("/mnt/host/docs").unwrap();
+        assert!(temp_dir.path().join("docs").is_dir());
+
+        driver.delete_file("/mnt/host/notes.txt").unwrap();
+        assert!(!temp_dir.path().join("notes.txt").exists());
+        assert!(driver.delete_file("/mnt/host/notes.txt").is_err());
+    }
+
+    #[test]
+    fn test_read_range_at_end_of_file() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut host = mounted(temp_dir.path());
+        host.write_file("/mnt/host/digits.txt", b"0123456789")
+            .unwrap();
+        let mut mock = MockFileSystem::new();
+        mock.write_file("/mnt/host/digits.txt", b"0123456789")
+            .unwrap();
+        let default = FullReadOnly(&mock);
+
+        let drivers: [&dyn FileSystemDriver; 3] = [&host, &mock, &default];
+        for driver in drivers {
+            let read = |offset, len| driver.read_range("/mnt/host/digits.txt", offset, len);
+            assert_eq!(read(0, 4).unwrap(), b"0123");
+            assert_eq!(read(6, 4).unwrap(), b"6789");
+            assert_eq!(read(8, 100).unwrap(), b"89");
+            assert_eq!(read(10, 4).unwrap(), b"");
+            assert_eq!(read(u64::MAX, usize::MAX).unwrap(), b"");
+            assert_eq!(read(3, 0).unwrap(), b"");
+            assert!(driver.read_range("/mnt/host/missing", 0, 1).is_err());
+        }
+    }
+
+    /// Driver relying on the default `read_range`.
+    struct FullReadOnly<'a>(&'a MockFileSystem);
+
+    impl FileSystemDriver for FullReadOnly<'_> {
+        fn mount(&mut self, _path: &str) -> Result<()> {
+            Ok(())
+        }
+        fn unmount(&mut self) -> Result<()> {
+            Ok(())
+        }
+        fn list_files(&self, path: &str) -> Result<Vec<String>> {
+            self.0.list_files(path)
+        }
+        fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+            self.0.read_file(path)
+        }
+        fn write_file(&mut self, _path: &str, _data: &[u8]) -> Result<()> {
+            Ok(())
+        }
+        fn is_mounted(&self) -> bool {
+            true
+        }
+    }
+
+    #[test]
+    fn test_rejects_parent_dir_escape() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut driver = mounted(temp_dir.path());
+
+        assert!(driver.read_file("/mnt/host/../etc/passwd").is_err());
+        assert!(driver.write_file("/mnt/host/a/../../x", b"x").is_err());
+        assert!(driver.read_file("/mnt/hostile/file").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rejects_symlink_outside_root() {
+        let outside = tempfile::tempdir().unwrap();
+        fs::write(outside.path().join("secret.txt"), b"secret").unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(outside.path(), temp_dir.path().join("link")).unwrap();
+        std::os::unix::fs::symlink(
+            outside.path().join("secret.txt"),
+            temp_dir.path().join("secret.txt"),
+        )
+        .unwrap();
+        let mut driver = mounted(temp_dir.path());
+
+        assert!(driver.read_file("/mnt/host/link/secret.txt").is_err());
+        assert!(driver.read_file("/mnt/host/secret.txt").is_err());
+        assert!(driver.write_file("/mnt/host/link/new.txt", b"x").is_err());
+        assert!(driver.list_files("/mnt/host/link").is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_rejects_dangling_symlink_outside_root() {
+        let outside = tempfile::tempdir().unwrap();
+        let target = outside.path().join("planted.txt");
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::os::unix::fs::symlink(&target, temp_dir.path().join("dangling.txt")).unwrap();
+        let mut driver = mounted(temp_dir.path());
+
+        assert!(driver.write_file("/mnt/host/dangling.txt", b"x").is_err());
+        assert!(!target.exists());
+        assert!(driver.write_file("/mnt/host/fresh.txt", b"x").is_ok());
+    }
+
+    #[test]
+    fn test_host_stat_and_entries() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        fs::create_dir(temp_dir.path().join("docs")).unwrap();
+        fs::write(temp_dir.path().join("a.txt"), b"12345").unwrap();
+        let driver = mounted(temp_dir.path());
+
+        let entries = driver.list_entries("/mnt/host").unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].0, "/mnt/host/a.txt");
+        assert_eq!(entries[0].1.size, 5);
+        assert!(entries[0].1.modified.is_some());
+        assert!(entries[1].1.is_dir);
+        assert!(driver.stat("/mnt/host/docs").unwrap().is_dir);
+    }
+
+    #[test]
+    fn test_mock_lists_children_with_metadata() {
+        let mut mock = MockFileSystem::new();
+        mock.write_file("/mnt/root/a.txt", b"abc").unwrap();
+        mock.write_file("/mnt/root/docs/b.md", b"b").unwrap();
+        mock.set_metadata("/mnt/root/empty", FileMetadata::dir());
+
+        let entries = mock.list_entries("/mnt/root").unwrap();
+        let summary: Vec<(&str, bool, u64)> = entries
+            .iter()
+            .map(|(p, m)| (p.as_str(), m.is_dir, m.size))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("/mnt/root/a.txt", false, 3),
+                ("/mnt/root/docs", true, 0),
+                ("/mnt/root/empty", true, 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_list_large_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        for i in 0..1000 {
+            fs::write(temp_dir.path().join(format!("file{:04}.txt", i)), b"").unwrap();
+        }
+        let driver = mounted(temp_dir.path());
+
+        let files = driver.list_files("/mnt/host").unwrap();
+        assert_eq!(files.len(), 1000);
+        assert_eq!(files[0], "/mnt/host/file0000.txt");
+        assert_eq!(files[999], "/mnt/host/file0999.txt");
+    }
+}
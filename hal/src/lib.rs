@@ -1,10 +1,12 @@
 pub mod block;
 pub mod filesystem;
 pub mod input;
+pub mod removable;
 
-pub use block::BlockDevice;
-pub use filesystem::FileSystemDriver;
+pub use block::{BlockDevice, FileBlockDevice, MockBlockDevice};
+pub use filesystem::{FileMetadata, FileSystemDriver, HostFileSystem};
 pub use input::InputDriver;
+pub use removable::{RemovableDevice, RemovableMediaDetector, SystemRemovableMedia};
 
 use lucastra_core::Result;
 
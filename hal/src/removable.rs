@@ -0,0 +1,476 @@
+//! Removable media (USB stick, SD card) detection.
+//!
+//! [`SystemRemovableMedia`] asks the OS: sysfs on Linux, `GetDriveTypeW` on
+//! Windows and mount flags on macOS. Callers hold a
+//! `dyn RemovableMediaDetector` so tests can substitute a fake.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A removable block device or volume currently attached.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RemovableDevice {
+    /// Device path, e.g. `/dev/sdb1` or `E:\`
+    pub device: String,
+    pub name: String,
+    pub size_bytes: Option<u64>,
+    pub mount_point: Option<PathBuf>,
+}
+
+/// Decides whether paths live on removable media and lists such devices.
+pub trait RemovableMediaDetector: Send + Sync {
+    /// Whether `path` (which need not exist yet) is on removable media.
+    fn is_removable(&self, path: &Path) -> bool;
+
+    /// Removable devices currently attached.
+    fn removable_devices(&self) -> Vec<RemovableDevice>;
+}
+
+/// Detector backed by the running OS.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemRemovableMedia;
+
+#[cfg(target_os = "linux")]
+impl RemovableMediaDetector for SystemRemovableMedia {
+    fn is_removable(&self, path: &Path) -> bool {
+        SysfsDetector::system().is_removable(path)
+    }
+
+    fn removable_devices(&self) -> Vec<RemovableDevice> {
+        SysfsDetector::system().removable_devices()
+    }
+}
+
+#[cfg(windows)]
+impl RemovableMediaDetector for SystemRemovableMedia {
+    fn is_removable(&self, path: &Path) -> bool {
+        DriveLetterDetector::new(windows_drive_kind).is_removable(path)
+    }
+
+    fn removable_devices(&self) -> Vec<RemovableDevice> {
+        DriveLetterDetector::new(windows_drive_kind).removable_devices()
+    }
+}
+
+#[cfg(target_os = "macos")]
+impl RemovableMediaDetector for SystemRemovableMedia {
+    fn is_removable(&self, path: &Path) -> bool {
+        macos::mount_flags(&existing_ancestor(path))
+            .is_some_and(|flags| flags & macos::MNT_REMOVABLE != 0)
+    }
+
+    fn removable_devices(&self) -> Vec<RemovableDevice> {
+        macos::removable_volumes()
+    }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos", windows)))]
+impl RemovableMediaDetector for SystemRemovableMedia {
+    fn is_removable(&self, _path: &Path) -> bool {
+        false
+    }
+
+    fn removable_devices(&self) -> Vec<RemovableDevice> {
+        Vec::new()
+    }
+}
+
+/// Closest ancestor of `path` (or `path` itself) that exists.
+fn existing_ancestor(path: &Path) -> PathBuf {
+    let mut current = path;
+    loop {
+        if let Ok(real) = fs::canonicalize(current) {
+            return real;
+        }
+        match current.parent() {
+            Some(parent) => current = parent,
+            None => return path.to_path_buf(),
+        }
+    }
+}
+
+/// Linux detector that reads `/sys/block` and `/proc/mounts`.
+///
+/// A disk counts as removable when its `removable` attribute is `1` or it
+/// sits on a USB bus (USB hard drives often report `removable=0`).
+#[derive(Debug, Clone)]
+pub struct SysfsDetector {
+    sys_block: PathBuf,
+    mounts: PathBuf,
+}
+
+impl SysfsDetector {
+    pub fn new(sys_block: impl Into<PathBuf>, mounts: impl Into<PathBuf>) -> Self {
+        Self {
+            sys_block: sys_block.into(),
+            mounts: mounts.into(),
+        }
+    }
+
+    pub fn system() -> Self {
+        Self::new("/sys/block", "/proc/mounts")
+    }
+
+    /// `(device, mount point)` pairs from the mounts table.
+    fn mount_table(&self) -> Vec<(String, PathBuf)> {
+        let Ok(contents) = fs::read_to_string(&self.mounts) else {
+            return Vec::new();
+        };
+        contents
+            .lines()
+            .filter_map(|line| {
+                let mut fields = line.split_whitespace();
+                let device = fields.next()?;
+                let mount_point = fields.next()?.replace("\\040", " ");
+                Some((device.to_string(), PathBuf::from(mount_point)))
+            })
+            .collect()
+    }
+
+    /// Disk names under `/sys/block`, e.g. `sda`, `nvme0n1`.
+    fn disks(&self) -> Vec<String> {
+        let Ok(entries) = fs::read_dir(&self.sys_block) else {
+            return Vec::new();
+        };
+        let mut disks: Vec<String> = entries
+            .filter_map(|e| e.ok())
+            .map(|e| e.file_name().to_string_lossy().into_owned())
+            .collect();
+        disks.sort();
+        disks
+    }
+
+    /// Disk that a device node like `/dev/sdb1` or `/dev/nvme0n1p2` belongs to.
+    fn disk_for_device(&self, device: &str) -> Option<String> {
+        let node = fs::canonicalize(device)
+            .map(|p| p.to_string_lossy().into_owned())
+            .unwrap_or_else(|_| device.to_string());
+        let node = node.strip_prefix("/dev/")?;
+
+        self.disks()
+            .into_iter()
+            .filter(|disk| {
+                node.strip_prefix(disk.as_str()).is_some_and(|rest| {
+                    let digits = rest.strip_prefix('p').unwrap_or(rest);
+                    rest.is_empty()
+                        || (!digits.is_empty() && digits.chars().all(|c| c.is_ascii_digit()))
+                })
+            })
+            .max_by_key(|disk| disk.len())
+    }
+
+    fn disk_is_removable(&self, disk: &str) -> bool {
+        let dir = self.sys_block.join(disk);
+        let flagged = fs::read_to_string(dir.join("removable"))
+            .map(|v| v.trim() == "1")
+            .unwrap_or(false);
+        let on_usb = fs::canonicalize(&dir)
+            .map(|real| real.to_string_lossy().contains("/usb"))
+            .unwrap_or(false);
+        flagged || on_usb
+    }
+
+    fn read_size(dir: &Path) -> Option<u64> {
+        let sectors: u64 = fs::read_to_string(dir.join("size"))
+            .ok()?
+            .trim()
+            .parse()
+            .ok()?;
+        Some(sectors * 512)
+    }
+}
+
+impl RemovableMediaDetector for SysfsDetector {
+    fn is_removable(&self, path: &Path) -> bool {
+        let path = existing_ancestor(path);
+        self.mount_table()
+            .into_iter()
+            .filter(|(_, mount_point)| path.starts_with(mount_point))
+            .max_by_key(|(_, mount_point)| mount_point.components().count())
+            .and_then(|(device, _)| self.disk_for_device(&device))
+            .is_some_and(|disk| self.disk_is_removable(&disk))
+    }
+
+    fn removable_devices(&self) -> Vec<RemovableDevice> {
+        let mounts = self.mount_table();
+        let mount_point_of = |node: &str| {
+            mounts
+                .iter()
+                .find(|(device, _)| {
+                    device == node
+                        || fs::canonicalize(device).is_ok_and(|real| real == Path::new(node))
+                })
+                .map(|(_, mount_point)| mount_point.clone())
+        };
+
+        let mut devices = Vec::new();
+        for disk in self.disks() {
+            let dir = self.sys_block.join(&disk);
+            if !self.disk_is_removable(&disk) || Self::read_size(&dir).unwrap_or(0) == 0 {
+                continue;
+            }
+
+            let name = fs::read_to_string(dir.join("device").join("model"))
+                .map(|model| model.trim().to_string())
+                .ok()
+                .filter(|model| !model.is_empty())
+                .unwrap_or_else(|| "Removable Storage".to_string());
+
+            // Report partitions when the disk has any, otherwise the disk itself
+            let mut partitions: Vec<String> = fs::read_dir(&dir)
+                .map(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.file_name().to_string_lossy().into_owned())
+                        .filter(|entry| entry.starts_with(&disk) && entry != &disk)
+                        .collect()
+                })
+                .unwrap_or_default();
+            partitions.sort();
+            if partitions.is_empty() {
+                partitions.push(String::new());
+            }
+
+            for partition in partitions {
+                let (node, size_dir) = if partition.is_empty() {
+                    (format!("/dev/{}", disk), dir.clone())
+                } else {
+                    (format!("/dev/{}", partition), dir.join(&partition))
+                };
+                devices.push(RemovableDevice {
+                    mount_point: mount_point_of(&node),
+                    device: node,
+                    name: name.clone(),
+                    size_bytes: Self::read_size(&size_dir),
+                });
+            }
+        }
+        devices
+    }
+}
+
+/// Kind of a drive letter, as reported by `GetDriveTypeW`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DriveKind {
+    Removable,
+    Fixed,
+    Remote,
+    CdRom,
+    RamDisk,
+    Unknown,
+}
+
+/// Drive-letter detector. The lookup maps a root like `E:\` to its kind, so
+/// classification follows the drive type rather than the letter.
+pub struct DriveLetterDetector<F> {
+    lookup: F,
+}
+
+impl<F: Fn(&str) -> DriveKind + Send + Sync> DriveLetterDetector<F> {
+    pub fn new(lookup: F) -> Self {
+        Self { lookup }
+    }
+
+    /// `E:\` for paths like `E:\docs` or `e:/docs`.
+    fn drive_root(path: &Path) -> Option<String> {
+        let path = path.to_string_lossy();
+        let mut chars = path.chars();
+        let letter = chars.next()?;
+        (letter.is_ascii_alphabetic() && chars.next() == Some(':'))
+            .then(|| format!("{}:\\", letter.to_ascii_uppercase()))
+    }
+}
+
+impl<F: Fn(&str) -> DriveKind + Send + Sync> RemovableMediaDetector for DriveLetterDetector<F> {
+    fn is_removable(&self, path: &Path) -> bool {
+        Self::drive_root(path).is_some_and(|root| (self.lookup)(&root) == DriveKind::Removable)
+    }
+
+    fn removable_devices(&self) -> Vec<RemovableDevice> {
+        ('A'..='Z')
+            .map(|letter| format!("{}:\\", letter))
+            .filter(|root| (self.lookup)(root) == DriveKind::Removable)
+            .map(|root| RemovableDevice {
+                name: format!("Removable Drive ({})", &root[..2]),
+                device: root.clone(),
+                size_bytes: None,
+                mount_point: Some(PathBuf::from(root)),
+            })
+            .collect()
+    }
+}
+
+#[cfg(windows)]
+fn windows_drive_kind(root: &str) -> DriveKind {
+    use windows::core::PCWSTR;
+    use windows::Win32::Storage::FileSystem::GetDriveTypeW;
+
+    let wide: Vec<u16> = root.encode_utf16().chain(std::iter::once(0)).collect();
+    // SAFETY: `wide` is a NUL-terminated UTF-16 string that outlives the call
+    match unsafe { GetDriveTypeW(PCWSTR::from_raw(wide.as_ptr())) } {
+        2 => DriveKind::Removable,
+        3 => DriveKind::Fixed,
+        4 => DriveKind::Remote,
+        5 => DriveKind::CdRom,
+        6 => DriveKind::RamDisk,
+        _ => DriveKind::Unknown,
+    }
+}
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use super::RemovableDevice;
+    use std::ffi::{CStr, CString};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Path, PathBuf};
+
+    /// `MNT_REMOVABLE` from `<sys/mount.h>`.
+    pub const MNT_REMOVABLE: u32 = 0x0000_0200;
+
+    fn statfs(path: &Path) -> Option<libc::statfs> {
+        let c_path = CString::new(path.as_os_str().as_bytes()).ok()?;
+        // SAFETY: statfs only writes into the zeroed struct we pass
+        let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+        (unsafe { libc::statfs(c_path.as_ptr(), &mut stat) } == 0).then_some(stat)
+    }
+
+    pub fn mount_flags(path: &Path) -> Option<u32> {
+        statfs(path).map(|stat| stat.f_flags)
+    }
+
+    pub fn removable_volumes() -> Vec<RemovableDevice> {
+        let Ok(entries) = std::fs::read_dir("/Volumes") else {
+            return Vec::new();
+        };
+        entries
+            .filter_map(|e| e.ok())
+            .filter_map(|entry| {
+                let path = entry.path();
+                let stat = statfs(&path)?;
+                if stat.f_flags & MNT_REMOVABLE == 0 {
+                    return None;
+                }
+                // SAFETY: f_mntfromname is a NUL-terminated C string
+                let device = unsafe { CStr::from_ptr(stat.f_mntfromname.as_ptr()) }
+                    .to_string_lossy()
+                    .into_owned();
+                Some(RemovableDevice {
+                    device,
+                    name: entry.file_name().to_string_lossy().into_owned(),
+                    size_bytes: Some(stat.f_blocks * stat.f_bsize as u64),
+                    mount_point: Some(PathBuf::from(path)),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_primary_drive_e_is_not_removable() {
+        // A machine whose system drive is E: and whose USB stick is F:
+        let detector = DriveLetterDetector::new(|root: &str| match root {
+            "E:\\" => DriveKind::Fixed,
+            "F:\\" => DriveKind::Removable,
+            _ => DriveKind::Unknown,
+        });
+
+        assert!(!detector.is_removable(Path::new("E:\\Users\\me\\report.txt")));
+        assert!(detector.is_removable(Path::new("f:/photos/cat.jpg")));
+        assert!(!detector.is_removable(Path::new("/home/me/file.txt")));
+
+        let devices = detector.removable_devices();
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].device, "F:\\");
+    }
+
+    fn fake_sysfs() -> (tempfile::TempDir, SysfsDetector) {
+        let root = tempfile::tempdir().unwrap();
+        let sys_block = root.path().join("block");
+
+        let internal = sys_block.join("sda");
+        fs::create_dir_all(internal.join("sda1")).unwrap();
+        fs::write(internal.join("removable"), "0\n").unwrap();
+        fs::write(internal.join("size"), "1000000\n").unwrap();
+        fs::write(internal.join("sda1").join("size"), "999000\n").unwrap();
+
+        let stick = sys_block.join("sdb");
+        fs::create_dir_all(stick.join("sdb1")).unwrap();
+        fs::create_dir_all(stick.join("device")).unwrap();
+        fs::write(stick.join("removable"), "1\n").unwrap();
+        fs::write(stick.join("size"), "2048\n").unwrap();
+        fs::write(stick.join("sdb1").join("size"), "2000\n").unwrap();
+        fs::write(stick.join("device").join("model"), "Cruzer Blade \n").unwrap();
+
+        let media = root.path().join("media");
+        fs::create_dir_all(media.join("usb stick")).unwrap();
+        let mounts = root.path().join("mounts");
+        fs::write(
+            &mounts,
+            format!(
+                "/dev/sda1 / ext4 rw 0 0\n/dev/sdb1 {} vfat rw 0 0\n",
+                media
+                    .join("usb stick")
+                    .display()
+                    .to_string()
+                    .replace(' ', "\\040")
+            ),
+        )
+        .unwrap();
+
+        let detector = SysfsDetector::new(sys_block, mounts);
+        (root, detector)
+    }
+
+    #[test]
+    fn test_sysfs_classifies_by_backing_disk() {
+        let (root, detector) = fake_sysfs();
+        let media = fs::canonicalize(root.path().join("media")).unwrap();
+
+        assert!(detector.is_removable(&media.join("usb stick").join("new").join("a.txt")));
+        assert!(!detector.is_removable(&media.join("elsewhere.txt")));
+        assert!(!detector.is_removable(Path::new("/etc/hosts")));
+    }
+
+    #[test]
+    fn test_sysfs_lists_removable_partitions() {
+        let (_root, detector) = fake_sysfs();
+        let devices = detector.removable_devices();
+
+        assert_eq!(devices.len(), 1);
+        assert_eq!(devices[0].device, "/dev/sdb1");
+        assert_eq!(devices[0].name, "Cruzer Blade");
+        assert_eq!(devices[0].size_bytes, Some(2000 * 512));
+        assert!(devices[0]
+            .mount_point
+            .as_ref()
+            .is_some_and(|p| p.ends_with("usb stick")));
+    }
+
+    #[test]
+    fn test_disk_for_device_handles_partition_suffixes() {
+        let root = tempfile::tempdir().unwrap();
+        for disk in ["sda", "nvme0n1", "mmcblk0"] {
+            fs::create_dir_all(root.path().join(disk)).unwrap();
+        }
+        let detector = SysfsDetector::new(root.path(), root.path().join("mounts"));
+
+        assert_eq!(
+            detector.disk_for_device("/dev/sda2").as_deref(),
+            Some("sda")
+        );
+        assert_eq!(
+            detector.disk_for_device("/dev/nvme0n1p3").as_deref(),
+            Some("nvme0n1")
+        );
+        assert_eq!(
+            detector.disk_for_device("/dev/mmcblk0p1").as_deref(),
+            Some("mmcblk0")
+        );
+        assert_eq!(detector.disk_for_device("/dev/sdab1"), None);
+        assert_eq!(detector.disk_for_device("tmpfs"), None);
+    }
+}
@@ -0,0 +1,401 @@
+//! Translated user-facing strings for the GUI and CLI.
+//!
+//! Each language is a TOML bundle compiled into the binary, mapping dotted
+//! keys such as `gui.settings_saved` to templates. Placeholders name their
+//! argument (`"Transcript saved to {path}"`), so a translation can put them
+//! in any order; `{{` and `}}` are literal braces. A key missing from a
+//! bundle falls back to English, with a warning logged once per key.
+//!
+//! Strings are looked up in the [current](current) locale with [`t!`]:
+//!
+//! ```
+//! use lucastra_i18n::t;
+//!
+//! assert_eq!(t!("cli.goodbye"), "Goodbye! 👋");
+//! assert_eq!(t!("gui.transcript_saved", path = "/tmp/chat.md"), "Transcript saved to /tmp/chat.md");
+//! ```
+
+use chrono::{DateTime, Local, TimeZone};
+use std::collections::{BTreeSet, HashMap};
+use std::fmt::{self, Display};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
+
+/// Language used when no bundle matches, and for keys a bundle lacks.
+pub const DEFAULT_LANGUAGE: &str = "en";
+
+/// Bundles by language code.
+const BUNDLES: [(&str, &str); 2] = [
+    ("en", include_str!("../locales/en.toml")),
+    ("de", include_str!("../locales/de.toml")),
+];
+
+/// The locale [`t!`] translates with; English until [`set_current`].
+static CURRENT: RwLock<Option<Arc<Locale>>> = RwLock::new(None);
+
+/// Keys already reported missing, so each is logged once.
+static MISSING: Mutex<BTreeSet<String>> = Mutex::new(BTreeSet::new());
+
+/// Templates by dotted key.
+type Messages = HashMap<String, String>;
+
+/// Strings and number and date formats for one language.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Locale {
+    language: &'static str,
+    messages: Arc<Messages>,
+}
+
+impl Default for Locale {
+    fn default() -> Self {
+        Self::new(DEFAULT_LANGUAGE)
+    }
+}
+
+impl Locale {
+    /// The bundle for `language`, e.g. `de` or `de-AT`; English when there
+    /// is none.
+    pub fn new(language: &str) -> Self {
+        let primary = language
+            .split(['-', '_', '.'])
+            .next()
+            .unwrap_or_default()
+            .to_ascii_lowercase();
+        match BUNDLES.iter().find(|(code, _)| *code == primary) {
+            Some((code, _)) if *code == DEFAULT_LANGUAGE => Self::english(),
+            Some((code, bundle)) => Self {
+                language: code,
+                messages: Arc::new(parse_bundle(code, bundle)),
+            },
+            None => {
+                tracing::warn!("No translation for language {:?}; using English", language);
+                Self::english()
+            }
+        }
+    }
+
+    /// Locale for a `gui.language` setting: the language it names, or the
+    /// OS locale for `auto`.
+    pub fn resolve(setting: &str) -> Self {
+        let setting = setting.trim();
+        if !setting.is_empty() && !setting.eq_ignore_ascii_case("auto") {
+            return Self::new(setting);
+        }
+        match sys_locale::get_locale() {
+            Some(os) => Self::new(&os),
+            None => Self::english(),
+        }
+    }
+
+    fn english() -> Self {
+        Self {
+            language: DEFAULT_LANGUAGE,
+            messages: english_messages().clone(),
+        }
+    }
+
+    /// Language code of the bundle in use, e.g. `en`.
+    pub fn language(&self) -> &'static str {
+        self.language
+    }
+
+    /// The template for `key` with each `{name}` replaced by the argument
+    /// called `name`.
+    pub fn text(&self, key: &str, args: &[(&str, &dyn Display)]) -> String {
+        let template = self.messages.get(key).or_else(|| {
+            report_missing(self.language, key);
+            english_messages().get(key)
+        });
+        match template {
+            Some(template) => interpolate(template, args),
+            None => key.to_string(),
+        }
+    }
+
+    /// `value` with `decimals` digits after the separator and thousands
+    /// grouped, e.g. `1,234.5` in English and `1.234,5` in German.
+    pub fn number(&self, value: f64, decimals: usize) -> String {
+        let formatted = format!("{:.*}", decimals, value.abs());
+        let (whole, fraction) = formatted
+            .split_once('.')
+            .map_or((formatted.as_str(), None), |(whole, fraction)| {
+                (whole, Some(fraction))
+            });
+        let mut number = String::new();
+        if value.is_sign_negative() && formatted.chars().any(|c| c.is_ascii_digit() && c != '0') {
+            number.push('-');
+        }
+        number.push_str(&self.group(whole));
+        if let Some(fraction) = fraction {
+            number.push_str(self.format("format.decimal"));
+            number.push_str(fraction);
+        }
+        number
+    }
+
+    /// `value` with thousands grouped.
+    pub fn integer(&self, value: u64) -> String {
+        self.group(&value.to_string())
+    }
+
+    /// Unix time `seconds` as a local date and time, e.g. `2026-03-14 09:26`.
+    pub fn datetime(&self, seconds: i64) -> String {
+        match Local.timestamp_opt(seconds, 0).single() {
+            Some(time) => self.format_datetime(&time),
+            None => seconds.to_string(),
+        }
+    }
+
+    /// `time` in this language's date format, in its own time zone.
+    pub fn format_datetime<Tz: TimeZone>(&self, time: &DateTime<Tz>) -> String
+    where
+        Tz::Offset: Display,
+    {
+        time.format(self.format("format.datetime")).to_string()
+    }
+
+    fn group(&self, digits: &str) -> String {
+        let separator = self.format("format.group");
+        let mut grouped = String::with_capacity(digits.len() + digits.len() / 3);
+        for (i, digit) in digits.chars().enumerate() {
+            if i > 0 && (digits.len() - i).is_multiple_of(3) {
+                grouped.push_str(separator);
+            }
+            grouped.push(digit);
+        }
+        grouped
+    }
+
+    fn format(&self, key: &str) -> &str {
+        self.messages
+            .get(key)
+            .or_else(|| english_messages().get(key))
+            .map_or("", String::as_str)
+    }
+}
+
+/// Languages with a bundle.
+pub fn languages() -> impl Iterator<Item = &'static str> {
+    BUNDLES.iter().map(|(code, _)| *code)
+}
+
+/// Translate with `locale` from now on.
+pub fn set_current(locale: Locale) {
+    *CURRENT
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = Some(Arc::new(locale));
+}
+
+/// The locale set with [`set_current`], or English.
+pub fn current() -> Arc<Locale> {
+    let current = CURRENT
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone();
+    current.unwrap_or_else(|| Arc::new(Locale::english()))
+}
+
+/// The current locale's text for a key, with named arguments:
+/// `t!("gui.transcript_saved", path = path.display())`.
+#[macro_export]
+macro_rules! t {
+    ($key:expr) => {
+        $crate::current().text($key, &[])
+    };
+    ($key:expr, $($name:ident = $value:expr),+ $(,)?) => {
+        $crate::current().text(
+            $key,
+            &[$((stringify!($name), &$value as &dyn ::std::fmt::Display)),+],
+        )
+    };
+}
+
+fn english_messages() -> &'static Arc<Messages> {
+    static ENGLISH: OnceLock<Arc<Messages>> = OnceLock::new();
+    ENGLISH.get_or_init(|| Arc::new(parse_bundle(DEFAULT_LANGUAGE, BUNDLES[0].1)))
+}
+
+/// Flatten the tables of a bundle into dotted keys.
+fn parse_bundle(language: &str, bundle: &str) -> Messages {
+    fn flatten(prefix: &str, table: toml::Table, messages: &mut Messages) {
+        for (name, value) in table {
+            let key = if prefix.is_empty() {
+                name
+            } else {
+                format!("{}.{}", prefix, name)
+            };
+            match value {
+                toml::Value::String(template) => {
+                    messages.insert(key, template);
+                }
+                toml::Value::Table(table) => flatten(&key, table, messages),
+                other => tracing::warn!("Ignoring {} = {} in bundle: not text", key, other),
+            }
+        }
+    }
+
+    let mut messages = Messages::new();
+    match bundle.parse::<toml::Table>() {
+        Ok(table) => flatten("", table, &mut messages),
+        Err(e) => tracing::error!("Translation bundle {} is invalid: {}", language, e),
+    }
+    messages
+}
+
+fn report_missing(language: &str, key: &str) {
+    let mut missing = MISSING
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner());
+    if missing.insert(format!("{}:{}", language, key)) {
+        tracing::warn!("No {} translation for {}; using English", language, key);
+    }
+}
+
+/// `template` with each `{name}` replaced by its argument. Placeholders
+/// without an argument are kept as written.
+fn interpolate(template: &str, args: &[(&str, &dyn Display)]) -> String {
+    let mut text = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(['{', '}']) {
+        text.push_str(&rest[..start]);
+        let brace = &rest[start..];
+        if brace.starts_with("{{") || brace.starts_with("}}") {
+            text.push_str(&brace[..1]);
+            rest = &brace[2..];
+            continue;
+        }
+        let placeholder = brace
+            .strip_prefix('{')
+            .and_then(|inner| inner.split_once('}'))
+            .and_then(|(name, after)| {
+                let (_, value) = args.iter().find(|(arg, _)| *arg == name)?;
+                Some((value, after))
+            });
+        match placeholder {
+            Some((value, after)) => {
+                text.push_str(&value.to_string());
+                rest = after;
+            }
+            None => {
+                text.push_str(&brace[..1]);
+                rest = &brace[1..];
+            }
+        }
+    }
+    text.push_str(rest);
+    text
+}
+
+impl Display for Locale {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.language)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    /// Placeholder names in `template`.
+    fn placeholders(template: &str) -> BTreeSet<String> {
+        template
+            .replace("{{", "")
+            .split('{')
+            .skip(1)
+            .filter_map(|part| part.split_once('}').map(|(name, _)| name.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_every_bundle_translates_every_english_key() {
+        let english = english_messages();
+        for (language, bundle) in BUNDLES {
+            let messages = parse_bundle(language, bundle);
+            for (key, template) in english.iter() {
+                let translated = messages
+                    .get(key)
+                    .unwrap_or_else(|| panic!("{} lacks {}", language, key));
+                if !key.starts_with("format.") {
+                    assert_eq!(
+                        placeholders(translated),
+                        placeholders(template),
+                        "{} {} has different placeholders",
+                        language,
+                        key
+                    );
+                }
+            }
+            for key in messages.keys() {
+                assert!(
+                    english.contains_key(key),
+                    "{} has unknown key {}",
+                    language,
+                    key
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_keys_used_in_the_gui_and_cli_exist() {
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("..");
+        for dir in ["gui/src", "cli/src"] {
+            for entry in std::fs::read_dir(root.join(dir)).unwrap() {
+                let path = entry.unwrap().path();
+                let source = std::fs::read_to_string(&path).unwrap();
+                for (start, _) in source.match_indices("t!(\"") {
+                    // Not the end of format!, print! and the like
+                    if source[..start].ends_with(|c: char| c.is_alphanumeric() || c == '_') {
+                        continue;
+                    }
+                    let key = source[start + 4..].split('"').next().unwrap();
+                    assert!(
+                        english_messages().contains_key(key),
+                        "{} uses unknown key {}",
+                        path.display(),
+                        key
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_missing_keys_fall_back_to_english() {
+        let partial = Locale {
+            language: "de",
+            messages: Arc::new(parse_bundle("de", "[cli]\ngoodbye = \"Tschüss!\"\n")),
+        };
+        assert_eq!(partial.text("cli.goodbye", &[]), "Tschüss!");
+        assert_eq!(
+            partial.text("gui.settings_saved", &[]),
+            english_messages()["gui.settings_saved"]
+        );
+        assert_eq!(partial.text("no.such.key", &[]), "no.such.key");
+        // Number formats fall back too
+        assert_eq!(partial.number(1234.5, 1), "1,234.5");
+
+        assert_eq!(Locale::new("de_AT.UTF-8").language(), "de");
+        assert_eq!(Locale::new("xx").language(), DEFAULT_LANGUAGE);
+        assert_eq!(Locale::resolve("DE").language(), "de");
+    }
+
+    #[test]
+    fn test_arguments_are_placed_by_name() {
+        let args: [(&str, &dyn Display); 3] = [("first", &1), ("second", &"two"), ("third", &3.5)];
+        assert_eq!(
+            interpolate("{third}, then {first} and {second}", &args),
+            "3.5, then 1 and two"
+        );
+        assert_eq!(
+            interpolate("{{literal}} {first} {missing} }", &args),
+            "{literal} 1 {missing} }"
+        );
+
+        let german = Locale::new("de");
+        assert_eq!(german.number(-1234567.891, 2), "-1.234.567,89");
+        assert_eq!(german.integer(1000), "1.000");
+        assert_eq!(Locale::default().integer(999), "999");
+        assert_eq!(Locale::default().number(-0.01, 1), "0.0");
+    }
+}
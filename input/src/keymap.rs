@@ -0,0 +1,307 @@
+//! Translation of raw key events into text and shortcuts.
+
+use lucastra_core::{InputEvent, InputEventType, KeyCode};
+use std::fmt;
+
+/// Maps keys to the characters they type.
+pub trait KeyboardLayout: Send {
+    /// Character `key` types, shifted or not; `None` for keys that don't
+    /// type anything.
+    fn char_for(&self, key: KeyCode, shift: bool) -> Option<char>;
+}
+
+/// US QWERTY layout.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UsLayout;
+
+impl KeyboardLayout for UsLayout {
+    fn char_for(&self, key: KeyCode, shift: bool) -> Option<char> {
+        use KeyCode::*;
+        let (plain, shifted) = match key {
+            A => ('a', 'A'),
+            B => ('b', 'B'),
+            C => ('c', 'C'),
+            D => ('d', 'D'),
+            E => ('e', 'E'),
+            F => ('f', 'F'),
+            G => ('g', 'G'),
+            H => ('h', 'H'),
+            I => ('i', 'I'),
+            J => ('j', 'J'),
+            K => ('k', 'K'),
+            L => ('l', 'L'),
+            M => ('m', 'M'),
+            N => ('n', 'N'),
+            O => ('o', 'O'),
+            P => ('p', 'P'),
+            Q => ('q', 'Q'),
+            R => ('r', 'R'),
+            S => ('s', 'S'),
+            T => ('t', 'T'),
+            U => ('u', 'U'),
+            V => ('v', 'V'),
+            W => ('w', 'W'),
+            X => ('x', 'X'),
+            Y => ('y', 'Y'),
+            Z => ('z', 'Z'),
+            Num0 => ('0', ')'),
+            Num1 => ('1', '!'),
+            Num2 => ('2', '@'),
+            Num3 => ('3', '#'),
+            Num4 => ('4', '$'),
+            Num5 => ('5', '%'),
+            Num6 => ('6', '^'),
+            Num7 => ('7', '&'),
+            Num8 => ('8', '*'),
+            Num9 => ('9', '('),
+            Space => (' ', ' '),
+            _ => return None,
+        };
+        Some(if shift { shifted } else { plain })
+    }
+}
+
+/// Modifier keys held down, left or right.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub ctrl: bool,
+    pub alt: bool,
+}
+
+impl fmt::Display for Modifiers {
+    /// `Ctrl+Alt+Shift`, or nothing when none are held
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (self.ctrl, "Ctrl"),
+            (self.alt, "Alt"),
+            (self.shift, "Shift"),
+        ];
+        let held: Vec<&str> = names
+            .iter()
+            .filter(|(held, _)| *held)
+            .map(|(_, name)| *name)
+            .collect();
+        write!(f, "{}", held.join("+"))
+    }
+}
+
+/// Text typed by one key press.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TextInput {
+    /// Position of the key press in the raw event stream
+    pub seq: u64,
+    pub timestamp: u64,
+    pub text: String,
+}
+
+/// A key pressed while Ctrl or Alt was held, e.g. Ctrl+C.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Shortcut {
+    /// Position of the key press in the raw event stream
+    pub seq: u64,
+    pub timestamp: u64,
+    pub key: KeyCode,
+    pub modifiers: Modifiers,
+}
+
+impl fmt::Display for Shortcut {
+    /// `Ctrl+Shift+P`
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}+{:?}", self.modifiers, self.key)
+    }
+}
+
+/// What a key event composes to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ComposedEvent {
+    Text(TextInput),
+    Shortcut(Shortcut),
+}
+
+/// Tracks modifier keys and turns key presses into [`TextInput`] and
+/// [`Shortcut`] events.
+///
+/// Presses with Ctrl or Alt held are shortcuts and type nothing. Keys
+/// without a character in the layout, such as arrows and Enter, compose to
+/// nothing; apps read them from the raw events.
+pub struct KeyMapper {
+    layout: Box<dyn KeyboardLayout>,
+    // Each side is tracked on its own so releasing one Shift while the
+    // other is held keeps Shift down
+    left: Modifiers,
+    right: Modifiers,
+}
+
+impl KeyMapper {
+    pub fn new() -> Self {
+        Self::with_layout(Box::new(UsLayout))
+    }
+
+    pub fn with_layout(layout: Box<dyn KeyboardLayout>) -> Self {
+        Self {
+            layout,
+            left: Modifiers::default(),
+            right: Modifiers::default(),
+        }
+    }
+
+    /// Modifiers currently held.
+    pub fn modifiers(&self) -> Modifiers {
+        Modifiers {
+            shift: self.left.shift || self.right.shift,
+            ctrl: self.left.ctrl || self.right.ctrl,
+            alt: self.left.alt || self.right.alt,
+        }
+    }
+
+    /// Update modifier state from `event` and return what it composes to.
+    /// `seq` is the event's position in the raw stream.
+    pub fn process(&mut self, seq: u64, event: &InputEvent) -> Option<ComposedEvent> {
+        let pressed = match event.event_type {
+            InputEventType::KeyPress => true,
+            InputEventType::KeyRelease => false,
+            _ => return None,
+        };
+        let key = event.key?;
+
+        // Releasing a key that was never pressed just leaves it up
+        let modifier = match key {
+            KeyCode::LShift => Some(&mut self.left.shift),
+            KeyCode::RShift => Some(&mut self.right.shift),
+            KeyCode::LCtrl => Some(&mut self.left.ctrl),
+            KeyCode::RCtrl => Some(&mut self.right.ctrl),
+            KeyCode::LAlt => Some(&mut self.left.alt),
+            KeyCode::RAlt => Some(&mut self.right.alt),
+            _ => None,
+        };
+        if let Some(held) = modifier {
+            *held = pressed;
+            return None;
+        }
+        if !pressed {
+            return None;
+        }
+
+        let modifiers = self.modifiers();
+        if modifiers.ctrl || modifiers.alt {
+            return Some(ComposedEvent::Shortcut(Shortcut {
+                seq,
+                timestamp: event.timestamp,
+                key,
+                modifiers,
+            }));
+        }
+        let ch = self.layout.char_for(key, modifiers.shift)?;
+        Some(ComposedEvent::Text(TextInput {
+            seq,
+            timestamp: event.timestamp,
+            text: ch.to_string(),
+        }))
+    }
+}
+
+impl Default for KeyMapper {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(event_type: InputEventType, key: KeyCode) -> InputEvent {
+        InputEvent {
+            event_type,
+            timestamp: 0,
+            key: Some(key),
+            x: None,
+            y: None,
+            pressed: Some(event_type == InputEventType::KeyPress),
+        }
+    }
+
+    fn press(mapper: &mut KeyMapper, code: KeyCode) -> Option<ComposedEvent> {
+        mapper.process(0, &key(InputEventType::KeyPress, code))
+    }
+
+    fn release(mapper: &mut KeyMapper, code: KeyCode) -> Option<ComposedEvent> {
+        mapper.process(0, &key(InputEventType::KeyRelease, code))
+    }
+
+    fn text(event: Option<ComposedEvent>) -> Option<String> {
+        match event {
+            Some(ComposedEvent::Text(input)) => Some(input.text),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn test_shift_a_types_uppercase() {
+        let mut mapper = KeyMapper::new();
+        assert_eq!(text(press(&mut mapper, KeyCode::A)).as_deref(), Some("a"));
+
+        assert!(press(&mut mapper, KeyCode::LShift).is_none());
+        assert_eq!(text(press(&mut mapper, KeyCode::A)).as_deref(), Some("A"));
+        assert_eq!(
+            text(press(&mut mapper, KeyCode::Num1)).as_deref(),
+            Some("!")
+        );
+        assert!(release(&mut mapper, KeyCode::A).is_none());
+
+        release(&mut mapper, KeyCode::LShift);
+        assert_eq!(text(press(&mut mapper, KeyCode::A)).as_deref(), Some("a"));
+        assert!(press(&mut mapper, KeyCode::Enter).is_none());
+    }
+
+    #[test]
+    fn test_ctrl_shift_p_is_a_shortcut_without_text() {
+        let mut mapper = KeyMapper::new();
+        press(&mut mapper, KeyCode::RCtrl);
+        press(&mut mapper, KeyCode::LShift);
+
+        match press(&mut mapper, KeyCode::P) {
+            Some(ComposedEvent::Shortcut(shortcut)) => {
+                assert_eq!(shortcut.key, KeyCode::P);
+                assert_eq!(shortcut.to_string(), "Ctrl+Shift+P");
+            }
+            other => panic!("expected a shortcut, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_release_without_press_leaves_modifiers_up() {
+        let mut mapper = KeyMapper::new();
+        release(&mut mapper, KeyCode::LShift);
+        release(&mut mapper, KeyCode::RCtrl);
+        assert_eq!(mapper.modifiers(), Modifiers::default());
+        assert_eq!(text(press(&mut mapper, KeyCode::B)).as_deref(), Some("b"));
+
+        // The other side's Shift is still down
+        press(&mut mapper, KeyCode::LShift);
+        press(&mut mapper, KeyCode::RShift);
+        release(&mut mapper, KeyCode::RShift);
+        release(&mut mapper, KeyCode::RShift);
+        assert!(mapper.modifiers().shift);
+        assert_eq!(text(press(&mut mapper, KeyCode::B)).as_deref(), Some("B"));
+    }
+
+    #[test]
+    fn test_custom_layout() {
+        struct Dvorak;
+        impl KeyboardLayout for Dvorak {
+            fn char_for(&self, key: KeyCode, shift: bool) -> Option<char> {
+                match (key, shift) {
+                    (KeyCode::S, false) => Some('o'),
+                    (KeyCode::S, true) => Some('O'),
+                    _ => None,
+                }
+            }
+        }
+
+        let mut mapper = KeyMapper::with_layout(Box::new(Dvorak));
+        assert_eq!(text(press(&mut mapper, KeyCode::S)).as_deref(), Some("o"));
+        assert!(press(&mut mapper, KeyCode::A).is_none());
+    }
+}
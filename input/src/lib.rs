@@ -3,10 +3,26 @@ use lucastra_hal::InputDriver;
 use std::collections::VecDeque;
 use tracing::info;
 
+pub mod keymap;
+
+pub use keymap::{
+    ComposedEvent, KeyMapper, KeyboardLayout, Modifiers, Shortcut, TextInput, UsLayout,
+};
+
 /// Input manager service: polls input devices and buffers events.
+///
+/// Besides the raw events, key presses are composed into typed text and
+/// shortcuts by a [`KeyMapper`]. Both are queued in the same
+/// [`poll_events`](Self::poll_events) call as the raw event they came from,
+/// and carry its position in the raw stream as `seq`, so a consumer can
+/// line them up with [`get_sequenced_event`](Self::get_sequenced_event).
 pub struct InputManager {
     drivers: Vec<Box<dyn InputDriver + Send>>,
-    event_queue: VecDeque<InputEvent>,
+    event_queue: VecDeque<(u64, InputEvent)>,
+    next_seq: u64,
+    key_mapper: KeyMapper,
+    text_queue: VecDeque<TextInput>,
+    shortcut_queue: VecDeque<Shortcut>,
 }
 
 impl InputManager {
@@ -14,9 +30,19 @@ impl InputManager {
         Self {
             drivers: Vec::new(),
             event_queue: VecDeque::new(),
+            next_seq: 0,
+            key_mapper: KeyMapper::new(),
+            text_queue: VecDeque::new(),
+            shortcut_queue: VecDeque::new(),
         }
     }
 
+    /// Compose text with `layout` instead of US QWERTY.
+    pub fn with_layout(mut self, layout: Box<dyn KeyboardLayout>) -> Self {
+        self.key_mapper = KeyMapper::with_layout(layout);
+        self
+    }
+
     /// Register an input driver (keyboard, mouse, etc.).
     pub fn register_driver(&mut self, driver: Box<dyn InputDriver + Send>) {
         info!("Registering input driver");
@@ -27,7 +53,16 @@ impl InputManager {
     pub fn poll_events(&mut self) -> Result<()> {
         for driver in &mut self.drivers {
             while let Some(event) = driver.poll_event()? {
-                self.event_queue.push_back(event);
+                let seq = self.next_seq;
+                self.next_seq += 1;
+                match self.key_mapper.process(seq, &event) {
+                    Some(ComposedEvent::Text(text)) => self.text_queue.push_back(text),
+                    Some(ComposedEvent::Shortcut(shortcut)) => {
+                        self.shortcut_queue.push_back(shortcut)
+                    }
+                    None => {}
+                }
+                self.event_queue.push_back((seq, event));
             }
         }
         Ok(())
@@ -35,17 +70,39 @@ impl InputManager {
 
     /// Retrieve the next buffered input event (non-blocking).
     pub fn get_event(&mut self) -> Option<InputEvent> {
+        self.get_sequenced_event().map(|(_, event)| event)
+    }
+
+    /// Next buffered input event with its position in the raw stream.
+    pub fn get_sequenced_event(&mut self) -> Option<(u64, InputEvent)> {
         self.event_queue.pop_front()
     }
 
+    /// Take the text typed since the last call, oldest first.
+    pub fn poll_text(&mut self) -> Vec<TextInput> {
+        self.text_queue.drain(..).collect()
+    }
+
+    /// Take the shortcuts pressed since the last call, oldest first.
+    pub fn poll_shortcuts(&mut self) -> Vec<Shortcut> {
+        self.shortcut_queue.drain(..).collect()
+    }
+
+    /// Modifier keys currently held.
+    pub fn modifiers(&self) -> Modifiers {
+        self.key_mapper.modifiers()
+    }
+
     /// Check if there are pending events.
     pub fn has_events(&self) -> bool {
         !self.event_queue.is_empty()
     }
 
-    /// Clear all pending events.
+    /// Clear all pending events, including composed text and shortcuts.
     pub fn clear_events(&mut self) {
         self.event_queue.clear();
+        self.text_queue.clear();
+        self.shortcut_queue.clear();
     }
 }
 
@@ -54,3 +111,59 @@ impl Default for InputManager {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_core::{InputEventType, KeyCode};
+    use lucastra_hal::input::MockInputDriver;
+
+    fn key(event_type: InputEventType, key: KeyCode) -> InputEvent {
+        InputEvent {
+            event_type,
+            timestamp: 0,
+            key: Some(key),
+            x: None,
+            y: None,
+            pressed: Some(event_type == InputEventType::KeyPress),
+        }
+    }
+
+    #[test]
+    fn test_composed_events_line_up_with_raw_stream() {
+        use InputEventType::{KeyPress, KeyRelease};
+
+        let mut driver = MockInputDriver::new();
+        for (event_type, code) in [
+            (KeyPress, KeyCode::LShift),
+            (KeyPress, KeyCode::H),
+            (KeyRelease, KeyCode::H),
+            (KeyRelease, KeyCode::LShift),
+            (KeyPress, KeyCode::I),
+            (KeyPress, KeyCode::LCtrl),
+            (KeyPress, KeyCode::C),
+        ] {
+            driver.inject_event(key(event_type, code));
+        }
+        let mut input = InputManager::new();
+        input.register_driver(Box::new(driver));
+        input.poll_events().unwrap();
+
+        let text = input.poll_text();
+        let typed: String = text.iter().map(|t| t.text.as_str()).collect();
+        assert_eq!(typed, "Hi");
+        assert_eq!(text.iter().map(|t| t.seq).collect::<Vec<_>>(), vec![1, 4]);
+
+        let shortcuts = input.poll_shortcuts();
+        assert_eq!(shortcuts.len(), 1);
+        assert_eq!(shortcuts[0].to_string(), "Ctrl+C");
+        assert_eq!(shortcuts[0].seq, 6);
+        assert!(input.modifiers().ctrl);
+
+        // Raw events are all still there, in order
+        let (seq, event) = input.get_sequenced_event().unwrap();
+        assert_eq!((seq, event.key), (0, Some(KeyCode::LShift)));
+        assert_eq!(input.get_event().unwrap().key, Some(KeyCode::H));
+        assert!(input.poll_text().is_empty());
+    }
+}
@@ -1,7 +1,7 @@
 use tracing::info;
 
 #[cfg(feature = "relibc")]
-pub use lucastra_compat::SyscallHandler;
+pub use lucastra_compat::{ProcessTable, SyscallHandler};
 
 #[derive(Debug, Clone)]
 pub struct KernelConfig {
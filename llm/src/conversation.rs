@@ -1,20 +1,49 @@
 //! Conversation management for multi-turn LLM interactions.
 
+use crate::providers::{CompletionRequest, LLMProvider};
+use lucastra_core::compat::{self, SchemaError, UnsupportedVersion};
 use serde::{Deserialize, Serialize};
 use std::collections::VecDeque;
+use std::str::FromStr;
+use std::sync::Arc;
 use thiserror::Error;
+use tracing::warn;
 use uuid::Uuid;
 
 #[derive(Debug, Error)]
 pub enum ConversationError {
     #[error("conversation not found: {0}")]
     NotFound(String),
-    #[error("invalid message: {0}")]
-    InvalidMessage(String),
+    #[error("invalid message {index}: {reason}")]
+    InvalidMessage { index: usize, reason: String },
+    #[error("unknown export format: {0}")]
+    UnknownFormat(String),
+    #[error("corrupted conversation data: {0}")]
+    Corrupted(String),
+    #[error("serialization failed: {0}")]
+    Serialization(String),
+    #[error("summarization failed: {0}")]
+    SummarizationFailed(String),
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error(transparent)]
+    UnsupportedVersion(#[from] UnsupportedVersion),
+}
+
+impl From<SchemaError> for ConversationError {
+    fn from(error: SchemaError) -> Self {
+        match error {
+            SchemaError::UnsupportedVersion(e) => Self::UnsupportedVersion(e),
+            SchemaError::Invalid { message, .. } => Self::Corrupted(message),
+        }
+    }
 }
 
 pub type ConversationResult<T> = std::result::Result<T, ConversationError>;
 
+/// Version of conversations saved or exported as JSON.
+pub const CONVERSATION_VERSION: u32 = 1;
+
 /// Message role in a conversation.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
 #[serde(rename_all = "lowercase")]
@@ -33,11 +62,17 @@ pub struct Message {
     pub timestamp: i64,
 }
 
+fn legacy_schema_version() -> u32 {
+    1
+}
+
+/// Now in Unix seconds; 0 if the clock is set before 1970.
 fn default_timestamp() -> i64 {
-    std::time::SystemTime::now()
+    let secs = std::time::SystemTime::now()
         .duration_since(std::time::UNIX_EPOCH)
-        .unwrap()
-        .as_secs() as i64
+        .map(|d| d.as_secs())
+        .unwrap_or(0);
+    i64::try_from(secs).unwrap_or(i64::MAX)
 }
 
 impl Message {
@@ -66,13 +101,160 @@ impl Message {
     }
 }
 
+/// Transcript format for [`Conversation::export`] and [`Conversation::import`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// Full conversation state, lossless.
+    Json,
+    /// Human-readable `**Role:** content` transcript.
+    Markdown,
+    /// OpenAI chat `messages` array (`[{"role": ..., "content": ...}]`).
+    OpenAI,
+}
+
+impl FromStr for ExportFormat {
+    type Err = ConversationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "json" => Ok(ExportFormat::Json),
+            "markdown" | "md" => Ok(ExportFormat::Markdown),
+            "openai" => Ok(ExportFormat::OpenAI),
+            other => Err(ConversationError::UnknownFormat(other.to_string())),
+        }
+    }
+}
+
+impl Role {
+    fn label(&self) -> &'static str {
+        match self {
+            Role::System => "System",
+            Role::User => "User",
+            Role::Assistant => "Assistant",
+        }
+    }
+
+    /// Parse a role name leniently; anything unrecognised is treated as the user.
+    fn parse_lenient(name: &str) -> Role {
+        match name.trim().to_lowercase().as_str() {
+            "system" => Role::System,
+            "assistant" => Role::Assistant,
+            _ => Role::User,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct OpenAIMessage {
+    role: String,
+    content: String,
+}
+
+/// Check that `messages` can be sent to a model: an assistant message is
+/// never followed by another one, since nothing but a user turn or tool
+/// result can come between two answers. Fails with
+/// [`ConversationError::InvalidMessage`] at the first offending message.
+pub fn validate_messages<'a>(
+    messages: impl IntoIterator<Item = &'a Message>,
+) -> ConversationResult<()> {
+    let mut previous: Option<&Role> = None;
+    for (index, message) in messages.into_iter().enumerate() {
+        if message.role == Role::Assistant && previous == Some(&Role::Assistant) {
+            return Err(ConversationError::InvalidMessage {
+                index,
+                reason: "follows another assistant message".to_string(),
+            });
+        }
+        previous = Some(&message.role);
+    }
+    Ok(())
+}
+
+/// Merge each run of consecutive messages with the same role into one,
+/// their contents separated by a blank line. The merged message keeps the
+/// first one's timestamp.
+pub fn merge_consecutive_roles(messages: Vec<Message>) -> Vec<Message> {
+    let mut merged: Vec<Message> = Vec::with_capacity(messages.len());
+    for message in messages {
+        match merged.last_mut() {
+            Some(last) if last.role == message.role => {
+                last.content.push_str("\n\n");
+                last.content.push_str(&message.content);
+            }
+            _ => merged.push(message),
+        }
+    }
+    merged
+}
+
+/// Flatten messages into a role-prefixed prompt for completion-style models.
+pub fn format_prompt<'a>(messages: impl IntoIterator<Item = &'a Message>) -> String {
+    messages
+        .into_iter()
+        .map(|msg| match msg.role {
+            Role::System => format!("System: {}\n", msg.content),
+            Role::User => format!("User: {}\n", msg.content),
+            Role::Assistant => format!("Assistant: {}\n", msg.content),
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const SUMMARY_PREFIX: &str = "Summary of earlier conversation: ";
+
+const SUMMARIZER_INSTRUCTIONS: &str = "Summarize the following conversation in a few sentences. \
+Keep decisions, facts and open questions; omit pleasantries.";
+
+const TITLE_INSTRUCTIONS: &str = "Give the following conversation a short title of at most six \
+words. Reply with the title only, without quotes or punctuation at the end.";
+
+/// Most words kept from a generated title.
+const TITLE_MAX_WORDS: usize = 6;
+
+/// Characters of the first user message used when no title can be generated.
+const FALLBACK_TITLE_CHARS: usize = 40;
+
+/// Longest title kept by [`Conversation::set_title`], in characters.
+pub const TITLE_MAX_CHARS: usize = 80;
+
+/// Make `title` safe to use as a file name: drops path separators,
+/// characters Windows rejects and control characters, collapses whitespace,
+/// strips trailing dots and caps the length at [`TITLE_MAX_CHARS`].
+pub fn sanitize_title(title: &str) -> String {
+    let cleaned: String = title
+        .chars()
+        .filter(|c| !matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|'))
+        .map(|c| if c.is_control() { ' ' } else { c })
+        .collect();
+    let words: Vec<&str> = cleaned.split_whitespace().collect();
+    let capped: String = words.join(" ").chars().take(TITLE_MAX_CHARS).collect();
+    capped.trim_end_matches(['.', ' ']).trim_start().to_string()
+}
+
+/// Provider used to compress evicted history.
+#[derive(Clone)]
+struct Summarizer(Arc<dyn LLMProvider>);
+
+impl std::fmt::Debug for Summarizer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("Summarizer").field(&self.0.name()).finish()
+    }
+}
+
 /// A conversation with context window management.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Conversation {
+    /// Conversations saved before versioning are version 1.
+    #[serde(default = "legacy_schema_version")]
+    schema_version: u32,
     pub id: String,
     messages: VecDeque<Message>,
     max_messages: usize,
     max_tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+    #[serde(skip)]
+    summarizer: Option<Summarizer>,
 }
 
 impl Conversation {
@@ -83,10 +265,13 @@ impl Conversation {
         }
 
         Self {
+            schema_version: CONVERSATION_VERSION,
             id: Uuid::new_v4().to_string(),
             messages,
             max_messages: 20,       // Keep last 20 messages by default
             max_tokens: Some(8000), // Rough token limit
+            title: None,
+            summarizer: None,
         }
     }
 
@@ -106,10 +291,155 @@ impl Conversation {
         self
     }
 
+    /// Summarize evicted history with `provider` instead of dropping it.
+    ///
+    /// With a summarizer set, adding messages no longer trims; call
+    /// [`Conversation::compact`] to bring the conversation back under budget.
+    pub fn with_summarizer(mut self, provider: Box<dyn LLMProvider>) -> Self {
+        self.summarizer = Some(Summarizer(Arc::from(provider)));
+        self
+    }
+
     /// Add a message to the conversation.
     pub fn add_message(&mut self, message: Message) {
         self.messages.push_back(message);
-        self.trim_context();
+        if self.summarizer.is_none() {
+            self.trim_context();
+        }
+    }
+
+    /// Compress the oldest messages into a summary when over budget.
+    ///
+    /// Without a summarizer this is the same as regular trimming. If the
+    /// summarizer fails the history is trimmed anyway and the error returned.
+    pub async fn compact(&mut self) -> ConversationResult<()> {
+        let Some(Summarizer(provider)) = self.summarizer.clone() else {
+            self.trim_context();
+            return Ok(());
+        };
+
+        // Dry-run the normal trimming to see how much history must go
+        let mut probe = self.clone();
+        probe.trim_context();
+        let excess = self.messages.len() - probe.messages.len();
+        if excess == 0 {
+            return Ok(());
+        }
+
+        let has_system = self
+            .messages
+            .front()
+            .is_some_and(|m| m.role == Role::System);
+        let start = if has_system { 1 } else { 0 };
+        // One extra message makes room for the summary itself
+        let count = (excess + 1).min(self.messages.len() - start);
+        let evicted: Vec<Message> = self.messages.range(start..start + count).cloned().collect();
+
+        let request = [
+            Message::system(SUMMARIZER_INSTRUCTIONS.to_string()),
+            Message::user(format_prompt(&evicted)),
+        ];
+        let response = provider
+            .complete_chat(&request, CompletionRequest::default())
+            .await;
+
+        match response {
+            Ok(response) => {
+                self.messages.drain(start..start + count);
+                self.messages.insert(
+                    start,
+                    Message::system(format!("{}{}", SUMMARY_PREFIX, response.content.trim())),
+                );
+                // The summary itself may still leave us over the token budget
+                self.trim_context();
+                Ok(())
+            }
+            Err(e) => {
+                self.trim_context();
+                Err(ConversationError::SummarizationFailed(e.to_string()))
+            }
+        }
+    }
+
+    /// The conversation's title, if it has one.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Set or replace the title. It is sanitized with [`sanitize_title`];
+    /// a title that ends up empty clears it.
+    pub fn set_title(&mut self, title: impl AsRef<str>) {
+        let title = sanitize_title(title.as_ref());
+        self.title = (!title.is_empty()).then_some(title);
+    }
+
+    /// Title the conversation once its first exchange is complete. Does
+    /// nothing if it already has a title or no answer yet.
+    pub async fn ensure_title(&mut self, provider: Option<&dyn LLMProvider>) {
+        let answered = self.messages.iter().any(|m| m.role == Role::Assistant);
+        if self.title.is_none() && answered {
+            self.regenerate_title(provider).await;
+        }
+    }
+
+    /// Replace the title with one asked of `provider`. Without a provider,
+    /// or if it fails or answers with nothing usable, the title is the start
+    /// of the first user message.
+    pub async fn regenerate_title(&mut self, provider: Option<&dyn LLMProvider>) {
+        let generated = match provider {
+            Some(provider) => self.generate_title(provider).await,
+            None => None,
+        };
+        match generated {
+            Some(title) => self.set_title(title),
+            None => {
+                let fallback = self
+                    .messages
+                    .iter()
+                    .find(|m| m.role == Role::User)
+                    .map(|m| {
+                        m.content
+                            .chars()
+                            .take(FALLBACK_TITLE_CHARS)
+                            .collect::<String>()
+                    })
+                    .unwrap_or_default();
+                self.set_title(fallback);
+            }
+        }
+    }
+
+    async fn generate_title(&self, provider: &dyn LLMProvider) -> Option<String> {
+        let history: Vec<&Message> = self
+            .messages
+            .iter()
+            .filter(|m| m.role != Role::System)
+            .collect();
+        let request = [
+            Message::system(TITLE_INSTRUCTIONS.to_string()),
+            Message::user(format_prompt(history)),
+        ];
+        let options = CompletionRequest {
+            max_tokens: Some(24),
+            temperature: Some(0.2),
+            ..CompletionRequest::default()
+        };
+        match provider.complete_chat(&request, options).await {
+            Ok(response) => {
+                let line = response.content.lines().find(|l| !l.trim().is_empty())?;
+                let line = line.trim().trim_matches(['"', '\'', '*', '#', ' ']);
+                let title = line
+                    .split_whitespace()
+                    .take(TITLE_MAX_WORDS)
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Some(title).filter(|t| !sanitize_title(t).is_empty())
+            }
+            Err(e) => {
+                warn!("Could not generate a conversation title: {}", e);
+                None
+            }
+        }
     }
 
     /// Add a user message.
@@ -127,6 +457,11 @@ impl Conversation {
         self.messages.iter().cloned().collect()
     }
 
+    /// Check the messages with [`validate_messages`].
+    pub fn validate(&self) -> ConversationResult<()> {
+        validate_messages(&self.messages)
+    }
+
     /// Get the number of messages (excluding system prompt).
     pub fn len(&self) -> usize {
         self.messages
@@ -200,15 +535,101 @@ impl Conversation {
 
     /// Format conversation for LLM prompt (generic format).
     pub fn to_prompt(&self) -> String {
-        self.messages
-            .iter()
-            .map(|msg| match msg.role {
-                Role::System => format!("System: {}\n", msg.content),
-                Role::User => format!("User: {}\n", msg.content),
-                Role::Assistant => format!("Assistant: {}\n", msg.content),
-            })
-            .collect::<Vec<_>>()
-            .join("\n")
+        format_prompt(self.messages.iter())
+    }
+
+    /// Export the conversation as a transcript in the given format.
+    pub fn export(&self, format: ExportFormat) -> ConversationResult<String> {
+        match format {
+            ExportFormat::Json => serde_json::to_string_pretty(self)
+                .map_err(|e| ConversationError::Serialization(e.to_string())),
+            ExportFormat::Markdown => {
+                let mut out = format!("# Conversation {}\n", self.id);
+                for msg in &self.messages {
+                    out.push_str(&format!("\n**{}:** {}\n", msg.role.label(), msg.content));
+                }
+                Ok(out)
+            }
+            ExportFormat::OpenAI => {
+                let messages: Vec<OpenAIMessage> = self
+                    .messages
+                    .iter()
+                    .map(|m| OpenAIMessage {
+                        role: m.role.label().to_lowercase(),
+                        content: m.content.clone(),
+                    })
+                    .collect();
+                serde_json::to_string_pretty(&messages)
+                    .map_err(|e| ConversationError::Serialization(e.to_string()))
+            }
+        }
+    }
+
+    /// Import a conversation from a transcript in the given format.
+    ///
+    /// Only JSON preserves the id, timestamps and window settings; the other
+    /// formats produce a fresh conversation. Imported history is kept as-is
+    /// and only trimmed when the next message is added.
+    pub fn import(data: &str, format: ExportFormat) -> ConversationResult<Self> {
+        match format {
+            ExportFormat::Json => Self::from_json(data),
+            ExportFormat::Markdown => Ok(Self::import_markdown(data)),
+            ExportFormat::OpenAI => {
+                let messages: Vec<OpenAIMessage> = serde_json::from_str(data)
+                    .map_err(|e| ConversationError::Corrupted(e.to_string()))?;
+                let mut conv = Self::new(None);
+                conv.messages = messages
+                    .into_iter()
+                    .map(|m| Message {
+                        role: Role::parse_lenient(&m.role),
+                        content: m.content,
+                        timestamp: default_timestamp(),
+                    })
+                    .collect();
+                Ok(conv)
+            }
+        }
+    }
+
+    /// Read a conversation saved as JSON. Versions newer than
+    /// [`CONVERSATION_VERSION`] fail with
+    /// [`ConversationError::UnsupportedVersion`].
+    pub fn from_json(data: &str) -> ConversationResult<Self> {
+        let value: serde_json::Value =
+            serde_json::from_str(data).map_err(|e| ConversationError::Corrupted(e.to_string()))?;
+        compat::json_version("conversation", &value, CONVERSATION_VERSION)?;
+        let mut conv: Self = serde_json::from_value(value)
+            .map_err(|e| ConversationError::Corrupted(e.to_string()))?;
+        conv.schema_version = CONVERSATION_VERSION;
+        Ok(conv)
+    }
+
+    fn import_markdown(data: &str) -> Self {
+        let mut conv = Self::new(None);
+        let mut current: Option<Message> = None;
+
+        for line in data.lines() {
+            if let Some((role, rest)) = parse_markdown_prefix(line) {
+                if let Some(msg) = current.take() {
+                    conv.messages.push_back(finish_markdown_message(msg));
+                }
+                current = Some(Message {
+                    role,
+                    content: rest.to_string(),
+                    timestamp: default_timestamp(),
+                });
+            } else if let Some(msg) = current.as_mut() {
+                msg.content.push('\n');
+                msg.content.push_str(line);
+            } else if let Some(id) = line.strip_prefix("# Conversation ") {
+                conv.id = id.trim().to_string();
+            }
+        }
+
+        if let Some(msg) = current {
+            conv.messages.push_back(finish_markdown_message(msg));
+        }
+        conv
     }
 
     /// Clear all messages except system prompt.
@@ -225,6 +646,24 @@ impl Conversation {
     }
 }
 
+/// Split a `**Role:** content` transcript line into its role and content.
+fn parse_markdown_prefix(line: &str) -> Option<(Role, &str)> {
+    let rest = line.strip_prefix("**")?;
+    let (name, content) = rest.split_once(":**")?;
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return None;
+    }
+    let content = content.strip_prefix(' ').unwrap_or(content);
+    Some((Role::parse_lenient(name), content))
+}
+
+fn finish_markdown_message(mut msg: Message) -> Message {
+    // Drop the blank separator line(s) that precede the next message
+    let trimmed = msg.content.trim_end_matches('\n').len();
+    msg.content.truncate(trimmed);
+    msg
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -271,6 +710,168 @@ mod tests {
         assert_eq!(conv.messages[0].role, Role::System);
     }
 
+    struct FixedSummary;
+
+    #[async_trait::async_trait]
+    impl LLMProvider for FixedSummary {
+        fn name(&self) -> &str {
+            "fixed"
+        }
+
+        fn default_model(&self) -> &str {
+            "fixed"
+        }
+
+        async fn health_check(&self) -> crate::providers::ProviderResult<bool> {
+            Ok(true)
+        }
+
+        async fn complete(
+            &self,
+            _request: CompletionRequest,
+        ) -> crate::providers::ProviderResult<crate::providers::CompletionResponse> {
+            Ok(crate::providers::CompletionResponse {
+                content: "the user chose Rust".to_string(),
+                stop_reason: crate::providers::StopReason::Complete,
+                tokens_used: None,
+                model: None,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compact_with_summarizer() {
+        let mut conv = Conversation::new(Some("System".to_string()))
+            .with_max_messages(4)
+            .with_summarizer(Box::new(FixedSummary));
+
+        for i in 0..8 {
+            conv.add_user_message(format!("Message {}", i));
+        }
+        assert_eq!(conv.messages.len(), 9); // Nothing dropped before compaction
+
+        conv.compact().await.unwrap();
+
+        assert!(conv.messages.len() <= 5);
+        assert_eq!(conv.messages[0].content, "System");
+        assert!(conv
+            .to_prompt()
+            .contains("Summary of earlier conversation: the user chose Rust"));
+        assert_eq!(conv.messages.back().unwrap().content, "Message 7");
+    }
+
+    #[tokio::test]
+    async fn test_compact_without_summarizer_trims() {
+        let mut conv = Conversation::new(None).with_max_messages(10);
+        for i in 0..5 {
+            conv.add_user_message(format!("Message {}", i));
+        }
+        conv.max_messages = 2;
+        conv.compact().await.unwrap();
+        assert_eq!(conv.messages.len(), 2);
+    }
+
+    /// Answers every request with its text, or fails when it has none.
+    struct FixedReply(Option<&'static str>);
+
+    #[async_trait::async_trait]
+    impl LLMProvider for FixedReply {
+        fn name(&self) -> &str {
+            "fixed-reply"
+        }
+
+        fn default_model(&self) -> &str {
+            "fixed-reply"
+        }
+
+        async fn health_check(&self) -> crate::providers::ProviderResult<bool> {
+            Ok(self.0.is_some())
+        }
+
+        async fn complete(
+            &self,
+            _request: CompletionRequest,
+        ) -> crate::providers::ProviderResult<crate::providers::CompletionResponse> {
+            let content = self.0.ok_or_else(|| {
+                crate::providers::ProviderError::RequestError("connection refused".to_string())
+            })?;
+            Ok(crate::providers::CompletionResponse {
+                content: content.to_string(),
+                stop_reason: crate::providers::StopReason::Complete,
+                tokens_used: None,
+                model: None,
+            })
+        }
+    }
+
+    fn first_exchange() -> Conversation {
+        let mut conv = Conversation::new(Some("System".to_string()));
+        conv.add_user_message(
+            "How do I mount a FAT32 USB stick on the kernel's virtual filesystem?".to_string(),
+        );
+        conv.add_assistant_message("Use the mount command.".to_string());
+        conv
+    }
+
+    #[tokio::test]
+    async fn test_title_is_generated_after_the_first_exchange() {
+        let provider = FixedReply(Some("\"Mounting USB: storage in LucAstra today.\"\nExtra"));
+        let mut conv = Conversation::new(None);
+        conv.add_user_message("Hello".to_string());
+        conv.ensure_title(Some(&provider)).await;
+        assert_eq!(conv.title(), None);
+
+        let mut conv = first_exchange();
+        conv.ensure_title(Some(&provider)).await;
+        assert_eq!(conv.title(), Some("Mounting USB storage in LucAstra today"));
+
+        // An existing title is kept until regenerated
+        conv.set_title("Mine");
+        conv.ensure_title(Some(&provider)).await;
+        assert_eq!(conv.title(), Some("Mine"));
+        conv.regenerate_title(Some(&provider)).await;
+        assert_eq!(conv.title(), Some("Mounting USB storage in LucAstra today"));
+
+        let restored = Conversation::from_json(&conv.export(ExportFormat::Json).unwrap()).unwrap();
+        assert_eq!(restored.title(), conv.title());
+    }
+
+    #[tokio::test]
+    async fn test_title_falls_back_to_the_first_user_message() {
+        let mut conv = first_exchange();
+        conv.ensure_title(Some(&FixedReply(None))).await;
+        assert_eq!(
+            conv.title(),
+            Some("How do I mount a FAT32 USB stick on the")
+        );
+
+        let mut conv = first_exchange();
+        conv.ensure_title(None).await;
+        assert_eq!(
+            conv.title(),
+            Some("How do I mount a FAT32 USB stick on the")
+        );
+    }
+
+    #[test]
+    fn test_titles_are_sanitized() {
+        assert_eq!(
+            sanitize_title("  a/b\\c: *what?*  \"x\" <y> | z.. "),
+            "abc what x y z"
+        );
+        assert_eq!(sanitize_title("line\none\ttab"), "line one tab");
+        assert_eq!(sanitize_title("../.."), "");
+        assert_eq!(
+            sanitize_title(&"word ".repeat(40)).chars().count(),
+            TITLE_MAX_CHARS - 1
+        );
+
+        let mut conv = Conversation::new(None);
+        conv.set_title("Notes");
+        conv.set_title("???");
+        assert_eq!(conv.title(), None);
+    }
+
     #[test]
     fn test_to_prompt() {
         let mut conv = Conversation::new(Some("Be helpful".to_string()));
@@ -294,4 +895,163 @@ mod tests {
         assert_eq!(conv.messages.len(), 1); // System prompt still there
         assert_eq!(conv.messages[0].role, Role::System);
     }
+
+    /// Build a varied conversation: multi-line, unicode and markdown-ish content.
+    fn sample_conversation(seed: usize) -> Conversation {
+        let fragments = [
+            "plain text",
+            "multi\nline\n\nwith blank line",
+            "unicode ✓ café 日本語",
+            "  leading spaces",
+            "**bold** and `code`",
+            "",
+        ];
+        let mut conv =
+            Conversation::new(Some(format!("System prompt {}", seed))).with_max_messages(100);
+        for i in 0..(seed % 7 + 3) {
+            let content = format!("{} #{}", fragments[(seed + i) % fragments.len()], i);
+            let mut msg = if i % 2 == 0 {
+                Message::user(content)
+            } else {
+                Message::assistant(content)
+            };
+            msg.timestamp = 1_700_000_000 + (seed * 100 + i) as i64;
+            conv.add_message(msg);
+        }
+        conv
+    }
+
+    fn roles_and_content(conv: &Conversation) -> Vec<(Role, String)> {
+        conv.messages()
+            .into_iter()
+            .map(|m| (m.role, m.content))
+            .collect()
+    }
+
+    #[test]
+    fn test_export_import_roundtrip_all_formats() {
+        for seed in 0..20 {
+            let conv = sample_conversation(seed);
+            for format in [
+                ExportFormat::Json,
+                ExportFormat::Markdown,
+                ExportFormat::OpenAI,
+            ] {
+                let exported = conv.export(format).unwrap();
+                let imported = Conversation::import(&exported, format).unwrap();
+                assert_eq!(
+                    roles_and_content(&imported),
+                    roles_and_content(&conv),
+                    "seed {} format {:?}",
+                    seed,
+                    format
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_json_roundtrip_preserves_timestamps_and_id() {
+        let conv = sample_conversation(3);
+        let imported = Conversation::import(
+            &conv.export(ExportFormat::Json).unwrap(),
+            ExportFormat::Json,
+        )
+        .unwrap();
+
+        assert_eq!(imported.id, conv.id);
+        let timestamps: Vec<i64> = imported.messages().iter().map(|m| m.timestamp).collect();
+        let expected: Vec<i64> = conv.messages().iter().map(|m| m.timestamp).collect();
+        assert_eq!(timestamps, expected);
+    }
+
+    #[test]
+    fn test_markdown_unknown_role_maps_to_user() {
+        let markdown = "# Conversation abc\n\n**Tool:** ran ls\n\n**Assistant:** done\n";
+        let conv = Conversation::import(markdown, ExportFormat::Markdown).unwrap();
+
+        assert_eq!(conv.id, "abc");
+        let messages = conv.messages();
+        assert_eq!(messages[0].role, Role::User);
+        assert_eq!(messages[0].content, "ran ls");
+        assert_eq!(messages[1].role, Role::Assistant);
+    }
+
+    #[test]
+    fn test_v1_conversation_still_loads() {
+        let conv = Conversation::from_json(include_str!("../tests/fixtures/v1/conversation.json"))
+            .unwrap();
+        assert_eq!(conv.id, "5f0c6d9e-2f7b-4a57-9d8e-0b6a3c1e2f44");
+        let messages = conv.messages();
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[2].role, Role::Assistant);
+        assert_eq!(messages[2].timestamp, 1733840405);
+
+        let saved: serde_json::Value =
+            serde_json::from_str(&conv.export(ExportFormat::Json).unwrap()).unwrap();
+        assert_eq!(saved["schema_version"], CONVERSATION_VERSION);
+    }
+
+    #[test]
+    fn test_newer_conversations_are_refused() {
+        let json = r#"{"schema_version": 9, "id": "x", "turns": []}"#;
+        assert!(matches!(
+            Conversation::import(json, ExportFormat::Json),
+            Err(ConversationError::UnsupportedVersion(UnsupportedVersion {
+                found: 9,
+                ..
+            }))
+        ));
+    }
+
+    #[test]
+    fn test_import_invalid_json() {
+        let result = Conversation::import("not json", ExportFormat::OpenAI);
+        assert!(matches!(result, Err(ConversationError::Corrupted(_))));
+        assert!(matches!(
+            "yaml".parse::<ExportFormat>(),
+            Err(ConversationError::UnknownFormat(format)) if format == "yaml"
+        ));
+    }
+
+    #[test]
+    fn test_consecutive_assistant_messages_are_invalid() {
+        let mut conv = first_exchange();
+        assert!(conv.validate().is_ok());
+
+        // Consecutive user and system messages are fine
+        conv.add_user_message("And exFAT?".to_string());
+        conv.add_user_message("Or NTFS?".to_string());
+        assert!(conv.validate().is_ok());
+
+        conv.add_assistant_message("exFAT works.".to_string());
+        conv.add_assistant_message("NTFS is read-only.".to_string());
+        assert!(matches!(
+            conv.validate(),
+            Err(ConversationError::InvalidMessage { index: 6, .. })
+        ));
+    }
+
+    #[test]
+    fn test_merging_consecutive_roles_heals_a_conversation() {
+        let messages = vec![
+            Message::system("System".to_string()),
+            Message::user("Hi".to_string()),
+            Message::assistant("Hello.".to_string()),
+            Message::assistant("How can I help?".to_string()),
+            Message::assistant("Ask away.".to_string()),
+            Message::user("Thanks".to_string()),
+        ];
+        assert!(matches!(
+            validate_messages(&messages),
+            Err(ConversationError::InvalidMessage { index: 3, .. })
+        ));
+
+        let merged = merge_consecutive_roles(messages);
+        assert!(validate_messages(&merged).is_ok());
+        assert_eq!(merged.len(), 4);
+        assert_eq!(merged[2].role, Role::Assistant);
+        assert_eq!(merged[2].content, "Hello.\n\nHow can I help?\n\nAsk away.");
+        assert_eq!(merged[3].content, "Thanks");
+    }
 }
@@ -0,0 +1,271 @@
+//! Disk persistence for conversations.
+//!
+//! Each conversation is stored as `<id>.json` inside the store directory
+//! (by default `~/.lucastra/data/conversations`).
+
+use crate::conversation::{Conversation, ConversationError, ConversationResult, Role};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+const TITLE_MAX_CHARS: usize = 50;
+
+/// Lightweight listing entry for a stored conversation.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationSummary {
+    pub id: String,
+    pub title: String,
+    /// Unix timestamp of the most recent message.
+    pub updated_at: i64,
+}
+
+/// JSON file store for conversations.
+#[derive(Debug)]
+pub struct ConversationStore {
+    dir: PathBuf,
+}
+
+impl ConversationStore {
+    pub fn new(dir: PathBuf) -> ConversationResult<Self> {
+        fs::create_dir_all(&dir)?;
+        Ok(Self { dir })
+    }
+
+    /// Directory the store writes to.
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Save (or overwrite) a conversation.
+    pub fn save(&self, conversation: &Conversation) -> ConversationResult<()> {
+        let path = self.path_for(&conversation.id)?;
+        let json = serde_json::to_string_pretty(conversation)
+            .map_err(|e| ConversationError::Serialization(e.to_string()))?;
+        fs::write(path, json)?;
+        Ok(())
+    }
+
+    /// Load a conversation by id.
+    pub fn load(&self, id: &str) -> ConversationResult<Conversation> {
+        let path = self.path_for(id)?;
+        if !path.exists() {
+            return Err(ConversationError::NotFound(id.to_string()));
+        }
+        let conversation = Self::read_file(&path)?;
+        if conversation.id != id {
+            return Err(ConversationError::Corrupted(format!(
+                "{}: holds conversation {}",
+                path.display(),
+                conversation.id
+            )));
+        }
+        Ok(conversation)
+    }
+
+    /// Check whether a conversation with this id has been saved.
+    pub fn exists(&self, id: &str) -> bool {
+        self.path_for(id).map(|p| p.exists()).unwrap_or(false)
+    }
+
+    /// List stored conversations, most recently updated first.
+    /// Files that cannot be read or parsed are skipped with a warning.
+    pub fn list(&self) -> ConversationResult<Vec<ConversationSummary>> {
+        let mut summaries = Vec::new();
+
+        for entry in fs::read_dir(&self.dir)? {
+            let path = match entry {
+                Ok(entry) => entry.path(),
+                Err(e) => {
+                    warn!("Skipping unreadable conversation entry: {}", e);
+                    continue;
+                }
+            };
+
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            match Self::read_file(&path) {
+                Ok(conversation) => summaries.push(summarize(&conversation)),
+                Err(e) => warn!("Skipping conversation file {}: {}", path.display(), e),
+            }
+        }
+
+        summaries.sort_by_key(|s| std::cmp::Reverse(s.updated_at));
+        Ok(summaries)
+    }
+
+    /// Delete a stored conversation.
+    pub fn delete(&self, id: &str) -> ConversationResult<()> {
+        let path = self.path_for(id)?;
+        if !path.exists() {
+            return Err(ConversationError::NotFound(id.to_string()));
+        }
+        fs::remove_file(path)?;
+        Ok(())
+    }
+
+    fn path_for(&self, id: &str) -> ConversationResult<PathBuf> {
+        // Ids become file names, so reject anything that could escape the store
+        if id.is_empty() || id.contains(['/', '\\']) || id.contains("..") {
+            return Err(ConversationError::NotFound(id.to_string()));
+        }
+        Ok(self.dir.join(format!("{}.json", id)))
+    }
+
+    fn read_file(path: &Path) -> ConversationResult<Conversation> {
+        let contents = fs::read_to_string(path)?;
+        Conversation::from_json(&contents).map_err(|e| match e {
+            ConversationError::Corrupted(e) => {
+                ConversationError::Corrupted(format!("{}: {}", path.display(), e))
+            }
+            e => e,
+        })
+    }
+}
+
+fn summarize(conversation: &Conversation) -> ConversationSummary {
+    let messages = conversation.messages();
+
+    let derived = || {
+        messages
+            .iter()
+            .find(|m| m.role == Role::User)
+            .map(|m| {
+                let line = m.content.lines().next().unwrap_or_default().trim();
+                if line.chars().count() > TITLE_MAX_CHARS {
+                    let truncated: String = line.chars().take(TITLE_MAX_CHARS).collect();
+                    format!("{}...", truncated)
+                } else {
+                    line.to_string()
+                }
+            })
+            .filter(|t| !t.is_empty())
+            .unwrap_or_else(|| "Untitled conversation".to_string())
+    };
+    let title = conversation
+        .title()
+        .map(str::to_string)
+        .unwrap_or_else(derived);
+
+    let updated_at = messages.iter().map(|m| m.timestamp).max().unwrap_or(0);
+
+    ConversationSummary {
+        id: conversation.id.clone(),
+        title,
+        updated_at,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_save_and_load_roundtrip() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let mut conv = Conversation::new(Some("System".to_string()));
+        conv.add_user_message("Hello".to_string());
+        conv.add_assistant_message("Hi there!".to_string());
+        store.save(&conv).unwrap();
+
+        let loaded = store.load(&conv.id).unwrap();
+        assert_eq!(loaded.id, conv.id);
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.messages()[1].content, "Hello");
+    }
+
+    #[test]
+    fn test_load_missing_conversation() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let result = store.load("does-not-exist");
+        assert!(matches!(result, Err(ConversationError::NotFound(_))));
+    }
+
+    #[test]
+    fn test_load_corrupted_conversation() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(temp_dir.path().to_path_buf()).unwrap();
+        fs::write(temp_dir.path().join("broken.json"), "{ not json").unwrap();
+
+        let result = store.load("broken");
+        assert!(matches!(result, Err(ConversationError::Corrupted(_))));
+
+        // A file renamed by hand holds some other conversation
+        let conv = Conversation::with_id("abc".to_string(), None);
+        store.save(&conv).unwrap();
+        fs::rename(
+            temp_dir.path().join("abc.json"),
+            temp_dir.path().join("xyz.json"),
+        )
+        .unwrap();
+        assert!(matches!(
+            store.load("xyz"),
+            Err(ConversationError::Corrupted(_))
+        ));
+    }
+
+    #[test]
+    fn test_list_skips_corrupted_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let mut conv = Conversation::with_id("abc".to_string(), None);
+        conv.add_user_message("What is the weather like today?".to_string());
+        store.save(&conv).unwrap();
+        fs::write(temp_dir.path().join("broken.json"), "garbage").unwrap();
+
+        let summaries = store.list().unwrap();
+        assert_eq!(summaries.len(), 1);
+        assert_eq!(summaries[0].id, "abc");
+        assert_eq!(summaries[0].title, "What is the weather like today?");
+    }
+
+    #[test]
+    fn test_list_prefers_the_stored_title() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let mut conv = Conversation::with_id("abc".to_string(), None);
+        conv.add_user_message("What is the weather like today?".to_string());
+        conv.set_title("Weather check");
+        store.save(&conv).unwrap();
+
+        let summaries = store.list().unwrap();
+        assert_eq!(summaries[0].title, "Weather check");
+    }
+
+    #[test]
+    fn test_delete_conversation() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(temp_dir.path().to_path_buf()).unwrap();
+
+        let conv = Conversation::new(None);
+        store.save(&conv).unwrap();
+        assert!(store.exists(&conv.id));
+
+        store.delete(&conv.id).unwrap();
+        assert!(!store.exists(&conv.id));
+        assert!(matches!(
+            store.delete(&conv.id),
+            Err(ConversationError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_rejects_path_traversal_ids() {
+        let temp_dir = TempDir::new().unwrap();
+        let store = ConversationStore::new(temp_dir.path().to_path_buf()).unwrap();
+
+        assert!(matches!(
+            store.load("../secrets"),
+            Err(ConversationError::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,414 @@
+//! Batched, concurrent embedding of many texts.
+//!
+//! [`EmbeddingPipeline`] splits texts into batches sized for the provider
+//! (see [`LLMProvider::max_embedding_batch`]), runs a bounded number of
+//! batches at once and skips texts already in the [`EmbeddingCache`]. A batch
+//! that fails is retried once; if it fails again its texts are reported in
+//! [`EmbeddingOutcome::failed`] and the rest of the pipeline carries on.
+
+use crate::cache::EmbeddingCache;
+use crate::providers::{EmbeddingRequest, LLMProvider, ProviderError, ProviderResult};
+use crate::rate_limit::{estimate_tokens, RateLimiter};
+use crate::usage::UsageTracker;
+use futures::future::join_all;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::Semaphore;
+use tracing::field::Empty;
+use tracing::{debug, info_span, warn, Instrument};
+
+/// Batches in flight at once unless configured otherwise.
+const DEFAULT_CONCURRENCY: usize = 4;
+
+/// How many of the input texts have an embedding (or have failed) so far.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EmbeddingProgress {
+    pub completed: usize,
+    pub total: usize,
+}
+
+/// A text that could not be embedded.
+#[derive(Debug, Clone)]
+pub struct FailedEmbedding {
+    /// Position in the input.
+    pub index: usize,
+    pub text: String,
+    pub error: String,
+}
+
+/// Result of [`EmbeddingPipeline::embed`].
+#[derive(Debug, Default)]
+pub struct EmbeddingOutcome {
+    /// One entry per input text, in input order; `None` where embedding failed.
+    pub embeddings: Vec<Option<Vec<f32>>>,
+    /// Texts embedded by the provider during this run.
+    pub embedded: usize,
+    /// Texts served from the cache.
+    pub from_cache: usize,
+    pub failed: Vec<FailedEmbedding>,
+}
+
+/// Embeds texts in provider-sized batches with bounded concurrency.
+pub struct EmbeddingPipeline {
+    provider: Arc<dyn LLMProvider>,
+    cache: Option<Mutex<EmbeddingCache>>,
+    concurrency: usize,
+    model: Option<String>,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    usage: Option<Arc<Mutex<UsageTracker>>>,
+}
+
+impl EmbeddingPipeline {
+    pub fn new(provider: Arc<dyn LLMProvider>) -> Self {
+        Self {
+            provider,
+            cache: None,
+            concurrency: DEFAULT_CONCURRENCY,
+            model: None,
+            rate_limiter: None,
+            usage: None,
+        }
+    }
+
+    /// Look texts up in `cache` before embedding them, and store new
+    /// embeddings there.
+    pub fn with_cache(mut self, cache: EmbeddingCache) -> Self {
+        self.cache = Some(Mutex::new(cache));
+        self
+    }
+
+    /// Run at most `concurrency` provider requests at once (at least 1).
+    pub fn with_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Embedding model to request instead of the provider's default.
+    pub fn with_model(mut self, model: String) -> Self {
+        self.model = Some(model);
+        self
+    }
+
+    /// Wait for `limiter` before every provider request, including retries.
+    pub fn with_rate_limiter(mut self, limiter: Arc<RateLimiter>) -> Self {
+        self.rate_limiter = Some(limiter);
+        self
+    }
+
+    /// Record every successful provider request in `tracker`.
+    pub fn with_usage_tracker(mut self, tracker: Arc<Mutex<UsageTracker>>) -> Self {
+        self.usage = Some(tracker);
+        self
+    }
+
+    /// Embed `texts`, calling `progress` as cached texts are found and as
+    /// each batch finishes.
+    pub async fn embed(
+        &self,
+        texts: Vec<String>,
+        progress: impl Fn(EmbeddingProgress) + Sync,
+    ) -> EmbeddingOutcome {
+        let total = texts.len();
+        let mut outcome = EmbeddingOutcome {
+            embeddings: vec![None; total],
+            ..Default::default()
+        };
+
+        let mut missing = Vec::new();
+        for (index, text) in texts.iter().enumerate() {
+            match self.cached(text) {
+                Some(embedding) => {
+                    outcome.embeddings[index] = Some(embedding);
+                    outcome.from_cache += 1;
+                }
+                None => missing.push(index),
+            }
+        }
+        let completed = AtomicUsize::new(outcome.from_cache);
+        if outcome.from_cache > 0 {
+            progress(EmbeddingProgress {
+                completed: outcome.from_cache,
+                total,
+            });
+        }
+
+        let semaphore = Semaphore::new(self.concurrency);
+        let batch_size = self.provider.max_embedding_batch().max(1);
+        let batches = missing.chunks(batch_size).map(|indices| {
+            let batch: Vec<String> = indices.iter().map(|&i| texts[i].clone()).collect();
+            let (semaphore, completed, progress) = (&semaphore, &completed, &progress);
+            async move {
+                let result = match semaphore.acquire().await {
+                    Ok(_permit) => self.embed_with_retry(batch).await,
+                    Err(e) => Err(ProviderError::RequestError(e.to_string())),
+                };
+                let completed =
+                    completed.fetch_add(indices.len(), Ordering::SeqCst) + indices.len();
+                progress(EmbeddingProgress { completed, total });
+                (indices, result)
+            }
+        });
+
+        for (indices, result) in join_all(batches).await {
+            match result {
+                Ok(embeddings) => {
+                    for (&index, embedding) in indices.iter().zip(embeddings) {
+                        self.store(&texts[index], &embedding);
+                        outcome.embeddings[index] = Some(embedding);
+                        outcome.embedded += 1;
+                    }
+                }
+                Err(e) => {
+                    outcome
+                        .failed
+                        .extend(indices.iter().map(|&index| FailedEmbedding {
+                            index,
+                            text: texts[index].clone(),
+                            error: e.to_string(),
+                        }));
+                }
+            }
+        }
+        outcome
+    }
+
+    async fn embed_with_retry(&self, batch: Vec<String>) -> ProviderResult<Vec<Vec<f32>>> {
+        match self.embed_batch(batch.clone()).await {
+            Ok(embeddings) => Ok(embeddings),
+            Err(e) => {
+                warn!("Embedding batch of {} failed, retrying: {}", batch.len(), e);
+                self.embed_batch(batch).await
+            }
+        }
+    }
+
+    async fn embed_batch(&self, batch: Vec<String>) -> ProviderResult<Vec<Vec<f32>>> {
+        let expected = batch.len();
+        if let Some(limiter) = &self.rate_limiter {
+            limiter
+                .acquire(batch.iter().map(|text| estimate_tokens(text)).sum())
+                .await;
+        }
+        debug!("Embedding batch of {} texts", expected);
+        let span = info_span!(
+            "llm_embed",
+            provider = self.provider.name(),
+            model = Empty,
+            texts = expected,
+            input_tokens = batch.iter().map(|text| estimate_tokens(text)).sum::<u32>(),
+        );
+        let response = self
+            .provider
+            .embed(EmbeddingRequest {
+                texts: batch.clone(),
+                model: self.model.clone(),
+            })
+            .instrument(span.clone())
+            .await?;
+        span.record("model", response.model.as_str());
+        if let Some(Ok(mut usage)) = self.usage.as_ref().map(|usage| usage.lock()) {
+            usage.record_embedding(self.provider.name(), &response.model, &batch);
+        }
+        if response.embeddings.len() != expected {
+            return Err(ProviderError::InvalidResponse(format!(
+                "expected {} embeddings, got {}",
+                expected,
+                response.embeddings.len()
+            )));
+        }
+        Ok(response.embeddings)
+    }
+
+    /// Cache namespace, so different providers and models never share entries.
+    fn cache_model(&self) -> String {
+        let model = self
+            .model
+            .as_deref()
+            .unwrap_or_else(|| self.provider.embedding_model());
+        format!("{}:{}", self.provider.name(), model)
+    }
+
+    fn cached(&self, text: &str) -> Option<Vec<f32>> {
+        let mut cache = self.cache.as_ref()?.lock().ok()?;
+        cache.get(text, &self.cache_model()).unwrap_or_else(|e| {
+            warn!("Embedding cache read failed: {}", e);
+            None
+        })
+    }
+
+    fn store(&self, text: &str, embedding: &[f32]) {
+        let Some(Ok(mut cache)) = self.cache.as_ref().map(|c| c.lock()) else {
+            return;
+        };
+        if let Err(e) = cache.put(text, &self.cache_model(), embedding.to_vec()) {
+            warn!("Embedding cache write failed: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::{CompletionRequest, CompletionResponse, EmbeddingResponse};
+    use async_trait::async_trait;
+    use std::collections::HashMap;
+    use std::time::Duration;
+    use tempfile::TempDir;
+
+    /// Embeds each text as `[len]`. Texts starting with "fail" always fail;
+    /// texts starting with "flaky" fail the first time they are sent.
+    #[derive(Default)]
+    struct MockEmbedder {
+        batch_size: usize,
+        requests: Mutex<Vec<Vec<String>>>,
+        attempts: Mutex<HashMap<String, usize>>,
+        in_flight: AtomicUsize,
+        max_in_flight: AtomicUsize,
+    }
+
+    #[async_trait]
+    impl LLMProvider for MockEmbedder {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        async fn health_check(&self) -> ProviderResult<bool> {
+            Ok(true)
+        }
+
+        async fn complete(
+            &self,
+            _request: CompletionRequest,
+        ) -> ProviderResult<CompletionResponse> {
+            Err(ProviderError::UnsupportedError("mock".to_string()))
+        }
+
+        async fn embed(&self, request: EmbeddingRequest) -> ProviderResult<EmbeddingResponse> {
+            let now = self.in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_in_flight.fetch_max(now, Ordering::SeqCst);
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            self.in_flight.fetch_sub(1, Ordering::SeqCst);
+
+            self.requests.lock().unwrap().push(request.texts.clone());
+            for text in &request.texts {
+                let mut attempts = self.attempts.lock().unwrap();
+                let attempt = attempts.entry(text.clone()).or_insert(0);
+                *attempt += 1;
+                if text.starts_with("fail") || (text.starts_with("flaky") && *attempt == 1) {
+                    return Err(ProviderError::RequestError(format!(
+                        "cannot embed {}",
+                        text
+                    )));
+                }
+            }
+            Ok(EmbeddingResponse {
+                embeddings: request.texts.iter().map(|t| vec![t.len() as f32]).collect(),
+                model: "mock-embed".to_string(),
+                dimensions: 1,
+            })
+        }
+
+        fn supports_embeddings(&self) -> bool {
+            true
+        }
+
+        fn max_embedding_batch(&self) -> usize {
+            self.batch_size
+        }
+
+        fn default_model(&self) -> &str {
+            "mock-embed"
+        }
+    }
+
+    fn texts(words: &[&str]) -> Vec<String> {
+        words.iter().map(|w| w.to_string()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_batches_run_concurrently_and_keep_input_order() {
+        let provider = Arc::new(MockEmbedder {
+            batch_size: 2,
+            ..Default::default()
+        });
+        let pipeline = EmbeddingPipeline::new(provider.clone()).with_concurrency(2);
+        let updates = Mutex::new(Vec::new());
+
+        let outcome = pipeline
+            .embed(
+                texts(&["a", "bb", "ccc", "dddd", "eeeee", "ffffff", "g"]),
+                |p| updates.lock().unwrap().push(p),
+            )
+            .await;
+
+        let lengths: Vec<f32> = outcome
+            .embeddings
+            .iter()
+            .map(|e| e.as_ref().unwrap()[0])
+            .collect();
+        assert_eq!(lengths, vec![1.0, 2.0, 3.0, 4.0, 5.0, 6.0, 1.0]);
+        assert_eq!(outcome.embedded, 7);
+        assert_eq!(provider.requests.lock().unwrap().len(), 4);
+        assert_eq!(provider.max_in_flight.load(Ordering::SeqCst), 2);
+
+        let updates = updates.into_inner().unwrap();
+        assert_eq!(updates.len(), 4);
+        assert_eq!(updates.last().unwrap().completed, 7);
+        assert!(updates.iter().all(|p| p.total == 7));
+    }
+
+    #[tokio::test]
+    async fn test_cached_texts_are_not_sent_again() {
+        let dir = TempDir::new().unwrap();
+        let provider = Arc::new(MockEmbedder {
+            batch_size: 8,
+            ..Default::default()
+        });
+        let first = EmbeddingPipeline::new(provider.clone())
+            .with_cache(EmbeddingCache::new(dir.path().to_path_buf()).unwrap());
+        first.embed(texts(&["one", "two"]), |_| {}).await;
+
+        let usage = Arc::new(Mutex::new(UsageTracker::new()));
+        let second = EmbeddingPipeline::new(provider.clone())
+            .with_cache(EmbeddingCache::new(dir.path().to_path_buf()).unwrap())
+            .with_usage_tracker(usage.clone());
+        let outcome = second.embed(texts(&["two", "three", "one"]), |_| {}).await;
+
+        assert_eq!(outcome.from_cache, 2);
+        assert_eq!(outcome.embedded, 1);
+        assert!(outcome.embeddings.iter().all(Option::is_some));
+        assert_eq!(
+            provider.requests.lock().unwrap().last().unwrap(),
+            &texts(&["three"])
+        );
+        let today = usage.lock().unwrap().today();
+        assert_eq!((today.requests, today.input_tokens), (1, 2));
+        assert_eq!(today.estimated_requests, 1);
+    }
+
+    #[tokio::test]
+    async fn test_failed_batches_are_retried_once_then_reported() {
+        let provider = Arc::new(MockEmbedder {
+            batch_size: 1,
+            ..Default::default()
+        });
+        let pipeline = EmbeddingPipeline::new(provider.clone());
+
+        let outcome = pipeline
+            .embed(texts(&["ok", "flaky", "fail-hard", "fine"]), |_| {})
+            .await;
+
+        assert_eq!(outcome.embedded, 3);
+        assert_eq!(outcome.failed.len(), 1);
+        assert_eq!(outcome.failed[0].index, 2);
+        assert_eq!(outcome.failed[0].text, "fail-hard");
+        assert!(outcome.failed[0].error.contains("cannot embed"));
+        assert!(outcome.embeddings[2].is_none());
+        assert!(outcome.embeddings[1].is_some());
+
+        let attempts = provider.attempts.lock().unwrap();
+        assert_eq!(attempts["flaky"], 2);
+        assert_eq!(attempts["fail-hard"], 2);
+        assert_eq!(attempts["ok"], 1);
+    }
+}
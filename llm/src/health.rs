@@ -0,0 +1,322 @@
+//! Background health monitoring for the LLM backend.
+//!
+//! A [`HealthChecker`] probes the backend and turns consecutive failures into
+//! a [`HealthState`]; once the backend has stayed down for a while it calls a
+//! recovery hook, e.g. to restart the server. [`HealthMonitor`] runs a
+//! checker on a background thread and shares the latest [`HealthStatus`].
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::sync::mpsc::{self, RecvTimeoutError, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread::JoinHandle;
+use std::time::Duration;
+use tracing::{info, warn};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthState {
+    #[default]
+    Healthy,
+    /// Failing, but not for long enough to give up on it.
+    Degraded,
+    Down,
+}
+
+impl fmt::Display for HealthState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            HealthState::Healthy => "healthy",
+            HealthState::Degraded => "degraded",
+            HealthState::Down => "down",
+        })
+    }
+}
+
+/// Latest outcome of health checks.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HealthStatus {
+    pub state: HealthState,
+    pub consecutive_failures: u32,
+    /// Recovery hook calls since the monitor started.
+    pub recoveries: u32,
+    /// Unix seconds of the last check; `None` before the first one.
+    pub last_checked: Option<i64>,
+}
+
+/// How many consecutive failures move the backend between states.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HealthPolicy {
+    /// Failures before the backend counts as down; fewer is degraded.
+    pub down_after: u32,
+    /// Failed checks while down before each recovery attempt.
+    pub recover_after: u32,
+}
+
+impl Default for HealthPolicy {
+    fn default() -> Self {
+        Self {
+            down_after: 3,
+            recover_after: 2,
+        }
+    }
+}
+
+type Probe = Box<dyn FnMut() -> bool + Send>;
+type Hook = Box<dyn FnMut() + Send>;
+type Observer = Box<dyn FnMut(&HealthStatus) + Send>;
+
+/// Probes the backend and tracks its state across checks.
+pub struct HealthChecker {
+    probe: Probe,
+    policy: HealthPolicy,
+    status: HealthStatus,
+    recover: Option<Hook>,
+    observer: Option<Observer>,
+}
+
+impl HealthChecker {
+    /// Checker calling `probe`, which returns whether the backend is healthy.
+    pub fn new(probe: impl FnMut() -> bool + Send + 'static) -> Self {
+        Self {
+            probe: Box::new(probe),
+            policy: HealthPolicy::default(),
+            status: HealthStatus::default(),
+            recover: None,
+            observer: None,
+        }
+    }
+
+    pub fn with_policy(mut self, policy: HealthPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Call `recover` each time the backend has been down for
+    /// `policy.recover_after` more checks.
+    pub fn with_recovery(mut self, recover: impl FnMut() + Send + 'static) -> Self {
+        self.recover = Some(Box::new(recover));
+        self
+    }
+
+    /// Pass the status to `observer` after every check.
+    pub fn with_observer(mut self, observer: impl FnMut(&HealthStatus) + Send + 'static) -> Self {
+        self.observer = Some(Box::new(observer));
+        self
+    }
+
+    pub fn status(&self) -> &HealthStatus {
+        &self.status
+    }
+
+    /// Probe once, update the state and run any hooks it calls for.
+    pub fn check(&mut self) -> &HealthStatus {
+        let healthy = (self.probe)();
+        let previous = self.status.state;
+        self.status.last_checked = Some(chrono::Utc::now().timestamp());
+
+        if healthy {
+            self.status.consecutive_failures = 0;
+            self.status.state = HealthState::Healthy;
+        } else {
+            self.status.consecutive_failures += 1;
+            self.status.state = if self.status.consecutive_failures >= self.policy.down_after {
+                HealthState::Down
+            } else {
+                HealthState::Degraded
+            };
+        }
+        if self.status.state != previous {
+            match self.status.state {
+                HealthState::Healthy => info!("LLM backend is {}", self.status.state),
+                _ => warn!(
+                    "LLM backend is {} after {} failed checks",
+                    self.status.state, self.status.consecutive_failures
+                ),
+            }
+        }
+
+        let checks_down = self
+            .status
+            .consecutive_failures
+            .saturating_sub(self.policy.down_after);
+        let recovery_due = self.status.state == HealthState::Down
+            && checks_down > 0
+            && checks_down.is_multiple_of(self.policy.recover_after.max(1));
+        if recovery_due {
+            if let Some(recover) = &mut self.recover {
+                warn!("LLM backend still down; attempting recovery");
+                self.status.recoveries += 1;
+                recover();
+            }
+        }
+
+        if let Some(observer) = &mut self.observer {
+            observer(&self.status);
+        }
+        &self.status
+    }
+}
+
+/// Shared view of a monitor's latest status.
+#[derive(Debug, Clone, Default)]
+pub struct HealthHandle(Arc<Mutex<HealthStatus>>);
+
+impl HealthHandle {
+    pub fn get(&self) -> HealthStatus {
+        self.0.lock().map(|s| s.clone()).unwrap_or_default()
+    }
+
+    /// Whether the backend is known to be down.
+    pub fn is_down(&self) -> bool {
+        self.get().state == HealthState::Down
+    }
+}
+
+/// Runs a [`HealthChecker`] every `interval` on a background thread.
+/// The first check happens straight away.
+pub struct HealthMonitor {
+    handle: HealthHandle,
+    stop: Option<Sender<()>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl HealthMonitor {
+    pub fn start(mut checker: HealthChecker, interval: Duration) -> Self {
+        let handle = HealthHandle::default();
+        let shared = handle.clone();
+        let (stop, stopped) = mpsc::channel::<()>();
+        let worker = std::thread::spawn(move || loop {
+            let status = checker.check().clone();
+            if let Ok(mut latest) = shared.0.lock() {
+                *latest = status;
+            }
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => continue,
+                Ok(()) | Err(RecvTimeoutError::Disconnected) => return,
+            }
+        });
+
+        Self {
+            handle,
+            stop: Some(stop),
+            worker: Some(worker),
+        }
+    }
+
+    pub fn status(&self) -> HealthStatus {
+        self.handle.get()
+    }
+
+    pub fn handle(&self) -> HealthHandle {
+        self.handle.clone()
+    }
+
+    /// Stop checking. A check in progress finishes first.
+    pub fn stop(&mut self) {
+        if let Some(stop) = self.stop.take() {
+            let _ = stop.send(());
+        }
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for HealthMonitor {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::VecDeque;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Probe answering from `script`, then healthy once it runs out.
+    fn scripted(script: &[bool]) -> impl FnMut() -> bool + Send + 'static {
+        let mut script: VecDeque<bool> = script.iter().copied().collect();
+        move || script.pop_front().unwrap_or(true)
+    }
+
+    #[test]
+    fn test_state_follows_consecutive_failures() {
+        let mut checker = HealthChecker::new(scripted(&[false, false, false, true, false]));
+
+        let states: Vec<_> = (0..5)
+            .map(|_| {
+                let status = checker.check();
+                (status.state, status.consecutive_failures)
+            })
+            .collect();
+
+        assert_eq!(
+            states,
+            vec![
+                (HealthState::Degraded, 1),
+                (HealthState::Degraded, 2),
+                (HealthState::Down, 3),
+                (HealthState::Healthy, 0),
+                (HealthState::Degraded, 1),
+            ]
+        );
+        assert!(checker.status().last_checked.is_some());
+    }
+
+    #[test]
+    fn test_recovery_runs_while_down_persists() {
+        let restarts = Arc::new(AtomicU32::new(0));
+        let counter = restarts.clone();
+        let mut checker = HealthChecker::new(scripted(&[false; 7]))
+            .with_policy(HealthPolicy {
+                down_after: 2,
+                recover_after: 2,
+            })
+            .with_recovery(move || {
+                counter.fetch_add(1, Ordering::SeqCst);
+            });
+
+        // Down on the 2nd failure; recovery after the 4th and 6th
+        let mut calls = Vec::new();
+        for _ in 0..7 {
+            checker.check();
+            calls.push(restarts.load(Ordering::SeqCst));
+        }
+        assert_eq!(calls, vec![0, 0, 0, 1, 1, 2, 2]);
+        assert_eq!(checker.status().recoveries, 2);
+
+        // Healthy again: no more recovery attempts
+        checker.check();
+        assert_eq!(checker.status().state, HealthState::Healthy);
+        assert_eq!(restarts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn test_monitor_shares_status_and_notifies_observer() {
+        let observed = Arc::new(Mutex::new(Vec::new()));
+        let sink = observed.clone();
+        let checker = HealthChecker::new(scripted(&[false, false, false]))
+            .with_observer(move |status| sink.lock().unwrap().push(status.state));
+        let mut monitor = HealthMonitor::start(checker, Duration::from_millis(10));
+
+        let deadline = std::time::Instant::now() + Duration::from_secs(5);
+        while observed.lock().unwrap().len() < 4 && std::time::Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        monitor.stop();
+
+        let observed = observed.lock().unwrap();
+        assert_eq!(
+            observed[..4],
+            [
+                HealthState::Degraded,
+                HealthState::Degraded,
+                HealthState::Down,
+                HealthState::Healthy
+            ]
+        );
+        assert_eq!(monitor.status().state, *observed.last().unwrap());
+    }
+}
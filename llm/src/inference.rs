@@ -1,100 +1,688 @@
 //! LLM inference and prompt management.
 
-use crate::client::LlamafileClient;
-use lucastra_core::Result;
+use crate::conversation::Message;
+use crate::health::HealthHandle;
+use crate::providers::llamafile::LlamafileProvider;
+use crate::providers::{
+    create_provider, CompletionRequest, CompletionResponse, LLMProvider, ProviderConfig,
+    ProviderError, ProviderResult, StopReason,
+};
+use crate::rag::{cited_sources, RagPromptBuilder};
+use crate::rate_limit::{estimate_tokens, RequestClass};
+use crate::streaming::{StreamChunk, StreamError, StreamResult};
+use crate::usage::UsageRecord;
+use futures::{Stream, StreamExt};
+use lucastra_core::{LuCastraError, Result, SourceRef};
 use serde::{Deserialize, Serialize};
-use tracing::info;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, OnceLock};
+use std::time::Duration;
+use tokio::runtime::Runtime;
+use tracing::field::Empty;
+use tracing::{info, info_span, warn, Instrument, Span};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InferenceRequest {
     pub prompt: String,
     pub max_tokens: Option<usize>,
     pub temperature: Option<f32>,
-    pub context: Option<Vec<String>>, // Retrieved context snippets for RAG
+    /// Retrieved search results for RAG, given to the model as numbered sources.
+    pub context: Option<Vec<SourceRef>>,
+    /// Earlier turns of the conversation the prompt continues, oldest first.
+    #[serde(default)]
+    pub history: Vec<Message>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct InferenceResponse {
     pub text: String,
     pub stop_reason: String,
+    /// Context sources the answer cited, in order of first citation.
+    #[serde(default)]
+    pub sources: Vec<SourceRef>,
 }
 
-/// LLM service that wraps the provider interface.
+/// Provider streams, boxed like [`LLMProvider::complete_stream`] returns them.
+pub type ChunkStream = Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>;
+
+/// A server that takes longer than this to answer a health check counts as
+/// down.
+const HEALTH_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runtime the blocking methods run on. It is shared so the provider's
+/// connection pool lives as long as the service instead of one call.
+fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_multi_thread()
+            .worker_threads(2)
+            .thread_name("lucastra-llm")
+            .enable_all()
+            .build()
+            .expect("Failed to start the LLM runtime")
+    })
+}
+
+/// LLM service answering [`InferenceRequest`]s with an [`LLMProvider`].
+///
+/// The async methods are the interface; the `_blocking` ones run them on
+/// a shared runtime for callers without one, and must not be called from
+/// async code. Cloning is cheap and shares the provider.
+#[derive(Clone)]
 pub struct LLMService {
-    client: LlamafileClient, // Legacy client for backward compatibility
-    system_prompt: String,
+    provider: Arc<dyn LLMProvider>,
+    /// Where requests go, for messages: the endpoint, else the provider name.
+    endpoint: String,
+    prompts: RagPromptBuilder,
+    /// Lets calls fail fast while a monitor reports the server down.
+    health: Option<HealthHandle>,
+    /// Answers instead of `provider` while it is down or unreachable.
+    offline: Option<Arc<dyn LLMProvider>>,
+    /// Whether the last answer came from `offline`.
+    answered_offline: Arc<AtomicBool>,
 }
 
 impl LLMService {
+    /// Service for the llamafile server at `endpoint`.
     pub fn new(endpoint: String) -> Self {
+        Self::with_provider(Box::new(LlamafileProvider::new(endpoint.clone())), endpoint)
+    }
+
+    /// Service for the provider `config` describes, fallbacks included.
+    pub fn from_config(config: ProviderConfig) -> ProviderResult<Self> {
+        let endpoint = config
+            .endpoint
+            .clone()
+            .unwrap_or_else(|| config.provider.clone());
+        let provider = runtime().block_on(create_provider(config))?;
+        Ok(Self::with_provider(provider, endpoint))
+    }
+
+    /// Service answering with `provider`, described as `endpoint` in errors.
+    pub fn with_provider(provider: Box<dyn LLMProvider>, endpoint: impl Into<String>) -> Self {
         Self {
-            client: LlamafileClient::new(endpoint),
-            system_prompt: "You are a helpful assistant embedded in an OS. Answer questions concisely and accurately.".to_string(),
+            provider: Arc::from(provider),
+            endpoint: endpoint.into(),
+            prompts: RagPromptBuilder::default(),
+            health: None,
+            offline: None,
+            answered_offline: Arc::new(AtomicBool::new(false)),
         }
     }
 
-    /// Check if the LLM server is online (blocking for backward compatibility).
-    pub fn health_check(&self) -> Result<bool> {
-        self.client
-            .health_check()
-            .map_err(|e| lucastra_core::LuCastraError::ServiceError(e.to_string()))
+    /// Fail requests straight away while `health` reports the server down.
+    pub fn with_health(mut self, health: HealthHandle) -> Self {
+        self.health = Some(health);
+        self
+    }
+
+    /// Answer with `provider` instead of failing while the service's own
+    /// provider is down or unreachable, e.g. with placeholders from
+    /// [`MockProvider`](crate::providers::mock::MockProvider).
+    pub fn with_offline_provider(mut self, provider: Box<dyn LLMProvider>) -> Self {
+        self.offline = Some(Arc::from(provider));
+        self
+    }
+
+    /// Whether the last answer came from the offline provider.
+    pub fn answered_offline(&self) -> bool {
+        self.answered_offline.load(Ordering::Relaxed)
+    }
+
+    /// Base URL of the server, or the provider name when it has none.
+    pub fn endpoint(&self) -> &str {
+        &self.endpoint
+    }
+
+    /// Check if the provider is online. Answers `false` when it takes
+    /// longer than five seconds.
+    pub async fn health_check(&self) -> Result<bool> {
+        match tokio::time::timeout(HEALTH_TIMEOUT, self.provider.health_check()).await {
+            Ok(healthy) => Ok(healthy?),
+            Err(_) => Ok(false),
+        }
+    }
+
+    /// Blocking [`LLMService::health_check`].
+    pub fn health_check_blocking(&self) -> Result<bool> {
+        runtime().block_on(self.health_check())
     }
 
     /// Perform inference with optional RAG context.
-    pub fn infer(&self, request: InferenceRequest) -> Result<InferenceResponse> {
-        let prompt = self.build_prompt(&request.prompt, request.context.clone());
+    ///
+    /// Fails with [`LuCastraError::LlmUnavailable`] when the server can't be
+    /// reached or is known to be down, unless there is an offline provider.
+    pub async fn infer(&self, request: InferenceRequest) -> Result<InferenceResponse> {
+        let completion = self.completion_request(&request, false);
+
+        info!("LLM inference request: {} chars", completion.prompt.len());
 
-        info!("LLM inference request: {} chars", prompt.len());
+        let response = self.complete(completion).await?;
+        Ok(InferenceResponse {
+            sources: cited_sources(
+                &response.content,
+                request.context.as_deref().unwrap_or_default(),
+            ),
+            text: response.content,
+            stop_reason: response.stop_reason.as_str().to_string(),
+        })
+    }
+
+    /// Perform inference, returning the provider's chunks as they arrive.
+    /// Providers that can't stream answer in a single chunk.
+    ///
+    /// Fails like [`LLMService::infer`] when the server is unavailable.
+    pub async fn infer_stream(&self, request: &InferenceRequest) -> Result<ChunkStream> {
+        let completion = self.completion_request(request, true);
+
+        info!(
+            "LLM streaming inference request: {} chars",
+            completion.prompt.len()
+        );
+
+        let unavailable = match self.ensure_available() {
+            Ok(()) => match self
+                .provider
+                .complete_stream(completion.clone())
+                .instrument(completion_span(self.provider.as_ref(), &completion))
+                .await
+            {
+                Ok(stream) => {
+                    self.answered_offline.store(false, Ordering::Relaxed);
+                    return Ok(stream);
+                }
+                Err(ProviderError::UnsupportedError(_)) => {
+                    let response = self
+                        .complete(CompletionRequest {
+                            stream: false,
+                            ..completion
+                        })
+                        .await?;
+                    return Ok(Box::pin(futures::stream::once(async move {
+                        Ok(StreamChunk {
+                            delta: response.content,
+                            finish_reason: Some(response.stop_reason),
+                        })
+                    })));
+                }
+                Err(e) => self.provider_error(e),
+            },
+            Err(e) => e,
+        };
+        let offline = self.offline_provider(unavailable)?;
+        let span = completion_span(offline.as_ref(), &completion);
+        offline
+            .complete_stream(completion)
+            .instrument(span)
+            .await
+            .map_err(|e| self.provider_error(e))
+    }
+
+    /// Blocking [`LLMService::infer`].
+    pub fn infer_blocking(&self, request: InferenceRequest) -> Result<InferenceResponse> {
+        runtime().block_on(self.infer(request))
+    }
 
-        let max_tokens = request.max_tokens.unwrap_or(256) as i32;
-        let temperature = request.temperature.unwrap_or(0.7);
+    /// Blocking [`LLMService::infer_stream`], passing each piece of
+    /// generated text to `on_delta` as it arrives. Generation stops early
+    /// once `on_delta` returns `false`.
+    pub fn infer_stream_blocking(
+        &self,
+        request: InferenceRequest,
+        mut on_delta: impl FnMut(&str) -> bool,
+    ) -> Result<InferenceResponse> {
+        let context = request.context.as_deref().unwrap_or_default();
+        runtime().block_on(async {
+            let mut stream = self.infer_stream(&request).await?;
 
-        // Try to call the LLM server, fall back to mock if unavailable
-        match self
-            .client
-            .complete(&prompt, Some(max_tokens), Some(temperature))
-        {
-            Ok(text) => Ok(InferenceResponse {
+            let mut text = String::new();
+            while let Some(chunk) = stream.next().await {
+                let chunk = chunk.map_err(|e| match e {
+                    StreamError::ConnectionClosed => self.unavailable(e),
+                    e => LuCastraError::ServiceError(e.to_string()),
+                })?;
+                text.push_str(&chunk.delta);
+                let keep_going = chunk.delta.is_empty() || on_delta(&chunk.delta);
+                if let Some(reason) = chunk.finish_reason {
+                    return Ok(InferenceResponse {
+                        sources: cited_sources(&text, context),
+                        text,
+                        stop_reason: reason.as_str().to_string(),
+                    });
+                }
+                if !keep_going {
+                    info!("LLM streaming inference stopped by caller");
+                    break;
+                }
+            }
+            Ok(InferenceResponse {
+                sources: cited_sources(&text, context),
                 text,
-                stop_reason: "complete".to_string(),
-            }),
-            Err(e) => {
-                info!("LLM server unavailable, using mock response: {}", e);
-                let mock_response = format!(
-                    "Mock response to: {}{}",
-                    request.prompt,
-                    if request.context.is_some() {
-                        " [with retrieved context]"
-                    } else {
-                        ""
-                    }
+                stop_reason: StopReason::Stop.as_str().to_string(),
+            })
+        })
+    }
+
+    /// Estimated usage of answering `request` with `response`. Providers
+    /// don't all report token counts, so they are estimated from the prompt
+    /// and answer text.
+    pub fn usage(
+        &self,
+        request: &InferenceRequest,
+        response: &InferenceResponse,
+    ) -> Option<UsageRecord> {
+        let prompt = self.build_prompt(request);
+        let provider = match &self.offline {
+            Some(offline) if self.answered_offline() => offline,
+            _ => &self.provider,
+        };
+        Some(UsageRecord {
+            provider: provider.name().to_string(),
+            model: provider.default_model().to_string(),
+            class: RequestClass::Completion,
+            input_tokens: estimate_tokens(&prompt) as u64,
+            output_tokens: estimate_tokens(&response.text) as u64,
+            cost_usd: 0.0,
+            estimated: true,
+            timestamp: chrono::Utc::now().timestamp(),
+        })
+    }
+
+    /// Complete with the provider, or with the offline provider while it
+    /// is unavailable.
+    async fn complete(&self, completion: CompletionRequest) -> Result<CompletionResponse> {
+        let unavailable = match self.ensure_available() {
+            Ok(()) => match complete_with(self.provider.as_ref(), completion.clone()).await {
+                Ok(response) => {
+                    self.answered_offline.store(false, Ordering::Relaxed);
+                    return Ok(response);
+                }
+                Err(e) => self.provider_error(e),
+            },
+            Err(e) => e,
+        };
+        complete_with(self.offline_provider(unavailable)?.as_ref(), completion)
+            .await
+            .map_err(|e| self.provider_error(e))
+    }
+
+    /// The offline provider to answer with after `error`, or `error` when
+    /// it isn't an outage or there is none.
+    fn offline_provider(&self, error: LuCastraError) -> Result<&Arc<dyn LLMProvider>> {
+        match (&self.offline, error) {
+            (Some(offline), LuCastraError::LlmUnavailable(reason)) => {
+                warn!(
+                    "LLM unavailable, answering with {}: {}",
+                    offline.name(),
+                    reason
                 );
-                Ok(InferenceResponse {
-                    text: mock_response,
-                    stop_reason: "mock".to_string(),
-                })
+                self.answered_offline.store(true, Ordering::Relaxed);
+                Ok(offline)
             }
+            (_, error) => Err(error),
         }
     }
 
-    /// Build a prompt with optional RAG context.
-    fn build_prompt(&self, query: &str, context: Option<Vec<String>>) -> String {
-        let mut prompt = format!("{}\n\n", self.system_prompt);
-
-        if let Some(docs) = context {
-            prompt.push_str("## Context\n");
-            for (i, doc) in docs.iter().enumerate() {
-                prompt.push_str(&format!("{}. {}\n", i + 1, doc));
+    fn ensure_available(&self) -> Result<()> {
+        match &self.health {
+            Some(health) if health.is_down() => {
+                Err(self.unavailable("server is not answering health checks"))
             }
-            prompt.push('\n');
+            _ => Ok(()),
         }
+    }
 
-        prompt.push_str(&format!("## User Query\n{}\n\n## Answer", query));
-        prompt
+    fn unavailable(&self, reason: impl std::fmt::Display) -> LuCastraError {
+        LuCastraError::LlmUnavailable(format!("{} ({})", self.endpoint(), reason))
+    }
+
+    fn provider_error(&self, error: ProviderError) -> LuCastraError {
+        match error {
+            ProviderError::RequestError(e) => self.unavailable(e),
+            e => e.into(),
+        }
+    }
+
+    /// The provider request for `request`, its context and history in the
+    /// prompt. Unset limits take the provider defaults.
+    fn completion_request(&self, request: &InferenceRequest, stream: bool) -> CompletionRequest {
+        let defaults = CompletionRequest::default();
+        CompletionRequest {
+            prompt: self.build_prompt(request),
+            max_tokens: request.max_tokens.or(defaults.max_tokens),
+            temperature: request.temperature.or(defaults.temperature),
+            stream,
+            ..defaults
+        }
+    }
+
+    /// Build a prompt with optional RAG context.
+    fn build_prompt(&self, request: &InferenceRequest) -> String {
+        self.prompts.build_with_history(
+            &request.history,
+            &request.prompt,
+            request.context.as_deref().unwrap_or_default(),
+        )
     }
 
     /// Set custom system prompt.
     pub fn set_system_prompt(&mut self, prompt: String) {
-        self.system_prompt = prompt;
+        self.prompts.set_system_prompt(prompt);
+    }
+}
+
+/// Span for one completion call to `provider`, with the estimated prompt
+/// tokens. Streams only cover opening the stream, so their answer's tokens
+/// aren't recorded.
+fn completion_span(provider: &dyn LLMProvider, completion: &CompletionRequest) -> Span {
+    info_span!(
+        "llm_complete",
+        provider = provider.name(),
+        model = provider.default_model(),
+        stream = completion.stream,
+        input_tokens = estimate_tokens(&completion.prompt),
+        output_tokens = Empty,
+        total_tokens = Empty,
+    )
+}
+
+/// `provider`'s answer to `completion`, traced by [`completion_span`] with
+/// the answer's estimated tokens and, when the provider reports them, the
+/// request's total tokens (prompt and answer).
+async fn complete_with(
+    provider: &dyn LLMProvider,
+    completion: CompletionRequest,
+) -> ProviderResult<CompletionResponse> {
+    let span = completion_span(provider, &completion);
+    let response = provider
+        .complete(completion)
+        .instrument(span.clone())
+        .await?;
+    span.record("output_tokens", estimate_tokens(&response.content));
+    if let Some(total_tokens) = response.tokens_used {
+        span.record("total_tokens", total_tokens);
+    }
+    if let Some(model) = &response.model {
+        span.record("model", model.as_str());
+    }
+    Ok(response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::mock::MockProvider;
+    use crate::providers::test_server::{serve, serve_sse};
+    use async_trait::async_trait;
+    use lucastra_core::SourceOrigin;
+    use std::sync::Mutex;
+
+    /// Answers "ok" and streams "a", "b", remembering the last request.
+    #[derive(Default)]
+    struct RecordingProvider {
+        last: Arc<Mutex<Option<CompletionRequest>>>,
+        streams: bool,
+    }
+
+    #[async_trait]
+    impl LLMProvider for RecordingProvider {
+        fn name(&self) -> &str {
+            "mock"
+        }
+
+        fn default_model(&self) -> &str {
+            "mock-model"
+        }
+
+        async fn health_check(&self) -> ProviderResult<bool> {
+            Ok(true)
+        }
+
+        async fn complete(&self, request: CompletionRequest) -> ProviderResult<CompletionResponse> {
+            *self.last.lock().unwrap() = Some(request);
+            Ok(CompletionResponse {
+                content: "ok".to_string(),
+                stop_reason: StopReason::Stop,
+                tokens_used: None,
+                model: None,
+            })
+        }
+
+        async fn complete_stream(&self, request: CompletionRequest) -> ProviderResult<ChunkStream> {
+            if !self.streams {
+                return Err(ProviderError::UnsupportedError("mock".to_string()));
+            }
+            *self.last.lock().unwrap() = Some(request);
+            let chunk = |delta: &str, finish_reason| {
+                Ok(StreamChunk {
+                    delta: delta.to_string(),
+                    finish_reason,
+                })
+            };
+            Ok(Box::pin(futures::stream::iter(vec![
+                chunk("a", None),
+                chunk("b", Some(StopReason::Length)),
+            ])))
+        }
+    }
+
+    fn request() -> InferenceRequest {
+        InferenceRequest {
+            prompt: "hi".to_string(),
+            max_tokens: Some(3),
+            temperature: None,
+            context: None,
+            history: Vec::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_requests_are_mapped_to_the_provider() {
+        let last = Arc::new(Mutex::new(None));
+        let llm = LLMService::with_provider(
+            Box::new(RecordingProvider {
+                last: last.clone(),
+                streams: false,
+            }),
+            "mock",
+        );
+        let source = SourceRef {
+            path: "/mnt/root/guide.txt".to_string(),
+            score: 1.0,
+            snippet: "LucAstra supports RAG.".to_string(),
+            origin: SourceOrigin::Index,
+        };
+
+        let response = llm
+            .infer(InferenceRequest {
+                max_tokens: Some(64),
+                temperature: Some(0.2),
+                context: Some(vec![source]),
+                ..request()
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.text, "ok");
+        assert_eq!(response.stop_reason, "stop");
+        let sent = last.lock().unwrap().take().unwrap();
+        assert_eq!(sent.max_tokens, Some(64));
+        assert_eq!(sent.temperature, Some(0.2));
+        assert!(!sent.stream);
+        let sources = sent.prompt.find("[1] /mnt/root/guide.txt").unwrap();
+        let snippet = sent.prompt.find("LucAstra supports RAG.").unwrap();
+        let query = sent.prompt.find("## User Query\nhi").unwrap();
+        assert!(sources < snippet && snippet < query);
+
+        llm.infer(InferenceRequest {
+            max_tokens: None,
+            ..request()
+        })
+        .await
+        .unwrap();
+        let sent = last.lock().unwrap().take().unwrap();
+        assert_eq!(sent.max_tokens, CompletionRequest::default().max_tokens);
+        assert_eq!(sent.temperature, CompletionRequest::default().temperature);
+        assert!(!sent.prompt.contains("## Sources"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_chunks_pass_through() {
+        let last = Arc::new(Mutex::new(None));
+        let llm = LLMService::with_provider(
+            Box::new(RecordingProvider {
+                last: last.clone(),
+                streams: true,
+            }),
+            "mock",
+        );
+
+        let chunks: Vec<_> = llm
+            .infer_stream(&request())
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].delta, "a");
+        assert!(chunks[0].finish_reason.is_none());
+        assert_eq!(chunks[1].delta, "b");
+        assert!(matches!(chunks[1].finish_reason, Some(StopReason::Length)));
+        assert!(last.lock().unwrap().take().unwrap().stream);
+    }
+
+    #[tokio::test]
+    async fn test_stream_without_provider_support_is_one_chunk() {
+        let llm = LLMService::with_provider(Box::<RecordingProvider>::default(), "mock");
+
+        let chunks: Vec<_> = llm.infer_stream(&request()).await.unwrap().collect().await;
+        assert_eq!(chunks.len(), 1);
+        let chunk = chunks[0].as_ref().unwrap();
+        assert_eq!(chunk.delta, "ok");
+        assert!(matches!(chunk.finish_reason, Some(StopReason::Stop)));
+    }
+
+    #[test]
+    fn test_from_config_builds_the_provider() {
+        let llm = LLMService::from_config(ProviderConfig {
+            endpoint: Some("http://127.0.0.1:1".to_string()),
+            ..ProviderConfig::default()
+        })
+        .unwrap();
+        assert_eq!(llm.endpoint(), "http://127.0.0.1:1");
+        assert!(!llm.health_check_blocking().unwrap());
+    }
+
+    #[test]
+    fn test_infer_attaches_cited_sources() {
+        let server = tokio::runtime::Runtime::new().unwrap();
+        let endpoint = server.block_on(serve(
+            200,
+            "application/json",
+            r#"{"choices":[{"text":"Rust powers it [2], with RAG [1]."}]}"#,
+        ));
+        let source = |path: &str| SourceRef {
+            path: path.to_string(),
+            score: 1.0,
+            snippet: format!("snippet of {}", path),
+            origin: SourceOrigin::Index,
+        };
+
+        let response = LLMService::new(endpoint)
+            .infer_blocking(InferenceRequest {
+                context: Some(vec![
+                    source("/mnt/root/guide.txt"),
+                    source("/mnt/root/readme.txt"),
+                ]),
+                ..request()
+            })
+            .unwrap();
+
+        let paths: Vec<_> = response.sources.iter().map(|s| s.path.as_str()).collect();
+        assert_eq!(paths, vec!["/mnt/root/readme.txt", "/mnt/root/guide.txt"]);
+    }
+
+    #[test]
+    fn test_unreachable_server_is_reported_as_unavailable() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let llm = LLMService::new(format!("http://127.0.0.1:{}", port));
+
+        assert!(matches!(
+            llm.infer_blocking(request()),
+            Err(LuCastraError::LlmUnavailable(_))
+        ));
+        assert!(matches!(
+            llm.infer_stream_blocking(request(), |_| true),
+            Err(LuCastraError::LlmUnavailable(_))
+        ));
+    }
+
+    #[test]
+    fn test_unreachable_server_falls_back_to_the_offline_provider() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let llm = LLMService::new(format!("http://127.0.0.1:{}", port))
+            .with_offline_provider(Box::new(MockProvider::new()));
+
+        let response = llm.infer_blocking(request()).unwrap();
+        assert!(response.text.starts_with("[placeholder]"));
+        assert!(response.text.ends_with("## User Query\nhi\n\n## Answer"));
+        assert!(llm.answered_offline());
+        assert_eq!(llm.usage(&request(), &response).unwrap().provider, "mock");
+
+        let streamed = llm.infer_stream_blocking(request(), |_| true).unwrap();
+        assert_eq!(streamed.text, response.text);
+    }
+
+    #[test]
+    fn test_infer_stream_reports_deltas_and_stop_reason() {
+        let server = tokio::runtime::Runtime::new().unwrap();
+        let endpoint = server.block_on(serve_sse(concat!(
+            "data: {\"content\":\"Hel\",\"stop\":false}\n\n",
+            "data: {\"content\":\"lo\",\"stop\":false}\n\n",
+            "data: {\"content\":\"\",\"stop\":true,\"stopped_limit\":true}\n\n",
+        )));
+
+        let mut deltas = Vec::new();
+        let response = LLMService::new(endpoint)
+            .infer_stream_blocking(request(), |delta| {
+                deltas.push(delta.to_string());
+                true
+            })
+            .unwrap();
+
+        assert_eq!(deltas, vec!["Hel", "lo"]);
+        assert_eq!(response.text, "Hello");
+        assert_eq!(response.stop_reason, "length");
+    }
+
+    #[test]
+    fn test_infer_stream_stops_when_caller_declines() {
+        let server = tokio::runtime::Runtime::new().unwrap();
+        let endpoint = server.block_on(serve_sse(concat!(
+            "data: {\"content\":\"one\",\"stop\":false}\n\n",
+            "data: {\"content\":\"two\",\"stop\":false}\n\n",
+            "data: {\"content\":\"\",\"stop\":true}\n\n",
+        )));
+
+        let mut deltas = 0;
+        let response = LLMService::new(endpoint)
+            .infer_stream_blocking(request(), |_| {
+                deltas += 1;
+                false
+            })
+            .unwrap();
+
+        assert_eq!(deltas, 1);
+        assert_eq!(response.text, "one");
+        assert_eq!(response.stop_reason, "stop");
     }
 }
@@ -4,23 +4,52 @@
 //! with async/await support, streaming responses, and embeddings generation.
 
 pub mod cache;
-pub mod client;
 pub mod conversation;
+pub mod conversation_store;
+pub mod embedding_pipeline;
+pub mod health;
 pub mod inference;
+pub mod prompt_template;
 pub mod providers;
+pub mod rag;
 pub mod rate_limit;
+pub mod server;
 pub mod streaming;
+pub mod usage;
+pub mod wire_log;
 
 pub use cache::{CacheError, CacheResult, EmbeddingCache};
-pub use client::LlamafileClient;
-pub use conversation::{Conversation, ConversationError, Message, Role};
-pub use inference::{InferenceRequest, InferenceResponse, LLMService};
+pub use conversation::{
+    merge_consecutive_roles, sanitize_title, validate_messages, Conversation, ConversationError,
+    ConversationResult, ExportFormat, Message, Role, CONVERSATION_VERSION,
+};
+pub use conversation_store::{ConversationStore, ConversationSummary};
+pub use embedding_pipeline::{
+    EmbeddingOutcome, EmbeddingPipeline, EmbeddingProgress, FailedEmbedding,
+};
+pub use health::{
+    HealthChecker, HealthHandle, HealthMonitor, HealthPolicy, HealthState, HealthStatus,
+};
+pub use inference::{ChunkStream, InferenceRequest, InferenceResponse, LLMService};
+pub use prompt_template::{
+    standard_variables, PromptError, PromptRegistry, PromptTemplate, TemplateSource,
+};
 pub use providers::{
-    CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse, LLMProvider,
-    ProviderConfig, ProviderError, ProviderResult, StopReason,
+    store_keyring_secret, CompletionRequest, CompletionResponse, EmbeddingRequest,
+    EmbeddingResponse, LLMProvider, ModelInfo, ProviderConfig, ProviderError, ProviderResult,
+    ProvidersConfig, StopReason,
+};
+pub use rag::{cited_sources, RagPromptBuilder};
+pub use rate_limit::{
+    estimate_tokens, Budget, RateLimiter, RateLimiters, RateLimits, RequestClass, Saturation,
+};
+pub use server::{
+    DownloadProgress, ModelSpec, ServerError, ServerManager, ServerOptions, ServerResult,
+    ServerStatus,
 };
-pub use rate_limit::RateLimiter;
 pub use streaming::{StreamChunk, StreamError, StreamResult, StreamableProvider};
+pub use usage::{ModelPrice, UsageError, UsageRecord, UsageResult, UsageTotals, UsageTracker};
+pub use wire_log::WireLog;
 
 use lucastra_core::Result;
 
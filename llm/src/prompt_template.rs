@@ -0,0 +1,465 @@
+//! System prompt templates.
+//!
+//! A template is text with `{name}` placeholders, filled in by
+//! [`PromptTemplate::render`]; `{{` and `}}` stand for literal braces.
+//! Templates ship built in and can be added or overridden by `.toml` or
+//! `.json` files in the prompts directory (by default
+//! `~/.lucastra/data/prompts`), named after the file:
+//!
+//! ```toml
+//! description = "Terse answers for the terminal"
+//! template = "You are LucAstra. Today is {date}. Answer {user_name} in one line."
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+use tracing::warn;
+
+/// Template used when none is configured.
+pub const DEFAULT_TEMPLATE: &str = lucastra_config::DEFAULT_SYSTEM_PROMPT_TEMPLATE;
+
+/// Template the tool-calling agent runs with. It needs the `tool_examples`,
+/// `first_tool_example`, `tool_schema`, `allowed_tools` and
+/// `final_answer_marker` variables.
+pub const AGENT_TEMPLATE: &str = "os_agent";
+
+const BUILTIN_TEMPLATES: &[(&str, &str, &str)] = &[
+    (
+        "assistant",
+        "General-purpose assistant",
+        "You are LucAstra, a helpful AI assistant integrated into an augmented operating system.",
+    ),
+    (
+        "coding",
+        "Programming help",
+        "You are LucAstra, a coding assistant for {user_name}. Today is {date}. \
+         Give working code with short explanations, point out bugs and edge cases, \
+         and say which language or tool versions you assume.",
+    ),
+    (
+        "document_qa",
+        "Questions about the user's documents",
+        "You are LucAstra, answering {user_name}'s questions about their documents. \
+         Today is {date}. Answer only from the documents you are given, quote them \
+         where it helps, and say so when they don't contain the answer.",
+    ),
+    (
+        AGENT_TEMPLATE,
+        "Tool-calling agent for the OS",
+        "You are the LucAstra OS assistant. To use tools, reply with only a JSON \
+         array of tool calls, for example:\n[{first_tool_example}]\n\n\
+         Available tools ({allowed_tools}):\n{tool_examples}\n\n\
+         Tool call schema:\n{tool_schema}\n\n\
+         Tool results will be sent back to you. When you can answer the user, \
+         reply with \"{final_answer_marker} <answer>\".",
+    ),
+];
+
+#[derive(Debug, Error)]
+pub enum PromptError {
+    #[error("prompt template not found: {0}")]
+    NotFound(String),
+
+    #[error("prompt template {template} uses unknown variable {{{variable}}}")]
+    UnknownVariable { template: String, variable: String },
+
+    #[error("prompt template {template} is malformed: {message}")]
+    Malformed { template: String, message: String },
+
+    #[error("failed to read prompt template {path}: {message}")]
+    Invalid { path: PathBuf, message: String },
+}
+
+pub type PromptResult<T> = std::result::Result<T, PromptError>;
+
+/// Where a template came from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TemplateSource {
+    BuiltIn,
+    File(PathBuf),
+}
+
+/// A named system prompt with `{variable}` placeholders.
+#[derive(Debug, Clone)]
+pub struct PromptTemplate {
+    pub name: String,
+    pub description: String,
+    pub template: String,
+    pub source: TemplateSource,
+}
+
+/// The contents of a template file.
+#[derive(Deserialize, Serialize)]
+struct TemplateFile {
+    #[serde(default)]
+    description: String,
+    template: String,
+}
+
+impl PromptTemplate {
+    pub fn new(name: impl Into<String>, template: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            description: String::new(),
+            template: template.into(),
+            source: TemplateSource::BuiltIn,
+        }
+    }
+
+    /// A built-in template by name.
+    pub fn builtin(name: &str) -> Option<Self> {
+        BUILTIN_TEMPLATES
+            .iter()
+            .find(|(builtin, _, _)| *builtin == name)
+            .map(|(name, description, template)| Self {
+                name: name.to_string(),
+                description: description.to_string(),
+                template: template.to_string(),
+                source: TemplateSource::BuiltIn,
+            })
+    }
+
+    /// Read a `.toml` or `.json` template file, named after its file stem.
+    pub fn from_file(path: &Path) -> PromptResult<Self> {
+        let invalid = |message: String| PromptError::Invalid {
+            path: path.to_path_buf(),
+            message,
+        };
+        let name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .filter(|s| !s.is_empty())
+            .ok_or_else(|| invalid("file name is not a template name".to_string()))?;
+        let contents = fs::read_to_string(path).map_err(|e| invalid(e.to_string()))?;
+        let file: TemplateFile = match path.extension().and_then(|e| e.to_str()) {
+            Some("toml") => toml::from_str(&contents).map_err(|e| invalid(e.to_string()))?,
+            Some("json") => serde_json::from_str(&contents).map_err(|e| invalid(e.to_string()))?,
+            _ => return Err(invalid("expected a .toml or .json file".to_string())),
+        };
+        Ok(Self {
+            name: name.to_string(),
+            description: file.description,
+            template: file.template,
+            source: TemplateSource::File(path.to_path_buf()),
+        })
+    }
+
+    /// Names of the variables the template uses, in order of first use.
+    pub fn variables(&self) -> PromptResult<Vec<String>> {
+        let mut names: Vec<String> = Vec::new();
+        for piece in self.pieces()? {
+            if let Piece::Variable(name) = piece {
+                if !names.iter().any(|n| n == name) {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        Ok(names)
+    }
+
+    /// Fill in every `{name}` from `variables`. A placeholder without a
+    /// value fails with [`PromptError::UnknownVariable`]; unused values are
+    /// ignored.
+    pub fn render(&self, variables: &HashMap<String, String>) -> PromptResult<String> {
+        let mut out = String::with_capacity(self.template.len());
+        for piece in self.pieces()? {
+            match piece {
+                Piece::Text(text) => out.push_str(text),
+                Piece::Variable(name) => match variables.get(name) {
+                    Some(value) => out.push_str(value),
+                    None => {
+                        return Err(PromptError::UnknownVariable {
+                            template: self.name.clone(),
+                            variable: name.to_string(),
+                        })
+                    }
+                },
+            }
+        }
+        Ok(out)
+    }
+
+    /// Split the template into literal text and placeholders.
+    fn pieces(&self) -> PromptResult<Vec<Piece<'_>>> {
+        let malformed = |message: String| PromptError::Malformed {
+            template: self.name.clone(),
+            message,
+        };
+        let template = self.template.as_str();
+        let mut pieces = Vec::new();
+        let mut text_start = 0;
+        let mut chars = template.char_indices().peekable();
+
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '{' | '}' if chars.peek().map(|&(_, next)| next) == Some(c) => {
+                    // An escaped brace: keep one of the pair
+                    pieces.push(Piece::Text(&template[text_start..=i]));
+                    chars.next();
+                    text_start = i + 2;
+                }
+                '{' => {
+                    let end = template[i..]
+                        .find('}')
+                        .map(|offset| i + offset)
+                        .ok_or_else(|| malformed(format!("unclosed {{ at byte {}", i)))?;
+                    let name = &template[i + 1..end];
+                    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                        return Err(malformed(format!(
+                            "invalid variable name {:?}; write {{{{ for a literal brace",
+                            name
+                        )));
+                    }
+                    pieces.push(Piece::Text(&template[text_start..i]));
+                    pieces.push(Piece::Variable(name));
+                    while chars.peek().is_some_and(|&(j, _)| j <= end) {
+                        chars.next();
+                    }
+                    text_start = end + 1;
+                }
+                '}' => {
+                    return Err(malformed(format!(
+                        "unmatched }} at byte {}; write }}}} for a literal brace",
+                        i
+                    )))
+                }
+                _ => {}
+            }
+        }
+        pieces.push(Piece::Text(&template[text_start..]));
+        Ok(pieces)
+    }
+}
+
+enum Piece<'a> {
+    Text(&'a str),
+    Variable(&'a str),
+}
+
+/// Values every caller can provide: `date` (today, `YYYY-MM-DD`) and
+/// `user_name` (from `USER` or `USERNAME`).
+pub fn standard_variables() -> HashMap<String, String> {
+    let user_name = std::env::var("USER")
+        .or_else(|_| std::env::var("USERNAME"))
+        .unwrap_or_else(|_| "the user".to_string());
+    HashMap::from([
+        (
+            "date".to_string(),
+            chrono::Local::now().format("%Y-%m-%d").to_string(),
+        ),
+        ("user_name".to_string(), user_name),
+    ])
+}
+
+/// The available templates: the built-in ones, shadowed by files of the
+/// same name in the prompts directory.
+#[derive(Debug, Clone)]
+pub struct PromptRegistry {
+    templates: BTreeMap<String, PromptTemplate>,
+}
+
+impl Default for PromptRegistry {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+impl PromptRegistry {
+    /// Only the built-in templates.
+    pub fn builtin() -> Self {
+        let templates = BUILTIN_TEMPLATES
+            .iter()
+            .filter_map(|(name, _, _)| PromptTemplate::builtin(name))
+            .map(|t| (t.name.clone(), t))
+            .collect();
+        Self { templates }
+    }
+
+    /// The built-in templates plus those in `dir`. A missing directory
+    /// leaves just the built-in ones; unreadable files are skipped with a
+    /// warning.
+    pub fn load(dir: &Path) -> Self {
+        let mut registry = Self::builtin();
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return registry,
+            Err(e) => {
+                warn!("Skipping prompts directory {}: {}", dir.display(), e);
+                return registry;
+            }
+        };
+
+        let mut paths: Vec<PathBuf> = entries.filter_map(|e| e.ok().map(|e| e.path())).collect();
+        paths.sort();
+        for path in paths {
+            if !matches!(
+                path.extension().and_then(|e| e.to_str()),
+                Some("toml" | "json")
+            ) {
+                continue;
+            }
+            match PromptTemplate::from_file(&path) {
+                Ok(template) => registry.insert(template),
+                Err(e) => warn!("{}", e),
+            }
+        }
+        registry
+    }
+
+    /// Add a template, replacing any of the same name.
+    pub fn insert(&mut self, template: PromptTemplate) {
+        self.templates.insert(template.name.clone(), template);
+    }
+
+    pub fn get(&self, name: &str) -> PromptResult<&PromptTemplate> {
+        self.templates
+            .get(name)
+            .ok_or_else(|| PromptError::NotFound(name.to_string()))
+    }
+
+    /// Every template, sorted by name.
+    pub fn list(&self) -> Vec<&PromptTemplate> {
+        self.templates.values().collect()
+    }
+
+    /// Render template `name` with `variables`.
+    pub fn render(&self, name: &str, variables: &HashMap<String, String>) -> PromptResult<String> {
+        self.get(name)?.render(variables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn vars(pairs: &[(&str, &str)]) -> HashMap<String, String> {
+        pairs
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn test_variables_are_substituted() {
+        let template = PromptTemplate::new(
+            "greeting",
+            "Hello {user_name}, today is {date}. {{literal}} and {user_name} again }}",
+        );
+        let rendered = template
+            .render(&vars(&[
+                ("user_name", "Ada"),
+                ("date", "2026-10-16"),
+                ("unused", "x"),
+            ]))
+            .unwrap();
+        assert_eq!(
+            rendered,
+            "Hello Ada, today is 2026-10-16. {literal} and Ada again }"
+        );
+        assert_eq!(template.variables().unwrap(), vec!["user_name", "date"]);
+
+        // Values are inserted as-is, braces and all
+        let json = PromptTemplate::new("schema", "Schema: {schema}");
+        assert_eq!(
+            json.render(&vars(&[("schema", "{\"type\":\"object\"}")]))
+                .unwrap(),
+            "Schema: {\"type\":\"object\"}"
+        );
+    }
+
+    #[test]
+    fn test_missing_variable_is_an_error() {
+        let template = PromptTemplate::new("greeting", "Hello {user_name}");
+        match template.render(&HashMap::new()) {
+            Err(PromptError::UnknownVariable { template, variable }) => {
+                assert_eq!(template, "greeting");
+                assert_eq!(variable, "user_name");
+            }
+            other => panic!("unexpected result: {:?}", other),
+        }
+
+        for malformed in ["Hello {user_name", "Hello {user name}", "a } b", "{}"] {
+            assert!(
+                matches!(
+                    PromptTemplate::new("bad", malformed).render(&HashMap::new()),
+                    Err(PromptError::Malformed { .. })
+                ),
+                "{}",
+                malformed
+            );
+        }
+    }
+
+    #[test]
+    fn test_file_templates_shadow_builtins() {
+        let dir = TempDir::new().unwrap();
+        fs::write(
+            dir.path().join("assistant.toml"),
+            "description = \"Mine\"\ntemplate = \"Custom for {user_name}\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("pirate.json"),
+            r#"{"template": "Talk like a pirate"}"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("broken.toml"), "template = ").unwrap();
+        fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let registry = PromptRegistry::load(dir.path());
+        let assistant = registry.get("assistant").unwrap();
+        assert_eq!(assistant.description, "Mine");
+        assert_eq!(
+            assistant.source,
+            TemplateSource::File(dir.path().join("assistant.toml"))
+        );
+        assert_eq!(
+            registry
+                .render("assistant", &vars(&[("user_name", "Ada")]))
+                .unwrap(),
+            "Custom for Ada"
+        );
+        assert_eq!(
+            registry.render("pirate", &HashMap::new()).unwrap(),
+            "Talk like a pirate"
+        );
+        assert_eq!(
+            registry.get("coding").unwrap().source,
+            TemplateSource::BuiltIn
+        );
+        assert!(registry.get("broken").is_err());
+
+        let names: Vec<&str> = registry.list().iter().map(|t| t.name.as_str()).collect();
+        assert_eq!(
+            names,
+            vec!["assistant", "coding", "document_qa", "os_agent", "pirate"]
+        );
+    }
+
+    #[test]
+    fn test_builtins_render_without_a_prompts_dir() {
+        let dir = TempDir::new().unwrap();
+        let registry = PromptRegistry::load(&dir.path().join("missing"));
+        let mut variables = standard_variables();
+        for name in [
+            "tool_examples",
+            "first_tool_example",
+            "tool_schema",
+            "allowed_tools",
+            "final_answer_marker",
+        ] {
+            variables.insert(name.to_string(), "x".to_string());
+        }
+        for template in registry.list() {
+            template.render(&variables).unwrap();
+        }
+        assert!(registry
+            .render(DEFAULT_TEMPLATE, &HashMap::new())
+            .unwrap()
+            .starts_with("You are LucAstra"));
+    }
+}
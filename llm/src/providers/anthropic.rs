@@ -1,6 +1,8 @@
 //! Anthropic Claude API provider implementation.
 
+use super::models::{collect_pages, ModelListCache, ModelPage};
 use super::*;
+use crate::wire_log;
 use reqwest::Client;
 use serde_json::{json, Value};
 
@@ -11,6 +13,7 @@ pub struct AnthropicProvider {
     api_key: String,
     base_url: String,
     model: String,
+    models: ModelListCache,
 }
 
 impl AnthropicProvider {
@@ -21,6 +24,7 @@ impl AnthropicProvider {
             api_key: api_key.into(),
             base_url: "https://api.anthropic.com".to_string(),
             model: "claude-3-5-sonnet-20241022".to_string(),
+            models: ModelListCache::default(),
         }
     }
 
@@ -35,6 +39,42 @@ impl AnthropicProvider {
         self.model = model.into();
         self
     }
+
+    /// The page of `/v1/models` after model `after_id`.
+    async fn model_page(&self, after_id: Option<String>) -> ProviderResult<ModelPage> {
+        let mut request = self
+            .client
+            .get(format!("{}/v1/models", self.base_url))
+            .header("x-api-key", &self.api_key)
+            .header("anthropic-version", "2023-06-01")
+            .query(&[("limit", "1000")]);
+        if let Some(after_id) = &after_id {
+            request = request.query(&[("after_id", after_id)]);
+        }
+        let (status, body) = send(request).await?;
+        if status == reqwest::StatusCode::UNAUTHORIZED {
+            return Err(ProviderError::AuthError("Invalid API key".to_string()));
+        }
+        check_status(status, &body)?;
+        parse_body(&body)
+    }
+}
+
+/// Send `request` and read the response body as text.
+async fn send(request: reqwest::RequestBuilder) -> ProviderResult<(reqwest::StatusCode, String)> {
+    wire_log::send_text("anthropic", request)
+        .await
+        .map_err(|e| ProviderError::RequestError(e.to_string()))
+}
+
+fn check_status(status: reqwest::StatusCode, body: &str) -> ProviderResult<()> {
+    if !status.is_success() {
+        return Err(ProviderError::RequestError(format!(
+            "HTTP {}: {}",
+            status, body
+        )));
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -72,34 +112,21 @@ impl LLMProvider for AnthropicProvider {
             "stop_sequences": request.stop_sequences,
         });
 
-        let response = self
+        let request = self
             .client
             .post(format!("{}/v1/messages", self.base_url))
             .header("x-api-key", &self.api_key)
             .header("anthropic-version", "2023-06-01")
             .header("content-type", "application/json")
-            .json(&body)
-            .send()
-            .await
-            .map_err(|e| ProviderError::RequestError(e.to_string()))?;
-
-        if !response.status().is_success() {
-            let status = response.status();
-            let error_text = response.text().await.unwrap_or_default();
-            return Err(ProviderError::RequestError(format!(
-                "HTTP {}: {}",
-                status, error_text
-            )));
-        }
+            .json(&body);
+        let (status, text) = send(request).await?;
+        check_status(status, &text)?;
 
-        let json: Value = response
-            .json()
-            .await
-            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        let json: Value = parse_body(&text)?;
 
         let content = json["content"][0]["text"]
             .as_str()
-            .ok_or_else(|| ProviderError::InvalidResponse("Missing content.text".to_string()))?
+            .ok_or_else(|| invalid_body("Missing content.text", &text))?
             .to_string();
 
         let stop_reason = match json["stop_reason"].as_str() {
@@ -117,6 +144,12 @@ impl LLMProvider for AnthropicProvider {
         })
     }
 
+    async fn list_models(&self) -> ProviderResult<Vec<ModelInfo>> {
+        self.models
+            .get_or_fetch(collect_pages(|after_id| self.model_page(after_id)))
+            .await
+    }
+
     fn supports_streaming(&self) -> bool {
         true
     }
@@ -150,6 +183,39 @@ mod tests {
         assert_eq!(provider.base_url, "https://custom.anthropic.com");
     }
 
+    #[tokio::test]
+    async fn test_list_models() {
+        let (base_url, requests) = crate::providers::test_server::serve_json_sequence(vec![
+            r#"{"data": [
+                {"type": "model", "id": "claude-3-5-sonnet-20241022",
+                 "display_name": "Claude 3.5 Sonnet", "max_input_tokens": 200000},
+                {"type": "model", "id": "claude-3-opus-20240229", "display_name": "Claude 3 Opus"}
+            ], "has_more": false, "first_id": "claude-3-5-sonnet-20241022",
+               "last_id": "claude-3-opus-20240229"}"#,
+        ])
+        .await;
+        let provider = AnthropicProvider::new("test-key").with_base_url(base_url);
+
+        let models = provider.list_models().await.unwrap();
+        assert_eq!(
+            models,
+            vec![
+                ModelInfo {
+                    id: "claude-3-5-sonnet-20241022".to_string(),
+                    context_length: Some(200_000),
+                },
+                ModelInfo {
+                    id: "claude-3-opus-20240229".to_string(),
+                    context_length: None,
+                },
+            ]
+        );
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec!["GET /v1/models?limit=1000 HTTP/1.1"]
+        );
+    }
+
     #[test]
     fn test_custom_model() {
         let provider = AnthropicProvider::new("test-key").with_model("claude-3-opus-20240229");
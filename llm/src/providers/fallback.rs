@@ -0,0 +1,369 @@
+//! Fallback provider that tries an ordered chain of providers.
+
+use super::{
+    CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse, LLMProvider,
+    ModelInfo, ProviderError, ProviderResult,
+};
+use crate::conversation::Message;
+use crate::streaming::{StreamChunk, StreamResult};
+use async_trait::async_trait;
+use futures::future::BoxFuture;
+use futures::Stream;
+use std::pin::Pin;
+use tracing::warn;
+
+/// Wraps an ordered list of providers and moves on to the next one when a
+/// provider is unhealthy or fails with a request error.
+///
+/// The provider that served a completion is recorded in
+/// `CompletionResponse.model` as `"<provider>:<model>"`.
+pub struct FallbackProvider {
+    providers: Vec<Box<dyn LLMProvider>>,
+}
+
+impl FallbackProvider {
+    /// Create a chain from providers in priority order.
+    pub fn new(providers: Vec<Box<dyn LLMProvider>>) -> ProviderResult<Self> {
+        if providers.is_empty() {
+            return Err(ProviderError::UnsupportedError(
+                "Fallback chain requires at least one provider".to_string(),
+            ));
+        }
+        Ok(Self { providers })
+    }
+
+    /// Names of the wrapped providers in priority order.
+    pub fn provider_names(&self) -> Vec<&str> {
+        self.providers.iter().map(|p| p.name()).collect()
+    }
+
+    /// Run `op` against each provider until one succeeds.
+    ///
+    /// Unhealthy providers and `RequestError`s move on to the next provider;
+    /// any other error is returned immediately.
+    async fn try_each<'s, T, F>(
+        &'s self,
+        operation: &str,
+        skip: impl Fn(&dyn LLMProvider) -> bool,
+        op: F,
+    ) -> ProviderResult<(&'s dyn LLMProvider, T)>
+    where
+        F: for<'p> Fn(&'p dyn LLMProvider) -> BoxFuture<'p, ProviderResult<T>>,
+    {
+        let mut failures = Vec::new();
+        let mut last_error = None;
+
+        for provider in &self.providers {
+            let provider = provider.as_ref();
+            if skip(provider) {
+                continue;
+            }
+
+            match provider.health_check().await {
+                Ok(true) => {}
+                Ok(false) => {
+                    warn!("{} unhealthy, skipping for {}", provider.name(), operation);
+                    failures.push(format!("{}: health check failed", provider.name()));
+                    continue;
+                }
+                Err(e) => {
+                    warn!("{} health check errored: {}", provider.name(), e);
+                    failures.push(format!("{}: {}", provider.name(), e));
+                    last_error = Some(e);
+                    continue;
+                }
+            }
+
+            match op(provider).await {
+                Ok(value) => return Ok((provider, value)),
+                Err(e @ ProviderError::RequestError(_)) => {
+                    warn!(
+                        "{} {} failed, trying next provider: {}",
+                        provider.name(),
+                        operation,
+                        e
+                    );
+                    failures.push(format!("{}: {}", provider.name(), e));
+                    last_error = Some(e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        Err(annotate(last_error, &failures, operation))
+    }
+}
+
+/// Re-wrap the last error with every provider's failure reason.
+fn annotate(
+    last_error: Option<ProviderError>,
+    failures: &[String],
+    operation: &str,
+) -> ProviderError {
+    let summary = if failures.is_empty() {
+        format!("no provider supports {}", operation)
+    } else {
+        format!(
+            "all providers failed {} ({})",
+            operation,
+            failures.join("; ")
+        )
+    };
+
+    match last_error {
+        Some(ProviderError::InvalidResponse(msg)) => {
+            ProviderError::InvalidResponse(format!("{}: {}", msg, summary))
+        }
+        Some(ProviderError::AuthError(msg)) => {
+            ProviderError::AuthError(format!("{}: {}", msg, summary))
+        }
+        Some(ProviderError::RateLimitError(msg)) => {
+            ProviderError::RateLimitError(format!("{}: {}", msg, summary))
+        }
+        Some(ProviderError::UnsupportedError(msg)) => {
+            ProviderError::UnsupportedError(format!("{}: {}", msg, summary))
+        }
+        Some(ProviderError::RequestError(msg)) => {
+            ProviderError::RequestError(format!("{}: {}", msg, summary))
+        }
+        None if failures.is_empty() => ProviderError::UnsupportedError(summary),
+        None => ProviderError::RequestError(summary),
+    }
+}
+
+fn served_by(provider: &dyn LLMProvider, mut response: CompletionResponse) -> CompletionResponse {
+    let model = response
+        .model
+        .take()
+        .unwrap_or_else(|| provider.default_model().to_string());
+    response.model = Some(format!("{}:{}", provider.name(), model));
+    response
+}
+
+#[async_trait]
+impl LLMProvider for FallbackProvider {
+    fn name(&self) -> &str {
+        "fallback"
+    }
+
+    fn default_model(&self) -> &str {
+        self.providers[0].default_model()
+    }
+
+    fn embedding_model(&self) -> &str {
+        self.providers[0].embedding_model()
+    }
+
+    async fn health_check(&self) -> ProviderResult<bool> {
+        for provider in &self.providers {
+            if let Ok(true) = provider.health_check().await {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> ProviderResult<CompletionResponse> {
+        let (provider, response) = self
+            .try_each("completion", |_| false, |p| p.complete(request.clone()))
+            .await?;
+        Ok(served_by(provider, response))
+    }
+
+    async fn complete_chat(
+        &self,
+        messages: &[Message],
+        request: CompletionRequest,
+    ) -> ProviderResult<CompletionResponse> {
+        let messages = messages.to_vec();
+        let (provider, response) = self
+            .try_each(
+                "chat completion",
+                |_| false,
+                |p| {
+                    let messages = messages.clone();
+                    let request = request.clone();
+                    Box::pin(async move { p.complete_chat(&messages, request).await })
+                },
+            )
+            .await?;
+        Ok(served_by(provider, response))
+    }
+
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+    ) -> ProviderResult<Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>> {
+        let (_, stream) = self
+            .try_each(
+                "streaming completion",
+                |p| !p.supports_streaming(),
+                |p| p.complete_stream(request.clone()),
+            )
+            .await?;
+        Ok(stream)
+    }
+
+    async fn complete_chat_stream(
+        &self,
+        messages: &[Message],
+        request: CompletionRequest,
+    ) -> ProviderResult<Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>> {
+        let messages = messages.to_vec();
+        let (_, stream) = self
+            .try_each(
+                "streaming chat completion",
+                |p| !p.supports_streaming(),
+                |p| {
+                    let messages = messages.clone();
+                    let request = request.clone();
+                    Box::pin(async move { p.complete_chat_stream(&messages, request).await })
+                },
+            )
+            .await?;
+        Ok(stream)
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> ProviderResult<EmbeddingResponse> {
+        let (_, response) = self
+            .try_each(
+                "embedding",
+                |p| !p.supports_embeddings(),
+                |p| p.embed(request.clone()),
+            )
+            .await?;
+        Ok(response)
+    }
+
+    /// Models of the first provider, whose model is the one configured.
+    async fn list_models(&self) -> ProviderResult<Vec<ModelInfo>> {
+        self.providers[0].list_models().await
+    }
+
+    fn supports_streaming(&self) -> bool {
+        self.providers.iter().any(|p| p.supports_streaming())
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        self.providers.iter().any(|p| p.supports_embeddings())
+    }
+
+    /// A batch has to fit whichever provider ends up serving it.
+    fn max_embedding_batch(&self) -> usize {
+        self.providers
+            .iter()
+            .filter(|p| p.supports_embeddings())
+            .map(|p| p.max_embedding_batch())
+            .min()
+            .unwrap_or(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::StopReason;
+
+    struct MockProvider {
+        name: &'static str,
+        healthy: bool,
+        fail: bool,
+    }
+
+    #[async_trait]
+    impl LLMProvider for MockProvider {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn default_model(&self) -> &str {
+            "mock-model"
+        }
+
+        async fn health_check(&self) -> ProviderResult<bool> {
+            Ok(self.healthy)
+        }
+
+        async fn complete(
+            &self,
+            _request: CompletionRequest,
+        ) -> ProviderResult<CompletionResponse> {
+            if self.fail {
+                return Err(ProviderError::RequestError(
+                    "connection refused".to_string(),
+                ));
+            }
+            Ok(CompletionResponse {
+                content: format!("from {}", self.name),
+                stop_reason: StopReason::Complete,
+                tokens_used: None,
+                model: None,
+            })
+        }
+    }
+
+    fn mock(name: &'static str, healthy: bool, fail: bool) -> Box<dyn LLMProvider> {
+        Box::new(MockProvider {
+            name,
+            healthy,
+            fail,
+        })
+    }
+
+    #[tokio::test]
+    async fn test_falls_back_on_request_error() {
+        let chain = FallbackProvider::new(vec![
+            mock("primary", true, true),
+            mock("backup", true, false),
+        ])
+        .unwrap();
+
+        let response = chain.complete(CompletionRequest::default()).await.unwrap();
+        assert_eq!(response.content, "from backup");
+        assert_eq!(response.model.as_deref(), Some("backup:mock-model"));
+    }
+
+    #[tokio::test]
+    async fn test_skips_unhealthy_provider() {
+        let chain = FallbackProvider::new(vec![
+            mock("primary", false, false),
+            mock("backup", true, false),
+        ])
+        .unwrap();
+
+        let response = chain.complete(CompletionRequest::default()).await.unwrap();
+        assert_eq!(response.content, "from backup");
+    }
+
+    #[tokio::test]
+    async fn test_all_providers_fail() {
+        let chain = FallbackProvider::new(vec![
+            mock("primary", false, false),
+            mock("backup", true, true),
+        ])
+        .unwrap();
+
+        let err = chain
+            .complete(CompletionRequest::default())
+            .await
+            .unwrap_err();
+        let message = err.to_string();
+        assert!(matches!(err, ProviderError::RequestError(_)));
+        assert!(message.contains("primary: health check failed"));
+        assert!(message.contains("backup: request failed: connection refused"));
+    }
+
+    #[tokio::test]
+    async fn test_embed_without_support() {
+        let chain = FallbackProvider::new(vec![mock("primary", true, false)]).unwrap();
+
+        let err = chain
+            .embed(EmbeddingRequest {
+                texts: vec!["text".to_string()],
+                model: None,
+            })
+            .await
+            .unwrap_err();
+        assert!(matches!(err, ProviderError::UnsupportedError(_)));
+    }
+}
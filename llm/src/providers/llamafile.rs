@@ -1,11 +1,16 @@
 //! Llamafile provider implementation.
 
 use super::{
-    CompletionRequest, CompletionResponse, LLMProvider, ProviderError, ProviderResult, StopReason,
+    parse_body, CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse,
+    LLMProvider, ProviderError, ProviderResult, StopReason,
 };
+use crate::streaming::{SseBuffer, StreamChunk, StreamError, StreamResult};
+use crate::wire_log;
 use async_trait::async_trait;
+use futures::{Stream, StreamExt};
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
+use std::pin::Pin;
 use tracing::debug;
 
 #[derive(Debug, Clone, Serialize)]
@@ -17,15 +22,63 @@ struct LlamafileCompletionRequest {
     temperature: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     top_p: Option<f32>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
+/// Body of an OpenAI-compatible `/v1/completions` response.
 #[derive(Debug, Clone, Deserialize)]
 struct LlamafileCompletionResponse {
+    choices: Vec<LlamafileChoice>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LlamafileChoice {
+    text: String,
+    #[serde(default)]
+    finish_reason: Option<String>,
+}
+
+/// A single SSE event from the llama.cpp `/completion` endpoint.
+#[derive(Debug, Clone, Deserialize)]
+struct LlamafileStreamEvent {
+    #[serde(default)]
     content: String,
     #[serde(default)]
     stop: bool,
+    #[serde(default)]
+    stopped_limit: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LlamafileBatchEmbeddingRequest<'a> {
+    input: &'a [String],
+    model: &'a str,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LlamafileBatchEmbeddingResponse {
+    data: Vec<LlamafileEmbeddingData>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct LlamafileEmbeddingData {
+    embedding: Vec<f32>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct LlamafileEmbeddingRequest<'a> {
+    content: &'a str,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+struct LlamafileEmbeddingResponse {
+    embedding: Vec<f32>,
+}
+
+/// Maximum texts sent in one embedding request to stay under server body limits.
+const EMBED_BATCH_SIZE: usize = 32;
+
 /// Llamafile provider for local LLM inference.
 pub struct LlamafileProvider {
     endpoint: String,
@@ -42,6 +95,74 @@ impl LlamafileProvider {
                 .expect("Failed to create HTTP client"),
         }
     }
+
+    /// Embed a batch through the OpenAI-compatible `/v1/embeddings` endpoint.
+    /// Returns `UnsupportedError` when the server doesn't expose it.
+    async fn embed_batch(&self, texts: &[String], model: &str) -> ProviderResult<Vec<Vec<f32>>> {
+        let url = format!("{}/v1/embeddings", self.endpoint);
+        debug!("Sending {} texts to {}", texts.len(), url);
+
+        let request = self
+            .client
+            .post(&url)
+            .json(&LlamafileBatchEmbeddingRequest {
+                input: texts,
+                model,
+            });
+        let (status, body) = send(request).await?;
+
+        if status == reqwest::StatusCode::NOT_FOUND {
+            return Err(ProviderError::UnsupportedError(
+                "/v1/embeddings not available".to_string(),
+            ));
+        }
+        check_status(status)?;
+
+        let batch: LlamafileBatchEmbeddingResponse = parse_body(&body)?;
+
+        if batch.data.len() != texts.len() {
+            return Err(ProviderError::InvalidResponse(format!(
+                "Expected {} embeddings, got {}",
+                texts.len(),
+                batch.data.len()
+            )));
+        }
+
+        Ok(batch.data.into_iter().map(|d| d.embedding).collect())
+    }
+
+    /// Embed a single text through the native `/embedding` endpoint.
+    async fn embed_single(&self, text: &str) -> ProviderResult<Vec<f32>> {
+        let url = format!("{}/embedding", self.endpoint);
+
+        let request = self
+            .client
+            .post(&url)
+            .json(&LlamafileEmbeddingRequest { content: text });
+        let (status, body) = send(request).await?;
+        check_status(status)?;
+
+        let embedding: LlamafileEmbeddingResponse = parse_body(&body)?;
+
+        Ok(embedding.embedding)
+    }
+}
+
+/// Send `request` and read the response body as text.
+async fn send(request: reqwest::RequestBuilder) -> ProviderResult<(reqwest::StatusCode, String)> {
+    wire_log::send_text("llamafile", request)
+        .await
+        .map_err(|e| ProviderError::RequestError(e.to_string()))
+}
+
+fn check_status(status: reqwest::StatusCode) -> ProviderResult<()> {
+    if !status.is_success() {
+        return Err(ProviderError::RequestError(format!(
+            "Server returned status {}",
+            status
+        )));
+    }
+    Ok(())
 }
 
 #[async_trait]
@@ -68,49 +189,149 @@ impl LLMProvider for LlamafileProvider {
             n_predict: request.max_tokens.map(|t| t as i32),
             temperature: request.temperature,
             top_p: request.top_p,
+            stream: false,
         };
 
         let url = format!("{}/v1/completions", self.endpoint);
         debug!("Sending completion request to {}", url);
 
-        let resp = self
-            .client
-            .post(&url)
-            .json(&llamafile_req)
-            .send()
-            .await
-            .map_err(|e| ProviderError::RequestError(e.to_string()))?;
-
-        if !resp.status().is_success() {
-            return Err(ProviderError::RequestError(format!(
-                "Server returned status {}",
-                resp.status()
-            )));
-        }
+        let (status, body) = send(self.client.post(&url).json(&llamafile_req)).await?;
+        check_status(status)?;
 
-        let llamafile_resp: LlamafileCompletionResponse = resp
-            .json()
-            .await
-            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        let llamafile_resp: LlamafileCompletionResponse = parse_body(&body)?;
+        let choice = llamafile_resp
+            .choices
+            .into_iter()
+            .next()
+            .ok_or_else(|| ProviderError::InvalidResponse("No text in response".to_string()))?;
 
         Ok(CompletionResponse {
-            content: llamafile_resp.content,
-            stop_reason: if llamafile_resp.stop {
-                StopReason::Stop
-            } else {
-                StopReason::Complete
+            content: choice.text,
+            stop_reason: match choice.finish_reason.as_deref() {
+                Some("stop") => StopReason::Stop,
+                Some("length") => StopReason::Length,
+                _ => StopReason::Complete,
             },
             tokens_used: None,
             model: Some(self.default_model().to_string()),
         })
     }
 
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+    ) -> ProviderResult<Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>> {
+        let llamafile_req = LlamafileCompletionRequest {
+            prompt: request.prompt,
+            n_predict: request.max_tokens.map(|t| t as i32),
+            temperature: request.temperature,
+            top_p: request.top_p,
+            stream: true,
+        };
+
+        let url = format!("{}/completion", self.endpoint);
+        debug!("Sending streaming completion request to {}", url);
+
+        let resp = wire_log::send_stream("llamafile", self.client.post(&url).json(&llamafile_req))
+            .await
+            .map_err(|e| ProviderError::RequestError(e.to_string()))?;
+        check_status(resp.status())?;
+
+        let mut body = resp.bytes_stream();
+        let stream = async_stream::stream! {
+            let mut buffer = SseBuffer::new();
+            loop {
+                match body.next().await {
+                    Some(Ok(bytes)) => {
+                        for data in buffer.push(&bytes) {
+                            let event: LlamafileStreamEvent = match serde_json::from_str(&data) {
+                                Ok(event) => event,
+                                Err(e) => {
+                                    yield Err(StreamError::ParseError(e.to_string()));
+                                    return;
+                                }
+                            };
+
+                            if event.stop {
+                                let reason = if event.stopped_limit {
+                                    StopReason::Length
+                                } else {
+                                    StopReason::Stop
+                                };
+                                yield Ok(StreamChunk {
+                                    delta: event.content,
+                                    finish_reason: Some(reason),
+                                });
+                                return;
+                            }
+
+                            yield Ok(StreamChunk {
+                                delta: event.content,
+                                finish_reason: None,
+                            });
+                        }
+                    }
+                    Some(Err(e)) => {
+                        yield Err(StreamError::Error(e.to_string()));
+                        return;
+                    }
+                    None => {
+                        // Server hung up before sending `stop: true`
+                        yield Err(StreamError::ConnectionClosed);
+                        return;
+                    }
+                }
+            }
+        };
+
+        Ok(Box::pin(stream))
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> ProviderResult<EmbeddingResponse> {
+        if request.texts.is_empty() || request.texts.iter().any(|t| t.trim().is_empty()) {
+            return Err(ProviderError::InvalidResponse(
+                "Cannot embed empty text".to_string(),
+            ));
+        }
+
+        let model = request
+            .model
+            .unwrap_or_else(|| self.default_model().to_string());
+
+        let mut embeddings = Vec::with_capacity(request.texts.len());
+        for batch in request.texts.chunks(EMBED_BATCH_SIZE) {
+            match self.embed_batch(batch, &model).await {
+                Ok(vectors) => embeddings.extend(vectors),
+                Err(ProviderError::UnsupportedError(_)) => {
+                    // Older servers only have the per-text endpoint
+                    for text in batch {
+                        embeddings.push(self.embed_single(text).await?);
+                    }
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        let dimensions = embeddings.first().map(|e| e.len()).unwrap_or(0);
+        if dimensions == 0 || embeddings.iter().any(|e| e.len() != dimensions) {
+            return Err(ProviderError::InvalidResponse(
+                "Inconsistent embedding dimensions in response".to_string(),
+            ));
+        }
+
+        Ok(EmbeddingResponse {
+            embeddings,
+            model,
+            dimensions,
+        })
+    }
+
     fn supports_streaming(&self) -> bool {
-        false // Will add SSE support in later iteration
+        true
     }
 
     fn supports_embeddings(&self) -> bool {
-        false // Llamafile doesn't expose embeddings endpoint by default
+        true
     }
 }
 
@@ -122,8 +343,8 @@ mod tests {
     async fn test_provider_creation() {
         let provider = LlamafileProvider::new("http://localhost:8000".to_string());
         assert_eq!(provider.name(), "llamafile");
-        assert!(!provider.supports_streaming());
-        assert!(!provider.supports_embeddings());
+        assert!(provider.supports_streaming());
+        assert!(provider.supports_embeddings());
     }
 
     #[tokio::test]
@@ -133,4 +354,103 @@ mod tests {
         assert!(result.is_ok());
         assert!(!result.unwrap());
     }
+
+    #[tokio::test]
+    async fn test_complete_reads_openai_choices() {
+        let body = r#"{"choices": [{"text": "Hello", "finish_reason": "length"}]}"#;
+        let endpoint = crate::providers::test_server::serve(200, "application/json", body).await;
+        let provider = LlamafileProvider::new(endpoint);
+
+        let response = provider
+            .complete(CompletionRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(response.content, "Hello");
+        assert!(matches!(response.stop_reason, StopReason::Length));
+    }
+
+    #[tokio::test]
+    async fn test_stream_until_stop() {
+        let body = concat!(
+            "data: {\"content\":\"Hel\",\"stop\":false}\n\n",
+            "data: {\"content\":\"lo\",\"stop\":false}\n\n",
+            "data: {\"content\":\"\",\"stop\":true,\"stopped_limit\":true}\n\n",
+        );
+        let endpoint = crate::providers::test_server::serve_sse(body).await;
+        let provider = LlamafileProvider::new(endpoint);
+
+        let stream = provider
+            .complete_stream(CompletionRequest::default())
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.collect().await;
+
+        assert_eq!(chunks.len(), 3);
+        let text: String = chunks
+            .iter()
+            .map(|c| c.as_ref().unwrap().delta.clone())
+            .collect();
+        assert_eq!(text, "Hello");
+        assert!(matches!(
+            chunks[2].as_ref().unwrap().finish_reason,
+            Some(StopReason::Length)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_stream_connection_dropped() {
+        let body = "data: {\"content\":\"partial\",\"stop\":false}\n\ndata: {\"cont";
+        let endpoint = crate::providers::test_server::serve_sse(body).await;
+        let provider = LlamafileProvider::new(endpoint);
+
+        let stream = provider
+            .complete_stream(CompletionRequest::default())
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.collect().await;
+
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].as_ref().unwrap().delta, "partial");
+        assert!(matches!(chunks[1], Err(StreamError::ConnectionClosed)));
+    }
+
+    #[tokio::test]
+    async fn test_embed_batch() {
+        let body = r#"{"data": [{"embedding": [0.1, 0.2, 0.3]}, {"embedding": [0.4, 0.5, 0.6]}]}"#;
+        let endpoint = crate::providers::test_server::serve(200, "application/json", body).await;
+        let provider = LlamafileProvider::new(endpoint);
+
+        let response = provider
+            .embed(EmbeddingRequest {
+                texts: vec!["first".to_string(), "second".to_string()],
+                model: None,
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(response.embeddings.len(), 2);
+        assert_eq!(response.dimensions, 3);
+        assert_eq!(response.model, "llamafile-7b");
+    }
+
+    #[tokio::test]
+    async fn test_embed_empty_text() {
+        let provider = LlamafileProvider::new("http://localhost:9999".to_string());
+
+        let result = provider
+            .embed(EmbeddingRequest {
+                texts: vec!["ok".to_string(), "  ".to_string()],
+                model: None,
+            })
+            .await;
+        assert!(matches!(result, Err(ProviderError::InvalidResponse(_))));
+
+        let result = provider
+            .embed(EmbeddingRequest {
+                texts: vec![],
+                model: None,
+            })
+            .await;
+        assert!(matches!(result, Err(ProviderError::InvalidResponse(_))));
+    }
 }
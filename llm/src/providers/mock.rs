@@ -0,0 +1,237 @@
+//! Mock provider for running without a reachable model.
+//!
+//! Everything it returns is derived from the request alone, so the same
+//! prompt always gets the same answer and the same text the same embedding.
+
+use super::{
+    CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse, LLMProvider,
+    ProviderError, ProviderResult, StopReason,
+};
+use crate::streaming::{StreamChunk, StreamResult};
+use async_trait::async_trait;
+use futures::Stream;
+use sha2::{Digest, Sha256};
+use std::pin::Pin;
+
+/// Embedding size when none is configured.
+pub const DEFAULT_DIMENSIONS: usize = 384;
+
+/// Provider that is always available and answers with placeholders:
+/// completions echo the prompt and embeddings are derived from a hash of
+/// the text.
+#[derive(Debug, Clone)]
+pub struct MockProvider {
+    dimensions: usize,
+    /// `mock-<dimensions>`, so embeddings of different sizes are told apart.
+    embedding_model: String,
+}
+
+impl Default for MockProvider {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockProvider {
+    pub fn new() -> Self {
+        Self::new_with(DEFAULT_DIMENSIONS)
+    }
+
+    fn new_with(dimensions: usize) -> Self {
+        Self {
+            dimensions,
+            embedding_model: format!("mock-{}", dimensions),
+        }
+    }
+
+    /// Return embeddings of `dimensions` values.
+    pub fn with_dimensions(self, dimensions: usize) -> Self {
+        Self::new_with(dimensions)
+    }
+
+    fn answer(request: &CompletionRequest) -> String {
+        format!("[placeholder] {}", request.prompt)
+    }
+
+    /// A unit vector of `self.dimensions` values in -1.0..=1.0, taken from
+    /// SHA-256 of the text and a block counter.
+    fn embedding(&self, text: &str) -> Vec<f32> {
+        let mut values = Vec::with_capacity(self.dimensions);
+        let mut block = 0u64;
+        while values.len() < self.dimensions {
+            let digest = Sha256::new()
+                .chain_update(block.to_le_bytes())
+                .chain_update(text.as_bytes())
+                .finalize();
+            for bytes in digest.chunks_exact(4) {
+                let value = u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+                values.push(value as f32 / u32::MAX as f32 * 2.0 - 1.0);
+            }
+            block += 1;
+        }
+        values.truncate(self.dimensions);
+
+        let norm = values.iter().map(|v| v * v).sum::<f32>().sqrt();
+        if norm > 0.0 {
+            values.iter_mut().for_each(|v| *v /= norm);
+        }
+        values
+    }
+}
+
+#[async_trait]
+impl LLMProvider for MockProvider {
+    fn name(&self) -> &str {
+        "mock"
+    }
+
+    fn default_model(&self) -> &str {
+        "mock"
+    }
+
+    fn embedding_model(&self) -> &str {
+        &self.embedding_model
+    }
+
+    async fn health_check(&self) -> ProviderResult<bool> {
+        Ok(true)
+    }
+
+    async fn complete(&self, request: CompletionRequest) -> ProviderResult<CompletionResponse> {
+        Ok(CompletionResponse {
+            content: Self::answer(&request),
+            stop_reason: StopReason::Stop,
+            tokens_used: None,
+            model: Some(self.default_model().to_string()),
+        })
+    }
+
+    /// Streams the completion a word at a time.
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+    ) -> ProviderResult<Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>> {
+        let answer = Self::answer(&request);
+        let mut chunks: Vec<_> = answer
+            .split_inclusive(char::is_whitespace)
+            .map(|word| StreamChunk {
+                delta: word.to_string(),
+                finish_reason: None,
+            })
+            .collect();
+        if let Some(last) = chunks.last_mut() {
+            last.finish_reason = Some(StopReason::Stop);
+        }
+        Ok(Box::pin(futures::stream::iter(chunks.into_iter().map(Ok))))
+    }
+
+    async fn embed(&self, request: EmbeddingRequest) -> ProviderResult<EmbeddingResponse> {
+        if self.dimensions == 0 {
+            return Err(ProviderError::InvalidResponse(
+                "Mock embeddings need at least one dimension".to_string(),
+            ));
+        }
+        Ok(EmbeddingResponse {
+            embeddings: request.texts.iter().map(|t| self.embedding(t)).collect(),
+            model: request
+                .model
+                .unwrap_or_else(|| self.embedding_model.clone()),
+            dimensions: self.dimensions,
+        })
+    }
+
+    fn supports_streaming(&self) -> bool {
+        true
+    }
+
+    fn supports_embeddings(&self) -> bool {
+        true
+    }
+
+    fn max_embedding_batch(&self) -> usize {
+        usize::MAX
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    fn prompt(text: &str) -> CompletionRequest {
+        CompletionRequest {
+            prompt: text.to_string(),
+            ..CompletionRequest::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_completions_are_deterministic() {
+        let provider = MockProvider::new();
+        let first = provider
+            .complete(prompt("What is LucAstra?"))
+            .await
+            .unwrap();
+        let again = provider
+            .complete(prompt("What is LucAstra?"))
+            .await
+            .unwrap();
+        let other = provider.complete(prompt("Something else")).await.unwrap();
+
+        assert_eq!(first.content, again.content);
+        assert!(first.content.contains("What is LucAstra?"));
+        assert_ne!(first.content, other.content);
+        assert!(provider.health_check().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_stream_chunks_add_up_to_the_completion() {
+        let provider = MockProvider::new();
+        let complete = provider.complete(prompt("one two three")).await.unwrap();
+        let chunks: Vec<_> = provider
+            .complete_stream(prompt("one two three"))
+            .await
+            .unwrap()
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        assert!(chunks.len() > 1);
+        let text: String = chunks.iter().map(|c| c.delta.as_str()).collect();
+        assert_eq!(text, complete.content);
+        assert!(chunks[..chunks.len() - 1]
+            .iter()
+            .all(|c| c.finish_reason.is_none()));
+        assert!(matches!(
+            chunks.last().unwrap().finish_reason,
+            Some(StopReason::Stop)
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_embeddings_have_the_configured_dimension() {
+        let provider = MockProvider::new().with_dimensions(20);
+        let request = EmbeddingRequest {
+            texts: vec!["alpha".to_string(), "beta".to_string(), "alpha".to_string()],
+            model: None,
+        };
+        let response = provider.embed(request).await.unwrap();
+
+        assert_eq!(response.dimensions, 20);
+        assert_eq!(response.model, "mock-20");
+        assert!(response.embeddings.iter().all(|e| e.len() == 20));
+        assert_eq!(response.embeddings[0], response.embeddings[2]);
+        assert_ne!(response.embeddings[0], response.embeddings[1]);
+        let norm: f32 = response.embeddings[0].iter().map(|v| v * v).sum();
+        assert!((norm - 1.0).abs() < 1e-4);
+
+        let default = MockProvider::new()
+            .embed(EmbeddingRequest {
+                texts: vec!["alpha".to_string()],
+                model: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(default.embeddings[0].len(), DEFAULT_DIMENSIONS);
+    }
+}
@@ -4,15 +4,26 @@
 //! enabling runtime provider switching and multi-provider support.
 
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
 pub mod anthropic;
+pub mod fallback;
 pub mod llamafile;
+pub mod mock;
+pub mod models;
 pub mod openai;
 
+#[cfg(test)]
+pub(crate) mod test_server;
+
+use crate::conversation::{format_prompt, Message};
 use crate::streaming::{StreamChunk, StreamResult};
 use futures::Stream;
+pub use lucastra_config::{ProviderConfig, ProvidersConfig};
+use lucastra_core::{ErrorCode, LuCastraError};
+pub use models::ModelInfo;
 use std::pin::Pin;
 
 #[derive(Debug, Error)]
@@ -31,6 +42,47 @@ pub enum ProviderError {
 
 pub type ProviderResult<T> = Result<T, ProviderError>;
 
+impl ProviderError {
+    /// Code a command failing with this error reports.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::RequestError(_) => ErrorCode::LlmUnavailable,
+            Self::InvalidResponse(_) => ErrorCode::Internal,
+            Self::AuthError(_) => ErrorCode::AuthFailed,
+            Self::RateLimitError(_) => ErrorCode::RateLimited,
+            Self::UnsupportedError(_) => ErrorCode::Unsupported,
+        }
+    }
+}
+
+impl From<ProviderError> for LuCastraError {
+    fn from(error: ProviderError) -> Self {
+        LuCastraError::Provider {
+            code: error.code(),
+            source: Box::new(error),
+        }
+    }
+}
+
+/// Characters of a response body quoted in [`ProviderError::InvalidResponse`].
+const INVALID_BODY_EXCERPT: usize = 512;
+
+/// Parse a response `body` as JSON.
+pub(crate) fn parse_body<T: DeserializeOwned>(body: &str) -> ProviderResult<T> {
+    serde_json::from_str(body).map_err(|e| invalid_body(e, body))
+}
+
+/// A [`ProviderError::InvalidResponse`] for `body` quoting its start, with
+/// any API keys masked.
+pub(crate) fn invalid_body(error: impl std::fmt::Display, body: &str) -> ProviderError {
+    let excerpt: String = body.chars().take(INVALID_BODY_EXCERPT).collect();
+    ProviderError::InvalidResponse(format!(
+        "{}; body: {}",
+        error,
+        crate::wire_log::redact_text(&excerpt)
+    ))
+}
+
 /// Common request format for LLM completions.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CompletionRequest {
@@ -73,6 +125,18 @@ pub enum StopReason {
     Error,
 }
 
+impl StopReason {
+    /// The serialized name, e.g. `"length"`.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            StopReason::Complete => "complete",
+            StopReason::Length => "length",
+            StopReason::Stop => "stop",
+            StopReason::Error => "error",
+        }
+    }
+}
+
 /// Embedding request for generating vector representations.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct EmbeddingRequest {
@@ -111,6 +175,35 @@ pub trait LLMProvider: Send + Sync {
         )))
     }
 
+    /// Generate a completion from structured chat messages.
+    ///
+    /// `request.prompt` is ignored. The default implementation flattens the
+    /// messages into a role-prefixed prompt for completion-only providers.
+    async fn complete_chat(
+        &self,
+        messages: &[Message],
+        request: CompletionRequest,
+    ) -> ProviderResult<CompletionResponse> {
+        self.complete(CompletionRequest {
+            prompt: format_prompt(messages),
+            ..request
+        })
+        .await
+    }
+
+    /// Streaming variant of [`LLMProvider::complete_chat`].
+    async fn complete_chat_stream(
+        &self,
+        messages: &[Message],
+        request: CompletionRequest,
+    ) -> ProviderResult<Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>> {
+        self.complete_stream(CompletionRequest {
+            prompt: format_prompt(messages),
+            ..request
+        })
+        .await
+    }
+
     /// Generate embeddings for the given texts.
     /// Returns error with UnsupportedError if provider doesn't support embeddings.
     async fn embed(&self, _request: EmbeddingRequest) -> ProviderResult<EmbeddingResponse> {
@@ -130,38 +223,99 @@ pub trait LLMProvider: Send + Sync {
         false
     }
 
+    /// Most texts worth sending in one `embed` call. Providers whose API
+    /// takes one text per request keep the default of 1.
+    fn max_embedding_batch(&self) -> usize {
+        1
+    }
+
     /// Get the default model name for this provider.
     fn default_model(&self) -> &str;
+
+    /// Model `embed` uses when the request names none.
+    fn embedding_model(&self) -> &str {
+        self.default_model()
+    }
+
+    /// Models the provider offers, fetched at most once an hour.
+    /// Returns UnsupportedError if the provider can't list them.
+    async fn list_models(&self) -> ProviderResult<Vec<ModelInfo>> {
+        Err(ProviderError::UnsupportedError(format!(
+            "{} does not list its models",
+            self.name()
+        )))
+    }
 }
 
-/// Provider configuration from config file.
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct ProviderConfig {
-    pub provider: String,
-    pub api_key: Option<String>,
-    pub endpoint: Option<String>,
-    pub model: Option<String>,
-    pub temperature: Option<f32>,
-    pub max_tokens: Option<usize>,
-    pub timeout_secs: Option<u64>,
+/// OS keyring service that `api_key_keyring` entries are stored under.
+pub const KEYRING_SERVICE: &str = "lucastra";
+
+/// The API key to use for `config`: `api_key`, then the `api_key_env`
+/// variable, then the `api_key_keyring` entry (under [`KEYRING_SERVICE`]).
+/// Keys found indirectly are never stored in the config, so saving it can't
+/// leak them.
+pub fn resolve_api_key(config: &ProviderConfig) -> Option<String> {
+    config.resolve_api_key_with(|name| std::env::var(name).ok(), keyring_secret)
 }
 
-impl Default for ProviderConfig {
-    fn default() -> Self {
-        Self {
-            provider: "llamafile".to_string(),
-            api_key: None,
-            endpoint: Some("http://localhost:8000".to_string()),
-            model: None,
-            temperature: Some(0.7),
-            max_tokens: Some(256),
-            timeout_secs: Some(30),
+/// Look up `entry` in the OS keyring.
+#[cfg(feature = "keyring")]
+fn keyring_secret(entry: &str) -> Option<String> {
+    match keyring::Entry::new(KEYRING_SERVICE, entry).and_then(|e| e.get_password()) {
+        Ok(secret) => Some(secret),
+        Err(e) => {
+            tracing::warn!("No API key in keyring entry {}: {}", entry, e);
+            None
         }
     }
 }
 
+#[cfg(not(feature = "keyring"))]
+fn keyring_secret(entry: &str) -> Option<String> {
+    tracing::warn!(
+        "api_key_keyring = \"{}\" ignored: built without the keyring feature",
+        entry
+    );
+    None
+}
+
+/// Save `secret` as keyring entry `entry` (under [`KEYRING_SERVICE`]), for
+/// configs that reference it with `api_key_keyring`.
+#[cfg(feature = "keyring")]
+pub fn store_keyring_secret(entry: &str, secret: &str) -> ProviderResult<()> {
+    keyring::Entry::new(KEYRING_SERVICE, entry)
+        .and_then(|e| e.set_password(secret))
+        .map_err(|e| ProviderError::AuthError(format!("keyring entry {}: {}", entry, e)))
+}
+
+#[cfg(not(feature = "keyring"))]
+pub fn store_keyring_secret(entry: &str, _secret: &str) -> ProviderResult<()> {
+    Err(ProviderError::UnsupportedError(format!(
+        "can't save keyring entry {}: built without the keyring feature",
+        entry
+    )))
+}
+
 /// Factory function to create a provider from config.
+///
+/// When `fallbacks` are configured the result is a [`fallback::FallbackProvider`]
+/// chain with this provider first. Fallbacks nested inside fallbacks are ignored.
 pub async fn create_provider(config: ProviderConfig) -> ProviderResult<Box<dyn LLMProvider>> {
+    if config.fallbacks.is_empty() {
+        return build_provider(config);
+    }
+
+    let mut config = config;
+    let fallbacks = std::mem::take(&mut config.fallbacks);
+
+    let mut chain = vec![build_provider(config)?];
+    for fallback in fallbacks {
+        chain.push(build_provider(fallback)?);
+    }
+    Ok(Box::new(fallback::FallbackProvider::new(chain)?))
+}
+
+fn build_provider(config: ProviderConfig) -> ProviderResult<Box<dyn LLMProvider>> {
     match config.provider.as_str() {
         "llamafile" => {
             let endpoint = config
@@ -170,8 +324,10 @@ pub async fn create_provider(config: ProviderConfig) -> ProviderResult<Box<dyn L
             Ok(Box::new(llamafile::LlamafileProvider::new(endpoint)))
         }
         "openai" => {
-            let api_key = config.api_key.ok_or_else(|| {
-                ProviderError::AuthError("OpenAI requires api_key in config".to_string())
+            let api_key = resolve_api_key(&config).ok_or_else(|| {
+                ProviderError::AuthError(
+                    "OpenAI requires api_key, api_key_env or api_key_keyring in config".to_string(),
+                )
             })?;
             let mut provider = openai::OpenAIProvider::new(api_key, config.model)?;
             if let Some(endpoint) = config.endpoint {
@@ -180,8 +336,11 @@ pub async fn create_provider(config: ProviderConfig) -> ProviderResult<Box<dyn L
             Ok(Box::new(provider))
         }
         "anthropic" => {
-            let api_key = config.api_key.ok_or_else(|| {
-                ProviderError::AuthError("Anthropic requires api_key in config".to_string())
+            let api_key = resolve_api_key(&config).ok_or_else(|| {
+                ProviderError::AuthError(
+                    "Anthropic requires api_key, api_key_env or api_key_keyring in config"
+                        .to_string(),
+                )
             })?;
             let mut provider = anthropic::AnthropicProvider::new(api_key);
             if let Some(endpoint) = config.endpoint {
@@ -192,9 +351,76 @@ pub async fn create_provider(config: ProviderConfig) -> ProviderResult<Box<dyn L
             }
             Ok(Box::new(provider))
         }
+        "mock" => {
+            let dimensions = config.dimensions.unwrap_or(mock::DEFAULT_DIMENSIONS);
+            Ok(Box::new(
+                mock::MockProvider::new().with_dimensions(dimensions),
+            ))
+        }
         _ => Err(ProviderError::UnsupportedError(format!(
             "Unknown provider: {}",
             config.provider
         ))),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_create_provider_with_fallbacks() {
+        let config: ProviderConfig = serde_json::from_str(
+            r#"{
+                "provider": "openai",
+                "api_key": "test-key",
+                "endpoint": null,
+                "model": null,
+                "temperature": null,
+                "max_tokens": null,
+                "timeout_secs": null,
+                "fallbacks": [{
+                    "provider": "llamafile",
+                    "api_key": null,
+                    "endpoint": "http://localhost:8000",
+                    "model": null,
+                    "temperature": null,
+                    "max_tokens": null,
+                    "timeout_secs": null
+                }]
+            }"#,
+        )
+        .unwrap();
+
+        let provider = create_provider(config).await.unwrap();
+        assert_eq!(provider.name(), "fallback");
+        assert_eq!(provider.default_model(), "gpt-4o-mini");
+    }
+
+    #[tokio::test]
+    async fn test_create_mock_provider() {
+        let provider = create_provider(ProviderConfig {
+            provider: "mock".to_string(),
+            dimensions: Some(8),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        assert_eq!(provider.name(), "mock");
+
+        let response = provider
+            .embed(EmbeddingRequest {
+                texts: vec!["text".to_string()],
+                model: None,
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.dimensions, 8);
+    }
+
+    #[tokio::test]
+    async fn test_create_provider_without_fallbacks() {
+        let provider = create_provider(ProviderConfig::default()).await.unwrap();
+        assert_eq!(provider.name(), "llamafile");
+    }
+}
@@ -0,0 +1,111 @@
+//! Model lists fetched from provider APIs.
+//!
+//! OpenAI and Anthropic both page their `/models` endpoint the same way: a
+//! `data` array, `has_more`, and the id of the last model to continue after.
+
+use super::{ProviderError, ProviderResult};
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a fetched model list is reused.
+pub const MODEL_LIST_TTL: Duration = Duration::from_secs(60 * 60);
+
+/// Most pages fetched for one list, in case a server never stops paging.
+const MAX_MODEL_PAGES: usize = 50;
+
+/// A model a provider offers.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ModelInfo {
+    pub id: String,
+    /// Tokens the model accepts, when the API says.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub context_length: Option<usize>,
+}
+
+/// One page of a `/models` response.
+#[derive(Debug, Clone, Deserialize)]
+pub(crate) struct ModelPage {
+    data: Vec<ApiModel>,
+    #[serde(default)]
+    has_more: bool,
+    #[serde(default)]
+    last_id: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct ApiModel {
+    id: String,
+    /// Named differently by each API and by OpenAI-compatible servers
+    #[serde(
+        default,
+        alias = "context_window",
+        alias = "max_input_tokens",
+        alias = "max_model_len"
+    )]
+    context_length: Option<usize>,
+}
+
+/// Every model of a paged `/models` endpoint. `page` fetches the page
+/// following the model id it is given, or the first page for `None`.
+pub(crate) async fn collect_pages<F, Fut>(mut page: F) -> ProviderResult<Vec<ModelInfo>>
+where
+    F: FnMut(Option<String>) -> Fut,
+    Fut: Future<Output = ProviderResult<ModelPage>>,
+{
+    let mut models = Vec::new();
+    let mut cursor = None;
+    for _ in 0..MAX_MODEL_PAGES {
+        let ModelPage {
+            data,
+            has_more,
+            last_id,
+        } = page(cursor.take()).await?;
+        let last_id = last_id.or_else(|| data.last().map(|model| model.id.clone()));
+        models.extend(data.into_iter().map(|model| ModelInfo {
+            id: model.id,
+            context_length: model.context_length,
+        }));
+        match last_id {
+            Some(last_id) if has_more => cursor = Some(last_id),
+            _ => return Ok(models),
+        }
+    }
+    Err(ProviderError::InvalidResponse(format!(
+        "model list still paging after {} pages",
+        MAX_MODEL_PAGES
+    )))
+}
+
+/// A model list and when it was fetched.
+type FetchedModels = Option<(Instant, Vec<ModelInfo>)>;
+
+/// The last model list a provider fetched, reused for [`MODEL_LIST_TTL`].
+/// Failed fetches aren't cached.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct ModelListCache {
+    cached: Arc<Mutex<FetchedModels>>,
+}
+
+impl ModelListCache {
+    pub(crate) async fn get_or_fetch(
+        &self,
+        fetch: impl Future<Output = ProviderResult<Vec<ModelInfo>>>,
+    ) -> ProviderResult<Vec<ModelInfo>> {
+        if let Some((fetched, models)) = &*self.lock() {
+            if fetched.elapsed() < MODEL_LIST_TTL {
+                return Ok(models.clone());
+            }
+        }
+        let models = fetch.await?;
+        *self.lock() = Some((Instant::now(), models.clone()));
+        Ok(models)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, FetchedModels> {
+        self.cached
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
@@ -1,17 +1,27 @@
 //! OpenAI provider implementation.
 
+use super::models::{collect_pages, ModelListCache, ModelPage};
 use super::{
-    CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse, LLMProvider,
-    ProviderError, ProviderResult, StopReason,
+    parse_body, CompletionRequest, CompletionResponse, EmbeddingRequest, EmbeddingResponse,
+    LLMProvider, ModelInfo, ProviderError, ProviderResult, StopReason,
 };
+use crate::conversation::{Message, Role};
+use crate::streaming::{SseBuffer, StreamChunk, StreamError, StreamResult};
+use crate::wire_log;
 use async_trait::async_trait;
+use futures::{Stream, StreamExt};
 use reqwest::{
     header::{HeaderMap, HeaderValue, AUTHORIZATION, CONTENT_TYPE},
     Client,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use std::pin::Pin;
 use tracing::debug;
 
+/// Inputs per embeddings request; the API allows more, but large batches
+/// risk hitting the request size limit.
+const OPENAI_EMBED_BATCH_SIZE: usize = 256;
+
 #[derive(Debug, Clone, Serialize)]
 struct OpenAICompletionRequest {
     model: String,
@@ -24,6 +34,8 @@ struct OpenAICompletionRequest {
     top_p: Option<f32>,
     #[serde(skip_serializing_if = "Option::is_none")]
     stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -38,6 +50,71 @@ struct OpenAIChoice {
     finish_reason: Option<String>,
 }
 
+#[derive(Debug, Clone, Serialize)]
+struct OpenAIChatRequest<'a> {
+    model: String,
+    messages: Vec<OpenAIChatMessage<'a>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    stream: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+struct OpenAIChatMessage<'a> {
+    role: &'a Role,
+    content: &'a str,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAIChatResponse {
+    choices: Vec<OpenAIChatChoice>,
+    usage: Option<OpenAIUsage>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAIChatChoice {
+    message: OpenAIChatResponseMessage,
+    finish_reason: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAIChatResponseMessage {
+    #[serde(default)]
+    content: Option<String>,
+}
+
+/// A single SSE event from a streamed `/chat/completions` request.
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAIChatStreamEvent {
+    choices: Vec<OpenAIChatStreamChoice>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAIChatStreamChoice {
+    delta: OpenAIChatResponseMessage,
+    finish_reason: Option<String>,
+}
+
+/// A single SSE event from a streamed `/completions` request.
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAIStreamEvent {
+    choices: Vec<OpenAIStreamChoice>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+struct OpenAIStreamChoice {
+    #[serde(default)]
+    text: String,
+    finish_reason: Option<String>,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 struct OpenAIUsage {
     total_tokens: usize,
@@ -67,6 +144,7 @@ pub struct OpenAIProvider {
     embedding_model: String,
     client: Client,
     pub(crate) base_url: String,
+    models: ModelListCache,
 }
 
 impl OpenAIProvider {
@@ -91,6 +169,7 @@ impl OpenAIProvider {
             embedding_model: "text-embedding-3-small".to_string(),
             client,
             base_url: "https://api.openai.com/v1".to_string(),
+            models: ModelListCache::default(),
         })
     }
 
@@ -98,6 +177,155 @@ impl OpenAIProvider {
         self.base_url = base_url;
         self
     }
+
+    fn chat_request<'a>(
+        &self,
+        messages: &'a [Message],
+        request: CompletionRequest,
+        stream: bool,
+    ) -> OpenAIChatRequest<'a> {
+        OpenAIChatRequest {
+            model: self.model.clone(),
+            messages: messages
+                .iter()
+                .map(|m| OpenAIChatMessage {
+                    role: &m.role,
+                    content: &m.content,
+                })
+                .collect(),
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            stop: request.stop_sequences,
+            stream,
+        }
+    }
+
+    /// POST `body` and parse the JSON response.
+    async fn post_json<T: DeserializeOwned, B: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> ProviderResult<T> {
+        self.fetch(self.client.post(url).json(body)).await
+    }
+
+    /// POST `body` for a response that is streamed back.
+    async fn post_stream<B: Serialize + ?Sized>(
+        &self,
+        url: &str,
+        body: &B,
+    ) -> ProviderResult<reqwest::Response> {
+        let resp = wire_log::send_stream("openai", self.client.post(url).json(body))
+            .await
+            .map_err(map_send_error)?;
+        let status = resp.status();
+        if !status.is_success() {
+            let body = resp.text().await.unwrap_or_default();
+            return Err(status_error(status, &body));
+        }
+        Ok(resp)
+    }
+
+    /// Send `request` and parse the JSON response.
+    async fn fetch<T: DeserializeOwned>(
+        &self,
+        request: reqwest::RequestBuilder,
+    ) -> ProviderResult<T> {
+        let (status, body) = wire_log::send_text("openai", request)
+            .await
+            .map_err(map_send_error)?;
+        if !status.is_success() {
+            return Err(status_error(status, &body));
+        }
+        parse_body(&body)
+    }
+
+    /// The page of `/models` after model `after`.
+    async fn model_page(&self, after: Option<String>) -> ProviderResult<ModelPage> {
+        let mut request = self.client.get(format!("{}/models", self.base_url));
+        if let Some(after) = &after {
+            request = request.query(&[("after", after)]);
+        }
+        self.fetch(request).await
+    }
+}
+
+fn status_error(status: reqwest::StatusCode, body: &str) -> ProviderError {
+    ProviderError::RequestError(format!("OpenAI API returned {}: {}", status, body))
+}
+
+fn map_finish_reason(reason: Option<&str>) -> StopReason {
+    match reason {
+        Some("stop") => StopReason::Stop,
+        Some("length") => StopReason::Length,
+        _ => StopReason::Complete,
+    }
+}
+
+fn map_send_error(e: reqwest::Error) -> ProviderError {
+    if e.status() == Some(reqwest::StatusCode::UNAUTHORIZED) {
+        ProviderError::AuthError("Invalid API key".to_string())
+    } else if e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
+        ProviderError::RateLimitError("Rate limit exceeded".to_string())
+    } else {
+        ProviderError::RequestError(e.to_string())
+    }
+}
+
+/// Turn an OpenAI SSE body into a chunk stream.
+///
+/// `parse` extracts the delta text and optional finish reason from one `data:`
+/// payload; events without choices are skipped. The stream ends cleanly on
+/// `[DONE]`.
+fn sse_completion_stream<F>(
+    resp: reqwest::Response,
+    parse: F,
+) -> impl Stream<Item = StreamResult<StreamChunk>> + Send
+where
+    F: Fn(&str) -> serde_json::Result<Option<(String, Option<String>)>> + Send + 'static,
+{
+    let mut body = resp.bytes_stream();
+    async_stream::stream! {
+        let mut buffer = SseBuffer::new();
+        let mut finished = false;
+        loop {
+            match body.next().await {
+                Some(Ok(bytes)) => {
+                    for data in buffer.push(&bytes) {
+                        if data == "[DONE]" {
+                            return;
+                        }
+
+                        match parse(&data) {
+                            Ok(Some((delta, finish_reason))) => {
+                                let finish_reason = finish_reason
+                                    .map(|r| map_finish_reason(Some(&r)));
+                                finished |= finish_reason.is_some();
+                                yield Ok(StreamChunk { delta, finish_reason });
+                            }
+                            Ok(None) => {}
+                            Err(e) => {
+                                yield Err(StreamError::ParseError(e.to_string()));
+                                return;
+                            }
+                        }
+                    }
+                }
+                Some(Err(e)) => {
+                    yield Err(StreamError::Error(e.to_string()));
+                    return;
+                }
+                None => {
+                    // A finished completion without `[DONE]` is still usable
+                    if !finished {
+                        yield Err(StreamError::ConnectionClosed);
+                    }
+                    return;
+                }
+            }
+        }
+    }
 }
 
 #[async_trait]
@@ -110,6 +338,10 @@ impl LLMProvider for OpenAIProvider {
         &self.model
     }
 
+    fn embedding_model(&self) -> &str {
+        &self.embedding_model
+    }
+
     async fn health_check(&self) -> ProviderResult<bool> {
         let url = format!("{}/models", self.base_url);
         match self.client.get(&url).send().await {
@@ -126,51 +358,20 @@ impl LLMProvider for OpenAIProvider {
             temperature: request.temperature,
             top_p: request.top_p,
             stop: request.stop_sequences,
+            stream: false,
         };
 
         let url = format!("{}/completions", self.base_url);
         debug!("Sending OpenAI completion request to {}", url);
 
-        let resp = self
-            .client
-            .post(&url)
-            .json(&openai_req)
-            .send()
-            .await
-            .map_err(|e| {
-                if e.status() == Some(reqwest::StatusCode::UNAUTHORIZED) {
-                    ProviderError::AuthError("Invalid API key".to_string())
-                } else if e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
-                    ProviderError::RateLimitError("Rate limit exceeded".to_string())
-                } else {
-                    ProviderError::RequestError(e.to_string())
-                }
-            })?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::RequestError(format!(
-                "OpenAI API returned {}: {}",
-                status, body
-            )));
-        }
-
-        let openai_resp: OpenAICompletionResponse = resp
-            .json()
-            .await
-            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        let openai_resp: OpenAICompletionResponse = self.post_json(&url, &openai_req).await?;
 
         let choice = openai_resp
             .choices
             .first()
             .ok_or_else(|| ProviderError::InvalidResponse("No choices in response".to_string()))?;
 
-        let stop_reason = match choice.finish_reason.as_deref() {
-            Some("stop") => StopReason::Stop,
-            Some("length") => StopReason::Length,
-            _ => StopReason::Complete,
-        };
+        let stop_reason = map_finish_reason(choice.finish_reason.as_deref());
 
         Ok(CompletionResponse {
             content: choice.text.clone(),
@@ -180,6 +381,82 @@ impl LLMProvider for OpenAIProvider {
         })
     }
 
+    async fn complete_stream(
+        &self,
+        request: CompletionRequest,
+    ) -> ProviderResult<Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>> {
+        let openai_req = OpenAICompletionRequest {
+            model: self.model.clone(),
+            prompt: request.prompt,
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            stop: request.stop_sequences,
+            stream: true,
+        };
+
+        let url = format!("{}/completions", self.base_url);
+        debug!("Sending OpenAI streaming completion request to {}", url);
+
+        let resp = self.post_stream(&url, &openai_req).await?;
+
+        Ok(Box::pin(sse_completion_stream(resp, |data| {
+            let event: OpenAIStreamEvent = serde_json::from_str(data)?;
+            Ok(event
+                .choices
+                .into_iter()
+                .next()
+                .map(|c| (c.text, c.finish_reason)))
+        })))
+    }
+
+    async fn complete_chat(
+        &self,
+        messages: &[Message],
+        request: CompletionRequest,
+    ) -> ProviderResult<CompletionResponse> {
+        let chat_req = self.chat_request(messages, request, false);
+
+        let url = format!("{}/chat/completions", self.base_url);
+        debug!("Sending OpenAI chat completion request to {}", url);
+
+        let chat_resp: OpenAIChatResponse = self.post_json(&url, &chat_req).await?;
+
+        let choice =
+            chat_resp.choices.into_iter().next().ok_or_else(|| {
+                ProviderError::InvalidResponse("No choices in response".to_string())
+            })?;
+
+        Ok(CompletionResponse {
+            content: choice.message.content.unwrap_or_default(),
+            stop_reason: map_finish_reason(choice.finish_reason.as_deref()),
+            tokens_used: chat_resp.usage.map(|u| u.total_tokens),
+            model: Some(self.model.clone()),
+        })
+    }
+
+    async fn complete_chat_stream(
+        &self,
+        messages: &[Message],
+        request: CompletionRequest,
+    ) -> ProviderResult<Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>> {
+        let chat_req = self.chat_request(messages, request, true);
+
+        let url = format!("{}/chat/completions", self.base_url);
+        debug!("Sending OpenAI streaming chat request to {}", url);
+
+        let resp = self.post_stream(&url, &chat_req).await?;
+
+        Ok(Box::pin(sse_completion_stream(resp, |data| {
+            let event: OpenAIChatStreamEvent = serde_json::from_str(data)?;
+            Ok(event
+                .choices
+                .into_iter()
+                .next()
+                .map(|c| (c.delta.content.unwrap_or_default(), c.finish_reason)))
+        })))
+    }
+
     async fn embed(&self, request: EmbeddingRequest) -> ProviderResult<EmbeddingResponse> {
         let model = request
             .model
@@ -193,35 +470,7 @@ impl LLMProvider for OpenAIProvider {
         let url = format!("{}/embeddings", self.base_url);
         debug!("Sending OpenAI embedding request to {}", url);
 
-        let resp = self
-            .client
-            .post(&url)
-            .json(&openai_req)
-            .send()
-            .await
-            .map_err(|e| {
-                if e.status() == Some(reqwest::StatusCode::UNAUTHORIZED) {
-                    ProviderError::AuthError("Invalid API key".to_string())
-                } else if e.status() == Some(reqwest::StatusCode::TOO_MANY_REQUESTS) {
-                    ProviderError::RateLimitError("Rate limit exceeded".to_string())
-                } else {
-                    ProviderError::RequestError(e.to_string())
-                }
-            })?;
-
-        if !resp.status().is_success() {
-            let status = resp.status();
-            let body = resp.text().await.unwrap_or_default();
-            return Err(ProviderError::RequestError(format!(
-                "OpenAI API returned {}: {}",
-                status, body
-            )));
-        }
-
-        let openai_resp: OpenAIEmbeddingResponse = resp
-            .json()
-            .await
-            .map_err(|e| ProviderError::InvalidResponse(e.to_string()))?;
+        let openai_resp: OpenAIEmbeddingResponse = self.post_json(&url, &openai_req).await?;
 
         let embeddings: Vec<Vec<f32>> = openai_resp.data.into_iter().map(|d| d.embedding).collect();
 
@@ -236,13 +485,23 @@ impl LLMProvider for OpenAIProvider {
         })
     }
 
+    async fn list_models(&self) -> ProviderResult<Vec<ModelInfo>> {
+        self.models
+            .get_or_fetch(collect_pages(|after| self.model_page(after)))
+            .await
+    }
+
     fn supports_streaming(&self) -> bool {
-        true // OpenAI supports SSE streaming, will implement later
+        true
     }
 
     fn supports_embeddings(&self) -> bool {
         true
     }
+
+    fn max_embedding_batch(&self) -> usize {
+        OPENAI_EMBED_BATCH_SIZE
+    }
 }
 
 #[cfg(test)]
@@ -272,4 +531,134 @@ mod tests {
             .with_base_url("https://custom.openai.com/v1".to_string());
         assert_eq!(provider.base_url, "https://custom.openai.com/v1");
     }
+
+    #[tokio::test]
+    async fn test_list_models_follows_pages_and_is_cached() {
+        let (base_url, requests) = crate::providers::test_server::serve_json_sequence(vec![
+            r#"{"object": "list", "data": [{"id": "gpt-4o"}, {"id": "gpt-4o-mini"}],
+                "has_more": true, "last_id": "gpt-4o-mini"}"#,
+            r#"{"object": "list", "data": [{"id": "local-llama", "context_length": 8192}],
+                "has_more": false}"#,
+        ])
+        .await;
+        let provider = OpenAIProvider::new("test-key".to_string(), None)
+            .unwrap()
+            .with_base_url(base_url);
+
+        let models = provider.list_models().await.unwrap();
+        let ids: Vec<&str> = models.iter().map(|m| m.id.as_str()).collect();
+        assert_eq!(ids, vec!["gpt-4o", "gpt-4o-mini", "local-llama"]);
+        assert_eq!(models[0].context_length, None);
+        assert_eq!(models[2].context_length, Some(8192));
+        assert_eq!(
+            *requests.lock().unwrap(),
+            vec![
+                "GET /models HTTP/1.1",
+                "GET /models?after=gpt-4o-mini HTTP/1.1"
+            ]
+        );
+
+        // The server is gone; the list comes from the cache
+        assert_eq!(provider.list_models().await.unwrap(), models);
+    }
+
+    #[tokio::test]
+    async fn test_stream_chunk_ordering() {
+        let body = concat!(
+            "data: {\"choices\":[{\"text\":\"One\",\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"text\":\" two\",\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"text\":\" three\",\"finish_reason\":\"length\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let base_url = crate::providers::test_server::serve_sse(body).await;
+        let provider = OpenAIProvider::new("test-key".to_string(), None)
+            .unwrap()
+            .with_base_url(base_url);
+
+        let stream = provider
+            .complete_stream(CompletionRequest::default())
+            .await
+            .unwrap();
+        let chunks: Vec<StreamChunk> = stream.map(|c| c.unwrap()).collect().await;
+
+        let deltas: Vec<&str> = chunks.iter().map(|c| c.delta.as_str()).collect();
+        assert_eq!(deltas, vec!["One", " two", " three"]);
+        assert!(chunks[0].finish_reason.is_none());
+        assert!(matches!(chunks[2].finish_reason, Some(StopReason::Length)));
+    }
+
+    #[tokio::test]
+    async fn test_stream_malformed_json() {
+        let body = concat!(
+            "data: {\"choices\":[{\"text\":\"ok\",\"finish_reason\":null}]}\n\n",
+            "data: {not json}\n\n",
+        );
+        let base_url = crate::providers::test_server::serve_sse(body).await;
+        let provider = OpenAIProvider::new("test-key".to_string(), None)
+            .unwrap()
+            .with_base_url(base_url);
+
+        let stream = provider
+            .complete_stream(CompletionRequest::default())
+            .await
+            .unwrap();
+        let chunks: Vec<_> = stream.collect().await;
+
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].is_ok());
+        assert!(matches!(chunks[1], Err(StreamError::ParseError(_))));
+    }
+
+    #[tokio::test]
+    async fn test_complete_chat() {
+        let body = r#"{
+            "choices": [{"message": {"role": "assistant", "content": "Hi!"}, "finish_reason": "stop"}],
+            "usage": {"total_tokens": 12}
+        }"#;
+        let base_url = crate::providers::test_server::serve(200, "application/json", body).await;
+        let provider = OpenAIProvider::new("test-key".to_string(), None)
+            .unwrap()
+            .with_base_url(base_url);
+
+        let messages = vec![
+            Message::system("Be brief".to_string()),
+            Message::user("Hello".to_string()),
+        ];
+        let response = provider
+            .complete_chat(&messages, CompletionRequest::default())
+            .await
+            .unwrap();
+
+        assert_eq!(response.content, "Hi!");
+        assert!(matches!(response.stop_reason, StopReason::Stop));
+        assert_eq!(response.tokens_used, Some(12));
+    }
+
+    #[tokio::test]
+    async fn test_complete_chat_stream() {
+        let body = concat!(
+            "data: {\"choices\":[{\"delta\":{\"role\":\"assistant\"},\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{\"content\":\"Hi\"},\"finish_reason\":null}]}\n\n",
+            "data: {\"choices\":[{\"delta\":{},\"finish_reason\":\"stop\"}]}\n\n",
+            "data: [DONE]\n\n",
+        );
+        let base_url = crate::providers::test_server::serve_sse(body).await;
+        let provider = OpenAIProvider::new("test-key".to_string(), None)
+            .unwrap()
+            .with_base_url(base_url);
+
+        let messages = vec![Message::user("Hello".to_string())];
+        let stream = provider
+            .complete_chat_stream(&messages, CompletionRequest::default())
+            .await
+            .unwrap();
+        let chunks: Vec<StreamChunk> = stream.map(|c| c.unwrap()).collect().await;
+
+        let text: String = chunks.iter().map(|c| c.delta.as_str()).collect();
+        assert_eq!(text, "Hi");
+        assert!(matches!(
+            chunks.last().unwrap().finish_reason,
+            Some(StopReason::Stop)
+        ));
+    }
 }
@@ -0,0 +1,97 @@
+//! Minimal one-shot HTTP server for exercising providers in tests.
+
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Serve a single `text/event-stream` response and return the base URL.
+/// The connection is closed after `body` is written.
+pub(crate) async fn serve_sse(body: &'static str) -> String {
+    serve(200, "text/event-stream", body).await
+}
+
+/// Serve a single response with the given status and content type.
+pub(crate) async fn serve(status: u16, content_type: &'static str, body: &'static str) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (mut socket, _) = listener.accept().await.unwrap();
+        read_request(&mut socket).await;
+        respond(&mut socket, status, content_type, body).await;
+    });
+
+    format!("http://{}", addr)
+}
+
+/// Answer consecutive requests with `bodies` as JSON, one connection each.
+/// Returns the base URL and the request lines received (`GET /path?query
+/// HTTP/1.1`).
+pub(crate) async fn serve_json_sequence(
+    bodies: Vec<&'static str>,
+) -> (String, Arc<Mutex<Vec<String>>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let requests = Arc::new(Mutex::new(Vec::new()));
+
+    let seen = requests.clone();
+    tokio::spawn(async move {
+        for body in bodies {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let request = read_request(&mut socket).await;
+            let line = String::from_utf8_lossy(&request)
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            seen.lock().unwrap().push(line);
+            respond(&mut socket, 200, "application/json", body).await;
+        }
+    });
+
+    (format!("http://{}", addr), requests)
+}
+
+/// Drain the request headers and body before answering.
+async fn read_request(socket: &mut TcpStream) -> Vec<u8> {
+    let mut request = Vec::new();
+    let mut buf = [0u8; 4096];
+    loop {
+        let n = socket.read(&mut buf).await.unwrap_or(0);
+        if n == 0 {
+            break;
+        }
+        request.extend_from_slice(&buf[..n]);
+        if request_complete(&request) {
+            break;
+        }
+    }
+    request
+}
+
+async fn respond(socket: &mut TcpStream, status: u16, content_type: &str, body: &str) {
+    let head = format!(
+        "HTTP/1.1 {} OK\r\nContent-Type: {}\r\nConnection: close\r\n\r\n",
+        status, content_type
+    );
+    let _ = socket.write_all(head.as_bytes()).await;
+    let _ = socket.write_all(body.as_bytes()).await;
+    let _ = socket.shutdown().await;
+}
+
+fn request_complete(request: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(request);
+    let Some(header_end) = text.find("\r\n\r\n") else {
+        return false;
+    };
+    let content_length = text[..header_end]
+        .lines()
+        .find_map(|line| {
+            let (name, value) = line.split_once(':')?;
+            name.eq_ignore_ascii_case("content-length")
+                .then(|| value.trim().parse::<usize>().ok())
+                .flatten()
+        })
+        .unwrap_or(0);
+    request.len() >= header_end + 4 + content_length
+}
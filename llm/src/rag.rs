@@ -0,0 +1,214 @@
+//! Prompts for retrieval-augmented answers.
+//!
+//! [`RagPromptBuilder`] lays retrieved search results out as numbered
+//! sources and asks the model to cite them as `[1]`, `[2]`, …;
+//! [`cited_sources`] maps those citations in the answer back to the sources.
+//! Sources indexed from web pages are labelled with their URL, and text the
+//! user attached is marked as such.
+
+use crate::conversation::{format_prompt, Message};
+use lucastra_core::{SourceOrigin, SourceRef};
+
+const DEFAULT_SYSTEM_PROMPT: &str =
+    "You are a helpful assistant embedded in an OS. Answer questions concisely and accurately.";
+
+const CITATION_INSTRUCTIONS: &str = "Answer using the sources above where they help. \
+Cite each source you use by its number in square brackets, e.g. [1]. \
+If the sources don't cover the question, say so.";
+
+/// Builds the prompt for a query and the sources retrieved for it.
+#[derive(Debug, Clone)]
+pub struct RagPromptBuilder {
+    system_prompt: String,
+}
+
+impl Default for RagPromptBuilder {
+    fn default() -> Self {
+        Self::new(DEFAULT_SYSTEM_PROMPT)
+    }
+}
+
+impl RagPromptBuilder {
+    pub fn new(system_prompt: impl Into<String>) -> Self {
+        Self {
+            system_prompt: system_prompt.into(),
+        }
+    }
+
+    pub fn system_prompt(&self) -> &str {
+        &self.system_prompt
+    }
+
+    pub fn set_system_prompt(&mut self, prompt: String) {
+        self.system_prompt = prompt;
+    }
+
+    /// Prompt answering `query` from `sources`, numbered from 1 in order.
+    /// Without sources the model is not asked for citations.
+    pub fn build(&self, query: &str, sources: &[SourceRef]) -> String {
+        self.build_with_history(&[], query, sources)
+    }
+
+    /// Like [`RagPromptBuilder::build`], for a query following the earlier
+    /// turns in `history`.
+    pub fn build_with_history(
+        &self,
+        history: &[Message],
+        query: &str,
+        sources: &[SourceRef],
+    ) -> String {
+        let mut prompt = format!("{}\n\n", self.system_prompt);
+
+        if !history.is_empty() {
+            prompt.push_str(&format!(
+                "## Conversation So Far\n{}\n",
+                format_prompt(history)
+            ));
+        }
+
+        if !sources.is_empty() {
+            prompt.push_str("## Sources\n");
+            for (i, source) in sources.iter().enumerate() {
+                let origin = match source.origin {
+                    SourceOrigin::Index => "",
+                    SourceOrigin::Attachment => "Attached by the user: ",
+                };
+                prompt.push_str(&format!(
+                    "[{}] {}{}\n{}\n\n",
+                    i + 1,
+                    origin,
+                    source.label(),
+                    source.snippet.trim()
+                ));
+            }
+            prompt.push_str(CITATION_INSTRUCTIONS);
+            prompt.push_str("\n\n");
+        }
+
+        prompt.push_str(&format!("## User Query\n{}\n\n## Answer", query));
+        prompt
+    }
+}
+
+/// Sources cited in `answer`, in order of first citation. Accepts `[2]`,
+/// `[1][3]` and `[1, 3]`; numbers without a matching source are ignored.
+pub fn cited_sources(answer: &str, sources: &[SourceRef]) -> Vec<SourceRef> {
+    let mut cited: Vec<usize> = Vec::new();
+    let mut rest = answer;
+    while let Some(open) = rest.find('[') {
+        rest = &rest[open + 1..];
+        let Some(close) = rest.find(']') else {
+            break;
+        };
+        let numbers: Option<Vec<usize>> = rest[..close]
+            .split(',')
+            .map(|n| n.trim().parse().ok())
+            .collect();
+        for n in numbers.unwrap_or_default() {
+            if (1..=sources.len()).contains(&n) && !cited.contains(&n) {
+                cited.push(n);
+            }
+        }
+        rest = &rest[close + 1..];
+    }
+    cited.into_iter().map(|n| sources[n - 1].clone()).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn source(path: &str, snippet: &str) -> SourceRef {
+        SourceRef {
+            path: path.to_string(),
+            score: 1.0,
+            snippet: snippet.to_string(),
+            origin: SourceOrigin::Index,
+        }
+    }
+
+    #[test]
+    fn test_prompt_numbers_sources_in_order() {
+        let sources = vec![
+            source("/docs/a.txt", "alpha snippet"),
+            source("/docs/b.txt", "beta snippet"),
+            source("/docs/c.txt", "gamma snippet"),
+        ];
+        let prompt = RagPromptBuilder::default().build("what?", &sources);
+
+        let positions: Vec<usize> = [
+            "[1] /docs/a.txt\nalpha snippet",
+            "[2] /docs/b.txt\nbeta snippet",
+            "[3] /docs/c.txt\ngamma snippet",
+            "## User Query\nwhat?",
+        ]
+        .iter()
+        .map(|part| {
+            prompt
+                .find(part)
+                .unwrap_or_else(|| panic!("{part} missing"))
+        })
+        .collect();
+        assert!(positions.windows(2).all(|w| w[0] < w[1]));
+        assert!(prompt.contains("square brackets"));
+
+        let web = RagPromptBuilder::default().build(
+            "what?",
+            &[source("web://https://example.com/lanes", "web snippet")],
+        );
+        assert!(web.contains("[1] https://example.com/lanes\nweb snippet"));
+
+        let attached = SourceRef {
+            origin: SourceOrigin::Attachment,
+            ..source("/home/me/plan.txt", "attached snippet")
+        };
+        let mixed =
+            RagPromptBuilder::default().build("what?", &[attached, source("/docs/a.txt", "alpha")]);
+        assert!(mixed.contains(
+            "[1] Attached by the user: /home/me/plan.txt\nattached snippet\n\n[2] /docs/a.txt\nalpha"
+        ));
+
+        let plain = RagPromptBuilder::default().build("what?", &[]);
+        assert!(!plain.contains("## Sources"));
+        assert!(!plain.contains("square brackets"));
+    }
+
+    #[test]
+    fn test_history_comes_before_the_query() {
+        let history = [
+            Message::user("Which drives are mounted?".to_string()),
+            Message::assistant("/mnt/usb and /mnt/root.".to_string()),
+        ];
+        let prompt = RagPromptBuilder::default().build_with_history(
+            &history,
+            "And the second one?",
+            &[source("/docs/a.txt", "alpha snippet")],
+        );
+
+        let earlier = prompt
+            .find("## Conversation So Far\nUser: Which drives are mounted?\n\nAssistant: /mnt/usb")
+            .expect("history missing");
+        assert!(earlier < prompt.find("## Sources").unwrap());
+        assert!(prompt.ends_with("## User Query\nAnd the second one?\n\n## Answer"));
+        assert!(!RagPromptBuilder::default()
+            .build("what?", &[])
+            .contains("## Conversation"));
+    }
+
+    #[test]
+    fn test_citations_map_to_sources() {
+        let sources = vec![
+            source("/docs/a.txt", "alpha"),
+            source("/docs/b.txt", "beta"),
+            source("/docs/c.txt", "gamma"),
+        ];
+        let answer = "Beta says so [2]. Both agree [3][2], see also [1, 3], [7] and [note].";
+
+        let paths: Vec<String> = cited_sources(answer, &sources)
+            .into_iter()
+            .map(|s| s.path)
+            .collect();
+        assert_eq!(paths, vec!["/docs/b.txt", "/docs/c.txt", "/docs/a.txt"]);
+        assert!(cited_sources("No citations here.", &sources).is_empty());
+    }
+}
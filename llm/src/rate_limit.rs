@@ -1,78 +1,251 @@
 //! Rate limiting for API calls to prevent hitting provider limits.
+//!
+//! Each provider gets its own [`RateLimiter`] per [`RequestClass`], built
+//! from the [`RateLimits`] in its `ProviderConfig`. A limiter holds up to two
+//! token buckets: one counting requests and one counting (estimated) LLM
+//! tokens. Callers that would overdraw a bucket wait until it refills; the
+//! reservation is made up front, so queued callers are served in order with
+//! exactly the delay the budget demands.
 
-use std::sync::Arc;
-use std::time::{Duration, Instant};
-use tokio::sync::Mutex;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::time::Instant;
 
-/// Simple token bucket rate limiter.
+pub use lucastra_config::providers::{Budget, RateLimits, RequestClass};
+
+/// Rough token count for `text` (about four characters per token).
+pub fn estimate_tokens(text: &str) -> u32 {
+    text.chars().count().div_ceil(4) as u32
+}
+
+/// Bucket holding up to a minute's allowance, refilled continuously.
+///
+/// The level goes negative when callers reserve more than is available;
+/// they then wait for the deficit to refill.
+#[derive(Debug)]
+struct TokenBucket {
+    capacity: f64,
+    per_sec: f64,
+    level: f64,
+    updated: Instant,
+}
+
+impl TokenBucket {
+    fn per_minute(limit: u32, now: Instant) -> Self {
+        let capacity = limit.max(1) as f64;
+        Self {
+            capacity,
+            per_sec: capacity / 60.0,
+            level: capacity,
+            updated: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        self.level = (self.level + elapsed * self.per_sec).min(self.capacity);
+        self.updated = now;
+    }
+
+    /// Take `amount` and return how long until the bucket is out of debt.
+    fn reserve(&mut self, amount: f64, now: Instant) -> Duration {
+        self.refill(now);
+        self.level -= amount;
+        if self.level >= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.level / self.per_sec)
+        }
+    }
+
+    fn has(&mut self, amount: f64, now: Instant) -> bool {
+        self.refill(now);
+        self.level >= amount
+    }
+
+    /// Fraction of the allowance in use; above 1.0 when callers are queued.
+    fn saturation(&mut self, now: Instant) -> f64 {
+        self.refill(now);
+        1.0 - self.level / self.capacity
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    requests: Option<TokenBucket>,
+    tokens: Option<TokenBucket>,
+}
+
+/// Request and token budget for one provider and request class.
+#[derive(Debug)]
 pub struct RateLimiter {
-    tokens: Arc<Mutex<f64>>,
-    max_tokens: f64,
-    refill_rate: f64, // tokens per second
-    last_refill: Arc<Mutex<Instant>>,
+    buckets: Mutex<Buckets>,
 }
 
-impl RateLimiter {
-    /// Create a new rate limiter.
-    ///
-    /// # Arguments
-    /// * `requests_per_minute` - Maximum requests per minute
-    pub fn new(requests_per_minute: u32) -> Self {
-        let max_tokens = requests_per_minute as f64;
-        let refill_rate = requests_per_minute as f64 / 60.0; // tokens per second
+/// How much of a limiter's budget is in use.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Saturation {
+    /// Fraction of requests per minute in use, if limited.
+    pub requests: Option<f64>,
+    /// Fraction of tokens per minute in use, if limited.
+    pub tokens: Option<f64>,
+}
+
+impl Saturation {
+    /// The more saturated of the two budgets, or 0.0 when unlimited.
+    pub fn max(&self) -> f64 {
+        self.requests
+            .into_iter()
+            .chain(self.tokens)
+            .fold(0.0, f64::max)
+    }
+}
 
+impl RateLimiter {
+    /// Create a limiter enforcing `budget`.
+    pub fn new(budget: Budget) -> Self {
+        let now = Instant::now();
         Self {
-            tokens: Arc::new(Mutex::new(max_tokens)),
-            max_tokens,
-            refill_rate,
-            last_refill: Arc::new(Mutex::new(Instant::now())),
+            buckets: Mutex::new(Buckets {
+                requests: budget
+                    .requests_per_min
+                    .map(|limit| TokenBucket::per_minute(limit, now)),
+                tokens: budget
+                    .tokens_per_min
+                    .map(|limit| TokenBucket::per_minute(limit, now)),
+            }),
         }
     }
 
-    /// Wait until a token is available, then consume it.
-    pub async fn acquire(&self) {
-        loop {
-            {
-                let mut tokens = self.tokens.lock().await;
-                let mut last_refill = self.last_refill.lock().await;
-
-                // Refill tokens based on elapsed time
-                let now = Instant::now();
-                let elapsed = now.duration_since(*last_refill).as_secs_f64();
-                let new_tokens = elapsed * self.refill_rate;
-                *tokens = (*tokens + new_tokens).min(self.max_tokens);
-                *last_refill = now;
-
-                // If token available, consume and return
-                if *tokens >= 1.0 {
-                    *tokens -= 1.0;
-                    return;
-                }
-            }
+    /// A limiter that never waits.
+    pub fn unlimited() -> Self {
+        Self::new(Budget::default())
+    }
 
-            // Sleep a bit before retrying
-            tokio::time::sleep(Duration::from_millis(50)).await;
+    fn buckets(&self) -> std::sync::MutexGuard<'_, Buckets> {
+        self.buckets
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// Wait until one request costing `cost` tokens fits the budget, then
+    /// consume it.
+    pub async fn acquire(&self, cost: u32) {
+        let wait = {
+            let mut buckets = self.buckets();
+            let now = Instant::now();
+            let request_wait = buckets
+                .requests
+                .as_mut()
+                .map_or(Duration::ZERO, |b| b.reserve(1.0, now));
+            let token_wait = buckets
+                .tokens
+                .as_mut()
+                .map_or(Duration::ZERO, |b| b.reserve(cost as f64, now));
+            request_wait.max(token_wait)
+        };
+
+        if !wait.is_zero() {
+            tracing::debug!("Rate limited, waiting {:?}", wait);
+            tokio::time::sleep(wait).await;
         }
     }
 
-    /// Try to acquire without waiting. Returns true if acquired, false if would block.
-    pub async fn try_acquire(&self) -> bool {
-        let mut tokens = self.tokens.lock().await;
-        let mut last_refill = self.last_refill.lock().await;
+    /// Consume one request costing `cost` tokens if it fits right now.
+    pub fn try_acquire(&self, cost: u32) -> bool {
+        let mut buckets = self.buckets();
+        let now = Instant::now();
+        let fits = buckets.requests.as_mut().is_none_or(|b| b.has(1.0, now))
+            && buckets
+                .tokens
+                .as_mut()
+                .is_none_or(|b| b.has(cost as f64, now));
+        if fits {
+            if let Some(bucket) = buckets.requests.as_mut() {
+                bucket.reserve(1.0, now);
+            }
+            if let Some(bucket) = buckets.tokens.as_mut() {
+                bucket.reserve(cost as f64, now);
+            }
+        }
+        fits
+    }
 
-        // Refill tokens
+    pub fn saturation(&self) -> Saturation {
+        let mut buckets = self.buckets();
         let now = Instant::now();
-        let elapsed = now.duration_since(*last_refill).as_secs_f64();
-        let new_tokens = elapsed * self.refill_rate;
-        *tokens = (*tokens + new_tokens).min(self.max_tokens);
-        *last_refill = now;
-
-        if *tokens >= 1.0 {
-            *tokens -= 1.0;
-            true
-        } else {
-            false
+        Saturation {
+            requests: buckets.requests.as_mut().map(|b| b.saturation(now)),
+            tokens: buckets.tokens.as_mut().map(|b| b.saturation(now)),
+        }
+    }
+}
+
+/// Limiters for every configured provider and request class.
+///
+/// Providers without configured limits get an unlimited limiter.
+#[derive(Debug, Default)]
+pub struct RateLimiters {
+    limiters: Mutex<HashMap<(String, RequestClass), Arc<RateLimiter>>>,
+}
+
+impl RateLimiters {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Limiters for `config` and each of its fallbacks, keyed by provider
+    /// name.
+    pub fn from_config(config: &crate::providers::ProviderConfig) -> Self {
+        let limiters = Self::new();
+        for config in std::iter::once(config).chain(&config.fallbacks) {
+            limiters.configure(&config.provider, config.rate_limits);
         }
+        limiters
+    }
+
+    /// Replace `provider`'s limiters with fresh ones enforcing `limits`.
+    pub fn configure(&self, provider: &str, limits: RateLimits) {
+        let mut limiters = self.limiters();
+        for class in [RequestClass::Completion, RequestClass::Embedding] {
+            limiters.insert(
+                (provider.to_string(), class),
+                Arc::new(RateLimiter::new(limits.budget(class))),
+            );
+        }
+    }
+
+    fn limiters(
+        &self,
+    ) -> std::sync::MutexGuard<'_, HashMap<(String, RequestClass), Arc<RateLimiter>>> {
+        self.limiters
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+
+    /// The limiter for `provider`'s `class` requests.
+    pub fn limiter(&self, provider: &str, class: RequestClass) -> Arc<RateLimiter> {
+        self.limiters()
+            .entry((provider.to_string(), class))
+            .or_insert_with(|| Arc::new(RateLimiter::unlimited()))
+            .clone()
+    }
+
+    /// Wait for and consume budget for one `class` request to `provider`.
+    pub async fn acquire(&self, provider: &str, class: RequestClass, cost: u32) {
+        self.limiter(provider, class).acquire(cost).await;
+    }
+
+    /// Saturation of every limiter, sorted by provider and class.
+    pub fn saturation(&self) -> Vec<(String, RequestClass, Saturation)> {
+        let mut report: Vec<_> = self
+            .limiters()
+            .iter()
+            .map(|((provider, class), limiter)| (provider.clone(), *class, limiter.saturation()))
+            .collect();
+        report.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+        report
     }
 }
 
@@ -80,29 +253,121 @@ impl RateLimiter {
 mod tests {
     use super::*;
 
-    #[tokio::test]
-    async fn test_rate_limiter_basic() {
-        let limiter = RateLimiter::new(60); // 60 requests/min = 1 req/sec
-
-        // Should acquire immediately (bucket starts full)
-        limiter.acquire().await;
-        assert!(limiter.try_acquire().await); // Should still have tokens
+    fn budget(requests_per_min: Option<u32>, tokens_per_min: Option<u32>) -> Budget {
+        Budget {
+            requests_per_min,
+            tokens_per_min,
+        }
     }
 
-    #[tokio::test]
-    async fn test_rate_limiter_refill() {
-        let limiter = RateLimiter::new(60); // 1 req/sec
+    #[tokio::test(start_paused = true)]
+    async fn test_burst_above_bucket_queues_with_refill_delay() {
+        // 60 requests/min refills one request per second
+        let limiter = Arc::new(RateLimiter::new(budget(Some(60), None)));
+        let start = Instant::now();
 
-        // Drain all tokens
         for _ in 0..60 {
-            limiter.acquire().await;
+            limiter.acquire(0).await;
+        }
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!(!limiter.try_acquire(0));
+
+        // Three more queue behind each other, one second apart
+        let waiters: Vec<_> = (0..3)
+            .map(|_| {
+                let limiter = limiter.clone();
+                tokio::spawn(async move {
+                    limiter.acquire(0).await;
+                    start.elapsed()
+                })
+            })
+            .collect();
+        let mut finished = Vec::new();
+        for waiter in waiters {
+            finished.push(waiter.await.unwrap());
         }
+        finished.sort();
+        assert_eq!(
+            finished,
+            vec![
+                Duration::from_secs(1),
+                Duration::from_secs(2),
+                Duration::from_secs(3)
+            ]
+        );
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn test_token_budget_delays_expensive_requests() {
+        // 600 tokens/min refills 10 tokens per second
+        let limiter = RateLimiter::new(budget(Some(100), Some(600)));
+        let start = Instant::now();
+
+        limiter.acquire(500).await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        assert!((limiter.saturation().tokens.unwrap() - 500.0 / 600.0).abs() < 1e-9);
+
+        // 200 more needs 100 tokens of refill: ten seconds
+        limiter.acquire(200).await;
+        assert_eq!(start.elapsed(), Duration::from_secs(10));
+        assert!(limiter.saturation().max() >= 0.99);
+    }
 
-        // Should not have tokens immediately
-        assert!(!limiter.try_acquire().await);
+    #[tokio::test(start_paused = true)]
+    async fn test_providers_and_classes_do_not_share_buckets() {
+        let limiters = RateLimiters::new();
+        let strict = budget(Some(1), None);
+        limiters.configure(
+            "openai",
+            RateLimits {
+                completion: strict,
+                embedding: strict,
+            },
+        );
+        limiters.configure(
+            "llamafile",
+            RateLimits {
+                completion: strict,
+                embedding: Budget::default(),
+            },
+        );
+        let start = Instant::now();
+
+        limiters
+            .acquire("openai", RequestClass::Completion, 10)
+            .await;
+        limiters
+            .acquire("openai", RequestClass::Embedding, 10)
+            .await;
+        limiters
+            .acquire("llamafile", RequestClass::Completion, 10)
+            .await;
+        for _ in 0..5 {
+            limiters
+                .acquire("llamafile", RequestClass::Embedding, 10)
+                .await;
+        }
+        limiters
+            .acquire("unknown", RequestClass::Completion, 10)
+            .await;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+
+        assert!(!limiters
+            .limiter("openai", RequestClass::Completion)
+            .try_acquire(0));
+        let report = limiters.saturation();
+        let openai = report
+            .iter()
+            .find(|(p, c, _)| p == "openai" && *c == RequestClass::Completion)
+            .unwrap();
+        assert_eq!(openai.2.requests, Some(1.0));
+        assert_eq!(openai.2.tokens, None);
+    }
 
-        // Wait for refill (1 second should give ~1 token)
-        tokio::time::sleep(Duration::from_secs(1)).await;
-        assert!(limiter.try_acquire().await);
+    #[test]
+    fn test_estimate_tokens() {
+        assert_eq!(estimate_tokens(""), 0);
+        assert_eq!(estimate_tokens("abcd"), 1);
+        assert_eq!(estimate_tokens("abcde"), 2);
     }
 }
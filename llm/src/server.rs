@@ -0,0 +1,788 @@
+//! Launches and supervises a local llamafile (or llama.cpp `llama-server`)
+//! process.
+//!
+//! [`ServerManager::start`] adopts a healthy server already listening on the
+//! endpoint. Otherwise it finds the model, downloading it if allowed, spawns
+//! the server and polls `/health` until it answers. Health checks use plain
+//! TCP so they work inside or outside an async runtime. A start can be
+//! cancelled from another thread with the flag passed to
+//! [`ServerManager::with_cancel`].
+
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// How long a spawned server may take to load its model.
+pub const DEFAULT_STARTUP_TIMEOUT: Duration = Duration::from_secs(120);
+
+const HEALTH_POLL_INTERVAL: Duration = Duration::from_millis(250);
+const PROBE_TIMEOUT: Duration = Duration::from_millis(500);
+const SERVER_BINARIES: [&str; 2] = ["llamafile", "llama-server"];
+const MAX_DOWNLOAD_REDIRECTS: usize = 10;
+const DOWNLOAD_CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+#[derive(Debug, Error)]
+pub enum ServerError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("invalid server endpoint: {0}")]
+    InvalidEndpoint(String),
+    #[error("no llamafile or llama-server executable found in {0} or PATH")]
+    BinaryNotFound(String),
+    #[error("model {0} not found and not downloadable")]
+    ModelNotFound(String),
+    #[error("model download failed: {0}")]
+    DownloadError(String),
+    #[error("no checksum known for {0}; set one or place the model by hand")]
+    ChecksumUnavailable(String),
+    #[error("checksum mismatch for {file}: expected {expected}, got {actual}")]
+    ChecksumMismatch {
+        file: String,
+        expected: String,
+        actual: String,
+    },
+    #[error("{0} is in use by something other than a llama server")]
+    PortInUse(String),
+    #[error("server exited before becoming ready: {0}")]
+    Exited(String),
+    #[error("server not ready after {0:?}")]
+    Timeout(Duration),
+    #[error("start cancelled")]
+    Cancelled,
+}
+
+pub type ServerResult<T> = Result<T, ServerError>;
+
+/// A GGUF model file and where to get it.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ModelSpec {
+    pub file_name: String,
+    /// Download URL; `None` if the file must be placed by hand.
+    pub url: Option<String>,
+    /// Expected SHA-256 (hex). When unset, the checksum published by the
+    /// download server is used; without either the download fails.
+    pub sha256: Option<String>,
+}
+
+impl ModelSpec {
+    /// The built-in model for a `model_size` ("7b", "13b", "70b") and
+    /// `quantization` ("4bit", "8bit", "none").
+    pub fn for_size(model_size: &str, quantization: &str) -> Self {
+        let (repo, stem) = match model_size {
+            "13b" => ("TheBloke/Llama-2-13B-chat-GGUF", "llama-2-13b-chat"),
+            "70b" => ("TheBloke/Llama-2-70B-Chat-GGUF", "llama-2-70b-chat"),
+            _ => (
+                "TheBloke/Mistral-7B-Instruct-v0.2-GGUF",
+                "mistral-7b-instruct-v0.2",
+            ),
+        };
+        let quant = match quantization {
+            "8bit" => "Q8_0",
+            "none" => "f16",
+            _ => "Q4_K_M",
+        };
+        let file_name = format!("{}.{}.gguf", stem, quant);
+
+        // Unquantized weights and a single-file 70B Q8_0 aren't published
+        let published = quant != "f16" && !(model_size == "70b" && quant == "Q8_0");
+        Self {
+            url: published
+                .then(|| format!("https://huggingface.co/{}/resolve/main/{}", repo, file_name)),
+            file_name,
+            sha256: None,
+        }
+    }
+}
+
+/// How to find, fetch and run the server.
+#[derive(Debug, Clone)]
+pub struct ServerOptions {
+    /// Base URL the server listens on, e.g. `http://localhost:8000`.
+    pub endpoint: String,
+    /// Server executable; searched for in `models_dir` and `PATH` when unset.
+    pub binary: Option<PathBuf>,
+    pub models_dir: PathBuf,
+    pub model: ModelSpec,
+    /// Download the model into `models_dir` if it is missing.
+    pub auto_download: bool,
+    /// Offload all layers to the GPU.
+    pub use_gpu: bool,
+    pub startup_timeout: Duration,
+    /// Where the server's output goes; discarded when unset.
+    pub log_file: Option<PathBuf>,
+}
+
+impl ServerOptions {
+    pub fn new(
+        endpoint: impl Into<String>,
+        models_dir: impl Into<PathBuf>,
+        model: ModelSpec,
+    ) -> Self {
+        Self {
+            endpoint: endpoint.into(),
+            binary: None,
+            models_dir: models_dir.into(),
+            model,
+            auto_download: false,
+            use_gpu: false,
+            startup_timeout: DEFAULT_STARTUP_TIMEOUT,
+            log_file: None,
+        }
+    }
+}
+
+/// Bytes fetched so far while downloading a model.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DownloadProgress {
+    pub downloaded: u64,
+    /// Size reported by the server, if any.
+    pub total: Option<u64>,
+}
+
+type ProgressFn = Arc<dyn Fn(DownloadProgress) + Send + Sync>;
+
+/// What the manager is running.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ServerStatus {
+    Stopped,
+    /// A process we spawned.
+    Running {
+        pid: u32,
+    },
+    /// A healthy server that was already listening; left running on stop.
+    Adopted,
+    /// The spawned process has gone away.
+    Exited(String),
+}
+
+/// Owns the llama server process; the process is killed when dropped.
+pub struct ServerManager {
+    options: ServerOptions,
+    child: Option<Child>,
+    adopted: bool,
+    progress: Option<ProgressFn>,
+    cancel: Arc<AtomicBool>,
+}
+
+impl ServerManager {
+    pub fn new(options: ServerOptions) -> Self {
+        Self {
+            options,
+            child: None,
+            adopted: false,
+            progress: None,
+            cancel: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Abandon a start, including a model download, once `cancel` is set.
+    pub fn with_cancel(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Report model download progress to `progress`.
+    pub fn with_progress(
+        mut self,
+        progress: impl Fn(DownloadProgress) + Send + Sync + 'static,
+    ) -> Self {
+        self.progress = Some(Arc::new(progress));
+        self
+    }
+
+    pub fn options(&self) -> &ServerOptions {
+        &self.options
+    }
+
+    /// Make a server available on the endpoint, adopting a running one or
+    /// spawning our own and waiting until it is healthy.
+    pub fn start(&mut self) -> ServerResult<()> {
+        if matches!(
+            self.status(),
+            ServerStatus::Running { .. } | ServerStatus::Adopted
+        ) {
+            return Ok(());
+        }
+
+        let (host, port) = parse_endpoint(&self.options.endpoint)?;
+        let addr = resolve(&host, port)?;
+        if probe_health(addr) {
+            info!("Adopting llama server already running at {}", addr);
+            self.adopted = true;
+            return Ok(());
+        }
+        if TcpStream::connect_timeout(&addr, PROBE_TIMEOUT).is_ok() {
+            return Err(ServerError::PortInUse(addr.to_string()));
+        }
+
+        let binary = self.find_binary()?;
+        let model = self.ensure_model()?;
+        let (stdout, stderr) = match &self.options.log_file {
+            Some(path) => {
+                if let Some(parent) = path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let log = File::options().create(true).append(true).open(path)?;
+                (Stdio::from(log.try_clone()?), Stdio::from(log))
+            }
+            None => (Stdio::null(), Stdio::null()),
+        };
+
+        info!(
+            "Starting {} with {} on {}",
+            binary.display(),
+            model.display(),
+            addr
+        );
+        let child = server_command(&binary, &model, &host, port, self.options.use_gpu)
+            .stdin(Stdio::null())
+            .stdout(stdout)
+            .stderr(stderr)
+            .spawn()?;
+        self.child = Some(child);
+
+        let result = self.wait_until_healthy(addr);
+        if result.is_err() {
+            self.stop()?;
+        }
+        result
+    }
+
+    /// Kill the spawned server. An adopted server is only forgotten.
+    pub fn stop(&mut self) -> ServerResult<()> {
+        self.adopted = false;
+        if let Some(mut child) = self.child.take() {
+            info!("Stopping llama server (pid {})", child.id());
+            if child.try_wait()?.is_none() {
+                child.kill()?;
+            }
+            child.wait()?;
+        }
+        Ok(())
+    }
+
+    pub fn restart(&mut self) -> ServerResult<()> {
+        self.stop()?;
+        self.start()
+    }
+
+    pub fn status(&mut self) -> ServerStatus {
+        if self.adopted {
+            return ServerStatus::Adopted;
+        }
+        let Some(child) = &mut self.child else {
+            return ServerStatus::Stopped;
+        };
+        match child.try_wait() {
+            Ok(None) => ServerStatus::Running { pid: child.id() },
+            Ok(Some(status)) => ServerStatus::Exited(status.to_string()),
+            Err(e) => ServerStatus::Exited(e.to_string()),
+        }
+    }
+
+    /// Whether the endpoint answers `/health`, whoever runs the server.
+    pub fn is_healthy(&self) -> bool {
+        parse_endpoint(&self.options.endpoint)
+            .and_then(|(host, port)| resolve(&host, port))
+            .is_ok_and(probe_health)
+    }
+
+    fn wait_until_healthy(&mut self, addr: SocketAddr) -> ServerResult<()> {
+        let started = Instant::now();
+        loop {
+            if probe_health(addr) {
+                info!("Llama server ready after {:?}", started.elapsed());
+                return Ok(());
+            }
+            if let Some(child) = &mut self.child {
+                if let Some(status) = child.try_wait()? {
+                    self.child = None;
+                    // Lost a race for the port to a server that is now healthy
+                    if probe_health(addr) {
+                        info!("Adopting llama server that started at {}", addr);
+                        self.adopted = true;
+                        return Ok(());
+                    }
+                    return Err(ServerError::Exited(status.to_string()));
+                }
+            }
+            if self.cancel.load(Ordering::Relaxed) {
+                return Err(ServerError::Cancelled);
+            }
+            if started.elapsed() >= self.options.startup_timeout {
+                return Err(ServerError::Timeout(self.options.startup_timeout));
+            }
+            std::thread::sleep(HEALTH_POLL_INTERVAL);
+        }
+    }
+
+    fn find_binary(&self) -> ServerResult<PathBuf> {
+        if let Some(binary) = &self.options.binary {
+            return if binary.is_file() {
+                Ok(binary.clone())
+            } else {
+                Err(ServerError::BinaryNotFound(binary.display().to_string()))
+            };
+        }
+
+        let path_dirs = std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).collect::<Vec<_>>())
+            .unwrap_or_default();
+        std::iter::once(self.options.models_dir.clone())
+            .chain(path_dirs)
+            .flat_map(|dir| {
+                SERVER_BINARIES
+                    .map(|name| dir.join(format!("{}{}", name, std::env::consts::EXE_SUFFIX)))
+            })
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| {
+                ServerError::BinaryNotFound(self.options.models_dir.display().to_string())
+            })
+    }
+
+    /// Path of the model file, downloading it first if needed and allowed.
+    fn ensure_model(&self) -> ServerResult<PathBuf> {
+        let spec = &self.options.model;
+        let path = self.options.models_dir.join(&spec.file_name);
+        if path.is_file() {
+            return Ok(path);
+        }
+        match &spec.url {
+            Some(url) if self.options.auto_download => {
+                fs::create_dir_all(&self.options.models_dir)?;
+                info!("Downloading {} from {}", spec.file_name, url);
+                download(
+                    url,
+                    &path,
+                    spec.sha256.as_deref(),
+                    self.progress.as_ref(),
+                    &self.cancel,
+                )?;
+                Ok(path)
+            }
+            _ => Err(ServerError::ModelNotFound(path.display().to_string())),
+        }
+    }
+}
+
+impl Drop for ServerManager {
+    fn drop(&mut self) {
+        if let Err(e) = self.stop() {
+            warn!("Failed to stop llama server: {}", e);
+        }
+    }
+}
+
+/// Host and port of an `http://host:port/...` endpoint.
+fn parse_endpoint(endpoint: &str) -> ServerResult<(String, u16)> {
+    let invalid = || ServerError::InvalidEndpoint(endpoint.to_string());
+    let rest = endpoint.strip_prefix("http://").ok_or_else(invalid)?;
+    let authority = rest.split('/').next().unwrap_or_default();
+    match authority.rsplit_once(':') {
+        Some((host, port)) if !host.is_empty() => {
+            Ok((host.to_string(), port.parse().map_err(|_| invalid())?))
+        }
+        None if !authority.is_empty() => Ok((authority.to_string(), 80)),
+        _ => Err(invalid()),
+    }
+}
+
+fn resolve(host: &str, port: u16) -> ServerResult<SocketAddr> {
+    (host, port)
+        .to_socket_addrs()?
+        .next()
+        .ok_or_else(|| ServerError::InvalidEndpoint(format!("{}:{}", host, port)))
+}
+
+/// Whether `GET /health` on `addr` answers 200. A loading server answers 503.
+fn probe_health(addr: SocketAddr) -> bool {
+    let Ok(mut stream) = TcpStream::connect_timeout(&addr, PROBE_TIMEOUT) else {
+        return false;
+    };
+    let _ = stream.set_read_timeout(Some(PROBE_TIMEOUT));
+    let request = format!(
+        "GET /health HTTP/1.1\r\nHost: {}\r\nConnection: close\r\n\r\n",
+        addr
+    );
+    if stream.write_all(request.as_bytes()).is_err() {
+        return false;
+    }
+    let mut status_line = [0u8; 12];
+    stream.read_exact(&mut status_line).is_ok()
+        && status_line.starts_with(b"HTTP/1.")
+        && &status_line[9..12] == b"200"
+}
+
+/// llamafile needs `--server` to skip its CLI mode; llama-server doesn't.
+fn server_command(binary: &Path, model: &Path, host: &str, port: u16, use_gpu: bool) -> Command {
+    let mut command = Command::new(binary);
+    let is_llamafile = binary
+        .file_name()
+        .and_then(|name| name.to_str())
+        .is_some_and(|name| name.starts_with("llamafile"));
+    if is_llamafile {
+        command.args(["--server", "--nobrowser"]);
+    }
+    command
+        .arg("-m")
+        .arg(model)
+        .args(["--host", host, "--port", &port.to_string()])
+        .args(["-ngl", if use_gpu { "999" } else { "0" }]);
+    command
+}
+
+/// Await `future`, giving up once `cancel` is set even if the server has
+/// stopped sending.
+async fn unless_cancelled<T>(
+    cancel: &AtomicBool,
+    future: impl std::future::Future<Output = T>,
+) -> ServerResult<T> {
+    let cancelled = async {
+        while !cancel.load(Ordering::Relaxed) {
+            tokio::time::sleep(CANCEL_POLL_INTERVAL).await;
+        }
+    };
+    tokio::select! {
+        output = future => Ok(output),
+        () = cancelled => Err(ServerError::Cancelled),
+    }
+}
+
+/// Fetch `url` into `dest`, verifying its SHA-256. Runs on its own thread
+/// and runtime so it can be called from async code too.
+fn download(
+    url: &str,
+    dest: &Path,
+    expected: Option<&str>,
+    progress: Option<&ProgressFn>,
+    cancel: &AtomicBool,
+) -> ServerResult<()> {
+    std::thread::scope(|scope| {
+        scope
+            .spawn(|| {
+                let runtime = tokio::runtime::Builder::new_current_thread()
+                    .enable_all()
+                    .build()?;
+                runtime.block_on(download_async(url, dest, expected, progress, cancel))
+            })
+            .join()
+            .unwrap_or_else(|_| Err(ServerError::DownloadError("download panicked".into())))
+    })
+}
+
+async fn download_async(
+    url: &str,
+    dest: &Path,
+    expected: Option<&str>,
+    progress: Option<&ProgressFn>,
+    cancel: &AtomicBool,
+) -> ServerResult<()> {
+    let failed = |e: reqwest::Error| ServerError::DownloadError(e.to_string());
+    // Redirects are followed by hand: Hugging Face publishes the checksum on
+    // its first response, not on the CDN it redirects to
+    let client = reqwest::Client::builder()
+        .redirect(reqwest::redirect::Policy::none())
+        .connect_timeout(DOWNLOAD_CONNECT_TIMEOUT)
+        .build()
+        .map_err(failed)?;
+    let mut current =
+        reqwest::Url::parse(url).map_err(|e| ServerError::DownloadError(e.to_string()))?;
+    let mut published = None;
+    let mut hops = 0;
+    let mut response = loop {
+        let request = client.get(current.clone()).send();
+        let response = unless_cancelled(cancel, request).await?.map_err(failed)?;
+        published = published.or_else(|| published_sha256(&response));
+        let location = response
+            .headers()
+            .get(reqwest::header::LOCATION)
+            .and_then(|location| location.to_str().ok());
+        match location {
+            Some(location) if response.status().is_redirection() => {
+                hops += 1;
+                if hops > MAX_DOWNLOAD_REDIRECTS {
+                    return Err(ServerError::DownloadError(format!(
+                        "more than {} redirects",
+                        MAX_DOWNLOAD_REDIRECTS
+                    )));
+                }
+                current = current
+                    .join(location)
+                    .map_err(|e| ServerError::DownloadError(e.to_string()))?;
+            }
+            _ => break response.error_for_status().map_err(failed)?,
+        }
+    };
+    let expected = expected
+        .map(str::to_ascii_lowercase)
+        .or(published)
+        .ok_or_else(|| ServerError::ChecksumUnavailable(url.to_string()))?;
+    let total = response.content_length();
+
+    // Written under a temporary name so an interrupted download is never used
+    let mut partial_name = dest.as_os_str().to_owned();
+    partial_name.push(".part");
+    let partial = PathBuf::from(partial_name);
+    let mut file = File::create(&partial)?;
+    let mut hasher = Sha256::new();
+    let mut downloaded = 0u64;
+    loop {
+        let chunk = match unless_cancelled(cancel, response.chunk()).await {
+            Ok(Ok(Some(chunk))) => chunk,
+            Ok(Ok(None)) => break,
+            Ok(Err(e)) => return Err(failed(e)),
+            Err(e) => {
+                drop(file);
+                let _ = fs::remove_file(&partial);
+                return Err(e);
+            }
+        };
+        file.write_all(&chunk)?;
+        hasher.update(&chunk);
+        downloaded += chunk.len() as u64;
+        if let Some(progress) = progress {
+            progress(DownloadProgress { downloaded, total });
+        }
+    }
+    file.flush()?;
+    drop(file);
+
+    let actual = format!("{:x}", hasher.finalize());
+    if expected != actual {
+        let _ = fs::remove_file(&partial);
+        return Err(ServerError::ChecksumMismatch {
+            file: dest.display().to_string(),
+            expected,
+            actual,
+        });
+    }
+    fs::rename(&partial, dest)?;
+    Ok(())
+}
+
+/// SHA-256 that Hugging Face reports for large files in `X-Linked-Etag`.
+fn published_sha256(response: &reqwest::Response) -> Option<String> {
+    let etag = response.headers().get("x-linked-etag")?.to_str().ok()?;
+    let etag = etag.trim_matches('"').to_ascii_lowercase();
+    (etag.len() == 64 && etag.chars().all(|c| c.is_ascii_hexdigit())).then_some(etag)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::TcpListener;
+
+    /// Answer every connection with `status` and `body` until the test ends.
+    fn serve(status: u16, headers: impl Into<String>, body: &'static [u8]) -> SocketAddr {
+        let headers = headers.into();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+                let head = format!(
+                    "HTTP/1.1 {} OK\r\nContent-Length: {}\r\n{}Connection: close\r\n\r\n",
+                    status,
+                    body.len(),
+                    headers
+                );
+                let _ = stream.write_all(head.as_bytes());
+                let _ = stream.write_all(body);
+            }
+        });
+        addr
+    }
+
+    fn options(endpoint: String, models_dir: &Path) -> ServerOptions {
+        ServerOptions::new(endpoint, models_dir, ModelSpec::for_size("7b", "4bit"))
+    }
+
+    #[test]
+    fn test_parse_endpoint() {
+        assert_eq!(
+            parse_endpoint("http://localhost:8000").unwrap(),
+            ("localhost".to_string(), 8000)
+        );
+        assert_eq!(
+            parse_endpoint("http://127.0.0.1:9000/v1").unwrap(),
+            ("127.0.0.1".to_string(), 9000)
+        );
+        assert_eq!(
+            parse_endpoint("http://example").unwrap(),
+            ("example".to_string(), 80)
+        );
+        assert!(parse_endpoint("https://localhost:8000").is_err());
+        assert!(parse_endpoint("http://localhost:port").is_err());
+    }
+
+    #[test]
+    fn test_model_spec_for_size() {
+        let spec = ModelSpec::for_size("13b", "8bit");
+        assert_eq!(spec.file_name, "llama-2-13b-chat.Q8_0.gguf");
+        assert!(spec.url.unwrap().ends_with("/llama-2-13b-chat.Q8_0.gguf"));
+        assert!(ModelSpec::for_size("7b", "none").url.is_none());
+    }
+
+    #[test]
+    fn test_adopts_healthy_server() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = serve(200, "", b"{\"status\":\"ok\"}");
+        let mut manager = ServerManager::new(options(format!("http://{}", addr), dir.path()));
+
+        manager.start().unwrap();
+        assert_eq!(manager.status(), ServerStatus::Adopted);
+        manager.stop().unwrap();
+        assert_eq!(manager.status(), ServerStatus::Stopped);
+    }
+
+    #[test]
+    fn test_port_taken_by_other_service_is_reported() {
+        let dir = tempfile::tempdir().unwrap();
+        let addr = serve(404, "", b"not found");
+        let mut manager = ServerManager::new(options(format!("http://{}", addr), dir.path()));
+
+        assert!(matches!(manager.start(), Err(ServerError::PortInUse(_))));
+    }
+
+    #[test]
+    fn test_missing_binary_is_reported_before_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut opts = options(format!("http://127.0.0.1:{}", port), dir.path());
+        opts.binary = Some(dir.path().join("missing-llamafile"));
+        opts.auto_download = true;
+        let mut manager = ServerManager::new(opts);
+
+        assert!(matches!(
+            manager.start(),
+            Err(ServerError::BinaryNotFound(_))
+        ));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_server_exiting_early_is_reported() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let binary = dir.path().join("llama-server");
+        fs::write(&binary, "#!/bin/sh\nexit 3\n").unwrap();
+        fs::set_permissions(&binary, fs::Permissions::from_mode(0o755)).unwrap();
+        let model = ModelSpec::for_size("7b", "4bit");
+        fs::write(dir.path().join(&model.file_name), b"gguf").unwrap();
+
+        let port = TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let mut manager =
+            ServerManager::new(options(format!("http://127.0.0.1:{}", port), dir.path()));
+        assert!(matches!(manager.start(), Err(ServerError::Exited(_))));
+        assert_eq!(manager.status(), ServerStatus::Stopped);
+    }
+
+    #[test]
+    fn test_download_verifies_checksum() {
+        let dir = tempfile::tempdir().unwrap();
+        let wrong = "0".repeat(64);
+        let addr = serve(200, "", b"model bytes");
+        let url = format!("http://{}/model.gguf", addr);
+        let dest = dir.path().join("model.gguf");
+
+        let running = AtomicBool::new(false);
+
+        let err = download(&url, &dest, Some(&wrong), None, &running).unwrap_err();
+        assert!(matches!(err, ServerError::ChecksumMismatch { .. }));
+        assert!(!dest.exists());
+
+        let err = download(&url, &dest, None, None, &running).unwrap_err();
+        assert!(matches!(err, ServerError::ChecksumUnavailable(_)));
+        assert!(!dest.exists());
+
+        let actual = format!("{:x}", Sha256::digest(b"model bytes"));
+        let seen = Arc::new(std::sync::Mutex::new(Vec::new()));
+        let recorder = seen.clone();
+        let progress: ProgressFn = Arc::new(move |p| recorder.lock().unwrap().push(p));
+        download(&url, &dest, Some(&actual), Some(&progress), &running).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"model bytes");
+        assert_eq!(
+            seen.lock().unwrap().last(),
+            Some(&DownloadProgress {
+                downloaded: 11,
+                total: Some(11)
+            })
+        );
+    }
+
+    #[test]
+    fn test_download_uses_checksum_published_before_redirect() {
+        let dir = tempfile::tempdir().unwrap();
+        let actual = format!("{:x}", Sha256::digest(b"model bytes"));
+        let cdn = serve(200, "", b"model bytes");
+        let hub = serve(
+            302,
+            format!(
+                "Location: http://{}/model.gguf\r\nX-Linked-Etag: \"{}\"\r\n",
+                cdn, actual
+            ),
+            b"",
+        );
+        let url = format!("http://{}/resolve/main/model.gguf", hub);
+        let dest = dir.path().join("model.gguf");
+
+        download(&url, &dest, None, None, &AtomicBool::new(false)).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"model bytes");
+
+        fs::remove_file(&dest).unwrap();
+        let err = download(&url, &dest, None, None, &AtomicBool::new(true)).unwrap_err();
+        assert!(matches!(err, ServerError::Cancelled));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+
+    #[test]
+    fn test_cancel_abandons_stalled_download() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        // Sends the headers and part of the body, then goes quiet
+        std::thread::spawn(move || {
+            for mut stream in listener.incoming().flatten() {
+                let mut request = [0u8; 1024];
+                let _ = stream.read(&mut request);
+                let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\nmodel");
+                std::thread::sleep(Duration::from_secs(60));
+            }
+        });
+        let url = format!("http://{}/model.gguf", addr);
+        let dest = dir.path().join("model.gguf");
+        let cancel = Arc::new(AtomicBool::new(false));
+        let canceller = cancel.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(300));
+            canceller.store(true, Ordering::Relaxed);
+        });
+
+        let started = Instant::now();
+        let wrong = "0".repeat(64);
+        let err = download(&url, &dest, Some(&wrong), None, &cancel).unwrap_err();
+        assert!(matches!(err, ServerError::Cancelled));
+        assert!(started.elapsed() < Duration::from_secs(10));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 0);
+    }
+}
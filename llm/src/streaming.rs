@@ -1,5 +1,6 @@
 //! Streaming response support for real-time LLM output.
 
+use crate::providers::StopReason;
 use futures::Stream;
 use serde::{Deserialize, Serialize};
 use std::pin::Pin;
@@ -29,7 +30,8 @@ pub type StreamResult<T> = Result<T, StreamError>;
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct StreamChunk {
     pub delta: String,
-    pub finish_reason: Option<String>,
+    /// Set on the final chunk of a completion.
+    pub finish_reason: Option<StopReason>,
 }
 
 /// Trait for streaming completions.
@@ -42,5 +44,63 @@ pub trait StreamableProvider {
     ) -> StreamResult<Pin<Box<dyn Stream<Item = StreamResult<StreamChunk>> + Send>>>;
 }
 
-// TODO: Implement for OpenAI (SSE parsing)
-// TODO: Implement for llamafile (SSE parsing)
+/// Incremental parser for `text/event-stream` bodies.
+///
+/// Network reads can split an event anywhere, so bytes are buffered until a
+/// full line is available and only complete `data:` payloads are returned.
+#[derive(Debug, Default)]
+pub(crate) struct SseBuffer {
+    pending: Vec<u8>,
+}
+
+impl SseBuffer {
+    pub(crate) fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append raw bytes and return every complete `data:` payload.
+    pub(crate) fn push(&mut self, bytes: &[u8]) -> Vec<String> {
+        self.pending.extend_from_slice(bytes);
+
+        let mut events = Vec::new();
+        while let Some(pos) = self.pending.iter().position(|&b| b == b'\n') {
+            let line: Vec<u8> = self.pending.drain(..=pos).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\r', '\n']);
+
+            if let Some(data) = line.strip_prefix("data:") {
+                events.push(data.trim_start().to_string());
+            }
+            // Blank separators, comments (`:`) and `event:`/`id:` fields are ignored
+        }
+        events
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_buffer_complete_events() {
+        let mut buffer = SseBuffer::new();
+        let events = buffer.push(b"data: {\"a\":1}\n\ndata: {\"a\":2}\n\n");
+        assert_eq!(events, vec!["{\"a\":1}", "{\"a\":2}"]);
+    }
+
+    #[test]
+    fn test_sse_buffer_split_across_reads() {
+        let mut buffer = SseBuffer::new();
+        assert!(buffer.push(b"data: {\"con").is_empty());
+        assert!(buffer.push(b"tent\":\"hi\"}").is_empty());
+        let events = buffer.push(b"\r\n\r\n");
+        assert_eq!(events, vec!["{\"content\":\"hi\"}"]);
+    }
+
+    #[test]
+    fn test_sse_buffer_ignores_comments_and_fields() {
+        let mut buffer = SseBuffer::new();
+        let events = buffer.push(b": keep-alive\nevent: message\ndata: [DONE]\n\n");
+        assert_eq!(events, vec!["[DONE]"]);
+    }
+}
@@ -0,0 +1,426 @@
+//! Token and cost accounting per provider and model.
+//!
+//! [`UsageTracker`] records each completion and embedding request, prices it
+//! from the configured [`ModelPrice`] table and keeps daily aggregates that
+//! persist to a JSON file (normally `~/.lucastra/data/usage.json`).
+//! Responses that don't report `tokens_used` are estimated from character
+//! counts and counted in [`UsageTotals::estimated_requests`].
+
+use crate::providers::CompletionResponse;
+use crate::rate_limit::{estimate_tokens, RequestClass};
+use chrono::{DateTime, Datelike, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+pub use lucastra_config::providers::ModelPrice;
+
+#[derive(Debug, Error)]
+pub enum UsageError {
+    #[error("IO error: {0}")]
+    IoError(#[from] std::io::Error),
+    #[error("serialization error: {0}")]
+    SerializationError(#[from] serde_json::Error),
+}
+
+pub type UsageResult<T> = Result<T, UsageError>;
+
+/// Key in a provider's price table that matches any model.
+const ANY_MODEL: &str = "*";
+
+/// One recorded request.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UsageRecord {
+    pub provider: String,
+    pub model: String,
+    pub class: RequestClass,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+    /// Token counts were estimated from character counts.
+    pub estimated: bool,
+    /// Unix seconds.
+    pub timestamp: i64,
+}
+
+/// Summed usage over some period.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UsageTotals {
+    pub requests: u64,
+    pub input_tokens: u64,
+    pub output_tokens: u64,
+    pub cost_usd: f64,
+    /// Requests whose token counts were estimated.
+    #[serde(default)]
+    pub estimated_requests: u64,
+}
+
+impl UsageTotals {
+    pub fn tokens(&self) -> u64 {
+        self.input_tokens + self.output_tokens
+    }
+
+    fn add(&mut self, other: &UsageTotals) {
+        self.requests += other.requests;
+        self.input_tokens += other.input_tokens;
+        self.output_tokens += other.output_tokens;
+        self.cost_usd += other.cost_usd;
+        self.estimated_requests += other.estimated_requests;
+    }
+}
+
+/// Usage of one provider, model and request class on one day.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct DailyEntry {
+    provider: String,
+    model: String,
+    class: RequestClass,
+    #[serde(flatten)]
+    totals: UsageTotals,
+    first_used: i64,
+    last_used: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct UsageFile {
+    /// Keyed by local date, `YYYY-MM-DD`.
+    days: BTreeMap<String, Vec<DailyEntry>>,
+}
+
+/// Records token usage and cost, aggregated per day.
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    path: Option<PathBuf>,
+    data: UsageFile,
+    /// Provider name to model prices.
+    pricing: HashMap<String, HashMap<String, ModelPrice>>,
+}
+
+fn local_date(timestamp: i64) -> NaiveDate {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .with_timezone(&Local)
+        .date_naive()
+}
+
+fn date_key(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+impl UsageTracker {
+    /// A tracker that is never saved.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load usage from `path`, starting empty if it doesn't exist yet.
+    /// [`UsageTracker::save`] writes back to the same file.
+    pub fn load(path: impl AsRef<Path>) -> UsageResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let data = match fs::read_to_string(&path) {
+            Ok(contents) => serde_json::from_str(&contents)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => UsageFile::default(),
+            Err(e) => return Err(e.into()),
+        };
+        Ok(Self {
+            path: Some(path),
+            data,
+            pricing: HashMap::new(),
+        })
+    }
+
+    /// Price `provider`'s models with `prices`, keyed by model name.
+    pub fn with_pricing(mut self, provider: &str, prices: HashMap<String, ModelPrice>) -> Self {
+        self.pricing.insert(provider.to_string(), prices);
+        self
+    }
+
+    /// Price of `model`, falling back to the provider's `"*"` entry and then
+    /// to free.
+    pub fn price(&self, provider: &str, model: &str) -> ModelPrice {
+        self.pricing
+            .get(provider)
+            .and_then(|prices| prices.get(model).or_else(|| prices.get(ANY_MODEL)))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Record a completion of `prompt`.
+    ///
+    /// `tokens_used` is a total, so it is split between prompt and answer in
+    /// proportion to their estimated sizes. Without it both are estimated.
+    pub fn record_completion(
+        &mut self,
+        provider: &str,
+        prompt: &str,
+        response: &CompletionResponse,
+    ) -> UsageRecord {
+        let input_estimate = estimate_tokens(prompt) as u64;
+        let output_estimate = estimate_tokens(&response.content) as u64;
+        let (input_tokens, output_tokens, estimated) = match response.tokens_used {
+            Some(total) => {
+                let total = total as u64;
+                let output = (total * output_estimate)
+                    .checked_div(input_estimate + output_estimate)
+                    .unwrap_or(0);
+                (total - output, output, false)
+            }
+            None => (input_estimate, output_estimate, true),
+        };
+
+        let model = response.model.as_deref().unwrap_or("unknown");
+        self.record(UsageRecord {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            class: RequestClass::Completion,
+            input_tokens,
+            output_tokens,
+            cost_usd: self
+                .price(provider, model)
+                .cost(input_tokens, output_tokens),
+            estimated,
+            timestamp: chrono::Utc::now().timestamp(),
+        })
+    }
+
+    /// Record an embedding request for `texts`. Embedding responses carry no
+    /// token counts, so these are always estimates.
+    pub fn record_embedding(
+        &mut self,
+        provider: &str,
+        model: &str,
+        texts: &[String],
+    ) -> UsageRecord {
+        let input_tokens = texts.iter().map(|t| estimate_tokens(t) as u64).sum();
+        self.record(UsageRecord {
+            provider: provider.to_string(),
+            model: model.to_string(),
+            class: RequestClass::Embedding,
+            input_tokens,
+            output_tokens: 0,
+            cost_usd: self.price(provider, model).cost(input_tokens, 0),
+            estimated: true,
+            timestamp: chrono::Utc::now().timestamp(),
+        })
+    }
+
+    /// Add `record` to its day's aggregate.
+    pub fn record(&mut self, record: UsageRecord) -> UsageRecord {
+        let day = self
+            .data
+            .days
+            .entry(date_key(local_date(record.timestamp)))
+            .or_default();
+        let index = day
+            .iter()
+            .position(|e| {
+                e.provider == record.provider && e.model == record.model && e.class == record.class
+            })
+            .unwrap_or_else(|| {
+                day.push(DailyEntry {
+                    provider: record.provider.clone(),
+                    model: record.model.clone(),
+                    class: record.class,
+                    totals: UsageTotals::default(),
+                    first_used: record.timestamp,
+                    last_used: record.timestamp,
+                });
+                day.len() - 1
+            });
+
+        let entry = &mut day[index];
+        entry.totals.add(&UsageTotals {
+            requests: 1,
+            input_tokens: record.input_tokens,
+            output_tokens: record.output_tokens,
+            cost_usd: record.cost_usd,
+            estimated_requests: record.estimated as u64,
+        });
+        entry.first_used = entry.first_used.min(record.timestamp);
+        entry.last_used = entry.last_used.max(record.timestamp);
+        record
+    }
+
+    fn entries_between(
+        &self,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> impl Iterator<Item = &DailyEntry> + '_ {
+        self.data
+            .days
+            .range(date_key(from)..=date_key(to))
+            .flat_map(|(_, entries)| entries)
+    }
+
+    /// Totals for the days `from` through `to`, inclusive.
+    pub fn totals(&self, from: NaiveDate, to: NaiveDate) -> UsageTotals {
+        let mut totals = UsageTotals::default();
+        for entry in self.entries_between(from, to) {
+            totals.add(&entry.totals);
+        }
+        totals
+    }
+
+    /// Totals per provider and model for the days `from` through `to`,
+    /// sorted by provider then model.
+    pub fn breakdown(&self, from: NaiveDate, to: NaiveDate) -> Vec<(String, String, UsageTotals)> {
+        let mut by_model: BTreeMap<(String, String), UsageTotals> = BTreeMap::new();
+        for entry in self.entries_between(from, to) {
+            by_model
+                .entry((entry.provider.clone(), entry.model.clone()))
+                .or_default()
+                .add(&entry.totals);
+        }
+        by_model
+            .into_iter()
+            .map(|((provider, model), totals)| (provider, model, totals))
+            .collect()
+    }
+
+    /// Usage so far today.
+    pub fn today(&self) -> UsageTotals {
+        let today = Local::now().date_naive();
+        self.totals(today, today)
+    }
+
+    /// Usage so far this calendar month.
+    pub fn this_month(&self) -> UsageTotals {
+        let today = Local::now().date_naive();
+        self.totals(today.with_day(1).unwrap_or(today), today)
+    }
+
+    /// Write the daily aggregates back to the file this tracker was loaded
+    /// from. Does nothing for trackers made with [`UsageTracker::new`].
+    pub fn save(&self) -> UsageResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, serde_json::to_string_pretty(&self.data)?)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::StopReason;
+    use tempfile::TempDir;
+
+    fn completion(content: &str, tokens_used: Option<usize>) -> CompletionResponse {
+        CompletionResponse {
+            content: content.to_string(),
+            stop_reason: StopReason::Complete,
+            tokens_used,
+            model: Some("gpt-4o-mini".to_string()),
+        }
+    }
+
+    fn priced() -> UsageTracker {
+        UsageTracker::new().with_pricing(
+            "openai",
+            HashMap::from([(
+                "gpt-4o-mini".to_string(),
+                ModelPrice {
+                    input_per_1k: 1.0,
+                    output_per_1k: 2.0,
+                },
+            )]),
+        )
+    }
+
+    #[test]
+    fn test_reported_tokens_are_priced_and_split() {
+        let mut tracker = priced();
+        // 12 estimated prompt tokens and 4 answer tokens: 3/4 of 1000 is input
+        let record = tracker.record_completion(
+            "openai",
+            &"p".repeat(48),
+            &completion("abcdefghijklmnop", Some(1000)),
+        );
+
+        assert_eq!(record.input_tokens, 750);
+        assert_eq!(record.output_tokens, 250);
+        assert!(!record.estimated);
+        assert!((record.cost_usd - (0.75 + 0.5)).abs() < 1e-9);
+
+        let today = tracker.today();
+        assert_eq!(today.requests, 1);
+        assert_eq!(today.tokens(), 1000);
+        assert_eq!(today.estimated_requests, 0);
+        assert_eq!(tracker.this_month(), today);
+    }
+
+    #[test]
+    fn test_missing_token_counts_are_estimated() {
+        let mut tracker = priced();
+        let mut response = completion("four", None);
+        response.model = Some("llamafile-7b".to_string());
+        let record = tracker.record_completion("llamafile", "eight ch", &response);
+        tracker.record_embedding("llamafile", "llamafile-7b", &["abcdefgh".to_string()]);
+
+        assert_eq!((record.input_tokens, record.output_tokens), (2, 1));
+        assert!(record.estimated);
+        assert_eq!(record.cost_usd, 0.0);
+
+        let today = tracker.today();
+        assert_eq!(today.requests, 2);
+        assert_eq!(today.estimated_requests, 2);
+        assert_eq!(today.input_tokens, 4);
+    }
+
+    #[test]
+    fn test_daily_aggregates_persist_and_split_by_day() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("usage.json");
+        let now = chrono::Utc::now().timestamp();
+        let last_week = now - 7 * 86_400;
+
+        let mut tracker = UsageTracker::load(&path).unwrap();
+        for timestamp in [last_week, now, now] {
+            tracker.record(UsageRecord {
+                provider: "openai".to_string(),
+                model: "gpt-4o-mini".to_string(),
+                class: RequestClass::Completion,
+                input_tokens: 10,
+                output_tokens: 5,
+                cost_usd: 0.01,
+                estimated: false,
+                timestamp,
+            });
+        }
+        tracker.save().unwrap();
+
+        let tracker = UsageTracker::load(&path).unwrap();
+        assert_eq!(tracker.today().requests, 2);
+        assert_eq!(tracker.today().tokens(), 30);
+        let week = tracker.totals(local_date(last_week), local_date(now));
+        assert_eq!(week.requests, 3);
+        assert!((week.cost_usd - 0.03).abs() < 1e-9);
+
+        let breakdown = tracker.breakdown(local_date(last_week), local_date(now));
+        assert_eq!(breakdown.len(), 1);
+        assert_eq!(breakdown[0].1, "gpt-4o-mini");
+        assert_eq!(breakdown[0].2.requests, 3);
+    }
+
+    #[test]
+    fn test_wildcard_price() {
+        let tracker = UsageTracker::new().with_pricing(
+            "openai",
+            HashMap::from([(
+                "*".to_string(),
+                ModelPrice {
+                    input_per_1k: 0.5,
+                    output_per_1k: 0.0,
+                },
+            )]),
+        );
+        assert_eq!(tracker.price("openai", "anything").input_per_1k, 0.5);
+        assert_eq!(tracker.price("anthropic", "claude").input_per_1k, 0.0);
+    }
+}
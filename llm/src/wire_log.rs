@@ -0,0 +1,440 @@
+//! Opt-in log of the HTTP traffic between providers and their APIs.
+//!
+//! Once a [`WireLog`] is [installed](install), every provider request is
+//! appended to it as one JSON line: method, URL, headers and body going out,
+//! status and body coming back. The response body is kept as received text,
+//! so a reply that fails to parse can still be inspected. Credentials never
+//! reach the file: auth headers and secret-named fields are replaced,
+//! `sk-` keys anywhere in the text are masked, and long strings such as
+//! prompts are cut short.
+
+use chrono::Utc;
+use lucastra_config::LlmConfig;
+use reqwest::header::HeaderMap;
+use reqwest::{RequestBuilder, Response, StatusCode, Url};
+use serde_json::{json, Map, Value};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, RwLock};
+use std::time::Instant;
+
+/// Characters kept of each string in a logged body.
+pub const DEFAULT_PROMPT_CHARS: usize = lucastra_config::DEFAULT_DEBUG_LOG_PROMPT_CHARS;
+
+/// Size the log may reach before it is rotated to `<path>.1`.
+pub const DEFAULT_WIRE_LOG_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Written in place of secrets.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// The log providers write to, if any.
+static INSTALLED: RwLock<Option<WireLog>> = RwLock::new(None);
+
+/// Serializes appends so concurrent requests don't interleave lines.
+static WIRE_WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+/// JSONL log of provider requests and responses, rotated to `<path>.1` once
+/// it grows past `max_bytes`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct WireLog {
+    path: PathBuf,
+    prompt_chars: usize,
+    max_bytes: u64,
+}
+
+impl WireLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            prompt_chars: DEFAULT_PROMPT_CHARS,
+            max_bytes: DEFAULT_WIRE_LOG_MAX_BYTES,
+        }
+    }
+
+    /// The wire log to install for `config`, if `debug_log_requests` is set.
+    pub fn from_config(config: &LlmConfig) -> Option<Self> {
+        if !config.debug_log_requests {
+            return None;
+        }
+        let dir = lucastra_config::get_logs_dir()
+            .map_err(|e| tracing::warn!("Wire log disabled: {}", e))
+            .ok()?;
+        Some(
+            Self::new(dir.join("llm_wire.jsonl"))
+                .with_prompt_chars(config.debug_log_prompt_chars)
+                .with_max_bytes(u64::from(config.debug_log_max_size_mb) * 1024 * 1024),
+        )
+    }
+
+    /// Cut strings in logged bodies, prompts included, to `chars` characters.
+    pub fn with_prompt_chars(mut self, chars: usize) -> Self {
+        self.prompt_chars = chars;
+        self
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".1");
+        PathBuf::from(name)
+    }
+
+    /// Append `entry` as a single line.
+    fn append(&self, entry: &Value) -> std::io::Result<()> {
+        let mut line = entry.to_string();
+        line.push('\n');
+
+        let _guard = WIRE_WRITE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        if let Ok(metadata) = fs::metadata(&self.path) {
+            if metadata.len() > 0 && metadata.len() + line.len() as u64 > self.max_bytes {
+                fs::rename(&self.path, self.rotated_path())?;
+            }
+        }
+        fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?
+            .write_all(line.as_bytes())
+    }
+
+    /// The logged form of `request`, secrets removed.
+    fn request_entry(&self, provider: &str, request: &reqwest::Request) -> Map<String, Value> {
+        let body = request
+            .body()
+            .and_then(|body| body.as_bytes())
+            .map(|bytes| match serde_json::from_slice::<Value>(bytes) {
+                Ok(value) => self.sanitize(value),
+                Err(_) => Value::String(self.sanitize_text(&String::from_utf8_lossy(bytes))),
+            });
+
+        let mut entry = Map::new();
+        entry.insert("timestamp".into(), json!(Utc::now().to_rfc3339()));
+        entry.insert("provider".into(), json!(provider));
+        entry.insert("method".into(), json!(request.method().as_str()));
+        entry.insert("url".into(), json!(redact_url(request.url())));
+        entry.insert("request_headers".into(), redact_headers(request.headers()));
+        entry.insert("request_body".into(), body.unwrap_or(Value::Null));
+        entry
+    }
+
+    /// `value` with secret-named fields replaced and every string masked and
+    /// truncated.
+    fn sanitize(&self, value: Value) -> Value {
+        match value {
+            Value::String(text) => Value::String(self.sanitize_text(&text)),
+            Value::Array(items) => items.into_iter().map(|v| self.sanitize(v)).collect(),
+            Value::Object(fields) => fields
+                .into_iter()
+                .map(|(name, value)| {
+                    let value = if is_secret_name(&name) && !value.is_null() {
+                        json!(REDACTED)
+                    } else {
+                        self.sanitize(value)
+                    };
+                    (name, value)
+                })
+                .collect(),
+            other => other,
+        }
+    }
+
+    /// Masking comes first, so a key cut in half can't slip through.
+    fn sanitize_text(&self, text: &str) -> String {
+        truncate(&redact_text(text), self.prompt_chars)
+    }
+
+    fn write(&self, mut entry: Map<String, Value>, started: Instant) {
+        entry.insert(
+            "elapsed_ms".into(),
+            json!(started.elapsed().as_millis() as u64),
+        );
+        if let Err(e) = self.append(&Value::Object(entry)) {
+            tracing::warn!("Could not write wire log {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Log provider requests to `log` from now on, or stop logging with `None`.
+pub fn install(log: Option<WireLog>) {
+    *INSTALLED
+        .write()
+        .unwrap_or_else(|poisoned| poisoned.into_inner()) = log;
+}
+
+/// The log installed with [`install`].
+pub fn installed() -> Option<WireLog> {
+    INSTALLED
+        .read()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .clone()
+}
+
+/// Send `request` for `provider` and read the whole response body, logging
+/// both when a wire log is installed.
+pub(crate) async fn send_text(
+    provider: &str,
+    request: RequestBuilder,
+) -> reqwest::Result<(StatusCode, String)> {
+    let Some(log) = installed() else {
+        let response = request.send().await?;
+        let status = response.status();
+        return Ok((status, response.text().await?));
+    };
+
+    let started = Instant::now();
+    let (client, request) = request.build_split();
+    let request = request?;
+    let mut entry = log.request_entry(provider, &request);
+    let result = match client.execute(request).await {
+        Ok(response) => {
+            let status = response.status();
+            entry.insert("status".into(), json!(status.as_u16()));
+            response.text().await.map(|body| (status, body))
+        }
+        Err(e) => Err(e),
+    };
+    match &result {
+        Ok((_, body)) => {
+            entry.insert("response_body".into(), json!(log.sanitize_text(body)));
+        }
+        Err(e) => {
+            entry.insert("error".into(), json!(redact_text(&e.to_string())));
+        }
+    }
+    log.write(entry, started);
+    result
+}
+
+/// Send `request` for `provider` whose response is streamed. Only the
+/// request and status are logged; the body belongs to the caller.
+pub(crate) async fn send_stream(
+    provider: &str,
+    request: RequestBuilder,
+) -> reqwest::Result<Response> {
+    let Some(log) = installed() else {
+        return request.send().await;
+    };
+
+    let started = Instant::now();
+    let (client, request) = request.build_split();
+    let request = request?;
+    let mut entry = log.request_entry(provider, &request);
+    let result = client.execute(request).await;
+    match &result {
+        Ok(response) => {
+            entry.insert("status".into(), json!(response.status().as_u16()));
+            entry.insert("response_body".into(), json!("[streamed]"));
+        }
+        Err(e) => {
+            entry.insert("error".into(), json!(redact_text(&e.to_string())));
+        }
+    }
+    log.write(entry, started);
+    result
+}
+
+/// `text` with every `sk-` API key masked.
+pub fn redact_text(text: &str) -> String {
+    let is_key_char = |c: char| c.is_ascii_alphanumeric() || c == '-' || c == '_';
+    let mut redacted = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(start) = rest.find("sk-") {
+        let (before, candidate) = rest.split_at(start);
+        redacted.push_str(before);
+        let key_len = candidate[3..]
+            .find(|c: char| !is_key_char(c))
+            .unwrap_or(candidate.len() - 3);
+        // Only whole words with enough key characters to be a key
+        let starts_word = !before.ends_with(is_key_char);
+        if starts_word && key_len >= 8 {
+            redacted.push_str("sk-");
+            redacted.push_str(REDACTED);
+            rest = &candidate[3 + key_len..];
+        } else {
+            redacted.push_str("sk-");
+            rest = &candidate[3..];
+        }
+    }
+    redacted.push_str(rest);
+    redacted
+}
+
+/// Whether a header, field or query parameter called `name` holds a
+/// credential.
+fn is_secret_name(name: &str) -> bool {
+    let name = name.to_ascii_lowercase().replace('-', "_");
+    matches!(
+        name.as_str(),
+        "authorization" | "proxy_authorization" | "cookie" | "key" | "token" | "password"
+    ) || ["api_key", "apikey", "secret", "access_token", "auth_token"]
+        .iter()
+        .any(|suffix| name.ends_with(suffix))
+}
+
+fn redact_headers(headers: &HeaderMap) -> Value {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = if is_secret_name(name.as_str()) {
+                REDACTED.to_string()
+            } else {
+                redact_text(&String::from_utf8_lossy(value.as_bytes()))
+            };
+            (name.to_string(), Value::String(value))
+        })
+        .collect::<Map<_, _>>()
+        .into()
+}
+
+fn redact_url(url: &Url) -> String {
+    let mut url = url.clone();
+    if url.query_pairs().any(|(name, _)| is_secret_name(&name)) {
+        let pairs: Vec<(String, String)> = url
+            .query_pairs()
+            .map(|(name, value)| {
+                let value = if is_secret_name(&name) {
+                    REDACTED.to_string()
+                } else {
+                    value.into_owned()
+                };
+                (name.into_owned(), value)
+            })
+            .collect();
+        url.query_pairs_mut().clear().extend_pairs(pairs);
+    }
+    redact_text(url.as_str())
+}
+
+/// The first `chars` characters of `text`, noting how many were cut.
+fn truncate(text: &str, chars: usize) -> String {
+    match text.char_indices().nth(chars) {
+        Some((end, _)) => format!(
+            "{}… [{} more chars]",
+            &text[..end],
+            text[end..].chars().count()
+        ),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::providers::anthropic::AnthropicProvider;
+    use crate::providers::{CompletionRequest, LLMProvider, ProviderError};
+
+    const PROMPT_WITH_KEY: &str = include_str!("../tests/fixtures/wire_log/prompt_with_key.txt");
+    const PROMPT_KEY: &str = "sk-proj-4fQx9TzL2mWv8RkN3bYc7HdJ";
+    const API_KEY: &str = "sk-ant-REDACTED";
+
+    #[test]
+    fn test_bodies_are_masked_and_truncated() {
+        let log = WireLog::new("unused").with_prompt_chars(12);
+        let body = json!({
+            "model": "gpt-4o-mini",
+            "api_key": "anything",
+            "max_tokens": 64,
+            "messages": [{"role": "user", "content": format!("use {} please", PROMPT_KEY)}],
+        });
+
+        let sanitized = log.sanitize(body);
+        assert_eq!(sanitized["api_key"], REDACTED);
+        assert_eq!(sanitized["max_tokens"], 64);
+        assert_eq!(sanitized["model"], "gpt-4o-mini");
+        assert_eq!(
+            sanitized["messages"][0]["content"],
+            "use sk-[REDA… [12 more chars]"
+        );
+
+        assert_eq!(
+            redact_text("task-12345678 and sk-short"),
+            "task-12345678 and sk-short"
+        );
+        assert_eq!(
+            redact_url(&Url::parse("https://api.example.com/v1/models?key=abc&limit=5").unwrap()),
+            "https://api.example.com/v1/models?key=%5BREDACTED%5D&limit=5"
+        );
+    }
+
+    #[test]
+    fn test_log_is_rotated_at_its_size_cap() {
+        let dir = tempfile::tempdir().unwrap();
+        let log = WireLog::new(dir.path().join("logs/llm_wire.jsonl")).with_max_bytes(100);
+        let entry = json!({"padding": "x".repeat(60)});
+
+        log.append(&entry).unwrap();
+        assert!(!log.rotated_path().exists());
+        log.append(&entry).unwrap();
+        assert!(log.rotated_path().exists());
+        assert_eq!(fs::read_to_string(log.path()).unwrap().lines().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_keys_in_requests_are_masked_in_the_log() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("llm_wire.jsonl");
+        let base_url = crate::providers::test_server::serve(
+            200,
+            "application/json",
+            "<html><body>502 Bad Gateway</body></html>",
+        )
+        .await;
+        let provider = AnthropicProvider::new(API_KEY).with_base_url(base_url.clone());
+
+        install(Some(WireLog::new(&path)));
+        let result = provider
+            .complete(CompletionRequest {
+                prompt: PROMPT_WITH_KEY.to_string(),
+                ..CompletionRequest::default()
+            })
+            .await;
+        install(None);
+
+        match result {
+            Err(ProviderError::InvalidResponse(message)) => {
+                assert!(message.contains("<html><body>502 Bad Gateway</body></html>"))
+            }
+            other => panic!(
+                "expected an invalid response, got {:?}",
+                other.map(|r| r.content)
+            ),
+        }
+
+        let written = fs::read_to_string(&path).unwrap();
+        assert!(!written.contains(PROMPT_KEY));
+        assert!(!written.contains(API_KEY));
+        // Other tests' requests may land in the log while it is installed
+        let entry: Value = written
+            .lines()
+            .map(|line| serde_json::from_str::<Value>(line).unwrap())
+            .find(|entry| entry["url"].as_str().unwrap().starts_with(&base_url))
+            .unwrap();
+        assert_eq!(entry["provider"], "anthropic");
+        assert_eq!(entry["request_headers"]["x-api-key"], REDACTED);
+        assert_eq!(entry["status"], 200);
+        assert_eq!(
+            entry["response_body"],
+            "<html><body>502 Bad Gateway</body></html>"
+        );
+        let prompt = entry["request_body"]["messages"][0]["content"]
+            .as_str()
+            .unwrap();
+        assert!(prompt.contains("sk-[REDACTED]"));
+        assert!(prompt.contains("deployment checklist"));
+    }
+}
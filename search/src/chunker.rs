@@ -0,0 +1,282 @@
+//! Splits long documents into chunks before embedding.
+//!
+//! Sizes are counted in whitespace-separated tokens. Chunk offsets and
+//! lengths are byte positions into the original text.
+
+use serde::{Deserialize, Serialize};
+use std::str::FromStr;
+
+/// Default chunk size in tokens.
+pub const DEFAULT_CHUNK_SIZE: usize = 512;
+
+/// Default overlap between consecutive fixed-size chunks, in tokens.
+pub const DEFAULT_CHUNK_OVERLAP: usize = 64;
+
+/// How a document is divided into chunks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ChunkStrategy {
+    /// Windows of `size` tokens, each sharing `overlap` tokens with the previous one.
+    FixedSize { size: usize, overlap: usize },
+    /// One chunk per blank-line separated paragraph, split further past `max_size`.
+    Paragraph { max_size: usize },
+    /// One chunk per Markdown heading section, split further past `max_size`.
+    MarkdownHeadings { max_size: usize },
+}
+
+impl Default for ChunkStrategy {
+    fn default() -> Self {
+        ChunkStrategy::FixedSize {
+            size: DEFAULT_CHUNK_SIZE,
+            overlap: DEFAULT_CHUNK_OVERLAP,
+        }
+    }
+}
+
+impl FromStr for ChunkStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "fixed" => Ok(ChunkStrategy::default()),
+            "paragraph" => Ok(ChunkStrategy::Paragraph {
+                max_size: DEFAULT_CHUNK_SIZE,
+            }),
+            "markdown" => Ok(ChunkStrategy::MarkdownHeadings {
+                max_size: DEFAULT_CHUNK_SIZE,
+            }),
+            other => Err(format!(
+                "unknown chunk strategy '{}' (expected fixed, paragraph or markdown)",
+                other
+            )),
+        }
+    }
+}
+
+/// A region of a document.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Chunk {
+    pub text: String,
+    /// Byte offset of the chunk in the source document.
+    pub offset: usize,
+    /// Length of the chunk in bytes.
+    pub length: usize,
+}
+
+/// Splits text according to a [`ChunkStrategy`].
+#[derive(Debug, Clone, Default)]
+pub struct Chunker {
+    strategy: ChunkStrategy,
+}
+
+impl Chunker {
+    pub fn new(strategy: ChunkStrategy) -> Self {
+        Self { strategy }
+    }
+
+    pub fn strategy(&self) -> ChunkStrategy {
+        self.strategy
+    }
+
+    /// Split `text` into chunks. Blank text yields no chunks; text shorter
+    /// than one chunk yields exactly one.
+    pub fn chunk(&self, text: &str) -> Vec<Chunk> {
+        match self.strategy {
+            ChunkStrategy::FixedSize { size, overlap } => split_tokens(text, 0, size, overlap),
+            ChunkStrategy::Paragraph { max_size } => paragraph_spans(text)
+                .into_iter()
+                .flat_map(|(start, end)| split_tokens(&text[start..end], start, max_size, 0))
+                .collect(),
+            ChunkStrategy::MarkdownHeadings { max_size } => heading_spans(text)
+                .into_iter()
+                .flat_map(|(start, end)| split_tokens(&text[start..end], start, max_size, 0))
+                .collect(),
+        }
+    }
+}
+
+/// Fixed-size token windows over `text`, whose offsets are shifted by `base`.
+fn split_tokens(text: &str, base: usize, size: usize, overlap: usize) -> Vec<Chunk> {
+    let tokens = token_spans(text);
+    if tokens.is_empty() {
+        return Vec::new();
+    }
+
+    let size = size.max(1);
+    let step = size.saturating_sub(overlap).max(1);
+    let mut chunks = Vec::new();
+    let mut first = 0;
+
+    loop {
+        let last = (first + size).min(tokens.len());
+        let start = tokens[first].0;
+        let end = tokens[last - 1].1;
+        chunks.push(Chunk {
+            text: text[start..end].to_string(),
+            offset: base + start,
+            length: end - start,
+        });
+
+        // The window that reaches the end is the final one, even if a
+        // further step would still overlap it
+        if last == tokens.len() {
+            break;
+        }
+        first += step;
+    }
+
+    chunks
+}
+
+/// Byte spans of whitespace-separated tokens.
+fn token_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    for (i, c) in text.char_indices() {
+        match (c.is_whitespace(), start) {
+            (true, Some(s)) => {
+                spans.push((s, i));
+                start = None;
+            }
+            (false, None) => start = Some(i),
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// Byte spans of paragraphs separated by blank lines.
+fn paragraph_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut spans = Vec::new();
+    let mut start = None;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        if line.trim().is_empty() {
+            if let Some(s) = start.take() {
+                spans.push((s, offset));
+            }
+        } else if start.is_none() {
+            start = Some(offset);
+        }
+        offset += line.len();
+    }
+    if let Some(s) = start {
+        spans.push((s, text.len()));
+    }
+    spans
+}
+
+/// Byte spans of Markdown sections, each starting at a heading line.
+/// Text before the first heading forms its own section.
+fn heading_spans(text: &str) -> Vec<(usize, usize)> {
+    let mut starts = vec![0];
+    let mut in_fence = false;
+    let mut offset = 0;
+
+    for line in text.split_inclusive('\n') {
+        let trimmed = line.trim_start();
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            in_fence = !in_fence;
+        } else if !in_fence && is_heading(line) && offset > 0 {
+            starts.push(offset);
+        }
+        offset += line.len();
+    }
+
+    starts
+        .iter()
+        .zip(starts.iter().skip(1).chain(std::iter::once(&text.len())))
+        .map(|(&start, &end)| (start, end))
+        .collect()
+}
+
+fn is_heading(line: &str) -> bool {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    let rest = &line[indent..];
+    let hashes = rest.chars().take_while(|&c| c == '#').count();
+    indent <= 3
+        && (1..=6).contains(&hashes)
+        && rest[hashes..]
+            .chars()
+            .next()
+            .is_none_or(char::is_whitespace)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(count: usize) -> String {
+        (0..count)
+            .map(|i| format!("w{}", i))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    #[test]
+    fn test_short_document_is_one_chunk() {
+        let text = "  just a few words  ";
+        let chunks = Chunker::default().chunk(text);
+        assert_eq!(chunks.len(), 1);
+        assert_eq!(chunks[0].text, "just a few words");
+        assert_eq!(
+            &text[chunks[0].offset..chunks[0].offset + chunks[0].length],
+            "just a few words"
+        );
+        assert!(Chunker::default().chunk("   \n ").is_empty());
+    }
+
+    #[test]
+    fn test_fixed_size_overlap_without_duplicate_tail() {
+        let chunker = Chunker::new(ChunkStrategy::FixedSize {
+            size: 4,
+            overlap: 1,
+        });
+        // Windows start at tokens 0, 3, 6; the one at 6 reaches the end
+        let chunks = chunker.chunk(&words(10));
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["w0 w1 w2 w3", "w3 w4 w5 w6", "w6 w7 w8 w9"]);
+
+        let chunks = chunker.chunk(&words(8));
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["w0 w1 w2 w3", "w3 w4 w5 w6", "w6 w7"]);
+    }
+
+    #[test]
+    fn test_paragraph_chunks() {
+        let text = "First paragraph\nstill first.\n\n\nSecond one.\n";
+        let chunks = Chunker::new(ChunkStrategy::Paragraph { max_size: 100 }).chunk(text);
+        assert_eq!(chunks.len(), 2);
+        assert_eq!(chunks[0].text, "First paragraph\nstill first.");
+        assert_eq!(chunks[1].text, "Second one.");
+        assert_eq!(chunks[1].offset, text.find("Second").unwrap());
+    }
+
+    #[test]
+    fn test_markdown_heading_chunks() {
+        let text = "Intro text\n# Setup\nInstall it\n```\n# not a heading\n```\n## Usage\nRun it\n";
+        let chunks = Chunker::new(ChunkStrategy::MarkdownHeadings { max_size: 100 }).chunk(text);
+        let texts: Vec<&str> = chunks.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(
+            texts,
+            vec![
+                "Intro text",
+                "# Setup\nInstall it\n```\n# not a heading\n```",
+                "## Usage\nRun it"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_oversized_section_is_split() {
+        // "#", "Big" and eight words make ten tokens
+        let text = format!("# Big\n{}", words(8));
+        let chunks = Chunker::new(ChunkStrategy::MarkdownHeadings { max_size: 5 }).chunk(&text);
+        assert_eq!(chunks.len(), 2);
+        assert!(chunks[0].text.starts_with("# Big"));
+        assert_eq!(chunks[1].text, "w3 w4 w5 w6 w7");
+    }
+}
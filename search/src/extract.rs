@@ -0,0 +1,273 @@
+//! Text extraction for the file formats the indexer reads.
+//!
+//! The indexer hands each file to the first [`ContentExtractor`] that
+//! handles it, chosen by extension or leading bytes, and indexes the text it
+//! returns. Plain text is the fallback. Pages are separated by
+//! [`PAGE_BREAK`], so search results can say which page they matched on.
+
+use crate::chunker::Chunk;
+use lucastra_core::{LuCastraError, Result};
+use quick_xml::events::Event;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::Arc;
+
+/// Separates pages in extracted text (form feed).
+pub const PAGE_BREAK: char = '\u{c}';
+
+/// Format a document was extracted from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SourceFormat {
+    Text,
+    Pdf,
+    Docx,
+    Html,
+}
+
+impl SourceFormat {
+    /// Format of the file at `path` going by its extension.
+    pub fn of_path(path: &Path) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("pdf") => Self::Pdf,
+            Some("docx") => Self::Docx,
+            Some("html" | "htm") => Self::Html,
+            _ => Self::Text,
+        }
+    }
+
+    /// Short name shown with search results, e.g. `pdf`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Text => "text",
+            Self::Pdf => "pdf",
+            Self::Docx => "docx",
+            Self::Html => "html",
+        }
+    }
+}
+
+impl fmt::Display for SourceFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Vector index metadata for `chunk` of the document at `path` with text
+/// `content`: the `dir` it is in, ending in `/` so a prefix matches a whole
+/// tree, the local date it was `modified` on (`YYYY-MM-DD`) when `path` is
+/// a host file, the `format` it was extracted from, unless plain text, and
+/// the `page` it starts on in paged documents.
+pub fn chunk_metadata(path: &Path, content: &str, chunk: &Chunk) -> HashMap<String, String> {
+    let mut metadata = HashMap::new();
+    if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+        let mut dir = dir.to_string_lossy().into_owned();
+        if !dir.ends_with(std::path::MAIN_SEPARATOR) {
+            dir.push(std::path::MAIN_SEPARATOR);
+        }
+        metadata.insert("dir".to_string(), dir);
+    }
+    if let Ok(modified) = std::fs::metadata(path).and_then(|m| m.modified()) {
+        let date = chrono::DateTime::<chrono::Local>::from(modified).format("%Y-%m-%d");
+        metadata.insert("modified".to_string(), date.to_string());
+    }
+    let format = SourceFormat::of_path(path);
+    if format != SourceFormat::Text {
+        metadata.insert("format".to_string(), format.name().to_string());
+    }
+    if content.contains(PAGE_BREAK) {
+        let before = content.get(..chunk.offset).unwrap_or(content);
+        let page = before.matches(PAGE_BREAK).count() + 1;
+        metadata.insert("page".to_string(), page.to_string());
+    }
+    metadata
+}
+
+/// Turns the bytes of one file format into indexable text.
+pub trait ContentExtractor: fmt::Debug + Send + Sync {
+    fn format(&self) -> SourceFormat;
+
+    /// Whether this extractor reads the file at `path`, whose contents start
+    /// with `head`.
+    fn handles(&self, path: &Path, head: &[u8]) -> bool;
+
+    fn extract(&self, bytes: &[u8]) -> Result<String>;
+}
+
+/// UTF-8 text, stored as is. Handles every file.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TextExtractor;
+
+impl ContentExtractor for TextExtractor {
+    fn format(&self) -> SourceFormat {
+        SourceFormat::Text
+    }
+
+    fn handles(&self, _path: &Path, _head: &[u8]) -> bool {
+        true
+    }
+
+    fn extract(&self, bytes: &[u8]) -> Result<String> {
+        String::from_utf8(bytes.to_vec())
+            .map_err(|_| LuCastraError::ServiceError("not UTF-8 text".to_string()))
+    }
+}
+
+/// Text of each PDF page, in page order.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PdfExtractor;
+
+impl ContentExtractor for PdfExtractor {
+    fn format(&self) -> SourceFormat {
+        SourceFormat::Pdf
+    }
+
+    fn handles(&self, path: &Path, head: &[u8]) -> bool {
+        head.starts_with(b"%PDF-") || SourceFormat::of_path(path) == SourceFormat::Pdf
+    }
+
+    fn extract(&self, bytes: &[u8]) -> Result<String> {
+        // The parser panics on some malformed files; treat that as a failure
+        let pages = std::panic::catch_unwind(|| pdf_extract::extract_text_from_mem_by_pages(bytes))
+            .map_err(|_| LuCastraError::ServiceError("malformed PDF".to_string()))?
+            .map_err(|e| LuCastraError::ServiceError(format!("unreadable PDF: {}", e)))?;
+        Ok(pages
+            .iter()
+            .map(|page| page.trim())
+            .collect::<Vec<_>>()
+            .join(&format!("\n{}", PAGE_BREAK)))
+    }
+}
+
+/// Paragraph text of a Word document's body.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DocxExtractor;
+
+impl ContentExtractor for DocxExtractor {
+    fn format(&self) -> SourceFormat {
+        SourceFormat::Docx
+    }
+
+    fn handles(&self, path: &Path, head: &[u8]) -> bool {
+        SourceFormat::of_path(path) == SourceFormat::Docx && head.starts_with(b"PK\x03\x04")
+    }
+
+    fn extract(&self, bytes: &[u8]) -> Result<String> {
+        let invalid =
+            |e: &dyn fmt::Display| LuCastraError::ServiceError(format!("unreadable DOCX: {}", e));
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).map_err(|e| invalid(&e))?;
+        let mut xml = String::new();
+        archive
+            .by_name("word/document.xml")
+            .map_err(|e| invalid(&e))?
+            .read_to_string(&mut xml)
+            .map_err(|e| invalid(&e))?;
+
+        // Text runs are `<w:t>` elements; paragraphs are `<w:p>`
+        let mut reader = quick_xml::Reader::from_str(&xml);
+        let mut text = String::new();
+        let mut in_text = false;
+        loop {
+            match reader.read_event().map_err(|e| invalid(&e))? {
+                Event::Start(e) if e.local_name().as_ref() == b"t" => in_text = true,
+                Event::End(e) => match e.local_name().as_ref() {
+                    b"t" => in_text = false,
+                    b"p" => text.push('\n'),
+                    _ => {}
+                },
+                Event::Empty(e) => match e.local_name().as_ref() {
+                    b"tab" => text.push('\t'),
+                    b"br" | b"cr" => text.push('\n'),
+                    _ => {}
+                },
+                Event::Text(e) if in_text => text.push_str(&e.unescape().map_err(|e| invalid(&e))?),
+                Event::Eof => break,
+                _ => {}
+            }
+        }
+        Ok(text.trim_end().to_string())
+    }
+}
+
+/// The extractors an [`Indexer`](crate::Indexer) picks from: any added with
+/// [`Extractors::with`], then PDF, DOCX and the plain text fallback.
+#[derive(Debug, Clone)]
+pub struct Extractors {
+    extractors: Vec<Arc<dyn ContentExtractor>>,
+}
+
+impl Default for Extractors {
+    fn default() -> Self {
+        Self {
+            extractors: vec![Arc::new(PdfExtractor), Arc::new(DocxExtractor)],
+        }
+    }
+}
+
+impl Extractors {
+    /// Try `extractor` before the ones already added.
+    pub fn with(mut self, extractor: Arc<dyn ContentExtractor>) -> Self {
+        self.extractors.insert(0, extractor);
+        self
+    }
+
+    /// Extractor for the file at `path` starting with `head`.
+    pub fn for_file(&self, path: &Path, head: &[u8]) -> &dyn ContentExtractor {
+        self.extractors
+            .iter()
+            .find(|extractor| extractor.handles(path, head))
+            .map_or(&TextExtractor, |extractor| extractor.as_ref())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Indexer, SearchService};
+
+    const FIXTURES: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/extract");
+
+    #[test]
+    fn test_pdf_and_docx_phrases_become_searchable() {
+        let service = SearchService::new(None);
+        let summary = Indexer::new()
+            .index_path(Path::new(FIXTURES), &service)
+            .unwrap();
+        assert_eq!(summary.files_indexed, 2);
+        assert_eq!(summary.extraction_failures, 0);
+
+        let pdf = &service.search("\"harvest forecast\"", 5).unwrap()[0];
+        assert!(pdf.path.ends_with("report.pdf"));
+        assert_eq!(pdf.format.as_deref(), Some("pdf"));
+        assert_eq!(pdf.page, Some(2));
+        assert!(pdf.snippet.contains("**harvest** **forecast**"));
+
+        let docx = &service.search("\"greenhouse irrigation\"", 5).unwrap()[0];
+        assert!(docx.path.ends_with("minutes.docx"));
+        assert_eq!(docx.format.as_deref(), Some("docx"));
+        assert_eq!(docx.page, None);
+        assert!(docx
+            .snippet
+            .contains("approved the **greenhouse** **irrigation** budget"));
+    }
+
+    #[test]
+    fn test_extraction_is_chosen_by_extension_and_magic_bytes() {
+        let extractors = Extractors::default();
+        let format = |path: &str, head: &[u8]| extractors.for_file(Path::new(path), head).format();
+
+        assert_eq!(format("a.pdf", b""), SourceFormat::Pdf);
+        assert_eq!(format("scan.bin", b"%PDF-1.7"), SourceFormat::Pdf);
+        assert_eq!(format("a.docx", b"PK\x03\x04"), SourceFormat::Docx);
+        assert_eq!(format("a.zip", b"PK\x03\x04"), SourceFormat::Text);
+        assert_eq!(format("a.md", b"# notes"), SourceFormat::Text);
+
+        assert!(PdfExtractor.extract(b"%PDF-1.4 truncated").is_err());
+        assert!(DocxExtractor.extract(b"PK\x03\x04 truncated").is_err());
+    }
+}
@@ -0,0 +1,277 @@
+//! Hierarchical Navigable Small World graph for approximate nearest-neighbour search.
+//!
+//! Vectors are stored L2-normalised so the dot product equals cosine similarity.
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::{BinaryHeap, HashSet};
+
+/// Tuning parameters for the HNSW graph.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct HnswParams {
+    /// Neighbours kept per node on upper layers (layer 0 keeps twice as many).
+    pub m: usize,
+    /// Candidate list size while inserting.
+    pub ef_construction: usize,
+    /// Candidate list size while searching (raised to `k` if smaller).
+    pub ef_search: usize,
+}
+
+impl Default for HnswParams {
+    fn default() -> Self {
+        Self {
+            m: 16,
+            ef_construction: 200,
+            ef_search: 64,
+        }
+    }
+}
+
+/// Distance/node pair ordered by distance (ties broken by node id).
+#[derive(Debug, Clone, Copy)]
+struct Candidate {
+    distance: f32,
+    node: usize,
+}
+
+impl PartialEq for Candidate {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Candidate {}
+
+impl PartialOrd for Candidate {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Candidate {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.distance
+            .total_cmp(&other.distance)
+            .then(self.node.cmp(&other.node))
+    }
+}
+
+pub(crate) struct Hnsw {
+    params: HnswParams,
+    level_mult: f64,
+    vectors: Vec<Vec<f32>>,
+    /// `neighbors[node][layer]`
+    neighbors: Vec<Vec<Vec<usize>>>,
+    entry_point: Option<usize>,
+    max_layer: usize,
+    rng_state: u64,
+}
+
+impl Hnsw {
+    pub(crate) fn new(params: HnswParams) -> Self {
+        let m = params.m.max(2);
+        Self {
+            params: HnswParams { m, ..params },
+            level_mult: 1.0 / (m as f64).ln(),
+            vectors: Vec::new(),
+            neighbors: Vec::new(),
+            entry_point: None,
+            max_layer: 0,
+            // Fixed seed keeps graphs reproducible across rebuilds
+            rng_state: 0x9E37_79B9_7F4A_7C15,
+        }
+    }
+
+    /// Insert a vector; nodes are numbered in insertion order.
+    pub(crate) fn insert(&mut self, vector: &[f32]) -> usize {
+        let node = self.vectors.len();
+        let level = self.random_level();
+        self.vectors.push(normalize(vector));
+        self.neighbors.push(vec![Vec::new(); level + 1]);
+
+        let Some(mut entry) = self.entry_point else {
+            self.entry_point = Some(node);
+            self.max_layer = level;
+            return node;
+        };
+
+        let query = self.vectors[node].clone();
+
+        // Greedy descent through layers above the new node's level
+        for layer in (level + 1..=self.max_layer).rev() {
+            entry = self.search_layer(&query, &[entry], 1, layer)[0].node;
+        }
+
+        let mut entry_points = vec![entry];
+        for layer in (0..=level.min(self.max_layer)).rev() {
+            let candidates =
+                self.search_layer(&query, &entry_points, self.params.ef_construction, layer);
+            let max_neighbors = self.max_neighbors(layer);
+
+            let selected: Vec<usize> = candidates
+                .iter()
+                .take(self.params.m)
+                .map(|c| c.node)
+                .collect();
+            self.neighbors[node][layer] = selected.clone();
+
+            for neighbor in selected {
+                self.neighbors[neighbor][layer].push(node);
+                if self.neighbors[neighbor][layer].len() > max_neighbors {
+                    self.prune(neighbor, layer, max_neighbors);
+                }
+            }
+
+            entry_points = candidates.into_iter().map(|c| c.node).collect();
+        }
+
+        if level > self.max_layer {
+            self.max_layer = level;
+            self.entry_point = Some(node);
+        }
+
+        node
+    }
+
+    /// Approximate k nearest neighbours accepted by `accept`, as
+    /// `(node, cosine similarity)` pairs, best first.
+    ///
+    /// Rejected nodes are still traversed, and the candidate list widens until
+    /// `k` accepted nodes are found or the whole graph has been considered.
+    pub(crate) fn search_filtered(
+        &self,
+        query: &[f32],
+        k: usize,
+        accept: impl Fn(usize) -> bool,
+    ) -> Vec<(usize, f32)> {
+        let Some(mut entry) = self.entry_point else {
+            return Vec::new();
+        };
+        if k == 0 {
+            return Vec::new();
+        }
+
+        let query = normalize(query);
+        for layer in (1..=self.max_layer).rev() {
+            entry = self.search_layer(&query, &[entry], 1, layer)[0].node;
+        }
+
+        let mut ef = self.params.ef_search.max(k);
+        loop {
+            let candidates = self.search_layer(&query, &[entry], ef, 0);
+            let exhausted = candidates.len() >= self.vectors.len() || ef >= self.vectors.len();
+            let accepted: Vec<(usize, f32)> = candidates
+                .into_iter()
+                .filter(|c| accept(c.node))
+                .take(k)
+                .map(|c| (c.node, 1.0 - c.distance))
+                .collect();
+
+            if accepted.len() >= k || exhausted {
+                return accepted;
+            }
+            ef *= 2;
+        }
+    }
+
+    fn max_neighbors(&self, layer: usize) -> usize {
+        if layer == 0 {
+            self.params.m * 2
+        } else {
+            self.params.m
+        }
+    }
+
+    fn prune(&mut self, node: usize, layer: usize, keep: usize) {
+        let base = &self.vectors[node];
+        let mut scored: Vec<Candidate> = self.neighbors[node][layer]
+            .iter()
+            .map(|&n| Candidate {
+                distance: distance(base, &self.vectors[n]),
+                node: n,
+            })
+            .collect();
+        scored.sort();
+        scored.truncate(keep);
+        self.neighbors[node][layer] = scored.into_iter().map(|c| c.node).collect();
+    }
+
+    /// Best-first search within one layer; returns up to `ef` nodes, closest first.
+    fn search_layer(
+        &self,
+        query: &[f32],
+        entry_points: &[usize],
+        ef: usize,
+        layer: usize,
+    ) -> Vec<Candidate> {
+        let mut visited: HashSet<usize> = entry_points.iter().copied().collect();
+        // Min-heap of nodes to expand, max-heap of the best `ef` found so far
+        let mut to_visit: BinaryHeap<std::cmp::Reverse<Candidate>> = BinaryHeap::new();
+        let mut found: BinaryHeap<Candidate> = BinaryHeap::new();
+
+        for &node in entry_points {
+            let candidate = Candidate {
+                distance: distance(query, &self.vectors[node]),
+                node,
+            };
+            to_visit.push(std::cmp::Reverse(candidate));
+            found.push(candidate);
+        }
+        while found.len() > ef {
+            found.pop();
+        }
+
+        while let Some(std::cmp::Reverse(current)) = to_visit.pop() {
+            let worst = found.peek().map(|c| c.distance).unwrap_or(f32::INFINITY);
+            if current.distance > worst && found.len() >= ef {
+                break;
+            }
+
+            let Some(links) = self.neighbors[current.node].get(layer) else {
+                continue;
+            };
+            for &neighbor in links {
+                if !visited.insert(neighbor) {
+                    continue;
+                }
+                let candidate = Candidate {
+                    distance: distance(query, &self.vectors[neighbor]),
+                    node: neighbor,
+                };
+                let worst = found.peek().map(|c| c.distance).unwrap_or(f32::INFINITY);
+                if found.len() < ef || candidate.distance < worst {
+                    to_visit.push(std::cmp::Reverse(candidate));
+                    found.push(candidate);
+                    if found.len() > ef {
+                        found.pop();
+                    }
+                }
+            }
+        }
+
+        found.into_sorted_vec()
+    }
+
+    fn random_level(&mut self) -> usize {
+        // xorshift64*
+        self.rng_state ^= self.rng_state >> 12;
+        self.rng_state ^= self.rng_state << 25;
+        self.rng_state ^= self.rng_state >> 27;
+        let bits = self.rng_state.wrapping_mul(0x2545_F491_4F6C_DD1D);
+        let uniform = ((bits >> 11) as f64 + 1.0) / (1u64 << 53) as f64;
+        (-uniform.ln() * self.level_mult).floor() as usize
+    }
+}
+
+fn normalize(vector: &[f32]) -> Vec<f32> {
+    let norm = vector.iter().map(|x| x * x).sum::<f32>().sqrt();
+    if norm == 0.0 {
+        return vector.to_vec();
+    }
+    vector.iter().map(|x| x / norm).collect()
+}
+
+/// Cosine distance between two normalised vectors.
+fn distance(a: &[f32], b: &[f32]) -> f32 {
+    1.0 - a.iter().zip(b).map(|(x, y)| x * y).sum::<f32>()
+}
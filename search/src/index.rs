@@ -1,111 +1,402 @@
 //! BM25 inverted index implementation.
 
+use crate::query::{Clause, Query};
 use crate::tokenizer::Tokenizer;
-use lucastra_core::Result;
+use lucastra_config::SearchConfig;
+use lucastra_core::compat;
+use lucastra_core::{LuCastraError, Result};
+use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
+use std::path::Path;
 use tracing::debug;
 
-/// BM25 parameters.
-const K1: f32 = 1.5;
-const B: f32 = 0.75;
+/// Version of the saved index layout, stored as `schema_version` (earlier
+/// builds wrote `version`). Version 2 added term positions; unversioned
+/// indexes are version 1 and get positions rebuilt on load.
+pub const INDEX_VERSION: u32 = 2;
+
+/// BM25 ranking parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bm25Params {
+    /// Term-frequency saturation: 0 ignores repeats, larger values reward them.
+    pub k1: f32,
+    /// Document length normalization, from 0 (none) to 1 (full).
+    pub b: f32,
+}
+
+impl Default for Bm25Params {
+    fn default() -> Self {
+        Self { k1: 1.5, b: 0.75 }
+    }
+}
+
+impl Bm25Params {
+    /// Ranking parameters for the `[search]` settings.
+    pub fn from_config(config: &SearchConfig) -> Self {
+        Self {
+            k1: config.bm25_k1,
+            b: config.bm25_b,
+        }
+    }
+}
 
 /// Inverted index for BM25 scoring.
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BM25Index {
+    #[serde(
+        rename = "schema_version",
+        alias = "version",
+        default = "legacy_version"
+    )]
+    version: u32,
     /// Document ID → content tokens
     documents: HashMap<String, Vec<String>>,
     /// Term → set of document IDs
     term_docs: HashMap<String, HashSet<String>>,
-    /// Term → document frequencies
-    term_freqs: HashMap<String, HashMap<String, usize>>,
+    /// Term → document → positions of the term in the document's tokens,
+    /// ascending. A term's frequency is its number of positions.
+    #[serde(default)]
+    term_positions: HashMap<String, HashMap<String, Vec<usize>>>,
     /// Average document length
     avg_doc_len: f32,
+    /// How documents and queries are split into terms. Indexes saved
+    /// before this was recorded used the legacy tokenizer.
+    #[serde(default = "Tokenizer::legacy")]
+    tokenizer: Tokenizer,
+    /// Only affects scoring, so it isn't saved and can change at any time.
+    #[serde(skip)]
+    params: Bm25Params,
 }
 
 impl BM25Index {
     pub fn new() -> Self {
+        Self::with_tokenizer(Tokenizer::default())
+    }
+
+    /// Empty index splitting text with `tokenizer`.
+    pub fn with_tokenizer(tokenizer: Tokenizer) -> Self {
         Self {
+            version: INDEX_VERSION,
             documents: HashMap::new(),
             term_docs: HashMap::new(),
-            term_freqs: HashMap::new(),
+            term_positions: HashMap::new(),
             avg_doc_len: 0.0,
+            tokenizer,
+            params: Bm25Params::default(),
         }
     }
 
+    /// Score with `k1` and `b` instead of the defaults.
+    pub fn with_params(mut self, k1: f32, b: f32) -> Self {
+        self.set_params(Bm25Params { k1, b });
+        self
+    }
+
+    /// Change the ranking parameters; later searches use them.
+    pub fn set_params(&mut self, params: Bm25Params) {
+        self.params = params;
+    }
+
+    pub fn params(&self) -> Bm25Params {
+        self.params
+    }
+
+    pub fn tokenizer(&self) -> &Tokenizer {
+        &self.tokenizer
+    }
+
     /// Add a document to the index.
     pub fn add_document(&mut self, doc_id: &str, content: &str) -> Result<()> {
-        let tokens = Tokenizer::tokenize(content);
-        let tokens = Tokenizer::remove_stopwords(tokens);
+        let tokens = self.tokenizer.tokenize(content);
+        self.add_tokenized([(doc_id.to_string(), tokens)]);
+        Ok(())
+    }
+
+    /// Add documents already split by this index's tokenizer, updating the
+    /// average length once for the whole batch.
+    pub(crate) fn add_tokenized(&mut self, docs: impl IntoIterator<Item = (String, Vec<String>)>) {
+        for (doc_id, tokens) in docs {
+            debug!("Adding document {} with {} tokens", doc_id, tokens.len());
+
+            // Re-indexing replaces the old postings instead of double-counting
+            self.remove_postings(&doc_id);
+
+            self.add_postings(&doc_id, &tokens);
+            self.documents.insert(doc_id, tokens);
+        }
+        self.update_avg_doc_len();
+    }
+
+    /// Remove a document from the index. Returns false if it wasn't indexed.
+    pub fn remove_document(&mut self, doc_id: &str) -> bool {
+        self.remove_documents([doc_id]) > 0
+    }
+
+    /// Remove documents, updating the average length once. Returns how
+    /// many were indexed.
+    pub(crate) fn remove_documents<'a>(
+        &mut self,
+        doc_ids: impl IntoIterator<Item = &'a str>,
+    ) -> usize {
+        let removed = doc_ids
+            .into_iter()
+            .filter(|doc_id| self.remove_postings(doc_id))
+            .count();
+        if removed > 0 {
+            self.update_avg_doc_len();
+        }
+        removed
+    }
+
+    /// Check whether a document is indexed.
+    pub fn contains(&self, doc_id: &str) -> bool {
+        self.documents.contains_key(doc_id)
+    }
+
+    /// Number of indexed documents.
+    pub fn len(&self) -> usize {
+        self.documents.len()
+    }
+
+    /// Check if the index is empty.
+    pub fn is_empty(&self) -> bool {
+        self.documents.is_empty()
+    }
 
-        debug!("Adding document {} with {} tokens", doc_id, tokens.len());
+    /// Save the index as JSON.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)
+                .map_err(|e| LuCastraError::FilesystemError(e.to_string()))?;
+        }
+        let json = serde_json::to_string(self).map_err(|e| {
+            LuCastraError::ServiceError(format!("Failed to serialize index: {}", e))
+        })?;
+        std::fs::write(path, json).map_err(|e| LuCastraError::FilesystemError(e.to_string()))
+    }
 
-        // Store document
-        self.documents.insert(doc_id.to_string(), tokens.clone());
+    /// Load an index previously written by [`BM25Index::save`].
+    pub fn load(path: &Path) -> Result<Self> {
+        let json = std::fs::read_to_string(path)
+            .map_err(|e| LuCastraError::FilesystemError(e.to_string()))?;
+        let corrupted = |e: &dyn std::fmt::Display| {
+            LuCastraError::ServiceError(format!("Corrupted index {}: {}", path.display(), e))
+        };
+        let mut value: serde_json::Value =
+            serde_json::from_str(&json).map_err(|e| corrupted(&e))?;
+        // Read the version of either name before the layout it describes
+        if let Some(fields) = value.as_object_mut() {
+            if let Some(version) = fields.remove("version") {
+                fields.insert(compat::VERSION_FIELD.to_string(), version);
+            }
+        }
+        compat::json_version("search index", &value, INDEX_VERSION).map_err(|e| match e {
+            compat::SchemaError::UnsupportedVersion(e) => LuCastraError::UnsupportedVersion(e),
+            e => corrupted(&e),
+        })?;
+        let mut index: Self = serde_json::from_value(value).map_err(|e| corrupted(&e))?;
 
-        // Update term statistics
-        let mut term_count = HashMap::new();
-        for token in &tokens {
-            *term_count.entry(token.clone()).or_insert(0) += 1;
+        if index.version < INDEX_VERSION {
+            debug!(
+                "Upgrading index {} from version {}",
+                path.display(),
+                index.version
+            );
+            index.rebuild_postings();
+        }
+        Ok(index)
+    }
 
+    /// Record the postings of a document's tokens.
+    fn add_postings(&mut self, doc_id: &str, tokens: &[String]) {
+        for (position, token) in tokens.iter().enumerate() {
             self.term_docs
                 .entry(token.clone())
                 .or_default()
                 .insert(doc_id.to_string());
+            self.term_positions
+                .entry(token.clone())
+                .or_default()
+                .entry(doc_id.to_string())
+                .or_default()
+                .push(position);
         }
+    }
 
-        for (term, count) in term_count {
-            self.term_freqs
-                .entry(term)
-                .or_default()
-                .insert(doc_id.to_string(), count);
+    /// Recompute all postings from the stored document tokens.
+    fn rebuild_postings(&mut self) {
+        self.term_docs.clear();
+        self.term_positions.clear();
+        let documents = std::mem::take(&mut self.documents);
+        for (doc_id, tokens) in &documents {
+            self.add_postings(doc_id, tokens);
         }
+        self.documents = documents;
+        self.version = INDEX_VERSION;
+    }
 
-        // Recalculate average document length
-        let total_len: usize = self.documents.values().map(|d| d.len()).sum();
-        self.avg_doc_len = total_len as f32 / self.documents.len() as f32;
+    /// Drop a document's tokens and postings without touching the average length.
+    fn remove_postings(&mut self, doc_id: &str) -> bool {
+        let Some(tokens) = self.documents.remove(doc_id) else {
+            return false;
+        };
 
-        Ok(())
+        for token in tokens {
+            if let Some(docs) = self.term_docs.get_mut(&token) {
+                docs.remove(doc_id);
+                if docs.is_empty() {
+                    self.term_docs.remove(&token);
+                }
+            }
+            if let Some(positions) = self.term_positions.get_mut(&token) {
+                positions.remove(doc_id);
+                if positions.is_empty() {
+                    self.term_positions.remove(&token);
+                }
+            }
+        }
+        true
+    }
+
+    fn update_avg_doc_len(&mut self) {
+        let total_len: usize = self.documents.values().map(|d| d.len()).sum();
+        self.avg_doc_len = if self.documents.is_empty() {
+            0.0
+        } else {
+            total_len as f32 / self.documents.len() as f32
+        };
     }
 
-    /// Search for documents matching a query.
+    /// Search for the `top_k` best documents matching a query. See
+    /// [`BM25Index::search_all`] for the query syntax.
     pub fn search(&self, query: &str, top_k: usize) -> Result<Vec<(String, f32)>> {
-        let tokens = Tokenizer::tokenize(query);
-        let tokens = Tokenizer::remove_stopwords(tokens);
+        let mut ranked = self.search_all(query)?;
+        ranked.truncate(top_k);
+        Ok(ranked)
+    }
 
-        if tokens.is_empty() {
+    /// Every document matching a query, best first; ties are ordered by
+    /// document ID so pages of the ranking stay stable.
+    ///
+    /// Supports the [`Query`] syntax: `"quoted phrases"` must match as
+    /// adjacent terms, `+term` is required, `-term` excludes documents and
+    /// other terms are alternatives. Only required and alternative terms a
+    /// document matches add to its score.
+    pub fn search_all(&self, query: &str) -> Result<Vec<(String, f32)>> {
+        let query = Query::parse(query, &self.tokenizer);
+
+        if query.is_empty() {
             return Ok(Vec::new());
         }
 
-        let mut scores: HashMap<String, f32> = HashMap::new();
+        // Any match has to contain a required or alternative term
+        let candidates: HashSet<&String> = query
+            .scored_terms()
+            .filter_map(|term| self.term_docs.get(term))
+            .flatten()
+            .collect();
 
-        for token in tokens {
-            if let Some(docs) = self.term_docs.get(&token) {
-                let idf = self.idf(docs.len());
-
-                for doc_id in docs {
-                    let term_freq = self
-                        .term_freqs
-                        .get(&token)
-                        .and_then(|m| m.get(doc_id))
-                        .copied()
-                        .unwrap_or(0) as f32;
-
-                    let doc_len = self
-                        .documents
-                        .get(doc_id)
-                        .map(|d| d.len() as f32)
-                        .unwrap_or(0.0);
-
-                    let bm25_score = self.bm25_score(term_freq, idf, doc_len, self.avg_doc_len);
-
-                    *scores.entry(doc_id.clone()).or_insert(0.0) += bm25_score;
-                }
+        let mut ranked: Vec<(String, f32)> = Vec::new();
+        for doc_id in candidates {
+            if !query.must.iter().all(|c| self.matches(c, doc_id, true))
+                || query.must_not.iter().any(|c| self.matches(c, doc_id, true))
+            {
+                continue;
+            }
+            let matched: Vec<&Clause> = query
+                .should
+                .iter()
+                .filter(|c| self.matches(c, doc_id, false))
+                .collect();
+            if query.must.is_empty() && matched.is_empty() {
+                continue;
             }
+
+            let score = matched
+                .into_iter()
+                .chain(&query.must)
+                .flat_map(|c| &c.terms)
+                .map(|term| self.term_score(term, doc_id))
+                .sum();
+            ranked.push((doc_id.clone(), score));
         }
 
-        let mut ranked: Vec<_> = scores.into_iter().collect();
-        ranked.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap_or(std::cmp::Ordering::Equal));
+        ranked.sort_by(|a, b| {
+            b.1.partial_cmp(&a.1)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then_with(|| a.0.cmp(&b.0))
+        });
+
+        Ok(ranked)
+    }
+
+    /// Highest score any document could get for `query`: every known
+    /// required or alternative term matched with unbounded frequency. Terms
+    /// the index has never seen can't be matched by any document, so they
+    /// don't count.
+    pub fn max_score(&self, query: &str) -> f32 {
+        Query::parse(query, &self.tokenizer)
+            .scored_terms()
+            .filter_map(|term| self.term_docs.get(term))
+            .map(|docs| self.idf(docs.len()) * (self.params.k1 + 1.0))
+            .sum()
+    }
+
+    /// Whether a document matches a clause. Phrases need their terms in
+    /// order at adjacent positions; other clauses need all their terms if
+    /// `all` is set and any one otherwise.
+    fn matches(&self, clause: &Clause, doc_id: &str, all: bool) -> bool {
+        if clause.phrase {
+            return self.contains_phrase(&clause.terms, doc_id);
+        }
+        let mut present = clause
+            .terms
+            .iter()
+            .map(|term| self.positions(term, doc_id).is_some());
+        if all {
+            present.all(|p| p)
+        } else {
+            present.any(|p| p)
+        }
+    }
 
-        Ok(ranked.into_iter().take(top_k).collect())
+    fn contains_phrase(&self, terms: &[String], doc_id: &str) -> bool {
+        let Some((first, rest)) = terms.split_first() else {
+            return false;
+        };
+        let Some(starts) = self.positions(first, doc_id) else {
+            return false;
+        };
+        starts.iter().any(|&start| {
+            rest.iter().enumerate().all(|(offset, term)| {
+                self.positions(term, doc_id)
+                    .is_some_and(|p| p.binary_search(&(start + offset + 1)).is_ok())
+            })
+        })
+    }
+
+    fn positions(&self, term: &str, doc_id: &str) -> Option<&Vec<usize>> {
+        self.term_positions.get(term)?.get(doc_id)
+    }
+
+    /// BM25 contribution of one term to a document's score.
+    fn term_score(&self, term: &str, doc_id: &str) -> f32 {
+        let (Some(docs), Some(positions)) =
+            (self.term_docs.get(term), self.positions(term, doc_id))
+        else {
+            return 0.0;
+        };
+        let doc_len = self
+            .documents
+            .get(doc_id)
+            .map(|d| d.len() as f32)
+            .unwrap_or(0.0);
+        self.bm25_score(
+            positions.len() as f32,
+            self.idf(docs.len()),
+            doc_len,
+            self.avg_doc_len,
+        )
     }
 
     /// Calculate IDF (inverse document frequency).
@@ -116,8 +407,15 @@ impl BM25Index {
 
     /// Calculate BM25 score.
     fn bm25_score(&self, term_freq: f32, idf: f32, doc_len: f32, avg_doc_len: f32) -> f32 {
-        let numerator = term_freq * (K1 + 1.0);
-        let denominator = term_freq + K1 * (1.0 - B + B * (doc_len / avg_doc_len));
+        let Bm25Params { k1, b } = self.params;
+        let numerator = term_freq * (k1 + 1.0);
+        // An empty index has no average; count any document as typical
+        let relative_len = if avg_doc_len > 0.0 {
+            doc_len / avg_doc_len
+        } else {
+            1.0
+        };
+        let denominator = term_freq + k1 * (1.0 - b + b * relative_len);
         idf * (numerator / denominator)
     }
 
@@ -125,13 +423,226 @@ impl BM25Index {
     pub fn clear(&mut self) {
         self.documents.clear();
         self.term_docs.clear();
-        self.term_freqs.clear();
+        self.term_positions.clear();
         self.avg_doc_len = 0.0;
     }
 }
 
+/// Indexes saved before versioning.
+fn legacy_version() -> u32 {
+    1
+}
+
 impl Default for BM25Index {
     fn default() -> Self {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reindex_replaces_postings() {
+        let mut index = BM25Index::new();
+        index.add_document("doc", "rust rust rust").unwrap();
+        index.add_document("doc", "python").unwrap();
+
+        assert_eq!(index.len(), 1);
+        assert!(index.search("rust", 5).unwrap().is_empty());
+        assert_eq!(index.search("python", 5).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_max_score_bounds_search_scores() {
+        let mut index = BM25Index::new();
+        index
+            .add_document("a", "kernel kernel kernel scheduler")
+            .unwrap();
+        index.add_document("b", "scheduler tasks").unwrap();
+        index.add_document("c", "unrelated words").unwrap();
+
+        let max = index.max_score("kernel scheduler nonexistent");
+        assert!(max > 0.0);
+        for (_, score) in index.search("kernel scheduler nonexistent", 5).unwrap() {
+            assert!(score < max);
+        }
+        assert_eq!(index.max_score("nonexistent"), 0.0);
+    }
+
+    #[test]
+    fn test_k1_zero_ignores_term_frequency() {
+        let docs = [
+            ("repeats", "rust rust rust rust rust rust rust rust"),
+            ("both", "rust cargo"),
+            ("c", "cargo crate"),
+            ("d", "cargo build"),
+            ("e", "cargo test"),
+        ];
+        let build = |mut index: BM25Index| {
+            for (id, content) in docs {
+                index.add_document(id, content).unwrap();
+            }
+            index
+        };
+
+        // By default repeating "rust" outweighs also matching common "cargo"
+        let default = build(BM25Index::new()).search("rust cargo", 1).unwrap();
+        assert_eq!(default[0].0, "repeats");
+
+        // With k1 = 0 repeats count once, so matching both terms wins
+        let flat = build(BM25Index::new().with_params(0.0, 0.75))
+            .search("rust cargo", 1)
+            .unwrap();
+        assert_eq!(flat[0].0, "both");
+    }
+
+    #[test]
+    fn test_phrase_requires_adjacent_terms() {
+        let mut index = BM25Index::new();
+        index
+            .add_document("adjacent", "choose the model size that fits in memory")
+            .unwrap();
+        index
+            .add_document("apart", "the size of each model file varies")
+            .unwrap();
+        index
+            .add_document("reversed", "size model mismatch")
+            .unwrap();
+
+        let ids: Vec<_> = index
+            .search("\"model size\"", 10)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec!["adjacent"]);
+        assert_eq!(index.search("model size", 10).unwrap().len(), 3);
+    }
+
+    #[test]
+    fn test_required_and_excluded_terms() {
+        let mut index = BM25Index::new();
+        index
+            .add_document("draft", "kernel scheduler kernel scheduler draft")
+            .unwrap();
+        index
+            .add_document("final", "the kernel scheduler picks tasks")
+            .unwrap();
+        index.add_document("other", "scheduler only").unwrap();
+
+        assert_eq!(index.search("kernel scheduler", 1).unwrap()[0].0, "draft");
+
+        let ids: Vec<_> = index
+            .search("kernel scheduler -draft", 10)
+            .unwrap()
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect();
+        assert_eq!(ids, vec!["final", "other"]);
+
+        let required = index.search("+kernel scheduler", 10).unwrap();
+        assert!(required.iter().all(|(id, _)| id != "other"));
+        assert_eq!(required.len(), 2);
+    }
+
+    #[test]
+    fn test_unversioned_index_gets_positions_on_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("bm25.json");
+        let mut index = BM25Index::with_tokenizer(Tokenizer::legacy());
+        index.add_document("doc", "model size matters").unwrap();
+
+        // Write the layout used before positions were recorded
+        let mut json = serde_json::to_value(&index).unwrap();
+        let fields = json.as_object_mut().unwrap();
+        fields.remove("schema_version");
+        fields.remove("term_positions");
+        fields.insert(
+            "term_freqs".to_string(),
+            serde_json::json!({"model": {"doc": 1}, "size": {"doc": 1}, "matters": {"doc": 1}}),
+        );
+        std::fs::write(&path, json.to_string()).unwrap();
+
+        let loaded = BM25Index::load(&path).unwrap();
+        assert_eq!(loaded.version, INDEX_VERSION);
+        assert_eq!(loaded.search("\"model size\"", 5).unwrap()[0].0, "doc");
+        assert_eq!(
+            index.search("size", 5).unwrap(),
+            loaded.search("size", 5).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_v1_index_still_loads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("bm25.json");
+        std::fs::write(&path, include_str!("../tests/fixtures/v1/bm25_index.json")).unwrap();
+
+        let loaded = BM25Index::load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(
+            loaded.search("\"kernel scheduler\"", 5).unwrap()[0].0,
+            "/mnt/root/notes.txt"
+        );
+        let saved = serde_json::to_value(&loaded).unwrap();
+        assert_eq!(saved["schema_version"], INDEX_VERSION);
+    }
+
+    #[test]
+    fn test_newer_index_is_refused() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("bm25.json");
+        std::fs::write(&path, r#"{"schema_version": 7, "shards": []}"#).unwrap();
+
+        assert!(matches!(
+            BM25Index::load(&path),
+            Err(LuCastraError::UnsupportedVersion(e)) if e.found == 7
+        ));
+    }
+
+    #[test]
+    fn test_remove_document() {
+        let mut index = BM25Index::new();
+        index.add_document("a", "alpha beta").unwrap();
+        index.add_document("b", "beta gamma").unwrap();
+
+        assert!(index.remove_document("a"));
+        assert!(!index.remove_document("a"));
+        assert!(index.search("alpha", 5).unwrap().is_empty());
+        assert_eq!(index.search("beta", 5).unwrap()[0].0, "b");
+    }
+
+    #[test]
+    fn test_save_and_load_identical_scores() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("bm25.json");
+
+        let words = ["kernel", "search", "llamafile", "vector", "device", "mount"];
+        let mut index = BM25Index::new();
+        for i in 0..50 {
+            let content = format!(
+                "document {} about {} and {} with {}",
+                i,
+                words[i % words.len()],
+                words[(i * 7) % words.len()],
+                words[(i / 3) % words.len()]
+            );
+            index
+                .add_document(&format!("/docs/{}.txt", i), &content)
+                .unwrap();
+        }
+        index.save(&path).unwrap();
+
+        let loaded = BM25Index::load(&path).unwrap();
+        assert_eq!(loaded.len(), 50);
+        for query in ["kernel search", "vector", "device mount llamafile"] {
+            let mut expected = index.search(query, 50).unwrap();
+            let mut actual = loaded.search(query, 50).unwrap();
+            expected.sort_by(|a, b| a.0.cmp(&b.0));
+            actual.sort_by(|a, b| a.0.cmp(&b.0));
+            assert_eq!(expected, actual);
+        }
+    }
+}
@@ -0,0 +1,507 @@
+//! Directory crawler that feeds real files into the search index.
+
+use crate::extract::{ContentExtractor, Extractors, SourceFormat};
+use crate::profile::ProfileMatcher;
+use crate::{SearchService, INDEX_BATCH_SIZE};
+use lucastra_core::{LuCastraError, Result};
+use lucastra_fs::FilesystemManager;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tracing::{debug, warn};
+
+/// Default extensions picked up by the crawler.
+pub const DEFAULT_EXTENSIONS: &[&str] = &["txt", "md", "rs", "toml", "pdf", "docx", "html", "htm"];
+
+pub use lucastra_config::profile::DEFAULT_MAX_FILE_SIZE;
+
+/// How deep [`Indexer::index_mounted`] descends below its starting path.
+pub const DEFAULT_MAX_DEPTH: usize = 32;
+
+/// Outcome of an indexing run.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct IndexSummary {
+    pub files_indexed: usize,
+    pub files_skipped: usize,
+    pub bytes_indexed: u64,
+    /// Files skipped because their text couldn't be extracted, e.g.
+    /// damaged PDFs. Included in `files_skipped`.
+    #[serde(default)]
+    pub extraction_failures: usize,
+}
+
+/// Recursively indexes files under a directory into a [`SearchService`].
+///
+/// Files below the root of an [`IndexProfile`](crate::IndexProfile) follow
+/// its patterns and size limit; when roots nest, the innermost one applies.
+/// Text is taken from PDF and DOCX files, and other formats with extractors
+/// added by [`Indexer::with_extractor`].
+#[derive(Debug, Clone)]
+pub struct Indexer {
+    extensions: Vec<String>,
+    max_file_size: u64,
+    allowed_roots: Vec<PathBuf>,
+    profiles: Vec<ProfileMatcher>,
+    extractors: Extractors,
+}
+
+impl Indexer {
+    pub fn new() -> Self {
+        Self {
+            extensions: DEFAULT_EXTENSIONS.iter().map(|e| e.to_string()).collect(),
+            max_file_size: DEFAULT_MAX_FILE_SIZE,
+            allowed_roots: Vec::new(),
+            profiles: Vec::new(),
+            extractors: Extractors::default(),
+        }
+    }
+
+    /// Only index files with these extensions (case-insensitive, no leading dot).
+    pub fn with_extensions(mut self, extensions: Vec<String>) -> Self {
+        self.extensions = extensions
+            .into_iter()
+            .map(|e| e.trim_start_matches('.').to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Skip files larger than `bytes`.
+    pub fn with_max_file_size(mut self, bytes: u64) -> Self {
+        self.max_file_size = bytes;
+        self
+    }
+
+    /// Refuse to crawl outside these directories. Empty means unrestricted.
+    pub fn with_allowed_roots(mut self, roots: Vec<PathBuf>) -> Self {
+        self.allowed_roots = roots;
+        self
+    }
+
+    /// Index files below profile roots as the profiles say.
+    pub fn with_profiles(mut self, profiles: Vec<ProfileMatcher>) -> Self {
+        self.profiles = profiles;
+        self
+    }
+
+    /// Read files `extractor` handles with it, ahead of the built-in
+    /// extractors.
+    pub fn with_extractor(mut self, extractor: Arc<dyn ContentExtractor>) -> Self {
+        self.extractors = self.extractors.with(extractor);
+        self
+    }
+
+    /// Index a file or every matching file below a directory.
+    pub fn index_path(&self, path: &Path, service: &SearchService) -> Result<IndexSummary> {
+        let path = fs::canonicalize(path)
+            .map_err(|e| LuCastraError::FilesystemError(format!("{}: {}", path.display(), e)))?;
+        self.check_allowed(&path)?;
+
+        let mut batch = Batch::new(service);
+        if self.excludes(&path, path.is_dir()) {
+            debug!("Not indexing {}: excluded by its profile", path.display());
+        } else if path.is_dir() {
+            self.walk(&path, &mut batch);
+        } else {
+            self.index_file(&path, &mut batch);
+        }
+        Ok(batch.finish())
+    }
+
+    /// Index files reachable through a mounted virtual filesystem.
+    pub fn index_mounted(
+        &self,
+        filesystem: &FilesystemManager,
+        path: &str,
+        service: &SearchService,
+    ) -> Result<IndexSummary> {
+        let mut batch = Batch::new(service);
+
+        for entry in filesystem.list_recursive(path, DEFAULT_MAX_DEPTH)? {
+            let entry_path = Path::new(&entry.path);
+            let hidden = entry_path
+                .strip_prefix(path)
+                .unwrap_or(entry_path)
+                .components()
+                .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+            if entry.is_dir || hidden || !self.matches_extension(entry_path) {
+                continue;
+            }
+            if entry.size > self.max_file_size {
+                debug!(
+                    "Skipping {} ({} bytes exceeds limit)",
+                    entry.path, entry.size
+                );
+                batch.summary.files_skipped += 1;
+                continue;
+            }
+
+            let bytes = match filesystem.read_file(&entry.path) {
+                Ok(bytes) => bytes,
+                Err(e) => {
+                    warn!("Skipping {}: {}", entry.path, e);
+                    batch.summary.files_skipped += 1;
+                    continue;
+                }
+            };
+            let Some(content) = self.extract(entry_path, &bytes, &mut batch.summary) else {
+                continue;
+            };
+            let size = content.len() as u64;
+            batch.push(entry.path, content, size);
+        }
+
+        Ok(batch.finish())
+    }
+
+    /// Whether the profile covering `path` keeps it out of the index.
+    pub fn excludes(&self, path: &Path, is_dir: bool) -> bool {
+        self.profile_for(path)
+            .is_some_and(|profile| profile.excludes(path, is_dir))
+    }
+
+    /// Remove documents their profile now excludes, e.g. after an exclude
+    /// pattern was added. Returns the removed paths.
+    pub fn remove_excluded(&self, service: &SearchService) -> Vec<String> {
+        let excluded: Vec<String> = service
+            .paths()
+            .into_iter()
+            .filter(|path| self.excludes(Path::new(path), false))
+            .collect();
+        for path in &excluded {
+            service.remove_document(path);
+        }
+        excluded
+    }
+
+    fn profile_for(&self, path: &Path) -> Option<&ProfileMatcher> {
+        self.profiles
+            .iter()
+            .filter(|profile| profile.contains(path))
+            .max_by_key(|profile| profile.root().components().count())
+    }
+
+    fn matches_extension(&self, path: &Path) -> bool {
+        path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| self.extensions.contains(&e.to_lowercase()))
+    }
+
+    fn check_allowed(&self, path: &Path) -> Result<()> {
+        if self.allowed_roots.is_empty() {
+            return Ok(());
+        }
+
+        let allowed = self.allowed_roots.iter().any(|root| {
+            fs::canonicalize(root)
+                .map(|root| path.starts_with(root))
+                .unwrap_or(false)
+        });
+        if allowed {
+            Ok(())
+        } else {
+            Err(LuCastraError::PermissionDenied(format!(
+                "{} is outside the allowed directories",
+                path.display()
+            )))
+        }
+    }
+
+    fn walk(&self, dir: &Path, batch: &mut Batch) {
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Cannot read directory {}: {}", dir.display(), e);
+                return;
+            }
+        };
+
+        for entry in entries.flatten() {
+            let path = entry.path();
+            let hidden = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with('.'));
+            if hidden {
+                continue;
+            }
+
+            // Don't follow symlinks so link cycles can't recurse forever
+            let Ok(file_type) = entry.file_type() else {
+                continue;
+            };
+            if self.excludes(&path, file_type.is_dir()) {
+                continue;
+            }
+            if file_type.is_dir() {
+                self.walk(&path, batch);
+            } else if file_type.is_file() {
+                self.index_file(&path, batch);
+            }
+        }
+    }
+
+    fn index_file(&self, path: &Path, batch: &mut Batch) {
+        if !self.matches_extension(path) {
+            return;
+        }
+
+        let size = match fs::metadata(path) {
+            Ok(metadata) => metadata.len(),
+            Err(e) => {
+                warn!("Skipping {}: {}", path.display(), e);
+                batch.summary.files_skipped += 1;
+                return;
+            }
+        };
+        let max_file_size = self
+            .profile_for(path)
+            .map_or(self.max_file_size, |profile| {
+                profile.profile().max_file_size
+            });
+        if size > max_file_size {
+            debug!("Skipping {} ({} bytes exceeds limit)", path.display(), size);
+            batch.summary.files_skipped += 1;
+            return;
+        }
+
+        let bytes = match fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                warn!("Skipping {}: {}", path.display(), e);
+                batch.summary.files_skipped += 1;
+                return;
+            }
+        };
+        let Some(content) = self.extract(path, &bytes, &mut batch.summary) else {
+            return;
+        };
+
+        batch.push(path.to_string_lossy().into_owned(), content, size);
+    }
+
+    /// Text of the file at `path`, or `None` (counted as skipped) when it
+    /// has none.
+    fn extract(&self, path: &Path, bytes: &[u8], summary: &mut IndexSummary) -> Option<String> {
+        let extractor = self.extractors.for_file(path, bytes);
+        match extractor.extract(bytes) {
+            Ok(content) => Some(content),
+            Err(_) if extractor.format() == SourceFormat::Text => {
+                debug!("Skipping binary file {}", path.display());
+                summary.files_skipped += 1;
+                None
+            }
+            Err(e) => {
+                warn!("Skipping {}: {}", path.display(), e);
+                summary.files_skipped += 1;
+                summary.extraction_failures += 1;
+                None
+            }
+        }
+    }
+}
+
+impl Default for Indexer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Files read during one run, written to the index [`INDEX_BATCH_SIZE`] at
+/// a time so searches aren't locked out for the whole run.
+struct Batch<'a> {
+    service: &'a SearchService,
+    /// Path, content and size of each file not written yet
+    pending: Vec<(String, String, u64)>,
+    summary: IndexSummary,
+}
+
+impl<'a> Batch<'a> {
+    fn new(service: &'a SearchService) -> Self {
+        Self {
+            service,
+            pending: Vec::new(),
+            summary: IndexSummary::default(),
+        }
+    }
+
+    fn push(&mut self, path: String, content: String, size: u64) {
+        self.pending.push((path, content, size));
+        if self.pending.len() >= INDEX_BATCH_SIZE {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let (docs, sizes): (Vec<_>, Vec<_>) = self
+            .pending
+            .drain(..)
+            .map(|(path, content, size)| ((path, content), size))
+            .unzip();
+        let count = docs.len();
+        match self.service.index_documents(docs) {
+            Ok(()) => {
+                self.summary.files_indexed += count;
+                self.summary.bytes_indexed += sizes.iter().sum::<u64>();
+            }
+            Err(e) => {
+                warn!("Failed to index {} files: {}", count, e);
+                self.summary.files_skipped += count;
+            }
+        }
+    }
+
+    /// Write what's left and say how the run went.
+    fn finish(mut self) -> IndexSummary {
+        self.flush();
+        self.summary
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index_directory_recursively() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("nested/deeper")).unwrap();
+        fs::write(root.join("notes.md"), "Meeting notes about the kernel").unwrap();
+        fs::write(root.join("nested/deeper/lib.rs"), "fn main() {}").unwrap();
+        fs::write(root.join("image.png"), [0u8, 1, 2]).unwrap();
+        fs::write(root.join("binary.txt"), [0xffu8, 0xfe, 0x00]).unwrap();
+        fs::write(root.join(".hidden.md"), "secret").unwrap();
+
+        let service = SearchService::new(None);
+        let summary = Indexer::new().index_path(root, &service).unwrap();
+
+        assert_eq!(summary.files_indexed, 2);
+        assert_eq!(summary.files_skipped, 1); // binary.txt
+        assert_eq!(service.doc_count(), 2);
+        assert!(!service.search("kernel", 5).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_large_directories_are_written_in_batches() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        let count = INDEX_BATCH_SIZE * 2 + 5;
+        for i in 0..count {
+            fs::write(root.join(format!("{}.txt", i)), format!("note {}", i)).unwrap();
+        }
+
+        let service = SearchService::new(None);
+        let summary = Indexer::new().index_path(root, &service).unwrap();
+        assert_eq!(summary.files_indexed, count);
+        assert_eq!(service.doc_count(), count);
+        assert_eq!(service.search("note", count).unwrap().len(), count);
+    }
+
+    #[test]
+    fn test_max_file_size_and_extensions() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("small.log"), "tiny log").unwrap();
+        fs::write(root.join("large.log"), "x".repeat(100)).unwrap();
+
+        let service = SearchService::new(None);
+        let summary = Indexer::new()
+            .with_extensions(vec![".log".to_string()])
+            .with_max_file_size(50)
+            .index_path(root, &service)
+            .unwrap();
+
+        assert_eq!(summary.files_indexed, 1);
+        assert_eq!(summary.files_skipped, 1);
+        assert_eq!(summary.bytes_indexed, 8);
+    }
+
+    #[test]
+    fn test_index_mounted_filesystem() {
+        use lucastra_hal::{filesystem::MockFileSystem, FileSystemDriver};
+
+        let mut mock = MockFileSystem::new();
+        mock.write_file("/mnt/root/guide.md", b"kernel guide")
+            .unwrap();
+        mock.write_file("/mnt/root/src/lib.rs", b"fn kernel() {}")
+            .unwrap();
+        mock.write_file("/mnt/root/.git/config.toml", b"hidden")
+            .unwrap();
+        mock.write_file("/mnt/root/logo.png", b"png").unwrap();
+        let mut filesystem = FilesystemManager::new();
+        filesystem.mount("/mnt/root", mock).unwrap();
+
+        let service = SearchService::new(None);
+        let summary = Indexer::new()
+            .index_mounted(&filesystem, "/mnt/root", &service)
+            .unwrap();
+
+        assert_eq!(summary.files_indexed, 2);
+        assert_eq!(service.search("kernel", 5).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn test_profile_excludes_are_skipped_and_purged() {
+        use crate::IndexProfile;
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("node_modules/pkg")).unwrap();
+        fs::create_dir_all(root.join("build")).unwrap();
+        fs::write(root.join("notes.md"), "kernel notes").unwrap();
+        fs::write(root.join("node_modules/pkg/README.md"), "kernel package").unwrap();
+        fs::write(root.join("build/out.txt"), "kernel build output").unwrap();
+
+        let profile = IndexProfile::new("docs", root).with_exclude("node_modules/");
+        let indexer =
+            |profile: &IndexProfile| Indexer::new().with_profiles(vec![profile.matcher().unwrap()]);
+        let service = SearchService::new(None);
+        let summary = indexer(&profile).index_path(root, &service).unwrap();
+        assert_eq!(summary.files_indexed, 2);
+        let paths = service.paths();
+        assert!(paths.iter().all(|path| !path.contains("node_modules")));
+
+        // Directly indexing something below an excluded directory does nothing
+        let summary = indexer(&profile)
+            .index_path(&root.join("node_modules/pkg/README.md"), &service)
+            .unwrap();
+        assert_eq!(summary.files_indexed, 0);
+
+        let profile = profile.with_exclude("build");
+        let removed = indexer(&profile).remove_excluded(&service);
+        assert_eq!(removed.len(), 1);
+        assert!(removed[0].ends_with("out.txt"));
+        assert_eq!(service.doc_count(), 1);
+        assert_eq!(service.search("kernel", 5).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_extraction_failures_are_counted() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = temp_dir.path();
+        fs::write(root.join("notes.md"), "kernel notes").unwrap();
+        fs::write(root.join("broken.pdf"), "%PDF-1.4 not really").unwrap();
+        fs::write(root.join("broken.docx"), "PK\x03\x04").unwrap();
+
+        let service = SearchService::new(None);
+        let summary = Indexer::new().index_path(root, &service).unwrap();
+
+        assert_eq!(summary.files_indexed, 1);
+        assert_eq!(summary.files_skipped, 2);
+        assert_eq!(summary.extraction_failures, 2);
+    }
+
+    #[test]
+    fn test_rejects_path_outside_allowed_roots() {
+        let allowed = tempfile::tempdir().unwrap();
+        let other = tempfile::tempdir().unwrap();
+
+        let service = SearchService::new(None);
+        let result = Indexer::new()
+            .with_allowed_roots(vec![allowed.path().to_path_buf()])
+            .index_path(other.path(), &service);
+
+        assert!(result.is_err());
+    }
+}
@@ -1,74 +1,612 @@
 //! Full-text and vector search for filesystem indexing.
 
+pub mod chunker;
+pub mod extract;
+mod hnsw;
 pub mod index;
+pub mod indexer;
+pub mod query;
+pub mod retrieval;
+pub mod snippet;
 pub mod tokenizer;
 pub mod vector;
+pub mod watcher;
 
-pub use index::BM25Index;
-pub use tokenizer::Tokenizer;
-pub use vector::{VectorError, VectorIndex, VectorSearchResult};
+pub use chunker::{Chunk, ChunkStrategy, Chunker};
+pub use extract::{chunk_metadata, ContentExtractor, Extractors, SourceFormat, PAGE_BREAK};
+pub use index::{BM25Index, Bm25Params, INDEX_VERSION};
+pub use indexer::{IndexSummary, Indexer};
+pub use lucastra_config::profile::{self, IndexProfile, ProfileMatcher};
+pub use query::{Clause, Query};
+pub use retrieval::RetrievalOptions;
+pub use snippet::{Snippet, SnippetOptions};
+pub use tokenizer::{Tokenizer, DEFAULT_STOPWORDS};
+pub use vector::{
+    EmbeddingModelInfo, HnswParams, MetadataFilter, VectorError, VectorIndex, VectorSearchResult,
+};
+pub use watcher::{FileWatcher, WatchEvent};
 
-use lucastra_core::{command::SearchResult, Result};
+use lucastra_core::{command::SearchResult, LuCastraError, Result, SearchPage};
 use std::collections::HashMap;
-use tracing::info;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use tracing::{info, warn};
+
+const INDEX_FILE: &str = "bm25.json";
+const DOCUMENTS_FILE: &str = "documents.json";
+
+pub use lucastra_config::DEFAULT_MAX_RESULTS;
+
+/// Documents written to the index per write lock, so searches get a turn
+/// while a large directory is being indexed.
+pub const INDEX_BATCH_SIZE: usize = 64;
 
 /// Search service providing BM25-ranked document retrieval.
+///
+/// Every method takes `&self`: the index and its documents sit behind one
+/// [`RwLock`], so any number of searches run at once and a writer swaps in
+/// a whole batch of changes at a time. Searches never see a document in
+/// the index without its content or an average length from part of a
+/// batch.
 pub struct SearchService {
+    corpus: RwLock<Corpus>,
+    index_path: Option<PathBuf>,
+    snippet_options: SnippetOptions,
+    /// Default number of results for searches.
+    max_results: AtomicUsize,
+}
+
+/// Everything a write has to change together.
+#[derive(Default)]
+struct Corpus {
     index: BM25Index,
     documents: HashMap<String, String>, // path -> content
 }
 
 impl SearchService {
-    pub fn new() -> Self {
+    /// Create a search service, optionally persisted under `index_path`
+    /// (a directory). An existing index there is loaded; a missing or
+    /// corrupted one starts empty.
+    pub fn new(index_path: Option<PathBuf>) -> Self {
+        let mut corpus = Corpus::default();
+        if let Some(dir) = &index_path {
+            if dir.join(INDEX_FILE).exists() {
+                match Self::load_from(dir) {
+                    Ok((index, documents)) => {
+                        info!(
+                            "Loaded {} indexed documents from {}",
+                            documents.len(),
+                            dir.display()
+                        );
+                        corpus = Corpus { index, documents };
+                    }
+                    Err(e) => warn!("Ignoring unreadable search index {}: {}", dir.display(), e),
+                }
+            }
+        }
+
         Self {
-            index: BM25Index::new(),
-            documents: HashMap::new(),
+            corpus: RwLock::new(corpus),
+            index_path,
+            snippet_options: SnippetOptions::default(),
+            max_results: AtomicUsize::new(DEFAULT_MAX_RESULTS),
         }
     }
 
+    /// The index for searching. A writer that panicked can't have left it
+    /// half-updated, since every write is applied under the lock at once.
+    fn read(&self) -> RwLockReadGuard<'_, Corpus> {
+        self.corpus.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, Corpus> {
+        self.corpus.write().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Wrap matched terms in snippets with these markers instead of `**`.
+    pub fn with_highlight_markers(mut self, open: &str, close: &str) -> Self {
+        self.snippet_options.open_marker = open.to_string();
+        self.snippet_options.close_marker = close.to_string();
+        self
+    }
+
+    /// Rank with these BM25 parameters.
+    pub fn with_bm25_params(self, params: Bm25Params) -> Self {
+        self.set_bm25_params(params);
+        self
+    }
+
+    /// Change the BM25 parameters. Scoring happens at query time, so the
+    /// next search uses them without re-indexing.
+    pub fn set_bm25_params(&self, params: Bm25Params) {
+        self.write().index.set_params(params);
+    }
+
+    /// BM25 parameters searches rank with.
+    pub fn bm25_params(&self) -> Bm25Params {
+        self.read().index.params()
+    }
+
+    /// Return `max_results` results from searches that don't say how many.
+    pub fn with_max_results(self, max_results: usize) -> Self {
+        self.set_max_results(max_results);
+        self
+    }
+
+    pub fn set_max_results(&self, max_results: usize) {
+        self.max_results.store(max_results, Ordering::Relaxed);
+    }
+
+    /// Default number of results for a search.
+    pub fn max_results(&self) -> usize {
+        self.max_results.load(Ordering::Relaxed)
+    }
+
+    /// Split text with `tokenizer`, re-indexing stored documents if the
+    /// index was built differently.
+    pub fn with_tokenizer(self, tokenizer: Tokenizer) -> Self {
+        self.set_tokenizer(tokenizer);
+        self
+    }
+
+    /// Switch to `tokenizer`, re-indexing every document when it differs
+    /// from the current one. Returns whether the index was rebuilt.
+    pub fn set_tokenizer(&self, tokenizer: Tokenizer) -> bool {
+        let mut corpus = self.write();
+        if *corpus.index.tokenizer() == tokenizer {
+            return false;
+        }
+        info!(
+            "Re-indexing {} documents for new tokenizer settings",
+            corpus.documents.len()
+        );
+        let mut index = BM25Index::with_tokenizer(tokenizer);
+        index.set_params(corpus.index.params());
+        let tokenized: Vec<_> = corpus
+            .documents
+            .iter()
+            .map(|(path, content)| (path.clone(), index.tokenizer().tokenize(content)))
+            .collect();
+        index.add_tokenized(tokenized);
+        corpus.index = index;
+        true
+    }
+
+    /// How documents and queries are split into terms.
+    pub fn tokenizer(&self) -> Tokenizer {
+        self.read().index.tokenizer().clone()
+    }
+
+    /// Persist the index to its configured path (no-op when in-memory only).
+    pub fn save(&self) -> Result<()> {
+        let Some(dir) = &self.index_path else {
+            return Ok(());
+        };
+
+        let corpus = self.read();
+        corpus.index.save(&dir.join(INDEX_FILE))?;
+        let json = serde_json::to_string(&corpus.documents)
+            .map_err(|e| LuCastraError::ServiceError(e.to_string()))?;
+        std::fs::write(dir.join(DOCUMENTS_FILE), json)
+            .map_err(|e| LuCastraError::FilesystemError(e.to_string()))?;
+
+        info!("Saved search index to {}", dir.display());
+        Ok(())
+    }
+
+    /// Bytes the saved index takes on disk; `None` when in-memory only.
+    /// Files not saved yet count as empty.
+    pub fn index_size(&self) -> Result<Option<u64>> {
+        let Some(dir) = &self.index_path else {
+            return Ok(None);
+        };
+        let mut total = 0;
+        for file in [INDEX_FILE, DOCUMENTS_FILE] {
+            match std::fs::metadata(dir.join(file)) {
+                Ok(meta) => total += meta.len(),
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+                Err(e) => return Err(LuCastraError::FilesystemError(e.to_string())),
+            }
+        }
+        Ok(Some(total))
+    }
+
+    fn load_from(dir: &Path) -> Result<(BM25Index, HashMap<String, String>)> {
+        let index = BM25Index::load(&dir.join(INDEX_FILE))?;
+        let json = std::fs::read_to_string(dir.join(DOCUMENTS_FILE))
+            .map_err(|e| LuCastraError::FilesystemError(e.to_string()))?;
+        let documents =
+            serde_json::from_str(&json).map_err(|e| LuCastraError::ServiceError(e.to_string()))?;
+        Ok((index, documents))
+    }
+
     /// Index a document (file) by path.
-    pub fn index_document(&mut self, path: &str, content: &str) -> Result<()> {
-        info!("Indexing document: {}", path);
-        self.index.add_document(path, content)?;
-        self.documents.insert(path.to_string(), content.to_string());
+    pub fn index_document(&self, path: &str, content: &str) -> Result<()> {
+        self.index_documents(vec![(path.to_string(), content.to_string())])
+    }
+
+    /// Index documents as `(path, content)` pairs under one write lock.
+    /// They are split into terms beforehand, so searches only wait for the
+    /// postings to be updated. Callers adding many documents should send
+    /// them in batches of about [`INDEX_BATCH_SIZE`].
+    pub fn index_documents(&self, docs: Vec<(String, String)>) -> Result<()> {
+        if docs.is_empty() {
+            return Ok(());
+        }
+        let tokenizer = self.tokenizer();
+        let mut tokenized: Vec<_> = docs
+            .iter()
+            .map(|(path, content)| (path.clone(), tokenizer.tokenize(content)))
+            .collect();
+
+        let mut corpus = self.write();
+        // The tokenizer changed while these were being split
+        if *corpus.index.tokenizer() != tokenizer {
+            tokenized = docs
+                .iter()
+                .map(|(path, content)| (path.clone(), corpus.index.tokenizer().tokenize(content)))
+                .collect();
+        }
+        for (path, _) in &docs {
+            info!("Indexing document: {}", path);
+        }
+        corpus.index.add_tokenized(tokenized);
+        corpus.documents.extend(docs);
         Ok(())
     }
 
+    /// Remove a document, or every document below it when `path` was a
+    /// directory. Returns how many documents were removed.
+    pub fn remove_document(&self, path: &str) -> usize {
+        let prefix = format!(
+            "{}{}",
+            path.trim_end_matches('/'),
+            std::path::MAIN_SEPARATOR
+        );
+        let mut corpus = self.write();
+        let removed: Vec<String> = corpus
+            .documents
+            .keys()
+            .filter(|doc| doc.as_str() == path || doc.starts_with(&prefix))
+            .cloned()
+            .collect();
+
+        for doc in &removed {
+            info!("Removing document: {}", doc);
+            corpus.documents.remove(doc);
+        }
+        corpus
+            .index
+            .remove_documents(removed.iter().map(String::as_str));
+        removed.len()
+    }
+
     /// Search for documents by query string.
     pub fn search(&self, query: &str, top_k: usize) -> Result<Vec<SearchResult>> {
-        info!("Searching for: {}", query);
-        let results = self.index.search(query, top_k)?;
-        Ok(results
+        Ok(self.search_paged(query, 0, top_k)?.results)
+    }
+
+    /// Up to `limit` results starting `offset` results into the ranking,
+    /// with the total number of matches. An offset past the last match
+    /// gives an empty page.
+    pub fn search_paged(&self, query: &str, offset: usize, limit: usize) -> Result<SearchPage> {
+        self.page(&self.read(), query, offset, limit)
+    }
+
+    fn page(
+        &self,
+        corpus: &Corpus,
+        query: &str,
+        offset: usize,
+        limit: usize,
+    ) -> Result<SearchPage> {
+        info!(
+            "Searching for: {} (offset {}, limit {})",
+            query, offset, limit
+        );
+        let ranked = corpus.index.search_all(query)?;
+        let total_hits = ranked.len();
+        let results = ranked
             .into_iter()
+            .skip(offset)
+            .take(limit)
             .map(|(path, score)| {
-                let snippet = self
+                let snippet = corpus
                     .documents
                     .get(&path)
-                    .map(|c| c.chars().take(200).collect::<String>())
-                    .unwrap_or_else(|| "...".to_string());
+                    .map(|c| {
+                        snippet::build_snippet(
+                            c,
+                            query,
+                            corpus.index.tokenizer(),
+                            &self.snippet_options,
+                        )
+                    })
+                    .unwrap_or_else(|| Snippet {
+                        text: "...".to_string(),
+                        line_number: None,
+                        page: None,
+                    });
+                let format = match SourceFormat::of_path(Path::new(&path)) {
+                    SourceFormat::Text => None,
+                    format => Some(format.name().to_string()),
+                };
                 SearchResult {
                     path,
                     score,
-                    snippet,
+                    snippet: snippet.text,
+                    line_number: snippet.line_number,
+                    format,
+                    page: snippet.page,
                 }
             })
-            .collect())
+            .collect();
+        Ok(SearchPage {
+            results,
+            total_hits,
+            offset,
+        })
+    }
+
+    /// Like [`SearchService::search`], but scores are scaled to 0.0-1.0 by
+    /// the best score the query could get, so one threshold suits any corpus.
+    pub fn search_normalized(&self, query: &str, top_k: usize) -> Result<Vec<SearchResult>> {
+        // Hold the lock across both so the bound matches the scores
+        let corpus = self.read();
+        let max_score = corpus.index.max_score(query);
+        let mut results = self.page(&corpus, query, 0, top_k)?.results;
+        drop(corpus);
+        if max_score > 0.0 {
+            for result in &mut results {
+                result.score = (result.score / max_score).min(1.0);
+            }
+        }
+        Ok(results)
     }
 
     /// Clear all indexed documents.
-    pub fn clear(&mut self) {
-        self.index.clear();
-        self.documents.clear();
+    pub fn clear(&self) {
+        let mut corpus = self.write();
+        corpus.index.clear();
+        corpus.documents.clear();
+    }
+
+    /// Paths of the indexed documents.
+    pub fn paths(&self) -> Vec<String> {
+        self.read().documents.keys().cloned().collect()
+    }
+
+    /// A copy of the indexed documents as `(path, content)` pairs.
+    pub fn documents(&self) -> Vec<(String, String)> {
+        self.read()
+            .documents
+            .iter()
+            .map(|(path, content)| (path.clone(), content.clone()))
+            .collect()
     }
 
     /// Get document count.
     pub fn doc_count(&self) -> usize {
-        self.documents.len()
+        self.read().documents.len()
     }
 }
 
 impl Default for SearchService {
     fn default() -> Self {
-        Self::new()
+        Self::new(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_persisted_service_reloads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("search_index");
+
+        let service = SearchService::new(Some(path.clone()));
+        service
+            .index_document("/notes/rust.md", "Rust ownership and borrowing")
+            .unwrap();
+        service.save().unwrap();
+
+        let reloaded = SearchService::new(Some(path));
+        assert_eq!(reloaded.doc_count(), 1);
+        assert!(reloaded.index_size().unwrap().unwrap() > 0);
+        assert_eq!(SearchService::new(None).index_size().unwrap(), None);
+        let results = reloaded.search("ownership", 5).unwrap();
+        assert_eq!(results[0].path, "/notes/rust.md");
+        assert!(results[0].snippet.contains("borrowing"));
+        assert!(results[0].snippet.contains("**ownership**"));
+        assert_eq!(results[0].line_number, Some(1));
+    }
+
+    fn service(docs: &[(&str, &str)]) -> SearchService {
+        let service = SearchService::new(None);
+        for (path, content) in docs {
+            service.index_document(path, content).unwrap();
+        }
+        service
+    }
+
+    fn top_path(service: &SearchService, query: &str) -> Option<String> {
+        service
+            .search(query, 1)
+            .unwrap()
+            .into_iter()
+            .next()
+            .map(|r| r.path)
+    }
+
+    #[test]
+    fn test_plural_and_verb_forms_match() {
+        let service = service(&[
+            ("/notes/indexer.md", "The indexer indexed three directories"),
+            ("/notes/other.md", "Unrelated notes on cooking"),
+        ]);
+
+        assert_eq!(
+            top_path(&service, "indexing directory").as_deref(),
+            Some("/notes/indexer.md")
+        );
+        let results = service.search("directories", 1).unwrap();
+        assert!(results[0].snippet.contains("**directories**"));
+    }
+
+    #[test]
+    fn test_french_with_and_without_accents() {
+        let service = service(&[
+            (
+                "/fr/ecole.txt",
+                "Les élèves étudient à l'école chaque matin",
+            ),
+            (
+                "/fr/cuisine.txt",
+                "La recette demande du beurre et des œufs",
+            ),
+        ]);
+
+        assert_eq!(
+            top_path(&service, "élève école").as_deref(),
+            Some("/fr/ecole.txt")
+        );
+        assert_eq!(
+            top_path(&service, "eleves ecole").as_deref(),
+            Some("/fr/ecole.txt")
+        );
+    }
+
+    #[test]
+    fn test_short_chinese_query() {
+        let service = service(&[
+            ("/zh/search.txt", "这个系统使用搜索引擎查找文件"),
+            ("/zh/weather.txt", "今天的天气很好"),
+        ]);
+
+        assert_eq!(
+            top_path(&service, "搜索").as_deref(),
+            Some("/zh/search.txt")
+        );
+        assert_eq!(
+            top_path(&service, "天气").as_deref(),
+            Some("/zh/weather.txt")
+        );
+        let results = service.search("引擎", 1).unwrap();
+        assert!(results[0].snippet.contains("**引擎**"));
+    }
+
+    #[test]
+    fn test_changing_tokenizer_reindexes_documents() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("search_index");
+
+        let legacy = SearchService::new(Some(path.clone())).with_tokenizer(Tokenizer::legacy());
+        legacy
+            .index_document("/notes/a.md", "Indexing documents")
+            .unwrap();
+        legacy.save().unwrap();
+        assert!(legacy.search("index", 1).unwrap().is_empty());
+
+        // The saved index keeps its tokenizer until told otherwise
+        let reloaded = SearchService::new(Some(path));
+        assert_eq!(reloaded.tokenizer(), Tokenizer::legacy());
+        let upgraded = reloaded.with_tokenizer(Tokenizer::default());
+        assert_eq!(top_path(&upgraded, "index").as_deref(), Some("/notes/a.md"));
+    }
+
+    #[test]
+    fn test_retokenizing_keeps_bm25_params() {
+        let params = Bm25Params { k1: 0.9, b: 0.4 };
+        let service = SearchService::new(None)
+            .with_bm25_params(params)
+            .with_tokenizer(Tokenizer::legacy());
+        assert_eq!(service.bm25_params(), params);
+    }
+
+    #[test]
+    fn test_pages_are_disjoint_and_ranked() {
+        let service = SearchService::new(None);
+        for i in 0..25 {
+            // Fewer repeats of "kernel" rank lower
+            let content = format!("{} notes", "kernel ".repeat(25 - i));
+            service
+                .index_document(&format!("/docs/{:02}.txt", i), &content)
+                .unwrap();
+        }
+        service
+            .index_document("/docs/other.txt", "gardening")
+            .unwrap();
+
+        let first = service.search_paged("kernel", 0, 10).unwrap();
+        let second = service.search_paged("kernel", 10, 10).unwrap();
+        assert_eq!((first.total_hits, second.total_hits), (25, 25));
+        assert_eq!((first.results.len(), second.results.len()), (10, 10));
+        assert_eq!(second.offset, 10);
+
+        let first_paths: HashSet<_> = first.results.iter().map(|r| &r.path).collect();
+        assert!(second
+            .results
+            .iter()
+            .all(|r| !first_paths.contains(&r.path)));
+        let lowest_first = first.results.last().unwrap().score;
+        assert!(second.results.iter().all(|r| r.score < lowest_first));
+
+        let past_end = service.search_paged("kernel", 40, 10).unwrap();
+        assert!(past_end.results.is_empty());
+        assert_eq!(past_end.total_hits, 25);
+    }
+
+    #[test]
+    fn test_concurrent_indexing_and_searching() {
+        let service = std::sync::Arc::new(SearchService::new(None));
+        std::thread::scope(|scope| {
+            for writer in 0..2 {
+                let service = &service;
+                scope.spawn(move || {
+                    for round in 0..100 {
+                        let batch = (0..10)
+                            .map(|i| {
+                                let path = format!("/w{}/{}.txt", writer, i);
+                                let content =
+                                    format!("{} notes", "kernel ".repeat(1 + (i + round) % 7));
+                                (path, content)
+                            })
+                            .collect();
+                        service.index_documents(batch).unwrap();
+                        service.remove_document(&format!("/w{}", writer));
+                    }
+                });
+            }
+            for _ in 0..4 {
+                let service = &service;
+                scope.spawn(move || {
+                    for _ in 0..200 {
+                        for result in service.search("kernel notes", 20).unwrap() {
+                            assert!(result.score.is_finite() && result.score > 0.0);
+                        }
+                        for result in service.search_normalized("kernel", 20).unwrap() {
+                            assert!((0.0..=1.0).contains(&result.score));
+                        }
+                    }
+                });
+            }
+        });
+        assert_eq!(service.doc_count(), 0);
+    }
+
+    #[test]
+    fn test_custom_highlight_markers() {
+        let service = SearchService::new(None).with_highlight_markers("<em>", "</em>");
+        service
+            .index_document("/notes/a.md", "intro\nthe scheduler picks tasks")
+            .unwrap();
+
+        let results = service.search("scheduler", 1).unwrap();
+        assert!(results[0].snippet.contains("<em>scheduler</em>"));
+        assert_eq!(results[0].line_number, Some(2));
     }
 }
@@ -0,0 +1,126 @@
+//! Search query syntax.
+//!
+//! Plain words are alternatives: a document matching any of them is a hit.
+//! `"quoted words"` only match next to each other, `+word` or `+"..."` must
+//! match and `-word` or `-"..."` must not.
+
+use crate::tokenizer::Tokenizer;
+
+/// One word or phrase of a query, as index terms.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clause {
+    pub terms: Vec<String>,
+    /// Terms must appear next to each other, in order.
+    pub phrase: bool,
+}
+
+/// A parsed query.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Query {
+    /// At least one must match unless there are required clauses.
+    pub should: Vec<Clause>,
+    /// `+` clauses; all must match.
+    pub must: Vec<Clause>,
+    /// `-` clauses; none may match.
+    pub must_not: Vec<Clause>,
+}
+
+impl Query {
+    /// Parse `text`, turning each word or phrase into terms with `tokenizer`.
+    /// Words that yield no terms (stopwords, punctuation) are dropped.
+    pub fn parse(text: &str, tokenizer: &Tokenizer) -> Self {
+        let mut query = Query::default();
+        let mut chars = text.chars().peekable();
+        loop {
+            while chars.next_if(|c| c.is_whitespace()).is_some() {}
+            let Some(&first) = chars.peek() else {
+                break;
+            };
+            let target = match first {
+                '+' => {
+                    chars.next();
+                    &mut query.must
+                }
+                '-' => {
+                    chars.next();
+                    &mut query.must_not
+                }
+                _ => &mut query.should,
+            };
+
+            let quoted = chars.next_if_eq(&'"').is_some();
+            let mut raw = String::new();
+            while let Some(c) =
+                chars.next_if(|&c| if quoted { c != '"' } else { !c.is_whitespace() })
+            {
+                raw.push(c);
+            }
+            if quoted {
+                chars.next();
+            }
+
+            let terms = tokenizer.tokenize(&raw);
+            if !terms.is_empty() {
+                target.push(Clause {
+                    phrase: quoted && terms.len() > 1,
+                    terms,
+                });
+            }
+        }
+        query
+    }
+
+    /// Whether nothing could match: no required or optional clauses.
+    pub fn is_empty(&self) -> bool {
+        self.should.is_empty() && self.must.is_empty()
+    }
+
+    /// Terms that add to a document's score.
+    pub fn scored_terms(&self) -> impl Iterator<Item = &String> {
+        self.should
+            .iter()
+            .chain(&self.must)
+            .flat_map(|clause| &clause.terms)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clause(terms: &[&str], phrase: bool) -> Clause {
+        Clause {
+            terms: terms.iter().map(|t| t.to_string()).collect(),
+            phrase,
+        }
+    }
+
+    #[test]
+    fn test_parse_phrases_and_operators() {
+        let tokenizer = Tokenizer::default().with_stemming(false);
+        let query = Query::parse(r#""model size" +llama -draft -"old notes" the"#, &tokenizer);
+
+        assert_eq!(query.should, vec![clause(&["model", "size"], true)]);
+        assert_eq!(query.must, vec![clause(&["llama"], false)]);
+        assert_eq!(
+            query.must_not,
+            vec![clause(&["draft"], false), clause(&["old", "notes"], true)]
+        );
+    }
+
+    #[test]
+    fn test_parse_tolerates_stray_syntax() {
+        let tokenizer = Tokenizer::default().with_stemming(false);
+        let query = Query::parse(r#"x-ray - + "unclosed phrase"#, &tokenizer);
+
+        assert_eq!(
+            query.should,
+            vec![
+                clause(&["ray"], false),
+                clause(&["unclosed", "phrase"], true)
+            ]
+        );
+        assert!(query.must.is_empty() && query.must_not.is_empty());
+        assert!(Query::parse("-only", &tokenizer).is_empty());
+    }
+}
@@ -0,0 +1,203 @@
+//! Choosing which search results to give an LLM as context.
+
+use crate::SearchService;
+use lucastra_core::{command::SearchResult, Result};
+use std::collections::HashSet;
+use tracing::debug;
+
+/// Limits on the context retrieved for one query.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RetrievalOptions {
+    /// Most results to return.
+    pub top_k: usize,
+    /// Lowest normalized score (0.0-1.0) a result may have.
+    pub min_score: f32,
+    /// Word overlap (Jaccard, 0.0-1.0) at which a snippet counts as a
+    /// duplicate of a better-scoring one.
+    pub dedup_similarity: f32,
+    /// Estimated tokens all snippets together may take.
+    pub token_budget: usize,
+}
+
+impl Default for RetrievalOptions {
+    fn default() -> Self {
+        Self {
+            top_k: 3,
+            min_score: 0.2,
+            dedup_similarity: 0.8,
+            token_budget: 768,
+        }
+    }
+}
+
+impl SearchService {
+    /// Results worth giving an LLM for `query`, best first: normalized
+    /// scores of at least `min_score`, without near-duplicate snippets, and
+    /// dropping the lowest-scoring ones until the snippets fit the budget.
+    /// Empty when nothing relevant is indexed.
+    pub fn retrieve(&self, query: &str, options: &RetrievalOptions) -> Result<Vec<SearchResult>> {
+        // Fetch extra so dropped duplicates can be replaced
+        let candidates = self.search_normalized(query, options.top_k.saturating_mul(3))?;
+
+        let mut kept: Vec<SearchResult> = Vec::new();
+        let mut kept_words: Vec<HashSet<String>> = Vec::new();
+        for result in candidates {
+            if kept.len() == options.top_k || result.score < options.min_score {
+                break;
+            }
+            let words: HashSet<String> = self
+                .tokenizer()
+                .tokenize(&result.snippet)
+                .into_iter()
+                .collect();
+            if kept_words
+                .iter()
+                .any(|seen| similarity(seen, &words) >= options.dedup_similarity)
+            {
+                debug!("Skipping near-duplicate context from {}", result.path);
+                continue;
+            }
+            kept.push(result);
+            kept_words.push(words);
+        }
+
+        while kept.len() > 1 && context_tokens(&kept) > options.token_budget {
+            if let Some(dropped) = kept.pop() {
+                debug!("Context over budget; dropping {}", dropped.path);
+            }
+        }
+        if let Some(only) = kept.first_mut() {
+            let max_chars = options.token_budget.saturating_mul(4);
+            if only.snippet.chars().count() > max_chars {
+                only.snippet = only.snippet.chars().take(max_chars).collect();
+            }
+        }
+        Ok(kept)
+    }
+}
+
+/// Rough token count of all snippets (about four characters per token).
+fn context_tokens(results: &[SearchResult]) -> usize {
+    results
+        .iter()
+        .map(|r| r.snippet.chars().count().div_ceil(4))
+        .sum()
+}
+
+/// Jaccard similarity of two word sets.
+fn similarity(a: &HashSet<String>, b: &HashSet<String>) -> f32 {
+    let union = a.union(b).count();
+    if union == 0 {
+        return 1.0;
+    }
+    a.intersection(b).count() as f32 / union as f32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn service(docs: &[(&str, &str)]) -> SearchService {
+        let service = SearchService::new(None);
+        for (path, content) in docs {
+            service.index_document(path, content).unwrap();
+        }
+        service
+    }
+
+    #[test]
+    fn test_unrelated_query_retrieves_nothing() {
+        let service = service(&[
+            (
+                "/docs/os.txt",
+                "LucAstra is an augmented OS with embedded LLM",
+            ),
+            ("/docs/rust.txt", "LucAstra runs on Rust"),
+        ]);
+        let options = RetrievalOptions::default();
+
+        assert!(service
+            .retrieve("quantum chromodynamics lattice", &options)
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            service.retrieve("LucAstra Rust", &options).unwrap()[0].path,
+            "/docs/rust.txt"
+        );
+    }
+
+    #[test]
+    fn test_weak_matches_fall_below_threshold() {
+        let service = service(&[
+            ("/docs/kernel.txt", "kernel scheduler kernel scheduler"),
+            (
+                "/docs/misc.txt",
+                "notes about gardening, cooking, travel and one kernel mention",
+            ),
+            ("/docs/other.txt", "scheduler"),
+        ]);
+        let results = service
+            .retrieve(
+                "kernel scheduler",
+                &RetrievalOptions {
+                    min_score: 0.5,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        assert!(results.iter().all(|r| r.score >= 0.5));
+        assert!(results.iter().all(|r| r.path != "/docs/misc.txt"));
+        assert_eq!(results[0].path, "/docs/kernel.txt");
+    }
+
+    #[test]
+    fn test_near_duplicates_are_dropped() {
+        let text = "The scheduler picks the next runnable task on each tick";
+        let service = service(&[
+            ("/docs/a.txt", text),
+            ("/backup/a.txt", text),
+            ("/docs/b.txt", "The scheduler balances load across cores"),
+        ]);
+        let results = service
+            .retrieve(
+                "scheduler",
+                &RetrievalOptions {
+                    min_score: 0.0,
+                    ..Default::default()
+                },
+            )
+            .unwrap();
+
+        let paths: Vec<_> = results.iter().map(|r| r.path.as_str()).collect();
+        assert_eq!(paths.len(), 2);
+        assert!(paths.contains(&"/docs/b.txt"));
+    }
+
+    #[test]
+    fn test_token_budget_drops_lowest_scores_first() {
+        let service = service(&[
+            ("/docs/best.txt", "scheduler scheduler scheduler tasks"),
+            (
+                "/docs/good.txt",
+                "scheduler and a much longer description of timers",
+            ),
+        ]);
+        let options = RetrievalOptions {
+            min_score: 0.0,
+            token_budget: 10,
+            ..Default::default()
+        };
+
+        let results = service.retrieve("scheduler", &options).unwrap();
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].path, "/docs/best.txt");
+
+        let tiny = RetrievalOptions {
+            token_budget: 2,
+            ..options
+        };
+        let results = service.retrieve("scheduler", &tiny).unwrap();
+        assert_eq!(results[0].snippet.chars().count(), 8);
+    }
+}
@@ -0,0 +1,228 @@
+//! Query-aware snippet extraction with term highlighting.
+
+use crate::extract::PAGE_BREAK;
+use crate::tokenizer::Tokenizer;
+use std::collections::HashMap;
+
+/// Default snippet length in characters.
+pub const DEFAULT_SNIPPET_CHARS: usize = 200;
+
+/// How snippets are sized and how matched terms are marked.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetOptions {
+    pub max_chars: usize,
+    pub open_marker: String,
+    pub close_marker: String,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> Self {
+        Self {
+            max_chars: DEFAULT_SNIPPET_CHARS,
+            open_marker: "**".to_string(),
+            close_marker: "**".to_string(),
+        }
+    }
+}
+
+/// A highlighted excerpt and the 1-based line its first match starts on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Snippet {
+    pub text: String,
+    pub line_number: Option<usize>,
+    /// 1-based page of the first match when the content has
+    /// [`PAGE_BREAK`]s.
+    pub page: Option<usize>,
+}
+
+/// A query term occurrence, in char offsets.
+struct Match {
+    start: usize,
+    end: usize,
+    term: usize,
+}
+
+/// Build a snippet from the window of `content` with the most distinct query
+/// terms (ties broken by total matches), centred on those matches.
+///
+/// Falls back to the start of the document when no term matches.
+pub fn build_snippet(
+    content: &str,
+    query: &str,
+    tokenizer: &Tokenizer,
+    options: &SnippetOptions,
+) -> Snippet {
+    let chars: Vec<char> = content.chars().collect();
+    let matches = find_matches(content, &tokenizer.tokenize(query), tokenizer);
+
+    let Some((first, last)) = best_window(&matches, options.max_chars) else {
+        let end = chars.len().min(options.max_chars);
+        return Snippet {
+            text: excerpt(&chars, 0, end, &[], options),
+            line_number: None,
+            page: None,
+        };
+    };
+
+    let span_start = matches[first].start;
+    let span_end = matches[last].end;
+    let center = (span_start + span_end) / 2;
+    let start = center
+        .saturating_sub(options.max_chars / 2)
+        .min(chars.len().saturating_sub(options.max_chars))
+        .min(span_start);
+    let end = (start + options.max_chars).min(chars.len()).max(span_end);
+
+    let line_number = chars[..span_start].iter().filter(|&&c| c == '\n').count() + 1;
+    let page = chars.contains(&PAGE_BREAK).then(|| {
+        chars[..span_start]
+            .iter()
+            .filter(|&&c| c == PAGE_BREAK)
+            .count()
+            + 1
+    });
+
+    Snippet {
+        text: excerpt(&chars, start, end, &matches, options),
+        line_number: Some(line_number),
+        page,
+    }
+}
+
+/// Occurrences of query `terms` in `content`; overlapping ones (CJK
+/// characters and pairs) are merged.
+fn find_matches(content: &str, terms: &[String], tokenizer: &Tokenizer) -> Vec<Match> {
+    let mut matches: Vec<Match> = Vec::new();
+    if terms.is_empty() {
+        return matches;
+    }
+
+    for (word, range) in tokenizer.terms(content) {
+        let Some(term) = terms.iter().position(|t| *t == word) else {
+            continue;
+        };
+        match matches.last_mut() {
+            Some(last) if range.start < last.end => last.end = last.end.max(range.end),
+            _ => matches.push(Match {
+                start: range.start,
+                end: range.end,
+                term,
+            }),
+        }
+    }
+    matches
+}
+
+/// Indices of the first and last match in the best window no wider than `max_chars`.
+fn best_window(matches: &[Match], max_chars: usize) -> Option<(usize, usize)> {
+    let mut best: Option<((usize, usize), (usize, usize))> = None;
+    let mut counts: HashMap<usize, usize> = HashMap::new();
+    let mut left = 0;
+
+    for (right, m) in matches.iter().enumerate() {
+        *counts.entry(m.term).or_default() += 1;
+        while left < right && m.end - matches[left].start > max_chars {
+            let term = matches[left].term;
+            if let Some(count) = counts.get_mut(&term) {
+                *count -= 1;
+                if *count == 0 {
+                    counts.remove(&term);
+                }
+            }
+            left += 1;
+        }
+
+        let score = (counts.len(), right - left + 1);
+        if best.is_none_or(|(best_score, _)| score > best_score) {
+            best = Some((score, (left, right)));
+        }
+    }
+
+    best.map(|(_, window)| window)
+}
+
+fn excerpt(
+    chars: &[char],
+    start: usize,
+    end: usize,
+    matches: &[Match],
+    options: &SnippetOptions,
+) -> String {
+    let mut text = String::new();
+    if start > 0 {
+        text.push_str("...");
+    }
+
+    let mut pos = start;
+    for m in matches.iter().filter(|m| m.start >= start && m.end <= end) {
+        text.extend(&chars[pos..m.start]);
+        text.push_str(&options.open_marker);
+        text.extend(&chars[m.start..m.end]);
+        text.push_str(&options.close_marker);
+        pos = m.end;
+    }
+    text.extend(&chars[pos..end]);
+
+    if end < chars.len() {
+        text.push_str("...");
+    }
+    text.retain(|c| c != PAGE_BREAK);
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_snippet_centres_on_match_in_long_document() {
+        let mut content = "filler text line\n".repeat(500);
+        content.push_str("the kernel scheduler runs here\n");
+        content.push_str(&"more filler\n".repeat(200));
+        assert!(content.len() > 10 * 1024);
+
+        let snippet = build_snippet(
+            &content,
+            "scheduler",
+            &Tokenizer::default(),
+            &SnippetOptions::default(),
+        );
+        assert!(snippet.text.contains("**scheduler**"));
+        assert!(snippet.text.starts_with("..."));
+        assert_eq!(snippet.line_number, Some(501));
+    }
+
+    #[test]
+    fn test_snippet_prefers_window_with_most_distinct_terms() {
+        let content = format!(
+            "rust rust rust {} rust and cargo together {}",
+            "x ".repeat(300),
+            "y ".repeat(300)
+        );
+
+        let snippet = build_snippet(
+            &content,
+            "rust cargo",
+            &Tokenizer::default(),
+            &SnippetOptions::default(),
+        );
+        assert!(snippet.text.contains("**rust** and **cargo**"));
+    }
+
+    #[test]
+    fn test_snippet_without_matches_uses_document_start() {
+        let options = SnippetOptions {
+            max_chars: 10,
+            open_marker: "<b>".to_string(),
+            close_marker: "</b>".to_string(),
+        };
+        let snippet = build_snippet(
+            "abcdefghijklmnop",
+            "missing",
+            &Tokenizer::default(),
+            &options,
+        );
+        assert_eq!(snippet.text, "abcdefghij...");
+        assert_eq!(snippet.line_number, None);
+    }
+}
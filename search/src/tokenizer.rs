@@ -1,44 +1,238 @@
 //! Tokenizer for BM25 indexing.
+//!
+//! Words are lowercased, stripped of accents and stemmed, so "Indexing" and
+//! "indexes" both become `index` and "élèves" matches "eleve". CJK text has no
+//! spaces between words, so each CJK character and each pair of adjacent ones
+//! is a term of its own.
 
-/// Simple tokenizer that splits on whitespace and punctuation.
-pub struct Tokenizer;
+use lucastra_config::SearchConfig;
+use rust_stemmers::{Algorithm, Stemmer};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeSet;
+use std::ops::Range;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+
+pub use lucastra_config::DEFAULT_STOPWORDS;
+
+/// Splits text into index terms.
+///
+/// An index only matches queries tokenized the same way, so
+/// [`BM25Index`](crate::BM25Index) keeps the tokenizer it was built with.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Tokenizer {
+    /// Reduce words to their English stem.
+    pub stemming: bool,
+    /// Lowercase words left out of the index.
+    pub stopwords: BTreeSet<String>,
+    /// Tokenize as before stemming and Unicode support: split on anything
+    /// that isn't alphanumeric and drop words of two bytes or fewer. Indexes
+    /// saved by older versions use this.
+    pub legacy: bool,
+}
+
+impl Default for Tokenizer {
+    fn default() -> Self {
+        Self {
+            stemming: true,
+            stopwords: DEFAULT_STOPWORDS.iter().map(|w| w.to_string()).collect(),
+            legacy: false,
+        }
+    }
+}
 
 impl Tokenizer {
-    pub fn tokenize(text: &str) -> Vec<String> {
-        text.to_lowercase()
-            .split(|c: char| !c.is_alphanumeric())
-            .filter(|s| !s.is_empty() && s.len() > 2) // Filter stopwords by length
-            .map(|s| s.to_string())
-            .collect()
-    }
-
-    /// Check if a word is a common stopword.
-    pub fn is_stopword(word: &str) -> bool {
-        matches!(
-            word,
-            "the"
-                | "a"
-                | "an"
-                | "and"
-                | "or"
-                | "is"
-                | "in"
-                | "at"
-                | "to"
-                | "for"
-                | "of"
-                | "on"
-                | "with"
-                | "by"
-                | "from"
-        )
-    }
-
-    /// Remove stopwords from token list.
-    pub fn remove_stopwords(tokens: Vec<String>) -> Vec<String> {
-        tokens
+    /// Tokenizer for the `[search]` settings.
+    pub fn from_config(config: &SearchConfig) -> Self {
+        if config.legacy_tokenizer {
+            Self::legacy().with_stopwords(&config.stopwords)
+        } else {
+            Self::default()
+                .with_stemming(config.stemming)
+                .with_stopwords(&config.stopwords)
+        }
+    }
+
+    /// The original ASCII-minded tokenizer, kept for old indexes.
+    pub fn legacy() -> Self {
+        Self {
+            stemming: false,
+            legacy: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn with_stemming(mut self, stemming: bool) -> Self {
+        self.stemming = stemming;
+        self
+    }
+
+    pub fn with_stopwords<S: AsRef<str>>(mut self, stopwords: impl IntoIterator<Item = S>) -> Self {
+        self.stopwords = stopwords
             .into_iter()
-            .filter(|t| !Self::is_stopword(t))
-            .collect()
+            .map(|w| w.as_ref().to_lowercase())
+            .collect();
+        self
+    }
+
+    /// Index terms in `text`, stopwords removed.
+    pub fn tokenize(&self, text: &str) -> Vec<String> {
+        self.terms(text).into_iter().map(|(term, _)| term).collect()
+    }
+
+    /// Index terms in `text` with the char range each came from, in order.
+    pub fn terms(&self, text: &str) -> Vec<(String, Range<usize>)> {
+        let chars: Vec<char> = text.chars().collect();
+        if self.legacy {
+            return self.legacy_terms(&chars);
+        }
+
+        let stemmer = self.stemming.then(|| Stemmer::create(Algorithm::English));
+        let mut terms = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            let c = chars[i];
+            if is_cjk(c) {
+                terms.push((c.to_string(), i..i + 1));
+                if let Some(&next) = chars.get(i + 1).filter(|&&n| is_cjk(n)) {
+                    terms.push((format!("{}{}", c, next), i..i + 2));
+                }
+                i += 1;
+            } else if c.is_alphanumeric() {
+                let start = i;
+                while i < chars.len()
+                    && !is_cjk(chars[i])
+                    && (chars[i].is_alphanumeric() || is_combining_mark(chars[i]))
+                {
+                    i += 1;
+                }
+                let word = fold(&chars[start..i]);
+                if word.chars().count() < 2 || self.is_stopword(&word) {
+                    continue;
+                }
+                let term = match &stemmer {
+                    Some(stemmer) if !word.chars().any(|c| c.is_numeric()) => {
+                        stemmer.stem(&word).into_owned()
+                    }
+                    _ => word,
+                };
+                terms.push((term, start..i));
+            } else {
+                i += 1;
+            }
+        }
+        terms
+    }
+
+    /// Check if a lowercase word is a stopword.
+    pub fn is_stopword(&self, word: &str) -> bool {
+        self.stopwords.contains(word)
+    }
+
+    fn legacy_terms(&self, chars: &[char]) -> Vec<(String, Range<usize>)> {
+        let mut terms = Vec::new();
+        let mut i = 0;
+        while i < chars.len() {
+            if !chars[i].is_alphanumeric() {
+                i += 1;
+                continue;
+            }
+            let start = i;
+            while i < chars.len() && chars[i].is_alphanumeric() {
+                i += 1;
+            }
+            let word = chars[start..i].iter().collect::<String>().to_lowercase();
+            if word.len() > 2 && !self.is_stopword(&word) {
+                terms.push((word, start..i));
+            }
+        }
+        terms
+    }
+}
+
+/// Lowercase `word` and drop its accents.
+fn fold(word: &[char]) -> String {
+    word.iter()
+        .collect::<String>()
+        .to_lowercase()
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .collect()
+}
+
+/// Han, kana and hangul characters, which are written without spaces.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{3040}'..='\u{30FF}'
+        | '\u{3400}'..='\u{4DBF}'
+        | '\u{4E00}'..='\u{9FFF}'
+        | '\u{AC00}'..='\u{D7AF}'
+        | '\u{F900}'..='\u{FAFF}'
+        | '\u{20000}'..='\u{2A6DF}')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_word_forms_share_a_stem() {
+        let tokenizer = Tokenizer::default();
+        assert_eq!(
+            tokenizer.tokenize("Indexing indexes, indexed index"),
+            vec!["index"; 4]
+        );
+        assert_eq!(
+            tokenizer.tokenize("the files of a directory"),
+            vec!["file", "directori"]
+        );
+    }
+
+    #[test]
+    fn test_accents_are_folded() {
+        let tokenizer = Tokenizer::default().with_stemming(false);
+        // Precomposed and decomposed "é" tokenize the same way
+        assert_eq!(
+            tokenizer.tokenize("Les élèves étudient"),
+            tokenizer.tokenize("Les e\u{301}le\u{300}ves e\u{301}tudient")
+        );
+        assert_eq!(
+            tokenizer.tokenize("Les élèves étudient"),
+            vec!["les", "eleves", "etudient"]
+        );
+    }
+
+    #[test]
+    fn test_cjk_characters_and_pairs_are_terms() {
+        let terms = Tokenizer::default().terms("搜索引擎 rust");
+        let terms: Vec<_> = terms
+            .iter()
+            .map(|(term, range)| (term.as_str(), range.clone()))
+            .collect();
+        assert_eq!(
+            terms,
+            vec![
+                ("搜", 0..1),
+                ("搜索", 0..2),
+                ("索", 1..2),
+                ("索引", 1..3),
+                ("引", 2..3),
+                ("引擎", 2..4),
+                ("擎", 3..4),
+                ("rust", 5..9),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_custom_stopwords_and_legacy_mode() {
+        let tokenizer = Tokenizer::default().with_stopwords(["Rust"]);
+        assert_eq!(tokenizer.tokenize("rust and cargo"), vec!["and", "cargo"]);
+
+        let legacy = Tokenizer::legacy();
+        assert_eq!(
+            legacy.tokenize("Indexing the élèves 搜索"),
+            vec!["indexing", "élèves", "搜索"]
+        );
     }
 }
@@ -3,10 +3,26 @@
 //! This module provides semantic search capabilities using vector embeddings,
 //! replacing the simple TF-IDF keyword search with neural network-based similarity.
 
+use crate::chunker::Chunk;
+use crate::hnsw::Hnsw;
+pub use crate::hnsw::HnswParams;
+use lucastra_core::compat::{self, UnsupportedVersion};
+use lucastra_core::LuCastraError;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+/// First line of every persisted index file.
+const FILE_MAGIC: &str = "LUCASTRA_VECTOR_INDEX";
+
+/// Characters of chunk text kept as a result snippet.
+const CHUNK_SNIPPET_CHARS: usize = 200;
+
+/// Bump when the on-disk layout changes incompatibly.
+pub const FORMAT_VERSION: u32 = 1;
+
 #[derive(Debug, Error)]
 pub enum VectorError {
     #[error("index error: {0}")]
@@ -15,10 +31,55 @@ pub enum VectorError {
     DimensionMismatch { expected: usize, got: usize },
     #[error("empty embeddings")]
     EmptyEmbeddings,
+    #[error(
+        "index was built with embedding model {index} but embeddings now come from {active}; \
+         re-index with `lucastra-cli index --reindex`"
+    )]
+    MismatchedEmbeddingModel {
+        index: EmbeddingModelInfo,
+        active: EmbeddingModelInfo,
+    },
+    #[error(transparent)]
+    UnsupportedVersion(#[from] UnsupportedVersion),
 }
 
 pub type VectorResult<T> = std::result::Result<T, VectorError>;
 
+impl From<VectorError> for LuCastraError {
+    fn from(error: VectorError) -> Self {
+        LuCastraError::Vector(Box::new(error))
+    }
+}
+
+/// The model an index's embeddings come from. Embeddings of different
+/// models can't be compared, even when their dimensions happen to agree.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmbeddingModelInfo {
+    pub name: String,
+    pub provider: String,
+    pub dimensions: usize,
+}
+
+impl EmbeddingModelInfo {
+    pub fn new(provider: impl Into<String>, name: impl Into<String>, dimensions: usize) -> Self {
+        Self {
+            name: name.into(),
+            provider: provider.into(),
+            dimensions,
+        }
+    }
+}
+
+impl std::fmt::Display for EmbeddingModelInfo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}/{} ({} dimensions)",
+            self.provider, self.name, self.dimensions
+        )
+    }
+}
+
 /// A document with its vector embedding.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct VectorDocument {
@@ -26,6 +87,19 @@ pub struct VectorDocument {
     pub path: PathBuf,
     pub embedding: Vec<f32>,
     pub snippet: String,
+    /// Free-form attributes used for filtering (e.g. `dir`, `modified`).
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    /// Region of the source file this embedding covers (`None` = whole file).
+    #[serde(default)]
+    pub chunk: Option<ChunkRange>,
+}
+
+/// Byte range of a chunk within its source file.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ChunkRange {
+    pub offset: usize,
+    pub length: usize,
 }
 
 /// Search result with similarity score.
@@ -34,25 +108,153 @@ pub struct VectorSearchResult {
     pub path: PathBuf,
     pub score: f32,
     pub snippet: String,
+    #[serde(default)]
+    pub metadata: HashMap<String, String>,
+    #[serde(default)]
+    pub chunk: Option<ChunkRange>,
+}
+
+/// Filter over document metadata; every pair must match.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MetadataFilter {
+    pub equals: Vec<(String, String)>,
+    /// Values that must start with the given text, e.g. a `dir` below a
+    /// directory or a `modified` date in a month.
+    #[serde(default)]
+    pub prefixes: Vec<(String, String)>,
 }
 
-/// Simple vector index using cosine similarity (naive implementation).
+impl MetadataFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Require `metadata[key] == value`.
+    pub fn with_equals(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        self.equals.push((key.into(), value.into()));
+        self
+    }
+
+    /// Require `metadata[key]` to start with `prefix`.
+    pub fn with_prefix(mut self, key: impl Into<String>, prefix: impl Into<String>) -> Self {
+        self.prefixes.push((key.into(), prefix.into()));
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.equals.is_empty() && self.prefixes.is_empty()
+    }
+
+    pub fn matches(&self, metadata: &HashMap<String, String>) -> bool {
+        self.equals
+            .iter()
+            .all(|(key, value)| metadata.get(key) == Some(value))
+            && self.prefixes.iter().all(|(key, prefix)| {
+                metadata
+                    .get(key)
+                    .is_some_and(|value| value.starts_with(prefix.as_str()))
+            })
+    }
+}
+
+/// Serialized body of a persisted index.
+#[derive(Serialize, Deserialize)]
+struct PersistedIndex {
+    documents: Vec<VectorDocument>,
+    dimensions: Option<usize>,
+    next_id: usize,
+    #[serde(default)]
+    exact: bool,
+    #[serde(default)]
+    params: HnswParams,
+    /// Unset in indexes saved before models were recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    model: Option<EmbeddingModelInfo>,
+}
+
+/// Vector index using cosine similarity.
 ///
-/// TODO: Replace with HNSW for better performance on large corpora.
-/// Current implementation is O(n) for search, HNSW would be O(log n).
+/// By default documents are inserted into an HNSW graph and searches are
+/// approximate. [`VectorIndex::exact`] builds an index that scans every
+/// document instead, which is cheaper for small corpora and gives the
+/// ground truth when measuring recall.
 pub struct VectorIndex {
     documents: Vec<VectorDocument>,
     dimensions: Option<usize>,
     next_id: usize,
+    /// `None` for exact (brute-force) indexes.
+    graph: Option<Hnsw>,
+    params: HnswParams,
+    /// Model that produced the stored embeddings.
+    model: Option<EmbeddingModelInfo>,
+    /// Model callers are embedding with now; checked against `model`.
+    active_model: Option<EmbeddingModelInfo>,
 }
 
 impl VectorIndex {
     pub fn new() -> Self {
+        Self::with_params(HnswParams::default())
+    }
+
+    /// Approximate index with custom HNSW parameters.
+    pub fn with_params(params: HnswParams) -> Self {
         Self {
             documents: Vec::new(),
             dimensions: None,
             next_id: 0,
+            graph: Some(Hnsw::new(params)),
+            params,
+            model: None,
+            active_model: None,
+        }
+    }
+
+    /// Exact index that compares the query against every document.
+    pub fn exact() -> Self {
+        Self {
+            graph: None,
+            ..Self::new()
+        }
+    }
+
+    /// Record that embeddings come from `model`; see
+    /// [`VectorIndex::use_embedding_model`].
+    pub fn with_embedding_model(mut self, model: EmbeddingModelInfo) -> Self {
+        self.use_embedding_model(model);
+        self
+    }
+
+    /// Declare the model that embeddings added or searched with come from.
+    /// An empty index records it as its own; otherwise adding and searching
+    /// fail with [`VectorError::MismatchedEmbeddingModel`] while it differs
+    /// from the model the index was built with.
+    pub fn use_embedding_model(&mut self, model: EmbeddingModelInfo) {
+        if self.documents.is_empty() {
+            self.model = Some(model.clone());
         }
+        self.active_model = Some(model);
+    }
+
+    /// Model the stored embeddings come from, if known.
+    pub fn embedding_model(&self) -> Option<&EmbeddingModelInfo> {
+        self.model.as_ref()
+    }
+
+    fn check_model(&self) -> VectorResult<()> {
+        match (&self.model, &self.active_model) {
+            (Some(index), Some(active)) if index != active => {
+                Err(VectorError::MismatchedEmbeddingModel {
+                    index: index.clone(),
+                    active: active.clone(),
+                })
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether searches scan every document rather than the HNSW graph.
+    pub fn is_exact(&self) -> bool {
+        self.graph.is_none()
     }
 
     /// Add a document with its embedding to the index.
@@ -61,10 +263,49 @@ impl VectorIndex {
         path: PathBuf,
         embedding: Vec<f32>,
         snippet: String,
+    ) -> VectorResult<usize> {
+        self.add_document_with_metadata(path, embedding, snippet, HashMap::new())
+    }
+
+    /// Add a document with metadata that [`VectorIndex::search_filtered`] can match on.
+    pub fn add_document_with_metadata(
+        &mut self,
+        path: PathBuf,
+        embedding: Vec<f32>,
+        snippet: String,
+        metadata: HashMap<String, String>,
+    ) -> VectorResult<usize> {
+        self.insert(path, embedding, snippet, metadata, None)
+    }
+
+    /// Add the embedding of one chunk of `path`; the chunk text becomes the snippet.
+    pub fn add_chunk(
+        &mut self,
+        path: PathBuf,
+        embedding: Vec<f32>,
+        chunk: &Chunk,
+        metadata: HashMap<String, String>,
+    ) -> VectorResult<usize> {
+        let snippet = chunk.text.chars().take(CHUNK_SNIPPET_CHARS).collect();
+        let range = ChunkRange {
+            offset: chunk.offset,
+            length: chunk.length,
+        };
+        self.insert(path, embedding, snippet, metadata, Some(range))
+    }
+
+    fn insert(
+        &mut self,
+        path: PathBuf,
+        embedding: Vec<f32>,
+        snippet: String,
+        metadata: HashMap<String, String>,
+        chunk: Option<ChunkRange>,
     ) -> VectorResult<usize> {
         if embedding.is_empty() {
             return Err(VectorError::EmptyEmbeddings);
         }
+        self.check_model()?;
 
         // Validate dimension consistency
         if let Some(dims) = self.dimensions {
@@ -81,11 +322,17 @@ impl VectorIndex {
         let id = self.next_id;
         self.next_id += 1;
 
+        if let Some(graph) = self.graph.as_mut() {
+            graph.insert(&embedding);
+        }
+
         self.documents.push(VectorDocument {
             id,
             path,
             embedding,
             snippet,
+            metadata,
+            chunk,
         });
 
         Ok(id)
@@ -96,10 +343,24 @@ impl VectorIndex {
         &self,
         query_embedding: &[f32],
         k: usize,
+    ) -> VectorResult<Vec<VectorSearchResult>> {
+        self.search_filtered(query_embedding, k, &MetadataFilter::default())
+    }
+
+    /// Search only documents whose metadata matches `filter`.
+    ///
+    /// The filter is applied before the top-k cut, so up to `k` matching
+    /// documents are returned whenever that many exist.
+    pub fn search_filtered(
+        &self,
+        query_embedding: &[f32],
+        k: usize,
+        filter: &MetadataFilter,
     ) -> VectorResult<Vec<VectorSearchResult>> {
         if query_embedding.is_empty() {
             return Err(VectorError::EmptyEmbeddings);
         }
+        self.check_model()?;
 
         if let Some(dims) = self.dimensions {
             if query_embedding.len() != dims {
@@ -110,25 +371,37 @@ impl VectorIndex {
             }
         }
 
-        let mut scored_docs: Vec<(f32, &VectorDocument)> = self
-            .documents
-            .iter()
-            .map(|doc| {
-                let similarity = cosine_similarity(&doc.embedding, query_embedding);
-                (similarity, doc)
-            })
-            .collect();
-
-        // Sort by similarity (descending)
-        scored_docs.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+        let scored_docs: Vec<(f32, &VectorDocument)> = match &self.graph {
+            Some(graph) => graph
+                .search_filtered(query_embedding, k, |node| {
+                    filter.matches(&self.documents[node].metadata)
+                })
+                .into_iter()
+                .map(|(node, score)| (score, &self.documents[node]))
+                .collect(),
+            None => {
+                let mut scored: Vec<(f32, &VectorDocument)> = self
+                    .documents
+                    .iter()
+                    .filter(|doc| filter.matches(&doc.metadata))
+                    .map(|doc| (cosine_similarity(&doc.embedding, query_embedding), doc))
+                    .collect();
+
+                // Sort by similarity (descending)
+                scored.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+                scored.truncate(k);
+                scored
+            }
+        };
 
         Ok(scored_docs
             .into_iter()
-            .take(k)
             .map(|(score, doc)| VectorSearchResult {
                 path: doc.path.clone(),
                 score,
                 snippet: doc.snippet.clone(),
+                metadata: doc.metadata.clone(),
+                chunk: doc.chunk,
             })
             .collect())
     }
@@ -148,11 +421,147 @@ impl VectorIndex {
         self.dimensions
     }
 
+    /// Snippets of every document, in the order [`VectorIndex::reembedded`]
+    /// expects their new embeddings.
+    pub fn snippets(&self) -> Vec<&str> {
+        self.documents.iter().map(|d| d.snippet.as_str()).collect()
+    }
+
+    /// This index with every document's embedding replaced, in the order of
+    /// [`VectorIndex::snippets`], by one from `model`, which becomes the
+    /// index's recorded model.
+    pub fn reembedded(
+        &self,
+        model: EmbeddingModelInfo,
+        embeddings: Vec<Vec<f32>>,
+    ) -> VectorResult<Self> {
+        if embeddings.len() != self.documents.len() {
+            return Err(VectorError::IndexError(format!(
+                "{} embeddings for {} documents",
+                embeddings.len(),
+                self.documents.len()
+            )));
+        }
+
+        let mut index = if self.is_exact() {
+            Self::exact()
+        } else {
+            Self::with_params(self.params)
+        }
+        .with_embedding_model(model);
+        for (doc, embedding) in self.documents.iter().zip(embeddings) {
+            index.insert(
+                doc.path.clone(),
+                embedding,
+                doc.snippet.clone(),
+                doc.metadata.clone(),
+                doc.chunk,
+            )?;
+        }
+        Ok(index)
+    }
+
+    /// Keep only the documents whose path `keep` accepts. Returns how many
+    /// were removed.
+    pub fn retain(&mut self, keep: impl Fn(&Path) -> bool) -> usize {
+        if self.documents.iter().all(|doc| keep(&doc.path)) {
+            return 0;
+        }
+        let documents = std::mem::take(&mut self.documents);
+        let before = documents.len();
+        // The graph can't forget nodes, so it is rebuilt from what's kept
+        self.clear();
+        for doc in documents.into_iter().filter(|doc| keep(&doc.path)) {
+            self.dimensions.get_or_insert(doc.embedding.len());
+            if let Some(graph) = self.graph.as_mut() {
+                graph.insert(&doc.embedding);
+            }
+            self.documents.push(VectorDocument {
+                id: self.next_id,
+                ..doc
+            });
+            self.next_id += 1;
+        }
+        before - self.documents.len()
+    }
+
     /// Clear all documents from the index.
     pub fn clear(&mut self) {
         self.documents.clear();
         self.dimensions = None;
         self.next_id = 0;
+        if self.graph.is_some() {
+            self.graph = Some(Hnsw::new(self.params));
+        }
+    }
+
+    /// Save the index to a single file with a versioned header.
+    pub fn save(&self, path: &Path) -> VectorResult<()> {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).map_err(|e| VectorError::IndexError(e.to_string()))?;
+        }
+
+        let body = PersistedIndex {
+            documents: self.documents.clone(),
+            dimensions: self.dimensions,
+            next_id: self.next_id,
+            exact: self.is_exact(),
+            params: self.params,
+            model: self.model.clone(),
+        };
+
+        let file =
+            std::fs::File::create(path).map_err(|e| VectorError::IndexError(e.to_string()))?;
+        let mut writer = std::io::BufWriter::new(file);
+        writeln!(writer, "{} {}", FILE_MAGIC, FORMAT_VERSION)
+            .map_err(|e| VectorError::IndexError(e.to_string()))?;
+        serde_json::to_writer(&mut writer, &body)
+            .map_err(|e| VectorError::IndexError(e.to_string()))?;
+        writer
+            .flush()
+            .map_err(|e| VectorError::IndexError(e.to_string()))
+    }
+
+    /// Load an index written by [`VectorIndex::save`].
+    pub fn load(path: &Path) -> VectorResult<Self> {
+        let file = std::fs::File::open(path)
+            .map_err(|e| VectorError::IndexError(format!("{}: {}", path.display(), e)))?;
+        let mut reader = BufReader::new(file);
+
+        let mut header = String::new();
+        reader
+            .read_line(&mut header)
+            .map_err(|e| VectorError::IndexError(e.to_string()))?;
+        let version = header
+            .trim_end()
+            .strip_prefix(FILE_MAGIC)
+            .and_then(|v| v.trim().parse::<u64>().ok())
+            .ok_or_else(|| {
+                VectorError::IndexError(format!("{} is not a vector index file", path.display()))
+            })?;
+        compat::check_version("vector index", version, FORMAT_VERSION)?;
+
+        let body: PersistedIndex = serde_json::from_reader(reader)
+            .map_err(|e| VectorError::IndexError(format!("corrupted index: {}", e)))?;
+
+        // The graph isn't persisted; rebuilding it is deterministic
+        let graph = (!body.exact).then(|| {
+            let mut graph = Hnsw::new(body.params);
+            for doc in &body.documents {
+                graph.insert(&doc.embedding);
+            }
+            graph
+        });
+
+        Ok(Self {
+            documents: body.documents,
+            dimensions: body.dimensions,
+            next_id: body.next_id,
+            graph,
+            params: body.params,
+            model: body.model,
+            active_model: None,
+        })
     }
 }
 
@@ -164,7 +573,7 @@ impl Default for VectorIndex {
 
 /// Compute cosine similarity between two vectors.
 /// Returns value in range [-1, 1], where 1 means identical direction.
-fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+pub fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
     assert_eq!(a.len(), b.len(), "Vectors must have same length");
 
     let dot_product: f32 = a.iter().zip(b.iter()).map(|(x, y)| x * y).sum();
@@ -216,6 +625,29 @@ mod tests {
         assert_eq!(index.dimensions(), Some(3));
     }
 
+    #[test]
+    fn test_retain_drops_documents_and_keeps_search_working() {
+        let mut index = VectorIndex::new();
+        for (path, embedding) in [
+            ("/docs/a.md", vec![1.0, 0.0, 0.0]),
+            ("/docs/node_modules/b.md", vec![0.0, 1.0, 0.0]),
+            ("/docs/c.md", vec![0.0, 0.0, 1.0]),
+        ] {
+            index
+                .add_document(PathBuf::from(path), embedding, path.to_string())
+                .unwrap();
+        }
+
+        let removed = index.retain(|path| !path.starts_with("/docs/node_modules"));
+        assert_eq!(removed, 1);
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.retain(|_| true), 0);
+
+        let results = index.search(&[0.0, 0.1, 1.0], 3).unwrap();
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].path, PathBuf::from("/docs/c.md"));
+    }
+
     #[test]
     fn test_vector_index_dimension_mismatch() {
         let mut index = VectorIndex::new();
@@ -297,4 +729,283 @@ mod tests {
         assert_eq!(index.len(), 0);
         assert_eq!(index.dimensions(), None);
     }
+
+    #[test]
+    fn test_vector_index_save_and_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("vector_index.bin");
+
+        let mut index = VectorIndex::new();
+        index
+            .add_document(PathBuf::from("/a.txt"), vec![1.0, 0.0], "A".to_string())
+            .unwrap();
+        index
+            .add_document(PathBuf::from("/b.txt"), vec![0.0, 1.0], "B".to_string())
+            .unwrap();
+        index.save(&path).unwrap();
+
+        let loaded = VectorIndex::load(&path).unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded.dimensions(), Some(2));
+        let results = loaded.search(&[0.9, 0.1], 1).unwrap();
+        assert_eq!(results[0].path, PathBuf::from("/a.txt"));
+
+        // Dimension mismatch against a loaded index is an error, not a panic
+        assert!(matches!(
+            loaded.search(&[1.0, 0.0, 0.0], 1),
+            Err(VectorError::DimensionMismatch {
+                expected: 2,
+                got: 3
+            })
+        ));
+    }
+
+    /// Deterministic pseudo-random unit-cube vectors.
+    fn random_vectors(count: usize, dims: usize, seed: u64) -> Vec<Vec<f32>> {
+        let mut state = seed;
+        (0..count)
+            .map(|_| {
+                (0..dims)
+                    .map(|_| {
+                        state = state
+                            .wrapping_mul(6364136223846793005)
+                            .wrapping_add(1442695040888963407);
+                        ((state >> 33) as f32 / (1u64 << 31) as f32) * 2.0 - 1.0
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hnsw_recall_against_brute_force() {
+        let mut approximate = VectorIndex::new();
+        let mut exact = VectorIndex::exact();
+        for (i, embedding) in random_vectors(2000, 32, 7).into_iter().enumerate() {
+            let path = PathBuf::from(format!("/doc{}.txt", i));
+            approximate
+                .add_document(path.clone(), embedding.clone(), String::new())
+                .unwrap();
+            exact.add_document(path, embedding, String::new()).unwrap();
+        }
+
+        let queries = random_vectors(50, 32, 99);
+        let mut hits = 0;
+        for query in &queries {
+            let truth: Vec<PathBuf> = exact
+                .search(query, 10)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.path)
+                .collect();
+            hits += approximate
+                .search(query, 10)
+                .unwrap()
+                .iter()
+                .filter(|r| truth.contains(&r.path))
+                .count();
+        }
+
+        let recall = hits as f32 / (queries.len() * 10) as f32;
+        assert!(recall >= 0.95, "recall@10 was {}", recall);
+    }
+
+    #[test]
+    fn test_exact_flag_survives_save_and_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("vector_index.bin");
+
+        let mut index = VectorIndex::exact();
+        index
+            .add_document(PathBuf::from("/a.txt"), vec![1.0, 0.0], "A".to_string())
+            .unwrap();
+        index.save(&path).unwrap();
+
+        let loaded = VectorIndex::load(&path).unwrap();
+        assert!(loaded.is_exact());
+        assert!(!VectorIndex::new().is_exact());
+    }
+
+    fn tagged_index(mut index: VectorIndex) -> VectorIndex {
+        for (i, embedding) in random_vectors(200, 8, 3).into_iter().enumerate() {
+            let kind = if i % 10 == 0 { "project" } else { "other" };
+            let metadata = HashMap::from([("kind".to_string(), kind.to_string())]);
+            index
+                .add_document_with_metadata(
+                    PathBuf::from(format!("/doc{}.txt", i)),
+                    embedding,
+                    String::new(),
+                    metadata,
+                )
+                .unwrap();
+        }
+        index
+    }
+
+    #[test]
+    fn test_search_filtered_applies_filter_before_top_k() {
+        let filter = MetadataFilter::new().with_equals("kind", "project");
+        let query = random_vectors(1, 8, 11).remove(0);
+
+        for index in [
+            tagged_index(VectorIndex::new()),
+            tagged_index(VectorIndex::exact()),
+        ] {
+            let results = index.search_filtered(&query, 10, &filter).unwrap();
+            assert_eq!(results.len(), 10);
+            assert!(results.iter().all(|r| r.metadata["kind"] == "project"));
+        }
+
+        let none = MetadataFilter::new().with_equals("kind", "missing");
+        let index = tagged_index(VectorIndex::new());
+        assert!(index.search_filtered(&query, 5, &none).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_index_without_metadata_field() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("vector_index.bin");
+        std::fs::write(
+            &path,
+            "LUCASTRA_VECTOR_INDEX 1\n{\"documents\":[{\"id\":0,\"path\":\"/a.txt\",\
+             \"embedding\":[1.0,0.0],\"snippet\":\"A\"}],\"dimensions\":2,\"next_id\":1}",
+        )
+        .unwrap();
+
+        let index = VectorIndex::load(&path).unwrap();
+        let results = index.search(&[1.0, 0.0], 1).unwrap();
+        assert_eq!(results[0].path, PathBuf::from("/a.txt"));
+        assert!(results[0].metadata.is_empty());
+    }
+
+    #[test]
+    fn test_chunk_range_survives_save_and_load() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("vector_index.bin");
+        let chunk = Chunk {
+            text: "second section".to_string(),
+            offset: 120,
+            length: 14,
+        };
+
+        let mut index = VectorIndex::new();
+        index
+            .add_chunk(
+                PathBuf::from("/a.md"),
+                vec![1.0, 0.0],
+                &chunk,
+                HashMap::new(),
+            )
+            .unwrap();
+        index.save(&path).unwrap();
+
+        let results = VectorIndex::load(&path)
+            .unwrap()
+            .search(&[1.0, 0.0], 1)
+            .unwrap();
+        assert_eq!(results[0].snippet, "second section");
+        assert_eq!(
+            results[0].chunk,
+            Some(ChunkRange {
+                offset: 120,
+                length: 14
+            })
+        );
+    }
+
+    #[test]
+    fn test_v1_vector_index_still_loads() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("vector_index.bin");
+        std::fs::write(&path, include_str!("../tests/fixtures/v1/vector_index.bin")).unwrap();
+
+        let index = VectorIndex::load(&path).unwrap();
+        assert_eq!(index.len(), 2);
+        assert_eq!(index.dimensions(), Some(3));
+        let results = index.search(&[0.0, 1.0, 0.0], 1).unwrap();
+        assert_eq!(results[0].path, PathBuf::from("/mnt/root/todo.txt"));
+    }
+
+    #[test]
+    fn test_other_embedding_model_is_refused() {
+        let model_a = EmbeddingModelInfo::new("llamafile", "bge-small-en-v1.5", 3);
+        let model_b = EmbeddingModelInfo::new("openai", "text-embedding-3-small", 3);
+        let mut index = VectorIndex::new().with_embedding_model(model_a.clone());
+        index
+            .add_document(PathBuf::from("/a.txt"), vec![1.0, 0.0, 0.0], "a".into())
+            .unwrap();
+
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("vector_index.bin");
+        index.save(&path).unwrap();
+        let mut index = VectorIndex::load(&path).unwrap();
+        assert_eq!(index.embedding_model(), Some(&model_a));
+
+        index.use_embedding_model(model_b.clone());
+        let error = index.search(&[1.0, 0.0, 0.0], 1).unwrap_err();
+        assert!(error.to_string().contains("--reindex"));
+        match error {
+            VectorError::MismatchedEmbeddingModel { index, active } => {
+                assert_eq!(index, model_a);
+                assert_eq!(active, model_b);
+            }
+            other => panic!("unexpected error: {:?}", other),
+        }
+        assert!(matches!(
+            index.add_document(PathBuf::from("/b.txt"), vec![0.0, 1.0, 0.0], "b".into()),
+            Err(VectorError::MismatchedEmbeddingModel { .. })
+        ));
+
+        index.use_embedding_model(model_a);
+        assert_eq!(index.search(&[1.0, 0.0, 0.0], 1).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_reembedding_migrates_the_model() {
+        let old = EmbeddingModelInfo::new("llamafile", "bge-small-en-v1.5", 3);
+        let new = EmbeddingModelInfo::new("openai", "text-embedding-3-small", 2);
+        let mut index = VectorIndex::exact().with_embedding_model(old);
+        let mut metadata = HashMap::new();
+        metadata.insert("dir".to_string(), "notes".to_string());
+        index
+            .add_document_with_metadata(
+                PathBuf::from("/notes/a.txt"),
+                vec![1.0, 0.0, 0.0],
+                "alpha".into(),
+                metadata,
+            )
+            .unwrap();
+        index
+            .add_document(PathBuf::from("/b.txt"), vec![0.0, 1.0, 0.0], "beta".into())
+            .unwrap();
+        assert_eq!(index.snippets(), vec!["alpha", "beta"]);
+
+        assert!(index.reembedded(new.clone(), vec![vec![1.0, 0.0]]).is_err());
+        let mut migrated = index
+            .reembedded(new.clone(), vec![vec![0.0, 1.0], vec![1.0, 0.0]])
+            .unwrap();
+        assert_eq!(migrated.embedding_model(), Some(&new));
+        assert_eq!(migrated.dimensions(), Some(2));
+        assert!(migrated.is_exact());
+
+        migrated.use_embedding_model(new);
+        let results = migrated.search(&[0.0, 1.0], 1).unwrap();
+        assert_eq!(results[0].path, PathBuf::from("/notes/a.txt"));
+        assert_eq!(results[0].metadata["dir"], "notes");
+    }
+
+    #[test]
+    fn test_vector_index_rejects_unknown_version() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let path = temp_dir.path().join("vector_index.bin");
+        std::fs::write(&path, "LUCASTRA_VECTOR_INDEX 99\n{}").unwrap();
+
+        assert!(matches!(
+            VectorIndex::load(&path),
+            Err(VectorError::UnsupportedVersion(UnsupportedVersion {
+                found: 99,
+                ..
+            }))
+        ));
+    }
 }
@@ -0,0 +1,244 @@
+//! Filesystem watcher that keeps the search index in sync with disk.
+//!
+//! Raw notify events are debounced on a background thread; settled paths
+//! are sent back over a channel and applied with [`FileWatcher::apply`].
+
+use crate::{Indexer, SearchService};
+use lucastra_core::{LuCastraError, Result};
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use tracing::{debug, info, warn};
+
+/// Quiet period a path needs before it is re-indexed.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(500);
+
+/// A settled change to a watched path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WatchEvent {
+    /// The path exists and was created or modified.
+    Changed(PathBuf),
+    /// The path no longer exists.
+    Removed(PathBuf),
+}
+
+enum Signal {
+    Touched(PathBuf),
+    Stop,
+}
+
+/// Watches directories recursively and reports debounced changes.
+pub struct FileWatcher {
+    watcher: Option<RecommendedWatcher>,
+    signals: Sender<Signal>,
+    events: Receiver<WatchEvent>,
+    worker: Option<JoinHandle<()>>,
+    roots: Vec<PathBuf>,
+}
+
+impl FileWatcher {
+    /// Start watching `roots` with the default debounce interval.
+    pub fn start(roots: Vec<PathBuf>) -> Result<Self> {
+        Self::with_debounce(roots, DEFAULT_DEBOUNCE)
+    }
+
+    /// Start watching `roots`; roots that don't exist are skipped with a warning.
+    pub fn with_debounce(roots: Vec<PathBuf>, debounce: Duration) -> Result<Self> {
+        let (signal_tx, signal_rx) = mpsc::channel();
+        let (event_tx, event_rx) = mpsc::channel();
+
+        let notify_tx = signal_tx.clone();
+        let mut watcher =
+            notify::recommended_watcher(move |res: notify::Result<Event>| match res {
+                Ok(event) => {
+                    for path in event.paths {
+                        let _ = notify_tx.send(Signal::Touched(path));
+                    }
+                }
+                Err(e) => warn!("File watcher error: {}", e),
+            })
+            .map_err(|e| LuCastraError::ServiceError(format!("Failed to create watcher: {}", e)))?;
+
+        let mut watched = Vec::new();
+        for root in roots {
+            // Canonical roots keep event paths consistent with indexed paths
+            let Ok(root) = std::fs::canonicalize(&root) else {
+                warn!("Not watching missing directory {}", root.display());
+                continue;
+            };
+            match watcher.watch(&root, RecursiveMode::Recursive) {
+                Ok(()) => {
+                    info!("Watching {} for changes", root.display());
+                    watched.push(root);
+                }
+                Err(e) => warn!("Cannot watch {}: {}", root.display(), e),
+            }
+        }
+
+        let worker = std::thread::Builder::new()
+            .name("lucastra-file-watcher".to_string())
+            .spawn(move || debounce_loop(signal_rx, event_tx, debounce))
+            .map_err(|e| LuCastraError::ServiceError(e.to_string()))?;
+
+        Ok(Self {
+            watcher: Some(watcher),
+            signals: signal_tx,
+            events: event_rx,
+            worker: Some(worker),
+            roots: watched,
+        })
+    }
+
+    /// Directories actually being watched.
+    pub fn roots(&self) -> &[PathBuf] {
+        &self.roots
+    }
+
+    /// Take all settled events without blocking.
+    pub fn drain(&self) -> Vec<WatchEvent> {
+        self.events.try_iter().collect()
+    }
+
+    /// Apply pending events to `service`. Returns how many events were applied.
+    pub fn apply(&self, service: &SearchService, indexer: &Indexer) -> usize {
+        let events = self.drain();
+        for event in &events {
+            match event {
+                // Re-walking a whole root for its own metadata changes is wasted work
+                WatchEvent::Changed(path) if self.roots.contains(path) => {}
+                // Churn in excluded directories such as node_modules
+                WatchEvent::Changed(path) if indexer.excludes(path, path.is_dir()) => {}
+                WatchEvent::Changed(path) => {
+                    if let Err(e) = indexer.index_path(path, service) {
+                        debug!("Not re-indexing {}: {}", path.display(), e);
+                    }
+                }
+                WatchEvent::Removed(path) => {
+                    service.remove_document(&path.to_string_lossy());
+                }
+            }
+        }
+        events.len()
+    }
+
+    /// Stop watching and join the background thread.
+    pub fn stop(&mut self) {
+        // Dropping the notify watcher releases its OS handles
+        self.watcher.take();
+        let _ = self.signals.send(Signal::Stop);
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl Drop for FileWatcher {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Collect touched paths and emit each once it has been quiet for `debounce`.
+fn debounce_loop(signals: Receiver<Signal>, events: Sender<WatchEvent>, debounce: Duration) {
+    let mut pending: HashMap<PathBuf, Instant> = HashMap::new();
+
+    loop {
+        let timeout = pending
+            .values()
+            .map(|touched| (*touched + debounce).saturating_duration_since(Instant::now()))
+            .min()
+            .unwrap_or(Duration::from_secs(3600));
+
+        match signals.recv_timeout(timeout) {
+            Ok(Signal::Touched(path)) => {
+                pending.insert(path, Instant::now());
+            }
+            Ok(Signal::Stop) | Err(RecvTimeoutError::Disconnected) => return,
+            Err(RecvTimeoutError::Timeout) => {}
+        }
+
+        let now = Instant::now();
+        let settled: Vec<PathBuf> = pending
+            .iter()
+            .filter(|(_, touched)| now.duration_since(**touched) >= debounce)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            pending.remove(&path);
+            if events.send(classify(&path)).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+fn classify(path: &Path) -> WatchEvent {
+    if path.exists() {
+        WatchEvent::Changed(path.to_path_buf())
+    } else {
+        WatchEvent::Removed(path.to_path_buf())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn wait_for_events(watcher: &FileWatcher, timeout: Duration) -> Vec<WatchEvent> {
+        let deadline = Instant::now() + timeout;
+        let mut events = Vec::new();
+        while Instant::now() < deadline {
+            events.extend(watcher.drain());
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        events
+    }
+
+    #[test]
+    fn test_burst_of_writes_is_reported_once() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(temp_dir.path()).unwrap();
+        let watcher =
+            FileWatcher::with_debounce(vec![root.clone()], Duration::from_millis(300)).unwrap();
+
+        let file = root.join("notes.md");
+        for i in 0..5 {
+            fs::write(&file, format!("draft {}", i)).unwrap();
+            std::thread::sleep(Duration::from_millis(20));
+        }
+
+        let events = wait_for_events(&watcher, Duration::from_millis(1500));
+        let changes = events
+            .iter()
+            .filter(|e| **e == WatchEvent::Changed(file.clone()))
+            .count();
+        assert_eq!(changes, 1);
+    }
+
+    #[test]
+    fn test_apply_indexes_and_removes_files() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let root = fs::canonicalize(temp_dir.path()).unwrap();
+        let mut watcher =
+            FileWatcher::with_debounce(vec![root.clone()], Duration::from_millis(100)).unwrap();
+        let service = SearchService::new(None);
+        let indexer = Indexer::new();
+
+        let file = root.join("kernel.txt");
+        fs::write(&file, "scheduler internals").unwrap();
+        std::thread::sleep(Duration::from_millis(600));
+        watcher.apply(&service, &indexer);
+        assert_eq!(service.doc_count(), 1);
+
+        fs::remove_file(&file).unwrap();
+        std::thread::sleep(Duration::from_millis(600));
+        watcher.apply(&service, &indexer);
+        assert_eq!(service.doc_count(), 0);
+
+        watcher.stop();
+    }
+}
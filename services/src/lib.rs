@@ -1,26 +1,75 @@
 use std::collections::HashSet;
 
+use serde::{Deserialize, Serialize};
 use thiserror::Error;
-use tracing::info;
+use tracing::{info, warn};
 
 pub type ServiceResult<T> = Result<T, ServiceError>;
 
 pub trait Service {
     fn name(&self) -> &str;
     fn start(&mut self) -> ServiceResult<()>;
+
+    /// Release whatever `start` acquired.
+    fn stop(&mut self) -> ServiceResult<()> {
+        Ok(())
+    }
+
+    /// How a running service is doing.
+    fn health(&self) -> ServiceHealth {
+        ServiceHealth::Healthy
+    }
+
+    /// Services that must be running before this one starts.
+    fn dependencies(&self) -> Vec<String> {
+        Vec::new()
+    }
+}
+
+/// Health reported by a running service.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServiceHealth {
+    Healthy,
+    Degraded(String),
+    Unhealthy(String),
+}
+
+/// Lifecycle state tracked by the registry.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ServiceState {
+    Stopped,
+    Running,
+    Failed,
+}
+
+/// One row of [`ServiceRegistry::status`].
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ServiceStatus {
+    pub name: String,
+    pub state: ServiceState,
+    /// Only known while the service is running.
+    pub health: Option<ServiceHealth>,
+    pub last_error: Option<String>,
+}
+
+struct Entry {
+    service: Box<dyn Service + Send>,
+    state: ServiceState,
+    last_error: Option<String>,
 }
 
 pub struct ServiceRegistry {
-    started: HashSet<String>,
-    services: Vec<Box<dyn Service + Send>>, // simple registry for now
+    services: Vec<Entry>,
+    /// Names of running services in the order they started.
+    start_order: Vec<String>,
 }
 
 #[allow(clippy::derivable_impls)]
 impl Default for ServiceRegistry {
     fn default() -> Self {
         Self {
-            started: HashSet::new(),
             services: Vec::new(),
+            start_order: Vec::new(),
         }
     }
 }
@@ -31,27 +80,382 @@ impl ServiceRegistry {
     }
 
     pub fn register(&mut self, service: Box<dyn Service + Send>) -> ServiceResult<()> {
-        if self.started.contains(service.name()) {
-            return Err(ServiceError::AlreadyStarted(service.name().into()));
+        match self.position(service.name()) {
+            Some(i) if self.services[i].state == ServiceState::Running => {
+                Err(ServiceError::AlreadyStarted(service.name().into()))
+            }
+            Some(_) => Err(ServiceError::AlreadyRegistered(service.name().into())),
+            None => {
+                self.services.push(Entry {
+                    service,
+                    state: ServiceState::Stopped,
+                    last_error: None,
+                });
+                Ok(())
+            }
         }
-        self.services.push(service);
-        Ok(())
     }
 
+    /// Start every service that is not running, dependencies first.
+    ///
+    /// Missing or cyclic dependencies are reported before anything starts;
+    /// otherwise the first service that fails to start stops the run.
     pub fn start_all(&mut self) -> ServiceResult<()> {
-        for service in self.services.iter_mut() {
-            info!(service = service.name(), "Starting service");
-            service.start()?;
-            self.started.insert(service.name().into());
+        for name in self.dependency_order()? {
+            let i = self.position(&name).expect("ordered names are registered");
+            if self.services[i].state != ServiceState::Running {
+                self.start_entry(i)?;
+            }
         }
         Ok(())
     }
+
+    /// Stop running services in reverse start order. Every service is asked
+    /// to stop even if an earlier one fails; the first error is returned.
+    pub fn stop_all(&mut self) -> ServiceResult<()> {
+        let mut result = Ok(());
+        for name in self.start_order.clone().into_iter().rev() {
+            if let Err(e) = self.stop(&name) {
+                warn!(service = %name, "Failed to stop service: {}", e);
+                if result.is_ok() {
+                    result = Err(e);
+                }
+            }
+        }
+        result
+    }
+
+    /// Stop `name` if it is running. Does not stop services depending on it.
+    pub fn stop(&mut self, name: &str) -> ServiceResult<()> {
+        let i = self
+            .position(name)
+            .ok_or_else(|| ServiceError::NotFound(name.into()))?;
+        if self.services[i].state != ServiceState::Running {
+            return Ok(());
+        }
+
+        info!(service = name, "Stopping service");
+        self.start_order.retain(|n| n != name);
+        let entry = &mut self.services[i];
+        match entry.service.stop() {
+            Ok(()) => {
+                entry.state = ServiceState::Stopped;
+                Ok(())
+            }
+            Err(e) => {
+                entry.state = ServiceState::Failed;
+                entry.last_error = Some(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    /// Stop `name` if running, then start it again. Its dependencies must
+    /// already be running.
+    pub fn restart(&mut self, name: &str) -> ServiceResult<()> {
+        self.stop(name)?;
+        let i = self
+            .position(name)
+            .ok_or_else(|| ServiceError::NotFound(name.into()))?;
+        self.start_entry(i)
+    }
+
+    /// State of every registered service, in registration order.
+    pub fn status(&self) -> Vec<ServiceStatus> {
+        self.services
+            .iter()
+            .map(|entry| ServiceStatus {
+                name: entry.service.name().to_string(),
+                state: entry.state,
+                health: (entry.state == ServiceState::Running).then(|| entry.service.health()),
+                last_error: entry.last_error.clone(),
+            })
+            .collect()
+    }
+
+    fn position(&self, name: &str) -> Option<usize> {
+        self.services.iter().position(|e| e.service.name() == name)
+    }
+
+    fn start_entry(&mut self, i: usize) -> ServiceResult<()> {
+        let name = self.services[i].service.name().to_string();
+        let waiting_on = self.services[i]
+            .service
+            .dependencies()
+            .into_iter()
+            .find(|dep| {
+                self.position(dep)
+                    .is_none_or(|d| self.services[d].state != ServiceState::Running)
+            });
+
+        let result = match waiting_on {
+            Some(dependency) => Err(ServiceError::DependencyNotRunning {
+                service: name.clone(),
+                dependency,
+            }),
+            None => {
+                info!(service = %name, "Starting service");
+                self.services[i].service.start()
+            }
+        };
+
+        let entry = &mut self.services[i];
+        match result {
+            Ok(()) => {
+                entry.state = ServiceState::Running;
+                entry.last_error = None;
+                self.start_order.push(name);
+                Ok(())
+            }
+            Err(e) => {
+                warn!(service = %name, "Service failed to start: {}", e);
+                entry.state = ServiceState::Failed;
+                entry.last_error = Some(e.to_string());
+                Err(e)
+            }
+        }
+    }
+
+    /// Registered service names with each one after its dependencies.
+    fn dependency_order(&self) -> ServiceResult<Vec<String>> {
+        fn visit(
+            registry: &ServiceRegistry,
+            name: &str,
+            path: &mut Vec<String>,
+            done: &mut HashSet<String>,
+            order: &mut Vec<String>,
+        ) -> ServiceResult<()> {
+            if done.contains(name) {
+                return Ok(());
+            }
+            if let Some(start) = path.iter().position(|n| n == name) {
+                let mut cycle = path[start..].to_vec();
+                cycle.push(name.to_string());
+                return Err(ServiceError::DependencyCycle(cycle.join(" -> ")));
+            }
+
+            let i = registry.position(name).expect("caller checks registration");
+            path.push(name.to_string());
+            for dependency in registry.services[i].service.dependencies() {
+                if registry.position(&dependency).is_none() {
+                    return Err(ServiceError::MissingDependency {
+                        service: name.to_string(),
+                        dependency,
+                    });
+                }
+                visit(registry, &dependency, path, done, order)?;
+            }
+            path.pop();
+
+            done.insert(name.to_string());
+            order.push(name.to_string());
+            Ok(())
+        }
+
+        let mut order = Vec::new();
+        let mut done = HashSet::new();
+        for entry in &self.services {
+            visit(
+                self,
+                entry.service.name(),
+                &mut Vec::new(),
+                &mut done,
+                &mut order,
+            )?;
+        }
+        Ok(order)
+    }
 }
 
 #[derive(Debug, Error)]
 pub enum ServiceError {
     #[error("service already started: {0}")]
     AlreadyStarted(String),
+    #[error("service already registered: {0}")]
+    AlreadyRegistered(String),
+    #[error("service not found: {0}")]
+    NotFound(String),
+    #[error("service {service} depends on unregistered service {dependency}")]
+    MissingDependency { service: String, dependency: String },
+    #[error("service {service} needs {dependency} to be running")]
+    DependencyNotRunning { service: String, dependency: String },
+    #[error("service dependency cycle: {0}")]
+    DependencyCycle(String),
     #[error("service failed: {0}")]
     Failed(String),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Records lifecycle calls into a shared log.
+    struct TestService {
+        name: &'static str,
+        dependencies: Vec<&'static str>,
+        log: Arc<Mutex<Vec<String>>>,
+        failures_left: usize,
+    }
+
+    impl TestService {
+        fn new(
+            name: &'static str,
+            dependencies: &[&'static str],
+            log: &Arc<Mutex<Vec<String>>>,
+        ) -> Box<Self> {
+            Box::new(Self {
+                name,
+                dependencies: dependencies.to_vec(),
+                log: Arc::clone(log),
+                failures_left: 0,
+            })
+        }
+    }
+
+    impl Service for TestService {
+        fn name(&self) -> &str {
+            self.name
+        }
+
+        fn start(&mut self) -> ServiceResult<()> {
+            if self.failures_left > 0 {
+                self.failures_left -= 1;
+                return Err(ServiceError::Failed(format!("{} wedged", self.name)));
+            }
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("start {}", self.name));
+            Ok(())
+        }
+
+        fn stop(&mut self) -> ServiceResult<()> {
+            self.log.lock().unwrap().push(format!("stop {}", self.name));
+            Ok(())
+        }
+
+        fn dependencies(&self) -> Vec<String> {
+            self.dependencies.iter().map(|d| d.to_string()).collect()
+        }
+    }
+
+    #[test]
+    fn test_starts_dependencies_first_and_stops_in_reverse() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = ServiceRegistry::new();
+        registry
+            .register(TestService::new("llm", &["search"], &log))
+            .unwrap();
+        registry
+            .register(TestService::new("search", &["filesystem"], &log))
+            .unwrap();
+        registry
+            .register(TestService::new("filesystem", &[], &log))
+            .unwrap();
+
+        registry.start_all().unwrap();
+        registry.stop_all().unwrap();
+        assert_eq!(
+            *log.lock().unwrap(),
+            vec![
+                "start filesystem",
+                "start search",
+                "start llm",
+                "stop llm",
+                "stop search",
+                "stop filesystem",
+            ]
+        );
+        assert!(registry
+            .status()
+            .iter()
+            .all(|s| s.state == ServiceState::Stopped));
+    }
+
+    #[test]
+    fn test_cycles_and_missing_dependencies_fail_before_starting() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = ServiceRegistry::new();
+        registry
+            .register(TestService::new("plain", &[], &log))
+            .unwrap();
+        registry
+            .register(TestService::new("a", &["b"], &log))
+            .unwrap();
+        registry
+            .register(TestService::new("b", &["a"], &log))
+            .unwrap();
+
+        let err = registry.start_all().unwrap_err();
+        assert!(matches!(err, ServiceError::DependencyCycle(ref c) if c == "a -> b -> a"));
+        assert!(log.lock().unwrap().is_empty());
+
+        let mut registry = ServiceRegistry::new();
+        registry
+            .register(TestService::new("search", &["index"], &log))
+            .unwrap();
+        assert!(matches!(
+            registry.start_all(),
+            Err(ServiceError::MissingDependency { .. })
+        ));
+    }
+
+    #[test]
+    fn test_restart_after_start_failure() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = ServiceRegistry::new();
+        let mut devices = TestService::new("devices", &[], &log);
+        devices.failures_left = 1;
+        registry.register(devices).unwrap();
+        registry
+            .register(TestService::new("usb", &["devices"], &log))
+            .unwrap();
+
+        assert!(registry.start_all().is_err());
+        let status = registry.status();
+        assert_eq!(status[0].state, ServiceState::Failed);
+        assert_eq!(
+            status[0].last_error.as_deref(),
+            Some("service failed: devices wedged")
+        );
+        assert!(matches!(
+            registry.restart("usb"),
+            Err(ServiceError::DependencyNotRunning { .. })
+        ));
+
+        registry.restart("devices").unwrap();
+        registry.restart("usb").unwrap();
+        let status = registry.status();
+        assert_eq!(status[0].state, ServiceState::Running);
+        assert_eq!(status[0].last_error, None);
+        assert_eq!(status[1].health, Some(ServiceHealth::Healthy));
+        assert!(matches!(
+            registry.restart("bogus"),
+            Err(ServiceError::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_already_started_is_enforced() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let mut registry = ServiceRegistry::new();
+        registry
+            .register(TestService::new("search", &[], &log))
+            .unwrap();
+        assert!(matches!(
+            registry.register(TestService::new("search", &[], &log)),
+            Err(ServiceError::AlreadyRegistered(_))
+        ));
+
+        registry.start_all().unwrap();
+        assert!(matches!(
+            registry.register(TestService::new("search", &[], &log)),
+            Err(ServiceError::AlreadyStarted(_))
+        ));
+
+        // A second start_all leaves running services alone
+        registry.start_all().unwrap();
+        assert_eq!(*log.lock().unwrap(), vec!["start search"]);
+    }
+}
@@ -0,0 +1,175 @@
+//! Queue of host file operations and installs waiting for the user's
+//! consent.
+
+use crate::file_access::{FileOperation, HostFileAccessRequest};
+use crate::{Result, ToolError};
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+/// A queued request and when it was queued.
+struct PendingApproval<R> {
+    request: R,
+    queued_at: Instant,
+}
+
+/// A request taken out of the [`ApprovalBroker`].
+#[derive(Debug, Clone)]
+pub enum Approval<R = HostFileAccessRequest> {
+    /// Still within its TTL and safe to run
+    Valid(R),
+    /// Waited longer than the TTL and must not run
+    Expired(R),
+}
+
+/// Holds operations that need user approval until they are approved,
+/// denied, or expire after `ttl`.
+pub struct ApprovalBroker<R = HostFileAccessRequest> {
+    pending: HashMap<String, PendingApproval<R>>,
+    ttl: Duration,
+    next_id: u64,
+    prefix: &'static str,
+}
+
+impl ApprovalBroker {
+    /// Whether `operation` changes the host filesystem and so needs consent.
+    pub fn needs_approval(operation: FileOperation) -> bool {
+        matches!(
+            operation,
+            FileOperation::Write
+                | FileOperation::Move
+                | FileOperation::Copy
+                | FileOperation::Delete
+        )
+    }
+}
+
+impl<R: Clone> ApprovalBroker<R> {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            pending: HashMap::new(),
+            ttl,
+            next_id: 0,
+            prefix: "approval",
+        }
+    }
+
+    /// Start tokens with `prefix` so they can't be mistaken for another
+    /// broker's.
+    pub fn with_prefix(mut self, prefix: &'static str) -> Self {
+        self.prefix = prefix;
+        self
+    }
+
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    pub fn set_ttl(&mut self, ttl: Duration) {
+        self.ttl = ttl;
+    }
+
+    /// Queue a request and return the token that approves or denies it.
+    pub fn queue(&mut self, request: R) -> String {
+        self.next_id += 1;
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.subsec_nanos())
+            .unwrap_or_default();
+        let token = format!("{}-{}-{:08x}", self.prefix, self.next_id, nanos);
+        self.pending.insert(
+            token.clone(),
+            PendingApproval {
+                request,
+                queued_at: Instant::now(),
+            },
+        );
+        token
+    }
+
+    /// Whether a request is queued under `token`.
+    pub fn contains(&self, token: &str) -> bool {
+        self.pending.contains_key(token)
+    }
+
+    /// Remove a request from the queue so it can be run or cancelled.
+    pub fn take(&mut self, token: &str) -> Result<Approval<R>> {
+        let pending = self.pending.remove(token).ok_or_else(|| {
+            ToolError::PermissionDenied(format!("Unknown approval token: {}", token))
+        })?;
+
+        if pending.queued_at.elapsed() > self.ttl {
+            Ok(Approval::Expired(pending.request))
+        } else {
+            Ok(Approval::Valid(pending.request))
+        }
+    }
+
+    /// Drop every expired request and return them for auditing.
+    pub fn drain_expired(&mut self) -> Vec<R> {
+        let ttl = self.ttl;
+        let expired: Vec<String> = self
+            .pending
+            .iter()
+            .filter(|(_, p)| p.queued_at.elapsed() > ttl)
+            .map(|(token, _)| token.clone())
+            .collect();
+        expired
+            .into_iter()
+            .filter_map(|token| self.pending.remove(&token))
+            .map(|p| p.request)
+            .collect()
+    }
+
+    /// Requests still waiting for a decision, oldest first.
+    pub fn pending(&self) -> Vec<(String, R)> {
+        let mut pending: Vec<_> = self.pending.iter().collect();
+        pending.sort_by_key(|(_, p)| p.queued_at);
+        pending
+            .into_iter()
+            .map(|(token, p)| (token.clone(), p.request.clone()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn delete_request() -> HostFileAccessRequest {
+        HostFileAccessRequest {
+            operation: FileOperation::Delete,
+            path: PathBuf::from("/tmp/file.txt"),
+            dest_path: None,
+            requires_approval: true,
+        }
+    }
+
+    #[test]
+    fn test_take_returns_queued_request_once() {
+        let mut broker = ApprovalBroker::new(Duration::from_secs(60));
+        let token = broker.queue(delete_request());
+        assert_eq!(broker.pending().len(), 1);
+
+        let Approval::Valid(request) = broker.take(&token).unwrap() else {
+            panic!("request should not have expired");
+        };
+        assert_eq!(request.operation, FileOperation::Delete);
+        assert!(broker.take(&token).is_err());
+        assert!(broker.pending().is_empty());
+    }
+
+    #[test]
+    fn test_expired_requests_are_rejected() {
+        let mut broker = ApprovalBroker::new(Duration::ZERO);
+        let token = broker.queue(delete_request());
+        std::thread::sleep(Duration::from_millis(5));
+
+        assert!(matches!(broker.take(&token), Ok(Approval::Expired(_))));
+
+        broker.queue(delete_request());
+        std::thread::sleep(Duration::from_millis(5));
+        assert_eq!(broker.drain_expired().len(), 1);
+        assert!(broker.pending().is_empty());
+    }
+}
@@ -0,0 +1,100 @@
+use crate::{Result, ToolResult};
+use lucastra_calculator::Calculator;
+use serde::Serialize;
+use tracing::info;
+
+/// Longest expression the tool evaluates, in bytes.
+pub const MAX_EXPRESSION_LEN: usize = 256;
+
+/// Output of a successful calculation.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Calculation {
+    /// The expression as evaluated, evenly spaced.
+    pub expression: String,
+    pub result: f64,
+}
+
+/// Arithmetic tool so answers don't rely on the model doing math.
+///
+/// Every call starts from a fresh [`Calculator`], so variables assigned in
+/// one expression are not visible to the next.
+#[derive(Debug, Default)]
+pub struct CalculatorTool;
+
+impl CalculatorTool {
+    pub fn new() -> Self {
+        Self
+    }
+
+    pub fn execute(&self, expression: &str) -> Result<ToolResult> {
+        info!("Executing calculate tool: expression='{}'", expression);
+
+        if expression.len() > MAX_EXPRESSION_LEN {
+            return Ok(ToolResult::failure(
+                "calculate",
+                format!(
+                    "Expression is {} bytes; the limit is {}",
+                    expression.len(),
+                    MAX_EXPRESSION_LEN
+                ),
+            ));
+        }
+
+        let mut calculator = Calculator::new();
+        let calculation = calculator.eval(expression).and_then(|result| {
+            Ok(Calculation {
+                expression: calculator.normalize(expression)?,
+                result,
+            })
+        });
+        match calculation {
+            Ok(calculation) if !calculation.result.is_finite() => Ok(ToolResult::failure(
+                "calculate",
+                format!("{} is not a finite number", calculation.expression),
+            )),
+            Ok(calculation) => Ok(ToolResult::success(
+                "calculate",
+                serde_json::to_string(&calculation)?,
+            )),
+            Err(e) => Ok(ToolResult::failure("calculate", e.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_result_and_normalized_expression() {
+        let result = CalculatorTool::new().execute("2*(3+4)^2").unwrap();
+        assert!(result.success);
+        assert_eq!(result.tool, "calculate");
+        assert_eq!(
+            result.output,
+            r#"{"expression":"2 * (3 + 4) ^ 2","result":98.0}"#
+        );
+    }
+
+    #[test]
+    fn test_rejects_long_and_non_finite_expressions() {
+        let tool = CalculatorTool::new();
+        let long = vec!["1"; MAX_EXPRESSION_LEN].join("+");
+        let result = tool.execute(&long).unwrap();
+        assert!(!result.success);
+        assert!(result.output.contains("limit"));
+
+        let result = tool.execute("10^400").unwrap();
+        assert!(!result.success);
+        assert!(result.output.contains("not a finite number"));
+    }
+
+    #[test]
+    fn test_calls_do_not_share_variables() {
+        let tool = CalculatorTool::new();
+        assert!(tool.execute("x = 2").unwrap().success);
+        let result = tool.execute("x * 3").unwrap();
+        assert!(!result.success);
+        assert_eq!(result.output, "Undefined variable: x");
+    }
+}
@@ -0,0 +1,87 @@
+use crate::write::check_sandbox;
+use crate::{Result, ToolResult};
+use lucastra_fs::FilesystemManager;
+use tracing::info;
+
+/// Delete file tool implementation, sandboxed like [`crate::write::WriteTool`].
+pub struct DeleteTool<'a> {
+    filesystem: &'a mut FilesystemManager,
+    sandboxed: bool,
+}
+
+impl<'a> DeleteTool<'a> {
+    pub fn new(filesystem: &'a mut FilesystemManager, sandboxed: bool) -> Self {
+        Self {
+            filesystem,
+            sandboxed,
+        }
+    }
+
+    pub fn execute(&mut self, path: &str) -> Result<ToolResult> {
+        info!("Executing delete tool: path='{}'", path);
+
+        if self.sandboxed {
+            if let Err(reason) = check_sandbox(self.filesystem, path) {
+                return Ok(ToolResult::failure("delete", reason));
+            }
+        }
+
+        let size = self.filesystem.stat(path).map(|entry| entry.size).ok();
+        match self.filesystem.delete_file(path) {
+            Ok(()) => Ok(ToolResult::success(
+                "delete",
+                format!("Deleted '{}' ({} bytes)", path, size.unwrap_or(0)),
+            )),
+            Err(e) => {
+                let error = format!("Failed to delete file '{}': {}", path, e);
+                Ok(ToolResult::failure("delete", error))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_hal::filesystem::{HostFileSystem, MockFileSystem};
+
+    #[test]
+    fn test_delete_existing_file() {
+        let mut fs = FilesystemManager::new();
+        fs.mount("/mnt/root", MockFileSystem::new()).unwrap();
+        fs.write_file("/mnt/root/old.txt", b"stale").unwrap();
+
+        let result = DeleteTool::new(&mut fs, true)
+            .execute("/mnt/root/old.txt")
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("5 bytes"));
+        assert!(fs.read_file("/mnt/root/old.txt").is_err());
+    }
+
+    #[test]
+    fn test_delete_missing_file_is_failure_result() {
+        let mut fs = FilesystemManager::new();
+        fs.mount("/mnt/root", MockFileSystem::new()).unwrap();
+
+        let result = DeleteTool::new(&mut fs, true).execute("/mnt/root/missing.txt");
+        let result = result.expect("missing file should not be an Err");
+        assert!(!result.success);
+        assert_eq!(result.tool, "delete");
+    }
+
+    #[test]
+    fn test_sandbox_blocks_host_mount() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::write(temp_dir.path().join("keep.txt"), b"keep").unwrap();
+        let mut fs = FilesystemManager::new();
+        fs.mount("/mnt/host", HostFileSystem::new(temp_dir.path()))
+            .unwrap();
+
+        let result = DeleteTool::new(&mut fs, true)
+            .execute("/mnt/host/keep.txt")
+            .unwrap();
+        assert!(!result.success);
+        assert!(temp_dir.path().join("keep.txt").exists());
+    }
+}
@@ -0,0 +1,334 @@
+use crate::{Result, ToolError, ToolResult};
+use lucastra_browser::{host_of, Body, BrowserError, FetchResult, HtmlParser, HttpClient};
+use lucastra_config::SecurityConfig;
+use tracing::info;
+
+/// Bytes of page text returned unless the call asks for another amount.
+pub const DEFAULT_FETCH_BYTES: usize = 16 * 1024;
+
+/// Web page tool.
+///
+/// Fetches a URL on one of `security.allowed_domains` and returns the
+/// page's title and main text, headed by the URL it ended up at after
+/// redirects so answers can cite it. Anything other than HTML is described
+/// by its type and size only.
+pub struct FetchTool {
+    security: SecurityConfig,
+    client: HttpClient,
+}
+
+impl FetchTool {
+    pub fn new(security: &SecurityConfig) -> Self {
+        Self {
+            security: security.clone(),
+            client: HttpClient::new(),
+        }
+    }
+
+    pub fn with_client(mut self, client: HttpClient) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Fetch `url` and return at most `max_bytes` of its text.
+    pub fn execute(&self, url: &str, max_bytes: Option<usize>) -> Result<ToolResult> {
+        info!("Executing fetch tool: url='{}'", url);
+
+        // Every hop is checked, so a redirect can't leave the allowed domains
+        let security = self.security.clone();
+        let client = self
+            .client
+            .clone()
+            .with_hop_check(move |hop| check(&security, hop).map_err(|e| e.to_string()));
+        let fetched = match client.get(url) {
+            Ok(fetched) => fetched,
+            Err(BrowserError::Blocked(reason)) => {
+                return Ok(ToolResult::failure("fetch", reason));
+            }
+            Err(e) => {
+                let error = format!("Failed to fetch '{}': {}", url, e);
+                return Ok(ToolResult::failure("fetch", error));
+            }
+        };
+        if !fetched.is_success() {
+            return Ok(ToolResult::failure(
+                "fetch",
+                format!("HTTP {} from {}", fetched.status, fetched.final_url),
+            ));
+        }
+
+        let output = describe(&fetched, max_bytes.unwrap_or(DEFAULT_FETCH_BYTES));
+        Ok(ToolResult::success("fetch", output))
+    }
+}
+
+/// Whether `security` lets the tool request `url`.
+fn check(security: &SecurityConfig, url: &str) -> Result<()> {
+    if !security.allow_network {
+        return Err(ToolError::PermissionDenied(
+            "network access is disabled".to_string(),
+        ));
+    }
+    let host = host_of(url).unwrap_or_default();
+    if !security.allows_domain(&host) {
+        return Err(ToolError::PermissionDenied(format!(
+            "'{}' is not in security.allowed_domains",
+            host
+        )));
+    }
+    Ok(())
+}
+
+/// Tool output for a successful fetch: the final URL and status, then the
+/// page's title and text or, for other content, its type and size.
+fn describe(fetched: &FetchResult, max_bytes: usize) -> String {
+    let mut output = format!("URL: {}\nStatus: {}\n", fetched.final_url, fetched.status);
+    let content_type = fetched.content_type.as_deref();
+    let html = match &fetched.body {
+        Body::Text(html) if content_type.is_none_or(is_html) => html,
+        body => {
+            let size = match body {
+                Body::Text(text) => text.len() as u64,
+                Body::Binary { size } => *size,
+            };
+            output.push_str(&format!(
+                "Content-Type: {}\nSize: {} bytes",
+                content_type.unwrap_or("unknown"),
+                size
+            ));
+            return output;
+        }
+    };
+
+    let content = HtmlParser::parse(html);
+    let text = if content.main_text.is_empty() {
+        &content.text
+    } else {
+        &content.main_text
+    };
+    output.push_str(&format!("Title: {}\n\n", content.title));
+    let shown = truncate(text, max_bytes);
+    output.push_str(shown);
+    if shown.len() < text.len() {
+        output.push_str(&format!(
+            "\n[truncated: first {} of {} bytes of text]",
+            shown.len(),
+            text.len()
+        ));
+    }
+    output
+}
+
+/// Whether a `Content-Type` value is an HTML page
+fn is_html(content_type: &str) -> bool {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    matches!(mime.as_str(), "text/html" | "application/xhtml+xml")
+}
+
+/// Longest prefix of `text` within `max_bytes` that ends on a character
+/// boundary.
+fn truncate(text: &str, max_bytes: usize) -> &str {
+    if text.len() <= max_bytes {
+        return text;
+    }
+    let mut end = max_bytes;
+    while !text.is_char_boundary(end) {
+        end -= 1;
+    }
+    &text[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    const PAGE: &str = "<html><head><title>Release notes</title></head>\
+        <body><article><p>Version 2 adds tabs.</p></article></body></html>";
+
+    /// Serve `routes` as (path, status, extra headers, content type, body)
+    /// on a local port until the test ends. Returns the base URL.
+    fn serve(routes: Vec<(&'static str, u16, String, &'static str, String)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        std::thread::spawn(move || {
+            for stream in listener.incoming() {
+                let Ok(mut stream) = stream else {
+                    continue;
+                };
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut head = String::new();
+                let mut line = String::new();
+                while reader.read_line(&mut line).unwrap_or(0) > 0 && line != "\r\n" {
+                    head.push_str(&line);
+                    line.clear();
+                }
+                let path = head.split_whitespace().nth(1).unwrap_or("/").to_string();
+                let (status, headers, content_type, body) = routes
+                    .iter()
+                    .find(|(route, ..)| *route == path)
+                    .map(|(_, status, headers, content_type, body)| {
+                        (*status, headers.as_str(), *content_type, body.as_str())
+                    })
+                    .unwrap_or((404, "", "text/html", "<title>Not Found</title>"));
+                let _ = write!(
+                    stream,
+                    "HTTP/1.1 {} X\r\nContent-Type: {}\r\n{}Content-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    content_type,
+                    headers,
+                    body.len(),
+                    body
+                );
+            }
+        });
+        base
+    }
+
+    fn tool(allowed_domains: &[&str]) -> FetchTool {
+        FetchTool::new(&SecurityConfig {
+            allowed_domains: allowed_domains.iter().map(|d| d.to_string()).collect(),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_allowed_domain_returns_title_text_and_final_url() {
+        let base = serve(vec![
+            (
+                "/old",
+                301,
+                "Location: /notes\r\n".to_string(),
+                "text/html",
+                String::new(),
+            ),
+            (
+                "/notes",
+                200,
+                String::new(),
+                "text/html; charset=utf-8",
+                PAGE.to_string(),
+            ),
+        ]);
+
+        let result = tool(&["127.0.0.1"])
+            .execute(&format!("{}/old", base), None)
+            .unwrap();
+        assert!(result.success, "{}", result.output);
+        assert_eq!(
+            result.output,
+            format!(
+                "URL: {}/notes\nStatus: 200\nTitle: Release notes\n\nVersion 2 adds tabs.",
+                base
+            )
+        );
+    }
+
+    #[test]
+    fn test_disallowed_domain_is_denied() {
+        let base = serve(vec![(
+            "/notes",
+            200,
+            String::new(),
+            "text/html",
+            PAGE.to_string(),
+        )]);
+        let url = format!("{}/notes", base);
+
+        for tool in [tool(&[]), tool(&["example.com"])] {
+            let result = tool.execute(&url, None).unwrap();
+            assert!(!result.success);
+            assert!(result.output.starts_with("Permission denied:"));
+        }
+
+        let mut offline = tool(&["*"]);
+        offline.security.allow_network = false;
+        let result = offline.execute(&url, None).unwrap();
+        assert!(!result.success);
+        assert!(result.output.contains("network access is disabled"));
+    }
+
+    #[test]
+    fn test_redirect_off_the_allowed_domains_is_denied() {
+        // Never answered: any connection to it stays queued and shows up
+        // in `accept` below
+        let target = TcpListener::bind("127.0.0.1:0").unwrap();
+        target.set_nonblocking(true).unwrap();
+        let elsewhere = format!("http://localhost:{}", target.local_addr().unwrap().port());
+        let base = serve(vec![(
+            "/away",
+            302,
+            format!("Location: {}/notes\r\n", elsewhere),
+            "text/html",
+            String::new(),
+        )]);
+
+        let result = tool(&["127.0.0.1"])
+            .execute(&format!("{}/away", base), None)
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.output.contains("'localhost' is not in"));
+        assert_eq!(
+            target.accept().map(|_| ()).unwrap_err().kind(),
+            std::io::ErrorKind::WouldBlock,
+            "the disallowed host was contacted"
+        );
+    }
+
+    #[test]
+    fn test_oversized_text_is_truncated() {
+        let body = format!(
+            "<html><head><title>Log</title></head><body><p>{}</p></body></html>",
+            "é".repeat(100)
+        );
+        let base = serve(vec![("/log", 200, String::new(), "text/html", body)]);
+
+        let result = tool(&["127.0.0.1"])
+            .execute(&format!("{}/log", base), Some(51))
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.ends_with(&format!(
+            "\n\n{}\n[truncated: first 50 of 200 bytes of text]",
+            "é".repeat(25)
+        )));
+    }
+
+    #[test]
+    fn test_non_html_reports_type_and_size_only() {
+        let base = serve(vec![(
+            "/data.json",
+            200,
+            String::new(),
+            "application/json",
+            r#"{"secret":"not shown"}"#.to_string(),
+        )]);
+
+        let result = tool(&["*"])
+            .execute(&format!("{}/data.json", base), None)
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(
+            result.output,
+            format!(
+                "URL: {}/data.json\nStatus: 200\nContent-Type: application/json\nSize: 22 bytes",
+                base
+            )
+        );
+    }
+
+    #[test]
+    fn test_error_status_fails() {
+        let base = serve(vec![]);
+        let result = tool(&["127.0.0.1"])
+            .execute(&format!("{}/missing", base), None)
+            .unwrap();
+        assert!(!result.success);
+        assert_eq!(result.output, format!("HTTP 404 from {}/missing", base));
+    }
+}
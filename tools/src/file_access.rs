@@ -1,10 +1,20 @@
+use chrono::{DateTime, Utc};
+use lucastra_hal::{RemovableMediaDetector, SystemRemovableMedia};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::io::Write;
+use std::io::{BufRead, BufReader, Read, Write};
 use std::path::{Path, PathBuf};
-use std::time::SystemTime;
+use std::sync::{Arc, Mutex};
 use thiserror::Error;
 
+/// Default size at which the audit log is rotated.
+pub const DEFAULT_AUDIT_MAX_BYTES: u64 = 10 * 1024 * 1024;
+
+/// Serializes audit writes within the process so rotation and appends
+/// never interleave.
+static AUDIT_WRITE_LOCK: Mutex<()> = Mutex::new(());
+
 #[derive(Debug, Error)]
 pub enum FileAccessError {
     #[error("Path not in whitelist: {0}")]
@@ -26,7 +36,7 @@ pub enum FileAccessError {
 pub type FileAccessResult<T> = Result<T, FileAccessError>;
 
 /// File operation types for audit logging and validation
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
 pub enum FileOperation {
     Read,
     Write,
@@ -49,17 +59,42 @@ impl std::fmt::Display for FileOperation {
     }
 }
 
-/// Audit log entry for file operations
+impl std::str::FromStr for FileOperation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "read" => Ok(FileOperation::Read),
+            "write" => Ok(FileOperation::Write),
+            "move" => Ok(FileOperation::Move),
+            "copy" => Ok(FileOperation::Copy),
+            "delete" => Ok(FileOperation::Delete),
+            "list" => Ok(FileOperation::List),
+            other => Err(format!("unknown file operation: {}", other)),
+        }
+    }
+}
+
+/// Audit log entry for file operations and for tool calls denied by the
+/// permission policy
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AuditEntry {
+    /// RFC 3339 time the operation was attempted
     pub timestamp: String,
-    #[serde(rename = "op")]
-    pub operation: FileOperation,
+    /// File operation attempted; `None` for denied tools that touch no files
+    #[serde(rename = "op", default, skip_serializing_if = "Option::is_none")]
+    pub operation: Option<FileOperation>,
     pub source_path: String,
     pub dest_path: Option<String>,
     pub success: bool,
     pub error_msg: Option<String>,
     pub user_approved: bool,
+    /// Tool rejected by the permission policy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool: Option<String>,
+    /// Role the rejected tool call was checked against
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub role: Option<String>,
 }
 
 /// Host file access request (user confirmation required on first access)
@@ -71,12 +106,67 @@ pub struct HostFileAccessRequest {
     pub requires_approval: bool,
 }
 
+/// Resolve `path` to an absolute path with symlinks resolved, even when its
+/// last components don't exist yet (e.g. the target of a write).
+///
+/// The deepest existing ancestor is canonicalized, so a symlink anywhere in
+/// the existing portion is followed before the whitelist check; the missing
+/// components are re-appended and may not contain `..`.
+fn resolve_path(path: &Path) -> FileAccessResult<PathBuf> {
+    let invalid = || FileAccessError::InvalidPath(path.display().to_string());
+    let absolute = std::path::absolute(path).map_err(|_| invalid())?;
+
+    // symlink_metadata so a dangling symlink counts as existing and then
+    // fails to canonicalize, rather than being written through
+    let mut existing = absolute.as_path();
+    let mut missing = Vec::new();
+    while fs::symlink_metadata(existing).is_err() {
+        missing.push(existing.file_name().ok_or_else(invalid)?);
+        existing = existing.parent().ok_or_else(invalid)?;
+    }
+
+    let mut resolved = existing.canonicalize().map_err(|_| invalid())?;
+    for component in missing.iter().rev() {
+        resolved.push(component);
+    }
+    Ok(resolved)
+}
+
+/// Up to `max_bytes` of text file `path`, ending on a character boundary,
+/// and the file's size.
+fn read_text_prefix(path: &Path, max_bytes: usize) -> std::io::Result<(String, u64)> {
+    let file = fs::File::open(path)?;
+    let metadata = file.metadata()?;
+    if metadata.is_dir() {
+        return Err(std::io::Error::other("is a directory"));
+    }
+    let size = metadata.len();
+    let mut bytes = Vec::new();
+    file.take(max_bytes as u64).read_to_end(&mut bytes)?;
+    let not_text = || std::io::Error::new(std::io::ErrorKind::InvalidData, "not a text file");
+    if bytes.contains(&0) {
+        return Err(not_text());
+    }
+    match String::from_utf8(bytes) {
+        Ok(text) => Ok((text, size)),
+        // Cut off in the middle of a character
+        Err(e) if e.utf8_error().error_len().is_none() && size > max_bytes as u64 => {
+            let valid = e.utf8_error().valid_up_to();
+            let mut bytes = e.into_bytes();
+            bytes.truncate(valid);
+            Ok((String::from_utf8(bytes).map_err(|_| not_text())?, size))
+        }
+        Err(_) => Err(not_text()),
+    }
+}
+
 /// Validator for file access against whitelist
 pub struct FileAccessValidator {
     allowed_dirs: Vec<PathBuf>,
     allow_host_read: bool,
     allow_host_write: bool,
     allow_usb: bool,
+    detector: Arc<dyn RemovableMediaDetector>,
 }
 
 impl FileAccessValidator {
@@ -91,9 +181,16 @@ impl FileAccessValidator {
             allow_host_read,
             allow_host_write,
             allow_usb,
+            detector: Arc::new(SystemRemovableMedia),
         }
     }
 
+    /// Use `detector` to decide which paths are on removable media.
+    pub fn with_detector(mut self, detector: Arc<dyn RemovableMediaDetector>) -> Self {
+        self.detector = detector;
+        self
+    }
+
     /// Validate a path against whitelist
     pub fn validate_path(&self, path: &Path, operation: FileOperation) -> FileAccessResult<()> {
         // Reject write operations if disabled
@@ -114,47 +211,24 @@ impl FileAccessValidator {
         }
 
         // Check if path is in allowed dirs
-        let path = path
-            .canonicalize()
-            .map_err(|_| FileAccessError::InvalidPath(path.display().to_string()))?;
+        let path = resolve_path(path)?;
 
-        let is_allowed = self
-            .allowed_dirs
-            .iter()
-            .any(|allowed| path.starts_with(allowed));
+        let is_allowed = self.allowed_dirs.iter().any(|allowed| {
+            path.starts_with(allowed.canonicalize().unwrap_or_else(|_| allowed.clone()))
+        });
 
         if !is_allowed {
             return Err(FileAccessError::NotWhitelisted(path.display().to_string()));
         }
 
         // Check for USB (removable media)
-        if self.is_removable_media(&path) && !self.allow_usb {
+        if !self.allow_usb && self.detector.is_removable(&path) {
             return Err(FileAccessError::UsbNotAllowed);
         }
 
         Ok(())
     }
 
-    /// Check if path is on removable media (USB)
-    #[cfg(target_os = "windows")]
-    fn is_removable_media(&self, path: &Path) -> bool {
-        // Simplified: check if drive letter is in certain ranges or use Windows API
-        // For now, detect common USB mount points
-        if let Some(drive) = path.to_string_lossy().chars().next() {
-            // D-Z typically USB, C is main drive
-            ('D'..='Z').contains(&drive)
-        } else {
-            false
-        }
-    }
-
-    #[cfg(not(target_os = "windows"))]
-    fn is_removable_media(&self, path: &Path) -> bool {
-        // Linux: check /media or /mnt
-        let path_str = path.to_string_lossy();
-        path_str.contains("/media/") || path_str.contains("/mnt/")
-    }
-
     /// Get list of allowed directories
     pub fn allowed_dirs(&self) -> &[PathBuf] {
         &self.allowed_dirs
@@ -173,20 +247,196 @@ impl FileAccessValidator {
     }
 }
 
+/// Criteria for [`AuditLog::query`]. Unset fields match everything.
+#[derive(Debug, Clone, Default)]
+pub struct AuditFilter {
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+    pub operation: Option<FileOperation>,
+    pub success: Option<bool>,
+    pub path_prefix: Option<String>,
+    /// Keep only the most recent `limit` matches
+    pub limit: Option<usize>,
+}
+
+impl AuditFilter {
+    pub fn matches(&self, entry: &AuditEntry) -> bool {
+        if self.since.is_some() || self.until.is_some() {
+            let Ok(time) = DateTime::parse_from_rfc3339(&entry.timestamp) else {
+                return false;
+            };
+            let time = time.with_timezone(&Utc);
+            if self.since.is_some_and(|since| time < since)
+                || self.until.is_some_and(|until| time > until)
+            {
+                return false;
+            }
+        }
+
+        self.operation.is_none_or(|op| entry.operation == Some(op))
+            && self.success.is_none_or(|success| entry.success == success)
+            && self
+                .path_prefix
+                .as_deref()
+                .is_none_or(|prefix| entry.source_path.starts_with(prefix))
+    }
+}
+
+/// Entries returned by [`AuditLog::query`], oldest first.
+#[derive(Debug, Clone, Default)]
+pub struct AuditQueryResult {
+    pub entries: Vec<AuditEntry>,
+    /// Lines that could not be parsed as an [`AuditEntry`]
+    pub skipped: usize,
+}
+
+/// JSONL audit log, one [`AuditEntry`] per line, rotated to `<path>.1` once
+/// it grows past `max_bytes`.
+#[derive(Debug, Clone)]
+pub struct AuditLog {
+    path: PathBuf,
+    max_bytes: u64,
+}
+
+impl AuditLog {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            max_bytes: DEFAULT_AUDIT_MAX_BYTES,
+        }
+    }
+
+    pub fn with_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.max_bytes = max_bytes;
+        self
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    fn rotated_path(&self) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(".1");
+        PathBuf::from(name)
+    }
+
+    /// Append an entry as a single line.
+    pub fn append(&self, entry: &AuditEntry) -> std::io::Result<()> {
+        let mut line = serde_json::to_string(entry)?;
+        line.push('\n');
+
+        let _guard = AUDIT_WRITE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+
+        if let Some(dir) = self.path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        self.rotate_past(self.max_bytes.saturating_sub(line.len() as u64))?;
+
+        // O_APPEND plus a single write keeps lines whole across processes
+        let mut file = fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        file.write_all(line.as_bytes())
+    }
+
+    /// Rotate the log now if it is already past `max_bytes`, e.g. after the
+    /// limit was lowered. Returns whether it was rotated.
+    pub fn rotate_if_full(&self) -> std::io::Result<bool> {
+        let _guard = AUDIT_WRITE_LOCK
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        self.rotate_past(self.max_bytes)
+    }
+
+    /// Move a non-empty log longer than `limit` bytes to `<path>.1`.
+    fn rotate_past(&self, limit: u64) -> std::io::Result<bool> {
+        match fs::metadata(&self.path) {
+            Ok(metadata) if metadata.len() > 0 && metadata.len() > limit => {
+                fs::rename(&self.path, self.rotated_path())?;
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    /// Entries matching `filter` from the rotated and current files.
+    pub fn query(&self, filter: &AuditFilter) -> std::io::Result<AuditQueryResult> {
+        let mut result = AuditQueryResult::default();
+        for path in [self.rotated_path(), self.path.clone()] {
+            let file = match fs::File::open(&path) {
+                Ok(file) => file,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e),
+            };
+
+            for line in BufReader::new(file).lines() {
+                let line = line?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<AuditEntry>(&line) {
+                    Ok(entry) if filter.matches(&entry) => result.entries.push(entry),
+                    Ok(_) => {}
+                    Err(_) => result.skipped += 1,
+                }
+            }
+        }
+
+        if let Some(limit) = filter.limit {
+            let excess = result.entries.len().saturating_sub(limit);
+            result.entries.drain(..excess);
+        }
+        Ok(result)
+    }
+}
+
 /// Performs host file operations with validation and audit logging
 pub struct FileAccessTool {
     validator: FileAccessValidator,
-    audit_path: PathBuf,
+    audit_log: AuditLog,
 }
 
 impl FileAccessTool {
     pub fn new(validator: FileAccessValidator, audit_path: PathBuf) -> Self {
         Self {
             validator,
-            audit_path,
+            audit_log: AuditLog::new(audit_path),
         }
     }
 
+    /// Rotate the audit log once it exceeds `max_bytes`.
+    pub fn with_audit_max_bytes(mut self, max_bytes: u64) -> Self {
+        self.audit_log = self.audit_log.with_max_bytes(max_bytes);
+        self
+    }
+
+    pub fn audit_log(&self) -> &AuditLog {
+        &self.audit_log
+    }
+
+    pub fn validator(&self) -> &FileAccessValidator {
+        &self.validator
+    }
+
+    /// Audit a request the user denied or let expire, without running it.
+    pub fn record_rejected(&self, request: &HostFileAccessRequest, reason: &str) {
+        self.append_audit(AuditEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            operation: Some(request.operation),
+            source_path: request.path.display().to_string(),
+            dest_path: request.dest_path.as_ref().map(|p| p.display().to_string()),
+            success: false,
+            error_msg: Some(reason.to_string()),
+            user_approved: false,
+            tool: None,
+            role: None,
+        });
+    }
+
     pub fn execute(
         &self,
         operation: FileOperation,
@@ -194,13 +444,15 @@ impl FileAccessTool {
         dest_path: Option<&Path>,
     ) -> crate::ToolResult {
         let mut audit = AuditEntry {
-            timestamp: format!("{:?}", SystemTime::now()),
-            operation,
+            timestamp: Utc::now().to_rfc3339(),
+            operation: Some(operation),
             source_path: path.display().to_string(),
             dest_path: dest_path.map(|p| p.display().to_string()),
             success: false,
             error_msg: None,
             user_approved: true,
+            tool: None,
+            role: None,
         };
 
         let result = self.perform(operation, path, dest_path);
@@ -220,6 +472,31 @@ impl FileAccessTool {
         }
     }
 
+    /// The text of file `path`, at most `max_bytes` of it, and the file's
+    /// full size. Validated and audited as a read; binary files are
+    /// rejected.
+    pub fn read_text(&self, path: &Path, max_bytes: usize) -> FileAccessResult<(String, u64)> {
+        let result = self
+            .validator
+            .validate_path(path, FileOperation::Read)
+            .and_then(|()| {
+                read_text_prefix(path, max_bytes)
+                    .map_err(|e| FileAccessError::OperationFailed(e.to_string()))
+            });
+        self.append_audit(AuditEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            operation: Some(FileOperation::Read),
+            source_path: path.display().to_string(),
+            dest_path: None,
+            success: result.is_ok(),
+            error_msg: result.as_ref().err().map(|e| e.to_string()),
+            user_approved: true,
+            tool: None,
+            role: None,
+        });
+        result
+    }
+
     fn perform(
         &self,
         operation: FileOperation,
@@ -235,15 +512,8 @@ impl FileAccessTool {
             FileOperation::Move | FileOperation::Copy | FileOperation::Write
         ) {
             if let Some(dest) = dest_path {
-                if dest.exists() {
-                    self.validator.validate_path(dest, operation)?;
-                } else if let Some(parent) = dest.parent() {
-                    self.validator.validate_path(parent, operation)?;
-                } else {
-                    return Err(FileAccessError::InvalidPath(
-                        "Destination path has no parent".to_string(),
-                    ));
-                }
+                // Validation resolves destinations that don't exist yet
+                self.validator.validate_path(dest, operation)?;
             } else {
                 return Err(FileAccessError::InvalidPath(
                     "Destination path required".to_string(),
@@ -295,18 +565,12 @@ impl FileAccessTool {
     }
 
     fn append_audit(&self, entry: AuditEntry) {
-        if let Some(dir) = self.audit_path.parent() {
-            let _ = fs::create_dir_all(dir);
-        }
-
-        if let Ok(mut file) = std::fs::OpenOptions::new()
-            .create(true)
-            .append(true)
-            .open(&self.audit_path)
-        {
-            if let Ok(line) = serde_json::to_string(&entry) {
-                let _ = writeln!(file, "{}", line);
-            }
+        if let Err(e) = self.audit_log.append(&entry) {
+            tracing::warn!(
+                "Failed to write audit log {}: {}",
+                self.audit_log.path().display(),
+                e
+            );
         }
     }
 }
@@ -314,6 +578,7 @@ impl FileAccessTool {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::SystemTime;
 
     #[test]
     fn test_validator_rejects_write_when_disabled() {
@@ -336,16 +601,59 @@ mod tests {
         assert!(matches!(result, Err(FileAccessError::NotWhitelisted(_))));
     }
 
+    /// Treats everything under `root` as removable.
+    struct FakeDetector {
+        root: PathBuf,
+    }
+
+    impl RemovableMediaDetector for FakeDetector {
+        fn is_removable(&self, path: &Path) -> bool {
+            path.starts_with(&self.root)
+        }
+
+        fn removable_devices(&self) -> Vec<lucastra_hal::RemovableDevice> {
+            Vec::new()
+        }
+    }
+
+    #[test]
+    fn test_validator_uses_detector_for_usb() {
+        let temp = tempfile::tempdir().unwrap();
+        let stick = temp.path().canonicalize().unwrap().join("stick");
+        std::fs::create_dir_all(&stick).unwrap();
+        let detector = Arc::new(FakeDetector {
+            root: stick.clone(),
+        });
+
+        let blocked = FileAccessValidator::new(vec![temp.path().to_path_buf()], true, true, false)
+            .with_detector(detector.clone());
+        assert!(blocked
+            .validate_path(&temp.path().join("local.txt"), FileOperation::Read)
+            .is_ok());
+        assert!(matches!(
+            blocked.validate_path(&stick.join("photo.jpg"), FileOperation::Read),
+            Err(FileAccessError::UsbNotAllowed)
+        ));
+
+        let allowed = FileAccessValidator::new(vec![temp.path().to_path_buf()], true, true, true)
+            .with_detector(detector);
+        assert!(allowed
+            .validate_path(&stick.join("photo.jpg"), FileOperation::Read)
+            .is_ok());
+    }
+
     #[test]
     fn test_audit_entry_serialization() {
         let entry = AuditEntry {
             timestamp: "2024-12-10T10:00:00Z".to_string(),
-            operation: FileOperation::Read,
+            operation: Some(FileOperation::Read),
             source_path: "/home/user/file.txt".to_string(),
             dest_path: None,
             success: true,
             error_msg: None,
             user_approved: true,
+            tool: None,
+            role: None,
         };
 
         let json = serde_json::to_string(&entry).unwrap();
@@ -385,10 +693,43 @@ mod tests {
         let entry: AuditEntry = serde_json::from_str(last_line).unwrap();
 
         assert!(entry.success);
-        assert_eq!(entry.operation, FileOperation::Read);
+        assert_eq!(entry.operation, Some(FileOperation::Read));
         assert_eq!(entry.source_path, file_path.display().to_string());
     }
 
+    #[test]
+    fn test_read_text_is_capped_and_rejects_binary() {
+        let base = temp_base("read_text");
+        fs::write(base.join("notes.txt"), "héllo world").unwrap();
+        fs::write(base.join("blob.bin"), [0x7f, 0x45, 0x4c, 0x46, 0, 1]).unwrap();
+
+        let allowed = vec![base.canonicalize().unwrap()];
+        let validator = FileAccessValidator::new(allowed, true, false, false);
+        let audit_path = base.join("audit.log");
+        let tool = FileAccessTool::new(validator, audit_path.clone());
+
+        let (text, size) = tool.read_text(&base.join("notes.txt"), 64).unwrap();
+        assert_eq!((text.as_str(), size), ("héllo world", 12));
+        // The cap falls inside "é", which is left out
+        let (text, _) = tool.read_text(&base.join("notes.txt"), 2).unwrap();
+        assert_eq!(text, "h");
+        assert!(matches!(
+            tool.read_text(&base.join("blob.bin"), 64),
+            Err(FileAccessError::OperationFailed(_))
+        ));
+        assert!(matches!(
+            tool.read_text(&std::env::temp_dir().join("elsewhere.txt"), 64),
+            Err(FileAccessError::NotWhitelisted(_))
+        ));
+
+        let audit = fs::read_to_string(&audit_path).unwrap();
+        let outcomes: Vec<bool> = audit
+            .lines()
+            .map(|line| serde_json::from_str::<AuditEntry>(line).unwrap().success)
+            .collect();
+        assert_eq!(outcomes, [true, true, false, false]);
+    }
+
     #[test]
     fn test_execute_copy_denied_when_write_disabled() {
         let base = temp_base("copy_deny");
@@ -439,7 +780,7 @@ mod tests {
         let entry: AuditEntry = serde_json::from_str(last_line).unwrap();
 
         assert!(entry.success);
-        assert_eq!(entry.operation, FileOperation::Copy);
+        assert_eq!(entry.operation, Some(FileOperation::Copy));
         assert_eq!(entry.dest_path, Some(dest.display().to_string()));
     }
 
@@ -461,10 +802,222 @@ mod tests {
         let entry: AuditEntry = serde_json::from_str(last_line).unwrap();
 
         assert!(!entry.success);
-        assert_eq!(entry.operation, FileOperation::List);
+        assert_eq!(entry.operation, Some(FileOperation::List));
         assert!(entry
             .error_msg
             .unwrap_or_default()
             .contains("Permission denied"));
     }
+
+    fn entry(timestamp: &str, operation: FileOperation, path: &str, success: bool) -> AuditEntry {
+        AuditEntry {
+            timestamp: timestamp.to_string(),
+            operation: Some(operation),
+            source_path: path.to_string(),
+            dest_path: None,
+            success,
+            error_msg: None,
+            user_approved: true,
+            tool: None,
+            role: None,
+        }
+    }
+
+    #[test]
+    fn test_audit_query_filters() {
+        let base = temp_base("query");
+        let log = AuditLog::new(base.join("audit.log"));
+        log.append(&entry(
+            "2025-01-01T10:00:00Z",
+            FileOperation::Read,
+            "/home/a/x.txt",
+            true,
+        ))
+        .unwrap();
+        log.append(&entry(
+            "2025-01-02T10:00:00Z",
+            FileOperation::Delete,
+            "/home/a/y.txt",
+            false,
+        ))
+        .unwrap();
+        log.append(&entry(
+            "2025-01-03T10:00:00Z",
+            FileOperation::Read,
+            "/home/b/z.txt",
+            true,
+        ))
+        .unwrap();
+
+        let query = |filter: AuditFilter| log.query(&filter).unwrap().entries;
+
+        assert_eq!(query(AuditFilter::default()).len(), 3);
+        assert_eq!(
+            query(AuditFilter {
+                operation: Some(FileOperation::Read),
+                ..Default::default()
+            })
+            .len(),
+            2
+        );
+        assert_eq!(
+            query(AuditFilter {
+                success: Some(false),
+                ..Default::default()
+            })[0]
+                .source_path,
+            "/home/a/y.txt"
+        );
+        assert_eq!(
+            query(AuditFilter {
+                path_prefix: Some("/home/a/".to_string()),
+                ..Default::default()
+            })
+            .len(),
+            2
+        );
+
+        let since = DateTime::parse_from_rfc3339("2025-01-02T00:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let until = DateTime::parse_from_rfc3339("2025-01-02T23:59:59Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let in_range = query(AuditFilter {
+            since: Some(since),
+            until: Some(until),
+            ..Default::default()
+        });
+        assert_eq!(in_range.len(), 1);
+        assert_eq!(in_range[0].operation, Some(FileOperation::Delete));
+
+        let last = query(AuditFilter {
+            limit: Some(1),
+            ..Default::default()
+        });
+        assert_eq!(last[0].source_path, "/home/b/z.txt");
+    }
+
+    #[test]
+    fn test_audit_query_skips_malformed_lines() {
+        let base = temp_base("malformed");
+        let path = base.join("audit.log");
+        let log = AuditLog::new(&path);
+        log.append(&entry(
+            "2025-01-01T10:00:00Z",
+            FileOperation::List,
+            "/a",
+            true,
+        ))
+        .unwrap();
+        fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap()
+            .write_all(b"SystemTime { tv_sec: 1 } read /a\n{\"timestamp\":\n")
+            .unwrap();
+
+        let result = log.query(&AuditFilter::default()).unwrap();
+        assert_eq!(result.entries.len(), 1);
+        assert_eq!(result.skipped, 2);
+    }
+
+    #[test]
+    fn test_audit_log_rotates_and_queries_both_files() {
+        let base = temp_base("rotate");
+        let log = AuditLog::new(base.join("audit.log")).with_max_bytes(200);
+        for i in 0..3 {
+            log.append(&entry(
+                "2025-01-01T10:00:00Z",
+                FileOperation::Read,
+                &format!("/file{}", i),
+                true,
+            ))
+            .unwrap();
+        }
+
+        assert!(base.join("audit.log.1").exists());
+        assert!(fs::metadata(base.join("audit.log")).unwrap().len() <= 200);
+        let result = log.query(&AuditFilter::default()).unwrap();
+        assert_eq!(result.entries.len(), 2);
+        assert_eq!(result.entries[1].source_path, "/file2");
+
+        // A lowered limit applies without waiting for the next entry
+        assert!(!log.rotate_if_full().unwrap());
+        let lowered = log.clone().with_max_bytes(10);
+        assert!(lowered.rotate_if_full().unwrap());
+        assert!(!base.join("audit.log").exists());
+        assert!(!lowered.rotate_if_full().unwrap());
+    }
+
+    #[test]
+    fn test_concurrent_appends_keep_lines_whole() {
+        let base = temp_base("concurrent");
+        let log = AuditLog::new(base.join("audit.log"));
+
+        let handles: Vec<_> = (0..8)
+            .map(|t| {
+                let log = log.clone();
+                std::thread::spawn(move || {
+                    for i in 0..25 {
+                        let path = format!("/thread{}/{}", t, "x".repeat(i * 10));
+                        log.append(&entry(
+                            "2025-01-01T10:00:00Z",
+                            FileOperation::Write,
+                            &path,
+                            true,
+                        ))
+                        .unwrap();
+                    }
+                })
+            })
+            .collect();
+        for handle in handles {
+            handle.join().unwrap();
+        }
+
+        let result = log.query(&AuditFilter::default()).unwrap();
+        assert_eq!(result.entries.len(), 200);
+        assert_eq!(result.skipped, 0);
+    }
+
+    #[test]
+    fn test_validator_allows_new_file_in_missing_subdir() {
+        let documents = temp_base("new_file").join("Documents");
+        fs::create_dir_all(&documents).unwrap();
+        let validator = FileAccessValidator::new(vec![documents.clone()], true, true, false);
+
+        let report = documents.join("new").join("report.txt");
+        assert!(!report.parent().unwrap().exists());
+        assert!(validator
+            .validate_path(&report, FileOperation::Write)
+            .is_ok());
+    }
+
+    #[test]
+    fn test_validator_rejects_parent_dir_in_missing_components() {
+        let documents = temp_base("dotdot").join("Documents");
+        fs::create_dir_all(&documents).unwrap();
+        let validator = FileAccessValidator::new(vec![documents.clone()], true, true, false);
+
+        let escape = documents.join("new").join("..").join("..").join("evil.txt");
+        assert!(validator
+            .validate_path(&escape, FileOperation::Write)
+            .is_err());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validator_rejects_symlink_escape_for_new_file() {
+        let documents = temp_base("symlink").join("Documents");
+        fs::create_dir_all(&documents).unwrap();
+        std::os::unix::fs::symlink("/etc", documents.join("link-to-outside")).unwrap();
+        let validator = FileAccessValidator::new(vec![documents.clone()], true, true, false);
+
+        let evil = documents.join("link-to-outside").join("evil.txt");
+        assert!(matches!(
+            validator.validate_path(&evil, FileOperation::Write),
+            Err(FileAccessError::NotWhitelisted(_))
+        ));
+    }
 }
@@ -1,101 +1,372 @@
 use crate::{InstallMethod, Result, ToolResult};
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
-use tracing::info;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
+use tracing::{debug, info};
 
-/// Install program tool implementation
-pub struct InstallTool;
+/// Installer output kept in a result, in bytes; older output is dropped.
+pub const MAX_OUTPUT_BYTES: usize = 16 * 1024;
 
-impl InstallTool {
-    pub fn new() -> Self {
-        Self
+/// Starts installer processes. Tests substitute one that records what
+/// would have run.
+pub trait Spawner {
+    /// Run `program` with `args` to completion, passing each line of its
+    /// stdout and stderr to `on_line` as it arrives. Returns the exit code,
+    /// `None` when the process was killed by a signal.
+    fn run(
+        &self,
+        program: &str,
+        args: &[String],
+        on_line: &mut dyn FnMut(&str),
+    ) -> io::Result<Option<i32>>;
+
+    /// Save the file at `url` as `dest`.
+    fn download(&self, url: &str, dest: &Path) -> io::Result<()>;
+}
+
+/// Runs real processes, downloading with PowerShell on Windows and curl
+/// elsewhere.
+#[derive(Debug, Default)]
+pub struct ProcessSpawner;
+
+impl Spawner for ProcessSpawner {
+    fn run(
+        &self,
+        program: &str,
+        args: &[String],
+        on_line: &mut dyn FnMut(&str),
+    ) -> io::Result<Option<i32>> {
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()?;
+
+        // One reader per stream so neither pipe fills up while the other is read
+        let (tx, rx) = mpsc::channel();
+        let streams: [Option<Box<dyn Read + Send>>; 2] = [
+            child
+                .stdout
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Read + Send>),
+            child
+                .stderr
+                .take()
+                .map(|s| Box::new(s) as Box<dyn Read + Send>),
+        ];
+        let readers: Vec<_> = streams
+            .into_iter()
+            .flatten()
+            .map(|stream| {
+                let tx = tx.clone();
+                thread::spawn(move || {
+                    let mut reader = BufReader::new(stream);
+                    let mut line = Vec::new();
+                    while matches!(reader.read_until(b'\n', &mut line), Ok(n) if n > 0) {
+                        let text = String::from_utf8_lossy(&line).trim_end().to_string();
+                        if tx.send(text).is_err() {
+                            break;
+                        }
+                        line.clear();
+                    }
+                })
+            })
+            .collect();
+        drop(tx);
+
+        for line in rx {
+            on_line(&line);
+        }
+        for reader in readers {
+            let _ = reader.join();
+        }
+        Ok(child.wait()?.code())
     }
 
-    pub fn execute(&self, program: &str, method: &InstallMethod) -> Result<ToolResult> {
-        info!(
-            "Executing install tool: program='{}', method={:?}",
-            program, method
+    fn download(&self, url: &str, dest: &Path) -> io::Result<()> {
+        let dest = dest.display().to_string();
+        #[cfg(windows)]
+        let (program, args) = (
+            "powershell",
+            vec![
+                "-NoProfile".to_string(),
+                "-Command".to_string(),
+                format!("Invoke-WebRequest -Uri '{}' -OutFile '{}'", url, dest),
+            ],
+        );
+        #[cfg(not(windows))]
+        let (program, args) = (
+            "curl",
+            vec!["-fsSL".to_string(), "-o".to_string(), dest, url.to_string()],
         );
 
-        match method {
-            InstallMethod::Command { cmd, args } => self.execute_command(program, cmd, args),
-            InstallMethod::Download {
-                url,
-                installer_args,
-            } => self.download_and_install(program, url, installer_args),
+        let mut output = OutputTail::default();
+        match self.run(program, &args, &mut |line| output.push(line))? {
+            Some(0) => Ok(()),
+            code => Err(io::Error::other(format!(
+                "{} {}: {}",
+                program,
+                describe_exit(code),
+                output.finish().trim()
+            ))),
         }
     }
+}
 
-    fn execute_command(&self, program: &str, cmd: &str, args: &[String]) -> Result<ToolResult> {
-        info!("Running command: {} {:?}", cmd, args);
+/// What an install would run, resolved before anything executes.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct InstallPlan {
+    pub executable: String,
+    pub args: Vec<String>,
+    /// The executable and its arguments as one line
+    pub command_line: String,
+    /// Where the installer is downloaded from
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub download_url: Option<String>,
+    /// Directory the installer is downloaded to
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub target_dir: Option<PathBuf>,
+    /// Checksum the download must match before it runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
 
-        let output = Command::new(cmd)
-            .args(args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
-
-        if output.status.success() {
-            let stdout = String::from_utf8_lossy(&output.stdout);
-            let result = format!(
-                "Successfully installed '{}'\n\nOutput:\n{}",
-                program, stdout
-            );
-            Ok(ToolResult::success("install", result))
-        } else {
-            let stderr = String::from_utf8_lossy(&output.stderr);
-            let error = format!(
-                "Failed to install '{}': {}\n\nError output:\n{}",
-                program, output.status, stderr
-            );
-            Ok(ToolResult::failure("install", error))
+/// An install waiting for the user's approval before it downloads and
+/// runs anything.
+#[derive(Debug, Clone, Serialize)]
+pub struct InstallRequest {
+    pub program: String,
+    pub method: InstallMethod,
+}
+
+impl std::fmt::Display for InstallRequest {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.method {
+            InstallMethod::Command { cmd, args } => {
+                write!(
+                    f,
+                    "install '{}' with {}",
+                    self.program,
+                    command_line(cmd, args)
+                )
+            }
+            InstallMethod::Download { url, .. } => {
+                write!(f, "install '{}' from {}", self.program, url)
+            }
+        }
+    }
+}
+
+/// Install program tool implementation.
+///
+/// Commands may only run package managers on the allowlist. Downloads need
+/// a SHA-256 checksum or a host on the download allowlist, and the
+/// installer runs only once it is saved and any checksum matches.
+pub struct InstallTool {
+    allowed_installers: Vec<String>,
+    download_hosts: Vec<String>,
+    download_dir: PathBuf,
+    spawner: Box<dyn Spawner>,
+}
+
+impl InstallTool {
+    /// Tool allowed to run the executables named in `allowed_installers`,
+    /// e.g. `security.allowed_installers`
+    pub fn new(allowed_installers: Vec<String>) -> Self {
+        Self {
+            allowed_installers,
+            download_hosts: Vec::new(),
+            download_dir: dirs::download_dir().unwrap_or_else(std::env::temp_dir),
+            spawner: Box::new(ProcessSpawner),
         }
     }
 
-    fn download_and_install(
+    /// Hosts installers may be downloaded from without a checksum, e.g.
+    /// `security.allowed_download_hosts`
+    pub fn with_download_hosts(mut self, hosts: Vec<String>) -> Self {
+        self.download_hosts = hosts;
+        self
+    }
+
+    pub fn with_download_dir(mut self, dir: PathBuf) -> Self {
+        self.download_dir = dir;
+        self
+    }
+
+    pub fn with_spawner(mut self, spawner: impl Spawner + 'static) -> Self {
+        self.spawner = Box::new(spawner);
+        self
+    }
+
+    /// Install `program`. A dry run returns the [`InstallPlan`] as JSON
+    /// without downloading or running anything.
+    pub fn execute(
         &self,
         program: &str,
-        url: &str,
-        installer_args: &[String],
+        method: &InstallMethod,
+        dry_run: bool,
     ) -> Result<ToolResult> {
-        info!("Downloading from: {}", url);
-
-        // For MVP, we'll use PowerShell's Invoke-WebRequest
-        let temp_file = format!("C:\\Users\\Public\\Downloads\\{}_installer.exe", program);
-
-        // Download
-        let download_output = Command::new("powershell")
-            .args([
-                "-Command",
-                &format!("Invoke-WebRequest -Uri '{}' -OutFile '{}'", url, temp_file),
-            ])
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
+        info!(
+            "Executing install tool: program='{}', method={:?}, dry_run={}",
+            program, method, dry_run
+        );
 
-        if !download_output.status.success() {
-            let stderr = String::from_utf8_lossy(&download_output.stderr);
-            return Ok(ToolResult::failure(
+        let plan = match self.plan(program, method) {
+            Ok(plan) => plan,
+            Err(reason) => return Ok(ToolResult::failure("install", reason)),
+        };
+        if dry_run {
+            return Ok(ToolResult::success(
                 "install",
-                format!("Download failed: {}", stderr),
+                serde_json::to_string(&plan)?,
             ));
         }
 
-        // Install
-        info!("Installing from: {}", temp_file);
-        let install_output = Command::new(&temp_file)
-            .args(installer_args)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::piped())
-            .output()?;
+        if let Some(url) = &plan.download_url {
+            if let Err(reason) = self.download(url, &plan) {
+                return Ok(ToolResult::failure("install", reason));
+            }
+        }
+        self.run(program, &plan)
+    }
 
-        if install_output.status.success() {
-            let result = format!("Successfully downloaded and installed '{}'", program);
-            Ok(ToolResult::success("install", result))
+    /// Resolve what installing `program` runs, or why it may not.
+    pub fn plan(
+        &self,
+        program: &str,
+        method: &InstallMethod,
+    ) -> std::result::Result<InstallPlan, String> {
+        match method {
+            InstallMethod::Command { cmd, args } => {
+                let name = installer_name(cmd)
+                    .ok_or_else(|| format!("'{}' must be an executable name, not a path", cmd))?;
+                if !self
+                    .allowed_installers
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(name))
+                {
+                    return Err(format!(
+                        "'{}' is not an allowed installer; allowed: {}",
+                        cmd,
+                        self.allowed_installers.join(", ")
+                    ));
+                }
+                Ok(InstallPlan {
+                    executable: cmd.clone(),
+                    args: args.clone(),
+                    command_line: command_line(cmd, args),
+                    download_url: None,
+                    target_dir: None,
+                    sha256: None,
+                })
+            }
+            InstallMethod::Download {
+                url,
+                installer_args,
+                sha256,
+            } => {
+                let is_http = url.starts_with("https://") || url.starts_with("http://");
+                if !is_http || url.contains(|c: char| c.is_whitespace() || "'\"`".contains(c)) {
+                    return Err(format!("'{}' is not a valid download URL", url));
+                }
+                let sha256 = sha256.as_deref().map(str::to_ascii_lowercase);
+                match &sha256 {
+                    Some(sum) if sum.len() != 64 || !sum.chars().all(|c| c.is_ascii_hexdigit()) => {
+                        return Err(format!("'{}' is not a SHA-256 checksum", sum));
+                    }
+                    Some(_) => {}
+                    None => {
+                        let host = lucastra_browser::host_of(url).unwrap_or_default();
+                        if !self
+                            .download_hosts
+                            .iter()
+                            .any(|allowed| lucastra_config::domain_matches(&host, allowed))
+                        {
+                            return Err(format!(
+                                "Downloads from '{}' need a sha256 checksum; \
+                                 the host is not in security.allowed_download_hosts",
+                                host
+                            ));
+                        }
+                    }
+                }
+                let installer = self.download_dir.join(installer_file_name(program));
+                let executable = installer.display().to_string();
+                Ok(InstallPlan {
+                    command_line: command_line(&executable, installer_args),
+                    executable,
+                    args: installer_args.clone(),
+                    download_url: Some(url.clone()),
+                    target_dir: Some(self.download_dir.clone()),
+                    sha256,
+                })
+            }
+        }
+    }
+
+    /// Save the installer and check its checksum, deleting it on mismatch.
+    fn download(&self, url: &str, plan: &InstallPlan) -> std::result::Result<(), String> {
+        let installer = Path::new(&plan.executable);
+        info!("Downloading {} to {}", url, installer.display());
+        fs::create_dir_all(&self.download_dir)
+            .and_then(|_| self.spawner.download(url, installer))
+            .map_err(|e| format!("Download failed: {}", e))?;
+
+        if let Some(expected) = &plan.sha256 {
+            let actual = sha256_file(installer)
+                .map_err(|e| format!("Can't read {}: {}", installer.display(), e))?;
+            if &actual != expected {
+                let _ = fs::remove_file(installer);
+                return Err(format!(
+                    "Checksum mismatch for {}: expected {}, got {}; the installer was deleted",
+                    url, expected, actual
+                ));
+            }
+        }
+        make_executable(installer).map_err(|e| format!("Can't run {}: {}", installer.display(), e))
+    }
+
+    /// Run the planned command, reporting its exit code, duration and the
+    /// end of its output.
+    fn run(&self, program: &str, plan: &InstallPlan) -> Result<ToolResult> {
+        info!("Running command: {}", plan.command_line);
+        let started = Instant::now();
+        let mut output = OutputTail::default();
+        let code = self
+            .spawner
+            .run(&plan.executable, &plan.args, &mut |line| {
+                debug!("install: {}", line);
+                output.push(line);
+            })?;
+
+        let summary = format!(
+            "{} after {:.1}s",
+            describe_exit(code),
+            started.elapsed().as_secs_f64()
+        );
+        let output = output.finish();
+        if code == Some(0) {
+            Ok(ToolResult::success(
+                "install",
+                format!(
+                    "Installed '{}' with `{}`: {}\n\nOutput:\n{}",
+                    program, plan.command_line, summary, output
+                ),
+            ))
         } else {
-            let stderr = String::from_utf8_lossy(&install_output.stderr);
             Ok(ToolResult::failure(
                 "install",
-                format!("Installation failed: {}", stderr),
+                format!(
+                    "Failed to install '{}' with `{}`: {}\n\nOutput:\n{}",
+                    program, plan.command_line, summary, output
+                ),
             ))
         }
     }
@@ -103,6 +374,336 @@ impl InstallTool {
 
 impl Default for InstallTool {
     fn default() -> Self {
-        Self::new()
+        Self::new(lucastra_config::SecurityConfig::default().allowed_installers)
+    }
+}
+
+/// The last [`MAX_OUTPUT_BYTES`] of a process's output.
+#[derive(Debug, Default)]
+struct OutputTail {
+    text: String,
+    truncated: bool,
+}
+
+impl OutputTail {
+    fn push(&mut self, line: &str) {
+        self.text.push_str(line);
+        self.text.push('\n');
+        if self.text.len() > MAX_OUTPUT_BYTES {
+            let mut cut = self.text.len() - MAX_OUTPUT_BYTES;
+            while !self.text.is_char_boundary(cut) {
+                cut += 1;
+            }
+            self.text.drain(..cut);
+            self.truncated = true;
+        }
+    }
+
+    fn finish(self) -> String {
+        if self.truncated {
+            format!("[earlier output omitted]\n{}", self.text)
+        } else {
+            self.text
+        }
+    }
+}
+
+/// Executable name of `cmd` without a `.exe` suffix; `None` for paths,
+/// which could point anywhere.
+fn installer_name(cmd: &str) -> Option<&str> {
+    if cmd.is_empty() || cmd.contains(['/', '\\']) {
+        return None;
+    }
+    let lower = cmd.to_ascii_lowercase();
+    Some(match lower.strip_suffix(".exe") {
+        Some(stem) => &cmd[..stem.len()],
+        None => cmd,
+    })
+}
+
+/// File name for a downloaded installer, keeping only characters that are
+/// safe in paths.
+fn installer_file_name(program: &str) -> String {
+    let name: String = program
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    let suffix = if cfg!(windows) { ".exe" } else { "" };
+    format!("{}_installer{}", name, suffix)
+}
+
+fn command_line(executable: &str, args: &[String]) -> String {
+    std::iter::once(executable)
+        .chain(args.iter().map(String::as_str))
+        .map(|part| {
+            if part.is_empty() || part.contains(char::is_whitespace) {
+                format!("\"{}\"", part)
+            } else {
+                part.to_string()
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn describe_exit(code: Option<i32>) -> String {
+    match code {
+        Some(code) => format!("exit code {}", code),
+        None => "terminated by a signal".to_string(),
+    }
+}
+
+fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(0o755))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Records calls instead of spawning; downloads write `payload`.
+    #[derive(Clone, Default)]
+    struct FakeSpawner {
+        calls: Arc<Mutex<Vec<String>>>,
+        payload: Vec<u8>,
+        output_lines: usize,
+    }
+
+    impl Spawner for FakeSpawner {
+        fn run(
+            &self,
+            program: &str,
+            args: &[String],
+            on_line: &mut dyn FnMut(&str),
+        ) -> io::Result<Option<i32>> {
+            self.calls.lock().unwrap().push(command_line(program, args));
+            for i in 0..self.output_lines {
+                on_line(&format!("progress line {:05}", i));
+            }
+            Ok(Some(0))
+        }
+
+        fn download(&self, url: &str, dest: &Path) -> io::Result<()> {
+            self.calls.lock().unwrap().push(format!("download {}", url));
+            fs::write(dest, &self.payload)
+        }
+    }
+
+    fn temp_dir(name: &str) -> PathBuf {
+        std::env::temp_dir().join(format!("lucastra_install_{}_{}", name, std::process::id()))
+    }
+
+    fn tool(spawner: &FakeSpawner, dir: &Path) -> InstallTool {
+        InstallTool::new(vec!["cargo".to_string(), "winget".to_string()])
+            .with_spawner(spawner.clone())
+            .with_download_dir(dir.to_path_buf())
+    }
+
+    fn command(cmd: &str, args: &[&str]) -> InstallMethod {
+        InstallMethod::Command {
+            cmd: cmd.to_string(),
+            args: args.iter().map(|a| a.to_string()).collect(),
+        }
+    }
+
+    fn download(sha256: Option<&str>) -> InstallMethod {
+        download_from("https://example.com/setup.exe", sha256)
+    }
+
+    fn download_from(url: &str, sha256: Option<&str>) -> InstallMethod {
+        InstallMethod::Download {
+            url: url.to_string(),
+            installer_args: vec!["/silent".to_string()],
+            sha256: sha256.map(str::to_string),
+        }
+    }
+
+    fn sha256_hex(bytes: &[u8]) -> String {
+        format!("{:x}", Sha256::digest(bytes))
+    }
+
+    #[test]
+    fn test_disallowed_installers_are_rejected() {
+        let spawner = FakeSpawner::default();
+        let dir = temp_dir("reject");
+        let tool = tool(&spawner, &dir);
+
+        for method in [
+            command("rm", &["-rf", "/"]),
+            command("/tmp/evil/cargo", &["install", "ripgrep"]),
+            command("..\\cargo.exe", &[]),
+            command("", &[]),
+        ] {
+            let result = tool.execute("ripgrep", &method, false).unwrap();
+            assert!(!result.success, "{:?} was allowed", method);
+        }
+        let result = tool
+            .execute("ripgrep", &command("rm", &["-rf", "/"]), false)
+            .unwrap();
+        assert!(result.output.contains("not an allowed installer"));
+        assert!(spawner.calls.lock().unwrap().is_empty());
+
+        let result = tool
+            .execute(
+                "git",
+                &command("WinGet.exe", &["install", "Git.Git"]),
+                false,
+            )
+            .unwrap();
+        assert!(result.success);
+    }
+
+    #[test]
+    fn test_dry_run_never_spawns() {
+        let spawner = FakeSpawner::default();
+        let dir = temp_dir("dry_run");
+        let tool = tool(&spawner, &dir);
+
+        let result = tool
+            .execute("ripgrep", &command("cargo", &["install", "ripgrep"]), true)
+            .unwrap();
+        assert!(result.success);
+        let plan: serde_json::Value = serde_json::from_str(&result.output).unwrap();
+        assert_eq!(plan["command_line"], "cargo install ripgrep");
+
+        let sum = sha256_hex(b"installer");
+        let result = tool.execute("my app", &download(Some(&sum)), true).unwrap();
+        let plan: serde_json::Value = serde_json::from_str(&result.output).unwrap();
+        assert_eq!(plan["download_url"], "https://example.com/setup.exe");
+        assert_eq!(plan["target_dir"], dir.display().to_string());
+        assert_eq!(plan["sha256"], sum);
+        assert!(plan["executable"]
+            .as_str()
+            .unwrap()
+            .contains("my_app_installer"));
+
+        assert!(spawner.calls.lock().unwrap().is_empty());
+        assert!(!dir.exists());
+    }
+
+    #[test]
+    fn test_checksum_mismatch_aborts() {
+        let spawner = FakeSpawner {
+            payload: b"tampered installer".to_vec(),
+            ..FakeSpawner::default()
+        };
+        let dir = temp_dir("checksum");
+        let tool = tool(&spawner, &dir);
+
+        let result = tool
+            .execute("app", &download(Some(&sha256_hex(b"installer"))), false)
+            .unwrap();
+        assert!(!result.success);
+        assert!(result.output.contains("Checksum mismatch"));
+        assert_eq!(
+            *spawner.calls.lock().unwrap(),
+            ["download https://example.com/setup.exe"]
+        );
+        assert!(!dir.join(installer_file_name("app")).exists());
+
+        let result = tool
+            .execute(
+                "app",
+                &download(Some(&sha256_hex(b"tampered installer"))),
+                false,
+            )
+            .unwrap();
+        assert!(result.success, "{}", result.output);
+        assert_eq!(spawner.calls.lock().unwrap().len(), 3);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_unchecked_downloads_need_an_allowed_host() {
+        let spawner = FakeSpawner::default();
+        let dir = temp_dir("unchecked");
+        let tool = tool(&spawner, &dir).with_download_hosts(vec!["example.com".to_string()]);
+
+        for url in [
+            "https://evil.test/setup.exe",
+            "https://example.com.evil.test/setup.exe",
+            "https://notexample.com/setup.exe",
+        ] {
+            let result = tool
+                .execute("app", &download_from(url, None), false)
+                .unwrap();
+            assert!(!result.success, "{} was downloaded", url);
+            assert!(result.output.contains("need a sha256 checksum"));
+        }
+        assert!(spawner.calls.lock().unwrap().is_empty());
+
+        let result = tool
+            .execute(
+                "app",
+                &download_from("https://dl.example.com/setup.exe", None),
+                false,
+            )
+            .unwrap();
+        assert!(result.success, "{}", result.output);
+
+        let _ = fs::remove_dir_all(dir);
+    }
+
+    #[test]
+    fn test_process_spawner_streams_output() {
+        let mut lines = Vec::new();
+        let code = ProcessSpawner
+            .run("cargo", &["--version".to_string()], &mut |line| {
+                lines.push(line.to_string())
+            })
+            .unwrap();
+        assert_eq!(code, Some(0));
+        assert!(lines[0].starts_with("cargo "));
+
+        let mut lines = Vec::new();
+        let code = ProcessSpawner
+            .run("cargo", &["--no-such-flag".to_string()], &mut |line| {
+                lines.push(line.to_string())
+            })
+            .unwrap();
+        assert_ne!(code, Some(0));
+        assert!(!lines.is_empty());
+    }
+
+    #[test]
+    fn test_output_is_bounded_and_exit_reported() {
+        let spawner = FakeSpawner {
+            output_lines: 5000,
+            ..FakeSpawner::default()
+        };
+        let dir = temp_dir("output");
+        let tool = tool(&spawner, &dir);
+
+        let result = tool
+            .execute("ripgrep", &command("cargo", &["install", "ripgrep"]), false)
+            .unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("exit code 0 after"));
+        assert!(result.output.contains("[earlier output omitted]"));
+        assert!(result.output.ends_with("progress line 04999\n"));
+        assert!(!result.output.contains("progress line 00000"));
+        assert!(result.output.len() < MAX_OUTPUT_BYTES + 256);
     }
 }
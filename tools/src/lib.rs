@@ -1,10 +1,19 @@
+use lucastra_core::{ErrorCode, LuCastraError};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 
+pub mod approval;
+pub mod calculate;
+pub mod delete;
+pub mod fetch;
 pub mod file_access;
 pub mod install;
+pub mod parser;
+pub mod rbac;
 pub mod read;
 pub mod search;
+pub mod write;
 
 #[derive(Debug, Error)]
 pub enum ToolError {
@@ -17,8 +26,11 @@ pub enum ToolError {
     #[error("Install error: {0}")]
     Install(String),
 
+    #[error("Permission denied: {0}")]
+    PermissionDenied(String),
+
     #[error("Core error: {0}")]
-    Core(#[from] lucastra_core::LuCastraError),
+    Core(#[from] LuCastraError),
 
     #[error("JSON error: {0}")]
     Json(#[from] serde_json::Error),
@@ -29,20 +41,68 @@ pub enum ToolError {
 
 pub type Result<T> = std::result::Result<T, ToolError>;
 
+impl ToolError {
+    /// Code a command failing with this error reports.
+    pub fn code(&self) -> ErrorCode {
+        match self {
+            Self::Core(e) => e.code(),
+            Self::PermissionDenied(_) => ErrorCode::PermissionDenied,
+            Self::Io(e) if e.kind() == std::io::ErrorKind::NotFound => ErrorCode::NotFound,
+            Self::Io(e) if e.kind() == std::io::ErrorKind::PermissionDenied => {
+                ErrorCode::PermissionDenied
+            }
+            Self::Json(_) => ErrorCode::InvalidRequest,
+            Self::Search(_) | Self::Read(_) | Self::Install(_) | Self::Io(_) => ErrorCode::Internal,
+        }
+    }
+}
+
+impl From<ToolError> for LuCastraError {
+    fn from(error: ToolError) -> Self {
+        match error {
+            ToolError::Core(e) => e,
+            error => LuCastraError::Tool {
+                code: error.code(),
+                source: Box::new(error),
+            },
+        }
+    }
+}
+
 /// Tool abstraction for agentic tasks
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 #[serde(tag = "tool", content = "params")]
 pub enum Tool {
     /// Search the filesystem using BM25
     Search { query: String, top_k: Option<usize> },
 
-    /// Read file contents
-    Read { path: String },
+    /// Read file contents, `length` bytes from `offset` when given
+    Read {
+        path: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        offset: Option<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        length: Option<usize>,
+    },
+
+    /// Write (or append to) a file in the virtual filesystem
+    Write {
+        path: String,
+        content: String,
+        #[serde(default)]
+        append: bool,
+    },
+
+    /// Delete a file in the virtual filesystem
+    Delete { path: String },
 
     /// Install a program (via terminal command)
     Install {
         program: String,
         method: InstallMethod,
+        /// Report what would run without running it
+        #[serde(default)]
+        dry_run: bool,
     },
 
     /// Access host filesystem with validation and auditing
@@ -51,9 +111,56 @@ pub enum Tool {
         path: String,
         dest_path: Option<String>,
     },
+
+    /// Evaluate an arithmetic expression, e.g. `2 * pi` or `atan2(1, 1)`
+    Calculate { expression: String },
+
+    /// Fetch a web page on an allowed domain and return its title and text
+    Fetch {
+        url: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        max_bytes: Option<usize>,
+    },
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl Tool {
+    /// JSON schema for a tool call, generated from this enum so prompts and
+    /// [`parser::ToolCallParser`] can't drift from what deserializes.
+    pub fn schema_json() -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(Tool)).unwrap_or_default()
+    }
+
+    /// Short name used in [`ToolResult::tool`] and permission lists.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Tool::Search { .. } => "search",
+            Tool::Read { .. } => "read",
+            Tool::Write { .. } => "write",
+            Tool::Delete { .. } => "delete",
+            Tool::Install { .. } => "install",
+            Tool::HostFileAccess { .. } => "host_file_access",
+            Tool::Calculate { .. } => "calculate",
+            Tool::Fetch { .. } => "fetch",
+        }
+    }
+
+    /// File operation the tool performs, if it touches files.
+    pub fn file_operation(&self) -> Option<file_access::FileOperation> {
+        use file_access::FileOperation;
+        match self {
+            Tool::Read { .. } => Some(FileOperation::Read),
+            Tool::Write { .. } => Some(FileOperation::Write),
+            Tool::Delete { .. } => Some(FileOperation::Delete),
+            Tool::HostFileAccess { operation, .. } => Some(*operation),
+            Tool::Search { .. }
+            | Tool::Install { .. }
+            | Tool::Calculate { .. }
+            | Tool::Fetch { .. } => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub enum InstallMethod {
     /// Run a shell command
     Command { cmd: String, args: Vec<String> },
@@ -62,6 +169,9 @@ pub enum InstallMethod {
     Download {
         url: String,
         installer_args: Vec<String>,
+        /// Hex SHA-256 the download must match before it runs
+        #[serde(default)]
+        sha256: Option<String>,
     },
 }
 
@@ -71,6 +181,9 @@ pub struct ToolResult {
     pub tool: String,
     pub success: bool,
     pub output: String,
+    /// Set when the tool is waiting for the user to approve it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub approval_token: Option<String>,
 }
 
 impl ToolResult {
@@ -79,6 +192,7 @@ impl ToolResult {
             tool: tool.to_string(),
             success: true,
             output,
+            approval_token: None,
         }
     }
 
@@ -87,6 +201,97 @@ impl ToolResult {
             tool: tool.to_string(),
             success: false,
             output: error,
+            approval_token: None,
         }
     }
+
+    /// Result for an operation queued until the user approves `token`.
+    pub fn pending(tool: &str, token: String, message: String) -> Self {
+        Self {
+            tool: tool.to_string(),
+            success: false,
+            output: message,
+            approval_token: Some(token),
+        }
+    }
+
+    pub fn is_pending(&self) -> bool {
+        self.approval_token.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_write_tool_json_roundtrip() {
+        let tool = Tool::Write {
+            path: "/mnt/root/notes.txt".to_string(),
+            content: "summary".to_string(),
+            append: true,
+        };
+        let json = serde_json::to_string(&tool).unwrap();
+        assert!(json.contains(r#""tool":"Write""#));
+
+        match serde_json::from_str(&json).unwrap() {
+            Tool::Write {
+                path,
+                content,
+                append,
+            } => {
+                assert_eq!(path, "/mnt/root/notes.txt");
+                assert_eq!(content, "summary");
+                assert!(append);
+            }
+            other => panic!("unexpected tool: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_write_append_defaults_to_false() {
+        let json = r#"{"tool":"Write","params":{"path":"/mnt/root/a.txt","content":"x"}}"#;
+        assert!(matches!(
+            serde_json::from_str(json).unwrap(),
+            Tool::Write { append: false, .. }
+        ));
+    }
+
+    /// Tool calls as first released; they must keep deserializing.
+    const V1_TOOLS: &str = include_str!("../tests/fixtures/v1/tools.json");
+
+    #[test]
+    fn test_v1_tool_calls_still_deserialize() {
+        let tools: Vec<Tool> = serde_json::from_str(V1_TOOLS).unwrap();
+        assert_eq!(tools.len(), 6);
+        assert!(matches!(
+            &tools[2],
+            Tool::Read { path, offset: None, length: None } if path == "/mnt/root/notes.txt"
+        ));
+        assert!(matches!(&tools[3], Tool::Install { dry_run: false, .. }));
+        assert!(matches!(
+            &tools[5],
+            Tool::HostFileAccess {
+                operation: file_access::FileOperation::Copy,
+                dest_path: Some(_),
+                ..
+            }
+        ));
+    }
+
+    #[test]
+    fn test_delete_tool_json_roundtrip() {
+        let tool = Tool::Delete {
+            path: "/mnt/root/old.txt".to_string(),
+        };
+        let json = serde_json::to_string(&tool).unwrap();
+        assert_eq!(
+            json,
+            r#"{"tool":"Delete","params":{"path":"/mnt/root/old.txt"}}"#
+        );
+        assert!(matches!(
+            serde_json::from_str(&json).unwrap(),
+            Tool::Delete { path } if path == "/mnt/root/old.txt"
+        ));
+    }
 }
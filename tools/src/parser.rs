@@ -0,0 +1,640 @@
+//! Lenient parsing of tool calls from model output.
+//!
+//! Models wrap their JSON in prose and code fences, use single quotes,
+//! leave trailing commas and invent parameters. [`ToolCallParser`] digs the
+//! JSON out, repairs those slips and checks each call against
+//! [`Tool::schema_json`] on its own, so one bad call doesn't sink the rest.
+//! The diagnostics it returns are meant to be shown to the model so it can
+//! correct itself.
+
+use crate::Tool;
+use serde_json::{Map, Value};
+
+/// One call in a model reply.
+#[derive(Debug, Clone)]
+pub struct ParsedCall {
+    /// Position of the call in the reply, from 0
+    pub index: usize,
+    /// Tool name as the model wrote it
+    pub name: Option<String>,
+    /// The call, or everything wrong with it
+    pub tool: Result<Tool, String>,
+    /// Slips that were corrected or ignored, such as unknown parameters
+    pub warnings: Vec<String>,
+}
+
+impl ParsedCall {
+    /// How diagnostics refer to the call, e.g. `call 2 (Search)`.
+    pub fn label(&self) -> String {
+        match &self.name {
+            Some(name) => format!("call {} ({})", self.index + 1, name),
+            None => format!("call {}", self.index + 1),
+        }
+    }
+}
+
+/// Tool calls read from one model reply.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedToolCalls {
+    pub calls: Vec<ParsedCall>,
+    /// Why no calls could be read at all
+    pub error: Option<String>,
+}
+
+impl ParsedToolCalls {
+    /// Calls that passed validation, in order.
+    pub fn tools(&self) -> Vec<Tool> {
+        self.calls
+            .iter()
+            .filter_map(|call| call.tool.as_ref().ok().cloned())
+            .collect()
+    }
+
+    /// Every error and warning, one line each, for the model to act on.
+    pub fn diagnostics(&self) -> Vec<String> {
+        let mut diagnostics: Vec<String> = self.error.iter().cloned().collect();
+        for call in &self.calls {
+            if let Err(e) = &call.tool {
+                diagnostics.push(format!("{}: {}", call.label(), e));
+            }
+            for warning in &call.warnings {
+                diagnostics.push(format!("{}: {}", call.label(), warning));
+            }
+        }
+        diagnostics
+    }
+}
+
+/// Reads tool calls from model output, validating them against the
+/// schema generated for [`Tool`].
+#[derive(Debug, Clone)]
+pub struct ToolCallParser {
+    schema: Value,
+}
+
+impl ToolCallParser {
+    pub fn new() -> Self {
+        Self {
+            schema: Tool::schema_json(),
+        }
+    }
+
+    /// Parse a JSON array of calls, or a single call, from `output`.
+    pub fn parse(&self, output: &str) -> ParsedToolCalls {
+        let calls = match extract_json(output) {
+            Ok(Value::Array(calls)) => calls,
+            Ok(call) => vec![call],
+            Err(e) => {
+                return ParsedToolCalls {
+                    calls: Vec::new(),
+                    error: Some(e),
+                }
+            }
+        };
+        ParsedToolCalls {
+            calls: calls
+                .into_iter()
+                .enumerate()
+                .map(|(index, call)| self.parse_call(index, call))
+                .collect(),
+            error: None,
+        }
+    }
+
+    /// Tool names as the schema spells them.
+    pub fn tool_names(&self) -> Vec<&str> {
+        self.variants()
+            .filter_map(|variant| variant["properties"]["tool"]["enum"][0].as_str())
+            .collect()
+    }
+
+    fn variants(&self) -> impl Iterator<Item = &Value> {
+        self.schema["oneOf"].as_array().into_iter().flatten()
+    }
+
+    /// Schema of the tool called `name`, ignoring case, `_` and `-` so
+    /// `host_file_access` finds `HostFileAccess`.
+    fn variant(&self, name: &str) -> Option<(&str, &Value)> {
+        let key = normalize_name(name);
+        self.variants().find_map(|variant| {
+            let tool = variant["properties"]["tool"]["enum"][0].as_str()?;
+            (normalize_name(tool) == key).then_some((tool, variant))
+        })
+    }
+
+    fn parse_call(&self, index: usize, call: Value) -> ParsedCall {
+        let mut parsed = ParsedCall {
+            index,
+            name: None,
+            tool: Err(String::new()),
+            warnings: Vec::new(),
+        };
+        let Value::Object(mut call) = call else {
+            parsed.tool = Err(r#"expected an object like {"tool": ..., "params": {...}}"#.into());
+            return parsed;
+        };
+        let name = match call.remove("tool") {
+            Some(Value::String(name)) => name,
+            Some(_) => {
+                parsed.tool = Err(r#""tool" must be a string"#.to_string());
+                return parsed;
+            }
+            None => {
+                parsed.tool = Err(r#"missing "tool""#.to_string());
+                return parsed;
+            }
+        };
+        parsed.name = Some(name.clone());
+        let Some((tool, variant)) = self.variant(&name) else {
+            parsed.tool = Err(format!(
+                "unknown tool '{}'; available tools are {}",
+                name,
+                self.tool_names().join(", ")
+            ));
+            return parsed;
+        };
+
+        let mut params = match call.remove("params") {
+            Some(Value::Object(params)) => Value::Object(params),
+            Some(Value::Null) | None if call.is_empty() => Value::Object(Map::new()),
+            // Parameters written next to "tool" instead of under "params"
+            Some(Value::Null) | None => {
+                parsed
+                    .warnings
+                    .push(r#"parameters belong under "params""#.to_string());
+                Value::Object(std::mem::take(&mut call))
+            }
+            Some(_) => {
+                parsed.tool = Err(r#""params" must be an object"#.to_string());
+                return parsed;
+            }
+        };
+        for key in call.keys() {
+            parsed
+                .warnings
+                .push(format!("ignored unknown key '{}'", key));
+        }
+
+        let mut check = Check {
+            definitions: &self.schema["definitions"],
+            errors: Vec::new(),
+            warnings: Vec::new(),
+        };
+        check.value(&variant["properties"]["params"], &mut params, "params");
+        parsed.warnings.append(&mut check.warnings);
+        if !check.errors.is_empty() {
+            parsed.tool = Err(check.errors.join("; "));
+            return parsed;
+        }
+
+        let call = serde_json::json!({ "tool": tool, "params": params });
+        parsed.tool = serde_json::from_value(call).map_err(|e| e.to_string());
+        parsed
+    }
+}
+
+impl Default for ToolCallParser {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Validation of one call's parameters against the subset of JSON schema
+/// that schemars emits for [`Tool`]. Unknown object keys are dropped and
+/// enum values fixed up for case; anything else wrong is an error.
+struct Check<'a> {
+    definitions: &'a Value,
+    errors: Vec<String>,
+    warnings: Vec<String>,
+}
+
+impl Check<'_> {
+    fn value(&mut self, schema: &Value, value: &mut Value, path: &str) {
+        let schema = match schema["$ref"].as_str() {
+            Some(reference) => {
+                let name = reference.rsplit('/').next().unwrap_or_default();
+                &self.definitions[name]
+            }
+            None => schema,
+        };
+
+        if let Some(branches) = schema["oneOf"].as_array() {
+            self.one_of(branches, value, path);
+            return;
+        }
+
+        if let Some(allowed) = schema["enum"].as_array() {
+            let matched = allowed.iter().find(|option| match (option, &*value) {
+                (Value::String(option), Value::String(value)) => option.eq_ignore_ascii_case(value),
+                (option, value) => *option == value,
+            });
+            match matched {
+                Some(option) => *value = option.clone(),
+                None => self.errors.push(format!(
+                    "{} must be one of {}, not {}",
+                    path,
+                    join_values(allowed),
+                    value
+                )),
+            }
+            return;
+        }
+
+        if !type_matches(&schema["type"], value) {
+            self.errors.push(format!(
+                "{} must be {}, not {}",
+                path,
+                describe_type(&schema["type"]),
+                value
+            ));
+            return;
+        }
+
+        match value {
+            Value::Object(object) => self.object(schema, object, path),
+            Value::Array(items) => {
+                for (i, item) in items.iter_mut().enumerate() {
+                    self.value(&schema["items"], item, &format!("{}[{}]", path, i));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn object(&mut self, schema: &Value, object: &mut Map<String, Value>, path: &str) {
+        let properties = schema["properties"].as_object();
+        let unknown: Vec<String> = object
+            .keys()
+            .filter(|key| !properties.is_some_and(|p| p.contains_key(*key)))
+            .cloned()
+            .collect();
+        for key in unknown {
+            object.remove(&key);
+            self.warnings
+                .push(format!("ignored unknown parameter {}.{}", path, key));
+        }
+
+        let missing: Vec<&str> = schema["required"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter(|key| !object.contains_key(*key))
+            .collect();
+        if !missing.is_empty() {
+            self.errors.push(format!(
+                "{} is missing {}",
+                path,
+                missing
+                    .iter()
+                    .map(|key| format!("'{}'", key))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+
+        for (key, property) in properties.into_iter().flatten() {
+            if let Some(value) = object.get_mut(key) {
+                self.value(property, value, &format!("{}.{}", path, key));
+            }
+        }
+    }
+
+    /// Accept the first branch `value` fits without errors. Externally
+    /// tagged enums such as `InstallMethod` are reported by their tags.
+    fn one_of(&mut self, branches: &[Value], value: &mut Value, path: &str) {
+        for branch in branches {
+            let mut candidate = value.clone();
+            let mut check = Check {
+                definitions: self.definitions,
+                errors: Vec::new(),
+                warnings: Vec::new(),
+            };
+            check.value(branch, &mut candidate, path);
+            if check.errors.is_empty() {
+                *value = candidate;
+                self.warnings.append(&mut check.warnings);
+                return;
+            }
+        }
+
+        let tags: Vec<&str> = branches
+            .iter()
+            .filter_map(|branch| branch["required"][0].as_str())
+            .collect();
+        self.errors.push(format!(
+            "{} must be an object with one of the keys {}, not {}",
+            path,
+            tags.join(", "),
+            value
+        ));
+    }
+}
+
+/// Whether `value` has one of the types a schema allows; any value fits a
+/// schema without a type.
+fn type_matches(types: &Value, value: &Value) -> bool {
+    let fits = |ty: &str| match ty {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "integer" => value.is_u64() || value.is_i64(),
+        "number" => value.is_number(),
+        _ => true,
+    };
+    match types {
+        Value::String(ty) => fits(ty),
+        Value::Array(types) => types.iter().filter_map(Value::as_str).any(fits),
+        _ => true,
+    }
+}
+
+/// `"integer"` or `"string or null"` for a schema's `type`.
+fn describe_type(types: &Value) -> String {
+    match types {
+        Value::Array(types) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .collect::<Vec<_>>()
+            .join(" or "),
+        types => types.as_str().unwrap_or("valid").to_string(),
+    }
+}
+
+fn join_values(values: &[Value]) -> String {
+    values
+        .iter()
+        .map(|value| value.to_string())
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+fn normalize_name(name: &str) -> String {
+    name.chars()
+        .filter(|c| *c != '_' && *c != '-')
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// The first JSON object, or array of objects, in `output`, skipping
+/// brackets in the surrounding prose. Single quotes and trailing commas
+/// are repaired when the text doesn't parse as is.
+fn extract_json(output: &str) -> Result<Value, String> {
+    let mut first_error = None;
+    for (start, _) in output.match_indices(['[', '{']) {
+        let candidate = balanced(&output[start..]);
+        let parsed = serde_json::from_str::<Value>(candidate)
+            .or_else(|e| serde_json::from_str::<Value>(&repair(candidate)).map_err(|_| e));
+        match parsed {
+            Ok(value) if looks_like_calls(&value) => return Ok(value),
+            Ok(_) => {}
+            Err(e) => {
+                first_error.get_or_insert_with(|| format!("invalid JSON: {}", e));
+            }
+        }
+    }
+    Err(first_error.unwrap_or_else(|| "no JSON tool calls found in the reply".to_string()))
+}
+
+fn looks_like_calls(value: &Value) -> bool {
+    match value {
+        Value::Object(_) => true,
+        Value::Array(items) => !items.is_empty() && items.iter().all(Value::is_object),
+        _ => false,
+    }
+}
+
+/// `text` up to the bracket closing its first one, or all of it when the
+/// brackets never balance.
+fn balanced(text: &str) -> &str {
+    let mut depth = 0usize;
+    let mut quote = None;
+    let mut escaped = false;
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(_) if escaped => escaped = false,
+            Some(_) if c == '\\' => escaped = true,
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None => match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => depth += 1,
+                ']' | '}' => {
+                    depth = depth.saturating_sub(1);
+                    if depth == 0 {
+                        return &text[..=i];
+                    }
+                }
+                _ => {}
+            },
+        }
+    }
+    text
+}
+
+/// `text` with single-quoted strings double-quoted and trailing commas
+/// dropped, the slips models make most.
+fn repair(text: &str) -> String {
+    let mut repaired = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut quote = None;
+    while let Some(c) = chars.next() {
+        match quote {
+            Some(q) => match c {
+                '\\' => match chars.next() {
+                    // \' is only an escape inside single quotes
+                    Some('\'') => repaired.push('\''),
+                    Some(next) => {
+                        repaired.push('\\');
+                        repaired.push(next);
+                    }
+                    None => {}
+                },
+                c if c == q => {
+                    repaired.push('"');
+                    quote = None;
+                }
+                '"' => repaired.push_str("\\\""),
+                c => repaired.push(c),
+            },
+            None => match c {
+                '"' | '\'' => {
+                    quote = Some(c);
+                    repaired.push('"');
+                }
+                ',' => {
+                    let next = chars.clone().find(|c| !c.is_whitespace());
+                    if !matches!(next, Some(']' | '}')) {
+                        repaired.push(c);
+                    }
+                }
+                c => repaired.push(c),
+            },
+        }
+    }
+    repaired
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::file_access::FileOperation;
+    use crate::InstallMethod;
+
+    const FENCED: &str = include_str!("../tests/fixtures/tool_calls/fenced.txt");
+    const SINGLE_QUOTES: &str = include_str!("../tests/fixtures/tool_calls/single_quotes.txt");
+    const EXTRA_KEYS: &str = include_str!("../tests/fixtures/tool_calls/extra_keys.txt");
+    const ONE_BAD_CALL: &str = include_str!("../tests/fixtures/tool_calls/one_bad_call.txt");
+    const MISSING_PARAMS: &str = include_str!("../tests/fixtures/tool_calls/missing_params.txt");
+
+    #[test]
+    fn test_fenced_array_with_trailing_comma() {
+        let parsed = ToolCallParser::new().parse(FENCED);
+        assert!(
+            parsed.diagnostics().is_empty(),
+            "{:?}",
+            parsed.diagnostics()
+        );
+
+        let tools = parsed.tools();
+        assert_eq!(tools.len(), 2);
+        assert!(matches!(
+            &tools[0],
+            Tool::Search { query, top_k: Some(3) } if query == "quarterly report"
+        ));
+        assert!(matches!(
+            &tools[1],
+            Tool::Read { path, offset: None, length: None } if path == "/mnt/root/reports/q3.txt"
+        ));
+    }
+
+    #[test]
+    fn test_single_quoted_object() {
+        let parsed = ToolCallParser::new().parse(SINGLE_QUOTES);
+        assert!(
+            parsed.diagnostics().is_empty(),
+            "{:?}",
+            parsed.diagnostics()
+        );
+        match &parsed.tools()[..] {
+            [Tool::Write {
+                path,
+                content,
+                append: true,
+            }] => {
+                assert_eq!(path, "/mnt/root/notes.txt");
+                assert_eq!(content, r#"Don't forget the "draft" label"#);
+            }
+            other => panic!("unexpected tools: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_extra_keys_are_ignored_with_warnings() {
+        let parsed = ToolCallParser::new().parse(EXTRA_KEYS);
+        assert!(matches!(
+            &parsed.tools()[..],
+            [Tool::Calculate { expression }] if expression == "1250 * 0.15"
+        ));
+        assert_eq!(
+            parsed.diagnostics(),
+            vec![
+                "call 1 (calculate): ignored unknown key 'id'",
+                "call 1 (calculate): ignored unknown key 'reason'",
+                "call 1 (calculate): ignored unknown parameter params.precision",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_one_bad_call_among_good_ones() {
+        let parsed = ToolCallParser::new().parse(ONE_BAD_CALL);
+        assert_eq!(parsed.calls.len(), 4);
+
+        let tools = parsed.tools();
+        assert_eq!(tools.len(), 3);
+        assert!(matches!(&tools[0], Tool::Search { query, .. } if query == "llamafile"));
+        assert!(matches!(
+            &tools[1],
+            Tool::HostFileAccess {
+                operation: FileOperation::List,
+                ..
+            }
+        ));
+        assert!(matches!(&tools[2], Tool::Calculate { expression } if expression == "2 * pi"));
+
+        let bad = parsed.calls[1].tool.as_ref().unwrap_err();
+        assert!(bad.starts_with("unknown tool 'Serch'; available tools are Search, Read"));
+        assert_eq!(
+            parsed.diagnostics()[1],
+            r#"call 4 (Calculate): parameters belong under "params""#
+        );
+    }
+
+    #[test]
+    fn test_missing_and_mistyped_params_are_reported_per_call() {
+        let parsed = ToolCallParser::new().parse(MISSING_PARAMS);
+        let errors: Vec<&str> = parsed
+            .calls
+            .iter()
+            .map(|call| call.tool.as_ref().unwrap_err().as_str())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "params is missing 'path'",
+                "params.query must be string, not 42",
+                r#"params.method must be an object with one of the keys Command, Download, not {"Pip":{"package":"git"}}"#,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_bare_object_and_prose_without_json() {
+        let parser = ToolCallParser::new();
+        let parsed = parser.parse(
+            r#"{"tool":"Install","params":{"program":"rg","method":{"Command":{"cmd":"cargo","args":["install","ripgrep"]}}}}"#,
+        );
+        assert!(matches!(
+            &parsed.tools()[..],
+            [Tool::Install {
+                method: InstallMethod::Command { .. },
+                dry_run: false,
+                ..
+            }]
+        ));
+
+        let parsed = parser.parse("I don't need any tools [1].");
+        assert!(parsed.calls.is_empty());
+        assert_eq!(
+            parsed.diagnostics(),
+            vec!["no JSON tool calls found in the reply"]
+        );
+        assert!(parser
+            .parse(r#"[{"tool": "Search", "params": {"query": }}]"#)
+            .error
+            .unwrap()
+            .starts_with("invalid JSON"));
+    }
+
+    #[test]
+    fn test_schema_lists_every_tool() {
+        let parser = ToolCallParser::new();
+        assert_eq!(
+            parser.tool_names(),
+            vec![
+                "Search",
+                "Read",
+                "Write",
+                "Delete",
+                "Install",
+                "HostFileAccess",
+                "Calculate",
+                "Fetch"
+            ]
+        );
+        assert!(Tool::schema_json()["definitions"]["InstallMethod"].is_object());
+    }
+}
@@ -0,0 +1,348 @@
+//! Role-based permissions for tool calls.
+//!
+//! Each [`Role`] is granted a set of tools and file operations. A tool call is
+//! allowed only when its tool is granted and, for tools that touch files, its
+//! [`FileOperation`] is granted too.
+
+use crate::file_access::{AuditEntry, FileOperation};
+use crate::{Result, Tool, ToolError};
+use chrono::Utc;
+use lucastra_config::{RolePermissions, SecurityConfig};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::str::FromStr;
+use tracing::warn;
+
+const ALL_OPERATIONS: [FileOperation; 6] = [
+    FileOperation::Read,
+    FileOperation::Write,
+    FileOperation::Move,
+    FileOperation::Copy,
+    FileOperation::Delete,
+    FileOperation::List,
+];
+
+/// Permission level for tool execution.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Search and read files
+    Reader,
+    /// Also create, modify and delete files
+    Writer,
+    /// Everything, including installing programs
+    Admin,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Reader, Role::Writer, Role::Admin];
+
+    /// Built-in permissions for this role.
+    pub fn default_grant(self) -> RoleGrant {
+        match self {
+            Role::Reader => RoleGrant {
+                tools: vec!["search", "read", "host_file_access", "calculate", "fetch"],
+                operations: vec![FileOperation::Read, FileOperation::List],
+            },
+            Role::Writer => RoleGrant {
+                tools: vec![
+                    "search",
+                    "read",
+                    "write",
+                    "delete",
+                    "host_file_access",
+                    "calculate",
+                    "fetch",
+                ],
+                operations: ALL_OPERATIONS.to_vec(),
+            },
+            Role::Admin => RoleGrant {
+                tools: vec![
+                    "search",
+                    "read",
+                    "write",
+                    "delete",
+                    "install",
+                    "host_file_access",
+                    "calculate",
+                    "fetch",
+                ],
+                operations: ALL_OPERATIONS.to_vec(),
+            },
+        }
+    }
+}
+
+impl std::fmt::Display for Role {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Role::Reader => write!(f, "reader"),
+            Role::Writer => write!(f, "writer"),
+            Role::Admin => write!(f, "admin"),
+        }
+    }
+}
+
+impl FromStr for Role {
+    type Err = ToolError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Role::ALL
+            .into_iter()
+            .find(|role| role.to_string().eq_ignore_ascii_case(s.trim()))
+            .ok_or_else(|| ToolError::PermissionDenied(format!("Unknown role: {}", s)))
+    }
+}
+
+/// Tools and file operations granted to a role.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoleGrant {
+    pub tools: Vec<&'static str>,
+    pub operations: Vec<FileOperation>,
+}
+
+impl RoleGrant {
+    /// Build a grant from config, skipping unknown tool or operation names.
+    fn from_permissions(role: Role, permissions: &RolePermissions) -> Self {
+        let all_tools = Role::Admin.default_grant().tools;
+        let tools = permissions
+            .tools
+            .iter()
+            .filter_map(|name| {
+                let tool = all_tools.iter().find(|t| t.eq_ignore_ascii_case(name));
+                if tool.is_none() {
+                    warn!("Ignoring unknown tool '{}' for role {}", name, role);
+                }
+                tool.copied()
+            })
+            .collect();
+        let operations = permissions
+            .operations
+            .iter()
+            .filter_map(|name| match name.parse::<FileOperation>() {
+                Ok(op) => Some(op),
+                Err(e) => {
+                    warn!("Ignoring {} for role {}", e, role);
+                    None
+                }
+            })
+            .collect();
+        Self { tools, operations }
+    }
+}
+
+/// The active role and what every role is granted.
+#[derive(Debug, Clone)]
+pub struct PermissionPolicy {
+    role: Role,
+    grants: HashMap<Role, RoleGrant>,
+}
+
+impl PermissionPolicy {
+    /// Policy with built-in grants for every role.
+    pub fn new(role: Role) -> Self {
+        Self {
+            role,
+            grants: Role::ALL
+                .into_iter()
+                .map(|role| (role, role.default_grant()))
+                .collect(),
+        }
+    }
+
+    /// Load the active role and any grant overrides from config.
+    ///
+    /// An unrecognized role falls back to [`Role::Reader`].
+    pub fn from_config(security: &SecurityConfig) -> Self {
+        let role = security.role.parse().unwrap_or_else(|e| {
+            warn!("{}; falling back to reader", e);
+            Role::Reader
+        });
+
+        let mut policy = Self::new(role);
+        for (name, permissions) in &security.role_permissions {
+            match name.parse::<Role>() {
+                Ok(role) => policy.set_grant(role, RoleGrant::from_permissions(role, permissions)),
+                Err(e) => warn!("Ignoring permissions: {}", e),
+            }
+        }
+        policy
+    }
+
+    pub fn role(&self) -> Role {
+        self.role
+    }
+
+    pub fn grant(&self, role: Role) -> &RoleGrant {
+        &self.grants[&role]
+    }
+
+    pub fn set_grant(&mut self, role: Role, grant: RoleGrant) {
+        self.grants.insert(role, grant);
+    }
+
+    /// Check whether the active role may run `tool`.
+    pub fn check(&self, tool: &Tool) -> Result<()> {
+        let grant = self.grant(self.role);
+        if !grant.tools.contains(&tool.name()) {
+            return Err(ToolError::PermissionDenied(format!(
+                "role '{}' may not use the {} tool",
+                self.role,
+                tool.name()
+            )));
+        }
+
+        if let Some(operation) = tool.file_operation() {
+            if !grant.operations.contains(&operation) {
+                return Err(ToolError::PermissionDenied(format!(
+                    "role '{}' may not perform {} operations",
+                    self.role, operation
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Audit entry recording that `tool` was denied for the active role.
+    pub fn denial_entry(&self, tool: &Tool, reason: String) -> AuditEntry {
+        let (source_path, dest_path) = match tool {
+            Tool::Read { path, .. } | Tool::Write { path, .. } | Tool::Delete { path } => {
+                (path.clone(), None)
+            }
+            Tool::HostFileAccess {
+                path, dest_path, ..
+            } => (path.clone(), dest_path.clone()),
+            Tool::Install { program, .. } => (program.clone(), None),
+            Tool::Fetch { url, .. } => (url.clone(), None),
+            Tool::Search { .. } | Tool::Calculate { .. } => (String::new(), None),
+        };
+
+        AuditEntry {
+            timestamp: Utc::now().to_rfc3339(),
+            operation: tool.file_operation(),
+            source_path,
+            dest_path,
+            success: false,
+            error_msg: Some(reason),
+            user_approved: false,
+            tool: Some(tool.name().to_string()),
+            role: Some(self.role.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host_access(operation: FileOperation) -> Tool {
+        Tool::HostFileAccess {
+            operation,
+            path: "~/Documents/report.txt".to_string(),
+            dest_path: None,
+        }
+    }
+
+    #[test]
+    fn test_reader_can_search_and_read_only() {
+        let policy = PermissionPolicy::new(Role::Reader);
+
+        assert!(policy
+            .check(&Tool::Search {
+                query: "notes".to_string(),
+                top_k: None,
+            })
+            .is_ok());
+        assert!(policy
+            .check(&Tool::Read {
+                path: "/mnt/root/guide.txt".to_string(),
+                offset: None,
+                length: None,
+            })
+            .is_ok());
+        assert!(policy.check(&host_access(FileOperation::Read)).is_ok());
+
+        let install = Tool::Install {
+            program: "rust".to_string(),
+            method: crate::InstallMethod::Command {
+                cmd: "rustc".to_string(),
+                args: vec![],
+            },
+            dry_run: false,
+        };
+        assert!(matches!(
+            policy.check(&install),
+            Err(ToolError::PermissionDenied(_))
+        ));
+        assert!(matches!(
+            policy.check(&host_access(FileOperation::Write)),
+            Err(ToolError::PermissionDenied(_))
+        ));
+    }
+
+    #[test]
+    fn test_policy_from_config_applies_overrides() {
+        let mut security = SecurityConfig {
+            role: "Writer".to_string(),
+            ..Default::default()
+        };
+        security.role_permissions.insert(
+            "writer".to_string(),
+            RolePermissions {
+                tools: vec!["read".to_string(), "bogus".to_string()],
+                operations: vec!["read".to_string()],
+            },
+        );
+
+        let policy = PermissionPolicy::from_config(&security);
+        assert_eq!(policy.role(), Role::Writer);
+        assert_eq!(policy.grant(Role::Writer).tools, vec!["read"]);
+        assert!(policy
+            .check(&Tool::Delete {
+                path: "/mnt/root/a.txt".to_string(),
+            })
+            .is_err());
+    }
+
+    #[test]
+    fn test_unknown_role_falls_back_to_reader() {
+        let security = SecurityConfig {
+            role: "superuser".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            PermissionPolicy::from_config(&security).role(),
+            Role::Reader
+        );
+    }
+
+    #[test]
+    fn test_denials_are_written_to_the_audit_log() {
+        let temp = tempfile::tempdir().unwrap();
+        let log = crate::file_access::AuditLog::new(temp.path().join("audit.log"));
+        let policy = PermissionPolicy::new(Role::Reader);
+        let install = Tool::Install {
+            program: "rust".to_string(),
+            method: crate::InstallMethod::Command {
+                cmd: "rustc".to_string(),
+                args: vec![],
+            },
+            dry_run: false,
+        };
+
+        let reason = policy.check(&install).unwrap_err().to_string();
+        log.append(&policy.denial_entry(&install, reason)).unwrap();
+        log.append(&policy.denial_entry(&host_access(FileOperation::Delete), "no".into()))
+            .unwrap();
+
+        let entries = log.query(&Default::default()).unwrap().entries;
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].tool.as_deref(), Some("install"));
+        assert_eq!(entries[0].role.as_deref(), Some("reader"));
+        assert_eq!(entries[0].operation, None);
+        assert_eq!(entries[0].source_path, "rust");
+        assert!(!entries[0].success);
+        assert_eq!(entries[1].operation, Some(FileOperation::Delete));
+    }
+}
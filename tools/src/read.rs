@@ -1,29 +1,350 @@
 use crate::{Result, ToolResult};
 use lucastra_fs::FilesystemManager;
+use std::path::Path;
 use tracing::info;
 
-/// Read file tool implementation
+/// Bytes returned by one read unless configured otherwise.
+pub const DEFAULT_MAX_READ_BYTES: usize = 64 * 1024;
+
+/// Bytes of a binary file shown as a hex dump.
+pub const HEXDUMP_BYTES: usize = 256;
+
+/// Bytes checked for NULs when deciding whether content is binary.
+const SNIFF_BYTES: usize = 8 * 1024;
+
+/// Read file tool implementation.
+///
+/// Returns at most `max_bytes` of a file, optionally from `offset`, and
+/// says where to continue when more is left. Binary content is shown as a
+/// hex dump of its first bytes instead of mangled text.
 pub struct ReadTool<'a> {
     filesystem: &'a FilesystemManager,
+    max_bytes: usize,
 }
 
 impl<'a> ReadTool<'a> {
     pub fn new(filesystem: &'a FilesystemManager) -> Self {
-        Self { filesystem }
+        Self {
+            filesystem,
+            max_bytes: DEFAULT_MAX_READ_BYTES,
+        }
+    }
+
+    /// Cap on bytes returned per call, e.g. `tools.max_read_bytes`
+    pub fn with_max_bytes(mut self, max_bytes: usize) -> Self {
+        self.max_bytes = max_bytes.max(1);
+        self
     }
 
-    pub fn execute(&self, path: &str) -> Result<ToolResult> {
-        info!("Executing read tool: path='{}'", path);
+    /// Read `length` bytes of `path` from `offset`; by default from the
+    /// start as far as the cap allows.
+    pub fn execute(
+        &self,
+        path: &str,
+        offset: Option<u64>,
+        length: Option<usize>,
+    ) -> Result<ToolResult> {
+        info!(
+            "Executing read tool: path='{}', offset={:?}, length={:?}",
+            path, offset, length
+        );
 
-        match self.filesystem.read_file(path) {
-            Ok(bytes) => {
-                let content = String::from_utf8_lossy(&bytes).to_string();
-                Ok(ToolResult::success("read", content))
+        let read = self.filesystem.stat(path).and_then(|entry| {
+            if entry.is_dir {
+                return Ok(None);
+            }
+            let offset = offset.unwrap_or(0);
+            let length = length.unwrap_or(usize::MAX).min(self.max_bytes);
+            let bytes = self.filesystem.read_range(path, offset, length)?;
+            Ok(Some((entry.size, offset, bytes)))
+        });
+        let (size, offset, bytes) = match read {
+            Ok(Some(read)) => read,
+            Ok(None) => {
+                return Ok(ToolResult::failure(
+                    "read",
+                    format!("'{}' is a directory", path),
+                ))
             }
             Err(e) => {
                 let error = format!("Failed to read file '{}': {}", path, e);
-                Ok(ToolResult::failure("read", error))
+                return Ok(ToolResult::failure("read", error));
+            }
+        };
+
+        let mime = guess_mime(path);
+        if offset > 0 && offset >= size {
+            return Ok(ToolResult::success(
+                "read",
+                format!(
+                    "[offset {} is past the end of the {}-byte file ({})]",
+                    offset, size, mime
+                ),
+            ));
+        }
+
+        // Where the shown bytes end, so the next read picks up after them
+        let (mut output, end) = match text(&bytes, offset > 0) {
+            Some((skipped, text)) => (text.to_string(), offset + (skipped + text.len()) as u64),
+            None => {
+                let shown = &bytes[..bytes.len().min(HEXDUMP_BYTES)];
+                let dump = format!(
+                    "[binary file: {}, {} bytes; first {} bytes from offset {}]\n{}",
+                    mime,
+                    size,
+                    shown.len(),
+                    offset,
+                    hexdump(shown, offset)
+                );
+                (dump, offset + shown.len() as u64)
             }
+        };
+        if end < size {
+            output.push_str(&format!(
+                "\n[truncated: bytes {}-{} of {} ({}); continue with offset {}]",
+                offset, end, size, mime, end
+            ));
+        }
+        Ok(ToolResult::success("read", output))
+    }
+}
+
+/// Content type for a file name's extension; `application/octet-stream`
+/// when it isn't known.
+pub fn guess_mime(path: &str) -> &'static str {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(str::to_ascii_lowercase)
+        .unwrap_or_default();
+    match extension.as_str() {
+        "txt" | "log" | "text" => "text/plain",
+        "md" | "markdown" => "text/markdown",
+        "csv" => "text/csv",
+        "html" | "htm" => "text/html",
+        "css" => "text/css",
+        "xml" => "application/xml",
+        "json" | "jsonl" => "application/json",
+        "toml" => "application/toml",
+        "yaml" | "yml" => "application/yaml",
+        "js" | "mjs" => "text/javascript",
+        "rs" | "py" | "c" | "h" | "cpp" | "go" | "java" | "ts" | "sh" | "ps1" => "text/x-source",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "gz" | "tgz" => "application/gzip",
+        "tar" => "application/x-tar",
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "mp4" => "video/mp4",
+        "exe" | "dll" => "application/vnd.microsoft.portable-executable",
+        "wasm" => "application/wasm",
+        "sqlite" | "db" => "application/vnd.sqlite3",
+        _ => "application/octet-stream",
+    }
+}
+
+/// `bytes` as text after the number of bytes skipped to get to it, or
+/// `None` if they look binary. Characters cut in half at the ends of a
+/// range are dropped; `mid_file` allows a cut one at the start.
+fn text(bytes: &[u8], mid_file: bool) -> Option<(usize, &str)> {
+    if bytes[..bytes.len().min(SNIFF_BYTES)].contains(&0) {
+        return None;
+    }
+    let skip = if mid_file {
+        bytes
+            .iter()
+            .take(3)
+            .take_while(|b| (0x80..0xC0).contains(*b))
+            .count()
+    } else {
+        0
+    };
+    let bytes = &bytes[skip..];
+    let text = match std::str::from_utf8(bytes) {
+        Ok(text) => text,
+        // Only the last character is incomplete
+        Err(e) if e.error_len().is_none() => std::str::from_utf8(&bytes[..e.valid_up_to()]).ok()?,
+        Err(_) => return None,
+    };
+    Some((skip, text))
+}
+
+/// Hex dump with 16 bytes per line, addressed from `start`.
+fn hexdump(bytes: &[u8], start: u64) -> String {
+    bytes
+        .chunks(16)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let hex: Vec<String> = chunk.iter().map(|b| format!("{:02x}", b)).collect();
+            let ascii: String = chunk
+                .iter()
+                .map(|&b| {
+                    if b.is_ascii_graphic() || b == b' ' {
+                        b as char
+                    } else {
+                        '.'
+                    }
+                })
+                .collect();
+            format!(
+                "{:08x}  {:<47}  |{}|",
+                start + (i * 16) as u64,
+                hex.join(" "),
+                ascii
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_hal::filesystem::MockFileSystem;
+
+    const PNG: &[u8] = include_bytes!("../tests/fixtures/pixel.png");
+
+    fn filesystem(files: &[(&str, &[u8])]) -> FilesystemManager {
+        let mut fs = FilesystemManager::new();
+        fs.mount("/mnt/root", MockFileSystem::new()).unwrap();
+        for (path, content) in files {
+            fs.write_file(path, content).unwrap();
         }
+        fs
+    }
+
+    #[test]
+    fn test_whole_small_file_is_returned_as_is() {
+        let fs = filesystem(&[("/mnt/root/notes.txt", b"hello world")]);
+        let result = ReadTool::new(&fs)
+            .execute("/mnt/root/notes.txt", None, None)
+            .unwrap();
+        assert!(result.success);
+        assert_eq!(result.output, "hello world");
+    }
+
+    #[test]
+    fn test_range_reads_at_end_of_file() {
+        let fs = filesystem(&[("/mnt/root/digits.txt", b"0123456789")]);
+        let tool = ReadTool::new(&fs);
+        let read = |offset, length| {
+            tool.execute("/mnt/root/digits.txt", offset, length)
+                .unwrap()
+                .output
+        };
+
+        assert_eq!(
+            read(Some(2), Some(3)),
+            "234\n[truncated: bytes 2-5 of 10 (text/plain); continue with offset 5]"
+        );
+        assert_eq!(read(Some(7), Some(3)), "789");
+        assert_eq!(read(Some(7), Some(50)), "789");
+        assert_eq!(read(Some(7), None), "789");
+        assert_eq!(
+            read(Some(10), None),
+            "[offset 10 is past the end of the 10-byte file (text/plain)]"
+        );
+        assert!(read(Some(u64::MAX), Some(1)).contains("past the end"));
+    }
+
+    #[test]
+    fn test_reads_are_capped() {
+        let big = "x".repeat(1000);
+        let fs = filesystem(&[("/mnt/root/big.log", big.as_bytes())]);
+        let tool = ReadTool::new(&fs).with_max_bytes(100);
+
+        let output = tool
+            .execute("/mnt/root/big.log", None, None)
+            .unwrap()
+            .output;
+        assert!(output.starts_with(&"x".repeat(100)));
+        assert!(output.ends_with(
+            "\n[truncated: bytes 0-100 of 1000 (text/plain); continue with offset 100]"
+        ));
+
+        let output = tool
+            .execute("/mnt/root/big.log", Some(950), Some(500))
+            .unwrap()
+            .output;
+        assert_eq!(output, "x".repeat(50));
+    }
+
+    #[test]
+    fn test_split_characters_are_dropped() {
+        // "é" is two bytes; ranges can cut it at either end
+        let fs = filesystem(&[("/mnt/root/cafe.txt", "café au lait".as_bytes())]);
+        let tool = ReadTool::new(&fs);
+
+        let output = tool
+            .execute("/mnt/root/cafe.txt", Some(0), Some(4))
+            .unwrap()
+            .output;
+        assert_eq!(
+            output,
+            "caf\n[truncated: bytes 0-3 of 13 (text/plain); continue with offset 3]"
+        );
+        let output = tool
+            .execute("/mnt/root/cafe.txt", Some(4), None)
+            .unwrap()
+            .output;
+        assert_eq!(output, " au lait");
+    }
+
+    #[test]
+    fn test_binary_fixture_is_hex_dumped() {
+        let fs = filesystem(&[("/mnt/root/pixel.png", PNG)]);
+        let result = ReadTool::new(&fs)
+            .execute("/mnt/root/pixel.png", None, None)
+            .unwrap();
+        assert!(result.success);
+
+        let mut lines = result.output.lines();
+        assert_eq!(
+            lines.next().unwrap(),
+            format!(
+                "[binary file: image/png, {} bytes; first {} bytes from offset 0]",
+                PNG.len(),
+                PNG.len()
+            )
+        );
+        assert_eq!(
+            lines.next().unwrap(),
+            "00000000  89 50 4e 47 0d 0a 1a 0a 00 00 00 0d 49 48 44 52  |.PNG........IHDR|"
+        );
+        assert_eq!(lines.count(), (PNG.len() - 1) / 16);
+
+        let result = ReadTool::new(&fs)
+            .execute("/mnt/root/pixel.png", Some(16), Some(16))
+            .unwrap();
+        assert!(result.output.contains("\n00000010  "));
+        assert!(result.output.contains("continue with offset 32"));
+    }
+
+    #[test]
+    fn test_missing_file_and_directory_fail() {
+        let fs = filesystem(&[("/mnt/root/docs/a.txt", b"a")]);
+        let tool = ReadTool::new(&fs);
+
+        assert!(
+            !tool
+                .execute("/mnt/root/missing.txt", None, None)
+                .unwrap()
+                .success
+        );
+        let result = tool.execute("/mnt/root/docs", None, None).unwrap();
+        assert!(!result.success);
+        assert!(result.output.contains("is a directory"));
+    }
+
+    #[test]
+    fn test_guess_mime() {
+        assert_eq!(guess_mime("/mnt/root/README.MD"), "text/markdown");
+        assert_eq!(guess_mime("/a/b/archive.tar"), "application/x-tar");
+        assert_eq!(guess_mime("/a/b/noext"), "application/octet-stream");
     }
 }
@@ -0,0 +1,154 @@
+use crate::{Result, ToolResult};
+use lucastra_core::LuCastraError;
+use lucastra_fs::FilesystemManager;
+use std::path::{Component, Path};
+use tracing::info;
+
+/// Write file tool implementation.
+///
+/// With sandboxing on, writes stay inside the virtual filesystem: paths
+/// containing `..` and mounts backed by the host driver are refused.
+pub struct WriteTool<'a> {
+    filesystem: &'a mut FilesystemManager,
+    sandboxed: bool,
+}
+
+impl<'a> WriteTool<'a> {
+    pub fn new(filesystem: &'a mut FilesystemManager, sandboxed: bool) -> Self {
+        Self {
+            filesystem,
+            sandboxed,
+        }
+    }
+
+    pub fn execute(&mut self, path: &str, content: &str, append: bool) -> Result<ToolResult> {
+        info!("Executing write tool: path='{}', append={}", path, append);
+
+        if self.sandboxed {
+            if let Err(reason) = check_sandbox(self.filesystem, path) {
+                return Ok(ToolResult::failure("write", reason));
+            }
+        }
+
+        let mut data = Vec::new();
+        if append {
+            // A missing file is simply created; any other read error would
+            // lose its contents, so it fails the write
+            match self.filesystem.read_file(path) {
+                Ok(existing) => data = existing,
+                Err(LuCastraError::FileNotFound(_)) => {}
+                Err(e) => {
+                    let error = format!("Failed to read file '{}' to append: {}", path, e);
+                    return Ok(ToolResult::failure("write", error));
+                }
+            }
+        }
+        data.extend_from_slice(content.as_bytes());
+
+        match self.filesystem.write_file(path, &data) {
+            Ok(()) => Ok(ToolResult::success(
+                "write",
+                format!(
+                    "Wrote {} bytes to '{}' ({} bytes total)",
+                    content.len(),
+                    path,
+                    data.len()
+                ),
+            )),
+            Err(e) => {
+                let error = format!("Failed to write file '{}': {}", path, e);
+                Ok(ToolResult::failure("write", error))
+            }
+        }
+    }
+}
+
+/// Reject paths a sandboxed tool may not modify.
+pub(crate) fn check_sandbox(
+    filesystem: &FilesystemManager,
+    path: &str,
+) -> std::result::Result<(), String> {
+    if Path::new(path)
+        .components()
+        .any(|c| matches!(c, Component::ParentDir))
+    {
+        return Err(format!("Sandboxing forbids '..' in path '{}'", path));
+    }
+
+    match filesystem.is_host_backed(path) {
+        Ok(true) => Err(format!(
+            "Sandboxing forbids modifying host-backed path '{}'",
+            path
+        )),
+        Ok(false) => Ok(()),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use lucastra_hal::filesystem::{HostFileSystem, MockFileSystem};
+
+    fn virtual_fs() -> FilesystemManager {
+        let mut fs = FilesystemManager::new();
+        fs.mount("/mnt/root", MockFileSystem::new()).unwrap();
+        fs
+    }
+
+    #[test]
+    fn test_write_and_append_report_bytes() {
+        let mut fs = virtual_fs();
+        let mut tool = WriteTool::new(&mut fs, true);
+
+        let result = tool.execute("/mnt/root/notes.txt", "hello", false).unwrap();
+        assert!(result.success);
+        assert!(result.output.contains("Wrote 5 bytes"));
+
+        let result = tool.execute("/mnt/root/notes.txt", " world", true).unwrap();
+        assert!(result.output.contains("(11 bytes total)"));
+        assert_eq!(fs.read_file("/mnt/root/notes.txt").unwrap(), b"hello world");
+    }
+
+    #[test]
+    fn test_sandbox_blocks_host_mount() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let mut fs = virtual_fs();
+        fs.mount("/mnt/host", HostFileSystem::new(temp_dir.path()))
+            .unwrap();
+
+        let result = WriteTool::new(&mut fs, true)
+            .execute("/mnt/host/out.txt", "x", false)
+            .unwrap();
+        assert!(!result.success);
+        assert!(!temp_dir.path().join("out.txt").exists());
+
+        let result = WriteTool::new(&mut fs, true)
+            .execute("/mnt/root/../host/out.txt", "x", false)
+            .unwrap();
+        assert!(!result.success);
+
+        let result = WriteTool::new(&mut fs, false)
+            .execute("/mnt/host/out.txt", "x", false)
+            .unwrap();
+        assert!(result.success);
+        assert!(temp_dir.path().join("out.txt").exists());
+    }
+
+    #[test]
+    fn test_append_fails_when_the_file_cannot_be_read() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(temp_dir.path().join("dir")).unwrap();
+        let mut fs = virtual_fs();
+        fs.mount("/mnt/host", HostFileSystem::new(temp_dir.path()))
+            .unwrap();
+        let mut tool = WriteTool::new(&mut fs, false);
+
+        let result = tool.execute("/mnt/host/new.txt", "x", true).unwrap();
+        assert!(result.success);
+
+        let result = tool.execute("/mnt/host/dir", "x", true).unwrap();
+        assert!(!result.success);
+        assert!(temp_dir.path().join("dir").is_dir());
+    }
+}